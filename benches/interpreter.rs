@@ -0,0 +1,271 @@
+//! Benchmarks of the interpreter, on small programs that each stress a
+//! different part of it.
+//!
+//! Throughputs are given in wasm instructions, counted as written in the
+//! text format: `block`, `loop` and `if` count as one instruction each, while
+//! `end` and `else` are not counted. The count of each program is computed
+//! from its structure by the function next to it.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use exwasmable::{
+    interpreter::{Engine, Interpreter},
+    module::Module,
+    store::Store,
+};
+
+struct Program {
+    name: &'static str,
+    wasm: Vec<u8>,
+    arg: i32,
+    throughput: Throughput,
+}
+
+/// Recursive calls.
+fn fib() -> Program {
+    const N: i32 = 20;
+
+    let wat = r#"
+        (module
+            (func $fib (param $n i32) (result i32)
+                (if (result i32) (i32.lt_u (local.get $n) (i32.const 2))
+                    (then (local.get $n))
+                    (else
+                        (i32.add
+                            (call $fib (i32.sub (local.get $n) (i32.const 1)))
+                            (call $fib (i32.sub (local.get $n) (i32.const 2)))))))
+            (export "run" (func $fib)))
+    "#;
+
+    // Each call runs 5 instructions when `n < 2`, 13 otherwise. There are
+    // `fib(n + 1)` calls of the first kind, and one less of the second.
+    let (mut leaves, mut next) = (1_u64, 1);
+    for _ in 0..N {
+        (leaves, next) = (next, leaves + next);
+    }
+
+    Program {
+        name: "fib",
+        wasm: wat::parse_str(wat).unwrap(),
+        arg: N,
+        throughput: Throughput::Elements(5 * leaves + 13 * (leaves - 1)),
+    }
+}
+
+/// Byte loads and stores in a tight loop.
+fn sieve() -> Program {
+    const N: i32 = 100_000;
+
+    let wat = r#"
+        (module
+            (memory 2)
+            (func $clear (param $n i32)
+                (memory.fill (i32.const 0) (i32.const 0) (local.get $n)))
+            (func $sieve (param $n i32) (result i32) (local $i i32) (local $j i32) (local $count i32)
+                (call $clear (local.get $n))
+                (local.set $i (i32.const 2))
+                (block $done
+                    (loop $outer
+                        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                        (if (i32.eqz (i32.load8_u (local.get $i)))
+                            (then
+                                (local.set $count (i32.add (local.get $count) (i32.const 1)))
+                                (local.set $j (i32.add (local.get $i) (local.get $i)))
+                                (block $marked
+                                    (loop $mark
+                                        (br_if $marked (i32.ge_u (local.get $j) (local.get $n)))
+                                        (i32.store8 (local.get $j) (i32.const 1))
+                                        (local.set $j (i32.add (local.get $j) (local.get $i)))
+                                        (br $mark)))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $outer)))
+                (local.get $count))
+            (export "run" (func $sieve)))
+    "#;
+
+    // The number of iterations of the inner loop depends on the primes, so
+    // the sieve is run natively to count them.
+    let n = N as usize;
+    let mut composite = vec![false; n];
+    let mut count = 2 + 4 + 2 + 2 + 4 + 1;
+
+    for i in 2..n {
+        count += 4 + 4 + 4 + 1;
+
+        if !composite[i] {
+            count += 4 + 4 + 2 + 4;
+
+            for j in (2 * i..n).step_by(i) {
+                composite[j] = true;
+                count += 4 + 3 + 4 + 1;
+            }
+        }
+    }
+
+    Program {
+        name: "sieve",
+        wasm: wat::parse_str(wat).unwrap(),
+        arg: N,
+        throughput: Throughput::Elements(count),
+    }
+}
+
+/// Floating point arithmetic and 8-byte loads.
+fn matmul() -> Program {
+    const N: u64 = 32;
+
+    let wat = format!(
+        r#"
+        (module
+            (memory 1)
+            (func $matmul (param i32) (result i32) (local $i i32) (local $j i32) (local $k i32) (local $sum f64)
+                (local.set $i (i32.const 0))
+                (loop $rows
+                    (local.set $j (i32.const 0))
+                    (loop $cols
+                        (local.set $sum (f64.const 0))
+                        (local.set $k (i32.const 0))
+                        (loop $dot
+                            (local.set $sum
+                                (f64.add
+                                    (local.get $sum)
+                                    (f64.mul
+                                        (f64.load (i32.shl (i32.add (i32.mul (local.get $i) (i32.const {N})) (local.get $k)) (i32.const 3)))
+                                        (f64.load offset={B} (i32.shl (i32.add (i32.mul (local.get $k) (i32.const {N})) (local.get $j)) (i32.const 3))))))
+                            (local.set $k (i32.add (local.get $k) (i32.const 1)))
+                            (br_if $dot (i32.lt_u (local.get $k) (i32.const {N}))))
+                        (f64.store offset={C}
+                            (i32.shl (i32.add (i32.mul (local.get $i) (i32.const {N})) (local.get $j)) (i32.const 3))
+                            (local.get $sum))
+                        (local.set $j (i32.add (local.get $j) (i32.const 1)))
+                        (br_if $cols (i32.lt_u (local.get $j) (i32.const {N}))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $rows (i32.lt_u (local.get $i) (i32.const {N}))))
+                (i32.const 0))
+            (export "run" (func $matmul)))
+        "#,
+        B = N * N * 8,
+        C = 2 * N * N * 8,
+    );
+
+    let dot = 28;
+    let cols = 22 + N * dot;
+    let rows = 11 + N * cols;
+
+    Program {
+        name: "matmul",
+        wasm: wat::parse_str(wat).unwrap(),
+        arg: 0,
+        throughput: Throughput::Elements(3 + N * rows + 1),
+    }
+}
+
+/// Bulk memory copies.
+fn memcpy() -> Program {
+    const N: i32 = 16;
+
+    let wat = r#"
+        (module
+            (memory 2)
+            (func $memcpy (param $n i32) (result i32)
+                ;; Pages are only allocated once written.
+                (memory.fill (i32.const 0) (i32.const 0xaa) (i32.const 0x10000))
+                (loop $again
+                    (memory.copy (i32.const 0x10000) (i32.const 0) (i32.const 0x10000))
+                    (br_if $again (local.tee $n (i32.sub (local.get $n) (i32.const 1)))))
+                (local.get $n))
+            (export "run" (func $memcpy)))
+    "#;
+
+    Program {
+        name: "memcpy",
+        wasm: wat::parse_str(wat).unwrap(),
+        arg: N,
+        throughput: Throughput::Bytes(N as u64 * 0x10000),
+    }
+}
+
+/// Indirect calls to small functions.
+fn call_indirect() -> Program {
+    const N: i32 = 100_000;
+
+    let wat = r#"
+        (module
+            (type $unop (func (param i32) (result i32)))
+            (table funcref (elem $inc $dec $double $half))
+            (func $inc (type $unop) (i32.add (local.get 0) (i32.const 1)))
+            (func $dec (type $unop) (i32.sub (local.get 0) (i32.const 1)))
+            (func $double (type $unop) (i32.shl (local.get 0) (i32.const 1)))
+            (func $half (type $unop) (i32.shr_u (local.get 0) (i32.const 1)))
+            (func $dispatch (param $n i32) (result i32) (local $acc i32)
+                (block $done
+                    (loop $again
+                        (br_if $done (i32.eqz (local.get $n)))
+                        (local.set $acc
+                            (call_indirect (type $unop)
+                                (local.get $acc)
+                                (i32.and (local.get $n) (i32.const 3))))
+                        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                        (br $again)))
+                (local.get $acc))
+            (export "run" (func $dispatch)))
+    "#;
+
+    Program {
+        name: "call_indirect",
+        wasm: wat::parse_str(wat).unwrap(),
+        arg: N,
+        throughput: Throughput::Elements(17 * N as u64 + 6),
+    }
+}
+
+fn programs() -> Vec<Program> {
+    vec![fib(), sieve(), matmul(), memcpy(), call_indirect()]
+}
+
+fn run(c: &mut Criterion) {
+    for program in programs() {
+        let mut group = c.benchmark_group(program.name);
+        group.throughput(program.throughput.clone());
+
+        let module = Module::from_bytes(&program.wasm).unwrap();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let id = BenchmarkId::from_parameter(format!("{engine:?}").to_lowercase());
+            group.bench_function(id, |b| {
+                b.iter(|| interpreter.run("run", [program.arg.into()]).unwrap())
+            });
+        }
+
+        group.finish();
+    }
+}
+
+fn instantiate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instantiate");
+
+    for program in programs() {
+        let module = Module::from_bytes(&program.wasm).unwrap();
+
+        group.bench_function(program.name, |b| b.iter(|| Store::new(&module).unwrap()));
+    }
+
+    group.finish();
+}
+
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+
+    for program in programs() {
+        group.bench_function(program.name, |b| {
+            b.iter(|| Module::from_bytes(&program.wasm).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, run, instantiate, compile);
+criterion_main!(benches);
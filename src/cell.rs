@@ -0,0 +1,195 @@
+//! Untyped operand stack slots.
+//!
+//! Validation guarantees that the type of every operand is statically known,
+//! so the interpreter doesn't need to store the type of the values it
+//! manipulates. Values are stored as raw 128-bit [`Cell`]s, which is the size
+//! of the largest type, `v128`, and are converted from and to [`Val`]s only
+//! when they cross the host boundary.
+
+use crate::{
+    types::{HeapType, NumType, RefType, Types, ValType, VecType},
+    values::{ArrayAddr, ExnAddr, ExternAddr, FuncAddr, Num, Ref, StructAddr, Val},
+};
+
+/// A value whose type is known from the context.
+///
+/// Integers are stored as their two's complement representation, floats as
+/// their bits, vectors as their lanes in little-endian order and non-null
+/// references as their address. The unused high bits of a cell are zero,
+/// except for the references of the `any` hierarchy, which are tagged with
+/// their kind (see [`AnyRef`]).
+// It is `pub` only so that it can appear in the sealed traits of `func`: it
+// is not reachable from outside of the crate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(transparent)]
+pub struct Cell(u128);
+
+impl Cell {
+    /// The representation of null references.
+    ///
+    /// Addresses are 32 bits, so they never collide with it.
+    pub(crate) const NULL: Cell = Cell(u128::MAX);
+
+    pub(crate) fn from_val(val: Val) -> Cell {
+        match val {
+            Val::Num(Num::I32(val)) => Cell::from(val),
+            Val::Num(Num::I64(val)) => Cell::from(val),
+            Val::Num(Num::F32(val)) => Cell::from(val),
+            Val::Num(Num::F64(val)) => Cell::from(val),
+            Val::V128(val) => Cell::from(val),
+            Val::Ref(Ref::Null(_)) => Cell::NULL,
+            Val::Ref(Ref::Func(FuncAddr(addr))) => Cell::from(addr),
+            Val::Ref(Ref::Extern(ExternAddr(addr))) => Cell::from(addr),
+            Val::Ref(Ref::Exn(ExnAddr(addr))) => Cell::from(addr),
+            Val::Ref(Ref::I31(val)) => Cell::from_any(AnyRef::I31(val)),
+            Val::Ref(Ref::Struct(StructAddr(addr))) => Cell::from_any(AnyRef::Struct(addr)),
+            Val::Ref(Ref::Array(ArrayAddr(addr))) => Cell::from_any(AnyRef::Array(addr)),
+        }
+    }
+
+    /// Converts the cell to a value of type `type_`, whose concrete types
+    /// are defined by `types`.
+    pub(crate) fn to_val(self, type_: ValType, types: &impl Types) -> Val {
+        match type_ {
+            ValType::NumType(NumType::I32) => Val::from(i32::from_cell(self)),
+            ValType::NumType(NumType::I64) => Val::from(i64::from_cell(self)),
+            ValType::NumType(NumType::F32) => Val::from(f32::from_cell(self)),
+            ValType::NumType(NumType::F64) => Val::from(f64::from_cell(self)),
+            ValType::VecType(VecType::V128) => Val::from(u128::from_cell(self)),
+
+            ValType::RefType(type_) if self == Cell::NULL => {
+                Val::Ref(Ref::Null(RefType::nullable(type_.heap_type.top(types))))
+            }
+            ValType::RefType(type_) => match type_.heap_type.top(types) {
+                HeapType::Func => Val::Ref(Ref::Func(FuncAddr(u32::from_cell(self)))),
+                HeapType::Exn => Val::Ref(Ref::Exn(ExnAddr(u32::from_cell(self)))),
+                // External references can be converted to internal ones, and
+                // back.
+                _ => match self.to_any() {
+                    AnyRef::Host(addr) => Val::Ref(Ref::Extern(ExternAddr(addr))),
+                    AnyRef::I31(val) => Val::Ref(Ref::I31(val)),
+                    AnyRef::Struct(addr) => Val::Ref(Ref::Struct(StructAddr(addr))),
+                    AnyRef::Array(addr) => Val::Ref(Ref::Array(ArrayAddr(addr))),
+                },
+            },
+        }
+    }
+
+    /// Returns the cell holding the non-null reference `any`.
+    pub(crate) fn from_any(any: AnyRef) -> Cell {
+        let (tag, val) = match any {
+            AnyRef::Host(addr) => (0, addr.into()),
+            AnyRef::I31(val) => (1, (val & 0x7fff_ffff).into()),
+            AnyRef::Struct(ref_) => (2, ref_),
+            AnyRef::Array(ref_) => (3, ref_),
+        };
+
+        Cell(tag << 64 | u128::from(val))
+    }
+
+    /// Returns the non-null reference of the `any` or `extern` hierarchy
+    /// that the cell holds.
+    pub(crate) fn to_any(self) -> AnyRef {
+        let val = self.0 as u32;
+
+        match self.0 >> 64 {
+            0 => AnyRef::Host(val),
+            1 => AnyRef::I31(val),
+            2 => AnyRef::Struct(self.0 as u64),
+            _ => AnyRef::Array(self.0 as u64),
+        }
+    }
+
+    /// Returns the address of the structure or array the cell refers to, if
+    /// it may hold such a reference.
+    ///
+    /// Cells don't carry their type, so a number may be mistaken for a
+    /// reference.
+    pub(crate) fn object(self) -> Option<u32> {
+        matches!(self.0 >> 64, 2 | 3).then_some(self.0 as u32)
+    }
+}
+
+/// A non-null reference of the `any` hierarchy, whose kind is stored in the
+/// bits 64 and 65 of its cell.
+///
+/// Host references are not tagged, so that converting an external reference
+/// to an internal one, or back, leaves its cell as it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum AnyRef {
+    Host(u32),
+    /// An unboxed 31-bit integer, whose high bit is zero.
+    I31(u32),
+    /// A reference to a structure, whose low 32 bits are its address in the
+    /// heap, and whose high bits are the generation of the address.
+    Struct(u64),
+    /// Like [`AnyRef::Struct`], for an array.
+    Array(u64),
+}
+
+/// Reads a value of a statically known type from a [`Cell`].
+pub(crate) trait FromCell {
+    fn from_cell(cell: Cell) -> Self;
+}
+
+macro_rules! impl_cell_conversions {
+    ($ty:ty, |$val:ident| $into:expr, |$bits:ident| $from:expr) => {
+        impl From<$ty> for Cell {
+            #[inline(always)]
+            fn from($val: $ty) -> Cell {
+                Cell($into)
+            }
+        }
+
+        impl FromCell for $ty {
+            #[inline(always)]
+            fn from_cell(Cell($bits): Cell) -> $ty {
+                $from
+            }
+        }
+    };
+}
+
+impl_cell_conversions!(i32, |val| val as u32 as u128, |bits| bits as i32);
+impl_cell_conversions!(u32, |val| val as u128, |bits| bits as u32);
+impl_cell_conversions!(i64, |val| val as u64 as u128, |bits| bits as i64);
+impl_cell_conversions!(u64, |val| val as u128, |bits| bits as u64);
+impl_cell_conversions!(f32, |val| val.to_bits().into(), |bits| {
+    f32::from_bits(bits as u32)
+});
+impl_cell_conversions!(f64, |val| val.to_bits().into(), |bits| {
+    f64::from_bits(bits as u64)
+});
+impl_cell_conversions!(u128, |val| val, |bits| bits);
+
+// Vectors are also read and written as arrays of lanes.
+macro_rules! impl_lanes_conversions {
+    ($( $ty:ty ),*) => {
+        $(
+            impl From<[$ty; 16 / size_of::<$ty>()]> for Cell {
+                #[inline(always)]
+                fn from(lanes: [$ty; 16 / size_of::<$ty>()]) -> Cell {
+                    let mut bytes = [0; 16];
+                    for (bytes, lane) in bytes.chunks_exact_mut(size_of::<$ty>()).zip(lanes) {
+                        bytes.copy_from_slice(&lane.to_le_bytes());
+                    }
+
+                    Cell(u128::from_le_bytes(bytes))
+                }
+            }
+
+            impl FromCell for [$ty; 16 / size_of::<$ty>()] {
+                #[inline(always)]
+                fn from_cell(Cell(bits): Cell) -> Self {
+                    let bytes = bits.to_le_bytes();
+                    std::array::from_fn(|lane| {
+                        let start = lane * size_of::<$ty>();
+                        <$ty>::from_le_bytes(bytes[start..start + size_of::<$ty>()].try_into().unwrap())
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_lanes_conversions!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
@@ -0,0 +1,1285 @@
+//! Components, whose functions take and return the values described by WIT.
+//!
+//! A [`Component`] embeds core modules, which are instantiated in the store
+//! along with it. Its exports are core functions lifted with the canonical
+//! ABI, which passes strings, lists and the other compound values through
+//! the memory of the component, and its imports are lowered into core
+//! functions the same way. The host defines the functions and resource
+//! types that components import with [`Store::define_component_func`] and
+//! [`Store::define_resource`].
+//!
+//! Components can't nest other components, strings are encoded in UTF-8,
+//! and functions are synchronous.
+//!
+//! ```
+//! # use exwasmable::{
+//! #     component::{Component, FuncType, Type, Value},
+//! #     interpreter::Interpreter,
+//! #     store::Store,
+//! # };
+//! let wasm = wat::parse_str(
+//!     r#"(component
+//!         (core module $m
+//!             (func (export "add") (param i32 i32) (result i32)
+//!                 (i32.add (local.get 0) (local.get 1))))
+//!         (core instance $i (instantiate $m))
+//!         (func (export "add") (param "a" u32) (param "b" u32) (result u32)
+//!             (canon lift (core func $i "add"))))"#,
+//! )
+//! .unwrap();
+//!
+//! let component = Component::from_bytes(&wasm).unwrap();
+//! let mut store = Store::default();
+//! let instance = store.instantiate_component(&component).unwrap();
+//!
+//! let mut interpreter = Interpreter::new(&mut store);
+//! let sum = interpreter.run_component(instance, "add", &[Value::U32(41), Value::U32(1)]);
+//! assert_eq!(sum.unwrap(), Some(Value::U32(42)));
+//! ```
+//!
+//! [`Store::define_component_func`]: crate::store::Store::define_component_func
+//! [`Store::define_resource`]: crate::store::Store::define_resource
+
+use std::{
+    collections::{HashMap, HashSet},
+    iter::zip,
+    sync::Arc,
+};
+
+use wasmbin::{
+    component::{
+        self as binary, AliasTarget, Canon, CanonOpt, CoreInstance, CoreSort, DefinedType,
+        ExternDesc, InstanceDecl, PrimitiveValType, ResultList, Section, Sort, TypeBound,
+    },
+    types::ValueType,
+    visit::Visit,
+};
+
+use crate::{
+    lower::{Result, invalid},
+    module::{self, Module, ModuleError},
+    types::{self, AddrType, ExternType, NumType::I32, ValType},
+};
+
+pub(crate) use canonical::HandleTable;
+pub(crate) use instance::{ComponentInstance, HostItem, ResourceTypeDef};
+
+mod canonical;
+mod instance;
+
+/// A validated component, whose core modules are lowered.
+///
+/// Like modules, components are validated once and can then be instantiated
+/// any number of times, in any number of stores.
+#[derive(Clone, Debug)]
+pub struct Component {
+    pub(crate) data: Arc<ComponentData>,
+}
+
+/// The contents of a [`Component`].
+#[derive(Debug)]
+pub(crate) struct ComponentData {
+    pub(crate) modules: Vec<Module>,
+    /// Where each resource type of the component comes from, by the index
+    /// the types of the component refer to it with.
+    pub(crate) resources: Vec<ResourceDecl>,
+    pub(crate) defs: Vec<Def>,
+}
+
+/// A component instance in a [`Store`](crate::store::Store).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComponentInstanceAddr(pub(crate) u32);
+
+/// The type of the values that components take and return, as described by
+/// WIT.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Type {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    List(Box<Type>),
+    /// A record, whose fields are named.
+    Record(Vec<(String, Type)>),
+    Tuple(Vec<Type>),
+    /// A variant, whose cases are named and may have a payload.
+    Variant(Vec<(String, Option<Type>)>),
+    Enum(Vec<String>),
+    Option(Box<Type>),
+    Result {
+        ok: Option<Box<Type>>,
+        err: Option<Box<Type>>,
+    },
+    /// A set of at most 32 named flags.
+    Flags(Vec<String>),
+    /// A handle that owns a resource.
+    Own(ResourceType),
+    /// A handle that borrows a resource for the duration of a call.
+    Borrow(ResourceType),
+}
+
+/// A value that components take or return.
+///
+/// Records, variants, enums and flags are given by the names of their
+/// fields, cases and flags.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    S8(i8),
+    U8(u8),
+    S16(i16),
+    U16(u16),
+    S32(i32),
+    U32(u32),
+    S64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+    List(Vec<Value>),
+    /// The fields of a record, in the order of its type.
+    Record(Vec<(String, Value)>),
+    Tuple(Vec<Value>),
+    Variant(String, Option<Box<Value>>),
+    Enum(String),
+    Option(Option<Box<Value>>),
+    Result(std::result::Result<Option<Box<Value>>, Option<Box<Value>>>),
+    /// The flags that are set.
+    Flags(Vec<String>),
+    Own(Resource),
+    Borrow(Resource),
+}
+
+/// The type of a function of a component, whose parameters are named.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FuncType {
+    params: Vec<(String, Type)>,
+    result: Option<Type>,
+}
+
+/// A resource type of a store, defined by the host or by a component
+/// instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceType(pub(crate) u32);
+
+/// A resource, given by its type and by its representation, which the
+/// definer of its type chooses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Resource {
+    pub type_: ResourceType,
+    pub rep: u32,
+}
+
+/// Where a resource type of a component comes from.
+#[derive(Debug)]
+pub(crate) enum ResourceDecl {
+    /// The resource type is imported as `name`, or as the export `name` of
+    /// the instance imported as `instance`.
+    Import {
+        instance: Option<String>,
+        name: String,
+    },
+    /// The resource type is defined by the component.
+    Defined,
+    /// The resource type is declared by an instance type that is not
+    /// imported.
+    Abstract,
+}
+
+/// A definition of a component, which adds an item to the index space of its
+/// sort when the component is instantiated.
+///
+/// The definitions are validated, and replayed in order by each
+/// instantiation.
+#[derive(Debug)]
+pub(crate) enum Def {
+    /// Instantiates the module at `module`, with the core instances at the
+    /// indices of `args` by module name.
+    CoreInstantiate {
+        module: u32,
+        args: Vec<(String, u32)>,
+    },
+    /// Makes a core instance that exports core items, given by name, sort
+    /// and index.
+    CoreInstance(Vec<(String, CoreSort, u32)>),
+    /// Aliases the core item exported by the core instance at `instance` as
+    /// `name`.
+    CoreAlias {
+        instance: u32,
+        name: String,
+        sort: CoreSort,
+    },
+    ImportFunc {
+        name: String,
+        type_: FuncType,
+    },
+    /// Imports an instance, of which only functions are used at run time.
+    ImportInstance {
+        name: String,
+        funcs: Vec<(String, FuncType)>,
+    },
+    /// Aliases the function or instance exported by the instance at
+    /// `instance` as `name`.
+    Alias {
+        instance: u32,
+        name: String,
+    },
+    /// Makes an instance that exports items, given by name.
+    Instance(Vec<(String, Item)>),
+    /// Exports an item, which is also added to the index space of its sort.
+    Export {
+        name: String,
+        item: Item,
+    },
+    /// Defines the resource type at `resource`, whose destructor is the core
+    /// function at `dtor`.
+    Resource {
+        resource: u32,
+        dtor: Option<u32>,
+    },
+    Lift {
+        core_func: u32,
+        type_: FuncType,
+        options: Options,
+    },
+    Lower {
+        func: u32,
+        options: Options,
+    },
+    /// Makes the `resource.new` core function of the resource type at the
+    /// index.
+    ResourceNew(u32),
+    ResourceRep(u32),
+    ResourceDrop(u32),
+}
+
+/// An item that exists at run time, by its index in the index space of its
+/// sort.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Item {
+    Func(u32),
+    Instance(u32),
+    Resource(u32),
+}
+
+/// The canonical options of a lifted or lowered function, which are the
+/// indices of core items in a component, and their addresses once it is
+/// instantiated.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Options {
+    pub(crate) memory: Option<u32>,
+    pub(crate) realloc: Option<u32>,
+    pub(crate) post_return: Option<u32>,
+}
+
+/// The type of an item imported or exported by a component or an instance.
+#[derive(Clone, Debug)]
+enum Extern {
+    Func(FuncType),
+    Type(TypeDef),
+    Instance(InstanceType),
+}
+
+/// The exports of an instance, by name.
+type InstanceType = HashMap<String, Extern>;
+
+/// A type of the type index space.
+#[derive(Clone, Debug)]
+enum TypeDef {
+    Value(Type),
+    Func(FuncType),
+    /// A resource type, by its index in the resource types of the
+    /// component.
+    Resource(u32),
+    Instance(InstanceType),
+}
+
+/// The type of everything a component defines or imports, in the index
+/// spaces of the component.
+#[derive(Debug, Default)]
+struct Context {
+    modules: Vec<Module>,
+    /// The exports of each core instance.
+    core_instances: Vec<HashMap<String, ExternType>>,
+    /// The types of the core functions, tables, memories, globals and tags.
+    core_items: HashMap<CoreSort, Vec<ExternType>>,
+    types: Vec<TypeDef>,
+    funcs: Vec<FuncType>,
+    instances: Vec<InstanceType>,
+    resources: Vec<ResourceDecl>,
+    imports: HashSet<String>,
+    exports: HashSet<String>,
+    defs: Vec<Def>,
+}
+
+impl Type {
+    /// Whether `self` or one of the types it is made of satisfies
+    /// `predicate`.
+    pub(crate) fn contains(&self, predicate: &impl Fn(&Type) -> bool) -> bool {
+        predicate(self)
+            || match self {
+                Type::List(type_) | Type::Option(type_) => type_.contains(predicate),
+                Type::Record(fields) => fields.iter().any(|(_, type_)| type_.contains(predicate)),
+                Type::Tuple(types) => types.iter().any(|type_| type_.contains(predicate)),
+                Type::Variant(cases) => cases
+                    .iter()
+                    .filter_map(|(_, type_)| type_.as_ref())
+                    .any(|type_| type_.contains(predicate)),
+                Type::Result { ok, err } => {
+                    ok.iter().chain(err).any(|type_| type_.contains(predicate))
+                }
+                _ => false,
+            }
+    }
+
+    /// Replaces the resource types, which are indices in the resource types
+    /// of a component, by the element of `resources` at that index.
+    pub(crate) fn resolve(&self, resources: &[u32]) -> Type {
+        let resolve = |type_: &Type| type_.resolve(resources);
+        let resolve_box = |type_: &Type| Box::new(type_.resolve(resources));
+
+        match self {
+            Type::List(type_) => Type::List(resolve_box(type_)),
+            Type::Record(fields) => Type::Record(
+                fields
+                    .iter()
+                    .map(|(name, type_)| (name.clone(), resolve(type_)))
+                    .collect(),
+            ),
+            Type::Tuple(types) => Type::Tuple(types.iter().map(resolve).collect()),
+            Type::Variant(cases) => Type::Variant(
+                cases
+                    .iter()
+                    .map(|(name, type_)| (name.clone(), type_.as_ref().map(resolve)))
+                    .collect(),
+            ),
+            Type::Option(type_) => Type::Option(resolve_box(type_)),
+            Type::Result { ok, err } => Type::Result {
+                ok: ok.as_deref().map(resolve_box),
+                err: err.as_deref().map(resolve_box),
+            },
+            Type::Own(ResourceType(index)) => Type::Own(ResourceType(resources[*index as usize])),
+            Type::Borrow(ResourceType(index)) => {
+                Type::Borrow(ResourceType(resources[*index as usize]))
+            }
+            type_ => type_.clone(),
+        }
+    }
+}
+
+impl From<PrimitiveValType> for Type {
+    fn from(type_: PrimitiveValType) -> Type {
+        match type_ {
+            PrimitiveValType::Bool => Type::Bool,
+            PrimitiveValType::S8 => Type::S8,
+            PrimitiveValType::U8 => Type::U8,
+            PrimitiveValType::S16 => Type::S16,
+            PrimitiveValType::U16 => Type::U16,
+            PrimitiveValType::S32 => Type::S32,
+            PrimitiveValType::U32 => Type::U32,
+            PrimitiveValType::S64 => Type::S64,
+            PrimitiveValType::U64 => Type::U64,
+            PrimitiveValType::F32 => Type::F32,
+            PrimitiveValType::F64 => Type::F64,
+            PrimitiveValType::Char => Type::Char,
+            PrimitiveValType::String => Type::String,
+        }
+    }
+}
+
+impl Value {
+    /// Whether `self` is a value of type `type_`.
+    pub(crate) fn has_type(&self, type_: &Type) -> bool {
+        // A payload is there if and only if its case has a type.
+        let payload_has_type =
+            |value: &Option<Box<Value>>, type_: Option<&Type>| match (value, type_) {
+                (None, None) => true,
+                (Some(value), Some(type_)) => value.has_type(type_),
+                _ => false,
+            };
+
+        match (self, type_) {
+            (Value::Bool(_), Type::Bool)
+            | (Value::S8(_), Type::S8)
+            | (Value::U8(_), Type::U8)
+            | (Value::S16(_), Type::S16)
+            | (Value::U16(_), Type::U16)
+            | (Value::S32(_), Type::S32)
+            | (Value::U32(_), Type::U32)
+            | (Value::S64(_), Type::S64)
+            | (Value::U64(_), Type::U64)
+            | (Value::F32(_), Type::F32)
+            | (Value::F64(_), Type::F64)
+            | (Value::Char(_), Type::Char)
+            | (Value::String(_), Type::String) => true,
+            (Value::List(values), Type::List(type_)) => {
+                values.iter().all(|value| value.has_type(type_))
+            }
+            (Value::Record(fields), Type::Record(types)) => {
+                fields.len() == types.len()
+                    && zip(fields, types).all(|((name, value), (expected, type_))| {
+                        name == expected && value.has_type(type_)
+                    })
+            }
+            (Value::Tuple(values), Type::Tuple(types)) => {
+                values.len() == types.len()
+                    && zip(values, types).all(|(value, type_)| value.has_type(type_))
+            }
+            (Value::Variant(name, value), Type::Variant(cases)) => cases
+                .iter()
+                .any(|(case, type_)| case == name && payload_has_type(value, type_.as_ref())),
+            (Value::Enum(name), Type::Enum(names)) => names.contains(name),
+            (Value::Option(value), Type::Option(type_)) => {
+                value.as_ref().is_none_or(|value| value.has_type(type_))
+            }
+            (Value::Result(Ok(value)), Type::Result { ok, .. }) => {
+                payload_has_type(value, ok.as_deref())
+            }
+            (Value::Result(Err(value)), Type::Result { err, .. }) => {
+                payload_has_type(value, err.as_deref())
+            }
+            (Value::Flags(flags), Type::Flags(names)) => {
+                flags.iter().all(|flag| names.contains(flag))
+            }
+            (Value::Own(resource), Type::Own(type_))
+            | (Value::Borrow(resource), Type::Borrow(type_)) => resource.type_ == *type_,
+            _ => false,
+        }
+    }
+}
+
+impl FuncType {
+    pub fn new<S: Into<String>>(
+        params: impl IntoIterator<Item = (S, Type)>,
+        result: Option<Type>,
+    ) -> FuncType {
+        FuncType {
+            params: params
+                .into_iter()
+                .map(|(name, type_)| (name.into(), type_))
+                .collect(),
+            result,
+        }
+    }
+
+    pub fn params(&self) -> &[(String, Type)] {
+        &self.params
+    }
+
+    pub fn result(&self) -> Option<&Type> {
+        self.result.as_ref()
+    }
+
+    /// Returns the types of the parameters and of the result.
+    pub(crate) fn types(&self) -> impl Iterator<Item = &Type> {
+        self.params
+            .iter()
+            .map(|(_, type_)| type_)
+            .chain(&self.result)
+    }
+
+    /// Replaces the resource types, like [`Type::resolve`].
+    pub(crate) fn resolve(&self, resources: &[u32]) -> FuncType {
+        FuncType {
+            params: self
+                .params
+                .iter()
+                .map(|(name, type_)| (name.clone(), type_.resolve(resources)))
+                .collect(),
+            result: self.result.as_ref().map(|type_| type_.resolve(resources)),
+        }
+    }
+}
+
+impl Options {
+    /// Replaces the indices of the core items by their addresses, given by
+    /// sort in `core`.
+    pub(crate) fn resolve(&self, core: &HashMap<CoreSort, Vec<u32>>) -> Options {
+        let addr = |sort, index: Option<u32>| index.map(|index| core[&sort][index as usize]);
+
+        Options {
+            memory: addr(CoreSort::Memory, self.memory),
+            realloc: addr(CoreSort::Func, self.realloc),
+            post_return: addr(CoreSort::Func, self.post_return),
+        }
+    }
+}
+
+impl Component {
+    /// Decodes the binary component `bytes`, validates it and the core
+    /// modules it embeds.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Component, ModuleError> {
+        let component = binary::Component::decode_from(bytes).map_err(module::binary_error)?;
+
+        // Like the sections of modules, the ones of components are only
+        // decoded when they are first accessed.
+        component.visit(|_: &()| {}).map_err(module::decode_error)?;
+
+        Ok(Component::new(component)?)
+    }
+
+    /// Parses the text component `wat` and validates it.
+    #[cfg(feature = "wat")]
+    pub fn from_wat(wat: &str) -> Result<Component, ModuleError> {
+        let bytes = wat::parse_str(wat).map_err(ModuleError::Text)?;
+
+        Component::from_bytes(&bytes)
+    }
+
+    /// Validates `component` and the core modules it embeds.
+    pub(crate) fn new(component: binary::Component) -> Result<Component> {
+        let mut context = Context::default();
+
+        for section in component.sections {
+            match section {
+                Section::Custom(_) | Section::CoreType(_) => {}
+                Section::CoreModule(module) => {
+                    let module = contents(module)?;
+                    context.modules.push(Module::new(module)?);
+                }
+                Section::CoreInstance(instances) => {
+                    for instance in contents(instances)? {
+                        context.core_instance(instance)?;
+                    }
+                }
+                Section::Component(_) => invalid!("nested components are not supported"),
+                Section::Instance(instances) => {
+                    for instance in contents(instances)? {
+                        context.instance(instance)?;
+                    }
+                }
+                Section::Alias(aliases) => {
+                    for alias in contents(aliases)? {
+                        context.alias(alias)?;
+                    }
+                }
+                Section::Type(types) => {
+                    for type_ in contents(types)? {
+                        context.type_(&type_)?;
+                    }
+                }
+                Section::Canon(canons) => {
+                    for canon in contents(canons)? {
+                        context.canon(canon)?;
+                    }
+                }
+                Section::Start(_) => invalid!("start functions of components are not supported"),
+                Section::Import(imports) => {
+                    for import in contents(imports)? {
+                        context.import(import)?;
+                    }
+                }
+                Section::Export(exports) => {
+                    for export in contents(exports)? {
+                        context.export(export)?;
+                    }
+                }
+                Section::Value(_) => invalid!("values are not supported"),
+            }
+        }
+
+        Ok(Component {
+            data: Arc::new(ComponentData {
+                modules: context.modules,
+                resources: context.resources,
+                defs: context.defs,
+            }),
+        })
+    }
+}
+
+impl Context {
+    fn core_instance(&mut self, instance: CoreInstance) -> Result<()> {
+        let exports = match instance {
+            CoreInstance::Instantiate { module, args } => {
+                let Some(module_) = self.modules.get(module as usize) else {
+                    invalid!("unknown module {module}");
+                };
+
+                let mut instances = HashMap::new();
+                for arg in args {
+                    if arg.sort != CoreSort::Instance {
+                        invalid!("module instantiation arguments must be core instances");
+                    }
+                    if arg.index as usize >= self.core_instances.len() {
+                        invalid!("unknown core instance {}", arg.index);
+                    }
+
+                    instances.insert(arg.name, arg.index);
+                }
+
+                // The types of the imports are checked when they are
+                // resolved.
+                for import in module_.imports() {
+                    let Some(&instance) = instances.get(import.module) else {
+                        invalid!("missing module instantiation argument `{}`", import.module);
+                    };
+
+                    let export = self.core_instances[instance as usize].get(import.name);
+                    if export.is_none_or(|type_| core_sort(type_) != core_sort(&import.type_)) {
+                        invalid!("unknown import `{}` `{}`", import.module, import.name);
+                    }
+                }
+
+                let exports = module_
+                    .exports()
+                    .map(|export| (export.name.to_owned(), export.type_))
+                    .collect();
+
+                self.defs.push(Def::CoreInstantiate {
+                    module,
+                    args: instances.into_iter().collect(),
+                });
+                exports
+            }
+
+            CoreInstance::FromExports(items) => {
+                let mut exports = HashMap::new();
+                let mut def = Vec::new();
+                for item in items {
+                    exports.insert(item.name.clone(), self.core_item(item.sort, item.index)?);
+                    def.push((item.name, item.sort, item.index));
+                }
+
+                self.defs.push(Def::CoreInstance(def));
+                exports
+            }
+        };
+
+        self.core_instances.push(exports);
+        Ok(())
+    }
+
+    fn instance(&mut self, instance: binary::Instance) -> Result<()> {
+        let items = match instance {
+            binary::Instance::Instantiate { .. } => invalid!("nested components are not supported"),
+            binary::Instance::FromExports(items) => items,
+        };
+
+        let mut type_ = HashMap::new();
+        let mut exports = Vec::new();
+        for export in items {
+            let (extern_, item) = self.item(export.sort, export.index)?;
+            if type_.insert(export.name.0.clone(), extern_).is_some() {
+                invalid!("duplicate export name `{}`", export.name.0);
+            }
+            exports.extend(item.map(|item| (export.name.0, item)));
+        }
+
+        self.instances.push(type_);
+        self.defs.push(Def::Instance(exports));
+        Ok(())
+    }
+
+    fn alias(&mut self, alias: binary::Alias) -> Result<()> {
+        match (alias.sort, alias.target) {
+            (Sort::Core(sort), AliasTarget::CoreInstanceExport { instance, name }) => {
+                let Some(exports) = self.core_instances.get(instance as usize) else {
+                    invalid!("unknown core instance {instance}");
+                };
+                let Some(type_) = exports.get(&name).filter(|type_| core_sort(type_) == sort)
+                else {
+                    invalid!(
+                        "core instance {instance} has no {} `{name}`",
+                        sort_name(sort)
+                    );
+                };
+
+                let type_ = type_.clone();
+                self.core_items.entry(sort).or_default().push(type_);
+                self.defs.push(Def::CoreAlias {
+                    instance,
+                    name,
+                    sort,
+                });
+            }
+
+            (sort, AliasTarget::InstanceExport { instance, name }) => {
+                let Some(exports) = self.instances.get(instance as usize) else {
+                    invalid!("unknown instance {instance}");
+                };
+
+                match (sort, exports.get(&name)) {
+                    (Sort::Func, Some(Extern::Func(type_))) => {
+                        self.funcs.push(type_.clone());
+                        self.defs.push(Def::Alias { instance, name });
+                    }
+                    (Sort::Instance, Some(Extern::Instance(type_))) => {
+                        self.instances.push(type_.clone());
+                        self.defs.push(Def::Alias { instance, name });
+                    }
+                    (Sort::Type, Some(Extern::Type(type_))) => self.types.push(type_.clone()),
+                    _ => invalid!("instance {instance} has no {sort:?} export `{name}`"),
+                }
+            }
+
+            // Outer aliases of the component itself only refer to types it
+            // already has.
+            (Sort::Type, AliasTarget::Outer { count: 0, index }) => {
+                let type_ = type_def(&self.types, index)?.clone();
+                self.types.push(type_);
+            }
+
+            _ => invalid!("outer aliases are not supported"),
+        }
+
+        Ok(())
+    }
+
+    fn type_(&mut self, type_: &binary::Type) -> Result<()> {
+        let type_ = match type_ {
+            binary::Type::Resource(resource) => {
+                if resource.rep != ValueType::I32 {
+                    invalid!("resource representation must be i32");
+                }
+
+                if let Some(dtor) = resource.dtor {
+                    let expected = types::FuncType::new([ValType::NumType(I32)], []);
+                    if *self.core_func(dtor)? != expected {
+                        invalid!("type mismatch: resource destructor must have type {expected}");
+                    }
+                }
+
+                self.resources.push(ResourceDecl::Defined);
+                let index = self.resources.len() as u32 - 1;
+                self.defs.push(Def::Resource {
+                    resource: index,
+                    dtor: resource.dtor,
+                });
+                TypeDef::Resource(index)
+            }
+            type_ => define_type(&self.types, &mut self.resources, type_)?,
+        };
+
+        self.types.push(type_);
+        Ok(())
+    }
+
+    fn canon(&mut self, canon: Canon) -> Result<()> {
+        match canon {
+            Canon::Lift(lift) => {
+                let TypeDef::Func(type_) = type_def(&self.types, lift.ty)? else {
+                    invalid!("type {} is not a function type", lift.ty);
+                };
+
+                let type_ = type_.clone();
+                let options = self.options(&lift.options, &type_, false)?;
+                let expected = canonical::core_type(&type_, false);
+                if *self.core_func(lift.core_func)? != expected {
+                    invalid!("type mismatch: lifted function must have type {expected}");
+                }
+
+                self.funcs.push(type_.clone());
+                self.defs.push(Def::Lift {
+                    core_func: lift.core_func,
+                    type_,
+                    options,
+                });
+            }
+
+            Canon::Lower(lower) => {
+                let Some(type_) = self.funcs.get(lower.func as usize) else {
+                    invalid!("unknown function {}", lower.func);
+                };
+
+                let options = self.options(&lower.options, type_, true)?;
+                let type_ = canonical::core_type(type_, true);
+                self.core_items
+                    .entry(CoreSort::Func)
+                    .or_default()
+                    .push(ExternType::Func(type_));
+                self.defs.push(Def::Lower {
+                    func: lower.func,
+                    options,
+                });
+            }
+
+            Canon::ResourceNew(index) | Canon::ResourceRep(index) => {
+                let resource = self.resource(index)?;
+                if !matches!(self.resources[resource as usize], ResourceDecl::Defined) {
+                    invalid!("resource type {index} is not defined by the component");
+                }
+
+                let i32 = ValType::NumType(I32);
+                self.core_items
+                    .entry(CoreSort::Func)
+                    .or_default()
+                    .push(ExternType::Func(types::FuncType::new([i32], [i32])));
+                self.defs.push(match canon {
+                    Canon::ResourceNew(_) => Def::ResourceNew(resource),
+                    _ => Def::ResourceRep(resource),
+                });
+            }
+
+            Canon::ResourceDrop(index) => {
+                let resource = self.resource(index)?;
+
+                let i32 = ValType::NumType(I32);
+                self.core_items
+                    .entry(CoreSort::Func)
+                    .or_default()
+                    .push(ExternType::Func(types::FuncType::new([i32], [])));
+                self.defs.push(Def::ResourceDrop(resource));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import(&mut self, import: binary::Import) -> Result<()> {
+        let name = import.name.0;
+        if !self.imports.insert(name.clone()) {
+            invalid!("duplicate import name `{name}`");
+        }
+
+        match extern_type(&mut self.types, &mut self.resources, &import.desc)? {
+            Extern::Func(type_) => {
+                self.funcs.push(type_.clone());
+                self.defs.push(Def::ImportFunc { name, type_ });
+            }
+
+            Extern::Type(type_) => {
+                if let TypeDef::Resource(resource) = type_ {
+                    self.import_resource(resource, None, &name);
+                }
+            }
+
+            Extern::Instance(type_) => {
+                let mut funcs = Vec::new();
+                for (export, extern_) in &type_ {
+                    match extern_ {
+                        Extern::Func(func) => funcs.push((export.clone(), func.clone())),
+                        Extern::Type(TypeDef::Resource(resource)) => {
+                            self.import_resource(*resource, Some(&name), export);
+                        }
+                        Extern::Type(_) => {}
+                        Extern::Instance(_) => {
+                            invalid!("instances exported by imported instances are not supported")
+                        }
+                    }
+                }
+
+                self.instances.push(type_);
+                self.defs.push(Def::ImportInstance { name, funcs });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes the resource type at `resource` the one imported as `name`, or
+    /// as the export `name` of the instance imported as `instance`, unless it
+    /// is an existing resource type.
+    fn import_resource(&mut self, resource: u32, instance: Option<&str>, name: &str) {
+        let decl = &mut self.resources[resource as usize];
+        if let ResourceDecl::Abstract = decl {
+            *decl = ResourceDecl::Import {
+                instance: instance.map(str::to_owned),
+                name: name.to_owned(),
+            };
+        }
+    }
+
+    fn export(&mut self, export: binary::Export) -> Result<()> {
+        let name = export.name.0;
+        if !self.exports.insert(name.clone()) {
+            invalid!("duplicate export name `{name}`");
+        }
+
+        // The item is also added to the index space of its sort, under the
+        // type it is exported as, which is not checked.
+        let (type_, item) = self.item(export.sort, export.index)?;
+        match type_ {
+            Extern::Func(type_) => self.funcs.push(type_),
+            Extern::Type(type_) => self.types.push(type_),
+            Extern::Instance(type_) => self.instances.push(type_),
+        }
+
+        if let Some(item) = item {
+            self.defs.push(Def::Export { name, item });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the type of the item at `index` in the index space of `sort`,
+    /// and the item itself if it exists at run time.
+    fn item(&self, sort: Sort, index: u32) -> Result<(Extern, Option<Item>)> {
+        Ok(match sort {
+            Sort::Func => match self.funcs.get(index as usize) {
+                Some(type_) => (Extern::Func(type_.clone()), Some(Item::Func(index))),
+                None => invalid!("unknown function {index}"),
+            },
+            Sort::Instance => match self.instances.get(index as usize) {
+                Some(type_) => (Extern::Instance(type_.clone()), Some(Item::Instance(index))),
+                None => invalid!("unknown instance {index}"),
+            },
+            Sort::Type => {
+                let type_ = type_def(&self.types, index)?;
+                let item = match type_ {
+                    TypeDef::Resource(resource) => Some(Item::Resource(*resource)),
+                    _ => None,
+                };
+
+                (Extern::Type(type_.clone()), item)
+            }
+            Sort::Core(_) => invalid!("core items can't be exported by components"),
+            Sort::Value => invalid!("values are not supported"),
+            Sort::Component => invalid!("nested components are not supported"),
+        })
+    }
+
+    fn core_item(&self, sort: CoreSort, index: u32) -> Result<ExternType> {
+        match self
+            .core_items
+            .get(&sort)
+            .and_then(|items| items.get(index as usize))
+        {
+            Some(type_) => Ok(type_.clone()),
+            None => invalid!("unknown core {} {index}", sort_name(sort)),
+        }
+    }
+
+    fn core_func(&self, index: u32) -> Result<&types::FuncType> {
+        match self
+            .core_items
+            .get(&CoreSort::Func)
+            .and_then(|funcs| funcs.get(index as usize))
+        {
+            Some(ExternType::Func(type_)) => Ok(type_),
+            _ => invalid!("unknown core function {index}"),
+        }
+    }
+
+    /// Returns the index in the resource types of the component of the
+    /// resource type at `index`.
+    fn resource(&self, index: u32) -> Result<u32> {
+        match type_def(&self.types, index)? {
+            TypeDef::Resource(resource) => Ok(*resource),
+            _ => invalid!("type {index} is not a resource type"),
+        }
+    }
+
+    /// Checks the canonical options `opts` of a function of type `type_`,
+    /// which is lowered rather than lifted if `lower` is set.
+    fn options(&self, opts: &[CanonOpt], type_: &FuncType, lower: bool) -> Result<Options> {
+        let mut options = Options::default();
+
+        for opt in opts {
+            let (option, index, name) = match *opt {
+                CanonOpt::Utf8 => continue,
+                CanonOpt::Utf16 | CanonOpt::CompactUtf16 => {
+                    invalid!("string encodings other than UTF-8 are not supported")
+                }
+                CanonOpt::Memory(index) => {
+                    let ExternType::Mem(memory) = self.core_item(CoreSort::Memory, index)? else {
+                        unreachable!("core memories are memories");
+                    };
+                    if memory.addr_type != AddrType::I32 {
+                        invalid!("canonical option `memory` must be a 32-bit memory");
+                    }
+
+                    (&mut options.memory, index, "memory")
+                }
+                CanonOpt::Realloc(index) => {
+                    let i32 = ValType::NumType(I32);
+                    let expected = types::FuncType::new([i32; 4], [i32]);
+                    if *self.core_func(index)? != expected {
+                        invalid!(
+                            "type mismatch: canonical option `realloc` must have type {expected}"
+                        );
+                    }
+
+                    (&mut options.realloc, index, "realloc")
+                }
+                CanonOpt::PostReturn(index) => {
+                    let results = canonical::core_type(type_, false).results().to_vec();
+                    let expected = types::FuncType::new(results, []);
+                    if lower {
+                        invalid!(
+                            "canonical option `post-return` can only be given to lifted functions"
+                        );
+                    }
+                    if *self.core_func(index)? != expected {
+                        invalid!(
+                            "type mismatch: canonical option `post-return` must have type {expected}"
+                        );
+                    }
+
+                    (&mut options.post_return, index, "post-return")
+                }
+                CanonOpt::Async | CanonOpt::Callback(_) => {
+                    invalid!("asynchronous functions are not supported")
+                }
+            };
+
+            if option.replace(index).is_some() {
+                invalid!("canonical option `{name}` is given more than once");
+            }
+        }
+
+        // Strings and lists are stored in the memory, and so are the values
+        // that don't fit in the flat parameters or results. The side that
+        // receives them allocates the memory they are stored in.
+        let uses_memory =
+            |type_: &Type| type_.contains(&|type_| matches!(type_, Type::String | Type::List(_)));
+        let params = type_.params.iter().map(|(_, type_)| type_);
+        let (params_fit, result_fits) = canonical::fits_flat(type_);
+
+        if options.memory.is_none()
+            && (type_.types().any(uses_memory) || !params_fit || !result_fits)
+        {
+            invalid!("canonical option `memory` is required");
+        }
+
+        let receives = match lower {
+            false => params.clone().any(uses_memory) || !params_fit,
+            true => type_.result.as_ref().is_some_and(uses_memory),
+        };
+        if options.realloc.is_none() && receives {
+            invalid!("canonical option `realloc` is required");
+        }
+
+        Ok(options)
+    }
+}
+
+/// Returns the type defined by `type_`, in the scope of `types`, adding the
+/// resource types it declares to `resources`.
+fn define_type(
+    types: &[TypeDef],
+    resources: &mut Vec<ResourceDecl>,
+    type_: &binary::Type,
+) -> Result<TypeDef> {
+    Ok(match type_ {
+        binary::Type::Defined(type_) => TypeDef::Value(defined_type(types, type_)?),
+        binary::Type::Func(type_) => TypeDef::Func(func_type(types, type_)?),
+        binary::Type::Instance(decls) => TypeDef::Instance(instance_type(types, resources, decls)?),
+        binary::Type::Component(_) => invalid!("component types are not supported"),
+        binary::Type::Resource(_) => invalid!("resource types can only be defined by components"),
+    })
+}
+
+fn type_def(types: &[TypeDef], index: u32) -> Result<&TypeDef> {
+    match types.get(index as usize) {
+        Some(type_) => Ok(type_),
+        None => invalid!("unknown type {index}"),
+    }
+}
+
+fn val_type(types: &[TypeDef], type_: &binary::ValType) -> Result<Type> {
+    match type_ {
+        binary::ValType::Primitive(type_) => Ok(Type::from(*type_)),
+        binary::ValType::Type(index) => match type_def(types, *index)? {
+            TypeDef::Value(type_) => Ok(type_.clone()),
+            _ => invalid!("type {index} is not a value type"),
+        },
+    }
+}
+
+fn defined_type(types: &[TypeDef], type_: &DefinedType) -> Result<Type> {
+    let val_type = |type_| val_type(types, type_);
+    let boxed = |type_| val_type(type_).map(Box::new);
+    let resource = |index: u32| match type_def(types, index)? {
+        TypeDef::Resource(resource) => Ok(ResourceType(*resource)),
+        _ => invalid!("type {index} is not a resource type"),
+    };
+
+    Ok(match type_ {
+        DefinedType::Primitive(type_) => Type::from(*type_),
+        DefinedType::Record(fields) => {
+            if fields.is_empty() {
+                invalid!("record type must have at least one field");
+            }
+
+            let fields = fields
+                .iter()
+                .map(|field| Ok((field.name.clone(), val_type(&field.ty)?)))
+                .collect::<Result<_>>()?;
+            Type::Record(fields)
+        }
+        DefinedType::Variant(cases) => {
+            if cases.is_empty() {
+                invalid!("variant type must have at least one case");
+            }
+            if cases.iter().any(|case| case.refines.is_some()) {
+                invalid!("refinements of variant cases are not supported");
+            }
+
+            let cases = cases
+                .iter()
+                .map(|case| {
+                    Ok((
+                        case.name.clone(),
+                        case.ty.as_ref().map(val_type).transpose()?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            Type::Variant(cases)
+        }
+        DefinedType::List(type_) => Type::List(boxed(type_)?),
+        DefinedType::Tuple(types) => {
+            if types.is_empty() {
+                invalid!("tuple type must have at least one field");
+            }
+
+            Type::Tuple(types.iter().map(val_type).collect::<Result<_>>()?)
+        }
+        DefinedType::Flags(names) => {
+            if names.is_empty() || names.len() > 32 {
+                invalid!("flags type must have between 1 and 32 flags");
+            }
+
+            Type::Flags(names.clone())
+        }
+        DefinedType::Enum(names) => {
+            if names.is_empty() {
+                invalid!("enum type must have at least one case");
+            }
+
+            Type::Enum(names.clone())
+        }
+        DefinedType::Option(type_) => Type::Option(boxed(type_)?),
+        DefinedType::Result { ok, err } => Type::Result {
+            ok: ok.as_ref().map(boxed).transpose()?,
+            err: err.as_ref().map(boxed).transpose()?,
+        },
+        DefinedType::Own(index) => Type::Own(resource(*index)?),
+        DefinedType::Borrow(index) => Type::Borrow(resource(*index)?),
+    })
+}
+
+fn func_type(types: &[TypeDef], type_: &binary::FuncType) -> Result<FuncType> {
+    let params = type_
+        .params
+        .iter()
+        .map(|param| Ok((param.name.clone(), val_type(types, &param.ty)?)))
+        .collect::<Result<_>>()?;
+
+    let result = match &type_.results {
+        ResultList::Unnamed(type_) => Some(val_type(types, type_)?),
+        ResultList::Named(results) if results.is_empty() => None,
+        ResultList::Named(_) => invalid!("named results are not supported"),
+    };
+
+    let is_borrow = |type_: &Type| matches!(type_, Type::Borrow(_));
+    if result
+        .as_ref()
+        .is_some_and(|type_| type_.contains(&is_borrow))
+    {
+        invalid!("function results can't contain borrowed handles");
+    }
+
+    Ok(FuncType { params, result })
+}
+
+/// Returns the type of an instance declared by `decls`, in the scope of
+/// `outer`.
+fn instance_type(
+    outer: &[TypeDef],
+    resources: &mut Vec<ResourceDecl>,
+    decls: &[InstanceDecl],
+) -> Result<InstanceType> {
+    let mut types = Vec::new();
+    let mut exports = HashMap::new();
+
+    for decl in decls {
+        match decl {
+            // Core types are only used by the types of modules, which
+            // instances can't export.
+            InstanceDecl::CoreType(_) => {}
+            InstanceDecl::Type(type_) => {
+                let type_ = define_type(&types, resources, type_)?;
+                types.push(type_);
+            }
+            InstanceDecl::Alias(binary::Alias {
+                sort: Sort::Type,
+                target: AliasTarget::Outer { count: 1, index },
+            }) => types.push(type_def(outer, *index)?.clone()),
+            InstanceDecl::Alias(_) => {
+                invalid!("instance types only support outer aliases of types")
+            }
+            InstanceDecl::Export { name, desc } => {
+                let export = extern_type(&mut types, resources, desc)?;
+                if exports.insert(name.0.clone(), export).is_some() {
+                    invalid!("duplicate export name `{}`", name.0);
+                }
+            }
+        }
+    }
+
+    Ok(exports)
+}
+
+/// Returns the type of an item that is imported or exported as described by
+/// `desc`, in the scope of `types`, to which the types it declares are
+/// added.
+fn extern_type(
+    types: &mut Vec<TypeDef>,
+    resources: &mut Vec<ResourceDecl>,
+    desc: &ExternDesc,
+) -> Result<Extern> {
+    Ok(match desc {
+        ExternDesc::Func(index) => match type_def(types, *index)? {
+            TypeDef::Func(type_) => Extern::Func(type_.clone()),
+            _ => invalid!("type {index} is not a function type"),
+        },
+        ExternDesc::Type(bound) => {
+            let type_ = match bound {
+                TypeBound::Eq(index) => type_def(types, *index)?.clone(),
+                TypeBound::SubResource => {
+                    resources.push(ResourceDecl::Abstract);
+                    TypeDef::Resource(resources.len() as u32 - 1)
+                }
+            };
+
+            types.push(type_.clone());
+            Extern::Type(type_)
+        }
+        ExternDesc::Instance(index) => match type_def(types, *index)? {
+            TypeDef::Instance(type_) => Extern::Instance(type_.clone()),
+            _ => invalid!("type {index} is not an instance type"),
+        },
+        ExternDesc::Module(_) => invalid!("imports and exports of modules are not supported"),
+        ExternDesc::Value(_) => invalid!("values are not supported"),
+        ExternDesc::Component(_) => invalid!("nested components are not supported"),
+    })
+}
+
+/// Returns the contents of a section, which was already decoded.
+fn contents<T: wasmbin::io::Decode>(blob: wasmbin::builtins::Blob<T>) -> Result<T> {
+    blob.contents.try_into_contents().map_err(module::malformed)
+}
+
+fn core_sort(type_: &ExternType) -> CoreSort {
+    match type_ {
+        ExternType::Func(_) => CoreSort::Func,
+        ExternType::Table(_) => CoreSort::Table,
+        ExternType::Mem(_) => CoreSort::Memory,
+        ExternType::Global(_) => CoreSort::Global,
+        ExternType::Tag(_) => CoreSort::Tag,
+    }
+}
+
+fn sort_name(sort: CoreSort) -> &'static str {
+    match sort {
+        CoreSort::Func => "function",
+        CoreSort::Table => "table",
+        CoreSort::Memory => "memory",
+        CoreSort::Global => "global",
+        CoreSort::Tag => "tag",
+        CoreSort::Type => "type",
+        CoreSort::Module => "module",
+        CoreSort::Instance => "instance",
+    }
+}
@@ -0,0 +1,884 @@
+//! The canonical ABI, which lifts the core values of components into
+//! [`Value`]s and lowers them back.
+//!
+//! Values are flattened into core parameters and results when there are few
+//! enough of them, and are otherwise stored in the memory of the component,
+//! as are strings and lists.
+
+use std::{iter::zip, slice, sync::Arc};
+
+use crate::{
+    host::{Caller, HostFunc},
+    interpreter::{Trap, Unwind},
+    memory::Memory,
+    types::{self, NumType, ValType},
+    values::{Num, Val},
+};
+
+use super::{
+    FuncType, Options, Resource, ResourceType, Type, Value,
+    instance::{ComponentFunc, Dtor},
+};
+
+pub(crate) const MAX_FLAT_PARAMS: usize = 16;
+pub(crate) const MAX_FLAT_RESULTS: usize = 1;
+
+/// The handles to resources that a component instance holds, by index.
+#[derive(Debug, Default)]
+pub(crate) struct HandleTable {
+    /// The handles, of which the first is never used, so that index 0 is
+    /// never valid.
+    handles: Vec<Option<Handle>>,
+    /// The indices of the handles that were removed, which are reused.
+    free: Vec<u32>,
+    /// The number of borrow handles that were lowered into the instance and
+    /// not dropped yet.
+    pub(crate) borrows: u32,
+}
+
+#[derive(Debug)]
+pub(crate) struct Handle {
+    pub(crate) resource: Resource,
+    /// Whether the handle owns the resource, rather than borrowing it.
+    pub(crate) own: bool,
+    /// The number of borrows of the handle that were lifted out of the
+    /// instance, for the duration of a call.
+    lends: u32,
+}
+
+impl HandleTable {
+    fn insert(&mut self, handle: Handle) -> u32 {
+        if self.handles.is_empty() {
+            self.handles.push(None);
+        }
+
+        match self.free.pop() {
+            Some(index) => {
+                self.handles[index as usize] = Some(handle);
+                index
+            }
+            None => {
+                self.handles.push(Some(handle));
+                self.handles.len() as u32 - 1
+            }
+        }
+    }
+
+    /// Returns the handle at `index`, which must be a handle to a resource
+    /// of type `type_`.
+    fn get(&mut self, index: u32, type_: ResourceType) -> Result<&mut Handle, Trap> {
+        match self.handles.get_mut(index as usize) {
+            Some(Some(handle)) if handle.resource.type_ == type_ => Ok(handle),
+            _ => Err(Trap::InvalidHandle),
+        }
+    }
+
+    /// Removes the handle at `index`, which must not be lent.
+    pub(crate) fn remove(&mut self, index: u32, type_: ResourceType) -> Result<Handle, Trap> {
+        if self.get(index, type_)?.lends > 0 {
+            return Err(Trap::ResourceBorrowed);
+        }
+
+        self.free.push(index);
+        Ok(self.handles[index as usize].take().unwrap())
+    }
+}
+
+/// Returns the core type of a function of type `type_`, once it is lifted, or
+/// lowered if `lower` is set.
+///
+/// Lowered functions store a result that doesn't fit in the flat results at
+/// a pointer given as their last parameter, and lifted functions return a
+/// pointer to it.
+pub(crate) fn core_type(type_: &FuncType, lower: bool) -> types::FuncType {
+    let i32 = ValType::NumType(NumType::I32);
+
+    let mut params = Vec::new();
+    for (_, param) in &type_.params {
+        flatten(param, &mut params);
+    }
+    if params.len() > MAX_FLAT_PARAMS {
+        params = vec![NumType::I32];
+    }
+
+    let mut results = Vec::new();
+    if let Some(result) = &type_.result {
+        flatten(result, &mut results);
+    }
+
+    let params = params.into_iter().map(ValType::NumType);
+    let results = results.into_iter().map(ValType::NumType);
+    match (results.len() > MAX_FLAT_RESULTS, lower) {
+        (false, _) => types::FuncType::new(params, results),
+        (true, false) => types::FuncType::new(params, [i32]),
+        (true, true) => types::FuncType::new(params.chain([i32]), []),
+    }
+}
+
+/// Returns whether the parameters and the result of a function of type
+/// `type_` fit in the flat parameters and results.
+pub(crate) fn fits_flat(type_: &FuncType) -> (bool, bool) {
+    let mut params = Vec::new();
+    for (_, param) in &type_.params {
+        flatten(param, &mut params);
+    }
+
+    let mut results = Vec::new();
+    if let Some(result) = &type_.result {
+        flatten(result, &mut results);
+    }
+
+    (
+        params.len() <= MAX_FLAT_PARAMS,
+        results.len() <= MAX_FLAT_RESULTS,
+    )
+}
+
+/// Appends the core types that values of type `type_` are flattened into.
+fn flatten(type_: &Type, flat: &mut Vec<NumType>) {
+    match type_ {
+        Type::S64 | Type::U64 => flat.push(NumType::I64),
+        Type::F32 => flat.push(NumType::F32),
+        Type::F64 => flat.push(NumType::F64),
+        Type::String | Type::List(_) => flat.extend([NumType::I32; 2]),
+        Type::Record(fields) => {
+            for (_, type_) in fields {
+                flatten(type_, flat);
+            }
+        }
+        Type::Tuple(types) => {
+            for type_ in types {
+                flatten(type_, flat);
+            }
+        }
+        Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. } => {
+            // The payloads of the cases share their core values, whose types
+            // are joined.
+            let mut payload: Vec<NumType> = Vec::new();
+            for type_ in cases(type_).into_iter().flatten() {
+                let mut case = Vec::new();
+                flatten(type_, &mut case);
+                for (i, type_) in case.into_iter().enumerate() {
+                    match payload.get_mut(i) {
+                        Some(joined) => *joined = join(*joined, type_),
+                        None => payload.push(type_),
+                    }
+                }
+            }
+
+            flat.push(NumType::I32);
+            flat.extend(payload);
+        }
+        _ => flat.push(NumType::I32),
+    }
+}
+
+fn join(a: NumType, b: NumType) -> NumType {
+    match (a, b) {
+        _ if a == b => a,
+        (NumType::I32, NumType::F32) | (NumType::F32, NumType::I32) => NumType::I32,
+        _ => NumType::I64,
+    }
+}
+
+fn flat_len(type_: &Type) -> usize {
+    let mut flat = Vec::new();
+    flatten(type_, &mut flat);
+    flat.len()
+}
+
+/// Returns the payload type of each case of a variant, enum, option or
+/// result type.
+fn cases(type_: &Type) -> Vec<Option<&Type>> {
+    match type_ {
+        Type::Variant(cases) => cases.iter().map(|(_, type_)| type_.as_ref()).collect(),
+        Type::Enum(names) => vec![None; names.len()],
+        Type::Option(type_) => vec![None, Some(type_)],
+        Type::Result { ok, err } => vec![ok.as_deref(), err.as_deref()],
+        _ => unreachable!("{type_:?} has no cases"),
+    }
+}
+
+/// Returns the index of the case of `value`, of type `type_`, and its
+/// payload.
+fn case<'v>(value: &'v Value, type_: &Type) -> (u32, Option<&'v Value>) {
+    let (index, payload) = match (value, type_) {
+        (Value::Variant(name, payload), Type::Variant(cases)) => {
+            let index = cases.iter().position(|(case, _)| case == name);
+            (index.unwrap(), payload)
+        }
+        (Value::Enum(name), Type::Enum(names)) => {
+            (names.iter().position(|case| case == name).unwrap(), &None)
+        }
+        (Value::Option(None), _) => (0, &None),
+        (Value::Option(payload @ Some(_)), _) => (1, payload),
+        (Value::Result(Ok(payload)), _) => (0, payload),
+        (Value::Result(Err(payload)), _) => (1, payload),
+        _ => unreachable!("{value:?} is not a value of type {type_:?}"),
+    };
+
+    (index as u32, payload.as_deref())
+}
+
+/// Returns the value of the case at `index` of `type_`, with `payload`.
+fn make_case(type_: &Type, index: u32, payload: Option<Value>) -> Value {
+    let payload = payload.map(Box::new);
+
+    match type_ {
+        Type::Variant(cases) => Value::Variant(cases[index as usize].0.clone(), payload),
+        Type::Enum(names) => Value::Enum(names[index as usize].clone()),
+        Type::Option(_) => Value::Option(payload),
+        Type::Result { .. } if index == 0 => Value::Result(Ok(payload)),
+        Type::Result { .. } => Value::Result(Err(payload)),
+        _ => unreachable!("{type_:?} has no cases"),
+    }
+}
+
+fn align_to(ptr: u32, alignment: u32) -> u32 {
+    ptr.div_ceil(alignment) * alignment
+}
+
+pub(crate) fn alignment(type_: &Type) -> u32 {
+    match type_ {
+        Type::Bool | Type::S8 | Type::U8 => 1,
+        Type::S16 | Type::U16 => 2,
+        Type::S64 | Type::U64 | Type::F64 => 8,
+        Type::Record(fields) => fields
+            .iter()
+            .map(|(_, type_)| alignment(type_))
+            .max()
+            .unwrap(),
+        Type::Tuple(types) => types.iter().map(alignment).max().unwrap(),
+        Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. } => {
+            discriminant_size(type_).max(max_case_alignment(type_))
+        }
+        Type::Flags(names) => flags_size(names.len()),
+        _ => 4,
+    }
+}
+
+pub(crate) fn size(type_: &Type) -> u32 {
+    match type_ {
+        Type::Bool | Type::S8 | Type::U8 => 1,
+        Type::S16 | Type::U16 => 2,
+        Type::S64 | Type::U64 | Type::F64 | Type::String | Type::List(_) => 8,
+        Type::Record(_) | Type::Tuple(_) => {
+            let mut size = 0;
+            for type_ in fields(type_) {
+                size = align_to(size, alignment(type_)) + self::size(type_);
+            }
+
+            align_to(size, alignment(type_))
+        }
+        Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. } => {
+            let payload = cases(type_).into_iter().flatten().map(self::size).max();
+            let size = payload_offset(type_) + payload.unwrap_or(0);
+
+            align_to(size, alignment(type_))
+        }
+        Type::Flags(names) => flags_size(names.len()),
+        _ => 4,
+    }
+}
+
+fn fields(type_: &Type) -> Vec<&Type> {
+    match type_ {
+        Type::Record(fields) => fields.iter().map(|(_, type_)| type_).collect(),
+        Type::Tuple(types) => types.iter().collect(),
+        _ => unreachable!("{type_:?} has no fields"),
+    }
+}
+
+fn flags_size(len: usize) -> u32 {
+    match len {
+        0..=8 => 1,
+        9..=16 => 2,
+        _ => 4,
+    }
+}
+
+fn discriminant_size(type_: &Type) -> u32 {
+    match cases(type_).len() {
+        0..=0x100 => 1,
+        0x101..=0x10000 => 2,
+        _ => 4,
+    }
+}
+
+fn max_case_alignment(type_: &Type) -> u32 {
+    cases(type_)
+        .into_iter()
+        .flatten()
+        .map(alignment)
+        .max()
+        .unwrap_or(1)
+}
+
+/// Returns the offset of the payload of the values of `type_` in memory.
+fn payload_offset(type_: &Type) -> u32 {
+    align_to(discriminant_size(type_), max_case_alignment(type_))
+}
+
+/// Returns the bits of the core value `val`.
+pub(crate) fn to_flat(val: &Val) -> u64 {
+    match val {
+        Val::Num(Num::I32(val)) => *val as u32 as u64,
+        Val::Num(Num::I64(val)) => *val as u64,
+        Val::Num(Num::F32(val)) => val.to_bits() as u64,
+        Val::Num(Num::F64(val)) => val.to_bits(),
+        _ => unreachable!("{val:?} is not a flat value"),
+    }
+}
+
+/// Returns the core value of type `type_` whose bits are `bits`.
+pub(crate) fn from_flat(bits: u64, type_: &ValType) -> Val {
+    match type_ {
+        ValType::NumType(NumType::I32) => Val::from(bits as i32),
+        ValType::NumType(NumType::I64) => Val::from(bits as i64),
+        ValType::NumType(NumType::F32) => Val::from(f32::from_bits(bits as u32)),
+        ValType::NumType(NumType::F64) => Val::from(f64::from_bits(bits)),
+        _ => unreachable!("{type_} is not a flat type"),
+    }
+}
+
+fn next(flat: &mut slice::Iter<'_, u64>) -> u64 {
+    *flat.next().expect("the core values match the type")
+}
+
+/// NaNs have no payload once they cross the boundary of a component.
+fn canonicalize_f32(val: f32) -> f32 {
+    if val.is_nan() { f32::NAN } else { val }
+}
+
+fn canonicalize_f64(val: f64) -> f64 {
+    if val.is_nan() { f64::NAN } else { val }
+}
+
+/// The context in which values are lifted out of or lowered into the
+/// component instance at `instance`, through its canonical options.
+pub(crate) struct Cx<'c, 'a> {
+    pub(crate) caller: &'c mut Caller<'a>,
+    options: Options,
+    instance: u32,
+    /// The indices of the handles that were lent for the duration of the
+    /// call.
+    lends: Vec<u32>,
+}
+
+impl<'c, 'a> Cx<'c, 'a> {
+    pub(crate) fn new(caller: &'c mut Caller<'a>, options: Options, instance: u32) -> Self {
+        Cx {
+            caller,
+            options,
+            instance,
+            lends: Vec::new(),
+        }
+    }
+
+    fn memory(&mut self) -> &mut Memory {
+        let memory = self.options.memory.expect("validation requires a memory");
+        &mut self.caller.state.memories[memory as usize]
+    }
+
+    pub(crate) fn table(&mut self) -> &mut HandleTable {
+        &mut self.caller.state.handles[self.instance as usize]
+    }
+
+    /// Checks that `len` bytes at `ptr` are in bounds.
+    fn check_bounds(&mut self, ptr: u32, len: u64) -> Result<(), Trap> {
+        if u64::from(ptr) + len > self.memory().len() {
+            return Err(Trap::MemoryOutOfBounds);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a value of type `type_` can be stored at `ptr`.
+    pub(crate) fn check_pointer(&mut self, ptr: u32, type_: &Type) -> Result<(), Trap> {
+        if !ptr.is_multiple_of(alignment(type_)) {
+            return Err(Trap::UnalignedPointer);
+        }
+
+        self.check_bounds(ptr, size(type_).into())
+    }
+
+    /// Allocates `size` bytes aligned to `align` with the `realloc` function
+    /// of the component, and returns their address.
+    pub(crate) fn realloc(&mut self, align: u32, size: u32) -> Result<u32, Unwind> {
+        let realloc = self.options.realloc.expect("validation requires a realloc");
+        let args = [0, 0, align, size].map(|arg| Val::from(arg as i32));
+        let ptr = to_flat(&self.caller.call(realloc, &args)?[0]) as u32;
+
+        if !ptr.is_multiple_of(align) {
+            return Err(Trap::UnalignedPointer.into());
+        }
+        self.check_bounds(ptr, size.into())?;
+
+        Ok(ptr)
+    }
+
+    fn load_int(&mut self, ptr: u32, size: u32) -> Result<u64, Trap> {
+        let mut bytes = [0; 8];
+        self.memory()
+            .read_bytes(ptr.into(), &mut bytes[..size as usize])?;
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn store_int(&mut self, ptr: u32, size: u32, value: u64) -> Result<(), Trap> {
+        self.memory()
+            .write_bytes(ptr.into(), &value.to_le_bytes()[..size as usize])
+    }
+
+    /// Lifts a value of type `type_` out of the core values `flat`.
+    pub(crate) fn lift_flat(
+        &mut self,
+        flat: &mut slice::Iter<'_, u64>,
+        type_: &Type,
+    ) -> Result<Value, Trap> {
+        Ok(match type_ {
+            Type::String => {
+                let (ptr, len) = (next(flat) as u32, next(flat) as u32);
+                Value::String(self.load_string(ptr, len)?)
+            }
+            Type::List(type_) => {
+                let (ptr, len) = (next(flat) as u32, next(flat) as u32);
+                Value::List(self.load_list(ptr, len, type_)?)
+            }
+            Type::Record(fields) => Value::Record(
+                fields
+                    .iter()
+                    .map(|(name, type_)| Ok((name.clone(), self.lift_flat(flat, type_)?)))
+                    .collect::<Result<_, Trap>>()?,
+            ),
+            Type::Tuple(types) => Value::Tuple(
+                types
+                    .iter()
+                    .map(|type_| self.lift_flat(flat, type_))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. } => {
+                let index = next(flat) as u32;
+                let Some(&case) = cases(type_).get(index as usize) else {
+                    return Err(Trap::InvalidDiscriminant);
+                };
+
+                // The core values that the payload of the case doesn't use
+                // are skipped.
+                let mut padding = flat_len(type_) - 1;
+                let payload = match case {
+                    Some(case) => {
+                        padding -= flat_len(case);
+                        Some(self.lift_flat(flat, case)?)
+                    }
+                    None => None,
+                };
+                for _ in 0..padding {
+                    next(flat);
+                }
+
+                make_case(type_, index, payload)
+            }
+            type_ => self.lift_scalar(next(flat), type_)?,
+        })
+    }
+
+    /// Lifts a value of type `type_` out of the memory, at `ptr`.
+    pub(crate) fn load(&mut self, ptr: u32, type_: &Type) -> Result<Value, Trap> {
+        Ok(match type_ {
+            Type::String | Type::List(_) => {
+                let (data, len) = (
+                    self.load_int(ptr, 4)? as u32,
+                    self.load_int(ptr + 4, 4)? as u32,
+                );
+                match type_ {
+                    Type::List(type_) => Value::List(self.load_list(data, len, type_)?),
+                    _ => Value::String(self.load_string(data, len)?),
+                }
+            }
+            Type::Record(_) | Type::Tuple(_) => {
+                let mut values = Vec::new();
+                let mut offset = ptr;
+                for type_ in fields(type_) {
+                    offset = align_to(offset, alignment(type_));
+                    values.push(self.load(offset, type_)?);
+                    offset += size(type_);
+                }
+
+                match type_ {
+                    Type::Record(fields) => {
+                        let names = fields.iter().map(|(name, _)| name.clone());
+                        Value::Record(zip(names, values).collect())
+                    }
+                    _ => Value::Tuple(values),
+                }
+            }
+            Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. } => {
+                let index = self.load_int(ptr, discriminant_size(type_))? as u32;
+                let Some(&case) = cases(type_).get(index as usize) else {
+                    return Err(Trap::InvalidDiscriminant);
+                };
+
+                let ptr = ptr + payload_offset(type_);
+                let payload = case.map(|case| self.load(ptr, case)).transpose()?;
+                make_case(type_, index, payload)
+            }
+            type_ => {
+                let bits = self.load_int(ptr, size(type_))?;
+                self.lift_scalar(bits, type_)?
+            }
+        })
+    }
+
+    /// Lifts a value of type `type_`, which is neither a string, a list nor a
+    /// compound value, out of its bits.
+    fn lift_scalar(&mut self, bits: u64, type_: &Type) -> Result<Value, Trap> {
+        Ok(match type_ {
+            Type::Bool => Value::Bool(bits as u32 != 0),
+            Type::S8 => Value::S8(bits as i8),
+            Type::U8 => Value::U8(bits as u8),
+            Type::S16 => Value::S16(bits as i16),
+            Type::U16 => Value::U16(bits as u16),
+            Type::S32 => Value::S32(bits as i32),
+            Type::U32 => Value::U32(bits as u32),
+            Type::S64 => Value::S64(bits as i64),
+            Type::U64 => Value::U64(bits),
+            Type::F32 => Value::F32(canonicalize_f32(f32::from_bits(bits as u32))),
+            Type::F64 => Value::F64(canonicalize_f64(f64::from_bits(bits))),
+            Type::Char => Value::Char(char::from_u32(bits as u32).ok_or(Trap::InvalidChar)?),
+            Type::Flags(names) => Value::Flags(
+                names
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| bits >> i & 1 == 1)
+                    .map(|(_, name)| name.clone())
+                    .collect(),
+            ),
+            Type::Own(type_) => Value::Own(self.lift_own(bits as u32, *type_)?),
+            Type::Borrow(type_) => Value::Borrow(self.lift_borrow(bits as u32, *type_)?),
+            _ => unreachable!("{type_:?} is not a scalar type"),
+        })
+    }
+
+    fn load_string(&mut self, ptr: u32, len: u32) -> Result<String, Trap> {
+        // The bounds are checked before the string is allocated.
+        self.check_bounds(ptr, len.into())?;
+
+        let mut bytes = vec![0; len as usize];
+        self.memory().read_bytes(ptr.into(), &mut bytes)?;
+
+        String::from_utf8(bytes).map_err(|_| Trap::InvalidUtf8)
+    }
+
+    fn load_list(&mut self, ptr: u32, len: u32, type_: &Type) -> Result<Vec<Value>, Trap> {
+        let size = size(type_);
+        if !ptr.is_multiple_of(alignment(type_)) {
+            return Err(Trap::UnalignedPointer);
+        }
+        self.check_bounds(ptr, u64::from(len) * u64::from(size))?;
+
+        (0..len).map(|i| self.load(ptr + i * size, type_)).collect()
+    }
+
+    /// Lowers `value`, of type `type_`, into core values appended to `flat`.
+    pub(crate) fn lower_flat(
+        &mut self,
+        value: &Value,
+        type_: &Type,
+        flat: &mut Vec<u64>,
+    ) -> Result<(), Unwind> {
+        match (value, type_) {
+            (Value::String(string), _) => {
+                let (ptr, len) = self.store_string(string)?;
+                flat.extend([u64::from(ptr), u64::from(len)]);
+            }
+            (Value::List(values), Type::List(type_)) => {
+                let (ptr, len) = self.store_list(values, type_)?;
+                flat.extend([u64::from(ptr), u64::from(len)]);
+            }
+            (Value::Record(values), Type::Record(types)) => {
+                for ((_, value), (_, type_)) in zip(values, types) {
+                    self.lower_flat(value, type_, flat)?;
+                }
+            }
+            (Value::Tuple(values), Type::Tuple(types)) => {
+                for (value, type_) in zip(values, types) {
+                    self.lower_flat(value, type_, flat)?;
+                }
+            }
+            (_, Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. }) => {
+                let end = flat.len() + flat_len(type_);
+                let (index, payload) = case(value, type_);
+                flat.push(index.into());
+                if let (Some(payload), Some(case)) = (payload, cases(type_)[index as usize]) {
+                    self.lower_flat(payload, case, flat)?;
+                }
+                flat.resize(end, 0);
+            }
+            _ => {
+                let bits = self.lower_scalar(value, type_);
+                flat.push(bits);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lowers `value`, of type `type_`, into the memory, at `ptr`.
+    pub(crate) fn store(&mut self, ptr: u32, value: &Value, type_: &Type) -> Result<(), Unwind> {
+        match (value, type_) {
+            (Value::String(_) | Value::List(_), _) => {
+                let (data, len) = match (value, type_) {
+                    (Value::List(values), Type::List(type_)) => self.store_list(values, type_)?,
+                    (Value::String(string), _) => self.store_string(string)?,
+                    _ => unreachable!("{value:?} is not a value of type {type_:?}"),
+                };
+                self.store_int(ptr, 4, data.into())?;
+                self.store_int(ptr + 4, 4, len.into())?;
+            }
+            (Value::Record(_) | Value::Tuple(_), _) => {
+                let values: Vec<_> = match value {
+                    Value::Record(fields) => fields.iter().map(|(_, value)| value).collect(),
+                    Value::Tuple(values) => values.iter().collect(),
+                    _ => unreachable!(),
+                };
+
+                let mut offset = ptr;
+                for (value, type_) in zip(values, fields(type_)) {
+                    offset = align_to(offset, alignment(type_));
+                    self.store(offset, value, type_)?;
+                    offset += size(type_);
+                }
+            }
+            (_, Type::Variant(_) | Type::Enum(_) | Type::Option(_) | Type::Result { .. }) => {
+                let (index, payload) = case(value, type_);
+                self.store_int(ptr, discriminant_size(type_), index.into())?;
+                if let (Some(payload), Some(case)) = (payload, cases(type_)[index as usize]) {
+                    self.store(ptr + payload_offset(type_), payload, case)?;
+                }
+            }
+            _ => {
+                let bits = self.lower_scalar(value, type_);
+                self.store_int(ptr, size(type_), bits)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lowers `value`, of type `type_`, which is neither a string, a list nor
+    /// a compound value, into its bits.
+    fn lower_scalar(&mut self, value: &Value, type_: &Type) -> u64 {
+        match (value, type_) {
+            (Value::Bool(value), _) => *value as u64,
+            (Value::S8(value), _) => *value as i32 as u32 as u64,
+            (Value::U8(value), _) => *value as u64,
+            (Value::S16(value), _) => *value as i32 as u32 as u64,
+            (Value::U16(value), _) => *value as u64,
+            (Value::S32(value), _) => *value as u32 as u64,
+            (Value::U32(value), _) => *value as u64,
+            (Value::S64(value), _) => *value as u64,
+            (Value::U64(value), _) => *value,
+            (Value::F32(value), _) => canonicalize_f32(*value).to_bits() as u64,
+            (Value::F64(value), _) => canonicalize_f64(*value).to_bits(),
+            (Value::Char(value), _) => *value as u64,
+            (Value::Flags(flags), Type::Flags(names)) => names
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| flags.contains(name))
+                .fold(0, |bits, (i, _)| bits | 1 << i),
+            (Value::Own(resource), _) => self.lower_own(*resource).into(),
+            (Value::Borrow(resource), _) => self.lower_borrow(*resource).into(),
+            _ => unreachable!("{value:?} is not a value of type {type_:?}"),
+        }
+    }
+
+    fn store_string(&mut self, string: &str) -> Result<(u32, u32), Unwind> {
+        let len = string.len() as u32;
+        let ptr = self.realloc(1, len)?;
+        self.memory().write_bytes(ptr.into(), string.as_bytes())?;
+
+        Ok((ptr, len))
+    }
+
+    fn store_list(&mut self, values: &[Value], type_: &Type) -> Result<(u32, u32), Unwind> {
+        let (size, len) = (size(type_), values.len() as u32);
+        let ptr = self.realloc(alignment(type_), size * len)?;
+        for (i, value) in values.iter().enumerate() {
+            self.store(ptr + i as u32 * size, value, type_)?;
+        }
+
+        Ok((ptr, len))
+    }
+
+    /// Takes the resource owned by the handle at `index` out of the
+    /// instance.
+    fn lift_own(&mut self, index: u32, type_: ResourceType) -> Result<Resource, Trap> {
+        let table = self.table();
+        if !table.get(index, type_)?.own {
+            return Err(Trap::InvalidHandle);
+        }
+
+        Ok(table.remove(index, type_)?.resource)
+    }
+
+    /// Lends the resource of the handle at `index` until the end of the
+    /// call.
+    fn lift_borrow(&mut self, index: u32, type_: ResourceType) -> Result<Resource, Trap> {
+        let handle = self.table().get(index, type_)?;
+        handle.lends += 1;
+
+        let resource = handle.resource;
+        self.lends.push(index);
+        Ok(resource)
+    }
+
+    fn lower_own(&mut self, resource: Resource) -> u32 {
+        self.table().insert(Handle {
+            resource,
+            own: true,
+            lends: 0,
+        })
+    }
+
+    /// Lowers a borrow of `resource`, which is passed by its representation
+    /// when the instance defines its type.
+    fn lower_borrow(&mut self, resource: Resource) -> u32 {
+        let definer = self.caller.code.resource_types[resource.type_.0 as usize].instance;
+        if definer == Some(self.instance) {
+            return resource.rep;
+        }
+
+        let table = self.table();
+        table.borrows += 1;
+        table.insert(Handle {
+            resource,
+            own: false,
+            lends: 0,
+        })
+    }
+
+    /// Ends the lends of the call.
+    pub(crate) fn release(&mut self) {
+        for index in std::mem::take(&mut self.lends) {
+            if let Some(Some(handle)) = self.table().handles.get_mut(index as usize) {
+                handle.lends -= 1;
+            }
+        }
+    }
+}
+
+/// Returns the core function that lowers `func`, through `options`, into the
+/// component instance at `instance`.
+pub(crate) fn lower(func: ComponentFunc, options: Options, instance: u32) -> HostFunc {
+    let core_type = core_type(func.type_(), true);
+
+    HostFunc(Arc::new(move |caller, args| {
+        let type_ = func.type_();
+        let flat: Vec<_> = args.iter().map(to_flat).collect();
+        let mut cx = Cx::new(caller, options, instance);
+
+        let (params_fit, result_fits) = fits_flat(type_);
+        let params = match params_fit {
+            true => {
+                let mut flat = flat.iter();
+                let params = type_
+                    .params
+                    .iter()
+                    .map(|(_, type_)| cx.lift_flat(&mut flat, type_));
+                params.collect::<Result<Vec<_>, _>>()
+            }
+            false => {
+                let tuple = Type::Tuple(
+                    type_
+                        .params
+                        .iter()
+                        .map(|(_, type_)| type_.clone())
+                        .collect(),
+                );
+                let ptr = flat[0] as u32;
+                cx.check_pointer(ptr, &tuple)
+                    .and_then(|()| match cx.load(ptr, &tuple)? {
+                        Value::Tuple(values) => Ok(values),
+                        _ => unreachable!(),
+                    })
+            }
+        };
+        let params = match params {
+            Ok(params) => params,
+            Err(trap) => {
+                cx.release();
+                return Err(trap.into());
+            }
+        };
+
+        let result = func.call(cx.caller, &params);
+        cx.release();
+
+        match (result?, &type_.result) {
+            (Some(value), Some(type_)) if !result_fits => {
+                let ptr = *flat.last().unwrap() as u32;
+                cx.check_pointer(ptr, type_)?;
+                cx.store(ptr, &value, type_)?;
+                Ok(Vec::new())
+            }
+            (Some(value), Some(type_)) => {
+                let mut flat = Vec::new();
+                cx.lower_flat(&value, type_, &mut flat)?;
+                Ok(zip(flat, core_type.results())
+                    .map(|(bits, type_)| from_flat(bits, type_))
+                    .collect())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }))
+}
+
+/// Returns the `resource.new` core function of `type_`, which makes a handle
+/// in the component instance at `instance`.
+pub(crate) fn resource_new(type_: ResourceType, instance: u32) -> HostFunc {
+    HostFunc(Arc::new(move |caller, args| {
+        let resource = Resource {
+            type_,
+            rep: to_flat(&args[0]) as u32,
+        };
+        let index = Cx::new(caller, Options::default(), instance).lower_own(resource);
+
+        Ok(vec![Val::from(index as i32)])
+    }))
+}
+
+/// Returns the `resource.rep` core function of `type_`.
+pub(crate) fn resource_rep(type_: ResourceType, instance: u32) -> HostFunc {
+    HostFunc(Arc::new(move |caller, args| {
+        let table = &mut caller.state.handles[instance as usize];
+        let handle = table.get(to_flat(&args[0]) as u32, type_)?;
+
+        Ok(vec![Val::from(handle.resource.rep as i32)])
+    }))
+}
+
+/// Returns the `resource.drop` core function of `type_`, which runs the
+/// destructor of the resource when the handle owns it.
+pub(crate) fn resource_drop(type_: ResourceType, instance: u32) -> HostFunc {
+    HostFunc(Arc::new(move |caller, args| {
+        let table = &mut caller.state.handles[instance as usize];
+        let handle = table.remove(to_flat(&args[0]) as u32, type_)?;
+        if !handle.own {
+            table.borrows -= 1;
+            return Ok(Vec::new());
+        }
+
+        let code = caller.code;
+        let rep = handle.resource.rep;
+        match &code.resource_types[type_.0 as usize].dtor {
+            Some(Dtor::Core(func)) => {
+                caller.call(*func, &[Val::from(rep as i32)])?;
+            }
+            Some(Dtor::Host(dtor)) => dtor(caller, rep)?,
+            None => {}
+        }
+
+        Ok(Vec::new())
+    }))
+}
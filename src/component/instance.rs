@@ -0,0 +1,554 @@
+//! The instances of components, and the functions and resource types that
+//! the host defines for them.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    iter::zip,
+    sync::Arc,
+};
+
+use wasmbin::component::CoreSort;
+
+use crate::{
+    host::Caller,
+    interpreter::{Trap, Unwind},
+    store::{InstantiationError, Store},
+    types::{self, NumType, ValType},
+};
+
+use super::{
+    Component, ComponentInstanceAddr, Def, FuncType, Item, Options, ResourceDecl, ResourceType,
+    Type, Value,
+    canonical::{self, Cx},
+};
+
+/// The implementation of a component function defined by the host.
+pub(crate) type HostComponentFn =
+    dyn Fn(&mut Caller<'_>, &[Value]) -> Result<Option<Value>, Unwind> + Send + Sync;
+
+/// The implementation of the destructor of a resource type defined by the
+/// host, which gets the representation of the resource.
+pub(crate) type HostDtor = dyn Fn(&mut Caller<'_>, u32) -> Result<(), Unwind> + Send + Sync;
+
+/// A function of a component instance, or of the host.
+#[derive(Clone)]
+pub(crate) enum ComponentFunc {
+    Lifted(Arc<Lifted>),
+    Host {
+        type_: FuncType,
+        name: String,
+        func: Arc<HostComponentFn>,
+    },
+}
+
+/// A core function lifted out of a component instance.
+#[derive(Debug)]
+pub(crate) struct Lifted {
+    func: u32,
+    type_: FuncType,
+    options: Options,
+    /// The component instance whose memory and handles are used.
+    instance: u32,
+}
+
+/// A function, resource type or instance that components can import.
+#[derive(Clone, Debug)]
+pub(crate) enum HostItem {
+    Func(ComponentFunc),
+    Resource(ResourceType),
+    Instance(HashMap<String, HostItem>),
+}
+
+/// A resource type of the store.
+#[derive(Debug)]
+pub(crate) struct ResourceTypeDef {
+    /// The component instance that defines the type, which is `None` when
+    /// the host does.
+    pub(crate) instance: Option<u32>,
+    pub(crate) dtor: Option<Dtor>,
+}
+
+/// The destructor of a resource type, which is run when an owning handle is
+/// dropped.
+#[derive(Clone)]
+pub(crate) enum Dtor {
+    /// The core function at the address.
+    Core(u32),
+    Host(Arc<HostDtor>),
+}
+
+/// An instance of a component, of which only the exports are kept, since
+/// its core instances are in the store.
+#[derive(Debug, Default)]
+pub(crate) struct ComponentInstance {
+    exports: HashMap<String, Export>,
+}
+
+/// An item exported by a component instance.
+#[derive(Clone, Debug)]
+enum Export {
+    Func(ComponentFunc),
+    Instance(HashMap<String, Export>),
+    Resource(ResourceType),
+}
+
+impl Debug for ComponentFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComponentFunc::Lifted(lifted) => lifted.fmt(f),
+            ComponentFunc::Host { name, .. } => write!(f, "Host({name:?})"),
+        }
+    }
+}
+
+impl Debug for Dtor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dtor::Core(func) => write!(f, "Core({func})"),
+            Dtor::Host(_) => f.write_str("Host"),
+        }
+    }
+}
+
+impl ComponentFunc {
+    pub(crate) fn type_(&self) -> &FuncType {
+        match self {
+            ComponentFunc::Lifted(lifted) => &lifted.type_,
+            ComponentFunc::Host { type_, .. } => type_,
+        }
+    }
+
+    /// Calls the function with `args`, which must match its parameters.
+    pub(crate) fn call(
+        &self,
+        caller: &mut Caller<'_>,
+        args: &[Value],
+    ) -> Result<Option<Value>, Unwind> {
+        let type_ = self.type_();
+        assert!(
+            args.len() == type_.params.len()
+                && zip(args, &type_.params).all(|(arg, (_, type_))| arg.has_type(type_)),
+            "arguments don't match the parameters of {self:?}"
+        );
+
+        let result = match self {
+            ComponentFunc::Lifted(lifted) => lifted.call(caller, args)?,
+            ComponentFunc::Host { func, .. } => func(caller, args)?,
+        };
+
+        let matches = match (&result, &type_.result) {
+            (None, None) => true,
+            (Some(value), Some(type_)) => value.has_type(type_),
+            _ => false,
+        };
+        assert!(matches, "result doesn't match the type of {self:?}");
+
+        Ok(result)
+    }
+
+    /// Returns a name of the function that is suitable for diagnostics.
+    fn name(&self, store: &Store) -> String {
+        match self {
+            ComponentFunc::Lifted(lifted) => {
+                let code = &store.code;
+                code.func_name(&code.funcs[lifted.func as usize])
+            }
+            ComponentFunc::Host { name, .. } => name.clone(),
+        }
+    }
+}
+
+impl Lifted {
+    fn call(&self, caller: &mut Caller<'_>, args: &[Value]) -> Result<Option<Value>, Unwind> {
+        let core_type = canonical::core_type(&self.type_, false);
+        let (params_fit, result_fits) = canonical::fits_flat(&self.type_);
+
+        let mut cx = Cx::new(caller, self.options, self.instance);
+        let borrows = cx.table().borrows;
+
+        // The parameters that don't fit in the flat parameters are stored in
+        // memory allocated by the instance, as a tuple.
+        let mut flat = Vec::new();
+        if params_fit {
+            for (arg, (_, type_)) in zip(args, &self.type_.params) {
+                cx.lower_flat(arg, type_, &mut flat)?;
+            }
+        } else {
+            let types = self.type_.params.iter().map(|(_, type_)| type_.clone());
+            let tuple = Type::Tuple(types.collect());
+            let ptr = cx.realloc(canonical::alignment(&tuple), canonical::size(&tuple))?;
+            cx.store(ptr, &Value::Tuple(args.to_vec()), &tuple)?;
+            flat.push(ptr.into());
+        }
+
+        let args = zip(flat, core_type.params())
+            .map(|(bits, type_)| canonical::from_flat(bits, type_))
+            .collect::<Vec<_>>();
+        let results = cx.caller.call(self.func, &args)?;
+
+        let result = match &self.type_.result {
+            Some(type_) => {
+                let flat = results.iter().map(canonical::to_flat).collect::<Vec<_>>();
+                Some(match result_fits {
+                    true => cx.lift_flat(&mut flat.iter(), type_)?,
+                    false => {
+                        let ptr = flat[0] as u32;
+                        cx.check_pointer(ptr, type_)?;
+                        cx.load(ptr, type_)?
+                    }
+                })
+            }
+            None => None,
+        };
+
+        if let Some(post_return) = self.options.post_return {
+            cx.caller.call(post_return, &results)?;
+        }
+
+        // The borrow handles lowered for the call must be dropped by it.
+        if cx.table().borrows != borrows {
+            return Err(Trap::BorrowsRemain.into());
+        }
+
+        Ok(result)
+    }
+}
+
+impl ComponentInstance {
+    /// Returns the function exported as `name`, or as `func` by the instance
+    /// exported as `instance` if `name` is `instance#func`.
+    pub(crate) fn export_func(&self, name: &str) -> Option<&ComponentFunc> {
+        let export = match name.split_once('#') {
+            Some((instance, name)) => match self.exports.get(instance)? {
+                Export::Instance(exports) => exports.get(name)?,
+                _ => return None,
+            },
+            None => self.exports.get(name)?,
+        };
+
+        match export {
+            Export::Func(func) => Some(func),
+            _ => None,
+        }
+    }
+}
+
+impl Store {
+    /// Defines the host function `func`, of type `type_`, which components
+    /// import as `name`, or as the export `name` of the instance they import
+    /// as `instance`.
+    ///
+    /// The function gets arguments that match the parameters of `type_`, and
+    /// must return a result that matches its result. Like host functions
+    /// of modules, it must be defined before the components that import it
+    /// are instantiated.
+    pub fn define_component_func(
+        &mut self,
+        instance: Option<&str>,
+        name: &str,
+        type_: &FuncType,
+        func: impl Fn(&mut Caller<'_>, &[Value]) -> Result<Option<Value>, Unwind>
+        + Send
+        + Sync
+        + 'static,
+    ) {
+        let func = ComponentFunc::Host {
+            type_: type_.clone(),
+            name: name.to_owned(),
+            func: Arc::new(func),
+        };
+
+        self.define_component_item(instance, name, HostItem::Func(func));
+    }
+
+    /// Defines a resource type, which components import as `name`, or as the
+    /// export `name` of the instance they import as `instance`, and returns
+    /// it.
+    ///
+    /// The host chooses the representation of the resources of the type,
+    /// and `dtor` gets it when a component drops an owning handle.
+    pub fn define_resource(
+        &mut self,
+        instance: Option<&str>,
+        name: &str,
+        dtor: impl Fn(&mut Caller<'_>, u32) -> Result<(), Unwind> + Send + Sync + 'static,
+    ) -> ResourceType {
+        self.code.resource_types.push(ResourceTypeDef {
+            instance: None,
+            dtor: Some(Dtor::Host(Arc::new(dtor))),
+        });
+
+        let type_ = ResourceType(self.code.resource_types.len() as u32 - 1);
+        self.define_component_item(instance, name, HostItem::Resource(type_));
+        type_
+    }
+
+    fn define_component_item(&mut self, instance: Option<&str>, name: &str, item: HostItem) {
+        let items = match instance {
+            Some(instance) => {
+                let instance = self
+                    .component_imports
+                    .entry(instance.to_owned())
+                    .or_insert_with(|| HostItem::Instance(HashMap::new()));
+
+                match instance {
+                    HostItem::Instance(items) => items,
+                    _ => panic!("`{instance:?}` is not an instance"),
+                }
+            }
+            None => &mut self.component_imports,
+        };
+
+        items.insert(name.to_owned(), item);
+    }
+
+    /// Returns the resource type exported by `instance` as `name`, which
+    /// identifies the resources it defines.
+    pub fn component_resource(
+        &self,
+        instance: ComponentInstanceAddr,
+        name: &str,
+    ) -> Option<ResourceType> {
+        match self.code.components[instance.0 as usize]
+            .exports
+            .get(name)?
+        {
+            Export::Resource(type_) => Some(*type_),
+            _ => None,
+        }
+    }
+
+    /// Returns the item that components import as `name`, or as the export
+    /// `name` of the instance they import as `instance`.
+    fn component_import(&self, instance: Option<&str>, name: &str) -> Option<&HostItem> {
+        match instance {
+            Some(instance) => match self.component_imports.get(instance)? {
+                HostItem::Instance(items) => items.get(name),
+                _ => None,
+            },
+            None => self.component_imports.get(name),
+        }
+    }
+
+    /// Instantiates `component`, whose imports are the items defined by the
+    /// host, and returns the address of its instance.
+    ///
+    /// Its core modules are instantiated in the order of the component, and
+    /// their start functions are run.
+    pub fn instantiate_component(
+        &mut self,
+        component: &Component,
+    ) -> Result<ComponentInstanceAddr, InstantiationError> {
+        let data = &*component.data;
+
+        // The handle table of the instance has the same index.
+        let addr = self.code.components.len() as u32;
+        self.code.components.push(ComponentInstance::default());
+        self.state.handles.push(canonical::HandleTable::default());
+
+        // The resource types defined by the component are created along
+        // with their definition.
+        let mut resources = data
+            .resources
+            .iter()
+            .map(|decl| match decl {
+                ResourceDecl::Import { instance, name } => {
+                    match self.component_import(instance.as_deref(), name) {
+                        Some(HostItem::Resource(type_)) => Ok(type_.0),
+                        _ => Err(unlinkable("unknown import", instance.as_deref(), name)),
+                    }
+                }
+                ResourceDecl::Defined | ResourceDecl::Abstract => Ok(u32::MAX),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The addresses of the core items of the component, by sort.
+        let mut core = HashMap::<CoreSort, Vec<u32>>::new();
+        let mut core_instances = Vec::new();
+        let mut funcs = Vec::new();
+        let mut instances = Vec::new();
+        let mut exports = HashMap::new();
+
+        // The core functions that the component makes are exported by none
+        // of its core instances.
+        let host_instance = self.code.add_instance([]).0;
+
+        for def in &data.defs {
+            match def {
+                Def::CoreInstantiate { module, args } => {
+                    let args = args
+                        .iter()
+                        .map(|(name, index)| (name.clone(), core_instances[*index as usize]))
+                        .collect();
+                    let instance =
+                        self.instantiate_with(&data.modules[*module as usize], Some(&args))?;
+                    core_instances.push(instance);
+                }
+
+                Def::CoreInstance(items) => {
+                    let items = items.iter().map(|(name, sort, index)| {
+                        (name.clone(), *sort, core[sort][*index as usize])
+                    });
+                    let items = items.collect::<Vec<_>>();
+                    core_instances.push(self.code.add_instance(items));
+                }
+
+                Def::CoreAlias {
+                    instance,
+                    name,
+                    sort,
+                } => {
+                    let instance = core_instances[*instance as usize];
+                    let addr = self.code.export_addr(instance, name).unwrap();
+                    core.entry(*sort).or_default().push(addr);
+                }
+
+                Def::ImportFunc { name, type_ } => {
+                    let type_ = type_.resolve(&resources);
+                    funcs.push(self.import_func(None, name, &type_)?);
+                }
+
+                Def::ImportInstance { name, funcs: types } => {
+                    if !matches!(
+                        self.component_imports.get(name),
+                        Some(HostItem::Instance(_))
+                    ) {
+                        return Err(unlinkable("unknown import", None, name));
+                    }
+
+                    let mut instance = HashMap::new();
+                    for (export, type_) in types {
+                        let type_ = type_.resolve(&resources);
+                        let func = self.import_func(Some(name), export, &type_)?;
+                        instance.insert(export.clone(), Export::Func(func));
+                    }
+
+                    instances.push(instance);
+                }
+
+                Def::Alias { instance, name } => match &instances[*instance as usize][name] {
+                    Export::Func(func) => funcs.push(func.clone()),
+                    Export::Instance(instance) => instances.push(instance.clone()),
+                    Export::Resource(_) => {
+                        unreachable!("resource types are not aliased at run time")
+                    }
+                },
+
+                Def::Instance(items) => {
+                    let items = items.iter().map(|(name, item)| {
+                        (name.clone(), export(*item, &funcs, &instances, &resources))
+                    });
+                    instances.push(items.collect());
+                }
+
+                Def::Export { name, item } => {
+                    let export = export(*item, &funcs, &instances, &resources);
+                    match &export {
+                        Export::Func(func) => funcs.push(func.clone()),
+                        Export::Instance(instance) => instances.push(instance.clone()),
+                        Export::Resource(_) => {}
+                    }
+
+                    exports.insert(name.clone(), export);
+                }
+
+                Def::Resource { resource, dtor } => {
+                    self.code.resource_types.push(ResourceTypeDef {
+                        instance: Some(addr),
+                        dtor: dtor.map(|dtor| Dtor::Core(core[&CoreSort::Func][dtor as usize])),
+                    });
+                    resources[*resource as usize] = self.code.resource_types.len() as u32 - 1;
+                }
+
+                Def::Lift {
+                    core_func,
+                    type_,
+                    options,
+                } => {
+                    funcs.push(ComponentFunc::Lifted(Arc::new(Lifted {
+                        func: core[&CoreSort::Func][*core_func as usize],
+                        type_: type_.resolve(&resources),
+                        options: options.resolve(&core),
+                        instance: addr,
+                    })));
+                }
+
+                Def::Lower { func, options } => {
+                    let func: &ComponentFunc = &funcs[*func as usize];
+                    let type_ = canonical::core_type(func.type_(), true);
+                    let name = func.name(self);
+                    let host = canonical::lower(func.clone(), options.resolve(&core), addr);
+
+                    let func = self.code.add_host_func(&type_, &name, host_instance, host);
+                    core.entry(CoreSort::Func).or_default().push(func);
+                }
+
+                Def::ResourceNew(resource)
+                | Def::ResourceRep(resource)
+                | Def::ResourceDrop(resource) => {
+                    let type_ = ResourceType(resources[*resource as usize]);
+                    let (name, host) = match def {
+                        Def::ResourceNew(_) => {
+                            ("resource.new", canonical::resource_new(type_, addr))
+                        }
+                        Def::ResourceRep(_) => {
+                            ("resource.rep", canonical::resource_rep(type_, addr))
+                        }
+                        _ => ("resource.drop", canonical::resource_drop(type_, addr)),
+                    };
+                    let i32 = ValType::NumType(NumType::I32);
+                    let core_type = match def {
+                        Def::ResourceDrop(_) => types::FuncType::new([i32], []),
+                        _ => types::FuncType::new([i32], [i32]),
+                    };
+
+                    let func = self
+                        .code
+                        .add_host_func(&core_type, name, host_instance, host);
+                    core.entry(CoreSort::Func).or_default().push(func);
+                }
+            }
+        }
+
+        self.code.components[addr as usize].exports = exports;
+        Ok(ComponentInstanceAddr(addr))
+    }
+
+    /// Returns the function imported as `name`, or as the export `name` of
+    /// the instance imported as `instance`, which must have type `type_`.
+    fn import_func(
+        &self,
+        instance: Option<&str>,
+        name: &str,
+        type_: &FuncType,
+    ) -> Result<ComponentFunc, InstantiationError> {
+        match self.component_import(instance, name) {
+            Some(HostItem::Func(func)) if func.type_() == type_ => Ok(func.clone()),
+            Some(HostItem::Func(_)) => Err(unlinkable("incompatible import type", instance, name)),
+            _ => Err(unlinkable("unknown import", instance, name)),
+        }
+    }
+}
+
+/// Returns the export of `item`, given the functions, instances and resource
+/// types of the component instance.
+fn export(
+    item: Item,
+    funcs: &[ComponentFunc],
+    instances: &[HashMap<String, Export>],
+    resources: &[u32],
+) -> Export {
+    match item {
+        Item::Func(index) => Export::Func(funcs[index as usize].clone()),
+        Item::Instance(index) => Export::Instance(instances[index as usize].clone()),
+        Item::Resource(index) => Export::Resource(ResourceType(resources[index as usize])),
+    }
+}
+
+fn unlinkable(reason: &str, instance: Option<&str>, name: &str) -> InstantiationError {
+    InstantiationError::Unlinkable(match instance {
+        Some(instance) => format!("{reason} `{instance}` `{name}`"),
+        None => format!("{reason} `{name}`"),
+    })
+}
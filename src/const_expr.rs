@@ -0,0 +1,293 @@
+//! Constant expressions.
+//!
+//! They compute the initial value of globals and the elements of element
+//! segments, as well as the offset of active segments. They are evaluated
+//! once, when the module is instantiated, so they are not lowered.
+//!
+//! Besides constants, they can read immutable globals, and add, subtract and
+//! multiply integers, as allowed by the extended constant expressions
+//! proposal. They can also allocate structures and arrays, and convert
+//! references, as allowed by the GC proposal.
+
+use std::iter;
+
+use wasmbin::{
+    instructions::{GC, Instruction, SIMD},
+    types::FieldType,
+};
+
+use crate::{
+    cell::{AnyRef, Cell, FromCell},
+    gc::{self, Object},
+    interpreter::Trap,
+    lower::{Result, invalid, object_ref, unpacked},
+    module::Context,
+    store::{Code, Instance, State},
+    types::{HeapType, Mut, NumType, RefType, ValType, VecType},
+};
+
+const I32: ValType = ValType::NumType(NumType::I32);
+
+/// Checks that `expr` is a constant expression that evaluates to a value of
+/// type `expected`.
+///
+/// Only the first `globals` globals of the module can be read by the
+/// expression.
+pub(crate) fn validate(
+    expr: &[Instruction],
+    expected: ValType,
+    context: &Context,
+    globals: usize,
+) -> Result<()> {
+    let mut operands = Vec::new();
+
+    for instr in expr {
+        let type_ = match instr {
+            Instruction::I32Const(_) => ValType::NumType(NumType::I32),
+            Instruction::I64Const(_) => ValType::NumType(NumType::I64),
+            Instruction::F32Const(_) => ValType::NumType(NumType::F32),
+            Instruction::F64Const(_) => ValType::NumType(NumType::F64),
+            Instruction::SIMD(SIMD::V128Const(_)) => ValType::VecType(VecType::V128),
+            Instruction::RefNull(type_) => ValType::RefType(context.ref_null(type_)?),
+
+            Instruction::RefFunc(func) => {
+                let Some(type_) = context.funcs.get(func.index as usize) else {
+                    invalid!("unknown function {}", func.index);
+                };
+
+                ValType::RefType(RefType::non_nullable(HeapType::Type(type_.index)))
+            }
+
+            Instruction::GlobalGet(global) => {
+                let Some(type_) = context.globals[..globals].get(global.index as usize) else {
+                    invalid!("unknown global {}", global.index);
+                };
+
+                if type_.mut_ == Mut::Var {
+                    invalid!("constant expression required");
+                }
+
+                type_.val_type
+            }
+
+            Instruction::I32Add | Instruction::I32Sub | Instruction::I32Mul => {
+                pop_operands(&mut operands, &[I32; 2], context)?;
+                I32
+            }
+
+            Instruction::I64Add | Instruction::I64Sub | Instruction::I64Mul => {
+                let type_ = ValType::NumType(NumType::I64);
+                pop_operands(&mut operands, &[type_; 2], context)?;
+                type_
+            }
+
+            Instruction::GC(GC::StructNew(type_)) => {
+                let fields = context.struct_type(*type_)?;
+                let fields: Vec<_> = fields.iter().map(unpacked).collect();
+                pop_operands(&mut operands, &fields, context)?;
+                object_ref(*type_, false)
+            }
+
+            Instruction::GC(GC::StructNewDefault(type_)) => {
+                let fields = context.struct_type(*type_)?;
+                if !fields.iter().all(|field| unpacked(field).is_defaultable()) {
+                    invalid!("type mismatch: structure with a non-defaultable field");
+                }
+
+                object_ref(*type_, false)
+            }
+
+            Instruction::GC(GC::ArrayNew(type_)) => {
+                let elem = unpacked(context.array_type(*type_)?);
+                pop_operands(&mut operands, &[elem, I32], context)?;
+                object_ref(*type_, false)
+            }
+
+            Instruction::GC(GC::ArrayNewDefault(type_)) => {
+                if !unpacked(context.array_type(*type_)?).is_defaultable() {
+                    invalid!("type mismatch: array of a non-defaultable type");
+                }
+
+                pop_operands(&mut operands, &[I32], context)?;
+                object_ref(*type_, false)
+            }
+
+            Instruction::GC(GC::ArrayNewFixed { ty, len }) => {
+                let elem = unpacked(context.array_type(*ty)?);
+                if operands.len() < *len as usize {
+                    invalid!("type mismatch in constant expression");
+                }
+
+                pop_operands(&mut operands, &vec![elem; *len as usize], context)?;
+                object_ref(*ty, false)
+            }
+
+            Instruction::GC(GC::RefI31) => {
+                pop_operands(&mut operands, &[I32], context)?;
+                ValType::RefType(RefType::non_nullable(HeapType::I31))
+            }
+
+            Instruction::GC(GC::AnyConvertExtern) => {
+                convert(&mut operands, RefType::EXTERNREF, HeapType::Any, context)?
+            }
+
+            Instruction::GC(GC::ExternConvertAny) => {
+                convert(&mut operands, RefType::ANYREF, HeapType::Extern, context)?
+            }
+
+            _ => invalid!("constant expression required"),
+        };
+
+        operands.push(type_);
+    }
+
+    match operands.as_slice() {
+        [type_] if context.matches(*type_, expected) => Ok(()),
+        [_] | [] => invalid!("type mismatch in constant expression"),
+        _ => invalid!("constant expression required"),
+    }
+}
+
+/// Evaluates the constant expression `expr`, which was validated.
+///
+/// The indices used by `expr` are resolved in `instance`, and the
+/// structures and arrays it allocates are added to the heap of `state`.
+pub(crate) fn eval(
+    expr: &[Instruction],
+    instance: &Instance,
+    code: &Code,
+    state: &mut State,
+) -> Result<Cell, Trap> {
+    let mut operands = Vec::new();
+
+    // Pops the `len` last operands.
+    let pop = |operands: &mut Vec<Cell>, len: usize| operands.split_off(operands.len() - len);
+
+    for instr in expr {
+        let val = match instr {
+            Instruction::I32Const(val) => Cell::from(*val),
+            Instruction::I64Const(val) => Cell::from(*val),
+            Instruction::F32Const(val) => Cell::from(val.value),
+            Instruction::F64Const(val) => Cell::from(val.value),
+            Instruction::SIMD(SIMD::V128Const(bytes)) => Cell::from(u128::from_le_bytes(*bytes)),
+            Instruction::RefNull(_) => Cell::NULL,
+            Instruction::RefFunc(func) => Cell::from(instance.funcs[func.index as usize]),
+            Instruction::GlobalGet(global) => {
+                state.globals[instance.globals[global.index as usize] as usize].value
+            }
+
+            Instruction::I32Add => binary(&mut operands, i32::wrapping_add),
+            Instruction::I32Sub => binary(&mut operands, i32::wrapping_sub),
+            Instruction::I32Mul => binary(&mut operands, i32::wrapping_mul),
+            Instruction::I64Add => binary(&mut operands, i64::wrapping_add),
+            Instruction::I64Sub => binary(&mut operands, i64::wrapping_sub),
+            Instruction::I64Mul => binary(&mut operands, i64::wrapping_mul),
+
+            Instruction::GC(GC::StructNew(type_)) => {
+                let type_ = instance.types[type_.index as usize];
+                let fields = code.struct_type(type_);
+                let vals = pop(&mut operands, fields.len());
+                let fields = iter::zip(fields, vals).map(|(field, val)| pack(field, val));
+                alloc(state, AnyRef::Struct, type_, fields.collect())
+            }
+
+            Instruction::GC(GC::StructNewDefault(type_)) => {
+                let type_ = instance.types[type_.index as usize];
+                let fields = code.struct_type(type_).iter();
+                let fields = fields.map(|field| gc::default(&field.storage_type));
+                alloc(state, AnyRef::Struct, type_, fields.collect())
+            }
+
+            Instruction::GC(GC::ArrayNew(type_) | GC::ArrayNewDefault(type_)) => {
+                let type_ = instance.types[type_.index as usize];
+                let elem = code.array_type(type_);
+                let len = u32::from_cell(operands.pop().unwrap());
+                let val = match instr {
+                    Instruction::GC(GC::ArrayNew(_)) => pack(elem, operands.pop().unwrap()),
+                    _ => gc::default(&elem.storage_type),
+                };
+
+                if len > gc::MAX_ELEMENTS {
+                    return Err(Trap::AllocationTooLarge);
+                }
+
+                let elems = iter::repeat_n(val, len as usize);
+                alloc(state, AnyRef::Array, type_, elems.collect())
+            }
+
+            Instruction::GC(GC::ArrayNewFixed { ty, len }) => {
+                let type_ = instance.types[ty.index as usize];
+                let elem = code.array_type(type_);
+                let vals = pop(&mut operands, *len as usize);
+                let elems = vals.into_iter().map(|val| pack(elem, val));
+                alloc(state, AnyRef::Array, type_, elems.collect())
+            }
+
+            Instruction::GC(GC::RefI31) => {
+                let val = u32::from_cell(operands.pop().unwrap());
+                Cell::from_any(AnyRef::I31(val))
+            }
+
+            // Conversions leave the cell of the reference as it is.
+            Instruction::GC(GC::AnyConvertExtern | GC::ExternConvertAny) => continue,
+
+            instr => unreachable!("non constant instruction: `{instr:?}`"),
+        };
+
+        operands.push(val);
+    }
+
+    Ok(operands.pop().unwrap())
+}
+
+// Pops the operands of an instruction, which must be of type `types`.
+fn pop_operands(operands: &mut Vec<ValType>, types: &[ValType], context: &Context) -> Result<()> {
+    for type_ in types.iter().rev() {
+        match operands.pop() {
+            Some(operand) if context.matches(operand, *type_) => {}
+            _ => invalid!("type mismatch in constant expression"),
+        }
+    }
+
+    Ok(())
+}
+
+// Pops a reference of type `from`, and returns the type of its conversion to
+// the hierarchy of `to`.
+fn convert(
+    operands: &mut Vec<ValType>,
+    from: RefType,
+    to: HeapType,
+    context: &Context,
+) -> Result<ValType> {
+    match operands.pop() {
+        Some(ValType::RefType(type_)) if context.matches_ref(type_, from) => {
+            Ok(ValType::RefType(RefType {
+                nullable: type_.nullable,
+                heap_type: to,
+            }))
+        }
+        _ => invalid!("type mismatch in constant expression"),
+    }
+}
+
+// Wraps `val` to the size of a field of type `field`.
+fn pack(field: &FieldType, val: Cell) -> Cell {
+    gc::pack(&field.storage_type, val)
+}
+
+// Allocates an object of type `type_`, and returns a reference of kind `kind`
+// to it.
+fn alloc(state: &mut State, kind: fn(u64) -> AnyRef, type_: u32, fields: Box<[Cell]>) -> Cell {
+    let ref_ = state.heap.alloc(Object { type_, fields });
+
+    Cell::from_any(kind(ref_))
+}
+
+// Pops the two operands of a binary instruction, and returns its result.
+fn binary<T: FromCell + Into<Cell>>(operands: &mut Vec<Cell>, op: fn(T, T) -> T) -> Cell {
+    let rhs = T::from_cell(operands.pop().unwrap());
+    let lhs = T::from_cell(operands.pop().unwrap());
+
+    op(lhs, rhs).into()
+}
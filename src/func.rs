@@ -0,0 +1,270 @@
+//! Statically typed handles to exported functions.
+//!
+//! A [`TypedFunc`] checks the signature of a function once, when it is looked
+//! up, so that calling it converts the arguments and results without any
+//! check or allocation.
+
+use std::{
+    fmt::{self, Display},
+    marker::PhantomData,
+};
+
+use crate::{
+    cell::{Cell, FromCell},
+    interpreter::{Interpreter, Trap, Unwind},
+    store::{Func, StoreId},
+    types::{Binary, NumType, RefType, ValType, VecType},
+    values::{ExternAddr, FuncAddr},
+};
+
+/// An exported function, whose parameters are `Params` and whose results are
+/// `Results`.
+///
+/// It is obtained with [`Interpreter::get_typed_func`], and can only be
+/// called with an interpreter of the same store.
+pub struct TypedFunc<'store, Params, Results> {
+    store: StoreId,
+    func: &'store Func,
+    _signature: PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params, Results> Clone for TypedFunc<'_, Params, Results> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Params, Results> Copy for TypedFunc<'_, Params, Results> {}
+
+impl<'store, Params, Results> TypedFunc<'store, Params, Results>
+where
+    Params: WasmTyList,
+    Results: WasmTyList,
+{
+    /// Wraps `func`, a function of `store` whose type is `type_`, if it
+    /// matches `Params` and `Results`.
+    pub(crate) fn new(
+        store: StoreId,
+        func: &'store Func,
+        type_: &wasmbin::types::FuncType,
+    ) -> Result<Self, FuncError> {
+        let params = type_.params.iter().map(ValType::from_binary);
+        let results = type_.results.iter().map(ValType::from_binary);
+
+        if !params.eq(Params::types()) || !results.eq(Results::types()) {
+            return Err(FuncError::TypeMismatch);
+        }
+
+        Ok(TypedFunc {
+            store,
+            func,
+            _signature: PhantomData,
+        })
+    }
+
+    /// Calls the function with `params`.
+    ///
+    /// Traps with [`Trap::StoreMismatch`] if `interpreter` runs another
+    /// store than the one the function was looked up in.
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter<'store>,
+        params: Params,
+    ) -> Result<Results, Unwind> {
+        if interpreter.code().id != self.store {
+            return Err(Trap::StoreMismatch.into());
+        }
+
+        let results = interpreter.call(self.func, |args| params.store(args))?;
+
+        Ok(Results::load(results))
+    }
+}
+
+/// The error returned when a typed function can't be looked up.
+#[derive(Debug, PartialEq)]
+pub enum FuncError {
+    /// No function is exported with this name.
+    Unknown(String),
+    /// The function doesn't have the requested signature.
+    TypeMismatch,
+}
+
+impl Display for FuncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuncError::Unknown(name) => write!(f, "unknown function `{name}`"),
+            FuncError::TypeMismatch => write!(f, "function signature mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for FuncError {}
+
+/// A Rust type that represents a WebAssembly value.
+///
+/// `v128` is represented by `u128`, whose lanes are in little-endian order.
+/// `funcref` and `externref` are represented by `Option<FuncAddr>` and
+/// `Option<ExternAddr>`, `None` being the null reference.
+pub trait WasmTy: private::WasmTy {}
+
+/// A list of [`WasmTy`]s, used for the parameters and the results of a
+/// [`TypedFunc`].
+///
+/// It is implemented for tuples of up to 8 values, and for single values.
+pub trait WasmTyList: private::WasmTyList {}
+
+mod private {
+    use crate::{cell::Cell, types::ValType};
+
+    pub trait WasmTy: Copy {
+        const TYPE: ValType;
+
+        fn into_cell(self) -> Cell;
+
+        fn from_cell(cell: Cell) -> Self;
+    }
+
+    pub trait WasmTyList {
+        fn types() -> impl Iterator<Item = ValType>;
+
+        /// Writes the values to `cells`, which has one cell per value.
+        fn store(self, cells: &mut [Cell]);
+
+        /// Reads the values from `cells`, which has one cell per value.
+        fn load(cells: &[Cell]) -> Self;
+    }
+}
+
+macro_rules! impl_wasm_ty_num {
+    ($( $ty:ty => $num_type:ident ),* $(,)?) => {
+        $(
+            impl WasmTy for $ty {}
+
+            impl private::WasmTy for $ty {
+                const TYPE: ValType = ValType::NumType(NumType::$num_type);
+
+                #[inline(always)]
+                fn into_cell(self) -> Cell {
+                    Cell::from(self)
+                }
+
+                #[inline(always)]
+                fn from_cell(cell: Cell) -> Self {
+                    <$ty as FromCell>::from_cell(cell)
+                }
+            }
+        )*
+    };
+}
+
+impl_wasm_ty_num! {
+    i32 => I32,
+    u32 => I32,
+    i64 => I64,
+    u64 => I64,
+    f32 => F32,
+    f64 => F64,
+}
+
+impl WasmTy for u128 {}
+
+impl private::WasmTy for u128 {
+    const TYPE: ValType = ValType::VecType(VecType::V128);
+
+    #[inline(always)]
+    fn into_cell(self) -> Cell {
+        Cell::from(self)
+    }
+
+    #[inline(always)]
+    fn from_cell(cell: Cell) -> Self {
+        <u128 as FromCell>::from_cell(cell)
+    }
+}
+
+macro_rules! impl_wasm_ty_ref {
+    ($( $addr:ident => $ref_type:ident ),* $(,)?) => {
+        $(
+            impl WasmTy for Option<$addr> {}
+
+            impl private::WasmTy for Option<$addr> {
+                const TYPE: ValType = ValType::RefType(RefType::$ref_type);
+
+                #[inline(always)]
+                fn into_cell(self) -> Cell {
+                    match self {
+                        Some($addr(addr)) => Cell::from(addr),
+                        None => Cell::NULL,
+                    }
+                }
+
+                #[inline(always)]
+                fn from_cell(cell: Cell) -> Self {
+                    (cell != Cell::NULL).then(|| $addr(<u32 as FromCell>::from_cell(cell)))
+                }
+            }
+        )*
+    };
+}
+
+impl_wasm_ty_ref! {
+    FuncAddr => FUNCREF,
+    ExternAddr => EXTERNREF,
+}
+
+impl<T: WasmTy> WasmTyList for T {}
+
+impl<T: WasmTy> private::WasmTyList for T {
+    fn types() -> impl Iterator<Item = ValType> {
+        [T::TYPE].into_iter()
+    }
+
+    #[inline(always)]
+    fn store(self, cells: &mut [Cell]) {
+        cells[0] = self.into_cell();
+    }
+
+    #[inline(always)]
+    fn load(cells: &[Cell]) -> Self {
+        T::from_cell(cells[0])
+    }
+}
+
+macro_rules! impl_wasm_ty_list {
+    ($( ($( $ty:ident $idx:tt ),*) )*) => {
+        $(
+            impl<$( $ty: WasmTy ),*> WasmTyList for ($( $ty, )*) {}
+
+            impl<$( $ty: WasmTy ),*> private::WasmTyList for ($( $ty, )*) {
+                fn types() -> impl Iterator<Item = ValType> {
+                    [$( $ty::TYPE ),*].into_iter()
+                }
+
+                #[inline(always)]
+                #[allow(unused_variables)]
+                fn store(self, cells: &mut [Cell]) {
+                    $( cells[$idx] = self.$idx.into_cell(); )*
+                }
+
+                #[inline(always)]
+                #[allow(unused_variables, clippy::unused_unit)]
+                fn load(cells: &[Cell]) -> Self {
+                    ($( $ty::from_cell(cells[$idx]), )*)
+                }
+            }
+        )*
+    };
+}
+
+impl_wasm_ty_list! {
+    ()
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+}
@@ -0,0 +1,238 @@
+//! The heap of the GC proposal, which holds the structures and arrays
+//! allocated by the code.
+//!
+//! References to them are cells tagged with their kind (see [`AnyRef`]),
+//! whose low bits are an address of the heap. The heap is collected by a
+//! tracing collector whose roots are given by the interpreter: the operand
+//! stack, which holds the locals of every frame, the globals, the tables, the
+//! element segments and the payloads of the exceptions.
+//!
+//! Cells don't carry their type, so the collector is conservative: a number
+//! that looks like a reference to a live object keeps it alive. Collections
+//! only happen when the code allocates, which is when references held by the
+//! host become invalid, unless they are stored in a global or a table.
+//!
+//! The addresses of collected objects are reused, so a reference also holds
+//! the generation of its address, which changes each time the object at the
+//! address is collected. A reference that the host kept after its object was
+//! collected is then told apart from one to the object that reuses the
+//! address, and is rejected when the host passes it back.
+//!
+//! [`AnyRef`]: crate::cell::AnyRef
+
+use std::ops::{Index, IndexMut};
+
+use wasmbin::types::{StorageType, ValueType};
+
+use crate::{
+    cell::{Cell, FromCell},
+    values::{ArrayAddr, Ref, StructAddr, Val},
+};
+
+/// The maximum number of elements of an array.
+///
+/// The limit is not part of the specification, it prevents a module from
+/// allocating an unreasonable amount of memory.
+pub(crate) const MAX_ELEMENTS: u32 = 10_000_000;
+
+/// The number of cells that can be allocated before the first collection.
+const MIN_THRESHOLD: usize = 1 << 20;
+
+/// A structure or an array.
+#[derive(Debug)]
+pub(crate) struct Object {
+    /// The address of the type of the object in the store.
+    pub(crate) type_: u32,
+    /// The fields of a structure, or the elements of an array. Packed fields
+    /// hold their value zero-extended.
+    pub(crate) fields: Box<[Cell]>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Heap {
+    /// The objects, by address, which are `None` once collected.
+    objects: Vec<Option<Object>>,
+    /// The generation of each address.
+    generations: Vec<u32>,
+    /// The addresses of the collected objects, which are reused.
+    free: Vec<u32>,
+    /// The number of cells allocated since the last collection, headers
+    /// included.
+    allocated: usize,
+    /// The number of allocated cells after which the heap is collected.
+    threshold: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap {
+            objects: Vec::new(),
+            generations: Vec::new(),
+            free: Vec::new(),
+            allocated: 0,
+            threshold: MIN_THRESHOLD,
+        }
+    }
+}
+
+impl Heap {
+    /// Adds `object` to the heap, and returns a reference to it, whose low
+    /// 32 bits are its address, and whose high bits are the generation of
+    /// the address.
+    pub(crate) fn alloc(&mut self, object: Object) -> u64 {
+        self.allocated += object.fields.len() + 1;
+
+        let addr = match self.free.pop() {
+            Some(addr) => {
+                self.objects[addr as usize] = Some(object);
+                addr
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.generations.push(0);
+                self.objects.len() as u32 - 1
+            }
+        };
+
+        u64::from(self.generations[addr as usize]) << 32 | u64::from(addr)
+    }
+
+    /// Returns the object at `addr`, unless it was collected.
+    pub(crate) fn get(&self, addr: u32) -> Option<&Object> {
+        self.objects.get(addr as usize)?.as_ref()
+    }
+
+    /// Returns the object that the reference `ref_` refers to, unless it was
+    /// collected.
+    pub(crate) fn get_ref(&self, ref_: u64) -> Option<&Object> {
+        let addr = ref_ as u32;
+        let generation = self.generations.get(addr as usize)?;
+
+        self.get(addr)
+            .filter(|_| *generation == (ref_ >> 32) as u32)
+    }
+
+    /// Whether `val` refers to an object that was collected, which only the
+    /// references held by the host can.
+    pub(crate) fn is_collected(&self, val: &Val) -> bool {
+        match val {
+            Val::Ref(Ref::Struct(StructAddr(ref_)) | Ref::Array(ArrayAddr(ref_))) => {
+                self.get_ref(*ref_).is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether enough was allocated since the last collection for the heap to
+    /// be collected before the next allocation.
+    pub(crate) fn needs_collection(&self) -> bool {
+        self.allocated >= self.threshold
+    }
+
+    /// Frees the objects that are not reachable from `roots`.
+    pub(crate) fn collect(&mut self, roots: impl IntoIterator<Item = Cell>) {
+        let mut marked = vec![false; self.objects.len()];
+        let mut pending = Vec::new();
+
+        for root in roots {
+            pending.push(root);
+
+            while let Some(cell) = pending.pop() {
+                let Some(addr) = cell.object() else {
+                    continue;
+                };
+
+                let Some(Some(object)) = self.objects.get(addr as usize) else {
+                    continue;
+                };
+
+                if !marked[addr as usize] {
+                    marked[addr as usize] = true;
+                    pending.extend_from_slice(&object.fields);
+                }
+            }
+        }
+
+        let mut live = 0;
+        for (addr, (object, marked)) in self.objects.iter_mut().zip(marked).enumerate() {
+            match object {
+                Some(object) if marked => live += object.fields.len() + 1,
+                Some(_) => {
+                    *object = None;
+                    self.generations[addr] = self.generations[addr].wrapping_add(1);
+                    self.free.push(addr as u32);
+                }
+                None => {}
+            }
+        }
+
+        // The heap is collected again once it doubled.
+        self.allocated = 0;
+        self.threshold = MIN_THRESHOLD.max(live);
+    }
+}
+
+impl Index<u32> for Heap {
+    type Output = Object;
+
+    fn index(&self, addr: u32) -> &Object {
+        self.objects[addr as usize]
+            .as_ref()
+            .expect("reference to a collected object")
+    }
+}
+
+impl IndexMut<u32> for Heap {
+    fn index_mut(&mut self, addr: u32) -> &mut Object {
+        self.objects[addr as usize]
+            .as_mut()
+            .expect("reference to a collected object")
+    }
+}
+
+/// Returns the default value of a field of type `type_`.
+pub(crate) fn default(type_: &StorageType) -> Cell {
+    match type_ {
+        StorageType::Value(ValueType::Ref(_)) => Cell::NULL,
+        _ => Cell::default(),
+    }
+}
+
+/// Wraps `val` to the size of a field of type `type_`, if it is packed.
+pub(crate) fn pack(type_: &StorageType, val: Cell) -> Cell {
+    match type_ {
+        StorageType::I8 => Cell::from(u32::from_cell(val) & 0xff),
+        StorageType::I16 => Cell::from(u32::from_cell(val) & 0xffff),
+        StorageType::Value(_) => val,
+    }
+}
+
+/// Extends the value `val` of a packed field of type `type_` to an `i32`.
+pub(crate) fn unpack(type_: &StorageType, val: Cell, signed: bool) -> Cell {
+    match type_ {
+        StorageType::I8 if signed => Cell::from(i32::from(u32::from_cell(val) as i8)),
+        StorageType::I16 if signed => Cell::from(i32::from(u32::from_cell(val) as i16)),
+        _ => val,
+    }
+}
+
+/// Returns the size of a field of type `type_` in a data segment, in bytes.
+pub(crate) fn size(type_: &StorageType) -> usize {
+    match type_ {
+        StorageType::I8 => 1,
+        StorageType::I16 => 2,
+        StorageType::Value(ValueType::I32 | ValueType::F32) => 4,
+        StorageType::Value(ValueType::I64 | ValueType::F64) => 8,
+        StorageType::Value(ValueType::V128) => 16,
+        StorageType::Value(ValueType::Ref(_)) => unreachable!("reference in a data segment"),
+    }
+}
+
+/// Reads the fields of type `type_` stored in `bytes`, in little-endian order.
+pub(crate) fn read(type_: &StorageType, bytes: &[u8]) -> impl Iterator<Item = Cell> {
+    bytes.chunks_exact(size(type_)).map(|field| {
+        let mut bits = [0; 16];
+        bits[..field.len()].copy_from_slice(field);
+        Cell::from(u128::from_le_bytes(bits))
+    })
+}
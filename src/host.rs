@@ -0,0 +1,128 @@
+//! Functions implemented by the host, which modules import.
+//!
+//! Host functions are defined with [`Store::define_func`], and are exported
+//! by an instance that the store registers under the name of their module,
+//! so that they are imported like any other function.
+//!
+//! [`Store::define_func`]: crate::store::Store::define_func
+
+use std::{
+    fmt::{self, Debug},
+    iter::zip,
+    sync::Arc,
+};
+
+use wasmbin::sections::ExportDesc;
+
+use crate::{
+    cell::Cell,
+    interpreter::{Engine, Interpreter, Trap, Unwind},
+    memory::Memory,
+    store::{Code, State},
+    types::{Binary, ValType},
+    values::Val,
+};
+
+/// The implementation of a host function, which gets its arguments and
+/// returns its results, or stops the execution by trapping or exiting.
+pub(crate) type HostFn = dyn Fn(&mut Caller<'_>, &[Val]) -> Result<Vec<Val>, Unwind> + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct HostFunc(pub(crate) Arc<HostFn>);
+
+impl Debug for HostFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HostFunc")
+    }
+}
+
+/// The context in which a host function is called.
+pub struct Caller<'a> {
+    pub(crate) code: &'a Code,
+    pub(crate) state: &'a mut State,
+    /// The instance of the function that called the host function, which is
+    /// `None` when the host function is called directly.
+    pub(crate) instance: Option<u32>,
+    /// The live part of the operand stack of the interpreter, whose
+    /// references must survive the calls made by the host function.
+    pub(crate) stack: &'a [Cell],
+    /// The fuel left to the interpreter, which the calls made by the host
+    /// function consume.
+    pub(crate) fuel: &'a mut u64,
+    pub(crate) engine: Engine,
+}
+
+impl Caller<'_> {
+    /// Returns the memory exported as `name` by the instance of the calling
+    /// function.
+    pub fn memory(&mut self, name: &str) -> Option<CallerMemory<'_>> {
+        let instance = &self.code.instances[self.instance? as usize];
+        let ExportDesc::Mem(memory) = instance.export(name)? else {
+            return None;
+        };
+
+        let memory = &mut self.state.memories[instance.memories[memory.index as usize] as usize];
+
+        Some(CallerMemory(memory))
+    }
+
+    /// Runs the function at `func` with `args`, which must match its
+    /// parameters, and returns its results.
+    pub(crate) fn call(&mut self, func: u32, args: &[Val]) -> Result<Vec<Val>, Unwind> {
+        let code = self.code;
+        let func = &code.funcs[func as usize];
+        let type_ = &code[func.type_];
+
+        let pinned = self.state.pinned.len();
+        self.state.pinned.extend_from_slice(self.stack);
+
+        let mut interpreter = Interpreter::nested(code, self.state, self.engine, *self.fuel);
+        let results = interpreter
+            .call(func, |params| {
+                for (param, arg) in zip(params, args) {
+                    *param = Cell::from_val(*arg);
+                }
+            })
+            .map(|results| {
+                zip(results, &type_.results)
+                    .map(|(cell, result)| cell.to_val(ValType::from_binary(result), code))
+                    .collect()
+            });
+        *self.fuel = interpreter.fuel().unwrap();
+        drop(interpreter);
+
+        self.state.pinned.truncate(pinned);
+
+        results
+    }
+}
+
+/// A memory of the instance of a function that called a host function.
+///
+/// Its contents are copied rather than borrowed, since the ones of a shared
+/// memory may be modified by other threads at any time.
+pub struct CallerMemory<'a>(&'a mut Memory);
+
+impl CallerMemory<'_> {
+    /// Returns the size of the memory, in bytes.
+    pub fn len(&self) -> u64 {
+        self.0.len()
+    }
+
+    /// Returns whether the memory is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the bytes starting at `addr` to `buf`, or fails if they are out
+    /// of bounds.
+    pub fn read(&self, addr: u64, buf: &mut [u8]) -> Result<(), Trap> {
+        self.0.read_bytes(addr, buf)
+    }
+
+    /// Copies `bytes` to the memory, starting at `addr`, or fails if they
+    /// are out of bounds.
+    pub fn write(&mut self, addr: u64, bytes: &[u8]) -> Result<(), Trap> {
+        self.0.write_bytes(addr, bytes)
+    }
+}
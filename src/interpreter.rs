@@ -1,380 +1,1982 @@
-#![allow(unused)]
-
 use std::{
-    array,
-    cmp::PartialOrd,
-    convert::{From, TryFrom},
-    fmt::Debug,
-    iter,
-    mem::take,
-    ops::{Add, Sub},
-};
-
-use wasmbin::{
-    indices::{FuncId, LocalId},
-    instructions::{self, Instruction},
-    sections::{FuncBody, Locals},
-    types::{BlockType, ValueType},
+    fmt::{self, Debug, Display},
+    iter::{self, zip},
+    sync::atomic::{self, Ordering},
 };
 
 use crate::{
-    store::Store,
-    values::{self, Val},
+    cell::{AnyRef, Cell, FromCell},
+    component::{ComponentInstanceAddr, Value},
+    func::{FuncError, TypedFunc, WasmTyList},
+    gc::{self, Object},
+    host::Caller,
+    ir::{Branch, Op},
+    memory, numeric,
+    register::RegOp,
+    simd,
+    store::{Code, Exn, Func, InstanceAddr, State, Store},
+    types::{AddrType, Binary, ValType},
+    values::{ExnAddr, FuncAddr, TagAddr, Val},
 };
 
-pub type Result<'store, T = ComputationStatus<'store>, E = Trap> = std::result::Result<T, E>;
+pub type Result<T, E = Trap> = std::result::Result<T, E>;
+
+/// The maximum number of nested calls.
+///
+/// Like the other limits of the interpreter, it is not part of the
+/// specification: running out of it traps instead of exhausting the memory of
+/// the host.
+const MAX_FRAMES: usize = 100_000;
+
+/// The maximum size of the operand stack, in cells.
+const MAX_STACK: usize = 1 << 24;
 
 #[derive(Debug, PartialEq)]
 pub enum EvaluationStatus {
     Value(Vec<Val>),
+    /// A host function ended the program with an exit code, like WASI's
+    /// `proc_exit`.
+    Exited(i32),
+    /// An exception was thrown and not caught.
+    Exception(Exception),
     // Interrupted
 }
 
-pub enum ComputationStatus<'store> {
-    ContinueToNext,
-    Call(&'store FuncBody),
-    ContinueToElse,
-    ContinueToEnd,
-    Return,
-    // TODO: interruption.
+/// An exception that was not caught.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exception {
+    /// The tag the exception was thrown with, which can be compared to the
+    /// ones returned by [`Store::tag`].
+    pub tag: TagAddr,
+    pub payload: Vec<Val>,
 }
 
-#[derive(Debug)]
-pub struct Trap;
+/// Displayed as `uncaught exception of tag 0: (i32.const 42)`.
+impl Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "uncaught exception of tag {}", self.tag.0)?;
+
+        for (idx, val) in self.payload.iter().enumerate() {
+            let separator = if idx == 0 { ":" } else { "" };
+            write!(f, "{separator} {val}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Why the execution of a function was aborted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trap {
+    /// An `unreachable` instruction was executed.
+    Unreachable,
+    MemoryOutOfBounds,
+    TableOutOfBounds,
+    /// An indirect call used an index out of the bounds of its table.
+    UndefinedElement,
+    /// An indirect call used a null reference.
+    UninitializedElement,
+    IndirectCallTypeMismatch,
+    IntegerDivideByZero,
+    IntegerOverflow,
+    /// A NaN was truncated to an integer.
+    InvalidConversionToInteger,
+    /// The call stack or the operand stack exceeded its limit.
+    StackOverflow,
+    /// The interpreter ran out of fuel.
+    OutOfFuel,
+    /// The address of an atomic access is not a multiple of its size.
+    UnalignedAtomic,
+    /// A thread tried to wait on a memory that is not shared.
+    ExpectedSharedMemory,
+    /// A `throw_ref` was given a null reference.
+    NullExceptionReference,
+    /// A `call_ref` or `return_call_ref` was given a null reference.
+    NullFunctionReference,
+    /// A `ref.as_non_null` was given a null reference.
+    NullReference,
+    /// A structure instruction was given a null reference.
+    NullStructReference,
+    /// An array instruction was given a null reference.
+    NullArrayReference,
+    /// An `i31.get_s` or `i31.get_u` was given a null reference.
+    NullI31Reference,
+    ArrayOutOfBounds,
+    /// A `ref.cast` was given a reference of another type.
+    CastFailure,
+    /// An array larger than the limit of the interpreter was allocated.
+    AllocationTooLarge,
+    /// A component gave a variant whose discriminant is not one of its
+    /// cases.
+    InvalidDiscriminant,
+    /// A component gave a `char` that is not a Unicode scalar value.
+    InvalidChar,
+    /// A component gave a string that is not valid UTF-8.
+    InvalidUtf8,
+    /// A component gave a pointer that is not aligned for the values it
+    /// points to.
+    UnalignedPointer,
+    /// A component gave a handle that is not in its table, or that is of
+    /// another resource type.
+    InvalidHandle,
+    /// A component dropped or gave away a resource that is still borrowed.
+    ResourceBorrowed,
+    /// A component function returned before dropping the borrowed handles
+    /// it was given.
+    BorrowsRemain,
+    /// A typed function was called with the interpreter of another store.
+    StoreMismatch,
+    /// The host passed a reference to a structure or an array that was
+    /// collected.
+    CollectedReference,
+}
+
+/// Displayed as the messages of the specification tests.
+impl Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Trap::Unreachable => "unreachable",
+            Trap::MemoryOutOfBounds => "out of bounds memory access",
+            Trap::TableOutOfBounds => "out of bounds table access",
+            Trap::UndefinedElement => "undefined element",
+            Trap::UninitializedElement => "uninitialized element",
+            Trap::IndirectCallTypeMismatch => "indirect call type mismatch",
+            Trap::IntegerDivideByZero => "integer divide by zero",
+            Trap::IntegerOverflow => "integer overflow",
+            Trap::InvalidConversionToInteger => "invalid conversion to integer",
+            Trap::StackOverflow => "call stack exhausted",
+            Trap::OutOfFuel => "all fuel consumed",
+            Trap::UnalignedAtomic => "unaligned atomic",
+            Trap::ExpectedSharedMemory => "expected shared memory",
+            Trap::NullExceptionReference => "null exception reference",
+            Trap::NullFunctionReference => "null function reference",
+            Trap::NullReference => "null reference",
+            Trap::NullStructReference => "null structure reference",
+            Trap::NullArrayReference => "null array reference",
+            Trap::NullI31Reference => "null i31 reference",
+            Trap::ArrayOutOfBounds => "out of bounds array access",
+            Trap::CastFailure => "cast failure",
+            Trap::AllocationTooLarge => "allocation too large",
+            Trap::InvalidDiscriminant => "invalid variant discriminant",
+            Trap::InvalidChar => "invalid char",
+            Trap::InvalidUtf8 => "invalid utf-8",
+            Trap::UnalignedPointer => "unaligned pointer",
+            Trap::InvalidHandle => "unknown handle index",
+            Trap::ResourceBorrowed => "resource is borrowed",
+            Trap::BorrowsRemain => "borrow handles remain at the end of the call",
+            Trap::StoreMismatch => "function of another store",
+            Trap::CollectedReference => "reference to a collected object",
+        })
+    }
+}
+
+impl std::error::Error for Trap {}
+
+/// Why a function stopped before returning.
+///
+/// Traps and exits unwind all the frames being executed, and only traps are
+/// failures. Exceptions unwind the frames up to the `try_table` that catches
+/// them, if any.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unwind {
+    Trap(Trap),
+    /// A host function ended the program with an exit code.
+    Exit(i32),
+    /// An exception was thrown, by the code or by a host function that
+    /// rethrows an exception it was given a reference to.
+    Exception(ExnAddr),
+}
+
+impl Display for Unwind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unwind::Trap(trap) => write!(f, "{trap}"),
+            Unwind::Exit(code) => write!(f, "exited with code {code}"),
+            Unwind::Exception(ExnAddr(addr)) => write!(f, "uncaught exception {addr}"),
+        }
+    }
+}
+
+impl std::error::Error for Unwind {}
+
+impl From<Trap> for Unwind {
+    fn from(trap: Trap) -> Unwind {
+        Unwind::Trap(trap)
+    }
+}
+
+/// A function that was being executed when a trap happened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BacktraceFrame<'store> {
+    pub func: FuncAddr,
+    /// The name of the function, from the name section of its module or
+    /// from its exports.
+    pub name: Option<&'store str>,
+}
+
+/// The strategy used to run functions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Engine {
+    /// Runs the lowered ops on an operand stack.
+    #[default]
+    Stack,
+    /// Runs functions translated to a register machine.
+    ///
+    /// This engine is experimental: it is not faster than the stack engine
+    /// on every program yet, and is never used unless it is requested.
+    /// Functions that can't be translated are run by the stack engine.
+    Register,
+}
 
 pub struct Interpreter<'store> {
-    pub instr_stack: Vec<(usize, &'store FuncBody)>,
+    /// The callers of the function that is being executed.
+    instr_stack: Vec<Frame<'store>>,
     runner: InstructionRunner<'store>,
+    engine: Engine,
+    /// Whether the executed ops are printed.
+    trace: bool,
+    /// Whether `runner.fuel` is limited, rather than virtually infinite.
+    metered: bool,
+    /// The functions that were being executed when the last trap happened,
+    /// innermost first.
+    backtrace: Vec<&'store Func>,
 }
 
 #[derive(Debug)]
 struct InstructionRunner<'store> {
-    stack: Vec<Val>,
-    locals: Vec<Frame>,
-    pub store: &'store Store,
+    /// The operand stack, whose live part ends at `sp`.
+    ///
+    /// The locals of each frame are stored on this stack too, right before
+    /// their operands. The module is validated, so the type of the values
+    /// is statically known and isn't stored.
+    stack: Vec<Cell>,
+    sp: usize,
+    code: &'store Code,
+    state: &'store mut State,
+    /// The fuel left, which is consumed by calls and backward jumps.
+    fuel: u64,
 }
 
-impl Interpreter<'_> {
-    /// Creates a new [`Interpreter`].
+impl<'store> Interpreter<'store> {
+    /// Creates a new [`Interpreter`], which uses the default [`Engine`].
     pub fn new(store: &mut Store) -> Interpreter<'_> {
+        Interpreter::with_engine(store, Engine::default())
+    }
+
+    /// Creates a new [`Interpreter`] that runs functions with `engine`.
+    pub fn with_engine(store: &mut Store, engine: Engine) -> Interpreter<'_> {
         Interpreter {
             instr_stack: Vec::new(),
             runner: InstructionRunner::new(store),
+            engine,
+            trace: false,
+            metered: false,
+            backtrace: Vec::new(),
         }
     }
 
+    /// Creates an interpreter that runs functions on behalf of a host
+    /// function, with the `fuel` left to the interpreter that called it.
+    pub(crate) fn nested(
+        code: &'store Code,
+        state: &'store mut State,
+        engine: Engine,
+        fuel: u64,
+    ) -> Interpreter<'store> {
+        Interpreter {
+            instr_stack: Vec::new(),
+            runner: InstructionRunner {
+                stack: Vec::new(),
+                sp: 0,
+                code,
+                state,
+                fuel,
+            },
+            engine,
+            trace: false,
+            metered: true,
+            backtrace: Vec::new(),
+        }
+    }
+
+    /// Limits the execution to `fuel` units of fuel, or removes the limit.
+    ///
+    /// Each call and each backward jump, such as a loop iteration, consumes
+    /// one unit. Running out of fuel traps.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.metered = fuel.is_some();
+        self.runner.fuel = fuel.unwrap_or(u64::MAX);
+    }
+
+    /// Returns the fuel left, if it is limited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.metered.then_some(self.runner.fuel)
+    }
+
+    /// Prints each executed op to the standard error, indented by call
+    /// depth.
+    ///
+    /// Tracing runs every function with the stack engine.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Returns the functions that were being executed when the last trap
+    /// happened, innermost first.
+    pub fn backtrace(&self) -> impl ExactSizeIterator<Item = BacktraceFrame<'store>> {
+        let code = self.runner.code;
+
+        self.backtrace.iter().map(move |func| BacktraceFrame {
+            func: FuncAddr(code.func_addr(func)),
+            name: func.name.as_deref(),
+        })
+    }
+
     pub fn run(
         &mut self,
         func_name: &str,
         args: impl IntoIterator<Item = Val>,
     ) -> Result<EvaluationStatus, Trap> {
-        let (func_type, func_body) = self.runner.store.find_function(func_name);
-        let func_type = &self.runner.store[*func_type];
+        let func = self.runner.code.find_function(func_name);
+        self.invoke(func, args.into_iter().collect())
+    }
 
-        let args = args.into_iter().collect::<Vec<_>>();
+    /// Runs the function exported by `instance` as `func_name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instance` doesn't export a function named `func_name`.
+    pub fn run_in(
+        &mut self,
+        instance: InstanceAddr,
+        func_name: &str,
+        args: impl IntoIterator<Item = Val>,
+    ) -> Result<EvaluationStatus, Trap> {
+        let func = self
+            .runner
+            .code
+            .exported_function(instance, func_name)
+            .unwrap_or_else(|| panic!("unknown function `{func_name}`"));
 
-        assert_eq!(func_type.params.len(), args.len());
+        self.invoke(func, args.into_iter().collect())
+    }
 
-        self.instr_stack.push((0, func_body));
+    /// Runs the function exported by the component instance `instance` as
+    /// `func_name`, or as `func_name` by the instance it exports as
+    /// `instance_name`, when `func_name` is `instance_name#func_name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instance` doesn't export such a function, or if `args`
+    /// don't match its parameters.
+    pub fn run_component(
+        &mut self,
+        instance: ComponentInstanceAddr,
+        func_name: &str,
+        args: &[Value],
+    ) -> Result<Option<Value>, Unwind> {
+        let InstructionRunner {
+            code, state, fuel, ..
+        } = &mut self.runner;
+        let func = code.components[instance.0 as usize]
+            .export_func(func_name)
+            .unwrap_or_else(|| panic!("unknown function `{func_name}`"));
+
+        let mut caller = Caller {
+            code,
+            state,
+            instance: None,
+            stack: &[],
+            fuel,
+            engine: self.engine,
+        };
 
-        self.runner
-            .push_frame(args, &func_body.locals, &func_type.results);
+        func.call(&mut caller, args)
+    }
 
-        loop {
-            let (cursor, func) = match self.instr_stack.last_mut() {
-                Some((cursor, func)) => (cursor, func as &FuncBody),
-                None => return Ok(EvaluationStatus::Value(take(&mut self.runner.stack))),
-            };
+    /// Looks up the function exported as `func_name` by the last instance of
+    /// the store, and checks once that its signature is `Params -> Results`.
+    ///
+    /// ```
+    /// # use exwasmable::{interpreter::Interpreter, module::Module, store::Store};
+    /// let wasm = wat::parse_str(
+    ///     r#"(module (func (export "add") (param i32 i32) (result i32)
+    ///         (i32.add (local.get 0) (local.get 1))))"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let module = Module::from_bytes(&wasm).unwrap();
+    /// let mut store = Store::new(&module).unwrap();
+    /// let mut interpreter = Interpreter::new(&mut store);
+    ///
+    /// let add = interpreter.get_typed_func::<(i32, i32), i32>("add").unwrap();
+    /// assert_eq!(add.call(&mut interpreter, (41, 1)).unwrap(), 42);
+    /// ```
+    pub fn get_typed_func<Params, Results>(
+        &self,
+        func_name: &str,
+    ) -> Result<TypedFunc<'store, Params, Results>, FuncError>
+    where
+        Params: WasmTyList,
+        Results: WasmTyList,
+    {
+        let code = self.runner.code;
+        let instance = code.last_instance();
+        let func = code
+            .exported_function(instance, func_name)
+            .ok_or_else(|| FuncError::Unknown(func_name.to_owned()))?;
 
-            if *cursor >= func.expr.len() {
-                self.runner.return_from_func();
-                self.instr_stack.pop();
-                continue;
-            }
+        TypedFunc::new(code.id, func, &code[func.type_])
+    }
 
-            let status = match &func.expr[*cursor] {
-                Instruction::LocalGet(local) => self.runner.local_get(local),
-                Instruction::I32Add => self.runner.add::<i32>(),
-                Instruction::Call(func) => self.runner.call(*func),
-                Instruction::I32Const(val) => self.runner.const_::<i32>(*val),
-                Instruction::I32LtU => self.runner.lt_u::<i32>(),
-                Instruction::IfStart(block_type) => self.runner.if_start(block_type),
-                Instruction::IfElse => self.runner.if_else(),
-                Instruction::I32Sub => self.runner.sub::<i32>(),
-                Instruction::End => self.runner.end(),
-                Instruction::LocalSet(local) => self.runner.local_set(*local),
-                Instruction::Return => self.runner.return_(),
-
-                unknown => unreachable!("unknown instruction: `{unknown:?}`"),
-            }?;
-
-            *cursor += 1;
-            match status {
-                ComputationStatus::ContinueToNext => {}
-
-                ComputationStatus::Call(func) => self.instr_stack.push((0, func)),
-
-                ComputationStatus::ContinueToElse => {
-                    *cursor = Self::continue_to(func, Instruction::IfElse, *cursor);
-                }
+    pub(crate) fn code(&self) -> &'store Code {
+        self.runner.code
+    }
 
-                ComputationStatus::ContinueToEnd => {
-                    *cursor = Self::continue_to(func, Instruction::End, *cursor);
-                }
+    /// Runs `func` with `args`, which must match its parameters.
+    pub(crate) fn invoke(
+        &mut self,
+        func: &'store Func,
+        args: Vec<Val>,
+    ) -> Result<EvaluationStatus> {
+        let code = self.runner.code;
+        let type_ = &code[func.type_];
+
+        assert_eq!(func.params, args.len());
+
+        // Values lose their type when they enter the stack, so this is the
+        // last time it can be checked.
+        let heap = &self.runner.state.heap;
+        if args.iter().any(|arg| heap.is_collected(arg)) {
+            return Err(Trap::CollectedReference);
+        }
+        let args = zip(args, &type_.params)
+            .map(|(arg, param)| {
+                assert!(code.has_type(&arg, ValType::from_binary(param), heap));
+                Cell::from_val(arg)
+            })
+            .collect::<Vec<_>>();
 
-                ComputationStatus::Return => {
-                    self.instr_stack.pop().unwrap();
-                }
+        let results = self.call(func, |params| {
+            for (param, arg) in zip(params, args) {
+                *param = arg;
             }
-        }
-    }
+        });
+
+        let results = match results {
+            Ok(results) => results,
+            Err(Unwind::Trap(trap)) => return Err(trap),
+            Err(Unwind::Exit(code)) => return Ok(EvaluationStatus::Exited(code)),
+            Err(Unwind::Exception(ExnAddr(addr))) => {
+                let exn = &self.runner.state.exceptions[addr as usize];
+                let type_ = code.tag_type(exn.tag);
+                let payload = zip(&exn.payload, &type_.params)
+                    .map(|(cell, param)| cell.to_val(ValType::from_binary(param), code))
+                    .collect();
+
+                return Ok(EvaluationStatus::Exception(Exception {
+                    tag: TagAddr(exn.tag),
+                    payload,
+                }));
+            }
+        };
 
-    fn continue_to(func: &FuncBody, instr: Instruction, mut cursor: usize) -> usize {
-        let mut depth = 0;
+        // Lowering checked that the function leaves its results on the
+        // stack, a mismatch here is a bug of the engine.
+        assert_eq!(results.len(), type_.results.len());
 
-        loop {
-            match &func.expr[cursor] {
-                instr_ if depth == 0 && instr_ == &instr => {
-                    break cursor + 1;
-                }
+        let values = zip(results, &type_.results)
+            .map(|(cell, result)| cell.to_val(ValType::from_binary(result), code))
+            .collect();
 
-                Instruction::BlockStart(_) | Instruction::IfStart(_) => depth += 1,
+        Ok(EvaluationStatus::Value(values))
+    }
 
-                Instruction::End => depth -= 1,
+    /// Runs `func`, whose arguments are written by `write_args`, and returns
+    /// its results.
+    ///
+    /// The arguments must match the parameters of `func`, which is not
+    /// checked.
+    pub(crate) fn call(
+        &mut self,
+        func: &'store Func,
+        write_args: impl FnOnce(&mut [Cell]),
+    ) -> Result<&[Cell], Unwind> {
+        self.runner.reserve(func.params)?;
+        write_args(&mut self.runner.stack[..func.params]);
+        self.runner.sp = func.params;
+
+        self.runner.reserve(func.max_height)?;
+
+        let InstructionRunner { stack, sp, .. } = &mut self.runner;
+        let fp;
+        (fp, *sp) = push_frame(stack, *sp, func);
+
+        let result = match (self.engine, &func.registers) {
+            _ if self.trace => self.execute::<true>(func, fp),
+            (Engine::Register, Some(registers)) => self.execute_registers(func, registers, fp),
+            _ => self.execute::<false>(func, fp),
+        };
 
-                _ => {}
-            }
+        let results = self.runner.sp;
 
-            cursor += 1;
+        // Nothing survives a trap or an exit, but the frames that were being
+        // executed when trapping are kept for the backtrace.
+        if result.is_err() {
+            self.backtrace.clear();
         }
-    }
-}
 
-impl<'store> InstructionRunner<'store> {
-    fn new(store: &'store mut Store) -> InstructionRunner<'store> {
-        Self {
-            locals: Vec::new(),
-            stack: Vec::new(),
-            store,
+        if let Err(Unwind::Trap(_)) = result {
+            let frames = self.instr_stack.iter().rev();
+            self.backtrace.extend(frames.map(|frame| frame.func));
         }
+
+        self.runner.sp = 0;
+        self.instr_stack.clear();
+
+        result.map(|()| &self.runner.stack[..results])
     }
 
-    fn push_frame(
+    /// Runs `func` until it returns.
+    ///
+    /// The frame of `func`, which starts at `fp`, must already be pushed.
+    ///
+    /// When `TRACE` is set, each op is printed before being executed.
+    fn execute<const TRACE: bool>(
         &mut self,
-        arguments: impl IntoIterator<Item = Val>,
-        other_locals: &[Locals],
-        result: &[ValueType],
-    ) {
-        let locals = arguments
-            .into_iter()
-            .chain(other_locals.iter().flat_map(|Locals { repeat, ty }| {
-                iter::repeat_n(Val::default(ty), *repeat as usize)
-            }))
-            .collect();
+        mut func: &'store Func,
+        mut fp: usize,
+    ) -> Result<(), Unwind> {
+        let base = self.instr_stack.len();
+        let engine = self.engine;
+
+        let instr_stack = &mut self.instr_stack;
+        let InstructionRunner {
+            stack: stack_vec,
+            sp: saved_sp,
+            code: store,
+            state,
+            fuel,
+        } = &mut self.runner;
+
+        // Working on a slice rather than on the vector lets the compiler keep
+        // its pointer and length in registers.
+        let mut stack = &mut stack_vec[..];
+
+        let mut code = &*func.code;
+        let mut pc = 0;
+        let mut sp = *saved_sp;
+
+        // Ops stop the execution by breaking out of this block, so that the
+        // frame that trapped can be saved for the backtrace.
+        let unwind = 'trap: {
+            macro_rules! fail {
+                ($unwind:expr) => {
+                    break 'trap Unwind::from($unwind)
+                };
+            }
 
-        let frame = Frame {
-            init_stack_size: self.stack.len(),
-            locals,
-            arity: result.len(),
-        };
+            macro_rules! check {
+                ($result:expr) => {
+                    match $result {
+                        Ok(val) => val,
+                        Err(trap) => fail!(trap),
+                    }
+                };
+            }
 
-        self.locals.push(frame);
-    }
+            // Calls and backward jumps consume fuel, so that any long running
+            // code does.
+            macro_rules! jump {
+                ($target:expr) => {{
+                    let target = $target as usize;
 
-    fn pop_frame(&mut self) {
-        self.locals.pop().unwrap();
-    }
+                    if target < pc {
+                        *fuel = check!(fuel.checked_sub(1).ok_or(Trap::OutOfFuel));
+                    }
 
-    fn local_get(&mut self, local: &LocalId) -> Result<'store> {
-        let val = self.local_value(*local);
-        self.stack.push(val);
+                    pc = target;
+                }};
+            }
 
-        Ok(ComputationStatus::ContinueToNext)
-    }
+            macro_rules! push {
+                ($val:expr) => {{
+                    stack[sp] = Cell::from($val);
+                    sp += 1;
+                }};
+            }
 
-    fn run_const(&mut self, val: Val) -> Result<'store> {
-        self.stack.push(val);
+            macro_rules! pop {
+                () => {{
+                    sp -= 1;
+                    stack[sp]
+                }};
 
-        Ok(ComputationStatus::ContinueToNext)
-    }
+                ($ty:ty) => {
+                    <$ty>::from_cell(pop!())
+                };
+            }
 
-    fn add<T>(&mut self) -> Result<'store>
-    where
-        T: TryFrom<Val> + Add,
-        <T as TryFrom<Val>>::Error: Debug,
-        Val: From<<T as std::ops::Add>::Output>,
-    {
-        self.binop::<T>(|l, r| Some(Val::from(l + r)))
-    }
+            macro_rules! local {
+                ($ty:ty, $local:expr) => {
+                    <$ty>::from_cell(stack[fp + $local as usize])
+                };
+            }
 
-    fn call(&mut self, func: FuncId) -> Result<'store> {
-        let (type_, function) = &self.store[func];
-        let func_type = &self.store[*type_];
-        let locals = self
-            .stack
-            .drain(self.stack.len() - func_type.params.len()..)
-            .collect();
+            macro_rules! call {
+                ($callee:expr) => {{
+                    let callee = $callee;
+
+                    if instr_stack.len() >= MAX_FRAMES {
+                        fail!(Trap::StackOverflow);
+                    }
+
+                    *fuel = check!(fuel.checked_sub(1).ok_or(Trap::OutOfFuel));
+
+                    if sp + callee.max_height > stack.len() {
+                        check!(reserve(stack_vec, sp + callee.max_height));
+                        stack = &mut stack_vec[..];
+                    }
+                    instr_stack.push(Frame { pc, func, fp });
+                    (fp, sp) = push_frame(stack, sp, callee);
+
+                    func = callee;
+                    code = &func.code;
+                    pc = 0;
+                }};
+            }
+
+            // Tail calls reuse the frame of the caller, which is then gone
+            // from the backtrace.
+            macro_rules! tail_call {
+                ($callee:expr) => {{
+                    let callee: &'store Func = $callee;
+
+                    if callee.is_host() {
+                        // The next op returns once the host function did.
+                        call!(callee);
+                    } else {
+                        *fuel = check!(fuel.checked_sub(1).ok_or(Trap::OutOfFuel));
+
+                        stack.copy_within(sp - callee.params..sp, fp);
+                        sp = fp + callee.params;
+
+                        if sp + callee.max_height > stack.len() {
+                            check!(reserve(stack_vec, sp + callee.max_height));
+                            stack = &mut stack_vec[..];
+                        }
+                        (fp, sp) = push_frame(stack, sp, callee);
+
+                        func = callee;
+                        code = &func.code;
+                        pc = 0;
+                    }
+                }};
+            }
+
+            // Throws the exception at `addr`, which is caught by the
+            // innermost `try_table` around the op that threw it, or around the
+            // call that is waiting on it in one of the callers run by this
+            // loop. Uncaught exceptions stop the execution.
+            macro_rules! throw {
+                ($addr:expr) => {{
+                    let addr: u32 = $addr;
+                    let exn = &mut state.exceptions[addr as usize];
+
+                    let (catch, branch) = loop {
+                        if let Some(clause) = catch_clause(func, pc - 1, exn.tag) {
+                            break clause;
+                        }
+
+                        if instr_stack.len() == base {
+                            fail!(Unwind::Exception(ExnAddr(addr)));
+                        }
+
+                        Frame { pc, func, fp } = instr_stack.pop().unwrap();
+                        code = &func.code;
+                    };
+
+                    sp = fp + branch.height as usize;
+
+                    if let Op::Catch(_) | Op::CatchRef(_) = catch {
+                        stack[sp..sp + exn.payload.len()].copy_from_slice(&exn.payload);
+                        sp += exn.payload.len();
+                    }
+
+                    if let Op::CatchRef(_) | Op::CatchAllRef = catch {
+                        exn.referenced = true;
+                        push!(addr);
+                    } else if !exn.referenced && addr as usize == state.exceptions.len() - 1 {
+                        // Nothing refers to the exception that was just
+                        // thrown, it can be freed.
+                        state.exceptions.pop();
+                    }
+
+                    jump!(branch.pc);
+                    continue;
+                }};
+            }
+
+            // Returns from the current function, whose results are below
+            // `sp`.
+            macro_rules! ret {
+                () => {{
+                    if instr_stack.len() == base {
+                        *saved_sp = sp;
+                        return Ok(());
+                    }
+
+                    Frame { pc, func, fp } = instr_stack.pop().unwrap();
+                    code = &func.code;
+                }};
+            }
+
+            // Pops a function reference, and returns the function it refers
+            // to.
+            macro_rules! ref_callee {
+                () => {{
+                    let callee = pop!();
+                    if callee == Cell::NULL {
+                        fail!(Trap::NullFunctionReference);
+                    }
+
+                    &store.funcs[u32::from_cell(callee) as usize]
+                }};
+            }
+
+            // Pops an index in `table`, and returns the function it refers
+            // to, which must be of type `type_`.
+            macro_rules! indirect_callee {
+                ($type_:expr, $table:expr) => {{
+                    let idx = pop!(u64);
+                    let callee = state.tables[$table as usize].get(idx);
+                    let callee = check!(callee.map_err(|_| Trap::UndefinedElement));
+
+                    if callee == Cell::NULL {
+                        fail!(Trap::UninitializedElement);
+                    }
+
+                    let callee = &store.funcs[u32::from_cell(callee) as usize];
+                    if !store.type_matches(callee.type_.index, $type_) {
+                        fail!(Trap::IndirectCallTypeMismatch);
+                    }
+
+                    callee
+                }};
+            }
+
+            // Pops a reference to a structure or an array, and returns its
+            // address in the heap, or fails with `trap` if it is null.
+            macro_rules! pop_object {
+                ($trap:expr) => {{
+                    let ref_ = pop!();
+                    if ref_ == Cell::NULL {
+                        fail!($trap);
+                    }
+
+                    u32::from_cell(ref_)
+                }};
+            }
+
+            // Allocates an object of type `$type_`, whose fields are given by
+            // an iterator, and pushes a reference to it. The heap is collected
+            // first if needed, while the operands of the op are still on the
+            // stack.
+            macro_rules! alloc {
+                ($kind:ident, $type_:expr, $fields:expr) => {{
+                    let type_ = $type_;
+                    let fields = $fields.collect::<Box<[Cell]>>();
+                    let ref_ = state.heap.alloc(Object { type_, fields });
+                    push!(Cell::from_any(AnyRef::$kind(ref_)));
+                }};
+            }
+
+            loop {
+                let op = code[pc];
 
-        let init_stack_size = self.stack.len();
+                if TRACE {
+                    let depth = instr_stack.len() - base;
+                    eprintln!("{:depth$}{} @ {pc}: {op:?}", "", store.func_name(func));
+                }
 
-        let frame = Frame {
-            locals,
-            init_stack_size,
-            arity: func_type.results.len(),
+                pc += 1;
+
+                match op {
+                    Op::Unreachable => fail!(Trap::Unreachable),
+
+                    Op::Jump(target) => jump!(target),
+
+                    Op::JumpIfZero(target) => {
+                        if pop!(i32) == 0 {
+                            jump!(target);
+                        }
+                    }
+
+                    Op::JumpIfNonZero(target) => {
+                        if pop!(i32) != 0 {
+                            jump!(target);
+                        }
+                    }
+
+                    Op::Br(branch) => {
+                        let target;
+                        (target, sp) = take_branch(stack, fp, sp, branch);
+                        jump!(target);
+                    }
+
+                    Op::BrIf(branch) => {
+                        if pop!(i32) != 0 {
+                            let target;
+                            (target, sp) = take_branch(stack, fp, sp, branch);
+                            jump!(target);
+                        }
+                    }
+
+                    Op::BrOnNull(branch) => {
+                        if stack[sp - 1] == Cell::NULL {
+                            let target;
+                            (target, sp) = take_branch(stack, fp, sp - 1, branch);
+                            jump!(target);
+                        }
+                    }
+
+                    Op::BrOnNonNull(branch) => {
+                        if stack[sp - 1] == Cell::NULL {
+                            sp -= 1;
+                        } else {
+                            let target;
+                            (target, sp) = take_branch(stack, fp, sp, branch);
+                            jump!(target);
+                        }
+                    }
+
+                    Op::BrTable(len) => {
+                        // Out of bounds indices select the default label, which
+                        // is the last one.
+                        let idx = (pop!(i32) as u32).min(len);
+
+                        let Op::BrTableEntry(branch) = code[pc + idx as usize] else {
+                            unreachable!("invalid branch table entry")
+                        };
+
+                        let target;
+                        (target, sp) = take_branch(stack, fp, sp, branch);
+                        jump!(target);
+                    }
+
+                    Op::BrTableEntry(_) => unreachable!("branch table entry executed"),
+
+                    Op::Return => {
+                        sp = move_results(stack, sp, fp, func.results);
+                        ret!();
+                    }
+
+                    Op::ReturnLocal(local) => {
+                        stack[fp] = stack[fp + local as usize];
+                        sp = fp + 1;
+                        ret!();
+                    }
+
+                    Op::Call(callee) => call!(&store.funcs[callee as usize]),
+
+                    Op::CallIndirect { type_, table } => call!(indirect_callee!(type_, table)),
+
+                    Op::ReturnCall(callee) => tail_call!(&store.funcs[callee as usize]),
+
+                    Op::ReturnCallIndirect { type_, table } => {
+                        tail_call!(indirect_callee!(type_, table))
+                    }
+
+                    Op::CallRef => call!(ref_callee!()),
+
+                    Op::ReturnCallRef => tail_call!(ref_callee!()),
+
+                    Op::Host(host) => {
+                        let type_ = &store[func.type_];
+                        let args = zip(&stack[fp..fp + func.params], &type_.params)
+                            .map(|(arg, param)| arg.to_val(ValType::from_binary(param), *store))
+                            .collect::<Vec<_>>();
+
+                        let mut caller = Caller {
+                            code: store,
+                            state,
+                            instance: instr_stack.last().map(|frame| frame.func.instance),
+                            stack: &stack[..sp],
+                            fuel: &mut *fuel,
+                            engine,
+                        };
+                        let results = match (store.hosts[host as usize].0)(&mut caller, &args) {
+                            Err(Unwind::Exception(ExnAddr(addr))) => throw!(addr),
+                            results => check!(results),
+                        };
+
+                        // The host is trusted, but a mistake in its results
+                        // must not go unnoticed.
+                        assert_eq!(results.len(), func.results);
+                        if results.iter().any(|result| state.heap.is_collected(result)) {
+                            fail!(Trap::CollectedReference);
+                        }
+                        for (result, type_) in zip(results, &type_.results) {
+                            let type_ = ValType::from_binary(type_);
+                            assert!(store.has_type(&result, type_, &state.heap));
+                            push!(Cell::from_val(result));
+                        }
+                    }
+
+                    Op::Throw(tag) => {
+                        let len = store.tag_type(tag).params.len();
+                        sp -= len;
+
+                        state.exceptions.push(Exn {
+                            tag,
+                            payload: stack[sp..sp + len].into(),
+                            referenced: false,
+                        });
+                        throw!(state.exceptions.len() as u32 - 1);
+                    }
+
+                    Op::ThrowRef => {
+                        let exn = pop!();
+                        if exn == Cell::NULL {
+                            fail!(Trap::NullExceptionReference);
+                        }
+
+                        throw!(u32::from_cell(exn));
+                    }
+
+                    // The catch clauses are only read when an exception is
+                    // thrown.
+                    Op::TryTable(len) => pc += 2 * len as usize,
+
+                    Op::Catch(_) | Op::CatchRef(_) | Op::CatchAll | Op::CatchAllRef => {
+                        unreachable!("catch clause executed")
+                    }
+
+                    Op::Drop => sp -= 1,
+
+                    Op::Select => {
+                        let cond = pop!(i32);
+                        let rhs = pop!();
+                        let lhs = pop!();
+
+                        push!(if cond != 0 { lhs } else { rhs });
+                    }
+
+                    Op::LocalGet(local) => push!(stack[fp + local as usize]),
+
+                    Op::LocalSet(local) => stack[fp + local as usize] = pop!(),
+
+                    Op::LocalTee(local) => stack[fp + local as usize] = stack[sp - 1],
+
+                    Op::I32Const(val) => push!(val),
+                    Op::I64Const(val) => push!(val),
+                    Op::F32Const(val) => push!(val),
+                    Op::F64Const(val) => push!(val),
+                    Op::V128Const(index) => push!(func.v128s[index as usize]),
+
+                    Op::I8x16Shuffle(index) => {
+                        let rhs = pop!();
+                        let lhs = pop!();
+                        push!(simd::shuffle(func.v128s[index as usize], lhs, rhs));
+                    }
+
+                    Op::RefNull(_) => push!(Cell::NULL),
+                    Op::RefIsNull => {
+                        let is_null = pop!() == Cell::NULL;
+                        push!(i32::from(is_null));
+                    }
+                    Op::RefAsNonNull => {
+                        if stack[sp - 1] == Cell::NULL {
+                            fail!(Trap::NullReference);
+                        }
+                    }
+                    Op::RefFunc(func) => push!(func),
+
+                    Op::RefEq => {
+                        let rhs = pop!();
+                        let lhs = pop!();
+                        push!(i32::from(lhs == rhs));
+                    }
+
+                    Op::RefTest(type_) => {
+                        let ref_ = pop!();
+                        push!(i32::from(store.ref_matches(ref_, type_, &state.heap)));
+                    }
+
+                    Op::RefCast(type_) => {
+                        if !store.ref_matches(stack[sp - 1], type_, &state.heap) {
+                            fail!(Trap::CastFailure);
+                        }
+                    }
+
+                    Op::BrOnCast(type_) | Op::BrOnCastFail(type_) => {
+                        let matches = store.ref_matches(stack[sp - 1], type_, &state.heap);
+
+                        if matches == matches!(op, Op::BrOnCast(_)) {
+                            let Op::BrTableEntry(branch) = code[pc] else {
+                                unreachable!("cast branch without a branch table entry")
+                            };
+
+                            let target;
+                            (target, sp) = take_branch(stack, fp, sp, branch);
+                            jump!(target);
+                        } else {
+                            pc += 1;
+                        }
+                    }
+
+                    Op::RefI31 => {
+                        let val = pop!(u32);
+                        push!(Cell::from_any(AnyRef::I31(val)));
+                    }
+
+                    Op::I31GetS | Op::I31GetU => {
+                        let ref_ = pop!();
+                        if ref_ == Cell::NULL {
+                            fail!(Trap::NullI31Reference);
+                        }
+
+                        let AnyRef::I31(val) = ref_.to_any() else {
+                            unreachable!("`i31.get` of another reference")
+                        };
+
+                        // The value is sign-extended from its 31 bits.
+                        match op {
+                            Op::I31GetS => push!((val << 1) as i32 >> 1),
+                            _ => push!(val),
+                        }
+                    }
+
+                    Op::StructNew(type_) => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let fields = store.struct_type(type_);
+                        sp -= fields.len();
+                        let vals = zip(fields, &stack[sp..sp + fields.len()]);
+                        alloc!(
+                            Struct,
+                            type_,
+                            vals.map(|(field, val)| gc::pack(&field.storage_type, *val))
+                        );
+                    }
+
+                    Op::StructNewDefault(type_) => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let fields = store.struct_type(type_).iter();
+                        alloc!(
+                            Struct,
+                            type_,
+                            fields.map(|field| gc::default(&field.storage_type))
+                        );
+                    }
+
+                    Op::StructGet { field, .. } => {
+                        let addr = pop_object!(Trap::NullStructReference);
+                        push!(state.heap[addr].fields[field as usize]);
+                    }
+
+                    Op::StructGetS { type_, field } | Op::StructGetU { type_, field } => {
+                        let addr = pop_object!(Trap::NullStructReference);
+                        let val = state.heap[addr].fields[field as usize];
+                        let type_ = &store.struct_type(type_)[field as usize].storage_type;
+                        push!(gc::unpack(type_, val, matches!(op, Op::StructGetS { .. })));
+                    }
+
+                    Op::StructSet { type_, field } => {
+                        let val = pop!();
+                        let addr = pop_object!(Trap::NullStructReference);
+                        let type_ = &store.struct_type(type_)[field as usize].storage_type;
+                        state.heap[addr].fields[field as usize] = gc::pack(type_, val);
+                    }
+
+                    Op::ArrayNew(type_) => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let len = pop!(u32);
+                        let val = gc::pack(&store.array_type(type_).storage_type, pop!());
+                        if len > gc::MAX_ELEMENTS {
+                            fail!(Trap::AllocationTooLarge);
+                        }
+
+                        alloc!(Array, type_, iter::repeat_n(val, len as usize));
+                    }
+
+                    Op::ArrayNewDefault(type_) => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let len = pop!(u32);
+                        let val = gc::default(&store.array_type(type_).storage_type);
+                        if len > gc::MAX_ELEMENTS {
+                            fail!(Trap::AllocationTooLarge);
+                        }
+
+                        alloc!(Array, type_, iter::repeat_n(val, len as usize));
+                    }
+
+                    Op::ArrayNewFixed { type_, len } => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let elem = &store.array_type(type_).storage_type;
+                        sp -= len as usize;
+                        let vals = stack[sp..sp + len as usize].iter();
+                        alloc!(Array, type_, vals.map(|val| gc::pack(elem, *val)));
+                    }
+
+                    Op::ArrayNewData { type_, data } => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let elem = &store.array_type(type_).storage_type;
+                        let len = pop!(u32) as usize * gc::size(elem);
+                        let src = pop!(u32) as usize;
+                        let data = &state.datas[data as usize];
+                        if src + len > data.len() {
+                            fail!(Trap::MemoryOutOfBounds);
+                        }
+
+                        alloc!(Array, type_, gc::read(elem, &data[src..src + len]));
+                    }
+
+                    Op::ArrayNewElem { type_, elem } => {
+                        state.collect_garbage(&stack[..sp]);
+
+                        let len = pop!(u32) as usize;
+                        let src = pop!(u32) as usize;
+                        let elems = &state.elems[elem as usize];
+                        if src + len > elems.len() {
+                            fail!(Trap::TableOutOfBounds);
+                        }
+
+                        alloc!(Array, type_, elems[src..src + len].iter().copied());
+                    }
+
+                    Op::ArrayGet(type_) | Op::ArrayGetS(type_) | Op::ArrayGetU(type_) => {
+                        let idx = pop!(u32);
+                        let addr = pop_object!(Trap::NullArrayReference);
+                        let Some(&val) = state.heap[addr].fields.get(idx as usize) else {
+                            fail!(Trap::ArrayOutOfBounds);
+                        };
+
+                        let elem = &store.array_type(type_).storage_type;
+                        push!(gc::unpack(elem, val, matches!(op, Op::ArrayGetS(_))));
+                    }
+
+                    Op::ArraySet(type_) => {
+                        let val = pop!();
+                        let idx = pop!(u32);
+                        let addr = pop_object!(Trap::NullArrayReference);
+                        let Some(field) = state.heap[addr].fields.get_mut(idx as usize) else {
+                            fail!(Trap::ArrayOutOfBounds);
+                        };
+
+                        *field = gc::pack(&store.array_type(type_).storage_type, val);
+                    }
+
+                    Op::ArrayLen => {
+                        let addr = pop_object!(Trap::NullArrayReference);
+                        push!(state.heap[addr].fields.len() as u32);
+                    }
+
+                    Op::ArrayFill(type_) => {
+                        let len = pop!(u32) as usize;
+                        let val = gc::pack(&store.array_type(type_).storage_type, pop!());
+                        let dst = pop!(u32) as usize;
+                        let addr = pop_object!(Trap::NullArrayReference);
+
+                        let fields = &mut state.heap[addr].fields;
+                        if dst + len > fields.len() {
+                            fail!(Trap::ArrayOutOfBounds);
+                        }
+
+                        fields[dst..dst + len].fill(val);
+                    }
+
+                    Op::ArrayCopy => {
+                        let len = pop!(u32) as usize;
+                        let src = pop!(u32) as usize;
+                        let src_addr = pop_object!(Trap::NullArrayReference);
+                        let dst = pop!(u32) as usize;
+                        let dst_addr = pop_object!(Trap::NullArrayReference);
+
+                        let src_len = state.heap[src_addr].fields.len();
+                        if src + len > src_len || dst + len > state.heap[dst_addr].fields.len() {
+                            fail!(Trap::ArrayOutOfBounds);
+                        }
+
+                        if src_addr == dst_addr {
+                            let fields = &mut state.heap[dst_addr].fields;
+                            fields.copy_within(src..src + len, dst);
+                        } else {
+                            let vals = state.heap[src_addr].fields[src..src + len].to_vec();
+                            state.heap[dst_addr].fields[dst..dst + len].copy_from_slice(&vals);
+                        }
+                    }
+
+                    Op::ArrayInitData { type_, data } => {
+                        let elem = &store.array_type(type_).storage_type;
+                        let size = gc::size(elem);
+                        let len = pop!(u32) as usize;
+                        let src = pop!(u32) as usize;
+                        let dst = pop!(u32) as usize;
+                        let addr = pop_object!(Trap::NullArrayReference);
+
+                        let fields = &mut state.heap[addr].fields;
+                        if dst + len > fields.len() {
+                            fail!(Trap::ArrayOutOfBounds);
+                        }
+
+                        let data = &state.datas[data as usize];
+                        if src + len * size > data.len() {
+                            fail!(Trap::MemoryOutOfBounds);
+                        }
+
+                        let vals = gc::read(elem, &data[src..src + len * size]);
+                        for (field, val) in zip(&mut fields[dst..dst + len], vals) {
+                            *field = val;
+                        }
+                    }
+
+                    Op::ArrayInitElem(elem) => {
+                        let len = pop!(u32) as usize;
+                        let src = pop!(u32) as usize;
+                        let dst = pop!(u32) as usize;
+                        let addr = pop_object!(Trap::NullArrayReference);
+
+                        let fields = &mut state.heap[addr].fields;
+                        if dst + len > fields.len() {
+                            fail!(Trap::ArrayOutOfBounds);
+                        }
+
+                        let elems = &state.elems[elem as usize];
+                        if src + len > elems.len() {
+                            fail!(Trap::TableOutOfBounds);
+                        }
+
+                        fields[dst..dst + len].copy_from_slice(&elems[src..src + len]);
+                    }
+
+                    Op::GlobalGet(global) => push!(state.globals[global as usize].value),
+                    Op::GlobalSet(global) => state.globals[global as usize].value = pop!(),
+
+                    Op::TableGet(table) => {
+                        let idx = pop!(u64);
+                        push!(check!(state.tables[table as usize].get(idx)));
+                    }
+
+                    Op::TableSet(table) => {
+                        let val = pop!();
+                        let idx = pop!(u64);
+                        check!(state.tables[table as usize].set(idx, val));
+                    }
+
+                    Op::TableSize(table) => push!(state.tables[table as usize].size()),
+
+                    Op::TableGrow(table) => {
+                        let delta = pop!(u64);
+                        let init = pop!();
+                        let table = &mut state.tables[table as usize];
+                        let size = table.grow(delta, init);
+                        push!(grow_result(table.addr_type(), size.map(u64::from)));
+                    }
+
+                    Op::TableFill(table) => {
+                        let len = pop!(u64);
+                        let val = pop!();
+                        let dst = pop!(u64);
+                        check!(state.tables[table as usize].fill(dst, val, len));
+                    }
+
+                    Op::TableCopy {
+                        dst: dst_table,
+                        src: src_table,
+                    } => {
+                        let len = pop!(u64);
+                        let src = pop!(u64);
+                        let dst = pop!(u64);
+
+                        let tables = &mut state.tables;
+                        if dst_table == src_table {
+                            check!(tables[dst_table as usize].copy_within(dst, src, len));
+                        } else {
+                            let [dst_table, src_table] = tables
+                                .get_disjoint_mut([dst_table as usize, src_table as usize])
+                                .unwrap();
+                            check!(dst_table.init(dst, src_table.elements(), src, len));
+                        }
+                    }
+
+                    Op::TableInit { elem, table } => {
+                        let len = pop!(u64);
+                        let src = pop!(u64);
+                        let dst = pop!(u64);
+                        let elems = &state.elems[elem as usize];
+                        check!(state.tables[table as usize].init(dst, elems, src, len));
+                    }
+
+                    Op::ElemDrop(elem) => state.elems[elem as usize] = Box::default(),
+
+                    Op::MemorySize(memory) => push!(state.memories[memory as usize].size()),
+
+                    Op::MemoryGrow(memory) => {
+                        let delta = pop!(u64);
+                        let memory = &mut state.memories[memory as usize];
+                        let size = memory.grow(delta);
+                        push!(grow_result(memory.addr_type(), size));
+                    }
+
+                    Op::MemoryFill(memory) => {
+                        let len = pop!(u64);
+                        let val = pop!(u32);
+                        let dst = pop!(u64);
+                        check!(state.memories[memory as usize].fill(dst, val as u8, len));
+                    }
+
+                    Op::MemoryCopy {
+                        dst: dst_memory,
+                        src: src_memory,
+                    } => {
+                        let len = pop!(u64);
+                        let src = pop!(u64);
+                        let dst = pop!(u64);
+
+                        let memories = &mut state.memories;
+                        if dst_memory == src_memory {
+                            check!(memories[dst_memory as usize].copy_within(dst, src, len));
+                        } else {
+                            let [dst_memory, src_memory] = memories
+                                .get_disjoint_mut([dst_memory as usize, src_memory as usize])
+                                .unwrap();
+                            check!(memory::copy(dst_memory, dst, src_memory, src, len));
+                        }
+                    }
+
+                    Op::MemoryInit { data, memory } => {
+                        let len = pop!(u32);
+                        let src = pop!(u32);
+                        let dst = pop!(u64);
+                        let data = &state.datas[data as usize];
+                        check!(state.memories[memory as usize].init(dst, data, src, len));
+                    }
+
+                    Op::DataDrop(data) => state.datas[data as usize] = Box::default(),
+
+                    Op::I32AddImm(imm) => {
+                        let val = pop!(i32);
+                        push!(val.wrapping_add(imm));
+                    }
+
+                    Op::I32LocalAddImm { local, imm } => {
+                        let val = local!(i32, local);
+                        push!(val.wrapping_add(imm));
+                    }
+
+                    Op::I32LocalAddImmSet { src, dst, imm } => {
+                        let val = local!(i32, src);
+                        stack[fp + dst as usize] = Cell::from(val.wrapping_add(imm));
+                    }
+
+                    Op::I32LocalCmpImm { local, imm, cmp } => {
+                        let val = local!(i32, local);
+                        push!(i32::from(cmp.eval(val, imm)));
+                    }
+
+                    Op::JumpUnlessLocalCmpImm {
+                        local,
+                        imm,
+                        cmp,
+                        pc: target,
+                    } => {
+                        if !cmp.eval(local!(i32, local), imm) {
+                            jump!(target);
+                        }
+                    }
+
+                    Op::LocalCopy { src, dst } => {
+                        stack[fp + dst as usize] = stack[fp + src as usize];
+                    }
+
+                    // The most common numeric ops are dispatched directly, the
+                    // others go through a second dispatch.
+                    Op::I32Eqz => {
+                        let val = pop!(i32);
+                        push!(i32::from(val == 0));
+                    }
+
+                    Op::I32Add => {
+                        let rhs = pop!(i32);
+                        let lhs = pop!(i32);
+                        push!(lhs.wrapping_add(rhs));
+                    }
+
+                    Op::I32Sub => {
+                        let rhs = pop!(i32);
+                        let lhs = pop!(i32);
+                        push!(lhs.wrapping_sub(rhs));
+                    }
+
+                    Op::I32Mul => {
+                        let rhs = pop!(i32);
+                        let lhs = pop!(i32);
+                        push!(lhs.wrapping_mul(rhs));
+                    }
+
+                    op if op.is_unary() => {
+                        let val = pop!();
+                        push!(check!(numeric::unary(op, val)));
+                    }
+
+                    op if op.is_binary() => {
+                        let rhs = pop!();
+                        let lhs = pop!();
+                        push!(check!(numeric::binary(op, lhs, rhs)));
+                    }
+
+                    op if op.is_ternary() => {
+                        let third = pop!();
+                        let second = pop!();
+                        let first = pop!();
+                        push!(simd::ternary(op, first, second, third));
+                    }
+
+                    op if op.is_load() => {
+                        let addr = pop!(u64);
+                        let memory = &state.memories[op.memarg().memory as usize];
+                        push!(check!(memory::load(op, memory, addr)));
+                    }
+
+                    op if op.is_store() => {
+                        let val = pop!();
+                        let addr = pop!(u64);
+                        let memory = &mut state.memories[op.memarg().memory as usize];
+                        check!(memory::store(op, memory, addr, val));
+                    }
+
+                    op if op.is_lane_load() => {
+                        let val = pop!();
+                        let addr = pop!(u64);
+                        let memory = &state.memories[op.memarg().memory as usize];
+                        push!(check!(memory::load_lane(op, memory, addr, val)));
+                    }
+
+                    op if op.is_lane_store() => {
+                        let val = pop!();
+                        let addr = pop!(u64);
+                        let memory = &mut state.memories[op.memarg().memory as usize];
+                        check!(memory::store_lane(op, memory, addr, val));
+                    }
+
+                    op if op.is_atomic_rmw() => {
+                        let val = pop!();
+                        let addr = pop!(u64);
+                        let memory = &mut state.memories[op.memarg().memory as usize];
+                        push!(check!(memory::atomic_rmw(op, memory, addr, val)));
+                    }
+
+                    op if op.is_atomic_cmpxchg() => {
+                        let replacement = pop!();
+                        let expected = pop!();
+                        let addr = pop!(u64);
+                        let memory = &mut state.memories[op.memarg().memory as usize];
+                        push!(check!(memory::atomic_cmpxchg(
+                            op,
+                            memory,
+                            addr,
+                            expected,
+                            replacement
+                        )));
+                    }
+
+                    Op::MemoryAtomicWait32(memarg) | Op::MemoryAtomicWait64(memarg) => {
+                        let timeout = pop!(i64);
+                        let expected = pop!();
+                        let addr = pop!(u64);
+                        let memory = &state.memories[memarg.memory as usize];
+                        push!(check!(memory::atomic_wait(
+                            op, memory, addr, expected, timeout
+                        )));
+                    }
+
+                    Op::MemoryAtomicNotify(memarg) => {
+                        let count = pop!(u32);
+                        let addr = pop!(u64);
+                        let memory = &state.memories[memarg.memory as usize];
+                        push!(check!(memory::atomic_notify(
+                            memory,
+                            addr,
+                            memarg.offset,
+                            count
+                        )));
+                    }
+
+                    Op::AtomicFence => atomic::fence(Ordering::SeqCst),
+
+                    op => unreachable!("unknown op: `{op:?}`"),
+                }
+            }
         };
 
-        self.locals.push(frame);
+        // The frame that stopped is saved for the backtrace.
+        instr_stack.push(Frame { pc, func, fp });
 
-        Ok(ComputationStatus::Call(function))
+        Err(unwind)
     }
+}
 
-    fn const_<T>(&mut self, val: T) -> Result<'store>
-    where
-        Val: From<T>,
-    {
-        self.stack.push(val.into());
+impl<'store> Interpreter<'store> {
+    /// Runs `func`, whose register code is `registers`, until it returns.
+    ///
+    /// The frame of `func`, which starts at `fp`, must already be pushed.
+    fn execute_registers(
+        &mut self,
+        mut func: &'store Func,
+        mut code: &'store [RegOp],
+        mut fp: usize,
+    ) -> Result<(), Unwind> {
+        let base = self.instr_stack.len();
+        let store = self.runner.code;
+
+        // Working on a slice rather than on the vector lets the compiler keep
+        // its pointer and length in registers.
+        let mut stack = &mut self.runner.stack[..];
+
+        let mut pc = 0;
+
+        // Ops stop the execution by breaking out of this block, so that the
+        // frame that trapped can be saved for the backtrace.
+        let unwind = 'trap: {
+            macro_rules! fail {
+                ($unwind:expr) => {
+                    break 'trap Unwind::from($unwind)
+                };
+            }
 
-        Ok(ComputationStatus::ContinueToNext)
-    }
+            macro_rules! check {
+                ($result:expr) => {
+                    match $result {
+                        Ok(val) => val,
+                        Err(trap) => fail!(trap),
+                    }
+                };
+            }
 
-    fn lt_u<T>(&mut self) -> Result<'store>
-    where
-        T: TryFrom<Val> + PartialOrd,
-        <T as TryFrom<Val>>::Error: Debug,
-    {
-        self.binop::<T>(|lhs, rhs| {
-            let v = if lhs < rhs { 1 } else { 0 };
-            let v = v.into();
-            Some(v)
-        })
-    }
+            macro_rules! consume_fuel {
+                () => {
+                    let fuel = &mut self.runner.fuel;
+                    *fuel = check!(fuel.checked_sub(1).ok_or(Trap::OutOfFuel));
+                };
+            }
 
-    fn if_start(&mut self, block_type: &BlockType) -> Result<'store> {
-        match self.pop::<i32>() {
-            0 => Ok(ComputationStatus::ContinueToElse),
-            // The then branch is the next instruction.
-            _ => Ok(ComputationStatus::ContinueToNext),
-        }
-    }
+            // Calls and backward jumps consume fuel, so that any long running
+            // code does.
+            macro_rules! jump {
+                ($target:expr) => {{
+                    let target = $target as usize;
 
-    fn if_else(&self) -> Result<'store> {
-        Ok(ComputationStatus::ContinueToEnd)
-    }
+                    if target < pc {
+                        consume_fuel!();
+                    }
 
-    fn sub<T>(&mut self) -> Result<'store>
-    where
-        T: TryFrom<values::Val> + Sub<Output = T>,
-        <T as std::convert::TryFrom<values::Val>>::Error: std::fmt::Debug,
-        Val: From<T>,
-    {
-        self.binop::<T>(|lhs, rhs| Some(Val::from(lhs - rhs)))
-    }
+                    pc = target;
+                }};
+            }
 
-    fn end(&self) -> Result<'store> {
-        Ok(ComputationStatus::ContinueToNext)
-    }
+            macro_rules! reg {
+                ($ty:ty, $reg:expr) => {
+                    <$ty>::from_cell(stack[fp + $reg as usize])
+                };
 
-    fn local_set(&mut self, local: LocalId) -> Result<'store> {
-        let top = self.stack.pop().unwrap();
+                ($reg:expr) => {
+                    stack[fp + $reg as usize]
+                };
+            }
 
-        *self
-            .locals
-            .last_mut()
-            .unwrap()
-            .locals
-            .get_mut(local.index as usize)
-            .unwrap() = top;
+            macro_rules! set {
+                ($reg:expr, $val:expr) => {
+                    stack[fp + $reg as usize] = Cell::from($val)
+                };
+            }
 
-        Ok(ComputationStatus::ContinueToNext)
-    }
+            macro_rules! call {
+                ($callee:expr, $base:expr) => {{
+                    let callee: &'store Func = $callee;
+                    let sp = fp + $base as usize + callee.params;
+
+                    if self.instr_stack.len() >= MAX_FRAMES {
+                        fail!(Trap::StackOverflow);
+                    }
+
+                    consume_fuel!();
+
+                    if sp + callee.max_height > stack.len() {
+                        check!(reserve(&mut self.runner.stack, sp + callee.max_height));
+                        stack = &mut self.runner.stack[..];
+                    }
+
+                    let callee_fp;
+                    (callee_fp, self.runner.sp) = push_frame(stack, sp, callee);
+
+                    match &callee.registers {
+                        Some(registers) => {
+                            self.instr_stack.push(Frame { pc, func, fp });
+
+                            func = callee;
+                            code = registers;
+                            pc = 0;
+                            fp = callee_fp;
+                        }
+
+                        None => {
+                            // The caller is saved too, so that it is part of the
+                            // backtrace if the callee traps.
+                            self.instr_stack.push(Frame { pc, func, fp });
+                            self.execute::<false>(callee, callee_fp)?;
+                            self.instr_stack.pop();
+
+                            stack = &mut self.runner.stack[..];
+                        }
+                    }
+                }};
+            }
 
-    fn return_(&mut self) -> Result<'store> {
-        let old_frame = self.locals.pop().unwrap();
-        let result_last = self.stack.len();
-        let result_first = result_last - old_frame.arity;
-        let result = self
-            .stack
-            .drain(result_first..result_last)
-            .collect::<Vec<_>>();
+            // Returns from the current function, whose results are at `fp`.
+            macro_rules! ret {
+                () => {{
+                    if self.instr_stack.len() == base {
+                        self.runner.sp = fp + func.results;
+                        return Ok(());
+                    }
+
+                    let frame = self.instr_stack.pop().unwrap();
+                    (pc, func, fp) = (frame.pc, frame.func, frame.fp);
+                    code = func.registers.as_deref().unwrap();
+                }};
+            }
 
-        // Let's just check that the maths are mathing.
-        debug_assert_eq!(result.len(), old_frame.arity);
+            // Tail calls reuse the frame of the caller, which is then gone
+            // from the backtrace.
+            macro_rules! tail_call {
+                ($callee:expr, $base:expr) => {{
+                    let callee: &'store Func = $callee;
+                    let args = fp + $base as usize;
+
+                    consume_fuel!();
+
+                    stack.copy_within(args..args + callee.params, fp);
+                    let sp = fp + callee.params;
+
+                    if sp + callee.max_height > stack.len() {
+                        check!(reserve(&mut self.runner.stack, sp + callee.max_height));
+                        stack = &mut self.runner.stack[..];
+                    }
+
+                    (fp, self.runner.sp) = push_frame(stack, sp, callee);
+
+                    match &callee.registers {
+                        Some(registers) => {
+                            func = callee;
+                            code = registers;
+                            pc = 0;
+                        }
+
+                        None => {
+                            // The callee is run like a call, and its results
+                            // are returned right away.
+                            self.instr_stack.push(Frame { pc, func, fp });
+                            self.execute::<false>(callee, fp)?;
+                            self.instr_stack.pop();
+
+                            stack = &mut self.runner.stack[..];
+                            ret!();
+                        }
+                    }
+                }};
+            }
 
-        self.stack.truncate(old_frame.init_stack_size);
-        self.stack.extend(result);
+            // Returns the function at index `index` of `table`, which must be
+            // of type `type_`.
+            macro_rules! indirect_callee {
+                ($type_:expr, $table:expr, $index:expr) => {{
+                    let callee = self.runner.state.tables[$table as usize].get(reg!(u64, $index));
+                    let callee = check!(callee.map_err(|_| Trap::UndefinedElement));
 
-        Ok(ComputationStatus::Return)
-    }
+                    if callee == Cell::NULL {
+                        fail!(Trap::UninitializedElement);
+                    }
 
-    fn unop<T>(&mut self, f: impl FnOnce(T) -> Option<Val>) -> Result<'store>
-    where
-        T: TryFrom<Val>,
-        <T as TryFrom<Val>>::Error: Debug,
-    {
-        self.apply_typed::<T, 1>(|[a]| f(a))
+                    let callee = &store.funcs[u32::from_cell(callee) as usize];
+                    if !store.type_matches(callee.type_.index, $type_) {
+                        fail!(Trap::IndirectCallTypeMismatch);
+                    }
+
+                    callee
+                }};
+            }
+
+            loop {
+                let op = code[pc];
+                pc += 1;
+
+                match op {
+                    RegOp::Unreachable => fail!(Trap::Unreachable),
+
+                    RegOp::Jump(target) => jump!(target),
+
+                    RegOp::JumpIfZero { cond, pc: target } => {
+                        if reg!(i32, cond) == 0 {
+                            jump!(target);
+                        }
+                    }
+
+                    RegOp::JumpIfNonZero { cond, pc: target } => {
+                        if reg!(i32, cond) != 0 {
+                            jump!(target);
+                        }
+                    }
+
+                    RegOp::JumpUnlessCmpImm {
+                        src,
+                        imm,
+                        cmp,
+                        pc: target,
+                    } => {
+                        if !cmp.eval(reg!(i32, src), imm) {
+                            jump!(target);
+                        }
+                    }
+
+                    RegOp::BrTable { index, len } => {
+                        // Out of bounds indices select the default label, which
+                        // is the last one.
+                        pc += (reg!(u32, index)).min(len) as usize;
+                    }
+
+                    RegOp::Return { src } => {
+                        move_results(stack, fp + src as usize + func.results, fp, func.results);
+                        ret!();
+                    }
+
+                    RegOp::Call { func: callee, base } => {
+                        call!(&store.funcs[callee as usize], base)
+                    }
+
+                    RegOp::CallIndirect {
+                        type_,
+                        table,
+                        index,
+                        base,
+                    } => call!(indirect_callee!(type_, table, index), base),
+
+                    RegOp::ReturnCall { func: callee, base } => {
+                        tail_call!(&store.funcs[callee as usize], base)
+                    }
+
+                    RegOp::ReturnCallIndirect {
+                        type_,
+                        table,
+                        index,
+                        base,
+                    } => tail_call!(indirect_callee!(type_, table, index), base),
+
+                    RegOp::Copy { dst, src } => set!(dst, reg!(src)),
+
+                    RegOp::Const { dst, val } => set!(dst, val),
+
+                    RegOp::Select {
+                        dst,
+                        lhs,
+                        rhs,
+                        cond,
+                    } => {
+                        let val = if reg!(i32, cond) != 0 {
+                            reg!(lhs)
+                        } else {
+                            reg!(rhs)
+                        };
+
+                        set!(dst, val);
+                    }
+
+                    RegOp::RefIsNull { dst, src } => set!(dst, i32::from(reg!(src) == Cell::NULL)),
+
+                    RegOp::GlobalGet { dst, global } => {
+                        set!(dst, self.runner.state.globals[global as usize].value);
+                    }
+
+                    RegOp::GlobalSet { global, src } => {
+                        self.runner.state.globals[global as usize].value = reg!(src);
+                    }
+
+                    RegOp::Load { op, dst, addr } => {
+                        let memory = &self.runner.state.memories[op.memarg().memory as usize];
+                        set!(dst, check!(memory::load(op, memory, reg!(u64, addr))));
+                    }
+
+                    RegOp::Store { op, addr, src } => {
+                        let memory = &mut self.runner.state.memories[op.memarg().memory as usize];
+                        check!(memory::store(op, memory, reg!(u64, addr), reg!(src)));
+                    }
+
+                    RegOp::I32Eqz { dst, src } => set!(dst, i32::from(reg!(i32, src) == 0)),
+
+                    RegOp::I32Add { dst, lhs, rhs } => {
+                        set!(dst, reg!(i32, lhs).wrapping_add(reg!(i32, rhs)));
+                    }
+
+                    RegOp::I32Sub { dst, lhs, rhs } => {
+                        set!(dst, reg!(i32, lhs).wrapping_sub(reg!(i32, rhs)));
+                    }
+
+                    RegOp::I32AddImm { dst, src, imm } => {
+                        set!(dst, reg!(i32, src).wrapping_add(imm))
+                    }
+
+                    RegOp::I32Cmp { dst, lhs, rhs, cmp } => {
+                        set!(dst, i32::from(cmp.eval(reg!(i32, lhs), reg!(i32, rhs))));
+                    }
+
+                    RegOp::I32CmpImm { dst, src, imm, cmp } => {
+                        set!(dst, i32::from(cmp.eval(reg!(i32, src), imm)));
+                    }
+
+                    RegOp::Unary { op, dst, src } => {
+                        set!(dst, check!(numeric::unary(op, reg!(src))))
+                    }
+
+                    RegOp::Binary { op, dst, lhs, rhs } => {
+                        set!(dst, check!(numeric::binary(op, reg!(lhs), reg!(rhs))));
+                    }
+                }
+            }
+        };
+
+        // The frame that stopped is saved for the backtrace.
+        self.instr_stack.push(Frame { pc, func, fp });
+
+        Err(unwind)
     }
+}
 
-    fn binop<T>(&mut self, f: impl FnOnce(T, T) -> Option<Val>) -> Result<'store>
-    where
-        T: TryFrom<Val>,
-        <T as TryFrom<Val>>::Error: Debug,
-    {
-        self.apply_typed::<T, 2>(|[a, b]| f(a, b))
+impl<'store> InstructionRunner<'store> {
+    fn new(store: &'store mut Store) -> InstructionRunner<'store> {
+        Self {
+            stack: Vec::new(),
+            sp: 0,
+            code: &store.code,
+            state: &mut store.state,
+            fuel: u64::MAX,
+        }
     }
 
-    fn testop<T>(&mut self, f: impl FnOnce(T) -> Val) -> Result<'store>
-    where
-        T: TryFrom<Val>,
-        <T as TryFrom<Val>>::Error: Debug,
-    {
-        self.unop(|val| Some(f(val)))
+    /// Makes sure that `additional` values can be pushed on the stack.
+    fn reserve(&mut self, additional: usize) -> Result<()> {
+        reserve(&mut self.stack, self.sp + additional)
     }
+}
 
-    fn apply_typed<T, const N: usize>(
-        &mut self,
-        f: impl FnOnce([T; N]) -> Option<Val>,
-    ) -> Result<'store>
-    where
-        T: TryFrom<Val>,
-        <T as TryFrom<Val>>::Error: Debug,
-    {
-        let mut args = array::from_fn(|_| self.stack.pop().unwrap().try_into().unwrap());
-        args.reverse();
+/// Pushes the frame of `func`, whose arguments are on the top of the stack,
+/// and returns its frame pointer along with the new stack pointer.
+///
+/// The stack must be large enough for the maximum height of `func`, so that
+/// there is no need to check for overflows for each push.
+#[inline(always)]
+/// The result of `memory.grow` or `table.grow`, given the address type of the
+/// memory or table: its previous size, or -1 if it couldn't grow.
+fn grow_result(addr_type: AddrType, size: Option<u64>) -> Cell {
+    match (size, addr_type) {
+        (Some(size), _) => Cell::from(size),
+        (None, AddrType::I32) => Cell::from(-1_i32),
+        (None, AddrType::I64) => Cell::from(-1_i64),
+    }
+}
 
-        let out = f(args).ok_or(Trap)?;
+fn push_frame(stack: &mut [Cell], sp: usize, func: &Func) -> (usize, usize) {
+    let fp = sp - func.params;
+    let locals_end = sp + func.locals.len();
+    if !func.locals.is_empty() {
+        stack[sp..locals_end].copy_from_slice(&func.locals);
+    }
 
-        self.stack.push(out);
+    (fp, locals_end)
+}
 
-        Ok(ComputationStatus::ContinueToNext)
+/// Grows `stack` to at least `len` cells, or traps if that exceeds
+/// [`MAX_STACK`].
+fn reserve(stack: &mut Vec<Cell>, len: usize) -> Result<()> {
+    if len > MAX_STACK {
+        return Err(Trap::StackOverflow);
     }
 
-    fn local_value(&self, local: LocalId) -> Val {
-        *self
-            .locals
-            .last()
-            .unwrap()
-            .locals
-            .get(local.index as usize)
-            .unwrap()
+    if len > stack.len() {
+        let new_len = len.max(stack.len() * 2).clamp(1024, MAX_STACK);
+        stack.resize(new_len, Cell::default());
     }
 
-    fn return_from_func(&mut self) {
-        self.locals.pop().unwrap();
-    }
+    Ok(())
+}
 
-    fn pop<T>(&mut self) -> T
-    where
-        T: TryFrom<Val>,
-        <T as TryFrom<Val>>::Error: Debug,
-    {
-        self.stack.pop().unwrap().try_into().unwrap()
+/// Returns the catch clause of the innermost `try_table` of `func` around the
+/// op at `pc` that catches the exceptions of `tag`, along with the branch to
+/// its label.
+fn catch_clause(func: &Func, pc: usize, tag: u32) -> Option<(Op, Branch)> {
+    let handlers = func.handlers.iter();
+
+    handlers
+        .filter(|handler| (handler.start as usize..handler.end as usize).contains(&pc))
+        .find_map(|handler| {
+            let start = handler.start as usize;
+            let Op::TryTable(len) = func.code[start] else {
+                unreachable!("handler without `try_table`")
+            };
+
+            let clauses = &func.code[start + 1..start + 1 + 2 * len as usize];
+            clauses.chunks_exact(2).find_map(|clause| match *clause {
+                [
+                    catch @ (Op::Catch(filter) | Op::CatchRef(filter)),
+                    Op::BrTableEntry(branch),
+                ] => (filter == tag).then_some((catch, branch)),
+                [
+                    catch @ (Op::CatchAll | Op::CatchAllRef),
+                    Op::BrTableEntry(branch),
+                ] => Some((catch, branch)),
+                _ => unreachable!("invalid catch clause"),
+            })
+        })
+}
+
+/// Moves the values carried by `branch` to the height of its label, and
+/// returns where the execution continues along with the new stack pointer.
+#[inline(always)]
+fn take_branch(stack: &mut [Cell], fp: usize, sp: usize, branch: Branch) -> (usize, usize) {
+    let sp = move_results(
+        stack,
+        sp,
+        fp + branch.height as usize,
+        branch.arity as usize,
+    );
+
+    (branch.pc as usize, sp)
+}
+
+/// Moves the `arity` values on the top of the stack to `height`, and returns
+/// the new stack pointer.
+#[inline(always)]
+fn move_results(stack: &mut [Cell], sp: usize, height: usize, arity: usize) -> usize {
+    // Most functions have a single result, which is cheaper to move alone.
+    if arity == 1 {
+        stack[height] = stack[sp - 1];
+    } else {
+        stack.copy_within(sp - arity..sp, height);
     }
+
+    height + arity
 }
 
-#[derive(Clone, Debug, PartialEq)]
-struct Frame {
-    // The length of the stack when the frame is created
-    init_stack_size: usize,
-    locals: Vec<Val>,
-    arity: usize,
+/// The state of a caller, restored when its callee returns.
+#[derive(Clone, Debug)]
+struct Frame<'store> {
+    // The offset of the op to resume at.
+    pc: usize,
+    func: &'store Func,
+    // The offset of the first local of the frame in the stack.
+    fp: usize,
 }
@@ -0,0 +1,1243 @@
+//! The internal instruction set run by the interpreter.
+//!
+//! Function bodies are lowered to a flat stream of [`Op`]s when the store is
+//! built. Compared to [`Instruction`], ops are small and `Copy`, their indices
+//! are resolved, their immediates are decoded and their branch targets are
+//! absolute offsets in the stream. Structured control instructions (`block`,
+//! `loop`, `end`, ...) don't exist anymore, and some common instruction
+//! sequences are fused into a single op.
+
+use wasmbin::instructions::{Atomic, Instruction, Misc, SIMD, threads::AlignedMemArg};
+
+use crate::types::{NumType, RefType, ValType, VecType};
+
+/// A resolved branch to a label.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Branch {
+    /// The offset of the first op executed after the branch.
+    pub(crate) pc: u32,
+    /// The operand stack height at the label, relative to the frame pointer.
+    ///
+    /// The locals of the function are part of this height.
+    pub(crate) height: u32,
+    /// The number of values carried by the branch.
+    pub(crate) arity: u32,
+}
+
+/// The ops covered by a `try_table`, from its [`Op::TryTable`] at `start`,
+/// which is followed by its catch clauses, to its end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Handler {
+    pub(crate) start: u32,
+    pub(crate) end: u32,
+}
+
+/// The immediate of the ops that access a memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct MemArg {
+    pub(crate) memory: u32,
+    pub(crate) offset: u64,
+}
+
+/// An `i32` comparison, used by the fused ops.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum I32Cmp {
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    LeS,
+    LeU,
+    GeS,
+    GeU,
+}
+
+impl I32Cmp {
+    pub(crate) fn from_op(op: Op) -> Option<I32Cmp> {
+        Some(match op {
+            Op::I32Eq => I32Cmp::Eq,
+            Op::I32Ne => I32Cmp::Ne,
+            Op::I32LtS => I32Cmp::LtS,
+            Op::I32LtU => I32Cmp::LtU,
+            Op::I32GtS => I32Cmp::GtS,
+            Op::I32GtU => I32Cmp::GtU,
+            Op::I32LeS => I32Cmp::LeS,
+            Op::I32LeU => I32Cmp::LeU,
+            Op::I32GeS => I32Cmp::GeS,
+            Op::I32GeU => I32Cmp::GeU,
+            _ => return None,
+        })
+    }
+
+    #[inline(always)]
+    pub(crate) fn eval(self, lhs: i32, rhs: i32) -> bool {
+        match self {
+            I32Cmp::Eq => lhs == rhs,
+            I32Cmp::Ne => lhs != rhs,
+            I32Cmp::LtS => lhs < rhs,
+            I32Cmp::LtU => (lhs as u32) < (rhs as u32),
+            I32Cmp::GtS => lhs > rhs,
+            I32Cmp::GtU => (lhs as u32) > (rhs as u32),
+            I32Cmp::LeS => lhs <= rhs,
+            I32Cmp::LeU => (lhs as u32) <= (rhs as u32),
+            I32Cmp::GeS => lhs >= rhs,
+            I32Cmp::GeU => (lhs as u32) >= (rhs as u32),
+        }
+    }
+}
+
+/// The [`ValType`] named by one of the type tokens of [`ops!`].
+macro_rules! val_type {
+    (V128) => {
+        ValType::VecType(VecType::V128)
+    };
+
+    ($num:ident) => {
+        ValType::NumType(NumType::$num)
+    };
+
+    // The right operand of a binary op, which defaults to the type of the
+    // left one.
+    ($lhs:ident ; $rhs:ident) => {
+        val_type!($rhs)
+    };
+}
+
+macro_rules! ops {
+    (
+        $( #[$meta:meta] )*
+        pub(crate) enum Op {
+            $( $other:tt )*
+        }
+
+        unary {
+            $(
+                $unary_instr:pat => $unary:ident $( [$unary_imm:ty = $unary_imm_val:expr] )?
+                    ($unary_param:ident -> $unary_result:ident),
+            )*
+        }
+
+        binary {
+            $(
+                $binary_instr:pat => $binary:ident $( [$binary_imm:ty = $binary_imm_val:expr] )?
+                    ($binary_lhs:ident $( , $binary_rhs:ident )? -> $binary_result:ident),
+            )*
+        }
+
+        ternary {
+            $( $ternary_instr:pat => $ternary:ident($ternary_param:ident -> $ternary_result:ident), )*
+        }
+
+        load {
+            $( $load:ident($load_size:literal -> $load_result:ident), )*
+        }
+
+        store {
+            $( $store:ident($store_param:ident -> $store_size:literal), )*
+        }
+
+        vector load {
+            $( $vector_load:ident($vector_load_size:literal -> V128), )*
+        }
+
+        vector store {
+            $( $vector_store:ident(V128 -> $vector_store_size:literal), )*
+        }
+
+        lane load {
+            $( $lane_load:ident($lane_load_size:literal), )*
+        }
+
+        lane store {
+            $( $lane_store:ident($lane_store_size:literal), )*
+        }
+
+        atomic load {
+            $(
+                $atomic_load_instr:ident => $atomic_load:ident
+                    ($atomic_load_size:literal -> $atomic_load_result:ident),
+            )*
+        }
+
+        atomic store {
+            $(
+                $atomic_store_instr:ident => $atomic_store:ident
+                    ($atomic_store_param:ident -> $atomic_store_size:literal),
+            )*
+        }
+
+        atomic rmw {
+            $( $rmw_instr:ident => $rmw:ident($rmw_param:ident, $rmw_size:literal), )*
+        }
+
+        atomic cmpxchg {
+            $( $cmpxchg_instr:ident => $cmpxchg:ident($cmpxchg_param:ident, $cmpxchg_size:literal), )*
+        }
+    ) => {
+        $( #[$meta] )*
+        pub(crate) enum Op {
+            $( $other )*
+            $( $unary $( ($unary_imm) )?, )*
+            $( $binary $( ($binary_imm) )?, )*
+            $( $ternary, )*
+            $( $load(MemArg), )*
+            $( $store(MemArg), )*
+            $( $vector_load(MemArg), )*
+            $( $vector_store(MemArg), )*
+            /// Pops a `v128` and an `i32` address, and pushes the vector
+            /// with the given lane loaded from the memory.
+            $( $lane_load(MemArg, u8), )*
+            /// Pops a `v128` and an `i32` address, and stores the given lane
+            /// of the vector to the memory.
+            $( $lane_store(MemArg, u8), )*
+            $( $atomic_load(MemArg), )*
+            $( $atomic_store(MemArg), )*
+            /// Pops an operand and an `i32` address, replaces the value at
+            /// the address with the result of the op, and pushes the previous
+            /// value.
+            $( $rmw(MemArg), )*
+            /// Pops a replacement, an expected value and an `i32` address,
+            /// replaces the value at the address if it is the expected one,
+            /// and pushes the previous value.
+            $( $cmpxchg(MemArg), )*
+        }
+
+        /// A numeric op, along with its arity and its signature.
+        pub(crate) enum Numeric {
+            /// Pops one value of type `param`, pushes one value of type
+            /// `result`.
+            Unary { op: Op, param: ValType, result: ValType },
+            /// Pops a value of type `rhs` and a value of type `lhs`, pushes
+            /// one value of type `result`.
+            Binary { op: Op, lhs: ValType, rhs: ValType, result: ValType },
+            /// Pops three values of type `param`, pushes one value of type
+            /// `result`.
+            Ternary { op: Op, param: ValType, result: ValType },
+        }
+
+        /// A memory access op, along with its signature and the number of
+        /// bytes it accesses.
+        pub(crate) enum MemoryAccess {
+            /// Pops an `i32` address, pushes one value of type `result`.
+            Load { op: Op, size: u32, result: ValType, align_log2: u32 },
+            /// Pops a value of type `param` and an `i32` address.
+            Store { op: Op, size: u32, param: ValType, align_log2: u32 },
+            /// Pops a `v128` and an `i32` address, pushes a `v128`.
+            LoadLane { op: Op, size: u32, align_log2: u32 },
+            /// Pops a `v128` and an `i32` address.
+            StoreLane { op: Op, size: u32, align_log2: u32 },
+            /// Pops a value of type `param` and an `i32` address, pushes a
+            /// value of type `param`.
+            Rmw { op: Op, size: u32, param: ValType },
+            /// Pops two values of type `param` and an `i32` address, pushes a
+            /// value of type `param`.
+            Cmpxchg { op: Op, size: u32, param: ValType },
+        }
+
+        impl Op {
+            /// Whether the op is a numeric op that pops one value.
+            pub(crate) fn is_unary(self) -> bool {
+                matches!(self, $( Op::$unary { .. } )|*)
+            }
+
+            /// Whether the op is a numeric op that pops two values.
+            pub(crate) fn is_binary(self) -> bool {
+                matches!(self, $( Op::$binary { .. } )|*)
+            }
+
+            /// Whether the op is a numeric op that pops three values.
+            pub(crate) fn is_ternary(self) -> bool {
+                matches!(self, $( Op::$ternary )|*)
+            }
+
+            /// Whether the op loads a value from a memory.
+            pub(crate) fn is_load(self) -> bool {
+                matches!(
+                    self,
+                    $( Op::$load(_) )|* | $( Op::$vector_load(_) )|* | $( Op::$atomic_load(_) )|*
+                )
+            }
+
+            /// Whether the op stores a value to a memory.
+            pub(crate) fn is_store(self) -> bool {
+                matches!(
+                    self,
+                    $( Op::$store(_) )|* | $( Op::$vector_store(_) )|* | $( Op::$atomic_store(_) )|*
+                )
+            }
+
+            /// Whether the op loads a lane of a vector from a memory.
+            pub(crate) fn is_lane_load(self) -> bool {
+                matches!(self, $( Op::$lane_load(..) )|*)
+            }
+
+            /// Whether the op stores a lane of a vector to a memory.
+            pub(crate) fn is_lane_store(self) -> bool {
+                matches!(self, $( Op::$lane_store(..) )|*)
+            }
+
+            /// Whether the op is an atomic read-modify-write.
+            pub(crate) fn is_atomic_rmw(self) -> bool {
+                matches!(self, $( Op::$rmw(_) )|*)
+            }
+
+            /// Whether the op is an atomic compare-exchange.
+            pub(crate) fn is_atomic_cmpxchg(self) -> bool {
+                matches!(self, $( Op::$cmpxchg(_) )|*)
+            }
+
+            /// The immediate of a memory access op.
+            pub(crate) fn memarg(self) -> MemArg {
+                match self {
+                    $( Op::$load(memarg) )|*
+                    | $( Op::$store(memarg) )|*
+                    | $( Op::$vector_load(memarg) )|*
+                    | $( Op::$vector_store(memarg) )|*
+                    | $( Op::$lane_load(memarg, _) )|*
+                    | $( Op::$lane_store(memarg, _) )|*
+                    | $( Op::$atomic_load(memarg) )|*
+                    | $( Op::$atomic_store(memarg) )|*
+                    | $( Op::$rmw(memarg) )|*
+                    | $( Op::$cmpxchg(memarg) )|*
+                    | Op::MemoryAtomicNotify(memarg)
+                    | Op::MemoryAtomicWait32(memarg)
+                    | Op::MemoryAtomicWait64(memarg) => memarg,
+                    op => unreachable!("not a memory access: `{op:?}`"),
+                }
+            }
+
+            /// The immediate of a memory access op.
+            pub(crate) fn memarg_mut(&mut self) -> &mut MemArg {
+                match self {
+                    $( Op::$load(memarg) )|*
+                    | $( Op::$store(memarg) )|*
+                    | $( Op::$vector_load(memarg) )|*
+                    | $( Op::$vector_store(memarg) )|*
+                    | $( Op::$lane_load(memarg, _) )|*
+                    | $( Op::$lane_store(memarg, _) )|*
+                    | $( Op::$atomic_load(memarg) )|*
+                    | $( Op::$atomic_store(memarg) )|*
+                    | $( Op::$rmw(memarg) )|*
+                    | $( Op::$cmpxchg(memarg) )|*
+                    | Op::MemoryAtomicNotify(memarg)
+                    | Op::MemoryAtomicWait32(memarg)
+                    | Op::MemoryAtomicWait64(memarg) => memarg,
+                    op => unreachable!("not a memory access: `{op:?}`"),
+                }
+            }
+        }
+
+        impl Numeric {
+            /// Lowers a numeric instruction, returning `None` if `instr` is
+            /// not numeric.
+            pub(crate) fn from_instruction(instr: &Instruction) -> Option<Numeric> {
+                Some(match instr {
+                    $(
+                        $unary_instr => Numeric::Unary {
+                            op: Op::$unary $( ($unary_imm_val) )?,
+                            param: val_type!($unary_param),
+                            result: val_type!($unary_result),
+                        },
+                    )*
+                    $(
+                        $binary_instr => Numeric::Binary {
+                            op: Op::$binary $( ($binary_imm_val) )?,
+                            lhs: val_type!($binary_lhs),
+                            rhs: val_type!($binary_lhs $( ; $binary_rhs )?),
+                            result: val_type!($binary_result),
+                        },
+                    )*
+                    $(
+                        $ternary_instr => Numeric::Ternary {
+                            op: Op::$ternary,
+                            param: val_type!($ternary_param),
+                            result: val_type!($ternary_result),
+                        },
+                    )*
+                    _ => return None,
+                })
+            }
+        }
+
+        impl MemoryAccess {
+            /// Lowers a memory access instruction, returning `None` if
+            /// `instr` doesn't access a memory.
+            ///
+            /// The memory index of the returned op is the one of the module.
+            pub(crate) fn from_instruction(instr: &Instruction) -> Option<MemoryAccess> {
+                Some(match instr {
+                    $(
+                        Instruction::$load(memarg) => MemoryAccess::Load {
+                            op: Op::$load(MemArg::from(memarg)),
+                            size: $load_size,
+                            result: val_type!($load_result),
+                            align_log2: memarg.align_log2,
+                        },
+                    )*
+                    $(
+                        Instruction::$store(memarg) => MemoryAccess::Store {
+                            op: Op::$store(MemArg::from(memarg)),
+                            size: $store_size,
+                            param: val_type!($store_param),
+                            align_log2: memarg.align_log2,
+                        },
+                    )*
+                    $(
+                        Instruction::SIMD(SIMD::$vector_load(memarg)) => MemoryAccess::Load {
+                            op: Op::$vector_load(MemArg::from(memarg)),
+                            size: $vector_load_size,
+                            result: val_type!(V128),
+                            align_log2: memarg.align_log2,
+                        },
+                    )*
+                    $(
+                        Instruction::SIMD(SIMD::$vector_store(memarg)) => MemoryAccess::Store {
+                            op: Op::$vector_store(MemArg::from(memarg)),
+                            size: $vector_store_size,
+                            param: val_type!(V128),
+                            align_log2: memarg.align_log2,
+                        },
+                    )*
+                    $(
+                        Instruction::SIMD(SIMD::$lane_load(memarg, lane)) => MemoryAccess::LoadLane {
+                            op: Op::$lane_load(MemArg::from(memarg), u8::from(*lane)),
+                            size: $lane_load_size,
+                            align_log2: memarg.align_log2,
+                        },
+                    )*
+                    $(
+                        Instruction::SIMD(SIMD::$lane_store(memarg, lane)) => MemoryAccess::StoreLane {
+                            op: Op::$lane_store(MemArg::from(memarg), u8::from(*lane)),
+                            size: $lane_store_size,
+                            align_log2: memarg.align_log2,
+                        },
+                    )*
+                    $(
+                        Instruction::Atomic(Atomic::$atomic_load_instr(memarg)) => MemoryAccess::Load {
+                            op: Op::$atomic_load(MemArg::from(memarg)),
+                            size: $atomic_load_size,
+                            result: val_type!($atomic_load_result),
+                            align_log2: u32::trailing_zeros($atomic_load_size),
+                        },
+                    )*
+                    $(
+                        Instruction::Atomic(Atomic::$atomic_store_instr(memarg)) => MemoryAccess::Store {
+                            op: Op::$atomic_store(MemArg::from(memarg)),
+                            size: $atomic_store_size,
+                            param: val_type!($atomic_store_param),
+                            align_log2: u32::trailing_zeros($atomic_store_size),
+                        },
+                    )*
+                    $(
+                        Instruction::Atomic(Atomic::$rmw_instr(memarg)) => MemoryAccess::Rmw {
+                            op: Op::$rmw(MemArg::from(memarg)),
+                            size: $rmw_size,
+                            param: val_type!($rmw_param),
+                        },
+                    )*
+                    $(
+                        Instruction::Atomic(Atomic::$cmpxchg_instr(memarg)) => MemoryAccess::Cmpxchg {
+                            op: Op::$cmpxchg(MemArg::from(memarg)),
+                            size: $cmpxchg_size,
+                            param: val_type!($cmpxchg_param),
+                        },
+                    )*
+                    _ => return None,
+                })
+            }
+
+            /// The lowered op.
+            pub(crate) fn op(&self) -> Op {
+                match *self {
+                    MemoryAccess::Load { op, .. }
+                    | MemoryAccess::Store { op, .. }
+                    | MemoryAccess::LoadLane { op, .. }
+                    | MemoryAccess::StoreLane { op, .. }
+                    | MemoryAccess::Rmw { op, .. }
+                    | MemoryAccess::Cmpxchg { op, .. } => op,
+                }
+            }
+        }
+    };
+}
+
+impl From<&wasmbin::instructions::MemArg> for MemArg {
+    fn from(memarg: &wasmbin::instructions::MemArg) -> MemArg {
+        MemArg {
+            memory: memarg.memory.index,
+            offset: memarg.offset,
+        }
+    }
+}
+
+impl<const ALIGN_LOG2: u32> From<&AlignedMemArg<ALIGN_LOG2>> for MemArg {
+    fn from(memarg: &AlignedMemArg<ALIGN_LOG2>) -> MemArg {
+        MemArg {
+            memory: memarg.memory.index,
+            offset: memarg.offset,
+        }
+    }
+}
+
+ops! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub(crate) enum Op {
+        Unreachable,
+        /// Jumps to an offset without touching the operand stack.
+        Jump(u32),
+        /// Pops an `i32` and jumps if it is zero.
+        JumpIfZero(u32),
+        /// Pops an `i32` and jumps if it is not zero.
+        JumpIfNonZero(u32),
+        Br(Branch),
+        /// Pops an `i32` and branches if it is not zero.
+        BrIf(Branch),
+        /// Branches, dropping the reference on top of the stack, if it is
+        /// null.
+        BrOnNull(Branch),
+        /// Branches if the reference on top of the stack is not null, and
+        /// drops it otherwise.
+        BrOnNonNull(Branch),
+        /// Pops an index and takes the corresponding label.
+        ///
+        /// This op is followed by `len + 1` [`Op::BrTableEntry`], the last one
+        /// being the default label.
+        BrTable(u32),
+        BrTableEntry(Branch),
+        Return,
+        /// Returns the local at the given index, which is the single result
+        /// of the function.
+        ReturnLocal(u32),
+        /// Calls the function at the given address of the store.
+        Call(u32),
+        /// Pops an index in `table`, and calls the function it refers to,
+        /// which must be of type `type_`.
+        CallIndirect { type_: u32, table: u32 },
+        /// Calls the function at the given address of the store in place of
+        /// the current one, whose frame is reused.
+        ///
+        /// This op is followed by an [`Op::Return`], which is where a call to
+        /// a host function, which needs the frame of its caller, returns.
+        ReturnCall(u32),
+        /// Like [`Op::CallIndirect`], but replaces the current function like
+        /// [`Op::ReturnCall`].
+        ReturnCallIndirect { type_: u32, table: u32 },
+        /// Pops a function reference, and calls the function it refers to.
+        CallRef,
+        /// Like [`Op::CallRef`], but replaces the current function like
+        /// [`Op::ReturnCall`].
+        ReturnCallRef,
+        /// Runs the host function at the given index of the store on the
+        /// arguments of the current frame, and pushes its results.
+        Host(u32),
+        /// Pops the payload of the tag at the given address of the store, and
+        /// throws an exception with it.
+        Throw(u32),
+        /// Pops an exception reference, and throws the exception it refers
+        /// to again.
+        ThrowRef,
+        /// Starts a `try_table`, whose `len` catch clauses follow, each being
+        /// an [`Op::Catch`], [`Op::CatchRef`], [`Op::CatchAll`] or
+        /// [`Op::CatchAllRef`] followed by the [`Op::BrTableEntry`] of its
+        /// label.
+        ///
+        /// The clauses are skipped, they are only looked up through the
+        /// [`Handler`]s of the function when an exception is thrown.
+        TryTable(u32),
+        /// Catches the exceptions of the tag at the given address of the
+        /// store, and pushes their payload.
+        Catch(u32),
+        /// Like [`Op::Catch`], then pushes a reference to the exception.
+        CatchRef(u32),
+        /// Catches all the exceptions.
+        CatchAll,
+        /// Like [`Op::CatchAll`], then pushes a reference to the exception.
+        CatchAllRef,
+
+        Drop,
+        Select,
+
+        /// Pushes the local at the given offset from the frame pointer.
+        LocalGet(u32),
+        LocalSet(u32),
+        LocalTee(u32),
+
+        I32Const(i32),
+        I64Const(i64),
+        F32Const(f32),
+        F64Const(f64),
+        /// Pushes the vector at the given index of [`Func::v128s`].
+        ///
+        /// [`Func::v128s`]: crate::store::Func::v128s
+        V128Const(u32),
+        /// Pops two vectors, and pushes the vector whose lanes are selected
+        /// by the indices at the given index of [`Func::v128s`].
+        ///
+        /// [`Func::v128s`]: crate::store::Func::v128s
+        I8x16Shuffle(u32),
+
+        RefNull(RefType),
+        RefIsNull,
+        /// Traps if the reference on top of the stack is null.
+        RefAsNonNull,
+        RefFunc(u32),
+        /// Pops two references, and pushes whether they are the same.
+        RefEq,
+        /// Pops a reference, and pushes whether it is of the given type.
+        RefTest(RefType),
+        /// Traps if the reference on top of the stack is not of the given
+        /// type.
+        RefCast(RefType),
+        /// Branches if the reference on top of the stack is of the given
+        /// type.
+        ///
+        /// This op is followed by the [`Op::BrTableEntry`] of its label.
+        BrOnCast(RefType),
+        /// Like [`Op::BrOnCast`], but branches if the reference is not of
+        /// the given type.
+        BrOnCastFail(RefType),
+        /// Pops an `i32`, and pushes an `i31ref` holding its low 31 bits.
+        RefI31,
+        /// Pops an `i31ref`, and pushes its value sign-extended.
+        I31GetS,
+        /// Pops an `i31ref`, and pushes its value zero-extended.
+        I31GetU,
+
+        /// Pops the fields of a structure of the type at the given address of
+        /// the store, and pushes a reference to a new structure holding them.
+        StructNew(u32),
+        /// Pushes a reference to a new structure of the type at the given
+        /// address of the store, whose fields have their default value.
+        StructNewDefault(u32),
+        /// Pops a reference to a structure of type `type_`, and pushes its
+        /// field `field`.
+        StructGet { type_: u32, field: u32 },
+        /// Like [`Op::StructGet`], sign-extending a packed field.
+        StructGetS { type_: u32, field: u32 },
+        /// Like [`Op::StructGet`], zero-extending a packed field.
+        StructGetU { type_: u32, field: u32 },
+        /// Pops a value and a reference to a structure of type `type_`, and
+        /// sets its field `field`.
+        StructSet { type_: u32, field: u32 },
+        /// Pops a length and an initial value, and pushes a reference to a new
+        /// array of the type at the given address of the store.
+        ArrayNew(u32),
+        /// Like [`Op::ArrayNew`], with elements of their default value.
+        ArrayNewDefault(u32),
+        /// Pops `len` elements, and pushes a reference to a new array of type
+        /// `type_` holding them.
+        ArrayNewFixed { type_: u32, len: u32 },
+        /// Pops a length and an offset in the data segment `data`, and pushes
+        /// a reference to a new array of type `type_` whose elements are read
+        /// from the segment.
+        ArrayNewData { type_: u32, data: u32 },
+        /// Like [`Op::ArrayNewData`], with the element segment `elem`.
+        ArrayNewElem { type_: u32, elem: u32 },
+        /// Pops an index and a reference to an array of the type at the given
+        /// address of the store, and pushes the element at the index.
+        ArrayGet(u32),
+        /// Like [`Op::ArrayGet`], sign-extending a packed element.
+        ArrayGetS(u32),
+        /// Like [`Op::ArrayGet`], zero-extending a packed element.
+        ArrayGetU(u32),
+        /// Pops a value, an index and a reference to an array of the type at
+        /// the given address of the store, and sets the element at the index.
+        ArraySet(u32),
+        /// Pops an array reference, and pushes its length.
+        ArrayLen,
+        /// Pops a length, a value, an index and an array reference, and sets
+        /// the elements from the index on to the value.
+        ArrayFill(u32),
+        /// Pops a length, a source index and array, and a destination index
+        /// and array, and copies the elements of the source to the
+        /// destination.
+        ArrayCopy,
+        /// Pops a length, an offset in the data segment `data`, an index and
+        /// a reference to an array of type `type_`, and sets the elements
+        /// from the index on to the ones read from the segment.
+        ArrayInitData { type_: u32, data: u32 },
+        /// Like [`Op::ArrayInitData`], with the element segment at the given
+        /// address of the store.
+        ArrayInitElem(u32),
+
+        GlobalGet(u32),
+        GlobalSet(u32),
+
+        TableGet(u32),
+        TableSet(u32),
+        TableSize(u32),
+        TableGrow(u32),
+        TableFill(u32),
+        TableCopy { dst: u32, src: u32 },
+        TableInit { elem: u32, table: u32 },
+        ElemDrop(u32),
+
+        MemorySize(u32),
+        MemoryGrow(u32),
+        MemoryFill(u32),
+        MemoryCopy { dst: u32, src: u32 },
+        MemoryInit { data: u32, memory: u32 },
+        DataDrop(u32),
+        /// Pops a count and an `i32` address, wakes up to count threads
+        /// waiting on the address, and pushes the number of woken threads.
+        MemoryAtomicNotify(MemArg),
+        /// Pops an `i64` timeout, an expected `i32` and an `i32` address, and
+        /// waits until the address is notified if it holds the expected
+        /// value.
+        MemoryAtomicWait32(MemArg),
+        /// Like [`Op::MemoryAtomicWait32`], with an expected `i64`.
+        MemoryAtomicWait64(MemArg),
+        AtomicFence,
+
+        /// `i32.const imm` + `i32.add`.
+        I32AddImm(i32),
+        /// `local.get local` + `i32.const imm` + `i32.add`.
+        I32LocalAddImm { local: u32, imm: i32 },
+        /// `local.get src` + `i32.const imm` + `i32.add` + `local.set dst`.
+        I32LocalAddImmSet { src: u32, dst: u32, imm: i32 },
+        /// `local.get local` + `i32.const imm` + an `i32` comparison.
+        I32LocalCmpImm { local: u32, imm: i32, cmp: I32Cmp },
+        /// `local.get local` + `i32.const imm` + an `i32` comparison + `if`.
+        ///
+        /// Jumps to `pc` if the comparison does not hold.
+        JumpUnlessLocalCmpImm { local: u32, imm: i32, cmp: I32Cmp, pc: u32 },
+        /// `local.get src` + `local.set dst`.
+        LocalCopy { src: u32, dst: u32 },
+    }
+
+    unary {
+        Instruction::I32Eqz => I32Eqz(I32 -> I32),
+        Instruction::I64Eqz => I64Eqz(I64 -> I32),
+        Instruction::I32Clz => I32Clz(I32 -> I32),
+        Instruction::I32Ctz => I32Ctz(I32 -> I32),
+        Instruction::I32PopCnt => I32PopCnt(I32 -> I32),
+        Instruction::I64Clz => I64Clz(I64 -> I64),
+        Instruction::I64Ctz => I64Ctz(I64 -> I64),
+        Instruction::I64PopCnt => I64PopCnt(I64 -> I64),
+        Instruction::F32Abs => F32Abs(F32 -> F32),
+        Instruction::F32Neg => F32Neg(F32 -> F32),
+        Instruction::F32Ceil => F32Ceil(F32 -> F32),
+        Instruction::F32Floor => F32Floor(F32 -> F32),
+        Instruction::F32Trunc => F32Trunc(F32 -> F32),
+        Instruction::F32Nearest => F32Nearest(F32 -> F32),
+        Instruction::F32Sqrt => F32Sqrt(F32 -> F32),
+        Instruction::F64Abs => F64Abs(F64 -> F64),
+        Instruction::F64Neg => F64Neg(F64 -> F64),
+        Instruction::F64Ceil => F64Ceil(F64 -> F64),
+        Instruction::F64Floor => F64Floor(F64 -> F64),
+        Instruction::F64Trunc => F64Trunc(F64 -> F64),
+        Instruction::F64Nearest => F64Nearest(F64 -> F64),
+        Instruction::F64Sqrt => F64Sqrt(F64 -> F64),
+        Instruction::I32WrapI64 => I32WrapI64(I64 -> I32),
+        Instruction::I32TruncF32S => I32TruncF32S(F32 -> I32),
+        Instruction::I32TruncF332U => I32TruncF32U(F32 -> I32),
+        Instruction::I32TruncF64S => I32TruncF64S(F64 -> I32),
+        Instruction::I32TruncF64U => I32TruncF64U(F64 -> I32),
+        Instruction::I64ExtendI32S => I64ExtendI32S(I32 -> I64),
+        Instruction::I64ExtendI32U => I64ExtendI32U(I32 -> I64),
+        Instruction::I64TruncF32S => I64TruncF32S(F32 -> I64),
+        Instruction::I64TruncF32U => I64TruncF32U(F32 -> I64),
+        Instruction::I64TruncF64S => I64TruncF64S(F64 -> I64),
+        Instruction::I64TruncF64U => I64TruncF64U(F64 -> I64),
+        Instruction::F32ConvertI32S => F32ConvertI32S(I32 -> F32),
+        Instruction::F32ConvertI32U => F32ConvertI32U(I32 -> F32),
+        Instruction::F32ConvertI64S => F32ConvertI64S(I64 -> F32),
+        Instruction::F32ConvertI64U => F32ConvertI64U(I64 -> F32),
+        Instruction::F32DemoteF64 => F32DemoteF64(F64 -> F32),
+        Instruction::F64ConvertI32S => F64ConvertI32S(I32 -> F64),
+        Instruction::F64ConvertI32U => F64ConvertI32U(I32 -> F64),
+        Instruction::F64ConvertI64S => F64ConvertI64S(I64 -> F64),
+        Instruction::F64ConvertI64U => F64ConvertI64U(I64 -> F64),
+        Instruction::F64PromoteF32 => F64PromoteF32(F32 -> F64),
+        Instruction::I32ReinterpretF32 => I32ReinterpretF32(F32 -> I32),
+        Instruction::I64ReinterpretF64 => I64ReinterpretF64(F64 -> I64),
+        Instruction::F32ReinterpretI32 => F32ReinterpretI32(I32 -> F32),
+        Instruction::F64ReinterpretI64 => F64ReinterpretI64(I64 -> F64),
+        Instruction::I32Extend8S => I32Extend8S(I32 -> I32),
+        Instruction::I32Extend16S => I32Extend16S(I32 -> I32),
+        Instruction::I64Extend8S => I64Extend8S(I64 -> I64),
+        Instruction::I64Extend16S => I64Extend16S(I64 -> I64),
+        Instruction::I64Extend32S => I64Extend32S(I64 -> I64),
+        Instruction::Misc(Misc::I32TruncSatF32S) => I32TruncSatF32S(F32 -> I32),
+        Instruction::Misc(Misc::I32TruncSatF32U) => I32TruncSatF32U(F32 -> I32),
+        Instruction::Misc(Misc::I32TruncSatF64S) => I32TruncSatF64S(F64 -> I32),
+        Instruction::Misc(Misc::I32TruncSatF64U) => I32TruncSatF64U(F64 -> I32),
+        Instruction::Misc(Misc::I64TruncSatF32S) => I64TruncSatF32S(F32 -> I64),
+        Instruction::Misc(Misc::I64TruncSatF32U) => I64TruncSatF32U(F32 -> I64),
+        Instruction::Misc(Misc::I64TruncSatF64S) => I64TruncSatF64S(F64 -> I64),
+        Instruction::Misc(Misc::I64TruncSatF64U) => I64TruncSatF64U(F64 -> I64),
+        Instruction::SIMD(SIMD::V128Not) => V128Not(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Abs) => I8x16Abs(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Neg) => I8x16Neg(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Popcnt) => I8x16Popcnt(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Abs) => I16x8Abs(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Neg) => I16x8Neg(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Abs) => I32x4Abs(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Neg) => I32x4Neg(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Abs) => I64x2Abs(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Neg) => I64x2Neg(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Abs) => F32x4Abs(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Neg) => F32x4Neg(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Sqrt) => F32x4Sqrt(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Ceil) => F32x4Ceil(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Floor) => F32x4Floor(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Trunc) => F32x4Trunc(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Nearest) => F32x4Nearest(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Abs) => F64x2Abs(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Neg) => F64x2Neg(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Sqrt) => F64x2Sqrt(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Ceil) => F64x2Ceil(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Floor) => F64x2Floor(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Trunc) => F64x2Trunc(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Nearest) => F64x2Nearest(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtendLowI8x16S) => I16x8ExtendLowI8x16S(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtendHighI8x16S) => I16x8ExtendHighI8x16S(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtendLowI8x16U) => I16x8ExtendLowI8x16U(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtendHighI8x16U) => I16x8ExtendHighI8x16U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtendLowI16x8S) => I32x4ExtendLowI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtendHighI16x8S) => I32x4ExtendHighI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtendLowI16x8U) => I32x4ExtendLowI16x8U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtendHighI16x8U) => I32x4ExtendHighI16x8U(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtendLowI32x4S) => I64x2ExtendLowI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtendHighI32x4S) => I64x2ExtendHighI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtendLowI32x4U) => I64x2ExtendLowI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtendHighI32x4U) => I64x2ExtendHighI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtaddPairwiseI8x16S) => I16x8ExtaddPairwiseI8x16S(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtaddPairwiseI8x16U) => I16x8ExtaddPairwiseI8x16U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtaddPairwiseI16x8S) => I32x4ExtaddPairwiseI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtaddPairwiseI16x8U) => I32x4ExtaddPairwiseI16x8U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4TruncSatF32x4S) => I32x4TruncSatF32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4TruncSatF32x4U) => I32x4TruncSatF32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4ConvertI32x4S) => F32x4ConvertI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4ConvertI32x4U) => F32x4ConvertI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4TruncSatF64x2SZero) => I32x4TruncSatF64x2SZero(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4TruncSatF64x2UZero) => I32x4TruncSatF64x2UZero(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2ConvertLowI32x4S) => F64x2ConvertLowI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2ConvertLowI32x4U) => F64x2ConvertLowI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4DemoteF64x2Zero) => F32x4DemoteF64x2Zero(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2PromoteLowF32x4) => F64x2PromoteLowF32x4(V128 -> V128),
+        Instruction::SIMD(SIMD::V128AnyTrue) => V128AnyTrue(V128 -> I32),
+        Instruction::SIMD(SIMD::I8x16AllTrue) => I8x16AllTrue(V128 -> I32),
+        Instruction::SIMD(SIMD::I8x16Bitmask) => I8x16Bitmask(V128 -> I32),
+        Instruction::SIMD(SIMD::I16x8AllTrue) => I16x8AllTrue(V128 -> I32),
+        Instruction::SIMD(SIMD::I16x8Bitmask) => I16x8Bitmask(V128 -> I32),
+        Instruction::SIMD(SIMD::I32x4AllTrue) => I32x4AllTrue(V128 -> I32),
+        Instruction::SIMD(SIMD::I32x4Bitmask) => I32x4Bitmask(V128 -> I32),
+        Instruction::SIMD(SIMD::I64x2AllTrue) => I64x2AllTrue(V128 -> I32),
+        Instruction::SIMD(SIMD::I64x2Bitmask) => I64x2Bitmask(V128 -> I32),
+        Instruction::SIMD(SIMD::I8x16Splat) => I8x16Splat(I32 -> V128),
+        Instruction::SIMD(SIMD::I16x8Splat) => I16x8Splat(I32 -> V128),
+        Instruction::SIMD(SIMD::I32x4Splat) => I32x4Splat(I32 -> V128),
+        Instruction::SIMD(SIMD::I64x2Splat) => I64x2Splat(I64 -> V128),
+        Instruction::SIMD(SIMD::F32x4Splat) => F32x4Splat(F32 -> V128),
+        Instruction::SIMD(SIMD::F64x2Splat) => F64x2Splat(F64 -> V128),
+        Instruction::SIMD(SIMD::I8x16ExtractLaneS(lane)) => I8x16ExtractLaneS[u8 = u8::from(*lane)](V128 -> I32),
+        Instruction::SIMD(SIMD::I8x16ExtractLaneU(lane)) => I8x16ExtractLaneU[u8 = u8::from(*lane)](V128 -> I32),
+        Instruction::SIMD(SIMD::I16x8ExtractLaneS(lane)) => I16x8ExtractLaneS[u8 = u8::from(*lane)](V128 -> I32),
+        Instruction::SIMD(SIMD::I16x8ExtractLaneU(lane)) => I16x8ExtractLaneU[u8 = u8::from(*lane)](V128 -> I32),
+        Instruction::SIMD(SIMD::I32x4ExtractLane(lane)) => I32x4ExtractLane[u8 = u8::from(*lane)](V128 -> I32),
+        Instruction::SIMD(SIMD::I64x2ExtractLane(lane)) => I64x2ExtractLane[u8 = u8::from(*lane)](V128 -> I64),
+        Instruction::SIMD(SIMD::F32x4ExtractLane(lane)) => F32x4ExtractLane[u8 = u8::from(*lane)](V128 -> F32),
+        Instruction::SIMD(SIMD::F64x2ExtractLane(lane)) => F64x2ExtractLane[u8 = u8::from(*lane)](V128 -> F64),
+        Instruction::SIMD(SIMD::I32x4RelaxedTruncF32x4S) => I32x4RelaxedTruncF32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4RelaxedTruncF32x4U) => I32x4RelaxedTruncF32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4RelaxedTruncF64x2SZero) => I32x4RelaxedTruncF64x2SZero(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4RelaxedTruncF64x2UZero) => I32x4RelaxedTruncF64x2UZero(V128 -> V128),
+    }
+
+    binary {
+        Instruction::I32Eq => I32Eq(I32 -> I32),
+        Instruction::I32Ne => I32Ne(I32 -> I32),
+        Instruction::I32LtS => I32LtS(I32 -> I32),
+        Instruction::I32LtU => I32LtU(I32 -> I32),
+        Instruction::I32GtS => I32GtS(I32 -> I32),
+        Instruction::I32GtU => I32GtU(I32 -> I32),
+        Instruction::I32LeS => I32LeS(I32 -> I32),
+        Instruction::I32LeU => I32LeU(I32 -> I32),
+        Instruction::I32GeS => I32GeS(I32 -> I32),
+        Instruction::I32GeU => I32GeU(I32 -> I32),
+        Instruction::I64Eq => I64Eq(I64 -> I32),
+        Instruction::I64Ne => I64Ne(I64 -> I32),
+        Instruction::I64LtS => I64LtS(I64 -> I32),
+        Instruction::I64LtU => I64LtU(I64 -> I32),
+        Instruction::I64GtS => I64GtS(I64 -> I32),
+        Instruction::I64GtU => I64GtU(I64 -> I32),
+        Instruction::I64LeS => I64LeS(I64 -> I32),
+        Instruction::I64LeU => I64LeU(I64 -> I32),
+        Instruction::I64GeS => I64GeS(I64 -> I32),
+        Instruction::I64GeU => I64GeU(I64 -> I32),
+        Instruction::F32Eq => F32Eq(F32 -> I32),
+        Instruction::F32Ne => F32Ne(F32 -> I32),
+        Instruction::F32Lt => F32Lt(F32 -> I32),
+        Instruction::F32Gt => F32Gt(F32 -> I32),
+        Instruction::F32Le => F32Le(F32 -> I32),
+        Instruction::F32Ge => F32Ge(F32 -> I32),
+        Instruction::F64Eq => F64Eq(F64 -> I32),
+        Instruction::F64Ne => F64Ne(F64 -> I32),
+        Instruction::F64Lt => F64Lt(F64 -> I32),
+        Instruction::F64Gt => F64Gt(F64 -> I32),
+        Instruction::F64Le => F64Le(F64 -> I32),
+        Instruction::F64Ge => F64Ge(F64 -> I32),
+        Instruction::I32Add => I32Add(I32 -> I32),
+        Instruction::I32Sub => I32Sub(I32 -> I32),
+        Instruction::I32Mul => I32Mul(I32 -> I32),
+        Instruction::I32DivS => I32DivS(I32 -> I32),
+        Instruction::I32DivU => I32DivU(I32 -> I32),
+        Instruction::I32RemS => I32RemS(I32 -> I32),
+        Instruction::I32RemU => I32RemU(I32 -> I32),
+        Instruction::I32And => I32And(I32 -> I32),
+        Instruction::I32Or => I32Or(I32 -> I32),
+        Instruction::I32Xor => I32Xor(I32 -> I32),
+        Instruction::I32Shl => I32Shl(I32 -> I32),
+        Instruction::I32ShrS => I32ShrS(I32 -> I32),
+        Instruction::I32ShrU => I32ShrU(I32 -> I32),
+        Instruction::I32RotL => I32RotL(I32 -> I32),
+        Instruction::I32RotR => I32RotR(I32 -> I32),
+        Instruction::I64Add => I64Add(I64 -> I64),
+        Instruction::I64Sub => I64Sub(I64 -> I64),
+        Instruction::I64Mul => I64Mul(I64 -> I64),
+        Instruction::I64DivS => I64DivS(I64 -> I64),
+        Instruction::I64DivU => I64DivU(I64 -> I64),
+        Instruction::I64RemS => I64RemS(I64 -> I64),
+        Instruction::I64RemU => I64RemU(I64 -> I64),
+        Instruction::I64And => I64And(I64 -> I64),
+        Instruction::I64Or => I64Or(I64 -> I64),
+        Instruction::I64Xor => I64Xor(I64 -> I64),
+        Instruction::I64Shl => I64Shl(I64 -> I64),
+        Instruction::I64ShrS => I64ShrS(I64 -> I64),
+        Instruction::I64ShrU => I64ShrU(I64 -> I64),
+        Instruction::I64RotL => I64RotL(I64 -> I64),
+        Instruction::I64RotR => I64RotR(I64 -> I64),
+        Instruction::F32Add => F32Add(F32 -> F32),
+        Instruction::F32Sub => F32Sub(F32 -> F32),
+        Instruction::F32Mul => F32Mul(F32 -> F32),
+        Instruction::F32Div => F32Div(F32 -> F32),
+        Instruction::F32Min => F32Min(F32 -> F32),
+        Instruction::F32Max => F32Max(F32 -> F32),
+        Instruction::F32CopySign => F32CopySign(F32 -> F32),
+        Instruction::F64Add => F64Add(F64 -> F64),
+        Instruction::F64Sub => F64Sub(F64 -> F64),
+        Instruction::F64Mul => F64Mul(F64 -> F64),
+        Instruction::F64Div => F64Div(F64 -> F64),
+        Instruction::F64Min => F64Min(F64 -> F64),
+        Instruction::F64Max => F64Max(F64 -> F64),
+        Instruction::F64CopySign => F64CopySign(F64 -> F64),
+        Instruction::SIMD(SIMD::V128And) => V128And(V128 -> V128),
+        Instruction::SIMD(SIMD::V128Andnot) => V128Andnot(V128 -> V128),
+        Instruction::SIMD(SIMD::V128Or) => V128Or(V128 -> V128),
+        Instruction::SIMD(SIMD::V128Xor) => V128Xor(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Swizzle) => I8x16Swizzle(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Eq) => I8x16Eq(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Ne) => I8x16Ne(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16LtS) => I8x16LtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16LtU) => I8x16LtU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16GtS) => I8x16GtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16GtU) => I8x16GtU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16LeS) => I8x16LeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16LeU) => I8x16LeU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16GeS) => I8x16GeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16GeU) => I8x16GeU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Eq) => I16x8Eq(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Ne) => I16x8Ne(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8LtS) => I16x8LtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8LtU) => I16x8LtU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8GtS) => I16x8GtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8GtU) => I16x8GtU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8LeS) => I16x8LeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8LeU) => I16x8LeU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8GeS) => I16x8GeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8GeU) => I16x8GeU(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Eq) => I32x4Eq(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Ne) => I32x4Ne(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4LtS) => I32x4LtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4LtU) => I32x4LtU(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4GtS) => I32x4GtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4GtU) => I32x4GtU(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4LeS) => I32x4LeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4LeU) => I32x4LeU(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4GeS) => I32x4GeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4GeU) => I32x4GeU(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Eq) => I64x2Eq(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Ne) => I64x2Ne(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2LtS) => I64x2LtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2GtS) => I64x2GtS(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2LeS) => I64x2LeS(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2GeS) => I64x2GeS(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Eq) => F32x4Eq(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Ne) => F32x4Ne(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Lt) => F32x4Lt(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Gt) => F32x4Gt(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Le) => F32x4Le(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Ge) => F32x4Ge(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Eq) => F64x2Eq(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Ne) => F64x2Ne(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Lt) => F64x2Lt(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Gt) => F64x2Gt(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Le) => F64x2Le(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Ge) => F64x2Ge(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16NarrowI16x8S) => I8x16NarrowI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16NarrowI16x8U) => I8x16NarrowI16x8U(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8NarrowI32x4S) => I16x8NarrowI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8NarrowI32x4U) => I16x8NarrowI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Add) => I8x16Add(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16AddSatS) => I8x16AddSatS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16AddSatU) => I8x16AddSatU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Sub) => I8x16Sub(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16SubSatS) => I8x16SubSatS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16SubSatU) => I8x16SubSatU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16MinS) => I8x16MinS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16MinU) => I8x16MinU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16MaxS) => I8x16MaxS(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16MaxU) => I8x16MaxU(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16AvgrU) => I8x16AvgrU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Add) => I16x8Add(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8AddSatS) => I16x8AddSatS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8AddSatU) => I16x8AddSatU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Sub) => I16x8Sub(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8SubSatS) => I16x8SubSatS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8SubSatU) => I16x8SubSatU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Mul) => I16x8Mul(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8MinS) => I16x8MinS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8MinU) => I16x8MinU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8MaxS) => I16x8MaxS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8MaxU) => I16x8MaxU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8AvgrU) => I16x8AvgrU(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8Q15mulrSatS) => I16x8Q15mulrSatS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtmulLowI8x16S) => I16x8ExtmulLowI8x16S(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtmulHighI8x16S) => I16x8ExtmulHighI8x16S(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtmulLowI8x16U) => I16x8ExtmulLowI8x16U(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8ExtmulHighI8x16U) => I16x8ExtmulHighI8x16U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Add) => I32x4Add(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Sub) => I32x4Sub(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4Mul) => I32x4Mul(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4MinS) => I32x4MinS(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4MinU) => I32x4MinU(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4MaxS) => I32x4MaxS(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4MaxU) => I32x4MaxU(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4DotI16x8S) => I32x4DotI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtmulLowI16x8S) => I32x4ExtmulLowI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtmulHighI16x8S) => I32x4ExtmulHighI16x8S(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtmulLowI16x8U) => I32x4ExtmulLowI16x8U(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4ExtmulHighI16x8U) => I32x4ExtmulHighI16x8U(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Add) => I64x2Add(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Sub) => I64x2Sub(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2Mul) => I64x2Mul(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtmulLowI32x4S) => I64x2ExtmulLowI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtmulHighI32x4S) => I64x2ExtmulHighI32x4S(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtmulLowI32x4U) => I64x2ExtmulLowI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2ExtmulHighI32x4U) => I64x2ExtmulHighI32x4U(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Add) => F32x4Add(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Sub) => F32x4Sub(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Mul) => F32x4Mul(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Div) => F32x4Div(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Min) => F32x4Min(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Max) => F32x4Max(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Pmin) => F32x4Pmin(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4Pmax) => F32x4Pmax(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Add) => F64x2Add(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Sub) => F64x2Sub(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Mul) => F64x2Mul(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Div) => F64x2Div(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Min) => F64x2Min(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Max) => F64x2Max(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Pmin) => F64x2Pmin(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2Pmax) => F64x2Pmax(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16Shl) => I8x16Shl(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I8x16ShrS) => I8x16ShrS(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I8x16ShrU) => I8x16ShrU(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I16x8Shl) => I16x8Shl(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I16x8ShrS) => I16x8ShrS(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I16x8ShrU) => I16x8ShrU(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I32x4Shl) => I32x4Shl(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I32x4ShrS) => I32x4ShrS(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I32x4ShrU) => I32x4ShrU(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I64x2Shl) => I64x2Shl(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I64x2ShrS) => I64x2ShrS(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I64x2ShrU) => I64x2ShrU(V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I8x16ReplaceLane(lane)) => I8x16ReplaceLane[u8 = u8::from(*lane)](V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I16x8ReplaceLane(lane)) => I16x8ReplaceLane[u8 = u8::from(*lane)](V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I32x4ReplaceLane(lane)) => I32x4ReplaceLane[u8 = u8::from(*lane)](V128, I32 -> V128),
+        Instruction::SIMD(SIMD::I64x2ReplaceLane(lane)) => I64x2ReplaceLane[u8 = u8::from(*lane)](V128, I64 -> V128),
+        Instruction::SIMD(SIMD::F32x4ReplaceLane(lane)) => F32x4ReplaceLane[u8 = u8::from(*lane)](V128, F32 -> V128),
+        Instruction::SIMD(SIMD::F64x2ReplaceLane(lane)) => F64x2ReplaceLane[u8 = u8::from(*lane)](V128, F64 -> V128),
+        Instruction::SIMD(SIMD::I8x16RelaxedSwizzle) => I8x16RelaxedSwizzle(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4RelaxedMin) => F32x4RelaxedMin(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4RelaxedMax) => F32x4RelaxedMax(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2RelaxedMin) => F64x2RelaxedMin(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2RelaxedMax) => F64x2RelaxedMax(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8RelaxedQ15mulrS) => I16x8RelaxedQ15mulrS(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8RelaxedDotI8x16I7x16S) => I16x8RelaxedDotI8x16I7x16S(V128 -> V128),
+    }
+
+    ternary {
+        Instruction::SIMD(SIMD::V128Bitselect) => V128Bitselect(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4RelaxedMadd) => F32x4RelaxedMadd(V128 -> V128),
+        Instruction::SIMD(SIMD::F32x4RelaxedNmadd) => F32x4RelaxedNmadd(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2RelaxedMadd) => F64x2RelaxedMadd(V128 -> V128),
+        Instruction::SIMD(SIMD::F64x2RelaxedNmadd) => F64x2RelaxedNmadd(V128 -> V128),
+        Instruction::SIMD(SIMD::I8x16RelaxedLaneselect) => I8x16RelaxedLaneselect(V128 -> V128),
+        Instruction::SIMD(SIMD::I16x8RelaxedLaneselect) => I16x8RelaxedLaneselect(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4RelaxedLaneselect) => I32x4RelaxedLaneselect(V128 -> V128),
+        Instruction::SIMD(SIMD::I64x2RelaxedLaneselect) => I64x2RelaxedLaneselect(V128 -> V128),
+        Instruction::SIMD(SIMD::I32x4RelaxedDotI8x16I7x16AddS) => I32x4RelaxedDotI8x16I7x16AddS(V128 -> V128),
+    }
+
+    load {
+        I32Load(4 -> I32),
+        I64Load(8 -> I64),
+        F32Load(4 -> F32),
+        F64Load(8 -> F64),
+        I32Load8S(1 -> I32),
+        I32Load8U(1 -> I32),
+        I32Load16S(2 -> I32),
+        I32Load16U(2 -> I32),
+        I64Load8S(1 -> I64),
+        I64Load8U(1 -> I64),
+        I64Load16S(2 -> I64),
+        I64Load16U(2 -> I64),
+        I64Load32S(4 -> I64),
+        I64Load32U(4 -> I64),
+    }
+
+    store {
+        I32Store(I32 -> 4),
+        I64Store(I64 -> 8),
+        F32Store(F32 -> 4),
+        F64Store(F64 -> 8),
+        I32Store8(I32 -> 1),
+        I32Store16(I32 -> 2),
+        I64Store8(I64 -> 1),
+        I64Store16(I64 -> 2),
+        I64Store32(I64 -> 4),
+    }
+
+    vector load {
+        V128Load(16 -> V128),
+        V128Load8x8S(8 -> V128),
+        V128Load8x8U(8 -> V128),
+        V128Load16x4S(8 -> V128),
+        V128Load16x4U(8 -> V128),
+        V128Load32x2S(8 -> V128),
+        V128Load32x2U(8 -> V128),
+        V128Load8Splat(1 -> V128),
+        V128Load16Splat(2 -> V128),
+        V128Load32Splat(4 -> V128),
+        V128Load64Splat(8 -> V128),
+        V128Load32Zero(4 -> V128),
+        V128Load64Zero(8 -> V128),
+    }
+
+    vector store {
+        V128Store(V128 -> 16),
+    }
+
+    lane load {
+        V128Load8Lane(1),
+        V128Load16Lane(2),
+        V128Load32Lane(4),
+        V128Load64Lane(8),
+    }
+
+    lane store {
+        V128Store8Lane(1),
+        V128Store16Lane(2),
+        V128Store32Lane(4),
+        V128Store64Lane(8),
+    }
+
+    atomic load {
+        I32Load => I32AtomicLoad(4 -> I32),
+        I64Load => I64AtomicLoad(8 -> I64),
+        I32Load8U => I32AtomicLoad8U(1 -> I32),
+        I32Load16U => I32AtomicLoad16U(2 -> I32),
+        I64Load8U => I64AtomicLoad8U(1 -> I64),
+        I64Load16U => I64AtomicLoad16U(2 -> I64),
+        I64Load32U => I64AtomicLoad32U(4 -> I64),
+    }
+
+    atomic store {
+        I32Store => I32AtomicStore(I32 -> 4),
+        I64Store => I64AtomicStore(I64 -> 8),
+        I32Store8 => I32AtomicStore8(I32 -> 1),
+        I32Store16 => I32AtomicStore16(I32 -> 2),
+        I64Store8 => I64AtomicStore8(I64 -> 1),
+        I64Store16 => I64AtomicStore16(I64 -> 2),
+        I64Store32 => I64AtomicStore32(I64 -> 4),
+    }
+
+    atomic rmw {
+        I32RmwAdd => I32AtomicRmwAdd(I32, 4),
+        I64RmwAdd => I64AtomicRmwAdd(I64, 8),
+        I32Rmw8AddU => I32AtomicRmw8AddU(I32, 1),
+        I32Rmw16AddU => I32AtomicRmw16AddU(I32, 2),
+        I64Rmw8AddU => I64AtomicRmw8AddU(I64, 1),
+        I64Rmw16AddU => I64AtomicRmw16AddU(I64, 2),
+        I64Rmw32AddU => I64AtomicRmw32AddU(I64, 4),
+        I32RmwSub => I32AtomicRmwSub(I32, 4),
+        I64RmwSub => I64AtomicRmwSub(I64, 8),
+        I32Rmw8SubU => I32AtomicRmw8SubU(I32, 1),
+        I32Rmw16SubU => I32AtomicRmw16SubU(I32, 2),
+        I64Rmw8SubU => I64AtomicRmw8SubU(I64, 1),
+        I64Rmw16SubU => I64AtomicRmw16SubU(I64, 2),
+        I64Rmw32SubU => I64AtomicRmw32SubU(I64, 4),
+        I32RmwAnd => I32AtomicRmwAnd(I32, 4),
+        I64RmwAnd => I64AtomicRmwAnd(I64, 8),
+        I32Rmw8AndU => I32AtomicRmw8AndU(I32, 1),
+        I32Rmw16AndU => I32AtomicRmw16AndU(I32, 2),
+        I64Rmw8AndU => I64AtomicRmw8AndU(I64, 1),
+        I64Rmw16AndU => I64AtomicRmw16AndU(I64, 2),
+        I64Rmw32AndU => I64AtomicRmw32AndU(I64, 4),
+        I32RmwOr => I32AtomicRmwOr(I32, 4),
+        I64RmwOr => I64AtomicRmwOr(I64, 8),
+        I32Rmw8OrU => I32AtomicRmw8OrU(I32, 1),
+        I32Rmw16OrU => I32AtomicRmw16OrU(I32, 2),
+        I64Rmw8OrU => I64AtomicRmw8OrU(I64, 1),
+        I64Rmw16OrU => I64AtomicRmw16OrU(I64, 2),
+        I64Rmw32OrU => I64AtomicRmw32OrU(I64, 4),
+        I32RmwXor => I32AtomicRmwXor(I32, 4),
+        I64RmwXor => I64AtomicRmwXor(I64, 8),
+        I32Rmw8XorU => I32AtomicRmw8XorU(I32, 1),
+        I32Rmw16XorU => I32AtomicRmw16XorU(I32, 2),
+        I64Rmw8XorU => I64AtomicRmw8XorU(I64, 1),
+        I64Rmw16XorU => I64AtomicRmw16XorU(I64, 2),
+        I64Rmw32XorU => I64AtomicRmw32XorU(I64, 4),
+        I32RmwXchg => I32AtomicRmwXchg(I32, 4),
+        I64RmwXchg => I64AtomicRmwXchg(I64, 8),
+        I32Rmw8XchgU => I32AtomicRmw8XchgU(I32, 1),
+        I32Rmw16XchgU => I32AtomicRmw16XchgU(I32, 2),
+        I64Rmw8XchgU => I64AtomicRmw8XchgU(I64, 1),
+        I64Rmw16XchgU => I64AtomicRmw16XchgU(I64, 2),
+        I64Rmw32XchgU => I64AtomicRmw32XchgU(I64, 4),
+    }
+
+    atomic cmpxchg {
+        I32RmwCmpXchg => I32AtomicRmwCmpxchg(I32, 4),
+        I64RmwCmpXchg => I64AtomicRmwCmpxchg(I64, 8),
+        I32Rmw8CmpXchgU => I32AtomicRmw8CmpxchgU(I32, 1),
+        I32Rmw16CmpXchgU => I32AtomicRmw16CmpxchgU(I32, 2),
+        I64Rmw8CmpXchgU => I64AtomicRmw8CmpxchgU(I64, 1),
+        I64Rmw16CmpXchgU => I64AtomicRmw16CmpxchgU(I64, 2),
+        I64Rmw32CmpXchgU => I64AtomicRmw32CmpxchgU(I64, 4),
+    }
+}
+
+impl Op {
+    /// Sets the destination of an op that branches, once it is known.
+    pub(crate) fn set_target(&mut self, target: u32) {
+        match self {
+            Op::Jump(pc)
+            | Op::JumpIfZero(pc)
+            | Op::JumpIfNonZero(pc)
+            | Op::JumpUnlessLocalCmpImm { pc, .. }
+            | Op::Br(Branch { pc, .. })
+            | Op::BrIf(Branch { pc, .. })
+            | Op::BrOnNull(Branch { pc, .. })
+            | Op::BrOnNonNull(Branch { pc, .. })
+            | Op::BrTableEntry(Branch { pc, .. }) => *pc = target,
+
+            op => unreachable!("op has no target: `{op:?}`"),
+        }
+    }
+}
@@ -1,13 +1,42 @@
+mod cell;
+pub mod component;
+mod const_expr;
+pub mod func;
+mod gc;
+pub mod host;
 pub mod interpreter;
-pub mod parser;
+mod ir;
+mod lower;
+mod memory;
+pub mod module;
+mod numeric;
+mod register;
+mod simd;
 pub mod store;
+mod table;
 mod types;
 mod values;
+pub mod wasi;
+
+pub use types::{
+    AddrType, ExternType, FuncType, GlobalType, HeapType, Limits, MemType, Mut, NumType, RefType,
+    TableType, ValType, VecType,
+};
+pub use values::{ArrayAddr, ExnAddr, ExternAddr, FuncAddr, Num, Ref, StructAddr, TagAddr, Val, v};
 
 #[cfg(test)]
 mod tests {
-    use interpreter::{EvaluationStatus, Interpreter};
-    use store::Store;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use component::{Component, Resource, Type, Value};
+    use interpreter::{Engine, EvaluationStatus, Interpreter, Trap, Unwind};
+    use module::{Module, ModuleError};
+    use store::{SharedMemory, Store};
+    use types::Binary;
+    use wasi::Wasi;
 
     use super::*;
 
@@ -32,9 +61,9 @@ mod tests {
             )
         };
 
-        let module = parser::parse_all(&program);
+        let module = Module::from_bytes(&program).unwrap();
 
-        let mut store = Store::new(module);
+        let mut store = Store::new(&module).unwrap();
         let mut interpreter = Interpreter::new(&mut store);
 
         let values = interpreter
@@ -60,9 +89,9 @@ mod tests {
             )
         };
 
-        let module = parser::parse_all(&program);
+        let module = Module::from_bytes(&program).unwrap();
 
-        let mut store = Store::new(module);
+        let mut store = Store::new(&module).unwrap();
 
         let mut interpreter = Interpreter::new(&mut store);
 
@@ -98,9 +127,9 @@ mod tests {
             (export "fib" (func $fib)))
         };
 
-        let module = parser::parse_all(&program);
+        let module = Module::from_bytes(&program).unwrap();
 
-        let mut store = Store::new(module);
+        let mut store = Store::new(&module).unwrap();
 
         let mut interpreter = Interpreter::new(&mut store);
 
@@ -108,13 +137,1733 @@ mod tests {
 
         assert_eq!(values, EvaluationStatus::Value(vec![55_i32.into()]));
     }
+
+    #[test]
+    fn loop_with_branches() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func $sum (param $n i32) (result i32) (local $acc i32)
+                    block $done
+                      loop $again
+                        (local.get $n)
+                        (i32.const 1)
+                        (i32.lt_u)
+                        (br_if $done)
+                        (local.get $acc)
+                        (local.get $n)
+                        (i32.add)
+                        (local.set $acc)
+                        (local.get $n)
+                        (i32.const 1)
+                        (i32.sub)
+                        (local.set $n)
+                        (br $again)
+                      end
+                    end
+                    (local.get $acc))
+            (export "sum" (func $sum)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        let mut store = Store::new(&module).unwrap();
+
+        let mut interpreter = Interpreter::new(&mut store);
+
+        let values = interpreter.run("sum", [10_i32.into()]).unwrap();
+
+        assert_eq!(values, EvaluationStatus::Value(vec![55_i32.into()]));
+    }
+
+    #[test]
+    fn branch_table() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func $select (param $idx i32) (result i32)
+                    block $c
+                      block $b
+                        block $a
+                          (i32.const 99)
+                          (local.get $idx)
+                          (br_table $a $b $c)
+                        end
+                        (i32.const 10)
+                        (return)
+                      end
+                      (i32.const 20)
+                      (return)
+                    end
+                    (i32.const 30))
+            (export "select" (func $select)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        let mut store = Store::new(&module).unwrap();
+
+        let mut interpreter = Interpreter::new(&mut store);
+
+        for (idx, expected) in [(0, 10), (1, 20), (2, 30), (7, 30)] {
+            let values = interpreter.run("select", [v(idx)]).unwrap();
+
+            assert_eq!(values, EvaluationStatus::Value(vec![v(expected)]));
+        }
+    }
+
+    #[test]
+    fn register_engine() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func $fib (param $n i32) (result i32)
+                    (i32.lt_u (local.get $n) (i32.const 2))
+                    if (result i32)
+                        (local.get $n)
+                    else
+                        (call $fib (i32.sub (local.get $n) (i32.const 1)))
+                        (call $fib (i32.sub (local.get $n) (i32.const 2)))
+                        (i32.add)
+                    end)
+                (func $sum (param $n i32) (result i32) (local $acc i32)
+                    block $done
+                      loop $again
+                        (br_if $done (i32.eqz (local.get $n)))
+                        (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+                        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                        (br $again)
+                      end
+                    end
+                    (local.get $acc))
+                (func $swap (param $n i32) (result i32) (local $tmp i32)
+                    // The old value of `$n` must be read before it is overwritten.
+                    (local.get $n)
+                    (local.set $n (i32.mul (local.get $n) (i32.const 10)))
+                    (local.set $tmp (i32.add (local.get $n)))
+                    block $out (result i32)
+                      block $zero (result i32)
+                        (i32.const 100)
+                        (local.get $tmp)
+                        (br_table $zero $out (local.get $n))
+                      end
+                      (i32.add (i32.const 1))
+                    end)
+            (export "fib" (func $fib))
+            (export "sum" (func $sum))
+            (export "swap" (func $swap)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        let mut store = Store::new(&module).unwrap();
+
+        let mut interpreter = Interpreter::with_engine(&mut store, Engine::Register);
+
+        let cases = [
+            ("fib", 10, 55),
+            ("sum", 10, 55),
+            ("swap", 0, 1),
+            ("swap", 2, 22),
+        ];
+
+        for (func, arg, expected) in cases {
+            let values = interpreter.run(func, [v(arg)]).unwrap();
+
+            assert_eq!(values, EvaluationStatus::Value(vec![v(expected)]));
+        }
+    }
+
+    #[test]
+    fn memory_globals_and_tables() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (memory 1)
+                (data (i32.const 16) "\01\02\03\04")
+                (global $calls (mut i32) (i32.const 0))
+                (type $binop (func (param i32 i32) (result i32)))
+                (table 2 funcref)
+                (elem (i32.const 0) $add $sub)
+                (func $add (type $binop)
+                    (i32.add (local.get 0) (local.get 1)))
+                (func $sub (type $binop)
+                    (i32.sub (local.get 0) (local.get 1)))
+                (func $apply (param $op i32) (param $lhs i32) (param $rhs i32) (result i32)
+                    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+                    (call_indirect (type $binop) (local.get $lhs) (local.get $rhs) (local.get $op)))
+                (func $load (param $addr i32) (result i32)
+                    (i32.load (local.get $addr)))
+                (func $store (param $addr i32) (param $val i32) (result i32)
+                    (i64.store8 offset=1 (local.get $addr) (i64.extend_i32_u (local.get $val)))
+                    (i32.load8_u offset=1 (local.get $addr)))
+                (func $grow (param $delta i32) (result i32)
+                    (drop (memory.grow (local.get $delta)))
+                    (memory.size))
+                (func $calls (result i32)
+                    (global.get $calls))
+                (func $start
+                    (global.set $calls (i32.const 100)))
+                (start $start)
+            (export "apply" (func $apply))
+            (export "load" (func $load))
+            (export "store" (func $store))
+            (export "grow" (func $grow))
+            (export "calls" (func $calls)))
+        };
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let module = Module::from_bytes(&program).unwrap();
+
+            let mut store = Store::new(&module).unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let cases: [(_, &[i32], _); 7] = [
+                ("apply", &[0, 5, 3], 8),
+                ("apply", &[1, 5, 3], 2),
+                ("calls", &[], 102),
+                ("load", &[16], 0x0403_0201),
+                ("load", &[17], 0x0004_0302),
+                ("store", &[20, 0x1ff], 0xff),
+                ("grow", &[2], 3),
+            ];
+
+            for (func, args, expected) in cases {
+                let values = interpreter
+                    .run(func, args.iter().map(|arg| v(*arg)))
+                    .unwrap();
+
+                assert_eq!(values, EvaluationStatus::Value(vec![v(expected)]));
+            }
+
+            // Out of bounds accesses and calls trap.
+            assert!(interpreter.run("apply", [v(2), v(0), v(0)]).is_err());
+            assert!(interpreter.run("load", [v(3 * 0x1_0000 - 3)]).is_err());
+        }
+    }
+
+    #[test]
+    fn memory64() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (memory (export "memory") i64 1 3)
+                (table (export "table") i64 2 funcref)
+                (data (i64.const 0x10) "\01\02\03\04")
+                (func (export "load") (param i64) (result i32)
+                    (i32.load offset=0x10 (local.get 0)))
+                (func (export "store") (param i64 i32)
+                    (i32.store8 (local.get 0) (local.get 1)))
+                (func (export "grow") (param i64) (result i64)
+                    (memory.grow (local.get 0)))
+                (func (export "size") (result i64)
+                    (i64.add (memory.size) (table.size)))
+                (func (export "fill") (param i64 i64)
+                    (memory.fill (local.get 0) (i32.const 0xff) (local.get 1)))
+            )
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+        let exports = module
+            .exports()
+            .take(2)
+            .map(|export| format!("{} {}", export.name, export.type_))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            exports,
+            ["memory (memory i64 1 3)", "table (table i64 2 funcref)"],
+        );
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let cases: [(_, &[Val], _); 8] = [
+                ("load", &[v(0_i64)], v(0x0403_0201)),
+                ("load", &[v(1_i64)], v(0x0004_0302)),
+                ("size", &[], v(3_i64)),
+                ("grow", &[v(2_i64)], v(1_i64)),
+                ("grow", &[v(1_i64)], v(-1_i64)),
+                ("grow", &[v(1_i64 << 32)], v(-1_i64)),
+                ("size", &[], v(5_i64)),
+                ("load", &[v(3 * 0x1_0000 - 0x14_i64)], v(0)),
+            ];
+
+            for (func, args, expected) in cases {
+                let values = interpreter.run(func, args.iter().copied()).unwrap();
+                assert_eq!(values, EvaluationStatus::Value(vec![expected]), "{func}");
+            }
+
+            // Addresses are not wrapped to 32 bits, and effective addresses
+            // that overflow are out of bounds.
+            assert!(interpreter.run("store", [v(1_i64 << 32), v(0)]).is_err());
+            assert!(interpreter.run("load", [v(-0x10_i64)]).is_err());
+            assert!(interpreter.run("fill", [v(0_i64), v(-1_i64)]).is_err());
+        }
+
+        // The offset of an access to a 32-bit memory must be a 32-bit address,
+        // and the address operands of a 64-bit memory are `i64`s.
+        let offset = inline_wasm! {
+            (module
+                (memory 1)
+                (func (drop (i32.load offset=4294967296 (i32.const 0)))))
+        };
+        assert_eq!(
+            Module::from_bytes(&offset).unwrap_err().to_string(),
+            "invalid module: offset out of range",
+        );
+        let operand = inline_wasm! {
+            (module
+                (memory i64 1)
+                (func (drop (i32.load (i32.const 0)))))
+        };
+        assert!(Module::from_bytes(&operand).is_err());
+
+        // Without a limit, 64-bit memories grow past 4 GiB, and only the pages
+        // that are accessed are allocated.
+        #[rustfmt::skip]
+        let large = inline_wasm! {
+            (module
+                (memory i64 1)
+                (func (export "grow") (param i64) (result i64)
+                    (memory.grow (local.get 0)))
+                (func (export "load") (param i64) (result i64)
+                    (i64.load (local.get 0)))
+                (func (export "store") (param i64 i64)
+                    (i64.store (local.get 0) (local.get 1))))
+        };
+        let module = Module::from_bytes(&large).unwrap();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let cases: [(_, &[Val], _); 3] = [
+                ("grow", &[v(0x1_0000_i64)], v(1_i64)),
+                ("grow", &[v(1_i64 << 48)], v(-1_i64)),
+                ("load", &[v((1_i64 << 32) + 0xfff8)], v(0_i64)),
+            ];
+
+            for (func, args, expected) in cases {
+                let values = interpreter.run(func, args.iter().copied()).unwrap();
+                assert_eq!(values, EvaluationStatus::Value(vec![expected]), "{func}");
+            }
+
+            // Accesses can straddle two pages, one of which was never
+            // accessed.
+            let addr = (1_i64 << 32) - 4;
+            interpreter.run("store", [v(addr), v(-1_i64)]).unwrap();
+            let values = interpreter.run("load", [v(addr - 4)]).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(-0x1_0000_0000_i64)]));
+            assert!(
+                interpreter
+                    .run("load", [v((1_i64 << 32) + 0xfffc)])
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn typed_function_references() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (type $binop (func (param i32 i32) (result i32)))
+                (func $add (type $binop)
+                    (i32.add (local.get 0) (local.get 1)))
+                (global $add (ref null $binop) (ref.func $add))
+                (func (export "apply") (param i32 i32) (result i32)
+                    (call_ref $binop (local.get 0) (local.get 1) (global.get $add)))
+                (func (export "apply-null") (result i32)
+                    (call_ref $binop (i32.const 1) (i32.const 2) (ref.null $binop)))
+                (func (export "get") (result (ref $binop))
+                    (ref.as_non_null (global.get $add)))
+                (func (export "is-null") (param (ref null $binop)) (result i32)
+                    (block $null
+                        (br_on_null $null (local.get 0))
+                        (return (i32.const 0)))
+                    (i32.const 1))
+                (func (export "non-null") (param funcref) (result i32)
+                    (local $func (ref func))
+                    (local.set $func (ref.as_non_null (local.get 0)))
+                    (ref.is_null (local.get $func)))
+            )
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+        let get = module.exports().nth(2).unwrap();
+        assert_eq!(get.type_.to_string(), "(func (result (ref 0)))");
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+            let null = Val::Ref(Ref::Null(RefType::FUNCREF));
+
+            let values = interpreter.run("apply", [v(2), v(3)]).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(5)]));
+            assert_eq!(
+                interpreter.run("apply-null", []).unwrap_err(),
+                Trap::NullFunctionReference,
+            );
+
+            let EvaluationStatus::Value(add) = interpreter.run("get", []).unwrap() else {
+                panic!("`get` didn't return");
+            };
+            let values = interpreter.run("is-null", add.clone()).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(0)]));
+            let values = interpreter.run("is-null", [null]).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(1)]));
+            let values = interpreter.run("non-null", add).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(0)]));
+            assert_eq!(
+                interpreter.run("non-null", [null]).unwrap_err(),
+                Trap::NullReference,
+            );
+        }
+
+        // Non-nullable locals must be set before they are read, in the block
+        // that reads them.
+        let uninitialized = inline_wasm! {
+            (module
+                (func (local (ref func))
+                    (block (local.set 0 (ref.as_non_null (ref.null func))))
+                    (drop (local.get 0))))
+        };
+        assert_eq!(
+            Module::from_bytes(&uninitialized).unwrap_err().to_string(),
+            "invalid module: uninitialized local 0",
+        );
+    }
+
+    #[test]
+    fn typed_functions() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (table 1 funcref)
+                (func $add (param $lhs i32) (param $rhs i32) (result i32)
+                    (i32.add (local.get $lhs) (local.get $rhs)))
+                (func $split (param $n i64) (result i32 i32 f64)
+                    (i32.wrap_i64 (local.get $n))
+                    (i32.wrap_i64 (i64.shr_u (local.get $n) (i64.const 32)))
+                    (f64.convert_i64_s (local.get $n)))
+                (func $swap (param $ref externref) (result funcref externref)
+                    (table.get (i32.const 0))
+                    (local.get $ref))
+                (func $trap
+                    (unreachable))
+            (export "add" (func $add))
+            (export "split" (func $split))
+            (export "swap" (func $swap))
+            (export "trap" (func $trap)))
+        };
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let module = Module::from_bytes(&program).unwrap();
+
+            let mut store = Store::new(&module).unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let add = interpreter
+                .get_typed_func::<(i32, i32), i32>("add")
+                .unwrap();
+            for n in 0..10 {
+                assert_eq!(add.call(&mut interpreter, (n, 1)).unwrap(), n + 1);
+            }
+
+            let split = interpreter
+                .get_typed_func::<i64, (i32, i32, f64)>("split")
+                .unwrap();
+            let results = split.call(&mut interpreter, 0x2_0000_0001).unwrap();
+            assert_eq!(results, (1, 2, 8_589_934_593.0));
+
+            let swap = interpreter
+                .get_typed_func::<Option<ExternAddr>, (Option<FuncAddr>, Option<ExternAddr>)>(
+                    "swap",
+                )
+                .unwrap();
+            let results = swap.call(&mut interpreter, Some(ExternAddr(7))).unwrap();
+            assert_eq!(results, (None, Some(ExternAddr(7))));
+
+            let trap = interpreter.get_typed_func::<(), ()>("trap").unwrap();
+            assert!(trap.call(&mut interpreter, ()).is_err());
+            assert_eq!(add.call(&mut interpreter, (41, 1)).unwrap(), 42);
+
+            // Signatures are checked when the function is looked up.
+            assert_eq!(
+                interpreter.get_typed_func::<i32, i32>("add").err(),
+                Some(func::FuncError::TypeMismatch),
+            );
+            assert_eq!(
+                interpreter.get_typed_func::<(i32, i32), i64>("add").err(),
+                Some(func::FuncError::TypeMismatch),
+            );
+            assert_eq!(
+                interpreter.get_typed_func::<(), ()>("missing").err(),
+                Some(func::FuncError::Unknown("missing".to_owned())),
+            );
+
+            // Functions can't be called with the interpreter of another
+            // store, even one of the same module.
+            let mut other = Store::new(&module).unwrap();
+            let mut other = Interpreter::with_engine(&mut other, engine);
+            assert_eq!(
+                add.call(&mut other, (41, 1)),
+                Err(Unwind::Trap(Trap::StoreMismatch)),
+            );
+        }
+    }
+
+    #[test]
+    fn types_and_values() {
+        let i32 = ValType::NumType(NumType::I32);
+        let funcref = ValType::RefType(RefType::FUNCREF);
+
+        let func = FuncType::new([i32, ValType::NumType(NumType::F64)], [funcref]);
+        assert_eq!(func.to_string(), "(func (param i32 f64) (result funcref))");
+        assert_eq!(FuncType::new([], []).to_string(), "(func)");
+        assert_eq!(FuncType::from_binary(&func.to_binary()), func);
+
+        let limits = Limits {
+            min: 1,
+            max: Some(2),
+        };
+        let global = GlobalType {
+            mut_: Mut::Var,
+            val_type: i32,
+        };
+        let table = TableType {
+            addr_type: AddrType::I32,
+            limits,
+            type_: RefType::EXTERNREF,
+        };
+        let cases = [
+            (
+                ExternType::Func(func),
+                "(func (param i32 f64) (result funcref))",
+            ),
+            (ExternType::Table(table), "(table 1 2 externref)"),
+            (
+                ExternType::Mem(MemType {
+                    addr_type: AddrType::I32,
+                    limits,
+                    shared: false,
+                }),
+                "(memory 1 2)",
+            ),
+            (
+                ExternType::Mem(MemType {
+                    addr_type: AddrType::I32,
+                    limits,
+                    shared: true,
+                }),
+                "(memory 1 2 shared)",
+            ),
+            (ExternType::Global(global), "(global (mut i32))"),
+            (
+                ExternType::Tag(FuncType::new([i32], [])),
+                "(tag (param i32))",
+            ),
+        ];
+        for (type_, expected) in cases {
+            assert_eq!(type_.to_string(), expected);
+        }
+        assert_eq!(GlobalType::from_binary(&global.to_binary()), global);
+        assert_eq!(TableType::from_binary(&table.to_binary()), table);
+
+        let cases = [
+            (v(-1_i32), "(i32.const -1)"),
+            (v(u64::MAX), "(i64.const -1)"),
+            (v(1.5_f32), "(f32.const 1.5)"),
+            (v(-f64::INFINITY), "(f64.const -inf)"),
+            (v(f32::NAN), "(f32.const nan)"),
+            (
+                v(f64::from_bits(0x7ff0_0000_0000_0001)),
+                "(f64.const nan:0x1)",
+            ),
+            (Val::Ref(Ref::Null(RefType::FUNCREF)), "(ref.null func)"),
+            (Val::Ref(Ref::Extern(ExternAddr(5))), "(ref.extern 5)"),
+            (Val::Ref(Ref::Null(RefType::EXNREF)), "(ref.null exn)"),
+            (
+                v(0x0000_0004_0000_0003_0000_0002_ffff_ffff_u128),
+                "(v128.const i32x4 0xffffffff 0x00000002 0x00000003 0x00000004)",
+            ),
+        ];
+        for (val, expected) in cases {
+            assert_eq!(val.to_string(), expected);
+        }
+        assert_eq!(v(1_u32).type_(), i32);
+        assert_eq!(v(1_u128).type_(), ValType::VecType(VecType::V128));
+    }
+
+    #[test]
+    fn gc_structs_and_arrays() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (type $point (struct (field $x i32) (field $y (mut i32))))
+                (type $bytes (array (mut i8)))
+                (global $kept (mut (ref null $point)) (ref.null $point))
+                (func (export "new") (param i32 i32) (result (ref $point))
+                    (struct.new $point (local.get 0) (local.get 1)))
+                (func (export "sum") (param anyref) (result i32)
+                    (local $p (ref $point))
+                    (local.set $p (ref.cast (ref $point) (local.get 0)))
+                    (i32.add
+                        (struct.get $point $x (local.get $p))
+                        (struct.get $point $y (local.get $p))))
+                (func (export "keep") (param (ref $point))
+                    (global.set $kept (local.get 0)))
+                (func (export "kept") (result i32)
+                    (struct.get $point $y (global.get $kept)))
+                (func (export "i31") (param i32) (result i31ref)
+                    (ref.i31 (local.get 0)))
+                (func (export "churn") (param $n i32) (result i32)
+                    (local $last (ref null $bytes))
+                    (loop $alloc
+                        (local.set $last (array.new $bytes (local.get $n) (i32.const 1000)))
+                        (br_if $alloc
+                            (local.tee $n (i32.sub (local.get $n) (i32.const 1)))))
+                    (array.get_u $bytes (local.get $last) (i32.const 999)))
+            )
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+        let new = module.exports().next().unwrap();
+        assert_eq!(
+            new.type_.to_string(),
+            "(func (param i32 i32) (result (ref 0)))"
+        );
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let EvaluationStatus::Value(point) = interpreter.run("new", [v(2), v(3)]).unwrap()
+            else {
+                panic!("`new` didn't return");
+            };
+            assert!(matches!(point[..], [Val::Ref(Ref::Struct(_))]));
+            let values = interpreter.run("sum", point.clone()).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(5)]));
+            interpreter.run("keep", point).unwrap();
+            let EvaluationStatus::Value(dropped) = interpreter.run("new", [v(4), v(5)]).unwrap()
+            else {
+                panic!("`new` didn't return");
+            };
+
+            let values = interpreter.run("i31", [v(-1)]).unwrap();
+            let i31 = Val::Ref(Ref::I31(0x7fff_ffff));
+            assert_eq!(values, EvaluationStatus::Value(vec![i31]));
+            assert_eq!(i31.to_string(), "(ref.i31 2147483647)");
+            assert_eq!(
+                interpreter.run("sum", [i31]).unwrap_err(),
+                Trap::CastFailure
+            );
+
+            // The arrays are garbage as soon as the next one is allocated,
+            // and the structure stored in the global survives the
+            // collections.
+            let values = interpreter.run("churn", [v(3000)]).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(1)]));
+            let values = interpreter.run("kept", []).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(3)]));
+
+            // The host didn't keep the other structure alive, and can't pass
+            // it back once it is collected, even if its address was reused.
+            assert_eq!(
+                interpreter.run("sum", dropped).unwrap_err(),
+                Trap::CollectedReference
+            );
+
+            // The addresses of the collected arrays are reused.
+            drop(interpreter);
+            assert!(store.state.heap.get(3000).is_none());
+        }
+    }
+
+    #[test]
+    fn vector_ops() {
+        let program = inline_wasm! {
+            (module
+              (func (export "add") (param v128 v128) (result v128)
+                (i32x4.add (local.get 0) (local.get 1)))
+              (func (export "sum") (param v128) (result i32)
+                (i32.add
+                  (i32.add
+                    (i32x4.extract_lane 0 (local.get 0))
+                    (i32x4.extract_lane 1 (local.get 0)))
+                  (i32.add
+                    (i32x4.extract_lane 2 (local.get 0))
+                    (i32x4.extract_lane 3 (local.get 0)))))
+            )
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let add = interpreter
+                .get_typed_func::<(u128, u128), u128>("add")
+                .unwrap();
+            let lanes = 0x0000_0004_0000_0003_0000_0002_ffff_ffff;
+            assert_eq!(
+                add.call(&mut interpreter, (lanes, lanes)).unwrap(),
+                0x0000_0008_0000_0006_0000_0004_ffff_fffe,
+            );
+
+            let values = interpreter.run("sum", [Val::V128(lanes)]).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![8_i32.into()]));
+        }
+    }
+
+    #[test]
+    fn relaxed_simd() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func (export "swizzle") (param v128 v128) (result v128)
+                    (i8x16.relaxed_swizzle (local.get 0) (local.get 1)))
+                (func (export "laneselect") (param v128 v128 v128) (result v128)
+                    (i32x4.relaxed_laneselect (local.get 0) (local.get 1) (local.get 2)))
+                (func (export "madd") (param v128 v128 v128) (result v128)
+                    (f64x2.relaxed_madd (local.get 0) (local.get 1) (local.get 2)))
+            )
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        // Each case has the results of the default and the deterministic modes.
+        let swizzle = (
+            u128::MAX << 32 | 0x11_0f_01_00,
+            0x02_00_02_01,
+            0x00_00_02_01,
+        );
+        let mask = 0x8000_0000_ffff_ffff_0000_0000_ffff_0000;
+        let laneselect = (
+            0xaaaa_aaaa_aaaa_aaaa_5555_5555_aaaa_aaaa,
+            0xd555_5555_aaaa_aaaa_5555_5555_aaaa_5555,
+        );
+
+        for deterministic in [false, true] {
+            for engine in [Engine::Stack, Engine::Register] {
+                let mut store = Store::default();
+                store.set_deterministic_relaxed_simd(deterministic);
+                store.instantiate(&module).unwrap();
+                let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+                let expected = if deterministic { swizzle.2 } else { swizzle.1 };
+                let values = interpreter
+                    .run("swizzle", [Val::V128(0x0f_0e_02_01), Val::V128(swizzle.0)])
+                    .unwrap();
+                assert_eq!(values, EvaluationStatus::Value(vec![Val::V128(expected)]));
+
+                let expected = if deterministic {
+                    laneselect.1
+                } else {
+                    laneselect.0
+                };
+                let values = interpreter
+                    .run(
+                        "laneselect",
+                        [
+                            Val::V128(u128::MAX / 3 * 2),
+                            Val::V128(u128::MAX / 3),
+                            Val::V128(mask),
+                        ],
+                    )
+                    .unwrap();
+                assert_eq!(values, EvaluationStatus::Value(vec![Val::V128(expected)]));
+
+                // `0.1 * 10 - 1` is only zero if the product is rounded, which the fused
+                // multiply-add does not do.
+                let splat = |val: f64| Val::V128(u128::from(val.to_bits()) * (1 << 64 | 1));
+                let values = interpreter
+                    .run("madd", [splat(0.1), splat(10.0), splat(-1.0)])
+                    .unwrap();
+                let expected = splat(0.1_f64.mul_add(10.0, -1.0));
+                assert_eq!(values, EvaluationStatus::Value(vec![expected]));
+            }
+        }
+    }
+
+    #[test]
+    fn module_imports_and_exports() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (import "env" "memory" (memory 1))
+                (import "env" "counter" (global $counter (mut i64)))
+                (table $table 2 10 funcref)
+                (func $run (param $n i32) (result i32)
+                    (call $log (local.get $n))
+                    (local.get $n))
+            (export "run" (func $run))
+            (export "table" (table $table))
+            (export "memory" (memory 0))
+            (export "counter" (global $counter)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        let imports = module
+            .imports()
+            .map(|import| format!("{} {} {}", import.module, import.name, import.type_))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            imports,
+            [
+                "env log (func (param i32))",
+                "env memory (memory 1)",
+                "env counter (global (mut i64))",
+            ],
+        );
+
+        let exports = module
+            .exports()
+            .map(|export| format!("{} {}", export.name, export.type_))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            exports,
+            [
+                "run (func (param i32) (result i32))",
+                "table (table 2 10 funcref)",
+                "memory (memory 1)",
+                "counter (global (mut i64))",
+            ],
+        );
+    }
+
+    #[test]
+    fn modules_are_shared() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (global $count (mut i32) (i32.const 0))
+                (func $incr (result i32)
+                    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                    (global.get $count))
+            (export "incr" (func $incr)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        // Each instance has its own state, whichever store it lives in.
+        let threads = (0..4).map(|n| {
+            let module = module.clone();
+
+            std::thread::spawn(move || {
+                let mut store = Store::new(&module).unwrap();
+                store.instantiate(&module).unwrap();
+
+                let mut interpreter = Interpreter::new(&mut store);
+                let incr = interpreter.get_typed_func::<(), i32>("incr").unwrap();
+
+                for _ in 0..n {
+                    incr.call(&mut interpreter, ()).unwrap();
+                }
+
+                incr.call(&mut interpreter, ()).unwrap()
+            })
+        });
+
+        let counts = threads
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [1, 2, 3, 4]);
+
+        assert!(matches!(
+            Module::from_bytes(b"\0asm"),
+            Err(ModuleError::Malformed(_)),
+        ));
+    }
+
+    #[test]
+    fn components_are_not_modules() {
+        let component = Module::from_bytes(b"\0asm\x0d\0\x01\0").unwrap_err();
+        assert!(matches!(component, ModuleError::Component));
+        assert_eq!(
+            component.to_string(),
+            "the binary is a component, not a module"
+        );
+
+        assert!(Component::from_bytes(b"\0asm\x0d\0\x01\0").is_ok());
+        assert!(matches!(
+            Component::from_bytes(b"\0asm\x01\0\0\0"),
+            Err(ModuleError::Malformed(_)),
+        ));
+    }
+
+    #[test]
+    fn shared_memories() {
+        #[rustfmt::skip]
+        let main = inline_wasm! {
+            (module
+                (memory (export "memory") 1 1 shared)
+                (func (export "load") (result i32)
+                    (i32.atomic.load (i32.const 0))))
+        };
+        #[rustfmt::skip]
+        let worker = inline_wasm! {
+            (module
+                (import "env" "memory" (memory 1 1 shared))
+                (func (export "count") (param $n i32)
+                    (loop $loop
+                        (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 1)))
+                        (atomic.fence)
+                        (br_if $loop (local.tee $n (i32.sub (local.get $n) (i32.const 1))))))
+                (func (export "wait") (result i32)
+                    (memory.atomic.wait32 (i32.const 8) (i32.const 0) (i64.const -1)))
+                (func (export "notify") (result i32)
+                    (memory.atomic.notify (i32.const 8) (i32.const 1))))
+        };
+
+        let main = Module::from_bytes(&main).unwrap();
+        let worker = Module::from_bytes(&worker).unwrap();
+
+        let mut store = Store::default();
+        let instance = store.instantiate(&main).unwrap();
+        let memory = store.shared_memory(instance, "memory").unwrap();
+        assert_eq!(memory.type_().to_string(), "1 1 shared");
+
+        let new_store = |memory| {
+            let mut store = Store::default();
+            store.define_memory("env", "memory", memory);
+            store.instantiate(&worker).unwrap();
+            store
+        };
+
+        // The stores of each thread update the same memory.
+        let counters = (0..4).map(|_| {
+            let mut store = new_store(memory.clone());
+            std::thread::spawn(move || {
+                let mut interpreter = Interpreter::new(&mut store);
+                let count = interpreter.get_typed_func::<i32, ()>("count").unwrap();
+                count.call(&mut interpreter, 1000).unwrap();
+            })
+        });
+        for counter in counters.collect::<Vec<_>>() {
+            counter.join().unwrap();
+        }
+
+        let mut interpreter = Interpreter::new(&mut store);
+        let load = interpreter.get_typed_func::<(), i32>("load").unwrap();
+        assert_eq!(load.call(&mut interpreter, ()).unwrap(), 4000);
+
+        // A waiting thread is woken by another one.
+        let mut store = new_store(memory.clone());
+        let waiter = std::thread::spawn(move || {
+            let mut interpreter = Interpreter::new(&mut store);
+            let wait = interpreter.get_typed_func::<(), i32>("wait").unwrap();
+            wait.call(&mut interpreter, ()).unwrap()
+        });
+
+        let mut store = new_store(memory);
+        let mut interpreter = Interpreter::new(&mut store);
+        let notify = interpreter.get_typed_func::<(), i32>("notify").unwrap();
+        while notify.call(&mut interpreter, ()).unwrap() == 0 {
+            std::thread::yield_now();
+        }
+        assert_eq!(waiter.join().unwrap(), 0);
+
+        let unshared = MemType {
+            addr_type: AddrType::I32,
+            limits: Limits {
+                min: 1,
+                max: Some(1),
+            },
+            shared: false,
+        };
+        assert!(SharedMemory::new(unshared).is_none());
+        let memory = SharedMemory::new(MemType {
+            shared: true,
+            ..unshared
+        });
+        assert_eq!(memory.unwrap().type_().to_string(), "1 1 shared");
+    }
+
+    #[test]
+    fn shared_memory_accesses() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (import "env" "peek" (func $peek (param i32) (result i64)))
+                (memory (export "memory") 1 65536 shared)
+                (func (export "grow") (param i32) (result i32)
+                    (memory.grow (local.get 0)))
+                (func (export "store") (param i32 i64)
+                    (i64.store (local.get 0) (local.get 1)))
+                (func (export "load") (param i32) (result i64)
+                    (i64.load (local.get 0)))
+                (func (export "copy") (param i32 i32 i32)
+                    (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+                (func (export "fill") (param i32 i32 i32)
+                    (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+                (func (export "peek") (param i32) (result i64)
+                    (call $peek (local.get 0))))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        // The largest memory is only allocated as it grows.
+        let mut store = Store::default();
+        store.define_func(
+            "env",
+            "peek",
+            &FuncType::new(
+                [ValType::NumType(NumType::I32)],
+                [ValType::NumType(NumType::I64)],
+            ),
+            |caller, args| {
+                let [Val::Num(Num::I32(addr))] = *args else {
+                    unreachable!()
+                };
+                let mut bytes = [0; 8];
+                let memory = caller.memory("memory").unwrap();
+                memory.read(u64::from(addr as u32), &mut bytes)?;
+                Ok(vec![i64::from_le_bytes(bytes).into()])
+            },
+        );
+        store.instantiate(&module).unwrap();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+            let grow = interpreter.get_typed_func::<i32, i32>("grow").unwrap();
+            let store = interpreter
+                .get_typed_func::<(i32, i64), ()>("store")
+                .unwrap();
+            let load = interpreter.get_typed_func::<i32, i64>("load").unwrap();
+            let copy = interpreter
+                .get_typed_func::<(i32, i32, i32), ()>("copy")
+                .unwrap();
+            let fill = interpreter
+                .get_typed_func::<(i32, i32, i32), ()>("fill")
+                .unwrap();
+            let peek = interpreter.get_typed_func::<i32, i64>("peek").unwrap();
+
+            let size = grow.call(&mut interpreter, 1).unwrap();
+            let end = (size + 1) * 0x1_0000;
+
+            // Unaligned accesses may overlap two words, or two pages.
+            let addr = end - 0x1_0003;
+            let val = 0x0102_0304_0506_0708;
+            store.call(&mut interpreter, (addr, val)).unwrap();
+            assert_eq!(load.call(&mut interpreter, addr).unwrap(), val);
+            assert_eq!(peek.call(&mut interpreter, addr).unwrap(), val);
+
+            // Overlapping bytes are copied as if through a buffer.
+            copy.call(&mut interpreter, (addr + 1, addr, 0x2000))
+                .unwrap();
+            assert_eq!(load.call(&mut interpreter, addr + 1).unwrap(), val);
+            assert_eq!(load.call(&mut interpreter, addr).unwrap(), val << 8 | 0x08);
+            copy.call(&mut interpreter, (addr, addr + 1, 0x2000))
+                .unwrap();
+            assert_eq!(load.call(&mut interpreter, addr).unwrap(), val);
+
+            fill.call(&mut interpreter, (end - 12, 0xaa, 12)).unwrap();
+            assert_eq!(
+                load.call(&mut interpreter, end - 8).unwrap(),
+                -0x5555_5555_5555_5556
+            );
+            assert_eq!(
+                load.call(&mut interpreter, end - 4).unwrap_err(),
+                Unwind::Trap(Trap::MemoryOutOfBounds),
+            );
+            assert_eq!(
+                peek.call(&mut interpreter, end - 4).unwrap_err(),
+                Unwind::Trap(Trap::MemoryOutOfBounds),
+            );
+        }
+    }
+
+    #[test]
+    fn uncaught_exceptions() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (import "env" "rethrow" (func $rethrow (param exnref)))
+                (tag $e (export "e") (param i32 f64))
+                (func (export "throw") (param i32)
+                    (throw $e (local.get 0) (f64.const 1.5)))
+                (func (export "rethrow") (param i32) (result i32)
+                    (block $caught (result i32 f64)
+                        (try_table (catch $e $caught)
+                            (block $h (result exnref)
+                                (try_table (catch_all_ref $h)
+                                    (throw $e (local.get 0) (f64.const 0)))
+                                (unreachable))
+                            (call $rethrow))
+                        (unreachable))
+                    (drop)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+        let exnref = ValType::RefType(RefType::EXNREF);
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::default();
+            store.define_func(
+                "env",
+                "rethrow",
+                &FuncType::new([exnref], []),
+                |_, args| match args {
+                    [Val::Ref(Ref::Exn(exn))] => Err(Unwind::Exception(*exn)),
+                    _ => unreachable!(),
+                },
+            );
+            let instance = store.instantiate(&module).unwrap();
+            let tag = store.tag(instance, "e").unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+            let status = interpreter.run("throw", [v(7)]).unwrap();
+            let EvaluationStatus::Exception(exception) = status else {
+                panic!("expected an exception, got {status:?}");
+            };
+            assert_eq!(exception.tag, tag);
+            assert_eq!(exception.payload, [v(7), v(1.5)]);
+            assert_eq!(
+                exception.to_string(),
+                format!(
+                    "uncaught exception of tag {}: (i32.const 7) (f64.const 1.5)",
+                    tag.0
+                ),
+            );
+
+            // Host functions can rethrow the exceptions they are given.
+            assert_eq!(
+                interpreter.run("rethrow", [v(3)]),
+                Ok(EvaluationStatus::Value(vec![v(3)]))
+            );
+
+            let throw = interpreter.get_typed_func::<i32, ()>("throw").unwrap();
+            assert!(matches!(
+                throw.call(&mut interpreter, 1),
+                Err(Unwind::Exception(_))
+            ));
+        }
+
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (tag $e)
+                (func $start (throw $e))
+                (start $start))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+        assert_eq!(
+            Store::new(&module).unwrap_err().to_string(),
+            "start function threw an uncaught exception of tag 0",
+        );
+    }
+
+    #[test]
+    fn bulk_memory_and_data_segments() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (memory 1 2)
+                (data $hello "hello")
+                (func $init (param $dst i32)
+                    (memory.init $hello (local.get $dst) (i32.const 0) (i32.const 5)))
+                (func $copy (param $dst i32) (param $src i32) (param $len i32)
+                    (memory.copy (local.get $dst) (local.get $src) (local.get $len)))
+                (func $fill (param $dst i32) (param $val i32) (param $len i32)
+                    (memory.fill (local.get $dst) (local.get $val) (local.get $len)))
+                (func $drop
+                    (data.drop $hello))
+                (func $load8 (param $addr i32) (result i32)
+                    (i32.load8_u (local.get $addr)))
+                (func $grow (param $delta i32) (result i32)
+                    (memory.grow (local.get $delta)))
+            (export "init" (func $init))
+            (export "copy" (func $copy))
+            (export "fill" (func $fill))
+            (export "drop" (func $drop))
+            (export "load8" (func $load8))
+            (export "grow" (func $grow)))
+        };
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let module = Module::from_bytes(&program).unwrap();
+
+            let mut store = Store::new(&module).unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+            let values = |values: &[i32]| {
+                EvaluationStatus::Value(values.iter().map(|val| v(*val)).collect())
+            };
+            let mut run =
+                |func, args: &[i32]| interpreter.run(func, args.iter().map(|arg| v(*arg)));
+
+            run("init", &[0]).unwrap();
+            run("copy", &[10, 0, 5]).unwrap();
+            run("fill", &[20, 7, 3]).unwrap();
+            assert_eq!(run("load8", &[4]).unwrap(), values(&[i32::from(b'o')]));
+            assert_eq!(run("load8", &[14]).unwrap(), values(&[i32::from(b'o')]));
+            assert_eq!(run("load8", &[22]).unwrap(), values(&[7]));
+
+            // Memories grow up to their maximum.
+            assert_eq!(run("grow", &[1]).unwrap(), values(&[1]));
+            assert_eq!(run("grow", &[1]).unwrap(), values(&[-1]));
+
+            // Bulk accesses out of bounds trap, as do the ones of a dropped
+            // segment.
+            assert!(run("fill", &[0x1_ffff, 0, 2]).is_err());
+            assert!(run("copy", &[0, 0x1_fffe, 3]).is_err());
+            run("drop", &[]).unwrap();
+            assert!(run("init", &[0]).is_err());
+        }
+    }
+
+    #[test]
+    fn tables_and_indirect_calls() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (type $nullary (func (result i32)))
+                (table $table 3 4 funcref)
+                (elem (i32.const 0) $double $seven)
+                (func $double (type $unary)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func $seven (type $nullary)
+                    (i32.const 7))
+                (func $call (param $index i32) (param $arg i32) (result i32)
+                    (call_indirect (type $unary) (local.get $arg) (local.get $index)))
+                (func $grow (result i32)
+                    (table.grow $table (ref.null func) (i32.const 1)))
+                (func $size (result i32)
+                    (table.size $table))
+                (func $set (param $index i32)
+                    (table.set $table (local.get $index) (ref.func $double)))
+            (export "call" (func $call))
+            (export "grow" (func $grow))
+            (export "size" (func $size))
+            (export "set" (func $set)))
+        };
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let module = Module::from_bytes(&program).unwrap();
+
+            let mut store = Store::new(&module).unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+            let values = |values: &[i32]| {
+                EvaluationStatus::Value(values.iter().map(|val| v(*val)).collect())
+            };
+            let mut run =
+                |func, args: &[i32]| interpreter.run(func, args.iter().map(|arg| v(*arg)));
+
+            assert_eq!(run("call", &[0, 21]).unwrap(), values(&[42]));
+
+            // Calls through elements of another type, null elements and
+            // indices out of the table trap.
+            assert!(run("call", &[1, 0]).is_err());
+            assert!(run("call", &[2, 0]).is_err());
+            assert!(run("call", &[3, 0]).is_err());
+
+            // Tables grow up to their maximum, and the new elements can be
+            // set.
+            assert_eq!(run("grow", &[]).unwrap(), values(&[3]));
+            assert_eq!(run("grow", &[]).unwrap(), values(&[-1]));
+            assert_eq!(run("size", &[]).unwrap(), values(&[4]));
+            run("set", &[3]).unwrap();
+            assert_eq!(run("call", &[3, 5]).unwrap(), values(&[10]));
+            assert!(run("set", &[4]).is_err());
+        }
+    }
+
+    #[test]
+    fn globals_and_constant_expressions() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (global $base i32 (i32.const 40))
+                (global $wide (mut i64) (i64.const -1))
+                (global $answer funcref (ref.func $answer))
+                (table 1 funcref)
+                (func $answer (result i32)
+                    (i32.add (global.get $base) (i32.const 2)))
+                (func $indirect (result i32)
+                    (table.set (i32.const 0) (global.get $answer))
+                    (call_indirect (result i32) (i32.const 0)))
+                (func $bump (result i64)
+                    (global.set $wide (i64.add (global.get $wide) (i64.const 2)))
+                    (global.get $wide))
+            (export "indirect" (func $indirect))
+            (export "bump" (func $bump)))
+        };
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let module = Module::from_bytes(&program).unwrap();
+
+            let mut store = Store::new(&module).unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            let values = interpreter.run("indirect", []).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![v(42)]));
+
+            let values = interpreter.run("bump", []).unwrap();
+            assert_eq!(values, EvaluationStatus::Value(vec![1_i64.into()]));
+        }
+    }
+
+    #[test]
+    fn invalid_modules_are_rejected() {
+        let programs = [
+            (
+                inline_wasm! { (module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1)))) },
+                "invalid module: global 0 is immutable",
+            ),
+            (
+                inline_wasm! { (module (func $start (param i32)) (start $start)) },
+                "invalid module: start function must have type [] -> []",
+            ),
+            (
+                inline_wasm! { (module (memory 2 1)) },
+                "invalid module: size minimum must not be greater than maximum",
+            ),
+            (
+                inline_wasm! { (module (func (result i32) (local.get 5))) },
+                "invalid module: unknown local 5",
+            ),
+            (
+                inline_wasm! { (module (global i32 (i32.clz (i32.const 1)))) },
+                "invalid module: constant expression required",
+            ),
+        ];
+
+        for (program, message) in programs {
+            let error = Module::from_bytes(&program).err().unwrap();
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn type_mismatch_is_rejected() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func $f (param $n i64) (result i32)
+                    (local.get $n))
+            (export "f" (func $f)))
+        };
+
+        let error = Module::from_bytes(&program).err().unwrap();
+        assert!(matches!(error, ModuleError::Invalid(_)));
+        assert_eq!(
+            error.to_string(),
+            "invalid module: type mismatch: expected i32, found i64",
+        );
+    }
+
+    #[test]
+    fn integer_division_traps() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func $div (param $lhs i32) (param $rhs i32) (result i32)
+                    (local.get $lhs)
+                    (local.get $rhs)
+                    (i32.div_s))
+            (export "div" (func $div)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        let mut store = Store::new(&module).unwrap();
+
+        let mut interpreter = Interpreter::new(&mut store);
+
+        assert_eq!(
+            interpreter.run("div", [v(1), v(0)]),
+            Err(Trap::IntegerDivideByZero)
+        );
+        assert_eq!(
+            interpreter.run("div", [v(i32::MIN), v(-1)]),
+            Err(Trap::IntegerOverflow)
+        );
+
+        let values = interpreter.run("div", [v(-7), v(2)]).unwrap();
+        assert_eq!(values, EvaluationStatus::Value(vec![v(-3)]));
+    }
+
+    #[test]
+    fn wasi_sandbox() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (import "wasi_snapshot_preview1" "path_open"
+                    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_read"
+                    (func $fd_read (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "proc_exit"
+                    (func $proc_exit (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 32) "in.txt")
+                (data (i32.const 40) "copy.txt")
+                (data (i32.const 48) "../in.txt")
+                (func (export "_start")
+                    (if (i32.ne (i32.const 76) (call $path_open
+                            (i32.const 3) (i32.const 0) (i32.const 48) (i32.const 9)
+                            (i32.const 0) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 20)))
+                        (then (unreachable)))
+                    (if (call $path_open
+                            (i32.const 3) (i32.const 0) (i32.const 32) (i32.const 6)
+                            (i32.const 0) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 20))
+                        (then (unreachable)))
+                    (i32.store (i32.const 0) (i32.const 64))
+                    (i32.store (i32.const 4) (i32.const 32))
+                    (if (call $fd_read (i32.load (i32.const 20)) (i32.const 0) (i32.const 1) (i32.const 16))
+                        (then (unreachable)))
+                    (i32.store (i32.const 4) (i32.load (i32.const 16)))
+                    (if (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16))
+                        (then (unreachable)))
+                    (if (call $path_open
+                            (i32.const 3) (i32.const 0) (i32.const 40) (i32.const 8)
+                            (i32.const 1) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 20))
+                        (then (unreachable)))
+                    (if (call $fd_write (i32.load (i32.const 20)) (i32.const 0) (i32.const 1) (i32.const 16))
+                        (then (unreachable)))
+                    (call $proc_exit (i32.const 3))))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut wasi = Wasi::new();
+            wasi.preopen_dir("/data");
+            wasi.write_file("/data/in.txt", "hello").unwrap();
+
+            let mut store = Store::default();
+            wasi.link(&mut store);
+            store.instantiate(&module).unwrap();
+
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+            assert_eq!(
+                interpreter.run("_start", []),
+                Ok(EvaluationStatus::Exited(3))
+            );
+            assert_eq!(interpreter.backtrace().len(), 0);
+
+            assert_eq!(wasi.stdout(), b"hello");
+            assert_eq!(wasi.read_file("/data/copy.txt").unwrap(), b"hello");
+
+            // Exiting unwinds everything, so the interpreter can be reused.
+            let start = interpreter.get_typed_func::<(), ()>("_start").unwrap();
+            assert_eq!(start.call(&mut interpreter, ()), Err(Unwind::Exit(3)));
+            assert_eq!(wasi.stdout(), b"hellohello");
+        }
+    }
+
+    #[test]
+    fn fuel_and_backtrace() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (module
+                (func $spin (export "spin")
+                    (loop $again (br $again)))
+                (func $fail
+                    (unreachable))
+                (func $outer (export "outer")
+                    (call $fail)))
+        };
+
+        let module = Module::from_bytes(&program).unwrap();
+
+        for engine in [Engine::Stack, Engine::Register] {
+            let mut store = Store::new(&module).unwrap();
+            let mut interpreter = Interpreter::with_engine(&mut store, engine);
+
+            interpreter.set_fuel(Some(10));
+            assert_eq!(interpreter.run("spin", []), Err(Trap::OutOfFuel));
+            assert_eq!(interpreter.fuel(), Some(0));
+
+            interpreter.set_fuel(None);
+            assert_eq!(interpreter.run("outer", []), Err(Trap::Unreachable));
+
+            let names = interpreter
+                .backtrace()
+                .map(|frame| frame.name)
+                .collect::<Vec<_>>();
+            assert_eq!(names, [None, Some("outer")]);
+        }
+    }
+    #[test]
+    fn component_strings_and_records() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (component
+                (import "upper" (func $upper (param "s" string) (result string)))
+                (core module $libc
+                    (memory (export "memory") 1)
+                    (global $next (mut i32) (i32.const 1024))
+                    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                        (local $ptr i32)
+                        (local.set $ptr (i32.and
+                            (i32.add (global.get $next) (i32.sub (local.get 2) (i32.const 1)))
+                            (i32.sub (i32.const 0) (local.get 2))))
+                        (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+                        (local.get $ptr)))
+                (core instance $libc (instantiate $libc))
+                (core func $upper (canon lower (func $upper)
+                    (memory $libc "memory") (realloc (func $libc "realloc"))))
+                (core module $main
+                    (import "libc" "memory" (memory 1))
+                    (import "host" "upper" (func $upper (param i32 i32 i32)))
+                    (func (export "shout") (param i32 i32) (result i32)
+                        (call $upper (local.get 0) (local.get 1) (i32.const 0))
+                        (i32.const 0))
+                    (func (export "swap") (param i32 i32) (result i32)
+                        (i32.store (i32.const 8) (local.get 1))
+                        (i32.store (i32.const 12) (local.get 0))
+                        (i32.const 8)))
+                (core instance $main (instantiate $main
+                    (with "libc" (instance $libc))
+                    (with "host" (instance (export "upper" (func $upper))))))
+                (type $point (record (field "x" s32) (field "y" s32)))
+                (func (export "shout") (param "s" string) (result string)
+                    (canon lift (core func $main "shout")
+                        (memory $libc "memory") (realloc (func $libc "realloc"))))
+                (func (export "swap") (param "p" $point) (result $point)
+                    (canon lift (core func $main "swap") (memory $libc "memory"))))
+        };
+
+        let component = Component::from_bytes(&program).unwrap();
+
+        let mut store = Store::default();
+        store.define_component_func(
+            None,
+            "upper",
+            &component::FuncType::new([("s", Type::String)], Some(Type::String)),
+            |_, args| {
+                let [Value::String(s)] = args else {
+                    unreachable!()
+                };
+                Ok(Some(Value::String(s.to_uppercase())))
+            },
+        );
+        let instance = store.instantiate_component(&component).unwrap();
+
+        let mut interpreter = Interpreter::new(&mut store);
+        let shout = interpreter.run_component(instance, "shout", &[Value::String("héllo".into())]);
+        assert_eq!(shout, Ok(Some(Value::String("HÉLLO".into()))));
+
+        let point = |x, y| {
+            Value::Record(vec![
+                ("x".to_owned(), Value::S32(x)),
+                ("y".to_owned(), Value::S32(y)),
+            ])
+        };
+        let swap = interpreter.run_component(instance, "swap", &[point(1, -2)]);
+        assert_eq!(swap, Ok(Some(point(-2, 1))));
+    }
+
+    #[test]
+    fn component_lists_and_variants() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (component
+                (core module $m
+                    (memory (export "memory") 1)
+                    (global $next (mut i32) (i32.const 1024))
+                    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                        (global.set $next (i32.add (global.get $next) (local.get 3)))
+                        (i32.sub (global.get $next) (local.get 3)))
+                    (func (export "sum") (param $ptr i32) (param $len i32) (result i32)
+                        (local $sum i32)
+                        (if (i32.eqz (local.get $len))
+                            (then
+                                (i32.store8 (i32.const 0) (i32.const 0))
+                                (return (i32.const 0))))
+                        (loop $next
+                            (local.set $sum (i32.add (local.get $sum) (i32.load (local.get $ptr))))
+                            (local.set $ptr (i32.add (local.get $ptr) (i32.const 4)))
+                            (br_if $next (local.tee $len (i32.sub (local.get $len) (i32.const 1)))))
+                        (i32.store8 (i32.const 0) (i32.const 1))
+                        (i32.store (i32.const 4) (local.get $sum))
+                        (i32.const 0))
+                    (func (export "pick") (param i32) (result i32)
+                        (i32.store8 (i32.const 16) (local.get 0))
+                        (i64.store (i32.const 24)
+                            (select (i64.const 7) (i64.const 1099511627776)
+                                (i32.eq (local.get 0) (i32.const 1))))
+                        (i32.const 16))
+                    (func (export "unwrap") (param i32 i32 i32) (result i32)
+                        (select (local.get 2) (local.get 1) (local.get 0)))
+                    (func (export "bad") (result i32)
+                        (i32.store (i32.const 32) (i32.const 40))
+                        (i32.store (i32.const 36) (i32.const 1))
+                        (i32.store8 (i32.const 40) (i32.const 255))
+                        (i32.const 32)))
+                (core instance $m (instantiate $m))
+                (type $size (enum "none" "small" "big"))
+                (type $num (variant (case "none") (case "small" u8) (case "big" u64)))
+                (func (export "sum") (param "xs" (list u32)) (result (option u32))
+                    (canon lift (core func $m "sum")
+                        (memory $m "memory") (realloc (func $m "realloc"))))
+                (func (export "pick") (param "size" $size) (result $num)
+                    (canon lift (core func $m "pick") (memory $m "memory")))
+                (func (export "unwrap") (param "r" (result u32 (error string))) (result u32)
+                    (canon lift (core func $m "unwrap")
+                        (memory $m "memory") (realloc (func $m "realloc"))))
+                (func (export "bad") (result string)
+                    (canon lift (core func $m "bad") (memory $m "memory"))))
+        };
+
+        let component = Component::from_bytes(&program).unwrap();
+        let mut store = Store::default();
+        let instance = store.instantiate_component(&component).unwrap();
+        let mut interpreter = Interpreter::new(&mut store);
+
+        let list = |xs: &[u32]| Value::List(xs.iter().copied().map(Value::U32).collect());
+        let sum = interpreter.run_component(instance, "sum", &[list(&[1, 2, 39])]);
+        assert_eq!(sum, Ok(Some(Value::Option(Some(Box::new(Value::U32(42)))))));
+        let sum = interpreter.run_component(instance, "sum", &[list(&[])]);
+        assert_eq!(sum, Ok(Some(Value::Option(None))));
+
+        let mut pick = |size: &str| {
+            interpreter
+                .run_component(instance, "pick", &[Value::Enum(size.into())])
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(pick("none"), Value::Variant("none".into(), None));
+        let small = Some(Box::new(Value::U8(7)));
+        assert_eq!(pick("small"), Value::Variant("small".into(), small));
+        let big = Some(Box::new(Value::U64(1 << 40)));
+        assert_eq!(pick("big"), Value::Variant("big".into(), big));
+
+        // The payloads of the cases share the flat parameters.
+        let ok = Value::Result(Ok(Some(Box::new(Value::U32(5)))));
+        let unwrap = interpreter.run_component(instance, "unwrap", &[ok]);
+        assert_eq!(unwrap, Ok(Some(Value::U32(5))));
+        let err = Value::Result(Err(Some(Box::new(Value::String("abc".into())))));
+        let unwrap = interpreter.run_component(instance, "unwrap", &[err]);
+        assert_eq!(unwrap, Ok(Some(Value::U32(3))));
+
+        let bad = interpreter.run_component(instance, "bad", &[]);
+        assert_eq!(bad, Err(Unwind::Trap(Trap::InvalidUtf8)));
+    }
+
+    #[test]
+    fn component_resources() {
+        #[rustfmt::skip]
+        let program = inline_wasm! {
+            (component
+                (import "file" (type $file (sub resource)))
+                (import "open" (func $open (result (own $file))))
+                (import "size" (func $size (param "self" (borrow $file)) (result u32)))
+                (type $counter (resource (rep i32)))
+                (core func $open (canon lower (func $open)))
+                (core func $size (canon lower (func $size)))
+                (core func $drop (canon resource.drop $file))
+                (core func $new (canon resource.new $counter))
+                (core module $m
+                    (import "host" "open" (func $open (result i32)))
+                    (import "host" "size" (func $size (param i32) (result i32)))
+                    (import "host" "drop" (func $drop (param i32)))
+                    (import "host" "new" (func $new (param i32) (result i32)))
+                    (func (export "run") (result i32)
+                        (local $file i32)
+                        (local $size i32)
+                        (local.set $file (call $open))
+                        (local.set $size (call $size (local.get $file)))
+                        (call $drop (local.get $file))
+                        (local.get $size))
+                    (func (export "stale")
+                        (call $drop (i32.const 7)))
+                    (func (export "make") (param i32) (result i32)
+                        (call $new (local.get 0)))
+                    (func (export "get") (param i32) (result i32)
+                        (local.get 0)))
+                (core instance $m (instantiate $m
+                    (with "host" (instance
+                        (export "open" (func $open))
+                        (export "size" (func $size))
+                        (export "drop" (func $drop))
+                        (export "new" (func $new))))))
+                (export "counter" (type $counter))
+                (func (export "run") (result u32) (canon lift (core func $m "run")))
+                (func (export "stale") (canon lift (core func $m "stale")))
+                (func (export "make") (param "n" u32) (result (own $counter))
+                    (canon lift (core func $m "make")))
+                (func (export "get") (param "c" (borrow $counter)) (result u32)
+                    (canon lift (core func $m "get"))))
+        };
+
+        let component = Component::from_bytes(&program).unwrap();
+
+        let dropped = Arc::new(AtomicU32::new(0));
+        let mut store = Store::default();
+        let file = store.define_resource(None, "file", {
+            let dropped = dropped.clone();
+            move |_, rep| {
+                dropped.store(rep, Ordering::Relaxed);
+                Ok(())
+            }
+        });
+        store.define_component_func(
+            None,
+            "open",
+            &component::FuncType::new::<&str>([], Some(Type::Own(file))),
+            move |_, _| {
+                Ok(Some(Value::Own(Resource {
+                    type_: file,
+                    rep: 42,
+                })))
+            },
+        );
+        store.define_component_func(
+            None,
+            "size",
+            &component::FuncType::new([("self", Type::Borrow(file))], Some(Type::U32)),
+            |_, args| {
+                let [Value::Borrow(file)] = args else {
+                    unreachable!()
+                };
+                Ok(Some(Value::U32(file.rep + 1)))
+            },
+        );
+        let instance = store.instantiate_component(&component).unwrap();
+        let counter = store.component_resource(instance, "counter").unwrap();
+
+        let mut interpreter = Interpreter::new(&mut store);
+        let run = interpreter.run_component(instance, "run", &[]);
+        assert_eq!(run, Ok(Some(Value::U32(43))));
+        assert_eq!(dropped.load(Ordering::Relaxed), 42);
+
+        let stale = interpreter.run_component(instance, "stale", &[]);
+        assert_eq!(stale, Err(Unwind::Trap(Trap::InvalidHandle)));
+
+        // The component gets the representation of the resources it defines.
+        let resource = Resource {
+            type_: counter,
+            rep: 5,
+        };
+        let make = interpreter.run_component(instance, "make", &[Value::U32(5)]);
+        assert_eq!(make, Ok(Some(Value::Own(resource))));
+        let get = interpreter.run_component(instance, "get", &[Value::Borrow(resource)]);
+        assert_eq!(get, Ok(Some(Value::U32(5))));
+    }
 }
 
 #[cfg(test)]
 mod rust {
     use crate::{
         interpreter::{EvaluationStatus, Interpreter},
-        parser,
+        module::Module,
         store::Store,
         values::v,
     };
@@ -159,9 +1908,9 @@ mod rust {
 
         };
 
-        let module = parser::parse_all(&bytecode);
+        let module = Module::from_bytes(&bytecode).unwrap();
 
-        let mut store = Store::new(module);
+        let mut store = Store::new(&module).unwrap();
 
         let mut interpreter = Interpreter::new(&mut store);
 
@@ -0,0 +1,1818 @@
+//! One-time lowering of function bodies to [`Op`]s.
+//!
+//! The interpreter never scans the instruction stream to find where a branch
+//! lands. Instead, every function body is walked once when the [`Store`] is
+//! built, and every `if`, `else`, `br`, `br_if` and `br_table` gets its
+//! destination resolved to an absolute offset, together with the operand stack
+//! height and arity of the label it targets.
+//!
+//! Lowering also validates the function body: the type of every operand is
+//! tracked, so that the interpreter can store values without their type.
+//!
+//! [`Store`]: crate::store::Store
+
+use std::{
+    fmt::{self, Display},
+    iter,
+};
+
+use wasmbin::{
+    indices::{
+        DataId, ElemId, ExceptionId, FuncId, GlobalId, LabelId, LocalId, MemId, TableId, TypeId,
+    },
+    instructions::{Atomic, BrOnCast, CallIndirect, GC, Instruction, Misc, SIMD, TryTable},
+    sections::FuncBody,
+    types::{BlockType, FieldType, FuncType, StorageType, ValueType},
+};
+
+use crate::{
+    ir::{Branch, Handler, I32Cmp, MemArg, MemoryAccess, Numeric, Op},
+    module::Context,
+    types::{
+        AddrType, Binary, GlobalType, HeapType, Mut, NumType, RefType, TableType, ValType, VecType,
+    },
+};
+
+/// The error returned when a module is not valid.
+#[derive(Debug, PartialEq)]
+pub struct ValidationError(pub(crate) String);
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid module: {}", self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub(crate) type Result<T, E = ValidationError> = std::result::Result<T, E>;
+
+/// Returns a [`ValidationError`] from the current function.
+macro_rules! invalid {
+    ($($arg:tt)*) => {
+        return Err(crate::lower::ValidationError(format!($($arg)*)))
+    };
+}
+
+pub(crate) use invalid;
+
+const I32: ValType = ValType::NumType(NumType::I32);
+const I64: ValType = ValType::NumType(NumType::I64);
+const V128: ValType = ValType::VecType(VecType::V128);
+const EXNREF: ValType = ValType::RefType(RefType::EXNREF);
+const EQREF: ValType = ValType::RefType(RefType::EQREF);
+
+/// A function body lowered by [`lower`].
+pub(crate) struct Lowered {
+    pub(crate) code: Vec<Op>,
+    /// The constants and shuffle indices of the vector ops.
+    pub(crate) v128s: Vec<u128>,
+    /// The [`Handler`]s of the `try_table`s, innermost first.
+    pub(crate) handlers: Vec<Handler>,
+    /// The maximum height of the operand stack, locals included.
+    pub(crate) max_height: usize,
+}
+
+/// Validates `body` and lowers it to a stream of [`Op`]s.
+///
+/// The indices used by the ops are the ones of the module, whose `context`
+/// gives the type of everything `body` may refer to.
+pub(crate) fn lower(body: &FuncBody, type_: &FuncType, context: &Context) -> Result<Lowered> {
+    let locals = type_
+        .params
+        .iter()
+        .chain(
+            body.locals
+                .iter()
+                .flat_map(|locals| std::iter::repeat_n(&locals.ty, locals.repeat as usize)),
+        )
+        .map(|type_| context.check(ValType::from_binary(type_)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Parameters are initialized by the caller, and the other locals by
+    // their default value, if they have one.
+    let inits = locals
+        .iter()
+        .enumerate()
+        .map(|(idx, local)| idx < type_.params.len() || local.is_defaultable())
+        .collect();
+
+    let results = type_
+        .results
+        .iter()
+        .map(ValType::from_binary)
+        .collect::<Vec<_>>();
+
+    let mut lowerer = Lowerer {
+        ops: Vec::with_capacity(body.expr.len() + 1),
+        v128s: Vec::new(),
+        handlers: Vec::new(),
+        labels: Vec::new(),
+        operands: Vec::new(),
+        max_height: locals.len() as u32,
+        fusion_barrier: 0,
+        locals,
+        inits,
+        set_locals: Vec::new(),
+        results: results.clone(),
+        context,
+    };
+
+    lowerer.labels.push(Label {
+        kind: LabelKind::Func,
+        start: 0,
+        height: lowerer.height(),
+        params: Vec::new(),
+        results,
+        dead: false,
+        unreachable: false,
+        set_locals: 0,
+        fixups: Vec::new(),
+    });
+
+    for instr in &body.expr {
+        lowerer.instruction(instr)?;
+    }
+
+    // Falling off the end of the function returns from it.
+    if lowerer.labels.len() != 1 {
+        invalid!("unterminated block");
+    }
+    lowerer.end()?;
+    lowerer.ops.push(Op::Return);
+
+    // A jump to a `return` returns right away, which saves an op on the
+    // paths that end a function, such as the then branch of its last `if`.
+    for idx in 0..lowerer.ops.len() {
+        if let Op::Jump(target) = lowerer.ops[idx]
+            && let Op::Return = lowerer.ops[target as usize]
+        {
+            lowerer.ops[idx] = Op::Return;
+        }
+    }
+
+    // Returning a local is fused, and the `return` after it is only run when
+    // it is jumped to.
+    if type_.results.len() == 1 {
+        let ops = &mut lowerer.ops;
+        for idx in 1..ops.len() {
+            if let (Op::LocalGet(local), Op::Return) = (ops[idx - 1], ops[idx]) {
+                ops[idx - 1] = Op::ReturnLocal(local);
+            }
+        }
+    }
+
+    Ok(Lowered {
+        code: lowerer.ops,
+        v128s: lowerer.v128s,
+        handlers: lowerer.handlers,
+        max_height: lowerer.max_height as usize,
+    })
+}
+
+struct Lowerer<'a> {
+    ops: Vec<Op>,
+    // The constants and shuffle indices of the vector ops.
+    v128s: Vec<u128>,
+    // The `try_table`s lowered so far, which are added when they end.
+    handlers: Vec<Handler>,
+    labels: Vec<Label>,
+    // The type of the operands on the stack, locals excluded. The type of
+    // the operands popped from an empty stack in dead code is unknown.
+    operands: Vec<Option<ValType>>,
+    max_height: u32,
+    // The offset of the first op that may be fused with the next ones. Ops
+    // before a branch destination must not be fused with the ops after it.
+    fusion_barrier: usize,
+    // The type of the parameters and locals of the function.
+    locals: Vec<ValType>,
+    // Whether each local is initialized, which locals of a non-defaultable
+    // type only are once set.
+    inits: Vec<bool>,
+    // The non-defaultable locals that were set, which are uninitialized
+    // again when the label in which they were set ends.
+    set_locals: Vec<u32>,
+    // The result type of the function.
+    results: Vec<ValType>,
+    context: &'a Context,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LabelKind {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+struct Label {
+    kind: LabelKind,
+    // The offset of the first op of a loop, or of the op that jumps to the
+    // else branch of an `if`.
+    start: u32,
+    // The operand stack height when entering the label, parameters excluded.
+    height: u32,
+    // The type of the values the label takes from the stack when entering it.
+    params: Vec<ValType>,
+    // The type of the values left on the stack when the label ends.
+    results: Vec<ValType>,
+    // Whether the label itself is in dead code.
+    dead: bool,
+    // Whether the rest of the label is dead code.
+    unreachable: bool,
+    // The number of locals set before entering the label.
+    set_locals: usize,
+    // Ops whose destination is the end of this label.
+    fixups: Vec<u32>,
+}
+
+impl Label {
+    // The type of the values a branch to this label carries.
+    fn branch_types(&self) -> &[ValType] {
+        match self.kind {
+            LabelKind::Loop => &self.params,
+            _ => &self.results,
+        }
+    }
+
+    fn branch_arity(&self) -> u32 {
+        self.branch_types().len() as u32
+    }
+}
+
+impl<'a> Lowerer<'a> {
+    fn instruction(&mut self, instr: &Instruction) -> Result<()> {
+        if let Some(numeric) = Numeric::from_instruction(instr) {
+            match numeric {
+                Numeric::Unary { op, param, result } => {
+                    self.pop_expect(param)?;
+                    self.push(result);
+                    self.emit(op);
+                }
+
+                Numeric::Binary {
+                    op,
+                    lhs,
+                    rhs,
+                    result,
+                } => {
+                    self.pop_expect(rhs)?;
+                    self.pop_expect(lhs)?;
+                    self.push(result);
+                    self.emit_binary(op);
+                }
+
+                Numeric::Ternary { op, param, result } => {
+                    self.pop_types(&[param; 3])?;
+                    self.push(result);
+                    self.emit(op);
+                }
+            }
+
+            return Ok(());
+        }
+
+        if let Some(access) = MemoryAccess::from_instruction(instr) {
+            let addr = self.memarg(access.op().memarg())?;
+
+            let (op, size, align_log2) = match access {
+                MemoryAccess::Load {
+                    op,
+                    size,
+                    result,
+                    align_log2,
+                } => {
+                    self.pop_expect(addr)?;
+                    self.push(result);
+                    (op, size, align_log2)
+                }
+
+                MemoryAccess::Store {
+                    op,
+                    size,
+                    param,
+                    align_log2,
+                } => {
+                    self.pop_expect(param)?;
+                    self.pop_expect(addr)?;
+                    (op, size, align_log2)
+                }
+
+                MemoryAccess::LoadLane {
+                    op,
+                    size,
+                    align_log2,
+                } => {
+                    self.pop_expect(V128)?;
+                    self.pop_expect(addr)?;
+                    self.push(V128);
+                    (op, size, align_log2)
+                }
+
+                MemoryAccess::StoreLane {
+                    op,
+                    size,
+                    align_log2,
+                } => {
+                    self.pop_expect(V128)?;
+                    self.pop_expect(addr)?;
+                    (op, size, align_log2)
+                }
+
+                // Atomic accesses are always naturally aligned.
+                MemoryAccess::Rmw { op, size, param } => {
+                    self.pop_expect(param)?;
+                    self.pop_expect(addr)?;
+                    self.push(param);
+                    (op, size, size.trailing_zeros())
+                }
+
+                MemoryAccess::Cmpxchg { op, size, param } => {
+                    self.pop_expect(param)?;
+                    self.pop_expect(param)?;
+                    self.pop_expect(addr)?;
+                    self.push(param);
+                    (op, size, size.trailing_zeros())
+                }
+            };
+
+            if align_log2 >= 32 || 1 << align_log2 > size {
+                invalid!("alignment must not be larger than natural");
+            }
+
+            self.emit(op);
+
+            return Ok(());
+        }
+
+        match instr {
+            Instruction::Nop => {}
+
+            Instruction::Unreachable => {
+                self.emit(Op::Unreachable);
+                self.set_unreachable();
+            }
+
+            Instruction::BlockStart(block_type) => self.push_label(LabelKind::Block, block_type)?,
+
+            Instruction::LoopStart(block_type) => {
+                self.push_label(LabelKind::Loop, block_type)?;
+                self.fusion_barrier = self.ops.len();
+            }
+
+            Instruction::IfStart(block_type) => {
+                self.pop_expect(I32)?;
+                self.emit_if();
+                self.push_label(LabelKind::If, block_type)?;
+
+                let label = self.labels.last_mut().unwrap();
+                if !label.dead {
+                    label.start = self.ops.len() as u32 - 1;
+                }
+            }
+
+            Instruction::IfElse => {
+                if self.labels.last().unwrap().kind != LabelKind::If {
+                    invalid!("`else` outside of an `if`");
+                }
+
+                self.check_label_end()?;
+
+                let label = self.labels.last_mut().unwrap();
+
+                // The end of the then branch jumps over the else branch.
+                if !label.unreachable {
+                    label.fixups.push(self.ops.len() as u32);
+                    self.ops.push(Op::Jump(u32::MAX));
+                }
+
+                // A false condition lands at the beginning of the else branch.
+                if !label.dead {
+                    let else_ = self.ops.len() as u32;
+                    self.ops[label.start as usize].set_target(else_);
+                }
+
+                label.kind = LabelKind::Else;
+                label.unreachable = label.dead;
+                let operands = label.height as usize - self.locals.len();
+                let params = label.params.clone();
+                let set_locals = label.set_locals;
+
+                // The locals set by the then branch are not set in the else
+                // branch.
+                self.unset_locals(set_locals);
+
+                // The else branch takes the same parameters as the then
+                // branch.
+                self.operands.truncate(operands);
+                self.push_types(&params);
+                self.fusion_barrier = self.ops.len();
+            }
+
+            Instruction::End => {
+                if self.labels.len() == 1 {
+                    invalid!("unexpected `end`");
+                }
+
+                self.end()?;
+            }
+
+            Instruction::Br(label) => {
+                let types = self.label(*label)?.branch_types().to_vec();
+
+                self.emit_br(*label, false);
+                self.pop_types(&types)?;
+                self.set_unreachable();
+            }
+
+            Instruction::BrIf(label) => {
+                self.pop_expect(I32)?;
+                let types = self.label(*label)?.branch_types().to_vec();
+
+                self.emit_br(*label, true);
+                self.pop_types(&types)?;
+                self.push_types(&types);
+            }
+
+            Instruction::BrTable {
+                branches,
+                otherwise,
+            } => {
+                self.pop_expect(I32)?;
+
+                let arity = self.label(*otherwise)?.branch_arity();
+                for label in branches.iter().chain([otherwise]) {
+                    let types = self.label(*label)?.branch_types().to_vec();
+                    if types.len() as u32 != arity {
+                        invalid!("`br_table` labels have different arities");
+                    }
+
+                    self.pop_types(&types)?;
+                    self.push_types(&types);
+                }
+
+                self.emit(Op::BrTable(branches.len() as u32));
+
+                for label in branches.iter().chain([otherwise]) {
+                    let branch = self.branch(*label);
+                    self.emit(Op::BrTableEntry(branch));
+                }
+
+                self.set_unreachable();
+            }
+
+            Instruction::TryTable(try_table) => self.try_table(try_table)?,
+
+            Instruction::Throw(tag) => {
+                let params = self.tag(*tag)?;
+
+                self.pop_types(&params)?;
+                self.emit(Op::Throw(tag.index));
+                self.set_unreachable();
+            }
+
+            Instruction::ThrowRef => {
+                self.pop_expect(EXNREF)?;
+                self.emit(Op::ThrowRef);
+                self.set_unreachable();
+            }
+
+            Instruction::Return => {
+                self.pop_types(&self.results.clone())?;
+                self.emit(Op::Return);
+                self.set_unreachable();
+            }
+
+            Instruction::Call(func) => {
+                let type_ = self.func_type(*func)?;
+                let params = type_
+                    .params
+                    .iter()
+                    .map(ValType::from_binary)
+                    .collect::<Vec<_>>();
+                let results = type_
+                    .results
+                    .iter()
+                    .map(ValType::from_binary)
+                    .collect::<Vec<_>>();
+
+                self.pop_types(&params)?;
+                self.push_types(&results);
+                self.emit(Op::Call(func.index));
+            }
+
+            Instruction::CallIndirect(call) => {
+                let type_ = self.call_indirect_type(call)?;
+                let params = type_
+                    .params
+                    .iter()
+                    .map(ValType::from_binary)
+                    .collect::<Vec<_>>();
+                let results = type_
+                    .results
+                    .iter()
+                    .map(ValType::from_binary)
+                    .collect::<Vec<_>>();
+
+                self.pop_expect(self.table(call.table)?.addr_type.val_type())?;
+                self.pop_types(&params)?;
+                self.push_types(&results);
+                self.emit(Op::CallIndirect {
+                    type_: call.ty.index,
+                    table: call.table.index,
+                });
+            }
+
+            Instruction::CallRef(type_) => {
+                let func_type = self.context.type_(*type_)?;
+                let params = func_type
+                    .params
+                    .iter()
+                    .map(ValType::from_binary)
+                    .collect::<Vec<_>>();
+                let results = func_type
+                    .results
+                    .iter()
+                    .map(ValType::from_binary)
+                    .collect::<Vec<_>>();
+
+                self.pop_expect(func_ref(type_.index))?;
+                self.pop_types(&params)?;
+                self.push_types(&results);
+                self.emit(Op::CallRef);
+            }
+
+            Instruction::ReturnCall(func) => {
+                let type_ = self.func_type(*func)?;
+                let params = self.tail_call_params(type_)?;
+
+                self.pop_types(&params)?;
+                self.emit(Op::ReturnCall(func.index));
+                self.emit(Op::Return);
+                self.set_unreachable();
+            }
+
+            Instruction::ReturnCallIndirect(call) => {
+                let type_ = self.call_indirect_type(call)?;
+                let params = self.tail_call_params(type_)?;
+
+                self.pop_expect(self.table(call.table)?.addr_type.val_type())?;
+                self.pop_types(&params)?;
+                self.emit(Op::ReturnCallIndirect {
+                    type_: call.ty.index,
+                    table: call.table.index,
+                });
+                self.emit(Op::Return);
+                self.set_unreachable();
+            }
+
+            Instruction::ReturnCallRef(type_) => {
+                let params = self.tail_call_params(self.context.type_(*type_)?)?;
+
+                self.pop_expect(func_ref(type_.index))?;
+                self.pop_types(&params)?;
+                self.emit(Op::ReturnCallRef);
+                self.emit(Op::Return);
+                self.set_unreachable();
+            }
+
+            Instruction::Drop => {
+                self.pop()?;
+                self.emit(Op::Drop);
+            }
+
+            Instruction::Select => {
+                self.pop_expect(I32)?;
+                let rhs = self.pop()?;
+                let lhs = self.pop()?;
+
+                let type_ = match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) if lhs != rhs => {
+                        invalid!("`select` operands have different types")
+                    }
+                    (lhs, rhs) => lhs.or(rhs),
+                };
+
+                if let Some(ValType::RefType(_)) = type_ {
+                    invalid!("untyped `select` on references");
+                }
+
+                self.push_unknown(type_);
+                self.emit(Op::Select);
+            }
+
+            Instruction::SelectWithTypes(types) => {
+                let [type_] = types.as_slice() else {
+                    invalid!("`select` must have exactly one type");
+                };
+                let type_ = self.context.check(ValType::from_binary(type_))?;
+
+                self.pop_expect(I32)?;
+                self.pop_expect(type_)?;
+                self.pop_expect(type_)?;
+                self.push(type_);
+                self.emit(Op::Select);
+            }
+
+            Instruction::LocalGet(local) => {
+                let type_ = self.local(*local)?;
+                if !self.inits[local.index as usize] {
+                    invalid!("uninitialized local {}", local.index);
+                }
+
+                self.push(type_);
+                self.emit(Op::LocalGet(local.index));
+            }
+
+            Instruction::LocalSet(local) => {
+                let type_ = self.local(*local)?;
+                self.pop_expect(type_)?;
+                self.set_local(*local);
+                self.emit_local_set(local.index);
+            }
+
+            Instruction::LocalTee(local) => {
+                let type_ = self.local(*local)?;
+                self.pop_expect(type_)?;
+                self.set_local(*local);
+                self.push(type_);
+                self.emit(Op::LocalTee(local.index));
+            }
+
+            Instruction::I32Const(val) => {
+                self.push(I32);
+                self.emit(Op::I32Const(*val));
+            }
+
+            Instruction::I64Const(val) => {
+                self.push(ValType::NumType(NumType::I64));
+                self.emit(Op::I64Const(*val));
+            }
+
+            Instruction::F32Const(val) => {
+                self.push(ValType::NumType(NumType::F32));
+                self.emit(Op::F32Const(val.value));
+            }
+
+            Instruction::F64Const(val) => {
+                self.push(ValType::NumType(NumType::F64));
+                self.emit(Op::F64Const(val.value));
+            }
+
+            Instruction::SIMD(SIMD::V128Const(bytes)) => {
+                let index = self.v128(u128::from_le_bytes(*bytes));
+                self.push(V128);
+                self.emit(Op::V128Const(index));
+            }
+
+            Instruction::SIMD(SIMD::I8x16Shuffle(lanes)) => {
+                let index = self.v128(u128::from_le_bytes(lanes.map(u8::from)));
+                self.pop_types(&[V128; 2])?;
+                self.push(V128);
+                self.emit(Op::I8x16Shuffle(index));
+            }
+
+            Instruction::RefNull(type_) => {
+                let type_ = self.context.ref_null(type_)?;
+                self.push(ValType::RefType(type_));
+                self.emit(Op::RefNull(type_));
+            }
+
+            Instruction::RefIsNull => {
+                self.pop_ref()?;
+                self.push(I32);
+                self.emit(Op::RefIsNull);
+            }
+
+            Instruction::RefAsNonNull => {
+                let type_ = self.pop_ref()?;
+                self.push_unknown(type_.map(|type_| ValType::RefType(non_null(type_))));
+                self.emit(Op::RefAsNonNull);
+            }
+
+            Instruction::BrOnNull(label) => {
+                let type_ = self.pop_ref()?;
+                let types = self.label(*label)?.branch_types().to_vec();
+
+                self.pop_types(&types)?;
+                self.push_types(&types);
+                let branch = self.branch(*label);
+                self.emit(Op::BrOnNull(branch));
+                self.push_unknown(type_.map(|type_| ValType::RefType(non_null(type_))));
+            }
+
+            Instruction::BrOnNonNull(label) => {
+                let type_ = self.pop_ref()?;
+                let types = self.label(*label)?.branch_types().to_vec();
+
+                // The non-null reference is passed to the label, whose last
+                // type must be a reference type.
+                let [rest @ .., ValType::RefType(_)] = types.as_slice() else {
+                    invalid!("type mismatch: `br_on_non_null` to a label without a reference");
+                };
+
+                self.push_unknown(type_.map(|type_| ValType::RefType(non_null(type_))));
+                self.pop_types(&types)?;
+                self.push_types(rest);
+                let branch = self.branch(*label);
+                self.emit(Op::BrOnNonNull(branch));
+            }
+
+            Instruction::RefFunc(func) => {
+                self.func_type(*func)?;
+                if !self.context.refs.contains(&func.index) {
+                    invalid!("undeclared function reference {}", func.index);
+                }
+
+                // The reference is typed with the type of the function.
+                let type_ = self.context.funcs[func.index as usize];
+                let type_ = RefType::non_nullable(HeapType::Type(type_.index));
+                self.push(ValType::RefType(type_));
+                self.emit(Op::RefFunc(func.index));
+            }
+
+            Instruction::GlobalGet(global) => {
+                let type_ = self.global(*global)?;
+                self.push(type_.val_type);
+                self.emit(Op::GlobalGet(global.index));
+            }
+
+            Instruction::GlobalSet(global) => {
+                let type_ = self.global(*global)?;
+                if type_.mut_ == Mut::Const {
+                    invalid!("global {} is immutable", global.index);
+                }
+
+                self.pop_expect(type_.val_type)?;
+                self.emit(Op::GlobalSet(global.index));
+            }
+
+            Instruction::TableGet(table) => {
+                let TableType {
+                    addr_type, type_, ..
+                } = self.table(*table)?;
+                self.pop_expect(addr_type.val_type())?;
+                self.push(ValType::RefType(type_));
+                self.emit(Op::TableGet(table.index));
+            }
+
+            Instruction::TableSet(table) => {
+                let TableType {
+                    addr_type, type_, ..
+                } = self.table(*table)?;
+                self.pop_expect(ValType::RefType(type_))?;
+                self.pop_expect(addr_type.val_type())?;
+                self.emit(Op::TableSet(table.index));
+            }
+
+            Instruction::Misc(Misc::TableSize(table)) => {
+                let addr_type = self.table(*table)?.addr_type;
+                self.push(addr_type.val_type());
+                self.emit(Op::TableSize(table.index));
+            }
+
+            Instruction::Misc(Misc::TableGrow(table)) => {
+                let TableType {
+                    addr_type, type_, ..
+                } = self.table(*table)?;
+                self.pop_expect(addr_type.val_type())?;
+                self.pop_expect(ValType::RefType(type_))?;
+                self.push(addr_type.val_type());
+                self.emit(Op::TableGrow(table.index));
+            }
+
+            Instruction::Misc(Misc::TableFill(table)) => {
+                let TableType {
+                    addr_type, type_, ..
+                } = self.table(*table)?;
+                self.pop_expect(addr_type.val_type())?;
+                self.pop_expect(ValType::RefType(type_))?;
+                self.pop_expect(addr_type.val_type())?;
+                self.emit(Op::TableFill(table.index));
+            }
+
+            Instruction::Misc(Misc::TableCopy { dest, src }) => {
+                let (dest_type, src_type) = (self.table(*dest)?, self.table(*src)?);
+                if !self.context.matches_ref(src_type.type_, dest_type.type_) {
+                    invalid!("type mismatch: `table.copy` between tables of different types");
+                }
+
+                self.pop_types(&copy_types(dest_type.addr_type, src_type.addr_type))?;
+                self.emit(Op::TableCopy {
+                    dst: dest.index,
+                    src: src.index,
+                });
+            }
+
+            Instruction::Misc(Misc::TableInit { elem, table }) => {
+                let TableType {
+                    addr_type, type_, ..
+                } = self.table(*table)?;
+                if !self.context.matches_ref(self.elem(*elem)?, type_) {
+                    invalid!("type mismatch: `table.init` from a segment of another type");
+                }
+
+                self.pop_types(&[addr_type.val_type(), I32, I32])?;
+                self.emit(Op::TableInit {
+                    elem: elem.index,
+                    table: table.index,
+                });
+            }
+
+            Instruction::Misc(Misc::ElemDrop(elem)) => {
+                self.elem(*elem)?;
+                self.emit(Op::ElemDrop(elem.index));
+            }
+
+            Instruction::MemorySize(memory) => {
+                let addr = self.memory(*memory)?.val_type();
+                self.push(addr);
+                self.emit(Op::MemorySize(memory.index));
+            }
+
+            Instruction::MemoryGrow(memory) => {
+                let addr = self.memory(*memory)?.val_type();
+                self.pop_expect(addr)?;
+                self.push(addr);
+                self.emit(Op::MemoryGrow(memory.index));
+            }
+
+            Instruction::Misc(Misc::MemoryFill(memory)) => {
+                let addr = self.memory(*memory)?.val_type();
+                self.pop_types(&[addr, I32, addr])?;
+                self.emit(Op::MemoryFill(memory.index));
+            }
+
+            Instruction::Misc(Misc::MemoryCopy { dest, src }) => {
+                let (dest_type, src_type) = (self.memory(*dest)?, self.memory(*src)?);
+                self.pop_types(&copy_types(dest_type, src_type))?;
+                self.emit(Op::MemoryCopy {
+                    dst: dest.index,
+                    src: src.index,
+                });
+            }
+
+            Instruction::Misc(Misc::MemoryInit { data, mem }) => {
+                let addr = self.memory(*mem)?.val_type();
+                self.data(*data)?;
+                self.pop_types(&[addr, I32, I32])?;
+                self.emit(Op::MemoryInit {
+                    data: data.index,
+                    memory: mem.index,
+                });
+            }
+
+            Instruction::Misc(Misc::DataDrop(data)) => {
+                self.data(*data)?;
+                self.emit(Op::DataDrop(data.index));
+            }
+
+            Instruction::Atomic(Atomic::Wake(memarg)) => {
+                let addr = self.memarg(MemArg::from(memarg))?;
+                self.pop_types(&[addr, I32])?;
+                self.push(I32);
+                self.emit(Op::MemoryAtomicNotify(MemArg::from(memarg)));
+            }
+
+            Instruction::Atomic(Atomic::I32Wait(memarg)) => {
+                let addr = self.memarg(MemArg::from(memarg))?;
+                self.pop_types(&[addr, I32, I64])?;
+                self.push(I32);
+                self.emit(Op::MemoryAtomicWait32(MemArg::from(memarg)));
+            }
+
+            Instruction::Atomic(Atomic::I64Wait(memarg)) => {
+                let addr = self.memarg(MemArg::from(memarg))?;
+                self.pop_types(&[addr, I64, I64])?;
+                self.push(I32);
+                self.emit(Op::MemoryAtomicWait64(MemArg::from(memarg)));
+            }
+
+            Instruction::Atomic(Atomic::Fence(_)) => self.emit(Op::AtomicFence),
+
+            Instruction::RefEq => {
+                self.pop_types(&[EQREF; 2])?;
+                self.push(I32);
+                self.emit(Op::RefEq);
+            }
+
+            Instruction::GC(instr) => self.gc(instr)?,
+
+            unknown => unreachable!("unknown instruction: `{unknown:?}`"),
+        }
+
+        Ok(())
+    }
+
+    // Lowers an instruction of the GC proposal.
+    fn gc(&mut self, instr: &GC) -> Result<()> {
+        match instr {
+            GC::StructNew(type_) => {
+                let fields = self.context.struct_type(*type_)?;
+                let types = fields.iter().map(unpacked).collect::<Vec<_>>();
+
+                self.pop_types(&types)?;
+                self.push(object_ref(*type_, false));
+                self.emit(Op::StructNew(type_.index));
+            }
+
+            GC::StructNewDefault(type_) => {
+                let fields = self.context.struct_type(*type_)?;
+                if !fields.iter().all(|field| unpacked(field).is_defaultable()) {
+                    invalid!("type mismatch: structure with a non-defaultable field");
+                }
+
+                self.push(object_ref(*type_, false));
+                self.emit(Op::StructNewDefault(type_.index));
+            }
+
+            GC::StructGet { ty, field }
+            | GC::StructGetS { ty, field }
+            | GC::StructGetU { ty, field } => {
+                let type_ = self.field(*ty, *field)?;
+                let packed = !matches!(type_.storage_type, StorageType::Value(_));
+                match instr {
+                    GC::StructGet { .. } if packed => {
+                        invalid!("type mismatch: `struct.get` of packed field {field}")
+                    }
+                    GC::StructGetS { .. } | GC::StructGetU { .. } if !packed => {
+                        invalid!("type mismatch: extending read of unpacked field {field}")
+                    }
+                    _ => {}
+                }
+
+                self.pop_expect(object_ref(*ty, true))?;
+                self.push(unpacked(type_));
+                self.emit(match instr {
+                    GC::StructGet { .. } => Op::StructGet {
+                        type_: ty.index,
+                        field: *field,
+                    },
+                    GC::StructGetS { .. } => Op::StructGetS {
+                        type_: ty.index,
+                        field: *field,
+                    },
+                    _ => Op::StructGetU {
+                        type_: ty.index,
+                        field: *field,
+                    },
+                });
+            }
+
+            GC::StructSet { ty, field } => {
+                let type_ = self.field(*ty, *field)?;
+                if !type_.mutable {
+                    invalid!("field {field} is immutable");
+                }
+
+                self.pop_expect(unpacked(type_))?;
+                self.pop_expect(object_ref(*ty, true))?;
+                self.emit(Op::StructSet {
+                    type_: ty.index,
+                    field: *field,
+                });
+            }
+
+            GC::ArrayNew(type_) => {
+                let elem = unpacked(self.context.array_type(*type_)?);
+
+                self.pop_types(&[elem, I32])?;
+                self.push(object_ref(*type_, false));
+                self.emit(Op::ArrayNew(type_.index));
+            }
+
+            GC::ArrayNewDefault(type_) => {
+                if !unpacked(self.context.array_type(*type_)?).is_defaultable() {
+                    invalid!("type mismatch: array of a non-defaultable type");
+                }
+
+                self.pop_expect(I32)?;
+                self.push(object_ref(*type_, false));
+                self.emit(Op::ArrayNewDefault(type_.index));
+            }
+
+            GC::ArrayNewFixed { ty, len } => {
+                let elem = unpacked(self.context.array_type(*ty)?);
+
+                self.pop_types(&vec![elem; *len as usize])?;
+                self.push(object_ref(*ty, false));
+                self.emit(Op::ArrayNewFixed {
+                    type_: ty.index,
+                    len: *len,
+                });
+            }
+
+            GC::ArrayNewData { ty, data } => {
+                self.numeric_array(*ty)?;
+                self.data(*data)?;
+
+                self.pop_types(&[I32, I32])?;
+                self.push(object_ref(*ty, false));
+                self.emit(Op::ArrayNewData {
+                    type_: ty.index,
+                    data: data.index,
+                });
+            }
+
+            GC::ArrayNewElem { ty, elem } => {
+                self.elem_array(*ty, *elem)?;
+
+                self.pop_types(&[I32, I32])?;
+                self.push(object_ref(*ty, false));
+                self.emit(Op::ArrayNewElem {
+                    type_: ty.index,
+                    elem: elem.index,
+                });
+            }
+
+            GC::ArrayGet(type_) | GC::ArrayGetS(type_) | GC::ArrayGetU(type_) => {
+                let elem = self.context.array_type(*type_)?;
+                let packed = !matches!(elem.storage_type, StorageType::Value(_));
+                match instr {
+                    GC::ArrayGet(_) if packed => {
+                        invalid!("type mismatch: `array.get` of a packed array")
+                    }
+                    GC::ArrayGetS(_) | GC::ArrayGetU(_) if !packed => {
+                        invalid!("type mismatch: extending read of an unpacked array")
+                    }
+                    _ => {}
+                }
+
+                self.pop_types(&[object_ref(*type_, true), I32])?;
+                self.push(unpacked(elem));
+                self.emit(match instr {
+                    GC::ArrayGet(_) => Op::ArrayGet(type_.index),
+                    GC::ArrayGetS(_) => Op::ArrayGetS(type_.index),
+                    _ => Op::ArrayGetU(type_.index),
+                });
+            }
+
+            GC::ArraySet(type_) => {
+                let elem = unpacked(self.mutable_array(*type_)?);
+
+                self.pop_types(&[object_ref(*type_, true), I32, elem])?;
+                self.emit(Op::ArraySet(type_.index));
+            }
+
+            GC::ArrayLen => {
+                self.pop_expect(ValType::RefType(RefType::ARRAYREF))?;
+                self.push(I32);
+                self.emit(Op::ArrayLen);
+            }
+
+            GC::ArrayFill(type_) => {
+                let elem = unpacked(self.mutable_array(*type_)?);
+
+                self.pop_types(&[object_ref(*type_, true), I32, elem, I32])?;
+                self.emit(Op::ArrayFill(type_.index));
+            }
+
+            GC::ArrayCopy { dest, src } => {
+                let dest_elem = self.mutable_array(*dest)?;
+                let src_elem = self.context.array_type(*src)?;
+                let matches = match (&src_elem.storage_type, &dest_elem.storage_type) {
+                    (StorageType::Value(src), StorageType::Value(dest)) => self
+                        .context
+                        .matches(ValType::from_binary(src), ValType::from_binary(dest)),
+                    (src, dest) => src == dest,
+                };
+                if !matches {
+                    invalid!("type mismatch: `array.copy` between arrays of other types");
+                }
+
+                self.pop_types(&[
+                    object_ref(*dest, true),
+                    I32,
+                    object_ref(*src, true),
+                    I32,
+                    I32,
+                ])?;
+                self.emit(Op::ArrayCopy);
+            }
+
+            GC::ArrayInitData { ty, data } => {
+                self.mutable_array(*ty)?;
+                self.numeric_array(*ty)?;
+                self.data(*data)?;
+
+                self.pop_types(&[object_ref(*ty, true), I32, I32, I32])?;
+                self.emit(Op::ArrayInitData {
+                    type_: ty.index,
+                    data: data.index,
+                });
+            }
+
+            GC::ArrayInitElem { ty, elem } => {
+                self.mutable_array(*ty)?;
+                self.elem_array(*ty, *elem)?;
+
+                self.pop_types(&[object_ref(*ty, true), I32, I32, I32])?;
+                self.emit(Op::ArrayInitElem(elem.index));
+            }
+
+            GC::RefTest(type_) | GC::RefTestNull(type_) => {
+                let type_ = self.cast_type(type_, matches!(instr, GC::RefTestNull(_)))?;
+
+                self.pop_expect(self.top(type_))?;
+                self.push(I32);
+                self.emit(Op::RefTest(type_));
+            }
+
+            GC::RefCast(type_) | GC::RefCastNull(type_) => {
+                let type_ = self.cast_type(type_, matches!(instr, GC::RefCastNull(_)))?;
+
+                self.pop_expect(self.top(type_))?;
+                self.push(ValType::RefType(type_));
+                self.emit(Op::RefCast(type_));
+            }
+
+            GC::BrOnCast(cast) | GC::BrOnCastFail(cast) => {
+                self.br_on_cast(cast, matches!(instr, GC::BrOnCastFail(_)))?
+            }
+
+            GC::AnyConvertExtern => self.convert(RefType::EXTERNREF, HeapType::Any)?,
+
+            GC::ExternConvertAny => self.convert(RefType::ANYREF, HeapType::Extern)?,
+
+            GC::RefI31 => {
+                self.pop_expect(I32)?;
+                self.push(ValType::RefType(RefType::non_nullable(HeapType::I31)));
+                self.emit(Op::RefI31);
+            }
+
+            GC::I31GetS | GC::I31GetU => {
+                self.pop_expect(ValType::RefType(RefType::I31REF))?;
+                self.push(I32);
+                self.emit(match instr {
+                    GC::I31GetS => Op::I31GetS,
+                    _ => Op::I31GetU,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Lowers a `br_on_cast`, or a `br_on_cast_fail` if `fail` is set. The
+    // label is given the reference if the cast succeeds, or fails, and the
+    // reference is left on the stack otherwise, with the type it has then.
+    fn br_on_cast(&mut self, cast: &BrOnCast, fail: bool) -> Result<()> {
+        let from = RefType::from_binary(&cast.from);
+        let to = RefType::from_binary(&cast.to);
+        self.context.check(ValType::RefType(from))?;
+        self.context.check(ValType::RefType(to))?;
+        if !self.context.matches_ref(to, from) {
+            invalid!("type mismatch: `br_on_cast` to a type that is not a subtype");
+        }
+
+        // A null reference is cast to a nullable type only.
+        let diff = RefType {
+            nullable: from.nullable && !to.nullable,
+            heap_type: from.heap_type,
+        };
+        let (taken, kept) = if fail { (diff, to) } else { (to, diff) };
+
+        let types = self.label(cast.target)?.branch_types().to_vec();
+        let [rest @ .., ValType::RefType(_)] = types.as_slice() else {
+            invalid!("type mismatch: `br_on_cast` to a label without a reference");
+        };
+
+        self.pop_expect(ValType::RefType(from))?;
+        self.push(ValType::RefType(taken));
+        self.pop_types(&types)?;
+        self.push_types(rest);
+
+        self.emit(if fail {
+            Op::BrOnCastFail(to)
+        } else {
+            Op::BrOnCast(to)
+        });
+        let branch = self.branch(cast.target);
+        self.emit(Op::BrTableEntry(branch));
+        self.push(ValType::RefType(kept));
+
+        Ok(())
+    }
+
+    // Lowers a conversion of a reference of type `from` to the hierarchy of
+    // `to`, which keeps its nullability and its cell.
+    fn convert(&mut self, from: RefType, to: HeapType) -> Result<()> {
+        let type_ = self.pop_ref()?;
+        if let Some(type_) = type_
+            && !self.context.matches_ref(type_, from)
+        {
+            invalid!("type mismatch: expected {from}, found {type_}");
+        }
+
+        self.push(ValType::RefType(RefType {
+            nullable: type_.is_some_and(|type_| type_.nullable),
+            heap_type: to,
+        }));
+
+        Ok(())
+    }
+
+    // Returns the type a reference is tested against or cast to.
+    fn cast_type(&self, type_: &wasmbin::types::HeapType, nullable: bool) -> Result<RefType> {
+        let type_ = RefType {
+            nullable,
+            heap_type: HeapType::from_binary(type_),
+        };
+        self.context.check(ValType::RefType(type_))?;
+
+        Ok(type_)
+    }
+
+    // Returns the top type of the hierarchy of `type_`, of which the
+    // references that are cast are.
+    fn top(&self, type_: RefType) -> ValType {
+        ValType::RefType(RefType::nullable(type_.heap_type.top(self.context)))
+    }
+
+    // Returns the type of the field `field` of the structure type `type_`.
+    fn field(&self, type_: TypeId, field: u32) -> Result<&'a FieldType> {
+        match self.context.struct_type(type_)?.get(field as usize) {
+            Some(field) => Ok(field),
+            None => invalid!("unknown field {field}"),
+        }
+    }
+
+    // Returns the element type of the array type `type_`, which must be
+    // mutable.
+    fn mutable_array(&self, type_: TypeId) -> Result<&'a FieldType> {
+        let elem = self.context.array_type(type_)?;
+        if !elem.mutable {
+            invalid!("array type {} is immutable", type_.index);
+        }
+
+        Ok(elem)
+    }
+
+    // Checks that the elements of the array type `type_` can be read from a
+    // data segment.
+    fn numeric_array(&self, type_: TypeId) -> Result<()> {
+        if let StorageType::Value(ValueType::Ref(_)) = self.context.array_type(type_)?.storage_type
+        {
+            invalid!("type mismatch: array of references initialized from a data segment");
+        }
+
+        Ok(())
+    }
+
+    // Checks that the elements of the array type `type_` can be read from the
+    // element segment `elem`.
+    fn elem_array(&self, type_: TypeId, elem: ElemId) -> Result<()> {
+        let elem = self.elem(elem)?;
+        let matches = match self.context.array_type(type_)?.storage_type {
+            StorageType::Value(ValueType::Ref(ref type_)) => {
+                self.context.matches_ref(elem, RefType::from_binary(type_))
+            }
+            _ => false,
+        };
+
+        if !matches {
+            invalid!("type mismatch: array initialized from a segment of another type");
+        }
+
+        Ok(())
+    }
+
+    fn is_unreachable(&self) -> bool {
+        self.labels.last().unwrap().unreachable
+    }
+
+    fn emit(&mut self, op: Op) {
+        // Dead code is never executed, there is no need to keep it.
+        if !self.is_unreachable() {
+            self.ops.push(op);
+        }
+    }
+
+    // Adds a vector to the pool of the function, and returns its index.
+    fn v128(&mut self, val: u128) -> u32 {
+        self.v128s.push(val);
+        self.v128s.len() as u32 - 1
+    }
+
+    // The ops that can be fused with the next one.
+    fn fusable(&self) -> &[Op] {
+        &self.ops[self.fusion_barrier..]
+    }
+
+    fn emit_binary(&mut self, op: Op) {
+        let fused = match (self.fusable(), op) {
+            ([.., Op::LocalGet(local), Op::I32Const(imm)], Op::I32Add | Op::I32Sub) => {
+                let imm = if op == Op::I32Add {
+                    *imm
+                } else {
+                    imm.wrapping_neg()
+                };
+
+                Some((2, Op::I32LocalAddImm { local: *local, imm }))
+            }
+
+            ([.., Op::I32Const(imm)], Op::I32Add) => Some((1, Op::I32AddImm(*imm))),
+
+            ([.., Op::I32Const(imm)], Op::I32Sub) => Some((1, Op::I32AddImm(imm.wrapping_neg()))),
+
+            ([.., Op::LocalGet(local), Op::I32Const(imm)], op) => I32Cmp::from_op(op).map(|cmp| {
+                let (local, imm) = (*local, *imm);
+                (2, Op::I32LocalCmpImm { local, imm, cmp })
+            }),
+
+            _ => None,
+        };
+
+        self.emit_fused(fused, op);
+    }
+
+    fn emit_local_set(&mut self, dst: u32) {
+        let fused = match self.fusable() {
+            [.., Op::I32LocalAddImm { local, imm }] => Some((
+                1,
+                Op::I32LocalAddImmSet {
+                    src: *local,
+                    dst,
+                    imm: *imm,
+                },
+            )),
+
+            [.., Op::LocalGet(src)] => Some((1, Op::LocalCopy { src: *src, dst })),
+
+            _ => None,
+        };
+
+        self.emit_fused(fused, Op::LocalSet(dst));
+    }
+
+    fn emit_if(&mut self) {
+        let fused = match self.fusable() {
+            [.., Op::I32LocalCmpImm { local, imm, cmp }] => Some((
+                1,
+                Op::JumpUnlessLocalCmpImm {
+                    local: *local,
+                    imm: *imm,
+                    cmp: *cmp,
+                    pc: u32::MAX,
+                },
+            )),
+
+            [.., Op::I32Eqz] => Some((1, Op::JumpIfNonZero(u32::MAX))),
+
+            _ => None,
+        };
+
+        self.emit_fused(fused, Op::JumpIfZero(u32::MAX));
+    }
+
+    // Replaces the last `len` ops by `fused`, or emits `op` if no fusion is
+    // possible.
+    fn emit_fused(&mut self, fused: Option<(usize, Op)>, op: Op) {
+        match fused {
+            Some((len, fused)) if !self.is_unreachable() => {
+                self.ops.truncate(self.ops.len() - len);
+                self.ops.push(fused);
+            }
+
+            _ => self.emit(op),
+        }
+    }
+
+    // The label must exist.
+    fn emit_br(&mut self, label: LabelId, conditional: bool) {
+        let target = &self.labels[self.labels.len() - 1 - label.index as usize];
+
+        // When there is nothing to drop from the stack, the branch is a mere
+        // jump.
+        let is_jump = target.height + target.branch_arity() == self.height();
+
+        // A conditional jump on `i32.eqz` is a jump on zero.
+        let on_zero = conditional
+            && is_jump
+            && !self.is_unreachable()
+            && matches!(self.fusable(), [.., Op::I32Eqz]);
+
+        if on_zero {
+            self.ops.pop();
+        }
+
+        let branch = self.branch(label);
+
+        let op = match (conditional, is_jump) {
+            (false, true) => Op::Jump(branch.pc),
+            (false, false) => Op::Br(branch),
+            (true, true) if on_zero => Op::JumpIfZero(branch.pc),
+            (true, true) => Op::JumpIfNonZero(branch.pc),
+            (true, false) => Op::BrIf(branch),
+        };
+
+        self.emit(op);
+    }
+
+    fn push_label(&mut self, kind: LabelKind, block_type: &BlockType) -> Result<()> {
+        let (params, results) = match block_type {
+            BlockType::Empty => (Vec::new(), Vec::new()),
+            BlockType::Value(type_) => (
+                Vec::new(),
+                vec![self.context.check(ValType::from_binary(type_))?],
+            ),
+            BlockType::MultiValue(type_) => {
+                let type_ = self.context.type_(*type_)?;
+                (
+                    type_.params.iter().map(ValType::from_binary).collect(),
+                    type_.results.iter().map(ValType::from_binary).collect(),
+                )
+            }
+        };
+
+        // The parameters stay on the stack, but they belong to the new label.
+        self.pop_types(&params)?;
+
+        let dead = self.is_unreachable();
+
+        self.labels.push(Label {
+            kind,
+            start: self.ops.len() as u32,
+            height: self.height(),
+            params: params.clone(),
+            results,
+            dead,
+            unreachable: dead,
+            set_locals: self.set_locals.len(),
+            fixups: Vec::new(),
+        });
+
+        self.push_types(&params);
+
+        Ok(())
+    }
+
+    // Resolves the branch made by the next op to the label `label`.
+    //
+    // Forward branches can't know their destination yet, so a fixup is
+    // registered and the destination is set once the end of the label is
+    // reached.
+    fn branch(&mut self, label: LabelId) -> Branch {
+        let next = self.ops.len() as u32;
+        let unreachable = self.is_unreachable();
+        let depth = self.labels.len() - 1 - label.index as usize;
+        let label = &mut self.labels[depth];
+
+        let pc = match label.kind {
+            LabelKind::Loop => label.start,
+            _ => {
+                if !unreachable {
+                    label.fixups.push(next);
+                }
+                u32::MAX
+            }
+        };
+
+        Branch {
+            pc,
+            height: label.height,
+            arity: label.branch_arity(),
+        }
+    }
+
+    // Sets the destination of the forward branches to `label`, which ends at
+    // the next op.
+    fn resolve(&mut self, label: Label) {
+        let pc = self.ops.len() as u32;
+
+        for fixup in label.fixups {
+            self.ops[fixup as usize].set_target(pc);
+        }
+
+        self.fusion_barrier = self.ops.len();
+    }
+
+    // Checks that the stack holds exactly the results of the current label.
+    fn check_label_end(&mut self) -> Result<()> {
+        let label = self.labels.last().unwrap();
+        let (height, results) = (label.height, label.results.clone());
+
+        self.pop_types(&results)?;
+        if self.height() != height {
+            invalid!("values remaining on the stack at the end of a block");
+        }
+
+        self.push_types(&results);
+
+        Ok(())
+    }
+
+    // Ends the current label.
+    fn end(&mut self) -> Result<()> {
+        self.check_label_end()?;
+
+        let mut label = self.labels.pop().unwrap();
+        self.unset_locals(label.set_locals);
+
+        if label.kind == LabelKind::If {
+            // The missing else branch leaves its parameters as they are.
+            if label.params != label.results {
+                invalid!("type mismatch: `if` without `else` must return its parameters");
+            }
+
+            if !label.dead {
+                // No else branch: a false condition lands at the end.
+                label.fixups.push(label.start);
+            }
+        }
+
+        self.resolve(label);
+
+        Ok(())
+    }
+
+    // Lowers a `try_table`, whose body is nested in the instruction rather
+    // than ended by an `end`.
+    fn try_table(&mut self, try_table: &TryTable) -> Result<()> {
+        let start = self.ops.len() as u32;
+        self.emit(Op::TryTable(try_table.catches.len() as u32));
+
+        // The labels of the catch clauses are the ones around the
+        // `try_table`, which are given the payload and the reference.
+        for catch in &try_table.catches {
+            let mut types = match catch.exception_filter {
+                Some(tag) => self.tag(tag)?,
+                None => Vec::new(),
+            };
+            if catch.catch_ref {
+                types.push(EXNREF);
+            }
+
+            if self.label(catch.target)?.branch_types() != types {
+                invalid!("type mismatch: catch clause doesn't match its label");
+            }
+
+            self.emit(match (catch.exception_filter, catch.catch_ref) {
+                (Some(tag), false) => Op::Catch(tag.index),
+                (Some(tag), true) => Op::CatchRef(tag.index),
+                (None, false) => Op::CatchAll,
+                (None, true) => Op::CatchAllRef,
+            });
+
+            let branch = self.branch(catch.target);
+            self.emit(Op::BrTableEntry(branch));
+        }
+
+        self.push_label(LabelKind::Block, &try_table.block_type)?;
+        self.fusion_barrier = self.ops.len();
+
+        let depth = self.labels.len();
+        for instr in &try_table.instructions {
+            self.instruction(instr)?;
+        }
+
+        if self.labels.len() != depth {
+            invalid!("unterminated block");
+        }
+
+        // The clauses only catch the exceptions thrown by the body.
+        let end = self.ops.len() as u32;
+        let dead = self.labels.last().unwrap().dead;
+        self.end()?;
+
+        if !dead {
+            self.handlers.push(Handler { start, end });
+        }
+
+        Ok(())
+    }
+
+    fn label(&self, label: LabelId) -> Result<&Label> {
+        match self.labels.len().checked_sub(label.index as usize + 1) {
+            Some(depth) => Ok(&self.labels[depth]),
+            None => invalid!("unknown label {}", label.index),
+        }
+    }
+
+    fn local(&self, local: LocalId) -> Result<ValType> {
+        match self.locals.get(local.index as usize) {
+            Some(type_) => Ok(*type_),
+            None => invalid!("unknown local {}", local.index),
+        }
+    }
+
+    fn func_type(&self, func: FuncId) -> Result<&'a FuncType> {
+        match self.context.funcs.get(func.index as usize) {
+            Some(type_) => Ok(self.context.func_type(type_.index)),
+            None => invalid!("unknown function {}", func.index),
+        }
+    }
+
+    fn call_indirect_type(&self, call: &CallIndirect) -> Result<&'a FuncType> {
+        let table = self.table(call.table)?;
+        if !self.context.matches_ref(table.type_, RefType::FUNCREF) {
+            invalid!(
+                "type mismatch: `call_indirect` on a table of {}",
+                table.type_
+            );
+        }
+
+        self.context.type_(call.ty)
+    }
+
+    // Returns the parameters of a function of type `type_` that is tail
+    // called, whose results become the ones of the current function.
+    fn tail_call_params(&self, type_: &FuncType) -> Result<Vec<ValType>> {
+        let results = type_.results.iter().map(ValType::from_binary);
+        if results.len() != self.results.len()
+            || !iter::zip(results, &self.results)
+                .all(|(result, expected)| self.context.matches(result, *expected))
+        {
+            invalid!("type mismatch: tail call to a function with other results");
+        }
+
+        Ok(type_.params.iter().map(ValType::from_binary).collect())
+    }
+
+    // Returns the type of the payload of the exceptions of `tag`.
+    fn tag(&self, tag: ExceptionId) -> Result<Vec<ValType>> {
+        match self.context.tags.get(tag.index as usize) {
+            Some(type_) => {
+                let type_ = self.context.func_type(type_.index);
+                Ok(type_.params.iter().map(ValType::from_binary).collect())
+            }
+            None => invalid!("unknown tag {}", tag.index),
+        }
+    }
+
+    fn global(&self, global: GlobalId) -> Result<GlobalType> {
+        match self.context.globals.get(global.index as usize) {
+            Some(type_) => Ok(*type_),
+            None => invalid!("unknown global {}", global.index),
+        }
+    }
+
+    fn table(&self, table: TableId) -> Result<TableType> {
+        match self.context.tables.get(table.index as usize) {
+            Some(type_) => Ok(*type_),
+            None => invalid!("unknown table {}", table.index),
+        }
+    }
+
+    // Returns the type of the addresses of `memory`.
+    fn memory(&self, memory: MemId) -> Result<AddrType> {
+        match self.context.memories.get(memory.index as usize) {
+            Some(type_) => Ok(type_.addr_type),
+            None => invalid!("unknown memory {}", memory.index),
+        }
+    }
+
+    // Returns the type of the address operand of an access with `memarg`,
+    // whose offset must be a valid address.
+    fn memarg(&self, memarg: MemArg) -> Result<ValType> {
+        let addr_type = self.memory(MemId::from(memarg.memory))?;
+        if addr_type == AddrType::I32 && memarg.offset > u32::MAX.into() {
+            invalid!("offset out of range");
+        }
+
+        Ok(addr_type.val_type())
+    }
+
+    fn elem(&self, elem: ElemId) -> Result<RefType> {
+        match self.context.elems.get(elem.index as usize) {
+            Some(type_) => Ok(*type_),
+            None => invalid!("unknown elem segment {}", elem.index),
+        }
+    }
+
+    fn data(&self, data: DataId) -> Result<()> {
+        let Some(count) = self.context.datas else {
+            invalid!("data count section required");
+        };
+
+        if data.index >= count {
+            invalid!("unknown data segment {}", data.index);
+        }
+
+        Ok(())
+    }
+
+    fn set_unreachable(&mut self) {
+        let label = self.labels.last_mut().unwrap();
+        label.unreachable = true;
+        let operands = label.height as usize - self.locals.len();
+        self.operands.truncate(operands);
+    }
+
+    // The current height of the operand stack, relative to the frame pointer.
+    fn height(&self) -> u32 {
+        (self.locals.len() + self.operands.len()) as u32
+    }
+
+    fn push(&mut self, type_: ValType) {
+        self.push_unknown(Some(type_));
+    }
+
+    fn push_unknown(&mut self, type_: Option<ValType>) {
+        self.operands.push(type_);
+        self.max_height = self.max_height.max(self.height());
+    }
+
+    fn push_types(&mut self, types: &[ValType]) {
+        for type_ in types {
+            self.push(*type_);
+        }
+    }
+
+    fn pop(&mut self) -> Result<Option<ValType>> {
+        let label = self.labels.last().unwrap();
+
+        if self.height() == label.height {
+            // Dead code may pop values that were never pushed.
+            if label.unreachable {
+                return Ok(None);
+            }
+
+            invalid!("type mismatch: the operand stack is empty");
+        }
+
+        Ok(self.operands.pop().unwrap())
+    }
+
+    fn pop_expect(&mut self, expected: ValType) -> Result<()> {
+        match self.pop()? {
+            Some(actual) if !self.context.matches(actual, expected) => {
+                invalid!("type mismatch: expected {expected}, found {actual}")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn pop_types(&mut self, types: &[ValType]) -> Result<()> {
+        for type_ in types.iter().rev() {
+            self.pop_expect(*type_)?;
+        }
+
+        Ok(())
+    }
+
+    // Pops a reference, whose type is unknown in dead code.
+    fn pop_ref(&mut self) -> Result<Option<RefType>> {
+        match self.pop()? {
+            Some(ValType::RefType(type_)) => Ok(Some(type_)),
+            Some(type_) => invalid!("type mismatch: expected a reference, found {type_}"),
+            None => Ok(None),
+        }
+    }
+
+    // Marks `local` as initialized until the end of the current label.
+    fn set_local(&mut self, local: LocalId) {
+        let init = &mut self.inits[local.index as usize];
+        if !*init {
+            *init = true;
+            self.set_locals.push(local.index);
+        }
+    }
+
+    // Marks the locals set after the first `len` ones as uninitialized.
+    fn unset_locals(&mut self, len: usize) {
+        for local in self.set_locals.drain(len..) {
+            self.inits[local as usize] = false;
+        }
+    }
+}
+
+/// The type of the references to functions of type `type_` that `call_ref`
+/// calls.
+fn func_ref(type_: u32) -> ValType {
+    ValType::RefType(RefType::nullable(HeapType::Type(type_)))
+}
+
+fn non_null(type_: RefType) -> RefType {
+    RefType::non_nullable(type_.heap_type)
+}
+
+/// The type of the references to the structures or arrays of type `type_`.
+pub(crate) fn object_ref(type_: TypeId, nullable: bool) -> ValType {
+    ValType::RefType(RefType {
+        nullable,
+        heap_type: HeapType::Type(type_.index),
+    })
+}
+
+/// The type of the values of a field of type `type_`, packed fields being
+/// read and written as `i32`s.
+pub(crate) fn unpacked(type_: &FieldType) -> ValType {
+    match &type_.storage_type {
+        StorageType::Value(type_) => ValType::from_binary(type_),
+        StorageType::I8 | StorageType::I16 => I32,
+    }
+}
+
+/// The operand types of a copy from a memory or table whose addresses are of
+/// type `src` to one whose addresses are of type `dst`, whose length is only
+/// an `i64` if both are.
+fn copy_types(dst: AddrType, src: AddrType) -> [ValType; 3] {
+    let len = match (dst, src) {
+        (AddrType::I64, AddrType::I64) => I64,
+        _ => I32,
+    };
+
+    [dst.val_type(), src.val_type(), len]
+}
@@ -0,0 +1,294 @@
+//! Runs a function exported by a module from the command line.
+
+use std::{fmt::Display, process::ExitCode};
+
+use exwasmable::{
+    ExternAddr, ExternType, HeapType, Num, NumType, Ref, RefType, Val, ValType,
+    interpreter::{Engine, EvaluationStatus, Interpreter},
+    module::Module,
+    store::Store,
+    wasi::Wasi,
+};
+
+const USAGE: &str = "\
+Usage: exwasmable [OPTIONS] <FILE> <FUNC> [ARGS]...
+       exwasmable --wasi [OPTIONS] <FILE> [ARGS]...
+
+Runs the function FUNC exported by the module FILE with ARGS, which are parsed
+according to the signature of FUNC, and prints its results.
+
+With `--wasi`, runs the WASI program FILE instead, with the arguments ARGS.
+
+Options:
+      --fuel <N>            Traps after N calls and loop iterations
+      --max-memory <PAGES>  Limits the size of each memory to PAGES pages
+      --engine <ENGINE>     Runs functions with the `stack` or the `register` engine
+      --trace               Prints each executed instruction to the standard error
+      --wasi                Runs the `_start` function of a WASI program
+      --dir <DIR>           Gives a WASI program the host directory DIR, which is
+                            written `HOST::GUEST` to give HOST at the path GUEST
+      --env <KEY=VALUE>     Sets an environment variable of a WASI program
+  -h, --help                Prints this message
+";
+
+/// The options given on the command line.
+#[derive(Debug, Default)]
+struct Options {
+    fuel: Option<u64>,
+    max_memory: Option<u64>,
+    engine: Engine,
+    trace: bool,
+    wasi: bool,
+    /// The preopened directories, as a host path and a guest path.
+    dirs: Vec<(String, String)>,
+    env: Vec<(String, String)>,
+    file: String,
+    func: String,
+    args: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parses the command line arguments, or returns `None` if the help is
+/// requested.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for `{name}`"));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--fuel" => options.fuel = Some(parse_number(&value("--fuel")?)?),
+            "--max-memory" => options.max_memory = Some(parse_number(&value("--max-memory")?)?),
+            "--engine" => {
+                options.engine = match value("--engine")?.as_str() {
+                    "stack" => Engine::Stack,
+                    "register" => Engine::Register,
+                    engine => return Err(format!("unknown engine `{engine}`")),
+                }
+            }
+            "--trace" => options.trace = true,
+            "--wasi" => options.wasi = true,
+            "--dir" => {
+                let dir = value("--dir")?;
+                options.dirs.push(match dir.split_once("::") {
+                    Some((host, guest)) => (host.to_owned(), guest.to_owned()),
+                    None => (dir.clone(), dir),
+                });
+            }
+            "--env" => {
+                let var = value("--env")?;
+                let (key, value) = var
+                    .split_once('=')
+                    .ok_or(format!("invalid environment variable `{var}`"))?;
+                options.env.push((key.to_owned(), value.to_owned()));
+            }
+            // Everything after the function name is an argument of the
+            // function, even if it looks like an option.
+            "--" => positional.extend(args.by_ref()),
+            arg if arg.starts_with("--") && positional.len() < 2 => {
+                return Err(format!("unknown option `{arg}`"));
+            }
+            _ => {
+                positional.push(arg);
+
+                // A WASI program has no function name.
+                if positional.len() == if options.wasi { 1 } else { 2 } {
+                    positional.extend(args.by_ref());
+                }
+            }
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    options.file = positional.next().ok_or("missing module file")?;
+    options.func = match options.wasi {
+        true => "_start".to_owned(),
+        false => positional.next().ok_or("missing function name")?,
+    };
+    options.args = positional.collect();
+
+    Ok(Some(options))
+}
+
+fn parse_number<T: std::str::FromStr<Err: Display>>(val: &str) -> Result<T, String> {
+    val.parse()
+        .map_err(|error| format!("invalid number `{val}`: {error}"))
+}
+
+/// Runs the function, and returns the exit code of the process.
+fn run(options: &Options) -> Result<ExitCode, String> {
+    let bytes = std::fs::read(&options.file)
+        .map_err(|error| format!("can't read `{}`: {error}", options.file))?;
+    let module = load(&bytes)?;
+
+    let type_ = module
+        .exports()
+        .find_map(|export| match export.type_ {
+            ExternType::Func(type_) if export.name == options.func => Some(type_),
+            _ => None,
+        })
+        .ok_or_else(|| format!("the module exports no function `{}`", options.func))?;
+
+    // The arguments of a WASI program are given to it through WASI rather
+    // than as parameters.
+    let params = if options.wasi { &[] } else { &options.args[..] };
+    if params.len() != type_.params().len() {
+        return Err(format!(
+            "`{}` expects {} arguments, got {}",
+            options.func,
+            type_.params().len(),
+            params.len()
+        ));
+    }
+
+    let args = std::iter::zip(params, type_.params())
+        .map(|(arg, type_)| parse_val(arg, *type_))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut store = Store::default();
+    store.set_memory_limit(options.max_memory);
+
+    if options.wasi {
+        let mut wasi = Wasi::new();
+        wasi.set_args(std::iter::once(&options.file).chain(&options.args));
+        wasi.set_inherit_stdio(true);
+
+        for (key, value) in &options.env {
+            wasi.set_env(key, value);
+        }
+
+        for (host, guest) in &options.dirs {
+            wasi.preopen_host_dir(guest, host)
+                .map_err(|error| format!("can't open `{host}`: {error}"))?;
+        }
+
+        wasi.link(&mut store);
+    }
+
+    store
+        .instantiate(&module)
+        .map_err(|error| error.to_string())?;
+
+    let mut interpreter = Interpreter::with_engine(&mut store, options.engine);
+    interpreter.set_fuel(options.fuel);
+    interpreter.set_trace(options.trace);
+
+    match interpreter.run(&options.func, args) {
+        Ok(EvaluationStatus::Value(results)) => {
+            for result in results {
+                println!("{}", format_val(result));
+            }
+
+            Ok(ExitCode::SUCCESS)
+        }
+
+        // Only the low byte of the exit code is seen by the parent process,
+        // as on Unix.
+        Ok(EvaluationStatus::Exited(code)) => Ok(ExitCode::from(code as u8)),
+
+        Ok(EvaluationStatus::Exception(exception)) => Err(exception.to_string()),
+
+        Err(trap) => {
+            let mut message = format!("trap: {trap}\nbacktrace:");
+            for (idx, frame) in interpreter.backtrace().enumerate() {
+                match frame.name {
+                    Some(name) => message += &format!("\n  {idx}: {name}"),
+                    None => message += &format!("\n  {idx}: func[{}]", frame.func.0),
+                }
+            }
+
+            Err(message)
+        }
+    }
+}
+
+/// Loads a binary module, or a text module if the `wat` feature is enabled.
+fn load(bytes: &[u8]) -> Result<Module, String> {
+    if bytes.starts_with(b"\0asm") {
+        return Module::from_bytes(bytes).map_err(|error| error.to_string());
+    }
+
+    #[cfg(feature = "wat")]
+    {
+        let text = std::str::from_utf8(bytes).map_err(|error| error.to_string())?;
+        Module::from_wat(text).map_err(|error| error.to_string())
+    }
+
+    #[cfg(not(feature = "wat"))]
+    Err("text modules require the `wat` feature".to_owned())
+}
+
+/// Parses `arg` as a value of type `type_`.
+///
+/// Integers may be written as signed or unsigned, vectors as a hexadecimal
+/// `0x` literal of their 128 bits, and references are either `null` or, for
+/// `externref`, an address.
+fn parse_val(arg: &str, type_: ValType) -> Result<Val, String> {
+    let val = match type_ {
+        ValType::NumType(NumType::I32) => arg
+            .parse::<i32>()
+            .ok()
+            .or_else(|| arg.parse::<u32>().ok().map(|val| val as i32))
+            .map(Val::from),
+        ValType::NumType(NumType::I64) => arg
+            .parse::<i64>()
+            .ok()
+            .or_else(|| arg.parse::<u64>().ok().map(|val| val as i64))
+            .map(Val::from),
+        ValType::NumType(NumType::F32) => arg.parse::<f32>().ok().map(Val::from),
+        ValType::NumType(NumType::F64) => arg.parse::<f64>().ok().map(Val::from),
+        ValType::VecType(_) => arg
+            .strip_prefix("0x")
+            .and_then(|digits| u128::from_str_radix(digits, 16).ok())
+            .map(Val::from),
+        ValType::RefType(type_) if arg == "null" && type_.nullable => {
+            Some(Val::Ref(Ref::Null(type_)))
+        }
+        ValType::RefType(RefType {
+            heap_type: HeapType::Extern,
+            ..
+        }) => arg
+            .parse()
+            .ok()
+            .map(|addr| Val::Ref(Ref::Extern(ExternAddr(addr)))),
+        ValType::RefType(_) => None,
+    };
+
+    val.ok_or_else(|| format!("invalid {type_} argument `{arg}`"))
+}
+
+/// Formats `val` for the output, numbers being written without their type.
+fn format_val(val: Val) -> String {
+    match val {
+        Val::Num(Num::I32(val)) => val.to_string(),
+        Val::Num(Num::I64(val)) => val.to_string(),
+        Val::Num(Num::F32(val)) => val.to_string(),
+        Val::Num(Num::F64(val)) => val.to_string(),
+        Val::V128(val) => format!("{val:#034x}"),
+        Val::Ref(Ref::Null(_)) => "null".to_owned(),
+        Val::Ref(ref_) => ref_.to_string(),
+    }
+}
@@ -0,0 +1,1201 @@
+//! Linear memories, and the semantics of the ops that access them.
+//!
+//! The pages of a memory are allocated when they are first accessed rather
+//! than when it grows, so that growing a memory, even a 64-bit one by
+//! gigabytes, only costs the pages that the program then touches. Reading a
+//! page of an unshared memory that was never written doesn't allocate it.
+//!
+//! Shared memories can be accessed by several threads at once, so their bytes
+//! are never borrowed: they are stored as atomic 64-bit words, and every
+//! access goes through the words that it overlaps. The atomic accesses of
+//! the program are sequentially consistent, and the other ones are relaxed,
+//! which lets them race like the non-atomic accesses of the threads proposal
+//! without being undefined behavior. The pages of a shared memory never
+//! move. The threads waiting on a shared memory are tracked by a futex table
+//! that `memory.atomic.notify` looks up.
+//!
+//! Addresses are handled as 64-bit integers, whether they index a 32-bit
+//! memory or a 64-bit memory of the memory64 proposal. The effective address
+//! of an access adds its offset to its address operand, and is out of bounds
+//! when the addition overflows.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Debug},
+    ops::Range,
+    sync::{
+        Arc, Condvar, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{
+    cell::{Cell, FromCell},
+    interpreter::Trap,
+    ir::Op,
+    simd,
+    types::{AddrType, Limits, MemType},
+};
+
+/// The size of a memory page, in bytes.
+pub(crate) const PAGE_SIZE: usize = 0x1_0000;
+
+/// The maximum number of pages of a 32-bit memory.
+const MAX_PAGES: u64 = 0x1_0000;
+
+/// The maximum number of pages of a 64-bit memory, whose bytes can all be
+/// addressed by an `i64`.
+const MAX_PAGES_64: u64 = 1 << 48;
+
+/// The number of segments of the page table of a shared memory, the `n`th of
+/// which holds `2^n` pages, so that they hold [`MAX_PAGES_64`] pages.
+const SEGMENTS: usize = 49;
+
+/// The number of words of a page of a shared memory.
+const PAGE_WORDS: usize = PAGE_SIZE / size_of::<u64>();
+
+/// The number of bytes that are copied at once between shared memories.
+const CHUNK_SIZE: usize = 0x1000;
+
+#[derive(Debug)]
+pub(crate) struct Memory {
+    data: Data,
+    addr_type: AddrType,
+    /// The maximum size declared by the type of the memory, in pages.
+    max: Option<u64>,
+    /// The maximum size imposed by the store, in pages.
+    limit: u64,
+}
+
+#[derive(Debug)]
+enum Data {
+    Owned(Pages),
+    Shared(SharedMemory),
+}
+
+/// The pages of a memory that is not shared, which are `None` until they are
+/// first written.
+#[derive(Debug, Default)]
+struct Pages(Vec<Option<Box<[u8; PAGE_SIZE]>>>);
+
+impl Memory {
+    /// Creates a memory of type `type_`, which can't grow past `limit`
+    /// pages, or returns `None` if its pages can't be allocated.
+    pub(crate) fn new(type_: &MemType, limit: u64) -> Option<Memory> {
+        let Limits { min, max } = type_.limits;
+        let data = match max {
+            // Validation guarantees that shared memories have a maximum.
+            Some(max) if type_.shared => {
+                Data::Shared(SharedMemory::allocate(type_.addr_type, min, max, limit)?)
+            }
+            _ => {
+                let mut pages = Pages::default();
+                pages.grow(min)?;
+                Data::Owned(pages)
+            }
+        };
+
+        Some(Memory {
+            data,
+            addr_type: type_.addr_type,
+            max,
+            limit,
+        })
+    }
+
+    /// Wraps `memory`, which is imported from another store.
+    pub(crate) fn from_shared(memory: SharedMemory) -> Memory {
+        Memory {
+            addr_type: memory.0.addr_type,
+            max: Some(memory.0.max),
+            limit: memory.0.capacity,
+            data: Data::Shared(memory),
+        }
+    }
+
+    /// The type of the memory, whose minimum is its current size.
+    pub(crate) fn type_(&self) -> MemType {
+        MemType {
+            addr_type: self.addr_type,
+            limits: Limits {
+                min: self.size(),
+                max: self.max,
+            },
+            shared: matches!(self.data, Data::Shared(_)),
+        }
+    }
+
+    pub(crate) fn addr_type(&self) -> AddrType {
+        self.addr_type
+    }
+
+    /// The memory, if it is shared.
+    pub(crate) fn shared(&self) -> Option<&SharedMemory> {
+        match &self.data {
+            Data::Owned(_) => None,
+            Data::Shared(memory) => Some(memory),
+        }
+    }
+
+    /// The size of the memory, in bytes.
+    #[inline(always)]
+    pub(crate) fn len(&self) -> u64 {
+        self.size() * PAGE_SIZE as u64
+    }
+
+    /// The size of the memory, in pages.
+    #[inline(always)]
+    pub(crate) fn size(&self) -> u64 {
+        match &self.data {
+            Data::Owned(pages) => pages.0.len() as u64,
+            Data::Shared(memory) => memory.size(),
+        }
+    }
+
+    /// Grows the memory by `delta` pages, and returns its previous size, or
+    /// `None` if the memory can't grow that much.
+    pub(crate) fn grow(&mut self, delta: u64) -> Option<u64> {
+        let pages = match &mut self.data {
+            Data::Owned(pages) => pages,
+            Data::Shared(memory) => return memory.grow(delta),
+        };
+
+        let size = pages.0.len() as u64;
+        let max = self
+            .max
+            .unwrap_or(max_pages(self.addr_type))
+            .min(self.limit);
+        let new_size = size.checked_add(delta).filter(|size| *size <= max)?;
+
+        pages.grow(new_size)?;
+
+        Some(size)
+    }
+
+    // The range of `len` bytes starting at `addr`, if it is in bounds.
+    #[inline(always)]
+    fn range(&self, addr: u64, len: u64) -> Result<Range<usize>, Trap> {
+        match addr.checked_add(len) {
+            Some(end) if end <= self.len() => Ok(addr as usize..end as usize),
+            _ => Err(Trap::MemoryOutOfBounds),
+        }
+    }
+
+    // The range of an access of a `T` at `addr + offset`, if it is in bounds.
+    #[inline(always)]
+    fn access_range<T: Bytes>(&self, addr: u64, offset: u64) -> Result<Range<usize>, Trap> {
+        let addr = addr.checked_add(offset).ok_or(Trap::MemoryOutOfBounds)?;
+
+        self.range(addr, T::SIZE as u64)
+    }
+
+    // The range of an atomic access of a `T` at `addr + offset`, if it is in
+    // bounds and aligned.
+    fn atomic_range<T: Bytes>(&self, addr: u64, offset: u64) -> Result<Range<usize>, Trap> {
+        let range = self.access_range::<T>(addr, offset)?;
+
+        if range.start % T::SIZE != 0 {
+            return Err(Trap::UnalignedAtomic);
+        }
+
+        Ok(range)
+    }
+
+    // Reads the bytes starting at `addr`, which are in bounds, to `buf`.
+    #[inline(always)]
+    fn load(&self, addr: usize, buf: &mut [u8]) {
+        match &self.data {
+            Data::Owned(pages) => pages.load(addr, buf),
+            Data::Shared(memory) => memory.load(addr, buf),
+        }
+    }
+
+    // Writes `bytes` to the memory, starting at `addr`, which is in bounds.
+    #[inline(always)]
+    fn store(&mut self, addr: usize, bytes: &[u8]) {
+        match &mut self.data {
+            Data::Owned(pages) => pages.store(addr, bytes),
+            Data::Shared(memory) => memory.store(addr, bytes),
+        }
+    }
+
+    #[inline(always)]
+    fn read<T: Bytes>(&self, addr: u64, offset: u64) -> Result<T, Trap> {
+        let range = self.access_range::<T>(addr, offset)?;
+
+        let mut bytes = [0; 16];
+        self.load(range.start, &mut bytes[..T::SIZE]);
+
+        Ok(T::from_le(&bytes[..T::SIZE]))
+    }
+
+    #[inline(always)]
+    fn write<T: Bytes>(&mut self, addr: u64, offset: u64, val: T) -> Result<(), Trap> {
+        let range = self.access_range::<T>(addr, offset)?;
+
+        let mut bytes = [0; 16];
+        val.to_le(&mut bytes[..T::SIZE]);
+        self.store(range.start, &bytes[..T::SIZE]);
+
+        Ok(())
+    }
+
+    fn read_atomic<T: Atomic>(&self, addr: u64, offset: u64) -> Result<T, Trap> {
+        let range = self.atomic_range::<T>(addr, offset)?;
+
+        Ok(match &self.data {
+            Data::Owned(_) => {
+                let mut bytes = [0; 8];
+                self.load(range.start, &mut bytes[..T::SIZE]);
+                T::from_le(&bytes[..T::SIZE])
+            }
+            Data::Shared(memory) => memory.update(range.start, |_| None),
+        })
+    }
+
+    // Atomically replaces the `T` at `addr + offset` with the result of `f`,
+    // unless it is `None`, and returns the previous value.
+    fn update<T: Atomic>(
+        &mut self,
+        addr: u64,
+        offset: u64,
+        mut f: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, Trap> {
+        let range = self.atomic_range::<T>(addr, offset)?;
+
+        Ok(match &mut self.data {
+            Data::Owned(pages) => {
+                let mut bytes = [0; 8];
+                pages.load(range.start, &mut bytes[..T::SIZE]);
+                let val = T::from_le(&bytes[..T::SIZE]);
+                if let Some(new) = f(val) {
+                    new.to_le(&mut bytes[..T::SIZE]);
+                    pages.store(range.start, &bytes[..T::SIZE]);
+                }
+
+                val
+            }
+            Data::Shared(memory) => memory.update(range.start, f),
+        })
+    }
+
+    /// Copies the bytes starting at `addr` to `buf`.
+    pub(crate) fn read_bytes(&self, addr: u64, buf: &mut [u8]) -> Result<(), Trap> {
+        let range = self.range(addr, buf.len() as u64)?;
+        self.load(range.start, buf);
+
+        Ok(())
+    }
+
+    /// Copies `bytes` to the memory, starting at `addr`.
+    pub(crate) fn write_bytes(&mut self, addr: u64, bytes: &[u8]) -> Result<(), Trap> {
+        let range = self.range(addr, bytes.len() as u64)?;
+        self.store(range.start, bytes);
+
+        Ok(())
+    }
+
+    /// Sets `len` bytes starting at `dst` to `val`.
+    pub(crate) fn fill(&mut self, dst: u64, val: u8, len: u64) -> Result<(), Trap> {
+        let range = self.range(dst, len)?;
+
+        match &mut self.data {
+            Data::Owned(pages) => pages.fill(range, val),
+            Data::Shared(memory) => {
+                let chunk = [val; CHUNK_SIZE];
+                for start in range.clone().step_by(CHUNK_SIZE) {
+                    memory.store(start, &chunk[..(range.end - start).min(CHUNK_SIZE)]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies `len` bytes from `src` to `dst`, which may overlap.
+    pub(crate) fn copy_within(&mut self, dst: u64, src: u64, len: u64) -> Result<(), Trap> {
+        let src = self.range(src, len)?;
+        let dst = self.range(dst, len)?;
+
+        match &mut self.data {
+            Data::Owned(pages) => pages.copy_within(dst.start, src),
+            Data::Shared(memory) => memory.copy_within(dst.start, src),
+        }
+
+        Ok(())
+    }
+
+    /// Copies `len` bytes of `data`, starting at `src`, to `dst`.
+    pub(crate) fn init(&mut self, dst: u64, data: &[u8], src: u32, len: u32) -> Result<(), Trap> {
+        let dst = self.range(dst, len.into())?;
+        let src = data
+            .get(src as usize..)
+            .and_then(|data| data.get(..len as usize))
+            .ok_or(Trap::MemoryOutOfBounds)?;
+
+        self.store(dst.start, src);
+
+        Ok(())
+    }
+}
+
+impl Pages {
+    // Grows the memory to `size` pages, none of which are allocated, or
+    // returns `None` if the page table can't hold them.
+    fn grow(&mut self, size: u64) -> Option<()> {
+        let size = usize::try_from(size).ok()?;
+        self.0.try_reserve_exact(size - self.0.len()).ok()?;
+        self.0.resize(size, None);
+
+        Some(())
+    }
+
+    // Reads the bytes starting at `addr`, which are in bounds, to `buf`.
+    #[inline(always)]
+    fn load(&self, addr: usize, buf: &mut [u8]) {
+        for (page, in_page, range) in chunks(addr, buf.len()) {
+            match &self.0[page] {
+                Some(page) => buf[range].copy_from_slice(&page[in_page]),
+                None => buf[range].fill(0),
+            }
+        }
+    }
+
+    // Writes `bytes` to the memory, starting at `addr`, which is in bounds.
+    #[inline(always)]
+    fn store(&mut self, addr: usize, bytes: &[u8]) {
+        for (page, in_page, range) in chunks(addr, bytes.len()) {
+            self.page(page)[in_page].copy_from_slice(&bytes[range]);
+        }
+    }
+
+    // Sets the bytes of `range`, which is in bounds, to `val`.
+    fn fill(&mut self, range: Range<usize>, val: u8) {
+        for (page, in_page, _) in chunks(range.start, range.len()) {
+            // Pages that were never written are already zeroed.
+            if val == 0 && self.0[page].is_none() {
+                continue;
+            }
+
+            self.page(page)[in_page].fill(val);
+        }
+    }
+
+    // Copies the bytes of `src` to `dst`, which may overlap, as if they went
+    // through a buffer.
+    fn copy_within(&mut self, dst: usize, src: Range<usize>) {
+        // The bytes are copied by pieces that are each in a single page of
+        // the source and of the destination, starting from the end of the
+        // ranges when the source comes first, so that they are read before
+        // being overwritten.
+        let backwards = src.start < dst;
+        let mut done = 0;
+        while done < src.len() {
+            let left = src.len() - done;
+            let (offset, len) = if backwards {
+                let len = left
+                    .min((src.start + left - 1) % PAGE_SIZE + 1)
+                    .min((dst + left - 1) % PAGE_SIZE + 1);
+                (left - len, len)
+            } else {
+                let len = left
+                    .min(PAGE_SIZE - (src.start + done) % PAGE_SIZE)
+                    .min(PAGE_SIZE - (dst + done) % PAGE_SIZE);
+                (done, len)
+            };
+
+            self.copy_piece(dst + offset, src.start + offset, len);
+            done += len;
+        }
+    }
+
+    // Copies `len` bytes from `src` to `dst`, which are each in a single page.
+    fn copy_piece(&mut self, dst: usize, src: usize, len: usize) {
+        let (dst_page, dst) = (dst / PAGE_SIZE, dst % PAGE_SIZE);
+        let (src_page, src) = (src / PAGE_SIZE, src % PAGE_SIZE);
+
+        // A page that was never written holds zeros.
+        if dst_page == src_page {
+            if let Some(page) = &mut self.0[src_page] {
+                page.copy_within(src..src + len, dst);
+            }
+        } else if let Some(page) = self.0[src_page].take() {
+            self.page(dst_page)[dst..dst + len].copy_from_slice(&page[src..src + len]);
+            self.0[src_page] = Some(page);
+        } else if let Some(page) = &mut self.0[dst_page] {
+            page[dst..dst + len].fill(0);
+        }
+    }
+
+    // The `index`th page, which is allocated if it wasn't.
+    fn page(&mut self, index: usize) -> &mut [u8; PAGE_SIZE] {
+        self.0[index]
+            .get_or_insert_with(|| vec![0; PAGE_SIZE].into_boxed_slice().try_into().unwrap())
+    }
+}
+
+/// Splits the `len` bytes starting at `addr` by page, and returns the index
+/// of each page, along with the range of the bytes in the page and their
+/// range in the access.
+#[inline(always)]
+fn chunks(addr: usize, len: usize) -> impl Iterator<Item = (usize, Range<usize>, Range<usize>)> {
+    let mut done = 0;
+    std::iter::from_fn(move || {
+        if done == len {
+            return None;
+        }
+
+        let start = (addr + done) % PAGE_SIZE;
+        let count = (PAGE_SIZE - start).min(len - done);
+        let chunk = (
+            (addr + done) / PAGE_SIZE,
+            start..start + count,
+            done..done + count,
+        );
+        done += count;
+
+        Some(chunk)
+    })
+}
+
+/// Splits a copy of the bytes of `src` to `dst`, which may overlap, into
+/// chunks, and calls `copy` with the source and destination of each chunk,
+/// and their length.
+///
+/// Chunks are copied starting from the end of the ranges when the source
+/// comes first, so that they are read before being overwritten.
+fn for_chunks(dst: usize, src: Range<usize>, mut copy: impl FnMut(usize, usize, usize)) {
+    let mut copy = |offset: usize| {
+        copy(
+            src.start + offset,
+            dst + offset,
+            (src.len() - offset).min(CHUNK_SIZE),
+        );
+    };
+
+    let offsets = (0..src.len()).step_by(CHUNK_SIZE);
+    if src.start < dst {
+        offsets.rev().for_each(&mut copy);
+    } else {
+        offsets.for_each(&mut copy);
+    }
+}
+
+/// Copies `len` bytes from `src`, in `src_memory`, to `dst`, in `dst_memory`.
+pub(crate) fn copy(
+    dst_memory: &mut Memory,
+    dst: u64,
+    src_memory: &Memory,
+    src: u64,
+    len: u64,
+) -> Result<(), Trap> {
+    let src = src_memory.range(src, len)?;
+    let dst = dst_memory.range(dst, len)?;
+
+    // The same shared memory may be imported twice, in which case the ranges
+    // may overlap.
+    if let (Some(dst_memory), Some(src_memory)) = (dst_memory.shared(), src_memory.shared())
+        && Arc::ptr_eq(&dst_memory.0, &src_memory.0)
+    {
+        dst_memory.copy_within(dst.start, src);
+
+        return Ok(());
+    }
+
+    let mut chunk = [0; CHUNK_SIZE];
+    for start in src.clone().step_by(CHUNK_SIZE) {
+        let chunk = &mut chunk[..(src.end - start).min(CHUNK_SIZE)];
+        src_memory.load(start, chunk);
+        dst_memory.store(dst.start + (start - src.start), chunk);
+    }
+
+    Ok(())
+}
+
+/// A memory that several threads can access at once.
+///
+/// A shared memory is created by instantiating a module that defines it, or
+/// with [`SharedMemory::new`]. It can then be imported by the modules of
+/// other stores with [`Store::define_memory`], so that their interpreters can
+/// run on other threads. Handles are cheap to clone, and refer to the same
+/// memory.
+///
+/// [`Store::define_memory`]: crate::store::Store::define_memory
+#[derive(Clone)]
+pub struct SharedMemory(Arc<SharedBuffer>);
+
+struct SharedBuffer {
+    /// The pages of the memory, by segment, the `n`th segment holding the
+    /// `2^n` pages that follow the ones of the previous segments.
+    ///
+    /// A segment is allocated when the memory grows to its first page, and
+    /// a page when it is first accessed, after which neither moves. The bytes
+    /// of a page are stored as little-endian words, so that the accesses of
+    /// several threads never race outside of atomics.
+    segments: [OnceLock<Box<[OnceLock<SharedPage>]>>; SEGMENTS],
+    /// The current size of the memory, in pages, whose segments are all
+    /// allocated.
+    size: AtomicU64,
+    addr_type: AddrType,
+    /// The maximum size declared by the type of the memory, in pages.
+    max: u64,
+    /// The number of pages that the memory can grow to.
+    capacity: u64,
+    /// The threads waiting on an address, in the order in which they started
+    /// waiting.
+    waiters: Mutex<HashMap<usize, VecDeque<Arc<Waiter>>>>,
+}
+
+type SharedPage = Box<[AtomicU64; PAGE_WORDS]>;
+
+#[derive(Default)]
+struct Waiter {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl SharedMemory {
+    /// Creates a memory of type `type_`, or returns `None` if the type is not
+    /// shared, has no maximum, or is larger than its address type allows.
+    ///
+    /// Like the memories of a store without limit, the memory can grow up to
+    /// its maximum.
+    pub fn new(type_: MemType) -> Option<SharedMemory> {
+        let Limits { min, max } = type_.limits;
+        let max =
+            max.filter(|max| type_.shared && min <= *max && *max <= max_pages(type_.addr_type))?;
+
+        SharedMemory::allocate(type_.addr_type, min, max, max)
+    }
+
+    /// The type of the memory, whose minimum is its current size.
+    pub fn type_(&self) -> MemType {
+        MemType {
+            addr_type: self.0.addr_type,
+            limits: Limits {
+                min: self.size(),
+                max: Some(self.0.max),
+            },
+            shared: true,
+        }
+    }
+
+    // Creates a memory of `size` pages that can grow up to `max` pages, or
+    // `limit` pages if it is smaller, or returns `None` if its pages can't be
+    // allocated.
+    fn allocate(addr_type: AddrType, size: u64, max: u64, limit: u64) -> Option<SharedMemory> {
+        let memory = SharedMemory(Arc::new(SharedBuffer {
+            segments: [const { OnceLock::new() }; SEGMENTS],
+            size: AtomicU64::new(0),
+            addr_type,
+            max,
+            capacity: max.min(limit),
+            waiters: Mutex::default(),
+        }));
+
+        memory.grow(size)?;
+
+        Some(memory)
+    }
+
+    fn size(&self) -> u64 {
+        self.0.size.load(Ordering::SeqCst)
+    }
+
+    fn grow(&self, delta: u64) -> Option<u64> {
+        let mut size = self.size();
+
+        loop {
+            let new_size = size
+                .checked_add(delta)
+                .filter(|size| *size <= self.0.capacity)?;
+
+            // The segments are allocated before the new size is visible to
+            // the other threads. They stay allocated if another thread grows
+            // the memory first, and are then used by the next growth.
+            if new_size > size {
+                let (first, _) = segment_of(size);
+                let (last, _) = segment_of(new_size - 1);
+                for (index, segment) in self.0.segments[first..=last].iter().enumerate() {
+                    if segment.get().is_none() {
+                        let len = 1_usize << (first + index);
+                        let mut pages = Vec::new();
+                        pages.try_reserve_exact(len).ok()?;
+                        pages.resize_with(len, OnceLock::new);
+                        let _ = segment.set(pages.into_boxed_slice());
+                    }
+                }
+            }
+
+            match self
+                .0
+                .size
+                .compare_exchange(size, new_size, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(size) => return Some(size),
+                Err(current) => size = current,
+            }
+        }
+    }
+
+    // The word that holds the byte at `addr`, which is in bounds.
+    #[inline(always)]
+    fn word(&self, addr: usize) -> &AtomicU64 {
+        let (segment, index) = segment_of((addr / PAGE_SIZE) as u64);
+        let segment = self.0.segments[segment].get();
+        let segment = segment.expect("the segments in bounds are allocated");
+        let page = segment[index].get_or_init(|| {
+            let words = (0..PAGE_WORDS).map(|_| AtomicU64::new(0)).collect();
+            Box::<[AtomicU64]>::try_into(words).unwrap()
+        });
+
+        &page[addr % PAGE_SIZE / size_of::<u64>()]
+    }
+
+    // Calls `f` with each word overlapped by the bytes starting at `addr`,
+    // along with the range of these bytes in the word, and the range of the
+    // bytes that overlap it in the access.
+    #[inline(always)]
+    fn for_words(
+        &self,
+        addr: usize,
+        len: usize,
+        mut f: impl FnMut(&AtomicU64, Range<usize>, Range<usize>),
+    ) {
+        let mut done = 0;
+        while done < len {
+            let start = (addr + done) % size_of::<u64>();
+            let count = (size_of::<u64>() - start).min(len - done);
+
+            f(
+                self.word(addr + done),
+                start..start + count,
+                done..done + count,
+            );
+            done += count;
+        }
+    }
+
+    // Reads the bytes starting at `addr`, which are in bounds, to `buf`.
+    fn load(&self, addr: usize, buf: &mut [u8]) {
+        self.for_words(addr, buf.len(), |word, bytes, range| {
+            let word = word.load(Ordering::Relaxed).to_le_bytes();
+            buf[range].copy_from_slice(&word[bytes]);
+        });
+    }
+
+    // Writes `bytes` to the memory, starting at `addr`, which is in bounds.
+    fn store(&self, addr: usize, bytes: &[u8]) {
+        self.for_words(addr, bytes.len(), |word, in_word, range| {
+            if in_word.len() == size_of::<u64>() {
+                let val = u64::from_le_bytes(bytes[range].try_into().unwrap());
+                word.store(val, Ordering::Relaxed);
+                return;
+            }
+
+            // The other bytes of the word may be written by other threads.
+            let _ = word.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |val| {
+                let mut val = val.to_le_bytes();
+                val[in_word.clone()].copy_from_slice(&bytes[range.clone()]);
+                Some(u64::from_le_bytes(val))
+            });
+        });
+    }
+
+    // Copies the bytes of `src` to `dst`, which may overlap, as if they went
+    // through a buffer.
+    fn copy_within(&self, dst: usize, src: Range<usize>) {
+        let mut chunk = [0; CHUNK_SIZE];
+        for_chunks(dst, src, |src, dst, len| {
+            self.load(src, &mut chunk[..len]);
+            self.store(dst, &chunk[..len]);
+        });
+    }
+
+    // Atomically replaces the `T` at `addr`, which is in bounds and aligned,
+    // with the result of `f`, unless it is `None`, and returns the previous
+    // value.
+    fn update<T: Atomic>(&self, addr: usize, mut f: impl FnMut(T) -> Option<T>) -> T {
+        let word = self.word(addr);
+        let bytes = addr % size_of::<u64>()..addr % size_of::<u64>() + T::SIZE;
+        let get = |word: u64| T::from_le(&word.to_le_bytes()[bytes.clone()]);
+
+        let prev = word.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |word| {
+            let mut new = word.to_le_bytes();
+            f(get(word))?.to_le(&mut new[bytes.clone()]);
+            Some(u64::from_le_bytes(new))
+        });
+
+        get(prev.unwrap_or_else(|word| word))
+    }
+
+    // Blocks until `addr` is notified or `timeout` expires, unless `matches`
+    // returns `false`, and returns the result of `memory.atomic.wait`.
+    fn wait(&self, addr: usize, matches: impl FnOnce() -> bool, timeout: Option<Duration>) -> i32 {
+        // The value is checked with the table locked, so that a notification
+        // sent after it is modified can't be missed.
+        let mut waiters = self.0.waiters.lock().unwrap();
+        if !matches() {
+            return 1;
+        }
+
+        let waiter = Arc::new(Waiter::default());
+        waiters
+            .entry(addr)
+            .or_default()
+            .push_back(Arc::clone(&waiter));
+        drop(waiters);
+
+        let woken = waiter.woken.lock().unwrap();
+        let woken = match timeout {
+            Some(timeout) => {
+                let (woken, _) = waiter
+                    .condvar
+                    .wait_timeout_while(woken, timeout, |woken| !*woken)
+                    .unwrap();
+                woken
+            }
+            None => waiter.condvar.wait_while(woken, |woken| !*woken).unwrap(),
+        };
+
+        if *woken {
+            return 0;
+        }
+
+        drop(woken);
+
+        // The waiter may have been woken since it timed out, in which case it
+        // is not in the table anymore.
+        let mut waiters = self.0.waiters.lock().unwrap();
+        let Some(queue) = waiters.get_mut(&addr) else {
+            return 0;
+        };
+        let Some(index) = queue.iter().position(|other| Arc::ptr_eq(other, &waiter)) else {
+            return 0;
+        };
+
+        queue.remove(index);
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+
+        2
+    }
+
+    // Wakes up to `count` threads waiting on `addr`, and returns how many
+    // were woken.
+    fn notify(&self, addr: usize, count: u32) -> u32 {
+        let mut waiters = self.0.waiters.lock().unwrap();
+        let Some(queue) = waiters.get_mut(&addr) else {
+            return 0;
+        };
+
+        let woken = queue.len().min(count as usize);
+        for waiter in queue.drain(..woken) {
+            *waiter.woken.lock().unwrap() = true;
+            waiter.condvar.notify_one();
+        }
+
+        if queue.is_empty() {
+            waiters.remove(&addr);
+        }
+
+        woken as u32
+    }
+}
+
+impl Debug for SharedMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedMemory")
+            .field("type_", &self.type_())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A value that can be stored in memory.
+trait Bytes {
+    const SIZE: usize;
+
+    fn from_le(bytes: &[u8]) -> Self;
+
+    fn to_le(self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_bytes {
+    ($( $ty:ty ),*) => {
+        $(
+            impl Bytes for $ty {
+                const SIZE: usize = size_of::<$ty>();
+
+                #[inline(always)]
+                fn from_le(bytes: &[u8]) -> $ty {
+                    <$ty>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                #[inline(always)]
+                fn to_le(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_bytes!(i8, u8, i16, u16, i32, u32, i64, u64, u128, f32, f64);
+
+/// A value that can be accessed atomically.
+///
+/// Its size is at most the one of a word, so that an aligned value never
+/// overlaps two words of a shared memory.
+trait Atomic: Bytes + Copy + PartialEq {}
+
+impl Atomic for u8 {}
+impl Atomic for u16 {}
+impl Atomic for u32 {}
+impl Atomic for u64 {}
+
+/// Runs a load op, whose address operand is `addr`.
+#[inline(always)]
+pub(crate) fn load(op: Op, memory: &Memory, addr: u64) -> Result<Cell, Trap> {
+    macro_rules! load {
+        ($memarg:expr, $ty:ty) => {
+            Cell::from(memory.read::<$ty>(addr, $memarg.offset)?)
+        };
+
+        ($memarg:expr, $ty:ty as $as:ty) => {
+            Cell::from(<$as>::from(memory.read::<$ty>(addr, $memarg.offset)?))
+        };
+    }
+
+    macro_rules! load_atomic {
+        ($memarg:expr, $ty:ty) => {
+            Cell::from(memory.read_atomic::<$ty>(addr, $memarg.offset)?)
+        };
+
+        ($memarg:expr, $ty:ty as $as:ty) => {
+            Cell::from(<$as>::from(
+                memory.read_atomic::<$ty>(addr, $memarg.offset)?,
+            ))
+        };
+    }
+
+    Ok(match op {
+        Op::I32Load(memarg) => load!(memarg, i32),
+        Op::I64Load(memarg) => load!(memarg, i64),
+        Op::F32Load(memarg) => load!(memarg, f32),
+        Op::F64Load(memarg) => load!(memarg, f64),
+        Op::I32Load8S(memarg) => load!(memarg, i8 as i32),
+        Op::I32Load8U(memarg) => load!(memarg, u8 as i32),
+        Op::I32Load16S(memarg) => load!(memarg, i16 as i32),
+        Op::I32Load16U(memarg) => load!(memarg, u16 as i32),
+        Op::I64Load8S(memarg) => load!(memarg, i8 as i64),
+        Op::I64Load8U(memarg) => load!(memarg, u8 as i64),
+        Op::I64Load16S(memarg) => load!(memarg, i16 as i64),
+        Op::I64Load16U(memarg) => load!(memarg, u16 as i64),
+        Op::I64Load32S(memarg) => load!(memarg, i32 as i64),
+        Op::I64Load32U(memarg) => load!(memarg, u32 as i64),
+
+        // The other vector loads read scalars, which the vector ops then
+        // spread over the lanes.
+        Op::V128Load(memarg) => load!(memarg, u128),
+        Op::V128Load8x8S(memarg) => simd::unary(Op::I16x8ExtendLowI8x16S, load!(memarg, u64)),
+        Op::V128Load8x8U(memarg) => simd::unary(Op::I16x8ExtendLowI8x16U, load!(memarg, u64)),
+        Op::V128Load16x4S(memarg) => simd::unary(Op::I32x4ExtendLowI16x8S, load!(memarg, u64)),
+        Op::V128Load16x4U(memarg) => simd::unary(Op::I32x4ExtendLowI16x8U, load!(memarg, u64)),
+        Op::V128Load32x2S(memarg) => simd::unary(Op::I64x2ExtendLowI32x4S, load!(memarg, u64)),
+        Op::V128Load32x2U(memarg) => simd::unary(Op::I64x2ExtendLowI32x4U, load!(memarg, u64)),
+        Op::V128Load8Splat(memarg) => simd::unary(Op::I8x16Splat, load!(memarg, u8 as i32)),
+        Op::V128Load16Splat(memarg) => simd::unary(Op::I16x8Splat, load!(memarg, u16 as i32)),
+        Op::V128Load32Splat(memarg) => simd::unary(Op::I32x4Splat, load!(memarg, i32)),
+        Op::V128Load64Splat(memarg) => simd::unary(Op::I64x2Splat, load!(memarg, i64)),
+        // The high bits of a cell are zero.
+        Op::V128Load32Zero(memarg) => load!(memarg, u32),
+        Op::V128Load64Zero(memarg) => load!(memarg, u64),
+
+        Op::I32AtomicLoad(memarg) => load_atomic!(memarg, u32),
+        Op::I64AtomicLoad(memarg) => load_atomic!(memarg, u64),
+        Op::I32AtomicLoad8U(memarg) => load_atomic!(memarg, u8 as u32),
+        Op::I32AtomicLoad16U(memarg) => load_atomic!(memarg, u16 as u32),
+        Op::I64AtomicLoad8U(memarg) => load_atomic!(memarg, u8 as u64),
+        Op::I64AtomicLoad16U(memarg) => load_atomic!(memarg, u16 as u64),
+        Op::I64AtomicLoad32U(memarg) => load_atomic!(memarg, u32 as u64),
+
+        op => unreachable!("not a load: `{op:?}`"),
+    })
+}
+
+/// Runs a store op, whose operands are `addr` and `val`.
+#[inline(always)]
+pub(crate) fn store(op: Op, memory: &mut Memory, addr: u64, val: Cell) -> Result<(), Trap> {
+    macro_rules! store {
+        ($memarg:expr, $ty:ty) => {
+            memory.write(addr, $memarg.offset, <$ty>::from_cell(val))
+        };
+
+        ($memarg:expr, $ty:ty as $as:ty) => {
+            memory.write(addr, $memarg.offset, <$ty>::from_cell(val) as $as)
+        };
+    }
+
+    macro_rules! store_atomic {
+        ($memarg:expr, $ty:ty) => {
+            store_atomic!($memarg, $ty as $ty)
+        };
+
+        ($memarg:expr, $ty:ty as $as:ty) => {{
+            let val = <$ty>::from_cell(val) as $as;
+            memory.update(addr, $memarg.offset, |_| Some(val)).map(drop)
+        }};
+    }
+
+    match op {
+        Op::I32Store(memarg) => store!(memarg, i32),
+        Op::I64Store(memarg) => store!(memarg, i64),
+        Op::F32Store(memarg) => store!(memarg, f32),
+        Op::F64Store(memarg) => store!(memarg, f64),
+        Op::I32Store8(memarg) => store!(memarg, i32 as u8),
+        Op::I32Store16(memarg) => store!(memarg, i32 as u16),
+        Op::I64Store8(memarg) => store!(memarg, i64 as u8),
+        Op::I64Store16(memarg) => store!(memarg, i64 as u16),
+        Op::I64Store32(memarg) => store!(memarg, i64 as u32),
+        Op::V128Store(memarg) => store!(memarg, u128),
+
+        Op::I32AtomicStore(memarg) => store_atomic!(memarg, u32),
+        Op::I64AtomicStore(memarg) => store_atomic!(memarg, u64),
+        Op::I32AtomicStore8(memarg) => store_atomic!(memarg, u32 as u8),
+        Op::I32AtomicStore16(memarg) => store_atomic!(memarg, u32 as u16),
+        Op::I64AtomicStore8(memarg) => store_atomic!(memarg, u64 as u8),
+        Op::I64AtomicStore16(memarg) => store_atomic!(memarg, u64 as u16),
+        Op::I64AtomicStore32(memarg) => store_atomic!(memarg, u64 as u32),
+
+        op => unreachable!("not a store: `{op:?}`"),
+    }
+}
+
+/// Runs a lane load op, whose operands are `addr` and the vector `val`.
+pub(crate) fn load_lane(op: Op, memory: &Memory, addr: u64, val: Cell) -> Result<Cell, Trap> {
+    macro_rules! load_lane {
+        ($memarg:expr, $lane:expr, $ty:ty) => {{
+            let mut lanes = <[$ty; 16 / size_of::<$ty>()]>::from_cell(val);
+            lanes[$lane as usize] = memory.read::<$ty>(addr, $memarg.offset)?;
+            Cell::from(lanes)
+        }};
+    }
+
+    Ok(match op {
+        Op::V128Load8Lane(memarg, lane) => load_lane!(memarg, lane, u8),
+        Op::V128Load16Lane(memarg, lane) => load_lane!(memarg, lane, u16),
+        Op::V128Load32Lane(memarg, lane) => load_lane!(memarg, lane, u32),
+        Op::V128Load64Lane(memarg, lane) => load_lane!(memarg, lane, u64),
+
+        op => unreachable!("not a lane load: `{op:?}`"),
+    })
+}
+
+/// Runs a lane store op, whose operands are `addr` and the vector `val`.
+pub(crate) fn store_lane(op: Op, memory: &mut Memory, addr: u64, val: Cell) -> Result<(), Trap> {
+    macro_rules! store_lane {
+        ($memarg:expr, $lane:expr, $ty:ty) => {{
+            let lanes = <[$ty; 16 / size_of::<$ty>()]>::from_cell(val);
+            memory.write(addr, $memarg.offset, lanes[$lane as usize])
+        }};
+    }
+
+    match op {
+        Op::V128Store8Lane(memarg, lane) => store_lane!(memarg, lane, u8),
+        Op::V128Store16Lane(memarg, lane) => store_lane!(memarg, lane, u16),
+        Op::V128Store32Lane(memarg, lane) => store_lane!(memarg, lane, u32),
+        Op::V128Store64Lane(memarg, lane) => store_lane!(memarg, lane, u64),
+
+        op => unreachable!("not a lane store: `{op:?}`"),
+    }
+}
+
+/// Runs an atomic read-modify-write op, whose operands are `addr` and `val`.
+pub(crate) fn atomic_rmw(op: Op, memory: &mut Memory, addr: u64, val: Cell) -> Result<Cell, Trap> {
+    // The operand and the result of the narrow ops are wrapped and extended.
+    macro_rules! rmw {
+        ($memarg:expr, $ty:ty, $f:expr) => {{
+            let f: fn($ty, $ty) -> $ty = $f;
+            let val = u64::from_cell(val) as $ty;
+            let prev = memory.update(addr, $memarg.offset, |prev| Some(f(prev, val)))?;
+            Cell::from(u64::from(prev))
+        }};
+    }
+
+    Ok(match op {
+        Op::I32AtomicRmw8AddU(memarg) | Op::I64AtomicRmw8AddU(memarg) => {
+            rmw!(memarg, u8, u8::wrapping_add)
+        }
+        Op::I32AtomicRmw16AddU(memarg) | Op::I64AtomicRmw16AddU(memarg) => {
+            rmw!(memarg, u16, u16::wrapping_add)
+        }
+        Op::I32AtomicRmwAdd(memarg) | Op::I64AtomicRmw32AddU(memarg) => {
+            rmw!(memarg, u32, u32::wrapping_add)
+        }
+        Op::I64AtomicRmwAdd(memarg) => rmw!(memarg, u64, u64::wrapping_add),
+
+        Op::I32AtomicRmw8SubU(memarg) | Op::I64AtomicRmw8SubU(memarg) => {
+            rmw!(memarg, u8, u8::wrapping_sub)
+        }
+        Op::I32AtomicRmw16SubU(memarg) | Op::I64AtomicRmw16SubU(memarg) => {
+            rmw!(memarg, u16, u16::wrapping_sub)
+        }
+        Op::I32AtomicRmwSub(memarg) | Op::I64AtomicRmw32SubU(memarg) => {
+            rmw!(memarg, u32, u32::wrapping_sub)
+        }
+        Op::I64AtomicRmwSub(memarg) => rmw!(memarg, u64, u64::wrapping_sub),
+
+        Op::I32AtomicRmw8AndU(memarg) | Op::I64AtomicRmw8AndU(memarg) => {
+            rmw!(memarg, u8, |prev, val| prev & val)
+        }
+        Op::I32AtomicRmw16AndU(memarg) | Op::I64AtomicRmw16AndU(memarg) => {
+            rmw!(memarg, u16, |prev, val| prev & val)
+        }
+        Op::I32AtomicRmwAnd(memarg) | Op::I64AtomicRmw32AndU(memarg) => {
+            rmw!(memarg, u32, |prev, val| prev & val)
+        }
+        Op::I64AtomicRmwAnd(memarg) => rmw!(memarg, u64, |prev, val| prev & val),
+
+        Op::I32AtomicRmw8OrU(memarg) | Op::I64AtomicRmw8OrU(memarg) => {
+            rmw!(memarg, u8, |prev, val| prev | val)
+        }
+        Op::I32AtomicRmw16OrU(memarg) | Op::I64AtomicRmw16OrU(memarg) => {
+            rmw!(memarg, u16, |prev, val| prev | val)
+        }
+        Op::I32AtomicRmwOr(memarg) | Op::I64AtomicRmw32OrU(memarg) => {
+            rmw!(memarg, u32, |prev, val| prev | val)
+        }
+        Op::I64AtomicRmwOr(memarg) => rmw!(memarg, u64, |prev, val| prev | val),
+
+        Op::I32AtomicRmw8XorU(memarg) | Op::I64AtomicRmw8XorU(memarg) => {
+            rmw!(memarg, u8, |prev, val| prev ^ val)
+        }
+        Op::I32AtomicRmw16XorU(memarg) | Op::I64AtomicRmw16XorU(memarg) => {
+            rmw!(memarg, u16, |prev, val| prev ^ val)
+        }
+        Op::I32AtomicRmwXor(memarg) | Op::I64AtomicRmw32XorU(memarg) => {
+            rmw!(memarg, u32, |prev, val| prev ^ val)
+        }
+        Op::I64AtomicRmwXor(memarg) => rmw!(memarg, u64, |prev, val| prev ^ val),
+
+        Op::I32AtomicRmw8XchgU(memarg) | Op::I64AtomicRmw8XchgU(memarg) => {
+            rmw!(memarg, u8, |_, val| val)
+        }
+        Op::I32AtomicRmw16XchgU(memarg) | Op::I64AtomicRmw16XchgU(memarg) => {
+            rmw!(memarg, u16, |_, val| val)
+        }
+        Op::I32AtomicRmwXchg(memarg) | Op::I64AtomicRmw32XchgU(memarg) => {
+            rmw!(memarg, u32, |_, val| val)
+        }
+        Op::I64AtomicRmwXchg(memarg) => rmw!(memarg, u64, |_, val| val),
+
+        op => unreachable!("not an atomic read-modify-write: `{op:?}`"),
+    })
+}
+
+/// Runs an atomic compare-exchange op, whose operands are `addr`, `expected`
+/// and `replacement`.
+pub(crate) fn atomic_cmpxchg(
+    op: Op,
+    memory: &mut Memory,
+    addr: u64,
+    expected: Cell,
+    replacement: Cell,
+) -> Result<Cell, Trap> {
+    // Like the other operands, the expected value of the narrow ops is
+    // wrapped.
+    macro_rules! cmpxchg {
+        ($memarg:expr, $ty:ty) => {{
+            let expected = u64::from_cell(expected) as $ty;
+            let replacement = u64::from_cell(replacement) as $ty;
+            let prev = memory.update(addr, $memarg.offset, |prev| {
+                (prev == expected).then_some(replacement)
+            })?;
+            Cell::from(u64::from(prev))
+        }};
+    }
+
+    Ok(match op {
+        Op::I32AtomicRmw8CmpxchgU(memarg) | Op::I64AtomicRmw8CmpxchgU(memarg) => {
+            cmpxchg!(memarg, u8)
+        }
+        Op::I32AtomicRmw16CmpxchgU(memarg) | Op::I64AtomicRmw16CmpxchgU(memarg) => {
+            cmpxchg!(memarg, u16)
+        }
+        Op::I32AtomicRmwCmpxchg(memarg) | Op::I64AtomicRmw32CmpxchgU(memarg) => {
+            cmpxchg!(memarg, u32)
+        }
+        Op::I64AtomicRmwCmpxchg(memarg) => cmpxchg!(memarg, u64),
+
+        op => unreachable!("not an atomic compare-exchange: `{op:?}`"),
+    })
+}
+
+/// Runs a wait op, whose operands are `addr`, `expected` and `timeout`, in
+/// nanoseconds, and returns 0 if the thread was woken, 1 if the value at
+/// `addr` is not the expected one, and 2 if the timeout expired.
+///
+/// A negative timeout never expires.
+pub(crate) fn atomic_wait(
+    op: Op,
+    memory: &Memory,
+    addr: u64,
+    expected: Cell,
+    timeout: i64,
+) -> Result<i32, Trap> {
+    macro_rules! wait {
+        ($memarg:expr, $ty:ty) => {{
+            let range = memory.atomic_range::<$ty>(addr, $memarg.offset)?;
+            let shared = memory.shared().ok_or(Trap::ExpectedSharedMemory)?;
+            let expected = <$ty>::from_cell(expected);
+            let timeout = u64::try_from(timeout).ok().map(Duration::from_nanos);
+
+            let load = || shared.update::<$ty>(range.start, |_| None);
+            shared.wait(range.start, || load() == expected, timeout)
+        }};
+    }
+
+    Ok(match op {
+        Op::MemoryAtomicWait32(memarg) => wait!(memarg, u32),
+        Op::MemoryAtomicWait64(memarg) => wait!(memarg, u64),
+
+        op => unreachable!("not a wait: `{op:?}`"),
+    })
+}
+
+/// Runs `memory.atomic.notify`, whose operands are `addr` and `count`, and
+/// returns the number of woken threads.
+pub(crate) fn atomic_notify(
+    memory: &Memory,
+    addr: u64,
+    offset: u64,
+    count: u32,
+) -> Result<u32, Trap> {
+    let range = memory.atomic_range::<u32>(addr, offset)?;
+
+    // Nothing can wait on an unshared memory.
+    Ok(match memory.shared() {
+        Some(shared) => shared.notify(range.start, count),
+        None => 0,
+    })
+}
+
+/// Returns the segment of the page table of a shared memory that holds the
+/// `page`th page, and the index of the page in the segment.
+#[inline(always)]
+fn segment_of(page: u64) -> (usize, usize) {
+    let segment = (page + 1).ilog2();
+
+    (segment as usize, (page + 1 - (1 << segment)) as usize)
+}
+
+/// The maximum number of pages of a memory whose addresses are of type
+/// `addr_type`.
+pub(crate) fn max_pages(addr_type: AddrType) -> u64 {
+    match addr_type {
+        AddrType::I32 => MAX_PAGES,
+        AddrType::I64 => MAX_PAGES_64,
+    }
+}
@@ -0,0 +1,920 @@
+//! Validated modules, ready to be instantiated.
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    fmt::{self, Display},
+    iter,
+    sync::Arc,
+};
+
+use wasmbin::{
+    indices::TypeId,
+    instructions::{Expression, Instruction},
+    io::DecodeError,
+    sections::{
+        self, CustomSection, DataInit, Element, ExportDesc, Import, ImportDesc, Locals,
+        NameSubSection, Section, StdPayload, payload,
+    },
+    types::{CompositeType, FieldType, FuncType, StorageType, SubType},
+    visit::{Visit, VisitError},
+};
+
+use crate::{
+    cell::Cell,
+    const_expr,
+    lower::{self, Lowered, Result, ValidationError, invalid},
+    store::Func,
+    types::{
+        self, AddrType, Binary, ExternType, GlobalType, HeapType, MemType, RefType, TableType,
+        Types, ValType,
+    },
+    values::Val,
+};
+
+/// The type of everything a module defines or imports, in the index spaces
+/// of the module.
+#[derive(Debug, Default)]
+pub(crate) struct Context {
+    /// The types of all the recursive groups, in order.
+    pub(crate) types: Vec<SubType>,
+    /// The number of types of each recursive group.
+    pub(crate) groups: Vec<u32>,
+    /// The index of the first type that is equivalent to each type, by which
+    /// types are compared.
+    pub(crate) canonical: Vec<u32>,
+    /// The type of each function.
+    pub(crate) funcs: Vec<TypeId>,
+    pub(crate) tables: Vec<TableType>,
+    pub(crate) memories: Vec<MemType>,
+    pub(crate) globals: Vec<GlobalType>,
+    /// The type of each tag, whose parameters are the payload of its
+    /// exceptions.
+    pub(crate) tags: Vec<TypeId>,
+    /// The type of the references of each element segment.
+    pub(crate) elems: Vec<RefType>,
+    /// The number of data segments, if the module has a data count section.
+    pub(crate) datas: Option<u32>,
+    /// The functions that may be referred to by `ref.func` in function
+    /// bodies.
+    pub(crate) refs: HashSet<u32>,
+}
+
+/// A validated module, whose functions are lowered.
+///
+/// A module is decoded, validated and lowered once, and can then be
+/// instantiated any number of times, in any number of stores. Cloning it is
+/// cheap, and it can be shared between threads.
+#[derive(Clone, Debug)]
+pub struct Module {
+    pub(crate) data: Arc<ModuleData>,
+}
+
+/// The contents of a [`Module`].
+///
+/// The indices used by the lowered code are the ones of the module. They are
+/// resolved when the module is instantiated.
+#[derive(Debug)]
+pub(crate) struct ModuleData {
+    pub(crate) context: Context,
+    pub(crate) imports: Vec<Import>,
+    /// The functions defined by the module.
+    pub(crate) funcs: Vec<Func>,
+    /// The initializers of the tables defined by the module, whose elements
+    /// are null if they have none.
+    pub(crate) tables: Vec<Option<Expression>>,
+    /// The initializers of the globals defined by the module.
+    pub(crate) globals: Vec<Expression>,
+    pub(crate) exports: Vec<sections::Export>,
+    pub(crate) start: Option<u32>,
+    pub(crate) elems: Vec<Segment<Vec<Expression>>>,
+    pub(crate) datas: Vec<Segment<Vec<u8>>>,
+}
+
+/// The error returned when a module can't be loaded.
+#[derive(Debug)]
+pub enum ModuleError {
+    /// The binary module can't be decoded.
+    Malformed(MalformedError),
+    /// The text module can't be parsed.
+    #[cfg(feature = "wat")]
+    Text(wat::Error),
+    Invalid(ValidationError),
+    /// The binary is a component, which is loaded with
+    /// [`Component::from_bytes`] rather than as a module.
+    ///
+    /// [`Component::from_bytes`]: crate::component::Component::from_bytes
+    Component,
+}
+
+impl Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::Malformed(error) => write!(f, "malformed module: {error}"),
+            #[cfg(feature = "wat")]
+            ModuleError::Text(error) => error.fmt(f),
+            ModuleError::Invalid(error) => error.fmt(f),
+            ModuleError::Component => f.write_str("the binary is a component, not a module"),
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+impl From<ValidationError> for ModuleError {
+    fn from(error: ValidationError) -> ModuleError {
+        ModuleError::Invalid(error)
+    }
+}
+
+/// The error returned when a binary can't be decoded.
+#[derive(Debug)]
+pub struct MalformedError(pub(crate) DecodeError);
+
+impl Display for MalformedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for MalformedError {}
+
+/// An external value imported by a module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportType<'module> {
+    /// The name of the module the value is imported from.
+    pub module: &'module str,
+    pub name: &'module str,
+    pub type_: ExternType,
+}
+
+/// An external value exported by a module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportType<'module> {
+    pub name: &'module str,
+    pub type_: ExternType,
+}
+
+/// An element or data segment.
+#[derive(Debug)]
+pub(crate) struct Segment<T> {
+    pub(crate) mode: Mode,
+    pub(crate) init: T,
+}
+
+#[derive(Debug)]
+pub(crate) enum Mode {
+    /// The segment can be copied with `table.init` or `memory.init`.
+    Passive,
+    /// The segment is copied to a table or memory when the module is
+    /// instantiated.
+    Active { index: u32, offset: Expression },
+    /// The segment only declares the functions that may be referred to.
+    Declarative,
+}
+
+impl Module {
+    /// Decodes the binary module `bytes`, validates it and lowers its
+    /// functions.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Module, ModuleError> {
+        // Components start with the same magic as core modules, followed by
+        // their own version and a layer of 1.
+        if bytes.starts_with(b"\0asm") && bytes.get(6..8) == Some(&[1, 0]) {
+            return Err(ModuleError::Component);
+        }
+
+        let module = wasmbin::Module::decode_from(bytes).map_err(binary_error)?;
+
+        // Sections are only decoded when they are first accessed, after which
+        // `Module::new` can't tell a malformed section from an invalid one.
+        module.visit(|_: &()| {}).map_err(decode_error)?;
+
+        Ok(Module::new(module)?)
+    }
+
+    /// Parses the text module `wat`, validates it and lowers its functions.
+    ///
+    /// ```
+    /// # use exwasmable::module::Module;
+    /// let module = Module::from_wat(r#"(module (func (export "run")))"#).unwrap();
+    ///
+    /// assert_eq!(module.exports().next().unwrap().name, "run");
+    /// ```
+    #[cfg(feature = "wat")]
+    pub fn from_wat(wat: &str) -> Result<Module, ModuleError> {
+        let bytes = wat::parse_str(wat).map_err(ModuleError::Text)?;
+
+        Module::from_bytes(&bytes)
+    }
+
+    /// Validates `module` and lowers its functions.
+    pub(crate) fn new(mut module: wasmbin::Module) -> Result<Module> {
+        let groups = section::<payload::Type>(&mut module)?;
+        let mut context = Context {
+            groups: groups
+                .iter()
+                .map(|group| group.types.len() as u32)
+                .collect(),
+            types: groups.into_iter().flat_map(|group| group.types).collect(),
+            datas: module
+                .find_std_section_mut::<payload::DataCount>()
+                .map(|count| count.try_contents_mut().map(|count| *count))
+                .transpose()
+                .map_err(malformed)?,
+            ..Context::default()
+        };
+        context.canonicalize()?;
+
+        let imports = section::<payload::Import>(&mut module)?;
+        for import in &imports {
+            match &import.desc {
+                ImportDesc::Func(type_) => {
+                    context.type_(*type_)?;
+                    context.funcs.push(*type_);
+                }
+                ImportDesc::Table(type_) => {
+                    let type_ = TableType::from_binary(type_);
+                    context.check(ValType::RefType(type_.type_))?;
+                    context.tables.push(type_);
+                }
+                ImportDesc::Mem(type_) => context.memories.push(MemType::from_binary(type_)),
+                ImportDesc::Global(type_) => {
+                    let type_ = GlobalType::from_binary(type_);
+                    context.check(type_.val_type)?;
+                    context.globals.push(type_);
+                }
+                ImportDesc::Exception(type_) => context.tag(type_.func_type)?,
+            }
+        }
+
+        let imported_globals = context.globals.len();
+
+        let func_types = section::<payload::Function>(&mut module)?;
+        for type_ in &func_types {
+            context.type_(*type_)?;
+        }
+        context.funcs.extend(&func_types);
+
+        // Tables are defined before globals, so their initializers can only
+        // read imported globals.
+        let tables = section::<payload::Table>(&mut module)?
+            .into_iter()
+            .map(|table| {
+                let type_ = TableType::from_binary(&table.ty);
+                let val_type = context.check(ValType::RefType(type_.type_))?;
+
+                match &table.init {
+                    Some(init) => const_expr::validate(init, val_type, &context, imported_globals)?,
+                    None if !type_.type_.nullable => {
+                        invalid!(
+                            "type mismatch: table of non-nullable references without initializer"
+                        )
+                    }
+                    None => {}
+                }
+
+                context.tables.push(type_);
+                Ok(table.init)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let memories = section::<payload::Memory>(&mut module)?;
+        context
+            .memories
+            .extend(memories.iter().map(MemType::from_binary));
+
+        for tag in section::<payload::Exception>(&mut module)? {
+            context.tag(tag.ty)?;
+        }
+
+        let globals = section::<payload::Global>(&mut module)?;
+        for global in &globals {
+            let type_ = GlobalType::from_binary(&global.ty);
+            context.check(type_.val_type)?;
+            context.globals.push(type_);
+        }
+
+        for table in &context.tables {
+            let max = match table.addr_type {
+                AddrType::I32 => u32::MAX.into(),
+                AddrType::I64 => u64::MAX,
+            };
+            check_limits(&table.limits, max, "table")?;
+        }
+
+        for memory in &context.memories {
+            let max = crate::memory::max_pages(memory.addr_type);
+            check_limits(&memory.limits, max, "memory")?;
+
+            if memory.shared && memory.limits.max.is_none() {
+                invalid!("shared memory must have maximum");
+            }
+        }
+
+        let globals = globals
+            .into_iter()
+            .enumerate()
+            .map(|(idx, global)| {
+                let type_ = context.globals[imported_globals + idx];
+                const_expr::validate(
+                    &global.init,
+                    type_.val_type,
+                    &context,
+                    imported_globals + idx,
+                )?;
+
+                Ok(global.init)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let exports = section::<payload::Export>(&mut module)?;
+        let mut names = HashSet::new();
+        for export in &exports {
+            if !names.insert(&export.name) {
+                invalid!("duplicate export name `{}`", export.name);
+            }
+
+            let (index, len) = match &export.desc {
+                ExportDesc::Func(func) => {
+                    context.refs.insert(func.index);
+                    (func.index, context.funcs.len())
+                }
+                ExportDesc::Table(table) => (table.index, context.tables.len()),
+                ExportDesc::Mem(memory) => (memory.index, context.memories.len()),
+                ExportDesc::Global(global) => (global.index, context.globals.len()),
+                ExportDesc::Exception(tag) => (tag.index, context.tags.len()),
+            };
+
+            if index as usize >= len {
+                invalid!("unknown export index {index}");
+            }
+        }
+
+        let start = module
+            .find_std_section_mut::<payload::Start>()
+            .map(|start| start.try_contents_mut().map(|start| start.index))
+            .transpose()
+            .map_err(malformed)?;
+
+        if let Some(start) = start {
+            let Some(type_) = context.funcs.get(start as usize) else {
+                invalid!("unknown function {start}");
+            };
+
+            let type_ = context.func_type(type_.index);
+            if !type_.params.is_empty() || !type_.results.is_empty() {
+                invalid!("start function must have type [] -> []");
+            }
+        }
+
+        let elems = section::<payload::Element>(&mut module)?
+            .into_iter()
+            .map(|elem| context.elem(elem))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Functions referred to from the module, outside of function bodies,
+        // are declared.
+        let refs = globals
+            .iter()
+            .chain(tables.iter().flatten())
+            .chain(elems.iter().flat_map(|elem| &elem.init))
+            .flatten()
+            .filter_map(|instr| match instr {
+                Instruction::RefFunc(func) => Some(func.index),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        context.refs.extend(refs);
+
+        let datas = section::<payload::Data>(&mut module)?;
+        if context
+            .datas
+            .is_some_and(|count| count as usize != datas.len())
+        {
+            invalid!("data count and data section have inconsistent lengths");
+        }
+
+        let datas = datas
+            .into_iter()
+            .map(|data| {
+                let mode = match data.init {
+                    DataInit::Passive => Mode::Passive,
+                    DataInit::Active { offset } => Mode::Active { index: 0, offset },
+                    DataInit::ActiveWithMemory { memory, offset } => Mode::Active {
+                        index: memory.index,
+                        offset,
+                    },
+                };
+
+                if let Mode::Active { index, offset } = &mode {
+                    let Some(memory) = context.memories.get(*index as usize) else {
+                        invalid!("unknown memory {index}");
+                    };
+
+                    let addr = memory.addr_type.val_type();
+                    const_expr::validate(offset, addr, &context, context.globals.len())?;
+                }
+
+                Ok(Segment {
+                    mode,
+                    init: data.blob,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let bodies = section::<payload::Code>(&mut module)?;
+        if func_types.len() != bodies.len() {
+            invalid!("function and code sections have inconsistent lengths");
+        }
+
+        let imported_funcs = (context.funcs.len() - func_types.len()) as u32;
+        let mut names = func_names(&module, &exports);
+
+        let funcs = iter::zip(func_types, bodies)
+            .enumerate()
+            .map(|(idx, (type_, body))| {
+                let body = body.try_contents().map_err(malformed)?;
+                let func_type = context.func_type(type_.index);
+                let Lowered {
+                    code,
+                    v128s,
+                    handlers,
+                    max_height,
+                } = lower::lower(body, func_type, &context)?;
+
+                let locals = body
+                    .locals
+                    .iter()
+                    .flat_map(|Locals { repeat, ty }| {
+                        iter::repeat_n(Cell::from_val(Val::default(ty)), *repeat as usize)
+                    })
+                    .collect();
+
+                Ok(Func {
+                    type_,
+                    params: func_type.params.len(),
+                    locals,
+                    results: func_type.results.len(),
+                    code: code.into_boxed_slice(),
+                    v128s: v128s.into_boxed_slice(),
+                    handlers: handlers.into_boxed_slice(),
+                    max_height,
+                    registers: None,
+                    name: names.remove(&(imported_funcs + idx as u32)).map(Arc::from),
+                    instance: 0,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let data = ModuleData {
+            context,
+            imports,
+            funcs,
+            tables,
+            globals,
+            exports,
+            start,
+            elems,
+            datas,
+        };
+
+        Ok(Module {
+            data: Arc::new(data),
+        })
+    }
+
+    /// Returns the external values the module imports, in order.
+    pub fn imports(&self) -> impl ExactSizeIterator<Item = ImportType<'_>> {
+        let ModuleData {
+            context, imports, ..
+        } = &*self.data;
+
+        imports.iter().map(|import| {
+            let type_ = match &import.desc {
+                ImportDesc::Func(type_) => {
+                    ExternType::Func(types::FuncType::from_binary(context.func_type(type_.index)))
+                }
+                ImportDesc::Table(type_) => ExternType::Table(TableType::from_binary(type_)),
+                ImportDesc::Mem(type_) => ExternType::Mem(MemType::from_binary(type_)),
+                ImportDesc::Global(type_) => ExternType::Global(GlobalType::from_binary(type_)),
+                ImportDesc::Exception(type_) => ExternType::Tag(types::FuncType::from_binary(
+                    context.func_type(type_.func_type.index),
+                )),
+            };
+
+            ImportType {
+                module: &import.path.module,
+                name: &import.path.name,
+                type_,
+            }
+        })
+    }
+
+    /// Returns the external values the module exports, in order.
+    pub fn exports(&self) -> impl ExactSizeIterator<Item = ExportType<'_>> {
+        let ModuleData {
+            context, exports, ..
+        } = &*self.data;
+
+        exports.iter().map(|export| {
+            let type_ = match &export.desc {
+                ExportDesc::Func(func) => {
+                    let type_ = context.funcs[func.index as usize];
+                    ExternType::Func(types::FuncType::from_binary(context.func_type(type_.index)))
+                }
+                ExportDesc::Table(table) => ExternType::Table(context.tables[table.index as usize]),
+                ExportDesc::Mem(memory) => ExternType::Mem(context.memories[memory.index as usize]),
+                ExportDesc::Global(global) => {
+                    ExternType::Global(context.globals[global.index as usize])
+                }
+                ExportDesc::Exception(tag) => {
+                    let type_ = context.tags[tag.index as usize];
+                    ExternType::Tag(types::FuncType::from_binary(context.func_type(type_.index)))
+                }
+            };
+
+            ExportType {
+                name: &export.name,
+                type_,
+            }
+        })
+    }
+}
+
+impl Context {
+    /// Returns the function type `type_`.
+    pub(crate) fn type_(&self, type_: TypeId) -> Result<&FuncType> {
+        match self.composite_type(type_)? {
+            CompositeType::Func(func_type) => Ok(func_type),
+            _ => invalid!("type mismatch: type {} is not a function type", type_.index),
+        }
+    }
+
+    /// Returns the fields of the structure type `type_`.
+    pub(crate) fn struct_type(&self, type_: TypeId) -> Result<&[FieldType]> {
+        match self.composite_type(type_)? {
+            CompositeType::Struct(fields) => Ok(fields),
+            _ => invalid!(
+                "type mismatch: type {} is not a structure type",
+                type_.index
+            ),
+        }
+    }
+
+    /// Returns the element type of the array type `type_`.
+    pub(crate) fn array_type(&self, type_: TypeId) -> Result<&FieldType> {
+        match self.composite_type(type_)? {
+            CompositeType::Array(field) => Ok(field),
+            _ => invalid!("type mismatch: type {} is not an array type", type_.index),
+        }
+    }
+
+    fn composite_type(&self, type_: TypeId) -> Result<&CompositeType> {
+        match self.types.get(type_.index as usize) {
+            Some(sub_type) => Ok(&sub_type.composite_type),
+            None => invalid!("unknown type {}", type_.index),
+        }
+    }
+
+    /// Returns the function type at `index`, which was validated.
+    pub(crate) fn func_type(&self, index: u32) -> &FuncType {
+        match &self.types[index as usize].composite_type {
+            CompositeType::Func(type_) => type_,
+            _ => unreachable!("type {index} is not a function type"),
+        }
+    }
+
+    /// Checks that the concrete type `type_` refers to, if any, exists.
+    pub(crate) fn check(&self, type_: ValType) -> Result<ValType> {
+        if let ValType::RefType(RefType {
+            heap_type: HeapType::Type(index),
+            ..
+        }) = type_
+        {
+            self.composite_type(TypeId::from(index))?;
+        }
+
+        Ok(type_)
+    }
+
+    /// Returns the type of a `ref.null` of the heap type `type_`.
+    pub(crate) fn ref_null(&self, type_: &wasmbin::types::HeapType) -> Result<RefType> {
+        let type_ = RefType::nullable(HeapType::from_binary(type_));
+        self.check(ValType::RefType(type_))?;
+
+        Ok(type_)
+    }
+
+    /// Whether a value of type `actual` can be used where one of type
+    /// `expected` is.
+    pub(crate) fn matches(&self, actual: ValType, expected: ValType) -> bool {
+        actual.matches(expected, self)
+    }
+
+    pub(crate) fn matches_ref(&self, actual: RefType, expected: RefType) -> bool {
+        self.matches(ValType::RefType(actual), ValType::RefType(expected))
+    }
+
+    // Whether a field of type `actual` can be used where one of type
+    // `expected` is, mutable fields being invariant.
+    fn field_matches(&self, actual: &FieldType, expected: &FieldType) -> bool {
+        let storage_matches = |lhs: &StorageType, rhs: &StorageType| match (lhs, rhs) {
+            (StorageType::Value(lhs), StorageType::Value(rhs)) => {
+                self.matches(ValType::from_binary(lhs), ValType::from_binary(rhs))
+            }
+            (lhs, rhs) => lhs == rhs,
+        };
+
+        actual.mutable == expected.mutable
+            && storage_matches(&actual.storage_type, &expected.storage_type)
+            && (!actual.mutable || storage_matches(&expected.storage_type, &actual.storage_type))
+    }
+
+    // Checks that the types only refer to the types of their recursive group
+    // and of the groups before them, and finds the first type each one is
+    // equivalent to. It is the type at the same position in the first group
+    // whose types are the same once the types they refer to are replaced by
+    // their canonical type, the types of the group being replaced by their
+    // position in it.
+    fn canonicalize(&mut self) -> Result<()> {
+        // The groups that are not equivalent to an earlier one, by first
+        // type, along with their resolved types.
+        let mut distinct = Vec::<(u32, Vec<SubType>)>::new();
+        let mut first = 0;
+
+        for len in self.groups.clone() {
+            let end = first + len;
+            let group = &self.types[first as usize..end as usize];
+
+            for (index, type_) in (first..).zip(group) {
+                let refs = type_.visit(|type_: &TypeId| match type_.index {
+                    other if other < end => Ok(()),
+                    other => Err(other),
+                });
+                if let Err(VisitError::Custom(other)) = refs {
+                    invalid!("unknown type {other}");
+                }
+
+                match type_.supertypes[..] {
+                    [] => {}
+                    [supertype] if supertype.index < index => {}
+                    [supertype] => invalid!("unknown type {}", supertype.index),
+                    _ => invalid!("multiple supertypes"),
+                }
+            }
+
+            let mut resolve = |base: u32| {
+                self.canonical.truncate(first as usize);
+                self.canonical.extend(base..base + len);
+                group
+                    .iter()
+                    .map(|type_| types::resolve(type_, &self.canonical))
+                    .collect::<Vec<_>>()
+            };
+
+            let equivalent = distinct
+                .iter()
+                .find(|(base, types)| types.len() == group.len() && resolve(*base) == *types)
+                .map(|(base, _)| *base);
+
+            match equivalent {
+                Some(base) => {
+                    resolve(base);
+                }
+                None => {
+                    let types = resolve(first);
+                    distinct.push((first, types));
+                }
+            }
+
+            for index in first..end {
+                self.check_supertype(index)?;
+            }
+
+            first = end;
+        }
+
+        Ok(())
+    }
+
+    // Checks that the type at `index` matches its supertype, if it has one,
+    // which must not be final.
+    fn check_supertype(&self, index: u32) -> Result<()> {
+        let type_ = &self.types[index as usize];
+        let Some(supertype) = type_.supertypes.first() else {
+            return Ok(());
+        };
+
+        let supertype = &self.types[supertype.index as usize];
+        if supertype.is_final {
+            invalid!("sub type {index} of a final type");
+        }
+
+        let val_types = |types: &[wasmbin::types::ValueType]| {
+            types.iter().map(ValType::from_binary).collect::<Vec<_>>()
+        };
+        let all_match = |actual: Vec<ValType>, expected: Vec<ValType>| {
+            actual.len() == expected.len()
+                && iter::zip(actual, expected).all(|(lhs, rhs)| self.matches(lhs, rhs))
+        };
+
+        // Parameters are contravariant, and results covariant.
+        let matches = match (&type_.composite_type, &supertype.composite_type) {
+            (CompositeType::Func(lhs), CompositeType::Func(rhs)) => {
+                all_match(val_types(&rhs.params), val_types(&lhs.params))
+                    && all_match(val_types(&lhs.results), val_types(&rhs.results))
+            }
+            (CompositeType::Struct(lhs), CompositeType::Struct(rhs)) => {
+                lhs.len() >= rhs.len()
+                    && iter::zip(lhs, rhs).all(|(lhs, rhs)| self.field_matches(lhs, rhs))
+            }
+            (CompositeType::Array(lhs), CompositeType::Array(rhs)) => self.field_matches(lhs, rhs),
+            _ => false,
+        };
+
+        if !matches {
+            invalid!("type mismatch: type {index} doesn't match its supertype");
+        }
+
+        Ok(())
+    }
+
+    // Validates the type of a tag, which has no results, and adds the tag.
+    fn tag(&mut self, type_: TypeId) -> Result<()> {
+        if !self.type_(type_)?.results.is_empty() {
+            invalid!("non-empty tag result type");
+        }
+
+        self.tags.push(type_);
+
+        Ok(())
+    }
+
+    // Validates an element segment, whose elements are turned into constant
+    // expressions.
+    fn elem(&mut self, elem: Element) -> Result<Segment<Vec<Expression>>> {
+        let funcs = |funcs: Vec<wasmbin::indices::FuncId>| {
+            funcs
+                .into_iter()
+                .map(|func| vec![Instruction::RefFunc(func)])
+                .collect::<Vec<_>>()
+        };
+
+        let func_ref = RefType::FUNCREF;
+
+        let (mode, type_, init) = match elem {
+            Element::ActiveWithFuncs { offset, funcs: f } => {
+                (Mode::Active { index: 0, offset }, func_ref, funcs(f))
+            }
+            Element::PassiveWithFuncs { funcs: f, .. } => (Mode::Passive, func_ref, funcs(f)),
+            Element::ActiveWithTableAndFuncs {
+                table,
+                offset,
+                funcs: f,
+                ..
+            } => (
+                Mode::Active {
+                    index: table.index,
+                    offset,
+                },
+                func_ref,
+                funcs(f),
+            ),
+            Element::DeclarativeWithFuncs { funcs: f, .. } => {
+                (Mode::Declarative, func_ref, funcs(f))
+            }
+            Element::ActiveWithExprs { offset, exprs } => {
+                (Mode::Active { index: 0, offset }, func_ref, exprs)
+            }
+            Element::PassiveWithExprs { ty, exprs } => {
+                (Mode::Passive, RefType::from_binary(&ty), exprs)
+            }
+            Element::ActiveWithTableAndExprs {
+                table,
+                offset,
+                ty,
+                exprs,
+            } => (
+                Mode::Active {
+                    index: table.index,
+                    offset,
+                },
+                RefType::from_binary(&ty),
+                exprs,
+            ),
+            Element::DeclarativeWithExprs { ty, exprs } => {
+                (Mode::Declarative, RefType::from_binary(&ty), exprs)
+            }
+        };
+
+        self.check(ValType::RefType(type_))?;
+        for expr in &init {
+            const_expr::validate(expr, ValType::RefType(type_), self, self.globals.len())?;
+        }
+
+        if let Mode::Active { index, offset } = &mode {
+            let Some(table) = self.tables.get(*index as usize) else {
+                invalid!("unknown table {index}");
+            };
+
+            if !self.matches_ref(type_, table.type_) {
+                invalid!("type mismatch: element segment doesn't match its table");
+            }
+
+            let addr = table.addr_type.val_type();
+            const_expr::validate(offset, addr, self, self.globals.len())?;
+        }
+
+        self.elems.push(type_);
+
+        Ok(Segment { mode, init })
+    }
+}
+
+impl Types for Context {
+    fn sub_type(&self, index: u32) -> &SubType {
+        &self.types[index as usize]
+    }
+
+    fn canonical(&self, index: u32) -> u32 {
+        self.canonical[index as usize]
+    }
+}
+
+// The contents of the section `T`, which is empty if the module doesn't
+// have one.
+fn section<T>(module: &mut wasmbin::Module) -> Result<T>
+where
+    T: StdPayload + Default,
+{
+    match module.find_std_section_mut::<T>() {
+        Some(section) => section
+            .try_contents_mut()
+            .map(std::mem::take)
+            .map_err(malformed),
+        None => Ok(T::default()),
+    }
+}
+
+/// Returns the names of the functions, from the name section if it is well
+/// formed, or else from the exports.
+fn func_names(module: &wasmbin::Module, exports: &[sections::Export]) -> HashMap<u32, String> {
+    let mut names = exports
+        .iter()
+        .filter_map(|export| match &export.desc {
+            ExportDesc::Func(func) => Some((func.index, export.name.clone())),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Custom sections are never an error.
+    let section_names = module.sections.iter().find_map(|section| {
+        let Section::Custom(section) = section else {
+            return None;
+        };
+        let CustomSection::Name(subsections) = section.try_contents().ok()? else {
+            return None;
+        };
+
+        subsections
+            .try_contents()
+            .ok()?
+            .iter()
+            .find_map(|subsection| match subsection {
+                NameSubSection::Func(names) => names.try_contents().ok(),
+                _ => None,
+            })
+    });
+
+    for name in section_names.into_iter().flat_map(|names| &names.items) {
+        names.insert(name.index.index, name.value.clone());
+    }
+
+    names
+}
+
+/// Returns the error of a binary that can't be decoded.
+pub(crate) fn binary_error(error: DecodeError) -> ModuleError {
+    ModuleError::Malformed(MalformedError(error))
+}
+
+/// Returns the error of a binary whose sections can't be decoded.
+pub(crate) fn decode_error(error: VisitError<Infallible>) -> ModuleError {
+    binary_error(DecodeError::from(error))
+}
+
+pub(crate) fn malformed(error: wasmbin::io::DecodeError) -> ValidationError {
+    ValidationError(format!("malformed section: {error}"))
+}
+
+fn check_limits(limits: &crate::types::Limits, max: u64, kind: &str) -> Result<()> {
+    if limits.min > max || limits.max.is_some_and(|limit| limit > max) {
+        invalid!("{kind} size must be at most {max}");
+    }
+
+    if limits.max.is_some_and(|limit| limit < limits.min) {
+        invalid!("size minimum must not be greater than maximum");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,307 @@
+//! Numeric operations whose semantics differ from the ones of the Rust
+//! operators.
+
+use crate::{
+    cell::{Cell, FromCell},
+    interpreter::Trap,
+    ir::Op,
+    simd,
+};
+
+macro_rules! int_div {
+    ($ty:ty, $uty:ty, $div_s:ident, $div_u:ident, $rem_s:ident, $rem_u:ident) => {
+        pub(crate) fn $div_s(lhs: $ty, rhs: $ty) -> Result<$ty, Trap> {
+            if rhs == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+
+            // `MIN / -1` overflows.
+            lhs.checked_div(rhs).ok_or(Trap::IntegerOverflow)
+        }
+
+        pub(crate) fn $div_u(lhs: $ty, rhs: $ty) -> Result<$ty, Trap> {
+            if rhs == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+
+            Ok(((lhs as $uty) / (rhs as $uty)) as $ty)
+        }
+
+        pub(crate) fn $rem_s(lhs: $ty, rhs: $ty) -> Result<$ty, Trap> {
+            if rhs == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+
+            // Unlike the division, `MIN % -1` is defined and is zero.
+            Ok(lhs.wrapping_rem(rhs))
+        }
+
+        pub(crate) fn $rem_u(lhs: $ty, rhs: $ty) -> Result<$ty, Trap> {
+            if rhs == 0 {
+                return Err(Trap::IntegerDivideByZero);
+            }
+
+            Ok(((lhs as $uty) % (rhs as $uty)) as $ty)
+        }
+    };
+}
+
+int_div!(i32, u32, i32_div_s, i32_div_u, i32_rem_s, i32_rem_u);
+int_div!(i64, u64, i64_div_s, i64_div_u, i64_rem_s, i64_rem_u);
+
+macro_rules! float_min_max {
+    ($ty:ty, $min:ident, $max:ident) => {
+        /// The minimum of two floats, propagating NaNs and ordering `-0` before
+        /// `+0`.
+        pub(crate) fn $min(lhs: $ty, rhs: $ty) -> $ty {
+            if lhs.is_nan() || rhs.is_nan() {
+                <$ty>::NAN
+            } else if lhs == rhs {
+                // Only differs for zeros of different signs.
+                <$ty>::from_bits(lhs.to_bits() | rhs.to_bits())
+            } else {
+                lhs.min(rhs)
+            }
+        }
+
+        /// The maximum of two floats, propagating NaNs and ordering `-0` before
+        /// `+0`.
+        pub(crate) fn $max(lhs: $ty, rhs: $ty) -> $ty {
+            if lhs.is_nan() || rhs.is_nan() {
+                <$ty>::NAN
+            } else if lhs == rhs {
+                <$ty>::from_bits(lhs.to_bits() & rhs.to_bits())
+            } else {
+                lhs.max(rhs)
+            }
+        }
+    };
+}
+
+float_min_max!(f32, f32_min, f32_max);
+float_min_max!(f64, f64_min, f64_max);
+
+macro_rules! trunc {
+    ($name:ident, $float:ty => $int:ty, $min:expr, $max:expr) => {
+        /// Truncates a float to an integer, trapping if it is NaN or out of
+        /// the range of the integer type.
+        ///
+        /// `$min` and `$max` are the exclusive bounds of the floats that can
+        /// be truncated.
+        pub(crate) fn $name(val: $float) -> Result<$int, Trap> {
+            if val.is_nan() {
+                return Err(Trap::InvalidConversionToInteger);
+            }
+
+            if val <= $min || val >= $max {
+                return Err(Trap::IntegerOverflow);
+            }
+
+            Ok(val as $int)
+        }
+    };
+}
+
+trunc!(i32_trunc_f32_s, f32 => i32, -2147483904.0_f32, 2147483648.0_f32);
+trunc!(i32_trunc_f32_u, f32 => u32, -1.0_f32, 4294967296.0_f32);
+trunc!(i32_trunc_f64_s, f64 => i32, -2147483649.0_f64, 2147483648.0_f64);
+trunc!(i32_trunc_f64_u, f64 => u32, -1.0_f64, 4294967296.0_f64);
+trunc!(i64_trunc_f32_s, f32 => i64, -9223373136366403584.0_f32, 9223372036854775808.0_f32);
+trunc!(i64_trunc_f32_u, f32 => u64, -1.0_f32, 18446744073709551616.0_f32);
+trunc!(i64_trunc_f64_s, f64 => i64, -9223372036854777856.0_f64, 9223372036854775808.0_f64);
+trunc!(i64_trunc_f64_u, f64 => u64, -1.0_f64, 18446744073709551616.0_f64);
+
+/// Evaluates a numeric op that pops one value.
+#[inline(always)]
+pub(crate) fn unary(op: Op, val: Cell) -> Result<Cell, Trap> {
+    macro_rules! unop {
+        ($ty:ty, |$val:ident| $e:expr) => {{
+            let $val = <$ty>::from_cell(val);
+            Cell::from($e)
+        }};
+    }
+
+    Ok(match op {
+        Op::I32Eqz => unop!(i32, |val| i32::from(val == 0)),
+
+        Op::I64Eqz => unop!(i64, |val| i32::from(val == 0)),
+
+        Op::I32Clz => unop!(i32, |val| val.leading_zeros() as i32),
+        Op::I32Ctz => unop!(i32, |val| val.trailing_zeros() as i32),
+        Op::I32PopCnt => unop!(i32, |val| val.count_ones() as i32),
+
+        Op::I64Clz => unop!(i64, |val| i64::from(val.leading_zeros())),
+        Op::I64Ctz => unop!(i64, |val| i64::from(val.trailing_zeros())),
+        Op::I64PopCnt => unop!(i64, |val| i64::from(val.count_ones())),
+
+        Op::F32Abs => unop!(f32, |val| val.abs()),
+        Op::F32Neg => unop!(f32, |val| -val),
+        Op::F32Ceil => unop!(f32, |val| val.ceil()),
+        Op::F32Floor => unop!(f32, |val| val.floor()),
+        Op::F32Trunc => unop!(f32, |val| val.trunc()),
+        Op::F32Nearest => unop!(f32, |val| val.round_ties_even()),
+        Op::F32Sqrt => unop!(f32, |val| val.sqrt()),
+
+        Op::F64Abs => unop!(f64, |val| val.abs()),
+        Op::F64Neg => unop!(f64, |val| -val),
+        Op::F64Ceil => unop!(f64, |val| val.ceil()),
+        Op::F64Floor => unop!(f64, |val| val.floor()),
+        Op::F64Trunc => unop!(f64, |val| val.trunc()),
+        Op::F64Nearest => unop!(f64, |val| val.round_ties_even()),
+        Op::F64Sqrt => unop!(f64, |val| val.sqrt()),
+
+        Op::I32WrapI64 => unop!(i64, |val| val as i32),
+        Op::I32TruncF32S => unop!(f32, |val| i32_trunc_f32_s(val)?),
+        Op::I32TruncF32U => unop!(f32, |val| i32_trunc_f32_u(val)? as i32),
+        Op::I32TruncF64S => unop!(f64, |val| i32_trunc_f64_s(val)?),
+        Op::I32TruncF64U => unop!(f64, |val| i32_trunc_f64_u(val)? as i32),
+
+        Op::I64ExtendI32S => unop!(i32, |val| i64::from(val)),
+        Op::I64ExtendI32U => unop!(u32, |val| i64::from(val)),
+        Op::I64TruncF32S => unop!(f32, |val| i64_trunc_f32_s(val)?),
+        Op::I64TruncF32U => unop!(f32, |val| i64_trunc_f32_u(val)? as i64),
+        Op::I64TruncF64S => unop!(f64, |val| i64_trunc_f64_s(val)?),
+        Op::I64TruncF64U => unop!(f64, |val| i64_trunc_f64_u(val)? as i64),
+
+        Op::F32ConvertI32S => unop!(i32, |val| val as f32),
+        Op::F32ConvertI32U => unop!(u32, |val| val as f32),
+        Op::F32ConvertI64S => unop!(i64, |val| val as f32),
+        Op::F32ConvertI64U => unop!(u64, |val| val as f32),
+        Op::F32DemoteF64 => unop!(f64, |val| val as f32),
+
+        Op::F64ConvertI32S => unop!(i32, |val| f64::from(val)),
+        Op::F64ConvertI32U => unop!(u32, |val| f64::from(val)),
+        Op::F64ConvertI64S => unop!(i64, |val| val as f64),
+        Op::F64ConvertI64U => unop!(u64, |val| val as f64),
+        Op::F64PromoteF32 => unop!(f32, |val| f64::from(val)),
+
+        Op::I32ReinterpretF32 => unop!(f32, |val| val.to_bits() as i32),
+
+        Op::I64ReinterpretF64 => unop!(f64, |val| val.to_bits() as i64),
+
+        Op::F32ReinterpretI32 => unop!(u32, |val| f32::from_bits(val)),
+
+        Op::F64ReinterpretI64 => unop!(u64, |val| f64::from_bits(val)),
+
+        Op::I32Extend8S => unop!(i32, |val| i32::from(val as i8)),
+        Op::I32Extend16S => unop!(i32, |val| i32::from(val as i16)),
+
+        Op::I64Extend8S => unop!(i64, |val| i64::from(val as i8)),
+        Op::I64Extend16S => unop!(i64, |val| i64::from(val as i16)),
+        Op::I64Extend32S => unop!(i64, |val| i64::from(val as i32)),
+
+        // Saturating truncations are exactly what `as` does.
+        Op::I32TruncSatF32S => unop!(f32, |val| val as i32),
+        Op::I32TruncSatF32U => unop!(f32, |val| val as u32 as i32),
+        Op::I32TruncSatF64S => unop!(f64, |val| val as i32),
+        Op::I32TruncSatF64U => unop!(f64, |val| val as u32 as i32),
+
+        Op::I64TruncSatF32S => unop!(f32, |val| val as i64),
+        Op::I64TruncSatF32U => unop!(f32, |val| val as u64 as i64),
+        Op::I64TruncSatF64S => unop!(f64, |val| val as i64),
+        Op::I64TruncSatF64U => unop!(f64, |val| val as u64 as i64),
+
+        // Vector ops are evaluated out of line, to keep this function small.
+        op => simd::unary(op, val),
+    })
+}
+
+/// Evaluates a numeric op that pops two values.
+#[inline(always)]
+pub(crate) fn binary(op: Op, lhs: Cell, rhs: Cell) -> Result<Cell, Trap> {
+    macro_rules! binop {
+        ($ty:ty, |$lhs:ident, $rhs:ident| $e:expr) => {{
+            let $lhs = <$ty>::from_cell(lhs);
+            let $rhs = <$ty>::from_cell(rhs);
+            Cell::from($e)
+        }};
+    }
+
+    Ok(match op {
+        Op::I32Eq => binop!(i32, |lhs, rhs| i32::from(lhs == rhs)),
+        Op::I32Ne => binop!(i32, |lhs, rhs| i32::from(lhs != rhs)),
+        Op::I32LtS => binop!(i32, |lhs, rhs| i32::from(lhs < rhs)),
+        Op::I32LtU => binop!(u32, |lhs, rhs| i32::from(lhs < rhs)),
+        Op::I32GtS => binop!(i32, |lhs, rhs| i32::from(lhs > rhs)),
+        Op::I32GtU => binop!(u32, |lhs, rhs| i32::from(lhs > rhs)),
+        Op::I32LeS => binop!(i32, |lhs, rhs| i32::from(lhs <= rhs)),
+        Op::I32LeU => binop!(u32, |lhs, rhs| i32::from(lhs <= rhs)),
+        Op::I32GeS => binop!(i32, |lhs, rhs| i32::from(lhs >= rhs)),
+        Op::I32GeU => binop!(u32, |lhs, rhs| i32::from(lhs >= rhs)),
+
+        Op::I64Eq => binop!(i64, |lhs, rhs| i32::from(lhs == rhs)),
+        Op::I64Ne => binop!(i64, |lhs, rhs| i32::from(lhs != rhs)),
+        Op::I64LtS => binop!(i64, |lhs, rhs| i32::from(lhs < rhs)),
+        Op::I64LtU => binop!(u64, |lhs, rhs| i32::from(lhs < rhs)),
+        Op::I64GtS => binop!(i64, |lhs, rhs| i32::from(lhs > rhs)),
+        Op::I64GtU => binop!(u64, |lhs, rhs| i32::from(lhs > rhs)),
+        Op::I64LeS => binop!(i64, |lhs, rhs| i32::from(lhs <= rhs)),
+        Op::I64LeU => binop!(u64, |lhs, rhs| i32::from(lhs <= rhs)),
+        Op::I64GeS => binop!(i64, |lhs, rhs| i32::from(lhs >= rhs)),
+        Op::I64GeU => binop!(u64, |lhs, rhs| i32::from(lhs >= rhs)),
+
+        Op::F32Eq => binop!(f32, |lhs, rhs| i32::from(lhs == rhs)),
+        Op::F32Ne => binop!(f32, |lhs, rhs| i32::from(lhs != rhs)),
+        Op::F32Lt => binop!(f32, |lhs, rhs| i32::from(lhs < rhs)),
+        Op::F32Gt => binop!(f32, |lhs, rhs| i32::from(lhs > rhs)),
+        Op::F32Le => binop!(f32, |lhs, rhs| i32::from(lhs <= rhs)),
+        Op::F32Ge => binop!(f32, |lhs, rhs| i32::from(lhs >= rhs)),
+
+        Op::F64Eq => binop!(f64, |lhs, rhs| i32::from(lhs == rhs)),
+        Op::F64Ne => binop!(f64, |lhs, rhs| i32::from(lhs != rhs)),
+        Op::F64Lt => binop!(f64, |lhs, rhs| i32::from(lhs < rhs)),
+        Op::F64Gt => binop!(f64, |lhs, rhs| i32::from(lhs > rhs)),
+        Op::F64Le => binop!(f64, |lhs, rhs| i32::from(lhs <= rhs)),
+        Op::F64Ge => binop!(f64, |lhs, rhs| i32::from(lhs >= rhs)),
+
+        Op::I32Add => binop!(i32, |lhs, rhs| lhs.wrapping_add(rhs)),
+        Op::I32Sub => binop!(i32, |lhs, rhs| lhs.wrapping_sub(rhs)),
+        Op::I32Mul => binop!(i32, |lhs, rhs| lhs.wrapping_mul(rhs)),
+        Op::I32DivS => binop!(i32, |lhs, rhs| i32_div_s(lhs, rhs)?),
+        Op::I32DivU => binop!(i32, |lhs, rhs| i32_div_u(lhs, rhs)?),
+        Op::I32RemS => binop!(i32, |lhs, rhs| i32_rem_s(lhs, rhs)?),
+        Op::I32RemU => binop!(i32, |lhs, rhs| i32_rem_u(lhs, rhs)?),
+        Op::I32And => binop!(i32, |lhs, rhs| lhs & rhs),
+        Op::I32Or => binop!(i32, |lhs, rhs| lhs | rhs),
+        Op::I32Xor => binop!(i32, |lhs, rhs| lhs ^ rhs),
+        Op::I32Shl => binop!(i32, |lhs, rhs| lhs.wrapping_shl(rhs as u32)),
+        Op::I32ShrS => binop!(i32, |lhs, rhs| lhs.wrapping_shr(rhs as u32)),
+        Op::I32ShrU => binop!(u32, |lhs, rhs| lhs.wrapping_shr(rhs)),
+        Op::I32RotL => binop!(i32, |lhs, rhs| lhs.rotate_left(rhs as u32)),
+        Op::I32RotR => binop!(i32, |lhs, rhs| lhs.rotate_right(rhs as u32)),
+
+        Op::I64Add => binop!(i64, |lhs, rhs| lhs.wrapping_add(rhs)),
+        Op::I64Sub => binop!(i64, |lhs, rhs| lhs.wrapping_sub(rhs)),
+        Op::I64Mul => binop!(i64, |lhs, rhs| lhs.wrapping_mul(rhs)),
+        Op::I64DivS => binop!(i64, |lhs, rhs| i64_div_s(lhs, rhs)?),
+        Op::I64DivU => binop!(i64, |lhs, rhs| i64_div_u(lhs, rhs)?),
+        Op::I64RemS => binop!(i64, |lhs, rhs| i64_rem_s(lhs, rhs)?),
+        Op::I64RemU => binop!(i64, |lhs, rhs| i64_rem_u(lhs, rhs)?),
+        Op::I64And => binop!(i64, |lhs, rhs| lhs & rhs),
+        Op::I64Or => binop!(i64, |lhs, rhs| lhs | rhs),
+        Op::I64Xor => binop!(i64, |lhs, rhs| lhs ^ rhs),
+        Op::I64Shl => binop!(i64, |lhs, rhs| lhs.wrapping_shl(rhs as u32)),
+        Op::I64ShrS => binop!(i64, |lhs, rhs| lhs.wrapping_shr(rhs as u32)),
+        Op::I64ShrU => binop!(u64, |lhs, rhs| lhs.wrapping_shr(rhs as u32)),
+        Op::I64RotL => binop!(i64, |lhs, rhs| lhs.rotate_left(rhs as u32)),
+        Op::I64RotR => binop!(i64, |lhs, rhs| lhs.rotate_right(rhs as u32)),
+
+        Op::F32Add => binop!(f32, |lhs, rhs| lhs + rhs),
+        Op::F32Sub => binop!(f32, |lhs, rhs| lhs - rhs),
+        Op::F32Mul => binop!(f32, |lhs, rhs| lhs * rhs),
+        Op::F32Div => binop!(f32, |lhs, rhs| lhs / rhs),
+        Op::F32Min => binop!(f32, |lhs, rhs| f32_min(lhs, rhs)),
+        Op::F32Max => binop!(f32, |lhs, rhs| f32_max(lhs, rhs)),
+        Op::F32CopySign => binop!(f32, |lhs, rhs| lhs.copysign(rhs)),
+
+        Op::F64Add => binop!(f64, |lhs, rhs| lhs + rhs),
+        Op::F64Sub => binop!(f64, |lhs, rhs| lhs - rhs),
+        Op::F64Mul => binop!(f64, |lhs, rhs| lhs * rhs),
+        Op::F64Div => binop!(f64, |lhs, rhs| lhs / rhs),
+        Op::F64Min => binop!(f64, |lhs, rhs| f64_min(lhs, rhs)),
+        Op::F64Max => binop!(f64, |lhs, rhs| f64_max(lhs, rhs)),
+        Op::F64CopySign => binop!(f64, |lhs, rhs| lhs.copysign(rhs)),
+
+        op => simd::binary(op, lhs, rhs),
+    })
+}
@@ -0,0 +1,732 @@
+//! Translation of lowered functions to a register machine.
+//!
+//! The stack ops produced by lowering spend most of their time moving values
+//! between the locals and the top of the stack. Since the height of the
+//! operand stack is statically known at every op, each stack slot can be
+//! given a fixed register instead: register `r` of a frame is the cell at
+//! `fp + r`, so locals keep their index and stack slots are numbered after
+//! them. The frame layout is the same as the one of the stack engine, and so
+//! is the calling convention.
+//!
+//! `local.get` doesn't copy anything: the local is used in place by the op
+//! that consumes it, unless the local is overwritten first. Similarly, the
+//! result of an op followed by `local.set` is written directly to the local.
+//!
+//! Functions that use an op this translation doesn't know about are not
+//! translated, and are run by the stack engine instead.
+//!
+//! The register engine is experimental: the ops it saves are offset by larger
+//! ones, and it is not faster than the stack engine on every program yet.
+
+use wasmbin::indices::TypeId;
+
+use crate::{
+    cell::Cell,
+    ir::{Branch, I32Cmp, Op},
+    store::{Code, Func},
+};
+
+/// A register machine instruction.
+///
+/// Registers are offsets from the frame pointer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RegOp {
+    Unreachable,
+    Jump(u32),
+    JumpIfZero {
+        cond: u32,
+        pc: u32,
+    },
+    JumpIfNonZero {
+        cond: u32,
+        pc: u32,
+    },
+    /// Jumps to `pc` unless `src cmp imm` holds.
+    JumpUnlessCmpImm {
+        src: u32,
+        imm: i32,
+        cmp: I32Cmp,
+        pc: u32,
+    },
+    /// Takes the `min(index, len)`-th of the `len + 1` [`RegOp::Jump`]s that
+    /// follow.
+    BrTable {
+        index: u32,
+        len: u32,
+    },
+    /// Returns the values starting at `src`.
+    Return {
+        src: u32,
+    },
+    /// Calls a function whose arguments start at `base`. The results are
+    /// written at `base` too.
+    Call {
+        func: u32,
+        base: u32,
+    },
+    /// Calls the function at index `index` of `table`, like
+    /// [`RegOp::Call`].
+    CallIndirect {
+        type_: u32,
+        table: u32,
+        index: u32,
+        base: u32,
+    },
+    /// Calls a function whose arguments start at `base` in place of the
+    /// current one, whose frame is reused.
+    ReturnCall {
+        func: u32,
+        base: u32,
+    },
+    /// Calls the function at index `index` of `table`, like
+    /// [`RegOp::ReturnCall`].
+    ReturnCallIndirect {
+        type_: u32,
+        table: u32,
+        index: u32,
+        base: u32,
+    },
+
+    Copy {
+        dst: u32,
+        src: u32,
+    },
+    Const {
+        dst: u32,
+        val: Cell,
+    },
+    Select {
+        dst: u32,
+        lhs: u32,
+        rhs: u32,
+        cond: u32,
+    },
+    RefIsNull {
+        dst: u32,
+        src: u32,
+    },
+    GlobalGet {
+        dst: u32,
+        global: u32,
+    },
+    GlobalSet {
+        global: u32,
+        src: u32,
+    },
+
+    /// A load [`Op`].
+    Load {
+        op: Op,
+        dst: u32,
+        addr: u32,
+    },
+    /// A store [`Op`].
+    Store {
+        op: Op,
+        addr: u32,
+        src: u32,
+    },
+
+    I32Eqz {
+        dst: u32,
+        src: u32,
+    },
+    I32Add {
+        dst: u32,
+        lhs: u32,
+        rhs: u32,
+    },
+    I32Sub {
+        dst: u32,
+        lhs: u32,
+        rhs: u32,
+    },
+    I32AddImm {
+        dst: u32,
+        src: u32,
+        imm: i32,
+    },
+    I32Cmp {
+        dst: u32,
+        lhs: u32,
+        rhs: u32,
+        cmp: I32Cmp,
+    },
+    I32CmpImm {
+        dst: u32,
+        src: u32,
+        imm: i32,
+        cmp: I32Cmp,
+    },
+
+    /// Any other unary numeric [`Op`].
+    Unary {
+        op: Op,
+        dst: u32,
+        src: u32,
+    },
+    /// Any other binary numeric [`Op`].
+    Binary {
+        op: Op,
+        dst: u32,
+        lhs: u32,
+        rhs: u32,
+    },
+}
+
+impl RegOp {
+    fn set_target(&mut self, target: u32) {
+        match self {
+            RegOp::Jump(pc)
+            | RegOp::JumpIfZero { pc, .. }
+            | RegOp::JumpIfNonZero { pc, .. }
+            | RegOp::JumpUnlessCmpImm { pc, .. } => *pc = target,
+
+            op => unreachable!("op has no target: `{op:?}`"),
+        }
+    }
+
+    // The register written by the op, if it writes exactly one register.
+    fn dst_mut(&mut self) -> Option<&mut u32> {
+        match self {
+            RegOp::Copy { dst, .. }
+            | RegOp::Const { dst, .. }
+            | RegOp::Select { dst, .. }
+            | RegOp::RefIsNull { dst, .. }
+            | RegOp::GlobalGet { dst, .. }
+            | RegOp::Load { dst, .. }
+            | RegOp::I32Eqz { dst, .. }
+            | RegOp::I32Add { dst, .. }
+            | RegOp::I32Sub { dst, .. }
+            | RegOp::I32AddImm { dst, .. }
+            | RegOp::I32Cmp { dst, .. }
+            | RegOp::I32CmpImm { dst, .. }
+            | RegOp::Unary { dst, .. }
+            | RegOp::Binary { dst, .. } => Some(dst),
+
+            _ => None,
+        }
+    }
+}
+
+/// Translates the code of `func` to register ops, returning `None` if it
+/// uses an op that has no register counterpart.
+///
+/// `store` holds the functions and types the ops refer to, which are needed
+/// to know the stack effect of calls.
+pub(crate) fn translate(func: &Func, store: &Code) -> Option<Box<[RegOp]>> {
+    let locals = (func.params + func.locals.len()) as u32;
+
+    let mut translator = Translator {
+        out: Vec::with_capacity(func.code.len()),
+        pcs: vec![u32::MAX; func.code.len()],
+        fixups: Vec::new(),
+        heights: vec![None; func.code.len()],
+        targets: vec![false; func.code.len()],
+        locals,
+        height: Some(locals),
+        sources: (0..func.max_height as u32).collect(),
+        last_result: None,
+    };
+
+    for op in &func.code {
+        match *op {
+            Op::Jump(target)
+            | Op::JumpIfZero(target)
+            | Op::JumpIfNonZero(target)
+            | Op::JumpUnlessLocalCmpImm { pc: target, .. }
+            | Op::Br(Branch { pc: target, .. })
+            | Op::BrIf(Branch { pc: target, .. })
+            | Op::BrTableEntry(Branch { pc: target, .. }) => {
+                translator.targets[target as usize] = true;
+            }
+
+            _ => {}
+        }
+    }
+
+    let mut pc = 0;
+    while pc < func.code.len() {
+        pc = translator.op(pc, &func.code, func, store)?;
+    }
+
+    let Translator {
+        mut out,
+        pcs,
+        fixups,
+        ..
+    } = translator;
+
+    for (idx, target) in fixups {
+        out[idx].set_target(pcs[target as usize]);
+    }
+
+    Some(out.into_boxed_slice())
+}
+
+struct Translator {
+    out: Vec<RegOp>,
+    // The offset of the register op corresponding to each stack op.
+    pcs: Vec<u32>,
+    // Register ops whose destination is the stack op at the given offset.
+    fixups: Vec<(usize, u32)>,
+    // The stack height at the stack ops that are the destination of a
+    // forward branch.
+    heights: Vec<Option<u32>>,
+    // Whether a stack op is the destination of a branch.
+    targets: Vec<bool>,
+    locals: u32,
+    // The current stack height, or `None` in dead code.
+    height: Option<u32>,
+    // The register holding the value of each stack slot. It differs from
+    // the slot itself if the slot holds a local that was not copied yet.
+    sources: Vec<u32>,
+    // The register op that wrote the top of the stack, if it was emitted by
+    // the previous stack op.
+    last_result: Option<usize>,
+}
+
+impl Translator {
+    // Translates the stack op at `pc`, and returns the offset of the next
+    // stack op to translate.
+    fn op(&mut self, pc: usize, code: &[Op], func: &Func, store: &Code) -> Option<usize> {
+        // Results of the previous op can't be redirected across a branch
+        // destination.
+        let last_result = self.last_result.take().filter(|_| !self.targets[pc]);
+
+        if self.targets[pc] {
+            // Values must be in their slot when control flow merges.
+            if let Some(height) = self.height {
+                self.materialize(self.locals..height);
+            }
+
+            self.height = self.height.or(self.heights[pc]);
+        }
+
+        self.pcs[pc] = self.out.len() as u32;
+
+        let Some(h) = self.height else {
+            // Dead code is not lowered, but an op may follow a branch
+            // without being its destination.
+            return Some(pc + 1);
+        };
+
+        match code[pc] {
+            Op::Unreachable => {
+                self.out.push(RegOp::Unreachable);
+                self.height = None;
+            }
+
+            Op::Jump(target) => {
+                self.materialize(self.locals..h);
+                self.jump(RegOp::Jump(u32::MAX), target, h);
+                self.height = None;
+            }
+
+            Op::JumpIfZero(target) | Op::JumpIfNonZero(target) => {
+                let cond = self.source(h - 1);
+                let h = h - 1;
+                self.materialize(self.locals..h);
+
+                let op = match code[pc] {
+                    Op::JumpIfZero(_) => RegOp::JumpIfZero { cond, pc: u32::MAX },
+                    _ => RegOp::JumpIfNonZero { cond, pc: u32::MAX },
+                };
+
+                self.jump(op, target, h);
+                self.height = Some(h);
+            }
+
+            Op::JumpUnlessLocalCmpImm {
+                local,
+                imm,
+                cmp,
+                pc: target,
+            } => {
+                self.materialize(self.locals..h);
+                let op = RegOp::JumpUnlessCmpImm {
+                    src: local,
+                    imm,
+                    cmp,
+                    pc: u32::MAX,
+                };
+                self.jump(op, target, h);
+            }
+
+            Op::Br(branch) => {
+                self.take_branch(branch, h);
+                self.height = None;
+            }
+
+            Op::BrIf(branch) => {
+                let cond = self.source(h - 1);
+                let h = h - 1;
+                self.materialize(self.locals..h);
+
+                if self.is_jump(branch, h) {
+                    let op = RegOp::JumpIfNonZero { cond, pc: u32::MAX };
+                    self.jump(op, branch.pc, h);
+                } else {
+                    // Skip the moves when the branch is not taken.
+                    let skip = self.out.len();
+                    self.out.push(RegOp::JumpIfZero { cond, pc: u32::MAX });
+                    self.take_branch(branch, h);
+
+                    let next = self.out.len() as u32;
+                    self.out[skip].set_target(next);
+                }
+
+                self.height = Some(h);
+            }
+
+            Op::BrTable(len) => {
+                let index = self.source(h - 1);
+                let h = h - 1;
+                self.materialize(self.locals..h);
+
+                self.out.push(RegOp::BrTable { index, len });
+
+                let entries = &code[pc + 1..pc + 2 + len as usize];
+                let first = self.out.len();
+                self.out
+                    .extend((0..entries.len()).map(|_| RegOp::Jump(u32::MAX)));
+
+                // Branches that move values go through a stub.
+                for (idx, entry) in entries.iter().enumerate() {
+                    let Op::BrTableEntry(branch) = *entry else {
+                        unreachable!("invalid branch table entry")
+                    };
+
+                    if self.is_jump(branch, h) {
+                        self.fixups.push((first + idx, branch.pc));
+                        self.record_height(branch.pc, h);
+                    } else {
+                        let stub = self.out.len() as u32;
+                        self.out[first + idx].set_target(stub);
+                        self.take_branch(branch, h);
+                    }
+                }
+
+                for offset in pc + 1..pc + 2 + len as usize {
+                    self.pcs[offset] = self.out.len() as u32;
+                }
+
+                self.height = None;
+                return Some(pc + 2 + len as usize);
+            }
+
+            Op::BrTableEntry(_) => unreachable!("branch table entry outside of a table"),
+
+            Op::Return => {
+                self.ret(func, h);
+                self.height = None;
+            }
+
+            Op::ReturnLocal(local) => {
+                self.out.push(RegOp::Return { src: local });
+                self.height = None;
+            }
+
+            Op::Call(callee) => {
+                let callee_func = &store.funcs[callee as usize];
+                let base = h - callee_func.params as u32;
+
+                self.materialize(base..h);
+                self.out.push(RegOp::Call { func: callee, base });
+
+                let h = base + callee_func.results as u32;
+                self.reset_sources(base..h);
+                self.height = Some(h);
+            }
+
+            Op::CallIndirect { type_, table } => {
+                let index = self.source(h - 1);
+                let callee_type = &store[TypeId::from(type_)];
+                let base = h - 1 - callee_type.params.len() as u32;
+
+                self.materialize(base..h - 1);
+                self.out.push(RegOp::CallIndirect {
+                    type_,
+                    table,
+                    index,
+                    base,
+                });
+
+                let h = base + callee_type.results.len() as u32;
+                self.reset_sources(base..h);
+                self.height = Some(h);
+            }
+
+            Op::ReturnCall(callee) => {
+                let base = h - store.funcs[callee as usize].params as u32;
+
+                self.materialize(base..h);
+                self.out.push(RegOp::ReturnCall { func: callee, base });
+                self.height = None;
+            }
+
+            Op::ReturnCallIndirect { type_, table } => {
+                let index = self.source(h - 1);
+                let base = h - 1 - store[TypeId::from(type_)].params.len() as u32;
+
+                self.materialize(base..h - 1);
+                self.out.push(RegOp::ReturnCallIndirect {
+                    type_,
+                    table,
+                    index,
+                    base,
+                });
+                self.height = None;
+            }
+
+            Op::Drop => self.height = Some(h - 1),
+
+            Op::Select => {
+                let (lhs, rhs, cond) = (self.source(h - 3), self.source(h - 2), self.source(h - 1));
+                self.emit_result(
+                    RegOp::Select {
+                        dst: h - 3,
+                        lhs,
+                        rhs,
+                        cond,
+                    },
+                    h - 2,
+                );
+            }
+
+            Op::LocalGet(local) => {
+                self.sources[h as usize] = local;
+                self.height = Some(h + 1);
+            }
+
+            Op::LocalSet(local) => {
+                let src = self.source(h - 1);
+                self.materialize_local(local, h - 1);
+
+                match last_result {
+                    // The previous op can write to the local directly.
+                    Some(idx) if idx + 1 == self.out.len() && src == h - 1 => {
+                        *self.out[idx].dst_mut().unwrap() = local;
+                    }
+
+                    _ => self.out.push(RegOp::Copy { dst: local, src }),
+                }
+
+                self.height = Some(h - 1);
+            }
+
+            Op::LocalTee(local) => {
+                let src = self.source(h - 1);
+                self.materialize_local(local, h - 1);
+                self.out.push(RegOp::Copy { dst: local, src });
+            }
+
+            Op::LocalCopy { src, dst } => {
+                self.materialize_local(dst, h);
+                self.out.push(RegOp::Copy { dst, src });
+            }
+
+            Op::I32Const(val) => self.emit_const(Cell::from(val), h),
+            Op::I64Const(val) => self.emit_const(Cell::from(val), h),
+            Op::F32Const(val) => self.emit_const(Cell::from(val), h),
+            Op::F64Const(val) => self.emit_const(Cell::from(val), h),
+            Op::V128Const(index) => self.emit_const(Cell::from(func.v128s[index as usize]), h),
+            Op::RefNull(_) => self.emit_const(Cell::NULL, h),
+            Op::RefFunc(func) => self.emit_const(Cell::from(func), h),
+
+            Op::GlobalGet(global) => self.emit_result(RegOp::GlobalGet { dst: h, global }, h + 1),
+
+            Op::GlobalSet(global) => {
+                let src = self.source(h - 1);
+                self.out.push(RegOp::GlobalSet { global, src });
+                self.height = Some(h - 1);
+            }
+
+            Op::RefIsNull => {
+                let src = self.source(h - 1);
+                self.emit_result(RegOp::RefIsNull { dst: h - 1, src }, h);
+            }
+
+            Op::I32AddImm(imm) => {
+                let src = self.source(h - 1);
+                self.emit_result(
+                    RegOp::I32AddImm {
+                        dst: h - 1,
+                        src,
+                        imm,
+                    },
+                    h,
+                );
+            }
+
+            Op::I32LocalAddImm { local, imm } => {
+                let op = RegOp::I32AddImm {
+                    dst: h,
+                    src: local,
+                    imm,
+                };
+                self.emit_result(op, h + 1);
+            }
+
+            Op::I32LocalAddImmSet { src, dst, imm } => {
+                self.materialize_local(dst, h);
+                self.out.push(RegOp::I32AddImm { dst, src, imm });
+            }
+
+            Op::I32LocalCmpImm { local, imm, cmp } => {
+                let op = RegOp::I32CmpImm {
+                    dst: h,
+                    src: local,
+                    imm,
+                    cmp,
+                };
+                self.emit_result(op, h + 1);
+            }
+
+            op if op.is_unary() => {
+                let (dst, src) = (h - 1, self.source(h - 1));
+
+                let op = match op {
+                    Op::I32Eqz => RegOp::I32Eqz { dst, src },
+                    op => RegOp::Unary { op, dst, src },
+                };
+
+                self.emit_result(op, h);
+            }
+
+            op if op.is_binary() => {
+                let (dst, lhs, rhs) = (h - 2, self.source(h - 2), self.source(h - 1));
+
+                let op = match (op, I32Cmp::from_op(op)) {
+                    (Op::I32Add, _) => RegOp::I32Add { dst, lhs, rhs },
+                    (Op::I32Sub, _) => RegOp::I32Sub { dst, lhs, rhs },
+                    (_, Some(cmp)) => RegOp::I32Cmp { dst, lhs, rhs, cmp },
+                    (op, None) => RegOp::Binary { op, dst, lhs, rhs },
+                };
+
+                self.emit_result(op, h - 1);
+            }
+
+            op if op.is_load() => {
+                let addr = self.source(h - 1);
+                self.emit_result(
+                    RegOp::Load {
+                        op,
+                        dst: h - 1,
+                        addr,
+                    },
+                    h,
+                );
+            }
+
+            op if op.is_store() => {
+                let (addr, src) = (self.source(h - 2), self.source(h - 1));
+                self.out.push(RegOp::Store { op, addr, src });
+                self.height = Some(h - 2);
+            }
+
+            _ => return None,
+        }
+
+        Some(pc + 1)
+    }
+
+    // The register holding the value of the stack slot `slot`.
+    fn source(&self, slot: u32) -> u32 {
+        self.sources[slot as usize]
+    }
+
+    fn reset_sources(&mut self, slots: std::ops::Range<u32>) {
+        for slot in slots {
+            self.sources[slot as usize] = slot;
+        }
+    }
+
+    // Emits an op whose result is written to the top of the stack, which is
+    // then `height` high.
+    fn emit_result(&mut self, op: RegOp, height: u32) {
+        self.last_result = Some(self.out.len());
+        self.out.push(op);
+        self.reset_sources(height - 1..height);
+        self.height = Some(height);
+    }
+
+    fn emit_const(&mut self, val: Cell, h: u32) {
+        self.emit_result(RegOp::Const { dst: h, val }, h + 1);
+    }
+
+    // Copies the locals held by the stack slots `slots` to their slot.
+    fn materialize(&mut self, slots: std::ops::Range<u32>) {
+        for slot in slots {
+            let src = self.source(slot);
+            if src != slot {
+                self.out.push(RegOp::Copy { dst: slot, src });
+                self.sources[slot as usize] = slot;
+            }
+        }
+    }
+
+    // Copies the stack slots below `height` that hold `local` to their slot,
+    // before the local is overwritten.
+    fn materialize_local(&mut self, local: u32, height: u32) {
+        for slot in self.locals..height {
+            if self.source(slot) == local {
+                self.out.push(RegOp::Copy {
+                    dst: slot,
+                    src: local,
+                });
+                self.sources[slot as usize] = slot;
+            }
+        }
+    }
+
+    // Whether `branch`, taken at height `h`, leaves the stack as is.
+    fn is_jump(&self, branch: Branch, h: u32) -> bool {
+        branch.height + branch.arity == h
+    }
+
+    // Emits `op`, which jumps to the stack op `target` with the stack being
+    // `h` high.
+    fn jump(&mut self, op: RegOp, target: u32, h: u32) {
+        self.fixups.push((self.out.len(), target));
+        self.out.push(op);
+        self.record_height(target, h);
+    }
+
+    fn record_height(&mut self, target: u32, h: u32) {
+        if let Some(height) = self.heights.get_mut(target as usize) {
+            *height = Some(h);
+        }
+    }
+
+    // Moves the values carried by `branch` and jumps to its destination.
+    fn take_branch(&mut self, branch: Branch, h: u32) {
+        self.materialize(self.locals..branch.height);
+
+        for idx in 0..branch.arity {
+            let (dst, src) = (branch.height + idx, self.source(h - branch.arity + idx));
+            if dst != src {
+                self.out.push(RegOp::Copy { dst, src });
+            }
+        }
+
+        self.jump(
+            RegOp::Jump(u32::MAX),
+            branch.pc,
+            branch.height + branch.arity,
+        );
+    }
+
+    fn ret(&mut self, func: &Func, h: u32) {
+        let results = func.results as u32;
+
+        let src = if results == 1 {
+            self.source(h - 1)
+        } else {
+            self.materialize(h - results..h);
+            h - results
+        };
+
+        self.out.push(RegOp::Return { src });
+    }
+}
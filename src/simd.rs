@@ -0,0 +1,532 @@
+//! The semantics of the vector ops, which are evaluated lane by lane, without
+//! relying on the SIMD instructions of the host.
+//!
+//! The relaxed SIMD ops may return any of the results allowed by the relaxed
+//! SIMD proposal, so they follow the SIMD instructions of x86 where they
+//! differ from their deterministic semantics. The fused multiply-adds and the
+//! dot products always have their deterministic semantics. In deterministic
+//! mode, the other relaxed ops are replaced by their deterministic version
+//! when a module is instantiated, see
+//! [`Store::set_deterministic_relaxed_simd`](crate::Store::set_deterministic_relaxed_simd).
+
+use crate::{
+    cell::{Cell, FromCell},
+    ir::Op,
+    numeric::{f32_max, f32_min, f64_max, f64_min},
+};
+
+/// The lanes of a vector, as an array of `$ty`.
+macro_rules! lanes {
+    ($ty:ty, $cell:expr) => {
+        <[$ty; 16 / size_of::<$ty>()]>::from_cell($cell)
+    };
+}
+
+/// Builds a vector of `$ty` lanes whose `$i`th lane is `$e`.
+macro_rules! build {
+    ($ty:ty, |$i:ident| $e:expr) => {
+        Cell::from(std::array::from_fn::<$ty, { 16 / size_of::<$ty>() }, _>(|$i| $e))
+    };
+}
+
+/// Evaluates a vector op that pops one value.
+pub(crate) fn unary(op: Op, val: Cell) -> Cell {
+    macro_rules! unop {
+        ($ty:ty, |$val:ident| $e:expr) => {
+            Cell::from(lanes!($ty, val).map(|$val| $e))
+        };
+    }
+
+    // Extends half of the lanes of the operand to lanes twice as large.
+    macro_rules! extend {
+        ($from:ty => $to:ty, $start:expr) => {{
+            let lanes = lanes!($from, val);
+            build!($to, |i| <$to>::from(lanes[$start + i]))
+        }};
+    }
+
+    // Adds the pairs of adjacent lanes to lanes twice as large.
+    macro_rules! extadd_pairwise {
+        ($from:ty => $to:ty) => {{
+            let lanes = lanes!($from, val);
+            build!($to, |i| <$to>::from(lanes[2 * i])
+                + <$to>::from(lanes[2 * i + 1]))
+        }};
+    }
+
+    // Converts the two `f64` lanes to the two low lanes of the result, whose
+    // other lanes are zero.
+    macro_rules! narrow_f64x2 {
+        ($to:ty) => {{
+            let lanes = lanes!(f64, val);
+            build!($to, |i| lanes
+                .get(i)
+                .map_or(<$to>::default(), |lane| *lane as $to))
+        }};
+    }
+
+    fn all_true<T: PartialEq + Default>(lanes: impl IntoIterator<Item = T>) -> Cell {
+        Cell::from(i32::from(
+            lanes.into_iter().all(|lane| lane != T::default()),
+        ))
+    }
+
+    fn bitmask<T: PartialOrd + Default>(lanes: impl IntoIterator<Item = T>) -> Cell {
+        let mask = lanes.into_iter().enumerate().fold(0, |mask, (i, lane)| {
+            mask | i32::from(lane < T::default()) << i
+        });
+
+        Cell::from(mask)
+    }
+
+    match op {
+        Op::V128Not => Cell::from(!u128::from_cell(val)),
+        Op::V128AnyTrue => Cell::from(i32::from(u128::from_cell(val) != 0)),
+
+        Op::I8x16Abs => unop!(i8, |val| val.wrapping_abs()),
+        Op::I8x16Neg => unop!(i8, |val| val.wrapping_neg()),
+        Op::I8x16Popcnt => unop!(u8, |val| val.count_ones() as u8),
+        Op::I8x16AllTrue => all_true(lanes!(i8, val)),
+        Op::I8x16Bitmask => bitmask(lanes!(i8, val)),
+
+        Op::I16x8Abs => unop!(i16, |val| val.wrapping_abs()),
+        Op::I16x8Neg => unop!(i16, |val| val.wrapping_neg()),
+        Op::I16x8AllTrue => all_true(lanes!(i16, val)),
+        Op::I16x8Bitmask => bitmask(lanes!(i16, val)),
+        Op::I16x8ExtendLowI8x16S => extend!(i8 => i16, 0),
+        Op::I16x8ExtendHighI8x16S => extend!(i8 => i16, 8),
+        Op::I16x8ExtendLowI8x16U => extend!(u8 => u16, 0),
+        Op::I16x8ExtendHighI8x16U => extend!(u8 => u16, 8),
+        Op::I16x8ExtaddPairwiseI8x16S => extadd_pairwise!(i8 => i16),
+        Op::I16x8ExtaddPairwiseI8x16U => extadd_pairwise!(u8 => u16),
+
+        Op::I32x4Abs => unop!(i32, |val| val.wrapping_abs()),
+        Op::I32x4Neg => unop!(i32, |val| val.wrapping_neg()),
+        Op::I32x4AllTrue => all_true(lanes!(i32, val)),
+        Op::I32x4Bitmask => bitmask(lanes!(i32, val)),
+        Op::I32x4ExtendLowI16x8S => extend!(i16 => i32, 0),
+        Op::I32x4ExtendHighI16x8S => extend!(i16 => i32, 4),
+        Op::I32x4ExtendLowI16x8U => extend!(u16 => u32, 0),
+        Op::I32x4ExtendHighI16x8U => extend!(u16 => u32, 4),
+        Op::I32x4ExtaddPairwiseI16x8S => extadd_pairwise!(i16 => i32),
+        Op::I32x4ExtaddPairwiseI16x8U => extadd_pairwise!(u16 => u32),
+        // Saturating truncations are exactly what `as` does.
+        Op::I32x4TruncSatF32x4S => unop!(f32, |val| val as i32),
+        Op::I32x4TruncSatF32x4U => unop!(f32, |val| val as u32),
+        Op::I32x4TruncSatF64x2SZero => narrow_f64x2!(i32),
+        Op::I32x4TruncSatF64x2UZero => narrow_f64x2!(u32),
+        Op::I32x4RelaxedTruncF32x4S => unop!(f32, |val| relaxed_trunc_s(val.into())),
+        Op::I32x4RelaxedTruncF32x4U => unop!(f32, |val| relaxed_trunc_u(val.into())),
+        Op::I32x4RelaxedTruncF64x2SZero => {
+            let lanes = lanes!(f64, val);
+            build!(i32, |i| lanes
+                .get(i)
+                .map_or(0, |lane| relaxed_trunc_s(*lane)))
+        }
+        Op::I32x4RelaxedTruncF64x2UZero => {
+            let lanes = lanes!(f64, val);
+            build!(u32, |i| lanes
+                .get(i)
+                .map_or(0, |lane| relaxed_trunc_u(*lane)))
+        }
+
+        Op::I64x2Abs => unop!(i64, |val| val.wrapping_abs()),
+        Op::I64x2Neg => unop!(i64, |val| val.wrapping_neg()),
+        Op::I64x2AllTrue => all_true(lanes!(i64, val)),
+        Op::I64x2Bitmask => bitmask(lanes!(i64, val)),
+        Op::I64x2ExtendLowI32x4S => extend!(i32 => i64, 0),
+        Op::I64x2ExtendHighI32x4S => extend!(i32 => i64, 2),
+        Op::I64x2ExtendLowI32x4U => extend!(u32 => u64, 0),
+        Op::I64x2ExtendHighI32x4U => extend!(u32 => u64, 2),
+
+        Op::F32x4Abs => unop!(f32, |val| val.abs()),
+        Op::F32x4Neg => unop!(f32, |val| -val),
+        Op::F32x4Sqrt => unop!(f32, |val| val.sqrt()),
+        Op::F32x4Ceil => unop!(f32, |val| val.ceil()),
+        Op::F32x4Floor => unop!(f32, |val| val.floor()),
+        Op::F32x4Trunc => unop!(f32, |val| val.trunc()),
+        Op::F32x4Nearest => unop!(f32, |val| val.round_ties_even()),
+        Op::F32x4ConvertI32x4S => unop!(i32, |val| val as f32),
+        Op::F32x4ConvertI32x4U => unop!(u32, |val| val as f32),
+        Op::F32x4DemoteF64x2Zero => narrow_f64x2!(f32),
+
+        Op::F64x2Abs => unop!(f64, |val| val.abs()),
+        Op::F64x2Neg => unop!(f64, |val| -val),
+        Op::F64x2Sqrt => unop!(f64, |val| val.sqrt()),
+        Op::F64x2Ceil => unop!(f64, |val| val.ceil()),
+        Op::F64x2Floor => unop!(f64, |val| val.floor()),
+        Op::F64x2Trunc => unop!(f64, |val| val.trunc()),
+        Op::F64x2Nearest => unop!(f64, |val| val.round_ties_even()),
+        Op::F64x2ConvertLowI32x4S => extend!(i32 => f64, 0),
+        Op::F64x2ConvertLowI32x4U => extend!(u32 => f64, 0),
+        Op::F64x2PromoteLowF32x4 => extend!(f32 => f64, 0),
+
+        Op::I8x16Splat => Cell::from([i32::from_cell(val) as i8; 16]),
+        Op::I16x8Splat => Cell::from([i32::from_cell(val) as i16; 8]),
+        Op::I32x4Splat => Cell::from([i32::from_cell(val); 4]),
+        Op::I64x2Splat => Cell::from([i64::from_cell(val); 2]),
+        Op::F32x4Splat => Cell::from([f32::from_cell(val); 4]),
+        Op::F64x2Splat => Cell::from([f64::from_cell(val); 2]),
+
+        Op::I8x16ExtractLaneS(lane) => Cell::from(i32::from(lanes!(i8, val)[lane as usize])),
+        Op::I8x16ExtractLaneU(lane) => Cell::from(i32::from(lanes!(u8, val)[lane as usize])),
+        Op::I16x8ExtractLaneS(lane) => Cell::from(i32::from(lanes!(i16, val)[lane as usize])),
+        Op::I16x8ExtractLaneU(lane) => Cell::from(i32::from(lanes!(u16, val)[lane as usize])),
+        Op::I32x4ExtractLane(lane) => Cell::from(lanes!(i32, val)[lane as usize]),
+        Op::I64x2ExtractLane(lane) => Cell::from(lanes!(i64, val)[lane as usize]),
+        Op::F32x4ExtractLane(lane) => Cell::from(lanes!(f32, val)[lane as usize]),
+        Op::F64x2ExtractLane(lane) => Cell::from(lanes!(f64, val)[lane as usize]),
+
+        op => unreachable!("not a unary op: `{op:?}`"),
+    }
+}
+
+// Truncates `val` to an `i32`, or returns `i32::MIN` if it is out of range,
+// like `cvttps2dq` on x86.
+fn relaxed_trunc_s(val: f64) -> i32 {
+    if val > -2147483649.0 && val < 2147483648.0 {
+        val as i32
+    } else {
+        i32::MIN
+    }
+}
+
+// Truncates `val` to an `u32`, or returns `u32::MAX` if it is out of range.
+fn relaxed_trunc_u(val: f64) -> u32 {
+    if val > -1.0 && val < 4294967296.0 {
+        val as u32
+    } else {
+        u32::MAX
+    }
+}
+
+/// Evaluates a vector op that pops two values.
+pub(crate) fn binary(op: Op, lhs: Cell, rhs: Cell) -> Cell {
+    macro_rules! binop {
+        ($ty:ty, |$lhs:ident, $rhs:ident| $e:expr) => {{
+            let (lhs, rhs) = (lanes!($ty, lhs), lanes!($ty, rhs));
+            Cell::from(std::array::from_fn::<_, { 16 / size_of::<$ty>() }, _>(|i| {
+                let ($lhs, $rhs) = (lhs[i], rhs[i]);
+                $e
+            }))
+        }};
+    }
+
+    // Compares the lanes, setting all the bits of the lanes of the result,
+    // which are `$mask`s, for which the comparison holds.
+    macro_rules! cmp {
+        ($ty:ty => $mask:ty, $cmp:tt) => {
+            binop!($ty, |lhs, rhs| -<$mask>::from(lhs $cmp rhs))
+        };
+    }
+
+    // Saturates the lanes of both operands to lanes half as large.
+    macro_rules! narrow {
+        ($from:ty => $to:ty) => {{
+            let (lhs, rhs) = (lanes!($from, lhs), lanes!($from, rhs));
+            build!($to, |i| {
+                let lane = lhs.get(i).copied().unwrap_or_else(|| rhs[i - lhs.len()]);
+                lane.clamp(<$from>::from(<$to>::MIN), <$from>::from(<$to>::MAX)) as $to
+            })
+        }};
+    }
+
+    // Multiplies half of the lanes of the operands to lanes twice as large.
+    macro_rules! extmul {
+        ($from:ty => $to:ty, $start:expr) => {{
+            let (lhs, rhs) = (lanes!($from, lhs), lanes!($from, rhs));
+            build!($to, |i| <$to>::from(lhs[$start + i])
+                * <$to>::from(rhs[$start + i]))
+        }};
+    }
+
+    // Shifts the lanes by the scalar operand, modulo their width.
+    macro_rules! shift {
+        ($ty:ty, $shift:ident) => {{
+            let rhs = u32::from_cell(rhs);
+            Cell::from(lanes!($ty, lhs).map(|lhs| lhs.$shift(rhs)))
+        }};
+    }
+
+    macro_rules! replace_lane {
+        ($ty:ty, $lane:expr, $val:expr) => {{
+            let mut lanes = lanes!($ty, lhs);
+            lanes[$lane as usize] = $val;
+            Cell::from(lanes)
+        }};
+    }
+
+    match op {
+        Op::V128And => Cell::from(u128::from_cell(lhs) & u128::from_cell(rhs)),
+        Op::V128Andnot => Cell::from(u128::from_cell(lhs) & !u128::from_cell(rhs)),
+        Op::V128Or => Cell::from(u128::from_cell(lhs) | u128::from_cell(rhs)),
+        Op::V128Xor => Cell::from(u128::from_cell(lhs) ^ u128::from_cell(rhs)),
+
+        Op::I8x16Swizzle => {
+            let (lhs, rhs) = (lanes!(u8, lhs), lanes!(u8, rhs));
+            Cell::from(rhs.map(|index| lhs.get(index as usize).copied().unwrap_or(0)))
+        }
+
+        // Like `pshufb` on x86, indices whose top bit is set select zero, and
+        // only the low four bits of the other ones matter.
+        Op::I8x16RelaxedSwizzle => {
+            let (lhs, rhs) = (lanes!(u8, lhs), lanes!(u8, rhs));
+            Cell::from(rhs.map(|index| match index {
+                0x80.. => 0,
+                _ => lhs[(index & 0xf) as usize],
+            }))
+        }
+
+        Op::I8x16Eq => cmp!(i8 => i8, ==),
+        Op::I8x16Ne => cmp!(i8 => i8, !=),
+        Op::I8x16LtS => cmp!(i8 => i8, <),
+        Op::I8x16LtU => cmp!(u8 => i8, <),
+        Op::I8x16GtS => cmp!(i8 => i8, >),
+        Op::I8x16GtU => cmp!(u8 => i8, >),
+        Op::I8x16LeS => cmp!(i8 => i8, <=),
+        Op::I8x16LeU => cmp!(u8 => i8, <=),
+        Op::I8x16GeS => cmp!(i8 => i8, >=),
+        Op::I8x16GeU => cmp!(u8 => i8, >=),
+
+        Op::I16x8Eq => cmp!(i16 => i16, ==),
+        Op::I16x8Ne => cmp!(i16 => i16, !=),
+        Op::I16x8LtS => cmp!(i16 => i16, <),
+        Op::I16x8LtU => cmp!(u16 => i16, <),
+        Op::I16x8GtS => cmp!(i16 => i16, >),
+        Op::I16x8GtU => cmp!(u16 => i16, >),
+        Op::I16x8LeS => cmp!(i16 => i16, <=),
+        Op::I16x8LeU => cmp!(u16 => i16, <=),
+        Op::I16x8GeS => cmp!(i16 => i16, >=),
+        Op::I16x8GeU => cmp!(u16 => i16, >=),
+
+        Op::I32x4Eq => cmp!(i32 => i32, ==),
+        Op::I32x4Ne => cmp!(i32 => i32, !=),
+        Op::I32x4LtS => cmp!(i32 => i32, <),
+        Op::I32x4LtU => cmp!(u32 => i32, <),
+        Op::I32x4GtS => cmp!(i32 => i32, >),
+        Op::I32x4GtU => cmp!(u32 => i32, >),
+        Op::I32x4LeS => cmp!(i32 => i32, <=),
+        Op::I32x4LeU => cmp!(u32 => i32, <=),
+        Op::I32x4GeS => cmp!(i32 => i32, >=),
+        Op::I32x4GeU => cmp!(u32 => i32, >=),
+
+        Op::I64x2Eq => cmp!(i64 => i64, ==),
+        Op::I64x2Ne => cmp!(i64 => i64, !=),
+        Op::I64x2LtS => cmp!(i64 => i64, <),
+        Op::I64x2GtS => cmp!(i64 => i64, >),
+        Op::I64x2LeS => cmp!(i64 => i64, <=),
+        Op::I64x2GeS => cmp!(i64 => i64, >=),
+
+        Op::F32x4Eq => cmp!(f32 => i32, ==),
+        Op::F32x4Ne => cmp!(f32 => i32, !=),
+        Op::F32x4Lt => cmp!(f32 => i32, <),
+        Op::F32x4Gt => cmp!(f32 => i32, >),
+        Op::F32x4Le => cmp!(f32 => i32, <=),
+        Op::F32x4Ge => cmp!(f32 => i32, >=),
+
+        Op::F64x2Eq => cmp!(f64 => i64, ==),
+        Op::F64x2Ne => cmp!(f64 => i64, !=),
+        Op::F64x2Lt => cmp!(f64 => i64, <),
+        Op::F64x2Gt => cmp!(f64 => i64, >),
+        Op::F64x2Le => cmp!(f64 => i64, <=),
+        Op::F64x2Ge => cmp!(f64 => i64, >=),
+
+        Op::I8x16NarrowI16x8S => narrow!(i16 => i8),
+        Op::I8x16NarrowI16x8U => narrow!(i16 => u8),
+        Op::I16x8NarrowI32x4S => narrow!(i32 => i16),
+        Op::I16x8NarrowI32x4U => narrow!(i32 => u16),
+
+        Op::I8x16Shl => shift!(i8, wrapping_shl),
+        Op::I8x16ShrS => shift!(i8, wrapping_shr),
+        Op::I8x16ShrU => shift!(u8, wrapping_shr),
+        Op::I8x16Add => binop!(i8, |lhs, rhs| lhs.wrapping_add(rhs)),
+        Op::I8x16AddSatS => binop!(i8, |lhs, rhs| lhs.saturating_add(rhs)),
+        Op::I8x16AddSatU => binop!(u8, |lhs, rhs| lhs.saturating_add(rhs)),
+        Op::I8x16Sub => binop!(i8, |lhs, rhs| lhs.wrapping_sub(rhs)),
+        Op::I8x16SubSatS => binop!(i8, |lhs, rhs| lhs.saturating_sub(rhs)),
+        Op::I8x16SubSatU => binop!(u8, |lhs, rhs| lhs.saturating_sub(rhs)),
+        Op::I8x16MinS => binop!(i8, |lhs, rhs| lhs.min(rhs)),
+        Op::I8x16MinU => binop!(u8, |lhs, rhs| lhs.min(rhs)),
+        Op::I8x16MaxS => binop!(i8, |lhs, rhs| lhs.max(rhs)),
+        Op::I8x16MaxU => binop!(u8, |lhs, rhs| lhs.max(rhs)),
+        Op::I8x16AvgrU => binop!(u8, |lhs, rhs| lhs.midpoint(rhs) + (lhs ^ rhs) % 2),
+
+        Op::I16x8Shl => shift!(i16, wrapping_shl),
+        Op::I16x8ShrS => shift!(i16, wrapping_shr),
+        Op::I16x8ShrU => shift!(u16, wrapping_shr),
+        Op::I16x8Add => binop!(i16, |lhs, rhs| lhs.wrapping_add(rhs)),
+        Op::I16x8AddSatS => binop!(i16, |lhs, rhs| lhs.saturating_add(rhs)),
+        Op::I16x8AddSatU => binop!(u16, |lhs, rhs| lhs.saturating_add(rhs)),
+        Op::I16x8Sub => binop!(i16, |lhs, rhs| lhs.wrapping_sub(rhs)),
+        Op::I16x8SubSatS => binop!(i16, |lhs, rhs| lhs.saturating_sub(rhs)),
+        Op::I16x8SubSatU => binop!(u16, |lhs, rhs| lhs.saturating_sub(rhs)),
+        Op::I16x8Mul => binop!(i16, |lhs, rhs| lhs.wrapping_mul(rhs)),
+        Op::I16x8MinS => binop!(i16, |lhs, rhs| lhs.min(rhs)),
+        Op::I16x8MinU => binop!(u16, |lhs, rhs| lhs.min(rhs)),
+        Op::I16x8MaxS => binop!(i16, |lhs, rhs| lhs.max(rhs)),
+        Op::I16x8MaxU => binop!(u16, |lhs, rhs| lhs.max(rhs)),
+        Op::I16x8AvgrU => binop!(u16, |lhs, rhs| lhs.midpoint(rhs) + (lhs ^ rhs) % 2),
+        Op::I16x8Q15mulrSatS => binop!(i16, |lhs, rhs| {
+            let product = (i32::from(lhs) * i32::from(rhs) + 0x4000) >> 15;
+            product.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+        }),
+        // Like `pmulhrsw` on x86, the product of `i16::MIN` by itself wraps.
+        Op::I16x8RelaxedQ15mulrS => binop!(i16, |lhs, rhs| {
+            ((i32::from(lhs) * i32::from(rhs) + 0x4000) >> 15) as i16
+        }),
+        // The lanes of the second operand are signed, as in deterministic
+        // mode.
+        Op::I16x8RelaxedDotI8x16I7x16S => {
+            let (lhs, rhs) = (lanes!(i8, lhs), lanes!(i8, rhs));
+            build!(i16, |i| {
+                let product = |i: usize| i16::from(lhs[i]) * i16::from(rhs[i]);
+                product(2 * i).wrapping_add(product(2 * i + 1))
+            })
+        }
+        Op::I16x8ExtmulLowI8x16S => extmul!(i8 => i16, 0),
+        Op::I16x8ExtmulHighI8x16S => extmul!(i8 => i16, 8),
+        Op::I16x8ExtmulLowI8x16U => extmul!(u8 => u16, 0),
+        Op::I16x8ExtmulHighI8x16U => extmul!(u8 => u16, 8),
+
+        Op::I32x4Shl => shift!(i32, wrapping_shl),
+        Op::I32x4ShrS => shift!(i32, wrapping_shr),
+        Op::I32x4ShrU => shift!(u32, wrapping_shr),
+        Op::I32x4Add => binop!(i32, |lhs, rhs| lhs.wrapping_add(rhs)),
+        Op::I32x4Sub => binop!(i32, |lhs, rhs| lhs.wrapping_sub(rhs)),
+        Op::I32x4Mul => binop!(i32, |lhs, rhs| lhs.wrapping_mul(rhs)),
+        Op::I32x4MinS => binop!(i32, |lhs, rhs| lhs.min(rhs)),
+        Op::I32x4MinU => binop!(u32, |lhs, rhs| lhs.min(rhs)),
+        Op::I32x4MaxS => binop!(i32, |lhs, rhs| lhs.max(rhs)),
+        Op::I32x4MaxU => binop!(u32, |lhs, rhs| lhs.max(rhs)),
+        Op::I32x4DotI16x8S => {
+            let (lhs, rhs) = (lanes!(i16, lhs), lanes!(i16, rhs));
+            build!(i32, |i| {
+                let product = |i: usize| i32::from(lhs[i]) * i32::from(rhs[i]);
+                // Only `2 * i16::MIN * i16::MIN` overflows.
+                product(2 * i).wrapping_add(product(2 * i + 1))
+            })
+        }
+        Op::I32x4ExtmulLowI16x8S => extmul!(i16 => i32, 0),
+        Op::I32x4ExtmulHighI16x8S => extmul!(i16 => i32, 4),
+        Op::I32x4ExtmulLowI16x8U => extmul!(u16 => u32, 0),
+        Op::I32x4ExtmulHighI16x8U => extmul!(u16 => u32, 4),
+
+        Op::I64x2Shl => shift!(i64, wrapping_shl),
+        Op::I64x2ShrS => shift!(i64, wrapping_shr),
+        Op::I64x2ShrU => shift!(u64, wrapping_shr),
+        Op::I64x2Add => binop!(i64, |lhs, rhs| lhs.wrapping_add(rhs)),
+        Op::I64x2Sub => binop!(i64, |lhs, rhs| lhs.wrapping_sub(rhs)),
+        Op::I64x2Mul => binop!(i64, |lhs, rhs| lhs.wrapping_mul(rhs)),
+        Op::I64x2ExtmulLowI32x4S => extmul!(i32 => i64, 0),
+        Op::I64x2ExtmulHighI32x4S => extmul!(i32 => i64, 2),
+        Op::I64x2ExtmulLowI32x4U => extmul!(u32 => u64, 0),
+        Op::I64x2ExtmulHighI32x4U => extmul!(u32 => u64, 2),
+
+        Op::F32x4Add => binop!(f32, |lhs, rhs| lhs + rhs),
+        Op::F32x4Sub => binop!(f32, |lhs, rhs| lhs - rhs),
+        Op::F32x4Mul => binop!(f32, |lhs, rhs| lhs * rhs),
+        Op::F32x4Div => binop!(f32, |lhs, rhs| lhs / rhs),
+        Op::F32x4Min => binop!(f32, |lhs, rhs| f32_min(lhs, rhs)),
+        Op::F32x4Max => binop!(f32, |lhs, rhs| f32_max(lhs, rhs)),
+        // The pseudo-minimum and maximum are the `<` based ones of C++.
+        Op::F32x4Pmin => binop!(f32, |lhs, rhs| if rhs < lhs { rhs } else { lhs }),
+        Op::F32x4Pmax => binop!(f32, |lhs, rhs| if lhs < rhs { rhs } else { lhs }),
+        // Like `minps` and `maxps` on x86, the second operand is returned if
+        // either operand is NaN.
+        Op::F32x4RelaxedMin => binop!(f32, |lhs, rhs| if lhs < rhs { lhs } else { rhs }),
+        Op::F32x4RelaxedMax => binop!(f32, |lhs, rhs| if lhs > rhs { lhs } else { rhs }),
+
+        Op::F64x2Add => binop!(f64, |lhs, rhs| lhs + rhs),
+        Op::F64x2Sub => binop!(f64, |lhs, rhs| lhs - rhs),
+        Op::F64x2Mul => binop!(f64, |lhs, rhs| lhs * rhs),
+        Op::F64x2Div => binop!(f64, |lhs, rhs| lhs / rhs),
+        Op::F64x2Min => binop!(f64, |lhs, rhs| f64_min(lhs, rhs)),
+        Op::F64x2Max => binop!(f64, |lhs, rhs| f64_max(lhs, rhs)),
+        Op::F64x2Pmin => binop!(f64, |lhs, rhs| if rhs < lhs { rhs } else { lhs }),
+        Op::F64x2Pmax => binop!(f64, |lhs, rhs| if lhs < rhs { rhs } else { lhs }),
+        Op::F64x2RelaxedMin => binop!(f64, |lhs, rhs| if lhs < rhs { lhs } else { rhs }),
+        Op::F64x2RelaxedMax => binop!(f64, |lhs, rhs| if lhs > rhs { lhs } else { rhs }),
+
+        Op::I8x16ReplaceLane(lane) => replace_lane!(i8, lane, i32::from_cell(rhs) as i8),
+        Op::I16x8ReplaceLane(lane) => replace_lane!(i16, lane, i32::from_cell(rhs) as i16),
+        Op::I32x4ReplaceLane(lane) => replace_lane!(i32, lane, i32::from_cell(rhs)),
+        Op::I64x2ReplaceLane(lane) => replace_lane!(i64, lane, i64::from_cell(rhs)),
+        Op::F32x4ReplaceLane(lane) => replace_lane!(f32, lane, f32::from_cell(rhs)),
+        Op::F64x2ReplaceLane(lane) => replace_lane!(f64, lane, f64::from_cell(rhs)),
+
+        op => unreachable!("not a binary op: `{op:?}`"),
+    }
+}
+
+/// Evaluates a vector op that pops three values.
+pub(crate) fn ternary(op: Op, first: Cell, second: Cell, third: Cell) -> Cell {
+    macro_rules! ternop {
+        ($ty:ty, |$first:ident, $second:ident, $third:ident| $e:expr) => {{
+            let (first, second, third) =
+                (lanes!($ty, first), lanes!($ty, second), lanes!($ty, third));
+            build!($ty, |i| {
+                let ($first, $second, $third) = (first[i], second[i], third[i]);
+                $e
+            })
+        }};
+    }
+
+    // Like `blendv` on x86, selects the lanes of the first operand where the
+    // top bit of the lane of the third one is set.
+    macro_rules! laneselect {
+        ($ty:ty) => {
+            ternop!($ty, |first, second, mask| if mask < 0 {
+                first
+            } else {
+                second
+            })
+        };
+    }
+
+    match op {
+        // Takes the bits of the first operand where the third one is set, and
+        // the ones of the second operand elsewhere.
+        Op::V128Bitselect => {
+            let mask = u128::from_cell(third);
+            Cell::from(u128::from_cell(first) & mask | u128::from_cell(second) & !mask)
+        }
+
+        // The multiply-adds are fused, as in deterministic mode.
+        Op::F32x4RelaxedMadd => ternop!(f32, |first, second, third| first.mul_add(second, third)),
+        Op::F32x4RelaxedNmadd => {
+            ternop!(f32, |first, second, third| (-first).mul_add(second, third))
+        }
+        Op::F64x2RelaxedMadd => ternop!(f64, |first, second, third| first.mul_add(second, third)),
+        Op::F64x2RelaxedNmadd => {
+            ternop!(f64, |first, second, third| (-first).mul_add(second, third))
+        }
+
+        Op::I8x16RelaxedLaneselect => laneselect!(i8),
+        Op::I16x8RelaxedLaneselect => laneselect!(i16),
+        Op::I32x4RelaxedLaneselect => laneselect!(i32),
+        Op::I64x2RelaxedLaneselect => laneselect!(i64),
+
+        // Adds the dot products of the groups of four lanes to the lanes of
+        // the third operand, the lanes of the second operand being signed.
+        Op::I32x4RelaxedDotI8x16I7x16AddS => {
+            let dot = lanes!(i16, binary(Op::I16x8RelaxedDotI8x16I7x16S, first, second));
+            let third = lanes!(i32, third);
+            build!(i32, |i| (i32::from(dot[2 * i]) + i32::from(dot[2 * i + 1]))
+                .wrapping_add(third[i]))
+        }
+
+        op => unreachable!("not a ternary op: `{op:?}`"),
+    }
+}
+
+/// Evaluates `i8x16.shuffle`, whose lane indices are `lanes`, on the
+/// concatenation of `lhs` and `rhs`.
+pub(crate) fn shuffle(lanes: u128, lhs: Cell, rhs: Cell) -> Cell {
+    let (lhs, rhs) = (lanes!(u8, lhs), lanes!(u8, rhs));
+    let lanes = lanes.to_le_bytes();
+
+    build!(u8, |i| {
+        let lane = lanes[i] as usize;
+        lhs.get(lane).copied().unwrap_or_else(|| rhs[lane - 16])
+    })
+}
@@ -1,92 +1,1075 @@
-use std::{iter::zip, mem::take, ops::Index};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    iter::zip,
+    ops::{Index, Range},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 use wasmbin::{
-    indices::{FuncId, TypeId},
-    sections::{
-        self, ExportDesc, FuncBody,
-        payload::{Code, Export, Function, Type},
-    },
-    types::FuncType,
+    component::CoreSort,
+    indices::{FuncId, MemId, TypeId},
+    sections::{self, ExportDesc, ImportDesc},
+    types::{CompositeType, FieldType, FuncType, SubType},
+};
+
+use crate::{
+    cell::{AnyRef, Cell, FromCell},
+    component::{ComponentInstance, HandleTable, HostItem, ResourceTypeDef},
+    const_expr,
+    gc::Heap,
+    host::{Caller, HostFunc},
+    interpreter::{EvaluationStatus, Exception, Interpreter, Trap, Unwind},
+    ir::{Handler, Op},
+    memory::{self, Memory},
+    module::{Mode, Module},
+    register::{self, RegOp},
+    table::Table,
+    types::{self, Binary, GlobalType, HeapType, MemType, Mut, RefType, TableType, Types, ValType},
+    values::{ArrayAddr, ExnAddr, ExternAddr, FuncAddr, Ref, StructAddr, TagAddr, Val},
 };
 
+pub use crate::{lower::ValidationError, memory::SharedMemory};
+
 /// Aggregates the data from multiple WASM modules.
 ///
 /// Once this struct is fully populated, it is passed to the interpreter.
 // Data stored here must be in a format that is ready to use by the interpreter.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Store {
-    funcs: Vec<(TypeId, FuncBody)>,
+    pub(crate) code: Code,
+    pub(crate) state: State,
+    /// The instances whose exports can be imported, by module name.
+    registered: HashMap<String, InstanceAddr>,
+    /// The functions, resource types and instances that components can
+    /// import, by name.
+    pub(crate) component_imports: HashMap<String, HostItem>,
+    /// The maximum size of the memories that are created, in pages.
+    memory_limit: Option<u64>,
+    /// Whether the relaxed SIMD ops of the modules that are instantiated have
+    /// their deterministic semantics.
+    deterministic_relaxed_simd: bool,
+}
+
+/// A module instance in a [`Store`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InstanceAddr(pub(crate) u32);
+
+/// Identifies a store, so that the handles to its functions are not used
+/// with the interpreter of another one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct StoreId(u64);
+
+impl Default for StoreId {
+    fn default() -> StoreId {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        StoreId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The parts of the store that don't change once a module is instantiated.
+#[derive(Debug, Default)]
+pub(crate) struct Code {
+    pub(crate) id: StoreId,
+    pub(crate) funcs: Vec<Func>,
+    /// The types of all the instances, whose recursive groups are
+    /// deduplicated so that types can be compared by address.
+    types: Vec<SubType>,
+    /// The addresses of the types of each recursive group.
+    groups: Vec<Range<u32>>,
+    /// The type address of each tag.
+    pub(crate) tags: Vec<u32>,
+    pub(crate) instances: Vec<Instance>,
+    /// The implementations of the host functions.
+    pub(crate) hosts: Vec<HostFunc>,
+    pub(crate) resource_types: Vec<ResourceTypeDef>,
+    pub(crate) components: Vec<ComponentInstance>,
+}
+
+/// The parts of the store that can be modified by the code.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    pub(crate) memories: Vec<Memory>,
+    pub(crate) tables: Vec<Table>,
+    pub(crate) globals: Vec<Global>,
+    /// The contents of the data segments, which are empty once dropped.
+    pub(crate) datas: Vec<Box<[u8]>>,
+    /// The contents of the element segments, which are empty once dropped.
+    pub(crate) elems: Vec<Box<[Cell]>>,
+    /// The exceptions that were thrown, which exception references refer
+    /// to.
+    pub(crate) exceptions: Vec<Exn>,
+    /// The structures and arrays allocated by the code.
+    pub(crate) heap: Heap,
+    /// The live parts of the operand stacks of the interpreters whose host
+    /// functions are calling back into the code, which hold references too.
+    pub(crate) pinned: Vec<Cell>,
+    /// The handle table of each component instance.
+    pub(crate) handles: Vec<HandleTable>,
+}
+
+impl State {
+    /// Collects the heap if enough was allocated since the last collection.
+    ///
+    /// The live part of the operand stack, `stack`, holds the references of
+    /// the code, along with the ones of the store.
+    pub(crate) fn collect_garbage(&mut self, stack: &[Cell]) {
+        if !self.heap.needs_collection() {
+            return;
+        }
+
+        let globals = self.globals.iter().map(|global| global.value);
+        let tables = self.tables.iter().flat_map(|table| table.elements());
+        let elems = self.elems.iter().flatten();
+        let payloads = self.exceptions.iter().flat_map(|exn| &exn.payload);
+        let roots = stack.iter().chain(&self.pinned);
+        let roots = roots.chain(tables).chain(elems).chain(payloads);
+
+        self.heap.collect(roots.copied().chain(globals));
+    }
+}
+
+/// A function, lowered to the internal instruction set.
+#[derive(Clone, Debug)]
+pub struct Func {
+    pub(crate) type_: TypeId,
+    /// The number of parameters of the function.
+    pub(crate) params: usize,
+    /// The initial value of the locals that are not parameters.
+    pub(crate) locals: Box<[Cell]>,
+    /// The number of results of the function.
+    pub(crate) results: usize,
+    pub(crate) code: Box<[Op]>,
+    /// The constants and shuffle indices of the vector ops of the code.
+    pub(crate) v128s: Box<[u128]>,
+    /// The `try_table`s of the code, innermost first.
+    pub(crate) handlers: Box<[Handler]>,
+    /// The maximum height of the operand stack, locals included.
+    pub(crate) max_height: usize,
+    /// The code of the function for the register engine, if it could be
+    /// translated.
+    pub(crate) registers: Option<Box<[RegOp]>>,
+    /// The name of the function, used in backtraces.
+    pub(crate) name: Option<Arc<str>>,
+    /// The address of the instance that defines the function.
+    pub(crate) instance: u32,
+}
+
+impl Func {
+    /// Whether the function is implemented by the host.
+    pub(crate) fn is_host(&self) -> bool {
+        matches!(*self.code, [Op::Host(_), ..])
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Global {
+    pub(crate) value: Cell,
+    pub(crate) type_: GlobalType,
+}
+
+/// A thrown exception.
+#[derive(Debug)]
+pub(crate) struct Exn {
+    /// The address of the tag the exception was thrown with.
+    pub(crate) tag: u32,
+    pub(crate) payload: Box<[Cell]>,
+    /// Whether the exception was caught with a reference, which may still
+    /// exist.
+    pub(crate) referenced: bool,
+}
+
+/// The addresses of everything a module instance defines or imports, in the
+/// index spaces of the module.
+#[derive(Debug, Default)]
+pub(crate) struct Instance {
+    pub(crate) types: Vec<u32>,
+    pub(crate) funcs: Vec<u32>,
+    pub(crate) tables: Vec<u32>,
+    pub(crate) memories: Vec<u32>,
+    pub(crate) globals: Vec<u32>,
+    pub(crate) tags: Vec<u32>,
+    pub(crate) elems: Vec<u32>,
+    pub(crate) datas: Vec<u32>,
     exports: Vec<sections::Export>,
-    types: Vec<FuncType>,
+}
+
+/// The error returned when a module can't be instantiated.
+#[derive(Debug)]
+pub enum InstantiationError {
+    /// The imports of the module can't be resolved.
+    Unlinkable(String),
+    /// Initializing a segment or running the start function trapped.
+    Trap(Trap),
+    /// A memory of the module is larger than the limit of the store.
+    ResourceLimit(String),
+    /// The start function ended the program with an exit code.
+    Exit(i32),
+    /// The start function threw an exception that it didn't catch.
+    Exception(Exception),
+}
+
+impl Display for InstantiationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstantiationError::Unlinkable(error) => write!(f, "unlinkable module: {error}"),
+            InstantiationError::Trap(trap) => write!(f, "instantiation trapped: {trap}"),
+            InstantiationError::ResourceLimit(error) => {
+                write!(f, "resource limit exceeded: {error}")
+            }
+            InstantiationError::Exit(code) => write!(f, "start function exited with code {code}"),
+            InstantiationError::Exception(exception) => {
+                write!(f, "start function threw an {exception}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstantiationError {}
+
+impl From<Trap> for InstantiationError {
+    fn from(trap: Trap) -> InstantiationError {
+        InstantiationError::Trap(trap)
+    }
 }
 
 impl Store {
-    pub(crate) fn new(mut module: wasmbin::Module) -> Self {
-        let function_section = module.find_std_section_mut::<Function>().unwrap();
-        let function_section = function_section
-            .try_contents_mut()
-            .unwrap()
-            .iter_mut()
-            .map(|func_type| *func_type)
-            .collect::<Vec<_>>();
+    /// Creates a store in which `module` is instantiated.
+    pub fn new(module: &Module) -> Result<Self, InstantiationError> {
+        let mut store = Store::default();
+        store.instantiate(module)?;
 
-        let code_section = module.find_std_section_mut::<Code>().unwrap();
-        let code_section = code_section
-            .try_contents_mut()
-            .unwrap()
-            .iter_mut()
-            .map(|body| take(body.try_contents_mut().unwrap()));
+        Ok(store)
+    }
 
-        let funcs = zip(function_section, code_section).collect();
+    /// Limits the size of the memories that are created from now on to
+    /// `pages`, or removes the limit.
+    ///
+    /// Instantiating a module whose memories are larger fails, and growing a
+    /// memory past the limit fails as if its maximum size was reached.
+    /// Without a limit, memories can grow up to the size that their address
+    /// type allows: 4 GiB for 32-bit memories, and 2^48 pages for 64-bit
+    /// ones.
+    pub fn set_memory_limit(&mut self, pages: Option<u64>) {
+        self.memory_limit = pages;
+    }
 
-        let exports = module
-            .find_std_section_mut::<Export>()
-            .unwrap()
-            .try_contents_mut()
-            .map(take)
-            .unwrap();
+    /// Gives the relaxed SIMD ops of the modules that are instantiated from
+    /// now on their deterministic semantics, or lets them return any of the
+    /// results allowed by the relaxed SIMD proposal.
+    ///
+    /// In deterministic mode, the relaxed SIMD ops have the deterministic
+    /// semantics of the proposal: they behave like the matching SIMD
+    /// instructions, the multiply-adds are fused and the lanes of the dot
+    /// products are signed.
+    pub fn set_deterministic_relaxed_simd(&mut self, deterministic: bool) {
+        self.deterministic_relaxed_simd = deterministic;
+    }
 
-        let type_section = module.find_std_section_mut::<Type>().unwrap();
+    /// Makes the exports of `instance` importable from the module `name`.
+    pub fn register(&mut self, name: impl Into<String>, instance: InstanceAddr) {
+        self.registered.insert(name.into(), instance);
+    }
 
-        let types = type_section.try_contents_mut().unwrap().drain(..).collect();
+    /// Defines the host function `func`, of type `type_`, which modules import
+    /// from the module `module` as `name`.
+    ///
+    /// The function is exported by the instance registered as `module`,
+    /// which is created if needed, and replaces any export named `name`.
+    /// Functions must be defined before the modules that import them are
+    /// instantiated.
+    pub fn define_func(
+        &mut self,
+        module: &str,
+        name: &str,
+        type_: &types::FuncType,
+        func: impl Fn(&mut Caller<'_>, &[Val]) -> Result<Vec<Val>, Unwind> + Send + Sync + 'static,
+    ) {
+        let Store {
+            code, registered, ..
+        } = self;
 
-        Store {
-            funcs,
-            exports,
-            types,
-        }
+        let instance = *registered.entry(module.to_owned()).or_insert_with(|| {
+            code.instances.push(Instance::default());
+            InstanceAddr(code.instances.len() as u32 - 1)
+        });
+
+        let addr = code.add_host_func(type_, name, instance.0, HostFunc(Arc::new(func)));
+
+        let instance = &mut code.instances[instance.0 as usize];
+        instance.exports.retain(|export| export.name != name);
+        instance.exports.push(sections::Export {
+            name: name.to_owned(),
+            desc: ExportDesc::Func(FuncId::from(instance.funcs.len() as u32)),
+        });
+        instance.funcs.push(addr);
+    }
+
+    /// Defines the shared memory `memory`, which modules import from the
+    /// module `module` as `name`.
+    ///
+    /// Like functions, the memory is exported by the instance registered as
+    /// `module`, and must be defined before the modules that import it are
+    /// instantiated.
+    pub fn define_memory(&mut self, module: &str, name: &str, memory: SharedMemory) {
+        let Store {
+            code,
+            state,
+            registered,
+            ..
+        } = self;
+
+        let instance = *registered.entry(module.to_owned()).or_insert_with(|| {
+            code.instances.push(Instance::default());
+            InstanceAddr(code.instances.len() as u32 - 1)
+        });
+
+        state.memories.push(Memory::from_shared(memory));
+
+        let instance = &mut code.instances[instance.0 as usize];
+        instance.exports.retain(|export| export.name != name);
+        instance.exports.push(sections::Export {
+            name: name.to_owned(),
+            desc: ExportDesc::Mem(MemId::from(instance.memories.len() as u32)),
+        });
+        instance.memories.push(state.memories.len() as u32 - 1);
+    }
+
+    /// Returns the memory exported by `instance` as `name`, if it is shared.
+    ///
+    /// The memory can then be imported by the modules of other stores, with
+    /// [`Store::define_memory`].
+    pub fn shared_memory(&self, instance: InstanceAddr, name: &str) -> Option<SharedMemory> {
+        let instance = &self.code.instances[instance.0 as usize];
+        let ExportDesc::Mem(memory) = instance.export(name)? else {
+            return None;
+        };
+
+        let memory = &self.state.memories[instance.memories[memory.index as usize] as usize];
+
+        memory.shared().cloned()
+    }
+
+    /// Returns the value of the global exported by `instance` as `name`.
+    pub fn global(&self, instance: InstanceAddr, name: &str) -> Option<Val> {
+        let instance = &self.code.instances[instance.0 as usize];
+        let ExportDesc::Global(global) = instance.export(name)? else {
+            return None;
+        };
+
+        let global = &self.state.globals[instance.globals[global.index as usize] as usize];
+
+        Some(global.value.to_val(global.type_.val_type, &self.code))
+    }
+
+    /// Returns the tag exported by `instance` as `name`, which identifies the
+    /// exceptions thrown with it.
+    pub fn tag(&self, instance: InstanceAddr, name: &str) -> Option<TagAddr> {
+        let instance = &self.code.instances[instance.0 as usize];
+        let ExportDesc::Exception(tag) = instance.export(name)? else {
+            return None;
+        };
+
+        Some(TagAddr(instance.tags[tag.index as usize]))
+    }
+
+    /// Instantiates `module`, resolving its imports with the exports of the
+    /// registered instances.
+    ///
+    /// This allocates the functions, tables, memories and globals of
+    /// `module`, initializes them and runs its start function.
+    pub fn instantiate(&mut self, module: &Module) -> Result<InstanceAddr, InstantiationError> {
+        self.instantiate_with(module, None)
     }
 
-    pub(crate) fn find_function(&self, sym_name: &str) -> &(TypeId, FuncBody) {
-        let desc = self
-            .exports
+    /// Instantiates `module`, resolving its imports with the exports of the
+    /// instances of `args`, by module name, or of the registered instances
+    /// if there are no `args`.
+    pub(crate) fn instantiate_with(
+        &mut self,
+        module: &Module,
+        args: Option<&HashMap<String, InstanceAddr>>,
+    ) -> Result<InstanceAddr, InstantiationError> {
+        let Store {
+            code,
+            state,
+            registered,
+            memory_limit,
+            deterministic_relaxed_simd,
+            ..
+        } = self;
+        let registered = args.unwrap_or(registered);
+        let module = &*module.data;
+        let context = &module.context;
+
+        let mut instance = Instance {
+            exports: module.exports.clone(),
+            ..Instance::default()
+        };
+
+        // Types only refer to the types of their group and of the groups
+        // before them, which are already interned.
+        let mut first = 0;
+        for len in &context.groups {
+            let end = first + *len as usize;
+            code.intern(&context.types[first..end], &mut instance.types);
+            first = end;
+        }
+
+        for import in &module.imports {
+            let path = &import.path;
+            let unlinkable = |reason| {
+                InstantiationError::Unlinkable(format!(
+                    "{reason} `{}` `{}`",
+                    path.module, path.name
+                ))
+            };
+
+            let Some((exporter, export)) = registered.get(&path.module).and_then(|addr| {
+                let exporter = &code.instances[addr.0 as usize];
+                Some((exporter, exporter.export(&path.name)?))
+            }) else {
+                return Err(unlinkable("unknown import"));
+            };
+
+            let matches = match (&import.desc, export) {
+                (ImportDesc::Func(type_), ExportDesc::Func(func)) => {
+                    let addr = exporter.funcs[func.index as usize];
+                    instance.funcs.push(addr);
+
+                    let actual = code.funcs[addr as usize].type_;
+                    code.type_matches(actual.index, instance.types[type_.index as usize])
+                }
+
+                (ImportDesc::Table(type_), ExportDesc::Table(table)) => {
+                    let addr = exporter.tables[table.index as usize];
+                    instance.tables.push(addr);
+
+                    let actual = state.tables[addr as usize].type_();
+                    let expected = TableType::from_binary(type_);
+                    actual.addr_type == expected.addr_type
+                        && actual.type_ == expected.type_.resolve(&instance.types)
+                        && actual.limits.matches(&expected.limits)
+                }
+
+                (ImportDesc::Mem(type_), ExportDesc::Mem(memory)) => {
+                    let addr = exporter.memories[memory.index as usize];
+                    instance.memories.push(addr);
+
+                    let actual = state.memories[addr as usize].type_();
+                    let expected = MemType::from_binary(type_);
+                    actual.addr_type == expected.addr_type
+                        && actual.shared == expected.shared
+                        && actual.limits.matches(&expected.limits)
+                }
+
+                (ImportDesc::Global(type_), ExportDesc::Global(global)) => {
+                    let addr = exporter.globals[global.index as usize];
+                    instance.globals.push(addr);
+
+                    // Immutable globals are covariant, and mutable ones
+                    // invariant.
+                    let actual = state.globals[addr as usize].type_;
+                    let expected = GlobalType::from_binary(type_);
+                    let val_type = expected.val_type.resolve(&instance.types);
+                    actual.mut_ == expected.mut_
+                        && match actual.mut_ {
+                            Mut::Const => code.matches(actual.val_type, val_type),
+                            Mut::Var => actual.val_type == val_type,
+                        }
+                }
+
+                (ImportDesc::Exception(type_), ExportDesc::Exception(tag)) => {
+                    let addr = exporter.tags[tag.index as usize];
+                    instance.tags.push(addr);
+
+                    code.tags[addr as usize] == instance.types[type_.func_type.index as usize]
+                }
+
+                _ => false,
+            };
+
+            if !matches {
+                return Err(unlinkable("incompatible import type"));
+            }
+        }
+
+        // Memories are allocated first, since that fails when they are too
+        // large.
+        let memories = context.memories[instance.memories.len()..]
             .iter()
-            .find_map(|export| {
-                if export.name == sym_name {
-                    Some(&export.desc)
-                } else {
-                    None
+            .map(|type_| {
+                let min = type_.limits.min;
+                let limit = memory_limit.unwrap_or(memory::max_pages(type_.addr_type));
+                if min > limit {
+                    return Err(InstantiationError::ResourceLimit(format!(
+                        "memory of {min} pages is larger than the limit of {limit} pages"
+                    )));
                 }
+
+                Memory::new(type_, limit).ok_or_else(|| {
+                    InstantiationError::ResourceLimit(format!(
+                        "memory of {min} pages can't be allocated"
+                    ))
+                })
             })
-            .unwrap();
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let first_func = code.funcs.len() as u32;
+        instance
+            .funcs
+            .extend(first_func..first_func + module.funcs.len() as u32);
+
+        for (type_, init) in zip(&context.tables[instance.tables.len()..], &module.tables) {
+            let type_ = TableType {
+                type_: type_.type_.resolve(&instance.types),
+                ..*type_
+            };
+            let init = match init {
+                Some(init) => const_expr::eval(init, &instance, code, state)?,
+                None => Cell::NULL,
+            };
+
+            instance.tables.push(state.tables.len() as u32);
+            state.tables.push(Table::new(&type_, init));
+        }
+
+        for memory in memories {
+            instance.memories.push(state.memories.len() as u32);
+            state.memories.push(memory);
+        }
+
+        // Tags are generative: each instantiation defines new ones.
+        for type_ in &context.tags[instance.tags.len()..] {
+            instance.tags.push(code.tags.len() as u32);
+            code.tags.push(instance.types[type_.index as usize]);
+        }
+
+        for (type_, init) in zip(&context.globals[instance.globals.len()..], &module.globals) {
+            let value = const_expr::eval(init, &instance, code, state)?;
 
-        let ExportDesc::Func(f) = desc else { panic!() };
+            instance.globals.push(state.globals.len() as u32);
+            state.globals.push(Global {
+                value,
+                type_: GlobalType {
+                    val_type: type_.val_type.resolve(&instance.types),
+                    ..*type_
+                },
+            });
+        }
+
+        for elem in &module.elems {
+            let elems = elem
+                .init
+                .iter()
+                .map(|expr| const_expr::eval(expr, &instance, code, state))
+                .collect::<Result<_, _>>()?;
+
+            instance.elems.push(state.elems.len() as u32);
+            state.elems.push(elems);
+        }
 
-        self.funcs.get(f.index as usize).unwrap()
+        for data in &module.datas {
+            instance.datas.push(state.datas.len() as u32);
+            state.datas.push(data.init.clone().into_boxed_slice());
+        }
+
+        for func in &module.funcs {
+            let mut func = func.clone();
+            func.type_ = TypeId::from(instance.types[func.type_.index as usize]);
+            func.instance = code.instances.len() as u32;
+            for op in &mut func.code {
+                relocate(op, &instance);
+                if *deterministic_relaxed_simd {
+                    make_deterministic(op);
+                }
+            }
+
+            code.funcs.push(func);
+        }
+
+        for addr in first_func as usize..code.funcs.len() {
+            let registers = register::translate(&code.funcs[addr], code);
+            code.funcs[addr].registers = registers;
+        }
+
+        // Segments are copied in order, and the ones copied before a trap
+        // stay in place.
+        for (elem, addr) in zip(&module.elems, &instance.elems) {
+            let addr = *addr as usize;
+
+            match &elem.mode {
+                Mode::Active { index, offset } => {
+                    let offset = u64::from_cell(const_expr::eval(offset, &instance, code, state)?);
+                    let table = &mut state.tables[instance.tables[*index as usize] as usize];
+                    let elems = &state.elems[addr];
+
+                    table.init(offset, elems, 0, elems.len() as u64)?;
+                    state.elems[addr] = Box::default();
+                }
+
+                Mode::Declarative => state.elems[addr] = Box::default(),
+
+                Mode::Passive => {}
+            }
+        }
+
+        for (data, addr) in zip(&module.datas, &instance.datas) {
+            let addr = *addr as usize;
+
+            if let Mode::Active { index, offset } = &data.mode {
+                let offset = u64::from_cell(const_expr::eval(offset, &instance, code, state)?);
+                let memory = &mut state.memories[instance.memories[*index as usize] as usize];
+                let data = &state.datas[addr];
+
+                memory.init(offset, data, 0, data.len() as u32)?;
+                state.datas[addr] = Box::default();
+            }
+        }
+
+        let start = module
+            .start
+            .map(|start| instance.funcs[start as usize] as usize);
+
+        let addr = InstanceAddr(code.instances.len() as u32);
+        code.instances.push(instance);
+
+        if let Some(start) = start {
+            let mut interpreter = Interpreter::new(self);
+            let func = &interpreter.code().funcs[start];
+            match interpreter.invoke(func, Vec::new())? {
+                EvaluationStatus::Value(_) => {}
+                EvaluationStatus::Exited(code) => return Err(InstantiationError::Exit(code)),
+                EvaluationStatus::Exception(exception) => {
+                    return Err(InstantiationError::Exception(exception));
+                }
+            }
+        }
+
+        Ok(addr)
+    }
+}
+
+impl Instance {
+    pub(crate) fn export(&self, name: &str) -> Option<&ExportDesc> {
+        self.exports
+            .iter()
+            .find(|export| export.name == name)
+            .map(|export| &export.desc)
     }
 }
 
-impl Index<FuncId> for Store {
-    type Output = (TypeId, FuncBody);
+impl Code {
+    /// Adds an instance that exports the items of `exports`, given by name,
+    /// sort and address, and returns its address.
+    pub(crate) fn add_instance(
+        &mut self,
+        exports: impl IntoIterator<Item = (String, CoreSort, u32)>,
+    ) -> InstanceAddr {
+        let mut instance = Instance::default();
+        for (name, sort, addr) in exports {
+            let (addrs, desc): (_, fn(u32) -> ExportDesc) = match sort {
+                CoreSort::Func => (&mut instance.funcs, |index| ExportDesc::Func(index.into())),
+                CoreSort::Table => (&mut instance.tables, |index| {
+                    ExportDesc::Table(index.into())
+                }),
+                CoreSort::Memory => (&mut instance.memories, |index| {
+                    ExportDesc::Mem(index.into())
+                }),
+                CoreSort::Global => (&mut instance.globals, |index| {
+                    ExportDesc::Global(index.into())
+                }),
+                CoreSort::Tag => (&mut instance.tags, |index| {
+                    ExportDesc::Exception(index.into())
+                }),
+                _ => unreachable!("{sort:?} items can't be exported"),
+            };
+
+            instance.exports.push(sections::Export {
+                name,
+                desc: desc(addrs.len() as u32),
+            });
+            addrs.push(addr);
+        }
+
+        self.instances.push(instance);
+        InstanceAddr(self.instances.len() as u32 - 1)
+    }
+
+    /// Returns the address of the item exported by `instance` as `name`.
+    pub(crate) fn export_addr(&self, instance: InstanceAddr, name: &str) -> Option<u32> {
+        let instance = &self.instances[instance.0 as usize];
+        let addr = match instance.export(name)? {
+            ExportDesc::Func(func) => instance.funcs[func.index as usize],
+            ExportDesc::Table(table) => instance.tables[table.index as usize],
+            ExportDesc::Mem(memory) => instance.memories[memory.index as usize],
+            ExportDesc::Global(global) => instance.globals[global.index as usize],
+            ExportDesc::Exception(tag) => instance.tags[tag.index as usize],
+        };
+
+        Some(addr)
+    }
+
+    /// Adds the types of the recursive group `group` to the store, unless it
+    /// already has an equivalent group, and appends their addresses to
+    /// `addrs`.
+    ///
+    /// The types before the group are the ones whose addresses are `addrs`.
+    fn intern(&mut self, group: &[SubType], addrs: &mut Vec<u32>) {
+        let first = addrs.len();
+        let len = group.len() as u32;
+
+        // The types of the group refer to each other by their address once
+        // the group starts at `base`.
+        let mut resolve = |base: u32| {
+            addrs.truncate(first);
+            addrs.extend(base..base + len);
+            group
+                .iter()
+                .map(|type_| types::resolve(type_, addrs))
+                .collect::<Vec<_>>()
+        };
+
+        let equivalent = self.groups.iter().find(|range| {
+            range.len() == group.len()
+                && resolve(range.start) == self.types[range.start as usize..range.end as usize]
+        });
+
+        match equivalent {
+            Some(range) => {
+                resolve(range.start);
+            }
+            None => {
+                let base = self.types.len() as u32;
+                let types = resolve(base);
+                self.types.extend(types);
+                self.groups.push(base..base + len);
+            }
+        }
+    }
+
+    /// Adds the host function `func`, of type `type_`, which belongs to the
+    /// instance at `instance`, and returns its address.
+    pub(crate) fn add_host_func(
+        &mut self,
+        type_: &types::FuncType,
+        name: &str,
+        instance: u32,
+        func: HostFunc,
+    ) -> u32 {
+        let (params, results) = (type_.params().len(), type_.results().len());
+
+        // The concrete types of host functions are addresses of the store.
+        let mut addrs = (0..self.types.len() as u32).collect();
+        let type_ = SubType {
+            is_final: true,
+            supertypes: Vec::new(),
+            composite_type: CompositeType::Func(type_.to_binary()),
+        };
+        self.intern(&[type_], &mut addrs);
+        let type_ = *addrs.last().unwrap();
+
+        self.hosts.push(func);
+        self.funcs.push(Func {
+            type_: TypeId::from(type_),
+            params,
+            locals: Box::default(),
+            results,
+            code: Box::new([Op::Host(self.hosts.len() as u32 - 1), Op::Return]),
+            v128s: Box::default(),
+            handlers: Box::default(),
+            max_height: params + results,
+            registers: None,
+            name: Some(Arc::from(name)),
+            instance,
+        });
+
+        self.funcs.len() as u32 - 1
+    }
+
+    /// Whether a value of type `actual` can be used where one of type
+    /// `expected` is, their concrete types being addresses of the store.
+    pub(crate) fn matches(&self, actual: ValType, expected: ValType) -> bool {
+        actual.matches(expected, self)
+    }
+
+    /// Whether the functions of the type at `actual` are of the type at
+    /// `expected`.
+    pub(crate) fn type_matches(&self, actual: u32, expected: u32) -> bool {
+        actual == expected || HeapType::Type(actual).matches(HeapType::Type(expected), self)
+    }
+
+    /// Returns the fields of the structure type at `addr`.
+    pub(crate) fn struct_type(&self, addr: u32) -> &[FieldType] {
+        match &self.types[addr as usize].composite_type {
+            CompositeType::Struct(fields) => fields,
+            _ => unreachable!("type {addr} is not a structure type"),
+        }
+    }
+
+    /// Returns the element type of the array type at `addr`.
+    pub(crate) fn array_type(&self, addr: u32) -> &FieldType {
+        match &self.types[addr as usize].composite_type {
+            CompositeType::Array(field) => field,
+            _ => unreachable!("type {addr} is not an array type"),
+        }
+    }
+
+    /// Whether `val` is a value of type `type_`, `heap` holding the
+    /// structures and arrays it may refer to.
+    pub(crate) fn has_type(&self, val: &Val, type_: ValType, heap: &Heap) -> bool {
+        let expected = match type_ {
+            ValType::RefType(type_) => type_,
+            type_ => return val.type_() == type_,
+        };
+
+        // The object must be alive, and of the kind of the reference.
+        let object_type = |ref_: u64, kind: HeapType| {
+            let type_ = HeapType::Type(heap.get_ref(ref_)?.type_);
+            (type_.abstract_(self) == kind).then_some(type_)
+        };
+
+        let heap_type = match val {
+            // Null references are of every nullable type of their hierarchy.
+            Val::Ref(Ref::Null(null)) => {
+                return expected.nullable
+                    && expected.heap_type.top(self) == null.heap_type.top(self);
+            }
+            Val::Ref(Ref::Func(FuncAddr(addr))) => {
+                Some(HeapType::Type(self.funcs[*addr as usize].type_.index))
+            }
+            // External references can be converted to internal ones.
+            Val::Ref(Ref::Extern(ExternAddr(_))) => match expected.heap_type.top(self) {
+                HeapType::Any => Some(HeapType::Any),
+                _ => Some(HeapType::Extern),
+            },
+            Val::Ref(Ref::Exn(ExnAddr(_))) => Some(HeapType::Exn),
+            Val::Ref(Ref::I31(_)) => Some(HeapType::I31),
+            Val::Ref(Ref::Struct(StructAddr(addr))) => object_type(*addr, HeapType::Struct),
+            Val::Ref(Ref::Array(ArrayAddr(addr))) => object_type(*addr, HeapType::Array),
+            _ => None,
+        };
+
+        heap_type.is_some_and(|heap_type| RefType::non_nullable(heap_type).matches(expected, self))
+    }
+
+    /// Whether the cell `ref_` of the hierarchy of `type_` holds a
+    /// reference of type `type_`, `heap` holding the structures and arrays
+    /// it may refer to.
+    pub(crate) fn ref_matches(&self, ref_: Cell, type_: RefType, heap: &Heap) -> bool {
+        if ref_ == Cell::NULL {
+            return type_.nullable;
+        }
+
+        let heap_type = match type_.heap_type.top(self) {
+            HeapType::Func => HeapType::Type(self.funcs[u32::from_cell(ref_) as usize].type_.index),
+            HeapType::Any => match ref_.to_any() {
+                AnyRef::Host(_) => HeapType::Any,
+                AnyRef::I31(_) => HeapType::I31,
+                AnyRef::Struct(ref_) | AnyRef::Array(ref_) => {
+                    HeapType::Type(heap[ref_ as u32].type_)
+                }
+            },
+            top => top,
+        };
+
+        heap_type.matches(type_.heap_type, self)
+    }
+
+    /// Returns the type of the tag at `addr`, whose parameters are the
+    /// payload of its exceptions.
+    pub(crate) fn tag_type(&self, addr: u32) -> &FuncType {
+        &self[TypeId::from(self.tags[addr as usize])]
+    }
+
+    /// Returns the address of `func`, which must be in the store.
+    pub(crate) fn func_addr(&self, func: &Func) -> u32 {
+        let offset = func as *const Func as usize - self.funcs.as_ptr() as usize;
+
+        (offset / size_of::<Func>()) as u32
+    }
+
+    /// Returns a name of `func` that is suitable for diagnostics.
+    pub(crate) fn func_name(&self, func: &Func) -> String {
+        match &func.name {
+            Some(name) => name.to_string(),
+            None => format!("func[{}]", self.func_addr(func)),
+        }
+    }
+
+    pub(crate) fn find_function(&self, sym_name: &str) -> &Func {
+        self.exported_function(self.last_instance(), sym_name)
+            .unwrap()
+    }
+
+    /// Returns the most recently created instance.
+    pub(crate) fn last_instance(&self) -> InstanceAddr {
+        InstanceAddr(self.instances.len() as u32 - 1)
+    }
+
+    /// Returns the function exported by `instance` as `name`.
+    pub(crate) fn exported_function(&self, instance: InstanceAddr, name: &str) -> Option<&Func> {
+        let instance = &self.instances[instance.0 as usize];
+        let ExportDesc::Func(f) = instance.export(name)? else {
+            return None;
+        };
+
+        Some(&self.funcs[instance.funcs[f.index as usize] as usize])
+    }
+}
+
+// Resolves the module indices used by `op` to addresses in the store.
+fn relocate(op: &mut Op, instance: &Instance) {
+    let resolve = |addrs: &[u32], idx: &mut u32| *idx = addrs[*idx as usize];
+
+    match op {
+        Op::Call(func) | Op::ReturnCall(func) | Op::RefFunc(func) => resolve(&instance.funcs, func),
+
+        Op::CallIndirect { type_, table } | Op::ReturnCallIndirect { type_, table } => {
+            resolve(&instance.types, type_);
+            resolve(&instance.tables, table);
+        }
+
+        Op::GlobalGet(global) | Op::GlobalSet(global) => resolve(&instance.globals, global),
+
+        Op::Throw(tag) | Op::Catch(tag) | Op::CatchRef(tag) => resolve(&instance.tags, tag),
+
+        Op::TableGet(table)
+        | Op::TableSet(table)
+        | Op::TableSize(table)
+        | Op::TableGrow(table)
+        | Op::TableFill(table) => resolve(&instance.tables, table),
+
+        Op::TableCopy { dst, src } => {
+            resolve(&instance.tables, dst);
+            resolve(&instance.tables, src);
+        }
+
+        Op::TableInit { elem, table } => {
+            resolve(&instance.elems, elem);
+            resolve(&instance.tables, table);
+        }
+
+        Op::ElemDrop(elem) => resolve(&instance.elems, elem),
+
+        Op::MemorySize(memory) | Op::MemoryGrow(memory) | Op::MemoryFill(memory) => {
+            resolve(&instance.memories, memory)
+        }
+
+        Op::MemoryCopy { dst, src } => {
+            resolve(&instance.memories, dst);
+            resolve(&instance.memories, src);
+        }
+
+        Op::MemoryInit { data, memory } => {
+            resolve(&instance.datas, data);
+            resolve(&instance.memories, memory);
+        }
+
+        Op::DataDrop(data) => resolve(&instance.datas, data),
+
+        Op::RefTest(type_) | Op::RefCast(type_) | Op::BrOnCast(type_) | Op::BrOnCastFail(type_) => {
+            *type_ = type_.resolve(&instance.types)
+        }
+
+        Op::StructNew(type_)
+        | Op::StructNewDefault(type_)
+        | Op::StructGet { type_, .. }
+        | Op::StructGetS { type_, .. }
+        | Op::StructGetU { type_, .. }
+        | Op::StructSet { type_, .. }
+        | Op::ArrayNew(type_)
+        | Op::ArrayNewDefault(type_)
+        | Op::ArrayNewFixed { type_, .. }
+        | Op::ArrayGet(type_)
+        | Op::ArrayGetS(type_)
+        | Op::ArrayGetU(type_)
+        | Op::ArraySet(type_)
+        | Op::ArrayFill(type_) => resolve(&instance.types, type_),
+
+        Op::ArrayNewData { type_, data } | Op::ArrayInitData { type_, data } => {
+            resolve(&instance.types, type_);
+            resolve(&instance.datas, data);
+        }
+
+        Op::ArrayNewElem { type_, elem } => {
+            resolve(&instance.types, type_);
+            resolve(&instance.elems, elem);
+        }
+
+        Op::ArrayInitElem(elem) => resolve(&instance.elems, elem),
+
+        Op::MemoryAtomicNotify(memarg)
+        | Op::MemoryAtomicWait32(memarg)
+        | Op::MemoryAtomicWait64(memarg) => resolve(&instance.memories, &mut memarg.memory),
+
+        op if op.is_load()
+            || op.is_store()
+            || op.is_lane_load()
+            || op.is_lane_store()
+            || op.is_atomic_rmw()
+            || op.is_atomic_cmpxchg() =>
+        {
+            resolve(&instance.memories, &mut op.memarg_mut().memory)
+        }
+
+        _ => {}
+    }
+}
+
+// Replaces the relaxed SIMD op `op` by its deterministic version, which is the
+// matching SIMD op when it has one.
+fn make_deterministic(op: &mut Op) {
+    *op = match op {
+        Op::I8x16RelaxedSwizzle => Op::I8x16Swizzle,
+        Op::I32x4RelaxedTruncF32x4S => Op::I32x4TruncSatF32x4S,
+        Op::I32x4RelaxedTruncF32x4U => Op::I32x4TruncSatF32x4U,
+        Op::I32x4RelaxedTruncF64x2SZero => Op::I32x4TruncSatF64x2SZero,
+        Op::I32x4RelaxedTruncF64x2UZero => Op::I32x4TruncSatF64x2UZero,
+        Op::I8x16RelaxedLaneselect
+        | Op::I16x8RelaxedLaneselect
+        | Op::I32x4RelaxedLaneselect
+        | Op::I64x2RelaxedLaneselect => Op::V128Bitselect,
+        Op::F32x4RelaxedMin => Op::F32x4Min,
+        Op::F32x4RelaxedMax => Op::F32x4Max,
+        Op::F64x2RelaxedMin => Op::F64x2Min,
+        Op::F64x2RelaxedMax => Op::F64x2Max,
+        Op::I16x8RelaxedQ15mulrS => Op::I16x8Q15mulrSatS,
+        // The other relaxed ops always have their deterministic semantics.
+        _ => return,
+    };
+}
+
+impl Index<FuncId> for Code {
+    type Output = Func;
     fn index(&self, func: FuncId) -> &Self::Output {
         &self.funcs[func.index as usize]
     }
 }
 
-impl Index<TypeId> for Store {
+/// Returns the function type at the address `type_`.
+impl Index<TypeId> for Code {
     type Output = FuncType;
 
     fn index(&self, type_: TypeId) -> &Self::Output {
-        &self.types[type_.index as usize]
+        match &self.types[type_.index as usize].composite_type {
+            CompositeType::Func(type_) => type_,
+            _ => unreachable!("type {} is not a function type", type_.index),
+        }
+    }
+}
+
+impl Types for Code {
+    fn sub_type(&self, addr: u32) -> &SubType {
+        &self.types[addr as usize]
+    }
+
+    // Equivalent types have the same address.
+    fn canonical(&self, addr: u32) -> u32 {
+        addr
     }
 }
@@ -0,0 +1,140 @@
+//! Tables of references.
+
+use crate::{
+    cell::Cell,
+    interpreter::Trap,
+    types::{AddrType, Limits, RefType, TableType},
+};
+
+/// The maximum number of elements of a table.
+///
+/// The limit is not part of the specification, it prevents a module from
+/// allocating an unreasonable amount of memory.
+pub(crate) const MAX_ELEMENTS: u32 = 10_000_000;
+
+#[derive(Debug)]
+pub(crate) struct Table {
+    elements: Vec<Cell>,
+    addr_type: AddrType,
+    /// The maximum size declared by the type of the table.
+    max: Option<u64>,
+    type_: RefType,
+}
+
+impl Table {
+    pub(crate) fn new(type_: &TableType, init: Cell) -> Table {
+        Table {
+            elements: vec![init; type_.limits.min as usize],
+            addr_type: type_.addr_type,
+            max: type_.limits.max,
+            type_: type_.type_,
+        }
+    }
+
+    /// The type of the table, whose minimum is its current size.
+    pub(crate) fn type_(&self) -> TableType {
+        TableType {
+            addr_type: self.addr_type,
+            limits: Limits {
+                min: self.size().into(),
+                max: self.max,
+            },
+            type_: self.type_,
+        }
+    }
+
+    pub(crate) fn addr_type(&self) -> AddrType {
+        self.addr_type
+    }
+
+    pub(crate) fn size(&self) -> u32 {
+        self.elements.len() as u32
+    }
+
+    /// Grows the table by `delta` elements set to `init`, and returns its
+    /// previous size, or `None` if the table can't grow that much.
+    pub(crate) fn grow(&mut self, delta: u64, init: Cell) -> Option<u32> {
+        let size = self.size();
+        let max = self.max.unwrap_or(u64::MAX).min(MAX_ELEMENTS.into());
+        let new_size = u64::from(size)
+            .checked_add(delta)
+            .filter(|size| *size <= max)?;
+
+        self.elements.resize(new_size as usize, init);
+
+        Some(size)
+    }
+
+    #[inline(always)]
+    pub(crate) fn get(&self, idx: u64) -> Result<Cell, Trap> {
+        self.elements
+            .get(index(idx))
+            .copied()
+            .ok_or(Trap::TableOutOfBounds)
+    }
+
+    pub(crate) fn set(&mut self, idx: u64, val: Cell) -> Result<(), Trap> {
+        *self
+            .elements
+            .get_mut(index(idx))
+            .ok_or(Trap::TableOutOfBounds)? = val;
+
+        Ok(())
+    }
+
+    /// Sets `len` elements starting at `dst` to `val`.
+    pub(crate) fn fill(&mut self, dst: u64, val: Cell, len: u64) -> Result<(), Trap> {
+        self.slice_mut(dst, len)?.fill(val);
+
+        Ok(())
+    }
+
+    /// Copies `len` elements from `src` to `dst`, which may overlap.
+    pub(crate) fn copy_within(&mut self, dst: u64, src: u64, len: u64) -> Result<(), Trap> {
+        self.slice_mut(dst, len)?;
+        self.slice_mut(src, len)?;
+
+        let src = src as usize;
+        self.elements
+            .copy_within(src..src + len as usize, dst as usize);
+
+        Ok(())
+    }
+
+    /// Copies `len` elements of `elems`, starting at `src`, to `dst`.
+    pub(crate) fn init(
+        &mut self,
+        dst: u64,
+        elems: &[Cell],
+        src: u64,
+        len: u64,
+    ) -> Result<(), Trap> {
+        let src = elems
+            .get(index(src)..)
+            .and_then(|elems| elems.get(..index(len)))
+            .ok_or(Trap::TableOutOfBounds)?;
+
+        self.slice_mut(dst, len)?.copy_from_slice(src);
+
+        Ok(())
+    }
+
+    // The `len` elements starting at `start`, if they are in bounds.
+    fn slice_mut(&mut self, start: u64, len: u64) -> Result<&mut [Cell], Trap> {
+        self.elements
+            .get_mut(index(start)..)
+            .and_then(|elements| elements.get_mut(..index(len)))
+            .ok_or(Trap::TableOutOfBounds)
+    }
+
+    pub(crate) fn elements(&self) -> &[Cell] {
+        &self.elements
+    }
+}
+
+// Converts the index operand `idx` to an index in a slice, which is out of
+// bounds if it doesn't fit in a `usize`.
+#[inline(always)]
+fn index(idx: u64) -> usize {
+    usize::try_from(idx).unwrap_or(usize::MAX)
+}
@@ -1,61 +1,764 @@
-#[derive(Clone, Copy)]
-pub(crate) enum NumType {
+//! The types of WebAssembly values and external values.
+//!
+//! They are converted from and to their [`wasmbin`] counterpart with
+//! [`Binary`], and are displayed in the text format.
+
+use std::fmt::{self, Display};
+
+use wasmbin::types::{CompositeType, FieldType, StorageType, SubType};
+
+/// Conversion of a type from and to its [`wasmbin`] counterpart.
+///
+/// It is not a pair of `From` impls, which would make `wasmbin` part of the
+/// public API.
+pub(crate) trait Binary<T> {
+    fn from_binary(type_: &T) -> Self;
+    fn to_binary(&self) -> T;
+}
+
+/// The type of a number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumType {
     I32,
     I64,
     F32,
     F64,
 }
 
-// TODO: VecType.
+impl Display for NumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NumType::I32 => "i32",
+            NumType::I64 => "i64",
+            NumType::F32 => "f32",
+            NumType::F64 => "f64",
+        })
+    }
+}
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum RefType {
-    FuncRef,
-    ExternRef,
+/// The type of a vector of packed numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VecType {
+    V128,
 }
 
-pub(crate) enum ValType {
+impl Display for VecType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VecType::V128 => "v128",
+        })
+    }
+}
+
+/// The type of the addresses of a memory, or of the indices of a table.
+///
+/// It is `i64` for the 64-bit memories and tables of the memory64 proposal.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AddrType {
+    #[default]
+    I32,
+    I64,
+}
+
+impl AddrType {
+    /// The type of the values holding addresses of this type.
+    pub fn val_type(self) -> ValType {
+        match self {
+            AddrType::I32 => ValType::NumType(NumType::I32),
+            AddrType::I64 => ValType::NumType(NumType::I64),
+        }
+    }
+}
+
+impl Display for AddrType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.val_type().fmt(f)
+    }
+}
+
+/// The type of the values that a reference can refer to.
+///
+/// Abstract heap types form four hierarchies, whose top types are `any`,
+/// `func`, `extern` and `exn`, and whose bottom types are `none`, `nofunc`,
+/// `noextern` and `noexn`. The `any` hierarchy holds the structures, arrays
+/// and unboxed scalars of the GC proposal, all of which are `eq`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapType {
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    None,
+    Func,
+    NoFunc,
+    Extern,
+    NoExtern,
+    Exn,
+    NoExn,
+    /// The functions, structures or arrays of the type at this index of the
+    /// type section, or of the store once the module is instantiated.
+    Type(u32),
+}
+
+impl Binary<wasmbin::types::HeapType> for HeapType {
+    fn from_binary(type_: &wasmbin::types::HeapType) -> HeapType {
+        match type_ {
+            wasmbin::types::HeapType::Any => HeapType::Any,
+            wasmbin::types::HeapType::Eq => HeapType::Eq,
+            wasmbin::types::HeapType::I31 => HeapType::I31,
+            wasmbin::types::HeapType::Struct => HeapType::Struct,
+            wasmbin::types::HeapType::Array => HeapType::Array,
+            wasmbin::types::HeapType::None => HeapType::None,
+            wasmbin::types::HeapType::Func => HeapType::Func,
+            wasmbin::types::HeapType::NoFunc => HeapType::NoFunc,
+            wasmbin::types::HeapType::Extern => HeapType::Extern,
+            wasmbin::types::HeapType::NoExtern => HeapType::NoExtern,
+            wasmbin::types::HeapType::Exception => HeapType::Exn,
+            wasmbin::types::HeapType::NoException => HeapType::NoExn,
+            wasmbin::types::HeapType::Type(type_) => HeapType::Type(type_.index),
+        }
+    }
+
+    fn to_binary(&self) -> wasmbin::types::HeapType {
+        match *self {
+            HeapType::Any => wasmbin::types::HeapType::Any,
+            HeapType::Eq => wasmbin::types::HeapType::Eq,
+            HeapType::I31 => wasmbin::types::HeapType::I31,
+            HeapType::Struct => wasmbin::types::HeapType::Struct,
+            HeapType::Array => wasmbin::types::HeapType::Array,
+            HeapType::None => wasmbin::types::HeapType::None,
+            HeapType::Func => wasmbin::types::HeapType::Func,
+            HeapType::NoFunc => wasmbin::types::HeapType::NoFunc,
+            HeapType::Extern => wasmbin::types::HeapType::Extern,
+            HeapType::NoExtern => wasmbin::types::HeapType::NoExtern,
+            HeapType::Exn => wasmbin::types::HeapType::Exception,
+            HeapType::NoExn => wasmbin::types::HeapType::NoException,
+            HeapType::Type(index) => wasmbin::types::HeapType::Type(index.into()),
+        }
+    }
+}
+
+/// The definitions of the concrete types that heap types refer to, which are
+/// the types of a module, by index, or the ones of a store, by address.
+pub(crate) trait Types {
+    /// Returns the definition of the type at `index`.
+    fn sub_type(&self, index: u32) -> &SubType;
+
+    /// Returns the index of the first type that is equivalent to the one at
+    /// `index`, by which types are compared.
+    fn canonical(&self, index: u32) -> u32;
+}
+
+impl HeapType {
+    /// Returns the abstract heap type of the values of this type, which is
+    /// `func`, `struct` or `array` for a concrete type.
+    pub(crate) fn abstract_(self, types: &impl Types) -> HeapType {
+        match self {
+            HeapType::Type(index) => match types.sub_type(index).composite_type {
+                CompositeType::Func(_) => HeapType::Func,
+                CompositeType::Struct(_) => HeapType::Struct,
+                CompositeType::Array(_) => HeapType::Array,
+            },
+            type_ => type_,
+        }
+    }
+
+    /// Returns the top type of the hierarchy of this type.
+    pub(crate) fn top(self, types: &impl Types) -> HeapType {
+        match self.abstract_(types) {
+            HeapType::Func | HeapType::NoFunc => HeapType::Func,
+            HeapType::Extern | HeapType::NoExtern => HeapType::Extern,
+            HeapType::Exn | HeapType::NoExn => HeapType::Exn,
+            _ => HeapType::Any,
+        }
+    }
+
+    /// Whether `self` is a subtype of `other`.
+    pub(crate) fn matches(self, other: HeapType, types: &impl Types) -> bool {
+        match (self, other) {
+            // Concrete types match the types they declare as their
+            // supertypes, transitively.
+            (HeapType::Type(lhs), HeapType::Type(rhs)) => {
+                let rhs = types.canonical(rhs);
+                let mut lhs = Some(lhs);
+                while let Some(index) = lhs {
+                    if types.canonical(index) == rhs {
+                        return true;
+                    }
+
+                    lhs = types
+                        .sub_type(index)
+                        .supertypes
+                        .first()
+                        .map(|type_| type_.index);
+                }
+
+                false
+            }
+            (HeapType::Type(_), other) => self.abstract_(types).matches(other, types),
+            (HeapType::None | HeapType::NoFunc | HeapType::NoExtern | HeapType::NoExn, other) => {
+                self.top(types) == other.top(types)
+            }
+            (_, HeapType::Type(_)) => false,
+            (HeapType::I31 | HeapType::Struct | HeapType::Array, HeapType::Eq) => true,
+            (lhs, HeapType::Any) => lhs.top(types) == HeapType::Any,
+            (lhs, rhs) => lhs == rhs,
+        }
+    }
+
+    /// Replaces the index of a concrete type by the element of `addrs` at
+    /// that index.
+    pub(crate) fn resolve(self, addrs: &[u32]) -> HeapType {
+        match self {
+            HeapType::Type(index) => HeapType::Type(addrs[index as usize]),
+            type_ => type_,
+        }
+    }
+}
+
+/// Displayed as `func`, or as the index of a concrete type, such as `1`.
+impl Display for HeapType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeapType::Any => f.write_str("any"),
+            HeapType::Eq => f.write_str("eq"),
+            HeapType::I31 => f.write_str("i31"),
+            HeapType::Struct => f.write_str("struct"),
+            HeapType::Array => f.write_str("array"),
+            HeapType::None => f.write_str("none"),
+            HeapType::Func => f.write_str("func"),
+            HeapType::NoFunc => f.write_str("nofunc"),
+            HeapType::Extern => f.write_str("extern"),
+            HeapType::NoExtern => f.write_str("noextern"),
+            HeapType::Exn => f.write_str("exn"),
+            HeapType::NoExn => f.write_str("noexn"),
+            HeapType::Type(index) => index.fmt(f),
+        }
+    }
+}
+
+/// The type of a reference, which refers to a value of its heap type, or is
+/// null if it is nullable.
+///
+/// Concrete heap types are the typed references of the function references
+/// proposal: a `(ref $t)` is also a `(ref func)`, or a `(ref struct)` or
+/// `(ref array)` if `$t` is a type of the GC proposal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefType {
+    pub nullable: bool,
+    pub heap_type: HeapType,
+}
+
+impl RefType {
+    pub const FUNCREF: RefType = RefType::nullable(HeapType::Func);
+    pub const EXTERNREF: RefType = RefType::nullable(HeapType::Extern);
+    /// A reference to an exception, which is caught by `catch_ref` and
+    /// rethrown by `throw_ref`.
+    pub const EXNREF: RefType = RefType::nullable(HeapType::Exn);
+    pub const ANYREF: RefType = RefType::nullable(HeapType::Any);
+    pub const EQREF: RefType = RefType::nullable(HeapType::Eq);
+    pub const I31REF: RefType = RefType::nullable(HeapType::I31);
+    pub const STRUCTREF: RefType = RefType::nullable(HeapType::Struct);
+    pub const ARRAYREF: RefType = RefType::nullable(HeapType::Array);
+    pub const NULLREF: RefType = RefType::nullable(HeapType::None);
+    pub const NULLFUNCREF: RefType = RefType::nullable(HeapType::NoFunc);
+    pub const NULLEXTERNREF: RefType = RefType::nullable(HeapType::NoExtern);
+    pub const NULLEXNREF: RefType = RefType::nullable(HeapType::NoExn);
+
+    pub const fn nullable(heap_type: HeapType) -> RefType {
+        RefType {
+            nullable: true,
+            heap_type,
+        }
+    }
+
+    pub const fn non_nullable(heap_type: HeapType) -> RefType {
+        RefType {
+            nullable: false,
+            heap_type,
+        }
+    }
+
+    /// Whether `self` is a subtype of `other`.
+    pub(crate) fn matches(self, other: RefType, types: &impl Types) -> bool {
+        (!self.nullable || other.nullable) && self.heap_type.matches(other.heap_type, types)
+    }
+
+    /// Replaces the index of the concrete type by the element of `addrs` at
+    /// that index.
+    pub(crate) fn resolve(self, addrs: &[u32]) -> RefType {
+        RefType {
+            heap_type: self.heap_type.resolve(addrs),
+            ..self
+        }
+    }
+}
+
+impl Binary<wasmbin::types::RefType> for RefType {
+    fn from_binary(type_: &wasmbin::types::RefType) -> RefType {
+        match type_ {
+            wasmbin::types::RefType::Func => RefType::FUNCREF,
+            wasmbin::types::RefType::Extern => RefType::EXTERNREF,
+            wasmbin::types::RefType::Exception => RefType::EXNREF,
+            wasmbin::types::RefType::Any => RefType::ANYREF,
+            wasmbin::types::RefType::Eq => RefType::EQREF,
+            wasmbin::types::RefType::I31 => RefType::I31REF,
+            wasmbin::types::RefType::Struct => RefType::STRUCTREF,
+            wasmbin::types::RefType::Array => RefType::ARRAYREF,
+            wasmbin::types::RefType::None => RefType::NULLREF,
+            wasmbin::types::RefType::NoFunc => RefType::NULLFUNCREF,
+            wasmbin::types::RefType::NoExtern => RefType::NULLEXTERNREF,
+            wasmbin::types::RefType::NoException => RefType::NULLEXNREF,
+            wasmbin::types::RefType::Nullable(type_) => {
+                RefType::nullable(HeapType::from_binary(type_))
+            }
+            wasmbin::types::RefType::NonNullable(type_) => {
+                RefType::non_nullable(HeapType::from_binary(type_))
+            }
+        }
+    }
+
+    fn to_binary(&self) -> wasmbin::types::RefType {
+        match *self {
+            RefType::FUNCREF => wasmbin::types::RefType::Func,
+            RefType::EXTERNREF => wasmbin::types::RefType::Extern,
+            RefType::EXNREF => wasmbin::types::RefType::Exception,
+            RefType::ANYREF => wasmbin::types::RefType::Any,
+            RefType::EQREF => wasmbin::types::RefType::Eq,
+            RefType::I31REF => wasmbin::types::RefType::I31,
+            RefType::STRUCTREF => wasmbin::types::RefType::Struct,
+            RefType::ARRAYREF => wasmbin::types::RefType::Array,
+            RefType::NULLREF => wasmbin::types::RefType::None,
+            RefType::NULLFUNCREF => wasmbin::types::RefType::NoFunc,
+            RefType::NULLEXTERNREF => wasmbin::types::RefType::NoExtern,
+            RefType::NULLEXNREF => wasmbin::types::RefType::NoException,
+            RefType {
+                nullable: true,
+                heap_type,
+            } => wasmbin::types::RefType::Nullable(heap_type.to_binary()),
+            RefType {
+                nullable: false,
+                heap_type,
+            } => wasmbin::types::RefType::NonNullable(heap_type.to_binary()),
+        }
+    }
+}
+
+/// Displayed as `funcref`, `(ref func)` or `(ref null 1)`.
+impl Display for RefType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RefType::FUNCREF => f.write_str("funcref"),
+            RefType::EXTERNREF => f.write_str("externref"),
+            RefType::EXNREF => f.write_str("exnref"),
+            RefType::ANYREF => f.write_str("anyref"),
+            RefType::EQREF => f.write_str("eqref"),
+            RefType::I31REF => f.write_str("i31ref"),
+            RefType::STRUCTREF => f.write_str("structref"),
+            RefType::ARRAYREF => f.write_str("arrayref"),
+            RefType::NULLREF => f.write_str("nullref"),
+            RefType::NULLFUNCREF => f.write_str("nullfuncref"),
+            RefType::NULLEXTERNREF => f.write_str("nullexternref"),
+            RefType::NULLEXNREF => f.write_str("nullexnref"),
+            RefType {
+                nullable: true,
+                heap_type,
+            } => write!(f, "(ref null {heap_type})"),
+            RefType {
+                nullable: false,
+                heap_type,
+            } => write!(f, "(ref {heap_type})"),
+        }
+    }
+}
+
+/// The type of a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValType {
     NumType(NumType),
-    // TODO: VecType
+    VecType(VecType),
     RefType(RefType),
 }
 
-pub(crate) struct ResultType {
-    types: Vec<ValType>,
+impl ValType {
+    /// Whether locals of this type have a default value, which non-nullable
+    /// references don't.
+    pub(crate) fn is_defaultable(self) -> bool {
+        !matches!(
+            self,
+            ValType::RefType(RefType {
+                nullable: false,
+                ..
+            })
+        )
+    }
+
+    /// Whether `self` is a subtype of `other`.
+    pub(crate) fn matches(self, other: ValType, types: &impl Types) -> bool {
+        match (self, other) {
+            (ValType::RefType(lhs), ValType::RefType(rhs)) => lhs.matches(rhs, types),
+            (lhs, rhs) => lhs == rhs,
+        }
+    }
+
+    /// Replaces the index of the concrete types by the element of `addrs` at
+    /// that index.
+    pub(crate) fn resolve(self, addrs: &[u32]) -> ValType {
+        match self {
+            ValType::RefType(type_) => ValType::RefType(type_.resolve(addrs)),
+            type_ => type_,
+        }
+    }
 }
 
-pub(crate) struct FuncType {
-    input: ResultType,
-    output: ResultType,
+impl Binary<wasmbin::types::ValueType> for ValType {
+    fn from_binary(type_: &wasmbin::types::ValueType) -> ValType {
+        match type_ {
+            wasmbin::types::ValueType::I32 => ValType::NumType(NumType::I32),
+            wasmbin::types::ValueType::I64 => ValType::NumType(NumType::I64),
+            wasmbin::types::ValueType::F32 => ValType::NumType(NumType::F32),
+            wasmbin::types::ValueType::F64 => ValType::NumType(NumType::F64),
+            wasmbin::types::ValueType::V128 => ValType::VecType(VecType::V128),
+            wasmbin::types::ValueType::Ref(type_) => ValType::RefType(RefType::from_binary(type_)),
+        }
+    }
+
+    fn to_binary(&self) -> wasmbin::types::ValueType {
+        match *self {
+            ValType::NumType(NumType::I32) => wasmbin::types::ValueType::I32,
+            ValType::NumType(NumType::I64) => wasmbin::types::ValueType::I64,
+            ValType::NumType(NumType::F32) => wasmbin::types::ValueType::F32,
+            ValType::NumType(NumType::F64) => wasmbin::types::ValueType::F64,
+            ValType::VecType(VecType::V128) => wasmbin::types::ValueType::V128,
+            ValType::RefType(type_) => wasmbin::types::ValueType::Ref(type_.to_binary()),
+        }
+    }
 }
 
-pub(crate) struct Limits {
-    min: u32,
-    max: Option<u32>,
+impl Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValType::NumType(type_) => type_.fmt(f),
+            ValType::VecType(type_) => type_.fmt(f),
+            ValType::RefType(type_) => type_.fmt(f),
+        }
+    }
 }
 
-pub(crate) struct MemType {
-    limits: Limits,
+/// The type of a function, which maps its parameters to its results.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuncType {
+    params: Box<[ValType]>,
+    results: Box<[ValType]>,
 }
 
-pub(crate) struct TableType {
-    limits: Limits,
-    type_: RefType,
+impl FuncType {
+    pub fn new(
+        params: impl IntoIterator<Item = ValType>,
+        results: impl IntoIterator<Item = ValType>,
+    ) -> FuncType {
+        FuncType {
+            params: params.into_iter().collect(),
+            results: results.into_iter().collect(),
+        }
+    }
+
+    pub fn params(&self) -> &[ValType] {
+        &self.params
+    }
+
+    pub fn results(&self) -> &[ValType] {
+        &self.results
+    }
 }
 
-pub(crate) struct GlobalType {
-    mut_: Mut,
-    val_type: ValType,
+impl Binary<wasmbin::types::FuncType> for FuncType {
+    fn from_binary(type_: &wasmbin::types::FuncType) -> FuncType {
+        FuncType::new(
+            type_.params.iter().map(ValType::from_binary),
+            type_.results.iter().map(ValType::from_binary),
+        )
+    }
+
+    fn to_binary(&self) -> wasmbin::types::FuncType {
+        wasmbin::types::FuncType {
+            params: self.params.iter().map(ValType::to_binary).collect(),
+            results: self.results.iter().map(ValType::to_binary).collect(),
+        }
+    }
 }
 
-pub(crate) enum Mut {
+/// Displayed as `(func (param i32 i32) (result i32))`.
+impl Display for FuncType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(func")?;
+        self.fmt_types(f)?;
+        write!(f, ")")
+    }
+}
+
+impl FuncType {
+    /// Writes the parameters and results, such as ` (param i32) (result i32)`.
+    fn fmt_types(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, types) in [("param", &self.params), ("result", &self.results)] {
+            if !types.is_empty() {
+                write!(f, " ({name}")?;
+                for type_ in types {
+                    write!(f, " {type_}")?;
+                }
+                write!(f, ")")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Replaces the module indices of the types `type_` refers to by the element
+/// of `addrs` at that index, such as their address in the store.
+pub(crate) fn resolve(type_: &SubType, addrs: &[u32]) -> SubType {
+    let val_types = |types: &[wasmbin::types::ValueType]| {
+        types
+            .iter()
+            .map(|type_| ValType::from_binary(type_).resolve(addrs).to_binary())
+            .collect()
+    };
+    let field = |field: &FieldType| FieldType {
+        storage_type: match &field.storage_type {
+            StorageType::Value(type_) => {
+                StorageType::Value(ValType::from_binary(type_).resolve(addrs).to_binary())
+            }
+            packed => packed.clone(),
+        },
+        mutable: field.mutable,
+    };
+
+    SubType {
+        is_final: type_.is_final,
+        supertypes: type_
+            .supertypes
+            .iter()
+            .map(|type_| addrs[type_.index as usize].into())
+            .collect(),
+        composite_type: match &type_.composite_type {
+            CompositeType::Func(type_) => CompositeType::Func(wasmbin::types::FuncType {
+                params: val_types(&type_.params),
+                results: val_types(&type_.results),
+            }),
+            CompositeType::Struct(fields) => {
+                CompositeType::Struct(fields.iter().map(field).collect())
+            }
+            CompositeType::Array(type_) => CompositeType::Array(field(type_)),
+        },
+    }
+}
+
+/// The size range of a memory, in pages, or of a table, in elements.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl Limits {
+    /// Whether an import declared with the limits `other` can be satisfied
+    /// by an external value with these limits.
+    pub(crate) fn matches(&self, other: &Limits) -> bool {
+        self.min >= other.min
+            && match (self.max, other.max) {
+                (_, None) => true,
+                (Some(max), Some(other)) => max <= other,
+                (None, Some(_)) => false,
+            }
+    }
+}
+
+impl Limits {
+    fn from_binary(limits: &wasmbin::types::Limits) -> Limits {
+        Limits {
+            min: limits.min,
+            max: limits.max,
+        }
+    }
+}
+
+impl AddrType {
+    fn from_limits(limits: &wasmbin::types::Limits) -> AddrType {
+        if limits.is_64 {
+            AddrType::I64
+        } else {
+            AddrType::I32
+        }
+    }
+
+    fn limits(self, limits: Limits) -> wasmbin::types::Limits {
+        wasmbin::types::Limits {
+            is_64: self == AddrType::I64,
+            min: limits.min,
+            max: limits.max,
+        }
+    }
+}
+
+/// Displayed as `1` or `1 2`.
+impl Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.min)?;
+
+        match self.max {
+            Some(max) => write!(f, " {max}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The type of a memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemType {
+    pub addr_type: AddrType,
+    pub limits: Limits,
+    /// Whether the memory can be accessed by several threads.
+    pub shared: bool,
+}
+
+impl Binary<wasmbin::types::MemType> for MemType {
+    fn from_binary(type_: &wasmbin::types::MemType) -> MemType {
+        MemType {
+            addr_type: AddrType::from_limits(&type_.limits),
+            limits: Limits::from_binary(&type_.limits),
+            shared: type_.is_shared,
+        }
+    }
+
+    fn to_binary(&self) -> wasmbin::types::MemType {
+        wasmbin::types::MemType {
+            is_shared: self.shared,
+            limits: self.addr_type.limits(self.limits),
+        }
+    }
+}
+
+/// Displayed as `1 2`, `i64 1 2`, or `1 2 shared`.
+impl Display for MemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.addr_type == AddrType::I64 {
+            f.write_str("i64 ")?;
+        }
+
+        self.limits.fmt(f)?;
+
+        if self.shared {
+            f.write_str(" shared")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The type of a table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableType {
+    pub addr_type: AddrType,
+    pub limits: Limits,
+    /// The type of the elements.
+    pub type_: RefType,
+}
+
+impl Binary<wasmbin::types::TableType> for TableType {
+    fn from_binary(type_: &wasmbin::types::TableType) -> TableType {
+        TableType {
+            addr_type: AddrType::from_limits(&type_.limits),
+            limits: Limits::from_binary(&type_.limits),
+            type_: RefType::from_binary(&type_.elem_type),
+        }
+    }
+
+    fn to_binary(&self) -> wasmbin::types::TableType {
+        wasmbin::types::TableType {
+            elem_type: self.type_.to_binary(),
+            limits: self.addr_type.limits(self.limits),
+        }
+    }
+}
+
+/// Displayed as `1 2 funcref`, or `i64 1 2 funcref`.
+impl Display for TableType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.addr_type == AddrType::I64 {
+            f.write_str("i64 ")?;
+        }
+
+        write!(f, "{} {}", self.limits, self.type_)
+    }
+}
+
+/// The type of a global.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobalType {
+    pub mut_: Mut,
+    pub val_type: ValType,
+}
+
+impl Binary<wasmbin::types::GlobalType> for GlobalType {
+    fn from_binary(type_: &wasmbin::types::GlobalType) -> GlobalType {
+        GlobalType {
+            mut_: if type_.mutable { Mut::Var } else { Mut::Const },
+            val_type: ValType::from_binary(&type_.value_type),
+        }
+    }
+
+    fn to_binary(&self) -> wasmbin::types::GlobalType {
+        wasmbin::types::GlobalType {
+            value_type: self.val_type.to_binary(),
+            mutable: self.mut_ == Mut::Var,
+        }
+    }
+}
+
+/// Displayed as `i32` or `(mut i32)`.
+impl Display for GlobalType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mut_ {
+            Mut::Const => self.val_type.fmt(f),
+            Mut::Var => write!(f, "(mut {})", self.val_type),
+        }
+    }
+}
+
+/// Whether a global can be modified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mut {
     Const,
     Var,
 }
 
-pub(crate) enum ExternalType {
+/// The type of an external value, which is imported or exported by a module.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExternType {
     Func(FuncType),
     Table(TableType),
     Mem(MemType),
     Global(GlobalType),
+    /// An exception tag, whose type gives the values carried by the
+    /// exceptions it is thrown with.
+    Tag(FuncType),
+}
+
+/// Displayed as an import or export description without name, such as
+/// `(memory 1)`, `(global (mut i32))` or `(tag (param i32))`.
+impl Display for ExternType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExternType::Func(type_) => type_.fmt(f),
+            ExternType::Table(type_) => write!(f, "(table {type_})"),
+            ExternType::Mem(type_) => write!(f, "(memory {type_})"),
+            ExternType::Global(type_) => write!(f, "(global {type_})"),
+            ExternType::Tag(type_) => {
+                write!(f, "(tag")?;
+                type_.fmt_types(f)?;
+                write!(f, ")")
+            }
+        }
+    }
 }
@@ -1,7 +1,13 @@
+//! WebAssembly values, as seen by the host.
+
+use std::fmt::{self, Display};
+
 use wasmbin::types::ValueType;
 
-use crate::types::{FuncType, RefType};
+use crate::types::{Binary, NumType, RefType, ValType, VecType};
 
+/// Converts `v` to a [`Val`], which is shorter than `Val::from` when building
+/// arguments.
 pub fn v<T>(v: T) -> Val
 where
     Val: From<T>,
@@ -9,6 +15,7 @@ where
     Val::from(v)
 }
 
+/// A number.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Num {
     I32(i32),
@@ -17,58 +24,140 @@ pub enum Num {
     F64(f64),
 }
 
+/// Formats a float as a literal of the text format, in which NaNs are
+/// written with their payload unless it is the canonical one.
+macro_rules! float_literal {
+    ($val:expr, $payload_mask:expr) => {{
+        let val = $val;
+        let sign = if val.is_sign_negative() { "-" } else { "" };
+        let payload = val.to_bits() & $payload_mask;
+        let canonical = $payload_mask / 2 + 1;
+
+        if val.is_nan() && payload == canonical {
+            format!("{sign}nan")
+        } else if val.is_nan() {
+            format!("{sign}nan:{payload:#x}")
+        } else if val.is_infinite() {
+            format!("{sign}inf")
+        } else {
+            format!("{val:?}")
+        }
+    }};
+}
+
+/// Displayed as a constant instruction, such as `(i32.const 42)`.
+impl Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Num::I32(val) => write!(f, "(i32.const {val})"),
+            Num::I64(val) => write!(f, "(i64.const {val})"),
+            Num::F32(val) => write!(f, "(f32.const {})", float_literal!(val, 0x7f_ffff_u32)),
+            Num::F64(val) => {
+                let literal = float_literal!(val, 0xf_ffff_ffff_ffff_u64);
+                write!(f, "(f64.const {literal})")
+            }
+        }
+    }
+}
+
+/// A reference.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Ref {
     Null(RefType),
-    Ref(FuncAddr),
+    Func(FuncAddr),
     Extern(ExternAddr),
+    Exn(ExnAddr),
+    /// An unboxed 31-bit integer, whose high bit is zero.
+    I31(u32),
+    Struct(StructAddr),
+    Array(ArrayAddr),
+}
+
+/// Displayed as a reference instruction, such as `(ref.null func)`.
+///
+/// Non-null references are displayed with their address, as in the
+/// specification tests.
+impl Display for Ref {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ref::Null(type_) => write!(f, "(ref.null {})", type_.heap_type),
+            Ref::Func(FuncAddr(addr)) => write!(f, "(ref.func {addr})"),
+            Ref::Extern(ExternAddr(addr)) => write!(f, "(ref.extern {addr})"),
+            Ref::Exn(ExnAddr(addr)) => write!(f, "(ref.exn {addr})"),
+            Ref::I31(val) => write!(f, "(ref.i31 {val})"),
+            Ref::Struct(StructAddr(ref_)) => write!(f, "(ref.struct {})", *ref_ as u32),
+            Ref::Array(ArrayAddr(ref_)) => write!(f, "(ref.array {})", *ref_ as u32),
+        }
+    }
 }
 
+/// A value, which is passed to or returned by a function, or stored in a
+/// global.
 #[derive(Clone, Debug, Copy, PartialEq)]
 pub enum Val {
     Num(Num),
-    // TODO: vec
+    /// A 128-bit vector, whose lanes are stored in little-endian order.
+    V128(u128),
     Ref(Ref),
 }
 
+/// Numbers and references are displayed as a constant instruction, such as
+/// `(i32.const 42)`, and vectors as four `i32` lanes, such as
+/// `(v128.const i32x4 0x00000001 0x00000000 0x00000000 0x00000000)`.
+impl Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Val::Num(num) => num.fmt(f),
+            Val::V128(val) => {
+                write!(f, "(v128.const i32x4")?;
+                for lane in 0..4 {
+                    write!(f, " {:#010x}", (val >> (32 * lane)) as u32)?;
+                }
+                write!(f, ")")
+            }
+            Val::Ref(ref_) => ref_.fmt(f),
+        }
+    }
+}
+
 impl Val {
+    /// Returns the zero value of `val_type`, with which locals are
+    /// initialized.
     pub fn default(val_type: &ValueType) -> Val {
         match val_type {
-            ValueType::V128 => todo!(),
+            ValueType::V128 => Val::V128(0),
             ValueType::F64 => 0.0_f64.into(),
             ValueType::F32 => 0.0_f32.into(),
             ValueType::I64 => 0_i64.into(),
             ValueType::I32 => 0_i32.into(),
-            ValueType::Ref(_) => todo!(),
+            ValueType::Ref(type_) => Val::null(RefType::from_binary(type_)),
         }
     }
 
-    pub(crate) fn i32(i: i32) -> Val {
-        Val::Num(Num::I32(i))
-    }
-
-    pub(crate) fn i64(i: i64) -> Val {
-        Val::Num(Num::I64(i))
-    }
-
-    pub(crate) fn f32(f: f32) -> Val {
-        Val::Num(Num::F32(f))
+    /// Returns the type of the value.
+    pub fn type_(&self) -> ValType {
+        match self {
+            Val::Num(Num::I32(_)) => ValType::NumType(NumType::I32),
+            Val::Num(Num::I64(_)) => ValType::NumType(NumType::I64),
+            Val::Num(Num::F32(_)) => ValType::NumType(NumType::F32),
+            Val::Num(Num::F64(_)) => ValType::NumType(NumType::F64),
+            Val::V128(_) => ValType::VecType(VecType::V128),
+            Val::Ref(Ref::Null(type_)) => ValType::RefType(*type_),
+            Val::Ref(Ref::Func(_)) => ValType::RefType(RefType::FUNCREF),
+            Val::Ref(Ref::Extern(_)) => ValType::RefType(RefType::EXTERNREF),
+            Val::Ref(Ref::Exn(_)) => ValType::RefType(RefType::EXNREF),
+            Val::Ref(Ref::I31(_)) => ValType::RefType(RefType::I31REF),
+            Val::Ref(Ref::Struct(_)) => ValType::RefType(RefType::STRUCTREF),
+            Val::Ref(Ref::Array(_)) => ValType::RefType(RefType::ARRAYREF),
+        }
     }
 
-    pub(crate) fn f64(f: f64) -> Val {
-        Val::Num(Num::F64(f))
+    pub(crate) fn i32(i: i32) -> Val {
+        Val::Num(Num::I32(i))
     }
 
     fn null(ty: RefType) -> Val {
-        Val::Ref(Ref::Null(ty))
-    }
-
-    fn ref_(addr: FuncAddr) -> Val {
-        Val::Ref(Ref::Ref(addr))
-    }
-
-    fn extern_(addr: ExternAddr) -> Val {
-        Val::Ref(Ref::Extern(addr))
+        Val::Ref(Ref::Null(RefType::nullable(ty.heap_type)))
     }
 }
 
@@ -103,22 +192,29 @@ impl_try_from_and_into!(i32, (Val::Num(Num::I32(val))) <=> (val));
 impl_try_from_and_into!(i64, (Val::Num(Num::I64(val))) <=> (val));
 impl_try_from_and_into!(f32, (Val::Num(Num::F32(val))) <=> (val));
 impl_try_from_and_into!(f64, (Val::Num(Num::F64(val))) <=> (val));
+impl_try_from_and_into!(u128, (Val::V128(val)) <=> (val));
 
-enum WasmResult {
-    Value(Vec<Val>),
-    Trap,
-}
+// Unsigned integers are stored as their two's complement representation.
+macro_rules! impl_try_from_and_into_unsigned {
+    ($ty:ty, $signed:ty) => {
+        impl TryFrom<Val> for $ty {
+            type Error = ();
 
-struct Store {
-    funcs: Vec<FuncInst>,
-    tables: Vec<TableInst>,
-    mems: Vec<MemInst>,
-    globals: Vec<GlobalInst>,
-    elems: Vec<ElemInst>,
-    datas: Vec<DataInst>,
+            fn try_from(value: Val) -> Result<Self, ()> {
+                <$signed>::try_from(value).map(|val| val as $ty)
+            }
+        }
+
+        impl From<$ty> for Val {
+            fn from(val: $ty) -> Val {
+                Val::from(val as $signed)
+            }
+        }
+    };
 }
 
-struct ModuleInst {}
+impl_try_from_and_into_unsigned!(u32, i32);
+impl_try_from_and_into_unsigned!(u64, i64);
 
 macro_rules! addr_ty {
     ($name:ident) => {
@@ -127,25 +223,22 @@ macro_rules! addr_ty {
     };
 }
 
-addr_ty!(Addr);
 addr_ty!(FuncAddr);
-addr_ty!(TableAddr);
-addr_ty!(MemAddr);
-addr_ty!(GlobalAddr);
-addr_ty!(ElemAddr);
-addr_ty!(DataAddr);
 addr_ty!(ExternAddr);
-
-enum FuncInst {
-    Local { type_: FuncType, module: ModuleInst },
+addr_ty!(TagAddr);
+addr_ty!(ExnAddr);
+
+macro_rules! object_addr_ty {
+    ($name:ident, $kind:literal) => {
+        #[doc = concat!("A reference to ", $kind, " of the heap of a store.")]
+        ///
+        /// It holds the generation of the address of the object along with the
+        /// address, so that it is rejected once the object is collected rather
+        /// than referring to the object that reuses the address.
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub struct $name(pub(crate) u64);
+    };
 }
 
-struct TableInst;
-
-struct MemInst;
-
-struct GlobalInst;
-
-struct ElemInst;
-
-struct DataInst;
+object_addr_ty!(StructAddr, "a structure");
+object_addr_ty!(ArrayAddr, "an array");
@@ -0,0 +1,1085 @@
+//! The `wasi_snapshot_preview1` imports, run in a sandbox.
+//!
+//! A [`Wasi`] holds everything a WASI program can observe: its arguments and
+//! environment, its standard streams, which are captured unless they are
+//! inherited from the host, and the directories it is given. Directories are
+//! either in memory, or on the host, in which case the program can't escape
+//! them.
+//!
+//! ```
+//! # use exwasmable::{interpreter::Interpreter, module::Module, store::Store, wasi::Wasi};
+//! let wasm = wat::parse_str(
+//!     r#"(module
+//!         (import "wasi_snapshot_preview1" "fd_write"
+//!             (func $fd_write (param i32 i32 i32 i32) (result i32)))
+//!         (memory (export "memory") 1)
+//!         (data (i32.const 8) "\10\00\00\00\06\00\00\00hello\n")
+//!         (func (export "_start")
+//!             (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 0)))))"#,
+//! )
+//! .unwrap();
+//!
+//! let wasi = Wasi::new();
+//! let mut store = Store::default();
+//! wasi.link(&mut store);
+//! store.instantiate(&Module::from_bytes(&wasm).unwrap()).unwrap();
+//!
+//! Interpreter::new(&mut store).run("_start", []).unwrap();
+//! assert_eq!(wasi.stdout(), b"hello\n");
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime},
+};
+
+use crate::{
+    host::CallerMemory,
+    interpreter::Unwind,
+    store::Store,
+    types::{
+        FuncType,
+        NumType::{self, I32, I64},
+        ValType,
+    },
+    values::{Num, Val},
+};
+
+use errno::Errno;
+use vfs::{FileType, Node, OpenFlags, Vfs};
+
+mod vfs;
+
+/// The name of the module from which WASI functions are imported.
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+/// The error codes returned by WASI functions.
+pub(crate) mod errno {
+    pub(crate) type Errno = u16;
+
+    pub(crate) const SUCCESS: Errno = 0;
+    pub(crate) const ACCES: Errno = 2;
+    pub(crate) const BADF: Errno = 8;
+    pub(crate) const EXIST: Errno = 20;
+    pub(crate) const FAULT: Errno = 21;
+    pub(crate) const FBIG: Errno = 22;
+    pub(crate) const ILSEQ: Errno = 25;
+    pub(crate) const INVAL: Errno = 28;
+    pub(crate) const IO: Errno = 29;
+    pub(crate) const ISDIR: Errno = 31;
+    pub(crate) const NAMETOOLONG: Errno = 37;
+    pub(crate) const NOENT: Errno = 44;
+    pub(crate) const NOSYS: Errno = 52;
+    pub(crate) const NOTDIR: Errno = 54;
+    pub(crate) const NOTEMPTY: Errno = 55;
+    pub(crate) const SPIPE: Errno = 70;
+    pub(crate) const XDEV: Errno = 75;
+    pub(crate) const NOTCAPABLE: Errno = 76;
+}
+
+const CLOCK_REALTIME: u32 = 0;
+const CLOCK_MONOTONIC: u32 = 1;
+const CLOCK_PROCESS_CPUTIME: u32 = 2;
+const CLOCK_THREAD_CPUTIME: u32 = 3;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+const FDFLAGS_APPEND: u16 = 1;
+
+const OFLAGS_CREAT: u32 = 1;
+const OFLAGS_DIRECTORY: u32 = 2;
+const OFLAGS_EXCL: u32 = 4;
+const OFLAGS_TRUNC: u32 = 8;
+
+/// Every right, since rights are not enforced.
+const RIGHTS_ALL: u64 = (1 << 29) - 1;
+
+/// The sandbox of a WASI program.
+///
+/// Clones share the same sandbox, so that it can be inspected once the
+/// program has run.
+#[derive(Clone, Debug)]
+pub struct Wasi {
+    context: Arc<Mutex<Context>>,
+}
+
+#[derive(Debug)]
+struct Context {
+    args: Vec<String>,
+    /// The environment variables, as `KEY=VALUE`.
+    env: Vec<String>,
+    /// Whether the standard streams are the ones of the host, rather than
+    /// the buffers below.
+    inherit_stdio: bool,
+    /// The input that has not been read yet.
+    stdin: Vec<u8>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    vfs: Vfs,
+    fds: BTreeMap<u32, Fd>,
+    /// The state of the random number generator.
+    random: u64,
+    /// When the sandbox was created, which is the origin of the monotonic
+    /// clock.
+    start: Instant,
+}
+
+#[derive(Debug)]
+enum Fd {
+    Stdin,
+    Stdout,
+    Stderr,
+    Node(OpenNode),
+}
+
+#[derive(Debug)]
+struct OpenNode {
+    node: Node,
+    type_: FileType,
+    offset: u64,
+    append: bool,
+    /// The path under which the directory is preopened.
+    preopen: Option<String>,
+}
+
+impl Default for Wasi {
+    fn default() -> Self {
+        Wasi::new()
+    }
+}
+
+impl Wasi {
+    /// Creates a sandbox without arguments, environment variables, input or
+    /// directories.
+    pub fn new() -> Wasi {
+        let context = Context {
+            args: Vec::new(),
+            env: Vec::new(),
+            inherit_stdio: false,
+            stdin: Vec::new(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            vfs: Vfs::default(),
+            fds: BTreeMap::from([(0, Fd::Stdin), (1, Fd::Stdout), (2, Fd::Stderr)]),
+            random: 0,
+            start: Instant::now(),
+        };
+
+        Wasi {
+            context: Arc::new(Mutex::new(context)),
+        }
+    }
+
+    /// Sets the arguments of the program, the first one being its name.
+    pub fn set_args(&mut self, args: impl IntoIterator<Item = impl Into<String>>) {
+        self.context().args = args.into_iter().map(Into::into).collect();
+    }
+
+    /// Sets the environment variable `key` to `value`.
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        let mut context = self.context();
+        let prefix = format!("{key}=");
+
+        context.env.retain(|var| !var.starts_with(&prefix));
+        context.env.push(format!("{prefix}{value}"));
+    }
+
+    /// Sets the bytes that the program reads from its standard input.
+    pub fn set_stdin(&mut self, input: impl Into<Vec<u8>>) {
+        self.context().stdin = input.into();
+    }
+
+    /// Makes the standard streams of the program the ones of the host,
+    /// rather than buffers.
+    pub fn set_inherit_stdio(&mut self, inherit: bool) {
+        self.context().inherit_stdio = inherit;
+    }
+
+    /// Seeds the generator behind `random_get`, which is deterministic and
+    /// not suitable for cryptography.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.context().random = seed;
+    }
+
+    /// Gives the program an empty in-memory directory, at `guest_path`.
+    pub fn preopen_dir(&mut self, guest_path: &str) {
+        let mut context = self.context();
+        let node = context.vfs.new_dir();
+
+        context.preopen(guest_path, node);
+    }
+
+    /// Gives the program the host directory `host_path`, at `guest_path`.
+    pub fn preopen_host_dir(
+        &mut self,
+        guest_path: &str,
+        host_path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let host_path = host_path.as_ref().canonicalize()?;
+        if !host_path.is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+
+        self.context().preopen(guest_path, Node::Host(host_path));
+
+        Ok(())
+    }
+
+    /// Writes `contents` to the file `path` of a preopened directory,
+    /// creating the missing directories along the way.
+    pub fn write_file(&self, path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        let mut context = self.context();
+        let (dir, path) = context.resolve(path).map_err(to_io)?;
+
+        if let Some((parents, _)) = path.rsplit_once('/') {
+            let mut parent = String::new();
+            for component in parents.split('/') {
+                parent += component;
+                parent += "/";
+
+                match context.vfs.create_dir(&dir, &parent) {
+                    Ok(()) | Err(errno::EXIST) => {}
+                    Err(error) => return Err(to_io(error)),
+                }
+            }
+        }
+
+        let flags = OpenFlags {
+            create: true,
+            truncate: true,
+            ..OpenFlags::default()
+        };
+        let file = context.vfs.open(&dir, path, flags).map_err(to_io)?;
+
+        context
+            .vfs
+            .write(&file, 0, contents.as_ref())
+            .map_err(to_io)
+    }
+
+    /// Returns the contents of the file `path` of a preopened directory.
+    pub fn read_file(&self, path: &str) -> io::Result<Vec<u8>> {
+        let context = self.context();
+        let (dir, path) = context.resolve(path).map_err(to_io)?;
+
+        let file = context.vfs.lookup(&dir, path).map_err(to_io)?;
+        let mut contents = vec![0; context.vfs.stat(&file).map_err(to_io)?.size as usize];
+        let len = context.vfs.read(&file, 0, &mut contents).map_err(to_io)?;
+        contents.truncate(len);
+
+        Ok(contents)
+    }
+
+    /// Returns what the program wrote to its standard output, unless it is
+    /// inherited.
+    pub fn stdout(&self) -> Vec<u8> {
+        self.context().stdout.clone()
+    }
+
+    /// Returns what the program wrote to its standard error, unless it is
+    /// inherited.
+    pub fn stderr(&self) -> Vec<u8> {
+        self.context().stderr.clone()
+    }
+
+    /// Defines the WASI functions in `store`, under [`MODULE`].
+    ///
+    /// Functions read and write the memory exported as `memory` by their
+    /// caller. `proc_exit` unwinds with [`Unwind::Exit`], and the functions that
+    /// the sandbox doesn't support return `ENOSYS`.
+    pub fn link(&self, store: &mut Store) {
+        for &(name, params, handler) in FUNCS {
+            let type_ = FuncType::new(
+                params.iter().map(|&param| ValType::NumType(param)),
+                [ValType::NumType(I32)],
+            );
+            let context = Arc::clone(&self.context);
+
+            store.define_func(MODULE, name, &type_, move |caller, args| {
+                let mut context = context.lock().unwrap();
+                let result = match caller.memory("memory") {
+                    Some(memory) => handler(&mut context, &mut Memory(memory), Args(args)),
+                    None => Err(errno::FAULT),
+                };
+
+                let errno = result.err().unwrap_or(errno::SUCCESS);
+
+                Ok(vec![Val::from(i32::from(errno))])
+            });
+        }
+
+        let type_ = FuncType::new([ValType::NumType(I32)], []);
+        store.define_func(MODULE, "proc_exit", &type_, |_, args| {
+            Err(Unwind::Exit(Args(args).u32(0) as i32))
+        });
+    }
+
+    fn context(&self) -> std::sync::MutexGuard<'_, Context> {
+        self.context.lock().unwrap()
+    }
+}
+
+impl Context {
+    fn preopen(&mut self, guest_path: &str, node: Node) {
+        let fd = self.free_fd();
+        self.fds.insert(
+            fd,
+            Fd::Node(OpenNode {
+                node,
+                type_: FileType::Dir,
+                offset: 0,
+                append: false,
+                preopen: Some(guest_path.to_owned()),
+            }),
+        );
+    }
+
+    /// Returns the preopened directory that contains the absolute path
+    /// `path`, along with the path relative to it.
+    fn resolve<'p>(&self, path: &'p str) -> Result<(Node, &'p str), Errno> {
+        let (preopen, node) = self
+            .fds
+            .values()
+            .filter_map(|fd| match fd {
+                Fd::Node(OpenNode {
+                    node,
+                    preopen: Some(preopen),
+                    ..
+                }) => Some((preopen.trim_end_matches('/'), node)),
+                _ => None,
+            })
+            .filter(|(preopen, _)| {
+                path.strip_prefix(preopen)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(preopen, _)| preopen.len())
+            .ok_or(errno::NOENT)?;
+
+        let path = path[preopen.len()..].trim_start_matches('/');
+
+        Ok((node.clone(), if path.is_empty() { "." } else { path }))
+    }
+
+    /// The lowest file descriptor that is not in use.
+    fn free_fd(&self) -> u32 {
+        (0..).find(|fd| !self.fds.contains_key(fd)).unwrap()
+    }
+
+    fn fd(&mut self, fd: u32) -> Result<&mut Fd, Errno> {
+        self.fds.get_mut(&fd).ok_or(errno::BADF)
+    }
+
+    /// The file or directory opened as `fd`.
+    fn node(&mut self, fd: u32) -> Result<&mut OpenNode, Errno> {
+        match self.fd(fd)? {
+            Fd::Node(open) => Ok(open),
+            _ => Err(errno::BADF),
+        }
+    }
+
+    /// The directory opened as `fd`.
+    fn dir(&mut self, fd: u32) -> Result<Node, Errno> {
+        match self.fd(fd)? {
+            Fd::Node(open) if open.type_ == FileType::Dir => Ok(open.node.clone()),
+            _ => Err(errno::NOTDIR),
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // SplitMix64.
+        self.random = self.random.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.random;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// The memory of the caller, whose out of bounds accesses fail with
+/// `EFAULT`.
+struct Memory<'a>(CallerMemory<'a>);
+
+impl Memory<'_> {
+    /// Returns a zeroed buffer of `len` bytes, if the `len` bytes starting at
+    /// `ptr` are in bounds.
+    fn buffer(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+        if u64::from(ptr) + u64::from(len) > self.0.len() {
+            return Err(errno::FAULT);
+        }
+
+        Ok(vec![0; len as usize])
+    }
+
+    fn read(&self, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+        let mut bytes = self.buffer(ptr, len)?;
+        self.0
+            .read(ptr.into(), &mut bytes)
+            .map_err(|_| errno::FAULT)?;
+
+        Ok(bytes)
+    }
+
+    fn write_bytes(&mut self, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+        self.0.write(ptr.into(), bytes).map_err(|_| errno::FAULT)
+    }
+
+    fn write<const N: usize>(&mut self, ptr: u32, bytes: [u8; N]) -> Result<(), Errno> {
+        self.write_bytes(ptr, &bytes)
+    }
+
+    fn read_u32(&self, ptr: u32) -> Result<u32, Errno> {
+        let bytes = self.read(ptr, 4)?;
+
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn str(&self, ptr: u32, len: u32) -> Result<String, Errno> {
+        String::from_utf8(self.read(ptr, len)?).map_err(|_| errno::ILSEQ)
+    }
+
+    /// Reads `len` buffers, as pairs of a pointer and a length, starting at
+    /// `ptr`.
+    fn iovecs(&self, ptr: u32, len: u32) -> Result<Vec<(u32, u32)>, Errno> {
+        (0..len)
+            .map(|idx| {
+                let iovec = ptr.wrapping_add(idx.wrapping_mul(8));
+                Ok((self.read_u32(iovec)?, self.read_u32(iovec.wrapping_add(4))?))
+            })
+            .collect()
+    }
+
+    /// Writes `strings`, NUL-terminated, to `buf`, and a pointer to each of
+    /// them to `ptrs`.
+    fn write_strings(&mut self, strings: &[String], ptrs: u32, buf: u32) -> Result<(), Errno> {
+        let (mut ptr, mut buf) = (ptrs, buf);
+
+        for string in strings {
+            let len = string.len() as u32;
+            self.write_bytes(buf, &[string.as_bytes(), &[0]].concat())?;
+
+            self.write(ptr, buf.to_le_bytes())?;
+
+            ptr = ptr.checked_add(4).ok_or(errno::FAULT)?;
+            buf = buf.checked_add(len + 1).ok_or(errno::FAULT)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the number of `strings` to `count`, and the size of their
+    /// NUL-terminated bytes to `size`.
+    fn write_sizes(&mut self, strings: &[String], count: u32, size: u32) -> Result<(), Errno> {
+        let len = strings.iter().map(|string| string.len() + 1).sum::<usize>();
+
+        self.write(count, (strings.len() as u32).to_le_bytes())?;
+        self.write(size, (len as u32).to_le_bytes())
+    }
+
+    fn write_filestat(&mut self, ptr: u32, filetype: u8, ino: u64, size: u64) -> Result<(), Errno> {
+        let mut stat = [0; 64];
+        stat[8..16].copy_from_slice(&ino.to_le_bytes());
+        stat[16] = filetype;
+        // There are no hard links.
+        stat[24..32].copy_from_slice(&1_u64.to_le_bytes());
+        stat[32..40].copy_from_slice(&size.to_le_bytes());
+
+        self.write(ptr, stat)
+    }
+}
+
+/// The arguments of a WASI function, whose types are known from its
+/// signature.
+#[derive(Clone, Copy)]
+struct Args<'a>(&'a [Val]);
+
+impl Args<'_> {
+    fn u32(self, idx: usize) -> u32 {
+        match self.0[idx] {
+            Val::Num(Num::I32(val)) => val as u32,
+            val => unreachable!("`{val}` is not an i32"),
+        }
+    }
+
+    fn u64(self, idx: usize) -> u64 {
+        match self.0[idx] {
+            Val::Num(Num::I64(val)) => val as u64,
+            val => unreachable!("`{val}` is not an i64"),
+        }
+    }
+}
+
+type Handler = fn(&mut Context, &mut Memory<'_>, Args<'_>) -> Result<(), Errno>;
+
+/// The WASI functions that return an errno, along with their parameters.
+#[rustfmt::skip]
+const FUNCS: &[(&str, &[NumType], Handler)] = &[
+    ("args_get", &[I32, I32], args_get),
+    ("args_sizes_get", &[I32, I32], args_sizes_get),
+    ("environ_get", &[I32, I32], environ_get),
+    ("environ_sizes_get", &[I32, I32], environ_sizes_get),
+    ("clock_res_get", &[I32, I32], clock_res_get),
+    ("clock_time_get", &[I32, I64, I32], clock_time_get),
+    ("fd_advise", &[I32, I64, I64, I32], fd_check),
+    ("fd_allocate", &[I32, I64, I64], fd_allocate),
+    ("fd_close", &[I32], fd_close),
+    ("fd_datasync", &[I32], fd_check),
+    ("fd_fdstat_get", &[I32, I32], fd_fdstat_get),
+    ("fd_fdstat_set_flags", &[I32, I32], fd_fdstat_set_flags),
+    ("fd_fdstat_set_rights", &[I32, I64, I64], fd_check),
+    ("fd_filestat_get", &[I32, I32], fd_filestat_get),
+    ("fd_filestat_set_size", &[I32, I64], fd_filestat_set_size),
+    ("fd_filestat_set_times", &[I32, I64, I64, I32], fd_check),
+    ("fd_pread", &[I32, I32, I32, I64, I32], fd_pread),
+    ("fd_prestat_get", &[I32, I32], fd_prestat_get),
+    ("fd_prestat_dir_name", &[I32, I32, I32], fd_prestat_dir_name),
+    ("fd_pwrite", &[I32, I32, I32, I64, I32], fd_pwrite),
+    ("fd_read", &[I32, I32, I32, I32], fd_read),
+    ("fd_readdir", &[I32, I32, I32, I64, I32], fd_readdir),
+    ("fd_renumber", &[I32, I32], fd_renumber),
+    ("fd_seek", &[I32, I64, I32, I32], fd_seek),
+    ("fd_sync", &[I32], fd_check),
+    ("fd_tell", &[I32, I32], fd_tell),
+    ("fd_write", &[I32, I32, I32, I32], fd_write),
+    ("path_create_directory", &[I32, I32, I32], path_create_directory),
+    ("path_filestat_get", &[I32, I32, I32, I32, I32], path_filestat_get),
+    ("path_filestat_set_times", &[I32, I32, I32, I32, I64, I64, I32], path_filestat_set_times),
+    ("path_link", &[I32, I32, I32, I32, I32, I32, I32], unsupported),
+    ("path_open", &[I32, I32, I32, I32, I32, I64, I64, I32, I32], path_open),
+    ("path_readlink", &[I32, I32, I32, I32, I32, I32], path_readlink),
+    ("path_remove_directory", &[I32, I32, I32], path_remove_directory),
+    ("path_rename", &[I32, I32, I32, I32, I32, I32], path_rename),
+    ("path_symlink", &[I32, I32, I32, I32, I32], unsupported),
+    ("path_unlink_file", &[I32, I32, I32], path_unlink_file),
+    ("poll_oneoff", &[I32, I32, I32, I32], unsupported),
+    ("proc_raise", &[I32], unsupported),
+    ("sched_yield", &[], |_, _, _| Ok(())),
+    ("random_get", &[I32, I32], random_get),
+    ("sock_accept", &[I32, I32, I32], unsupported),
+    ("sock_recv", &[I32, I32, I32, I32, I32, I32], unsupported),
+    ("sock_send", &[I32, I32, I32, I32, I32], unsupported),
+    ("sock_shutdown", &[I32, I32], unsupported),
+];
+
+/// Processes, sockets, links and polling are not supported.
+fn unsupported(_: &mut Context, _: &mut Memory<'_>, _: Args<'_>) -> Result<(), Errno> {
+    Err(errno::NOSYS)
+}
+
+/// Succeeds if the file descriptor exists, for the functions that are no-ops
+/// in the sandbox: data is never cached, and times and rights are not
+/// tracked.
+fn fd_check(context: &mut Context, _: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    context.fd(args.u32(0)).map(drop)
+}
+
+fn args_get(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    memory.write_strings(&context.args, args.u32(0), args.u32(1))
+}
+
+fn args_sizes_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    memory.write_sizes(&context.args, args.u32(0), args.u32(1))
+}
+
+fn environ_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    memory.write_strings(&context.env, args.u32(0), args.u32(1))
+}
+
+fn environ_sizes_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    memory.write_sizes(&context.env, args.u32(0), args.u32(1))
+}
+
+fn clock_res_get(_: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    match args.u32(0) {
+        CLOCK_REALTIME..=CLOCK_THREAD_CPUTIME => memory.write(args.u32(1), 1_u64.to_le_bytes()),
+        _ => Err(errno::INVAL),
+    }
+}
+
+fn clock_time_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let time = match args.u32(0) {
+        CLOCK_REALTIME => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default(),
+        // There is a single thread, whose CPU time is approximated by the
+        // time elapsed since the sandbox was created.
+        CLOCK_MONOTONIC | CLOCK_PROCESS_CPUTIME | CLOCK_THREAD_CPUTIME => context.start.elapsed(),
+        _ => return Err(errno::INVAL),
+    };
+
+    memory.write(args.u32(2), (time.as_nanos() as u64).to_le_bytes())
+}
+
+fn fd_allocate(context: &mut Context, _: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let node = context.node(args.u32(0))?.node.clone();
+    let end = args.u64(1).checked_add(args.u64(2)).ok_or(errno::FBIG)?;
+
+    if context.vfs.stat(&node)?.size < end {
+        context.vfs.set_size(&node, end)?;
+    }
+
+    Ok(())
+}
+
+fn fd_close(context: &mut Context, _: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    context
+        .fds
+        .remove(&args.u32(0))
+        .map(drop)
+        .ok_or(errno::BADF)
+}
+
+fn fd_fdstat_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let (filetype, flags) = match context.fd(args.u32(0))? {
+        Fd::Stdin | Fd::Stdout | Fd::Stderr => (FILETYPE_CHARACTER_DEVICE, 0),
+        Fd::Node(open) => (
+            filetype(open.type_),
+            if open.append { FDFLAGS_APPEND } else { 0 },
+        ),
+    };
+
+    let mut stat = [0; 24];
+    stat[0] = filetype;
+    stat[2..4].copy_from_slice(&flags.to_le_bytes());
+    stat[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+    stat[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+
+    memory.write(args.u32(1), stat)
+}
+
+fn fd_fdstat_set_flags(
+    context: &mut Context,
+    _: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let append = args.u32(1) as u16 & FDFLAGS_APPEND != 0;
+
+    match context.fd(args.u32(0))? {
+        Fd::Node(open) => open.append = append,
+        // The standard streams are not seekable, so they always append.
+        Fd::Stdin | Fd::Stdout | Fd::Stderr => {}
+    }
+
+    Ok(())
+}
+
+fn fd_filestat_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    match context.fd(args.u32(0))? {
+        Fd::Stdin | Fd::Stdout | Fd::Stderr => {
+            memory.write_filestat(args.u32(1), FILETYPE_CHARACTER_DEVICE, 0, 0)
+        }
+        Fd::Node(open) => {
+            let node = open.node.clone();
+            let stat = context.vfs.stat(&node)?;
+
+            memory.write_filestat(args.u32(1), filetype(stat.type_), stat.ino, stat.size)
+        }
+    }
+}
+
+fn fd_filestat_set_size(
+    context: &mut Context,
+    _: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let node = context.node(args.u32(0))?.node.clone();
+
+    context.vfs.set_size(&node, args.u64(1))
+}
+
+fn fd_pread(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let node = match context.fd(args.u32(0))? {
+        Fd::Node(open) => open.node.clone(),
+        _ => return Err(errno::SPIPE),
+    };
+
+    let mut read = 0;
+    for (ptr, len) in memory.iovecs(args.u32(1), args.u32(2))? {
+        let offset = args.u64(3) + read as u64;
+        let mut buf = memory.buffer(ptr, len)?;
+        let count = context.vfs.read(&node, offset, &mut buf)?;
+        memory.write_bytes(ptr, &buf[..count])?;
+        read += count;
+
+        if count < len as usize {
+            break;
+        }
+    }
+
+    memory.write(args.u32(4), (read as u32).to_le_bytes())
+}
+
+fn fd_prestat_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let Some(name) = &context.node(args.u32(0))?.preopen else {
+        return Err(errno::BADF);
+    };
+
+    let mut prestat = [0; 8];
+    prestat[4..].copy_from_slice(&(name.len() as u32).to_le_bytes());
+
+    memory.write(args.u32(1), prestat)
+}
+
+fn fd_prestat_dir_name(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let Some(name) = &context.node(args.u32(0))?.preopen else {
+        return Err(errno::BADF);
+    };
+
+    if (args.u32(2) as usize) < name.len() {
+        return Err(errno::NAMETOOLONG);
+    }
+
+    memory.write_bytes(args.u32(1), name.as_bytes())
+}
+
+fn fd_pwrite(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let node = match context.fd(args.u32(0))? {
+        Fd::Node(open) => open.node.clone(),
+        _ => return Err(errno::SPIPE),
+    };
+
+    let mut data = Vec::new();
+    for (buf, len) in memory.iovecs(args.u32(1), args.u32(2))? {
+        data.extend(memory.read(buf, len)?);
+    }
+
+    context.vfs.write(&node, args.u64(3), &data)?;
+
+    memory.write(args.u32(4), (data.len() as u32).to_le_bytes())
+}
+
+fn fd_read(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let Context {
+        inherit_stdio,
+        stdin,
+        vfs,
+        fds,
+        ..
+    } = context;
+    let fd = fds.get_mut(&args.u32(0)).ok_or(errno::BADF)?;
+
+    let mut read = 0;
+    for (ptr, len) in memory.iovecs(args.u32(1), args.u32(2))? {
+        let mut buf = memory.buffer(ptr, len)?;
+
+        let count = match fd {
+            Fd::Stdin if *inherit_stdio => io::stdin().read(&mut buf).map_err(|_| errno::IO)?,
+            Fd::Stdin => {
+                let len = buf.len().min(stdin.len());
+                buf[..len].copy_from_slice(&stdin[..len]);
+                stdin.drain(..len);
+                len
+            }
+            Fd::Node(open) => {
+                let len = vfs.read(&open.node, open.offset, &mut buf)?;
+                open.offset += len as u64;
+                len
+            }
+            Fd::Stdout | Fd::Stderr => return Err(errno::BADF),
+        };
+
+        memory.write_bytes(ptr, &buf[..count])?;
+
+        read += count;
+        if count < len as usize {
+            break;
+        }
+    }
+
+    memory.write(args.u32(3), (read as u32).to_le_bytes())
+}
+
+fn fd_readdir(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+    let (buf, buf_len, cookie) = (args.u32(1), args.u32(2), args.u64(3));
+
+    // Entries are written until the buffer is full, the last one being
+    // truncated.
+    let mut entries = Vec::new();
+    let all = context.vfs.read_dir(&dir)?;
+    for (idx, entry) in all.iter().enumerate().skip(cookie as usize) {
+        if entries.len() >= buf_len as usize {
+            break;
+        }
+
+        let mut header = [0; 24];
+        header[..8].copy_from_slice(&(idx as u64 + 1).to_le_bytes());
+        header[8..16].copy_from_slice(&entry.ino.to_le_bytes());
+        header[16..20].copy_from_slice(&(entry.name.len() as u32).to_le_bytes());
+        header[20] = filetype(entry.type_);
+
+        entries.extend_from_slice(&header);
+        entries.extend_from_slice(entry.name.as_bytes());
+    }
+
+    entries.truncate(buf_len as usize);
+    memory.write_bytes(buf, &entries)?;
+
+    memory.write(args.u32(4), (entries.len() as u32).to_le_bytes())
+}
+
+fn fd_renumber(context: &mut Context, _: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let (from, to) = (args.u32(0), args.u32(1));
+    context.fd(to)?;
+
+    let fd = context.fds.remove(&from).ok_or(errno::BADF)?;
+    context.fds.insert(to, fd);
+
+    Ok(())
+}
+
+fn fd_seek(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let Context { vfs, fds, .. } = context;
+    let open = match fds.get_mut(&args.u32(0)).ok_or(errno::BADF)? {
+        Fd::Node(open) if open.type_ == FileType::File => open,
+        Fd::Node(_) => return Err(errno::BADF),
+        Fd::Stdin | Fd::Stdout | Fd::Stderr => return Err(errno::SPIPE),
+    };
+
+    let base = match args.u32(2) {
+        0 => 0,
+        1 => open.offset,
+        2 => vfs.stat(&open.node)?.size,
+        _ => return Err(errno::INVAL),
+    };
+
+    let offset = base.checked_add_signed(args.u64(1) as i64);
+    open.offset = offset.ok_or(errno::INVAL)?;
+
+    memory.write(args.u32(3), open.offset.to_le_bytes())
+}
+
+fn fd_tell(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let offset = match context.fd(args.u32(0))? {
+        Fd::Node(open) => open.offset,
+        _ => return Err(errno::SPIPE),
+    };
+
+    memory.write(args.u32(1), offset.to_le_bytes())
+}
+
+fn fd_write(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let Context {
+        inherit_stdio,
+        stdout,
+        stderr,
+        vfs,
+        fds,
+        ..
+    } = context;
+    let fd = fds.get_mut(&args.u32(0)).ok_or(errno::BADF)?;
+
+    let mut written = 0;
+    for (buf, len) in memory.iovecs(args.u32(1), args.u32(2))? {
+        let data = memory.read(buf, len)?;
+
+        match fd {
+            Fd::Stdout if *inherit_stdio => io::stdout().write_all(&data).map_err(|_| errno::IO)?,
+            Fd::Stderr if *inherit_stdio => io::stderr().write_all(&data).map_err(|_| errno::IO)?,
+            Fd::Stdout => stdout.extend_from_slice(&data),
+            Fd::Stderr => stderr.extend_from_slice(&data),
+            Fd::Node(open) => {
+                if open.append {
+                    open.offset = vfs.stat(&open.node)?.size;
+                }
+
+                vfs.write(&open.node, open.offset, &data)?;
+                open.offset += data.len() as u64;
+            }
+            Fd::Stdin => return Err(errno::BADF),
+        }
+
+        written += data.len();
+    }
+
+    memory.write(args.u32(3), (written as u32).to_le_bytes())
+}
+
+fn path_create_directory(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+
+    context
+        .vfs
+        .create_dir(&dir, &memory.str(args.u32(1), args.u32(2))?)
+}
+
+fn path_filestat_get(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+    let node = context
+        .vfs
+        .lookup(&dir, &memory.str(args.u32(2), args.u32(3))?)?;
+    let stat = context.vfs.stat(&node)?;
+
+    memory.write_filestat(args.u32(4), filetype(stat.type_), stat.ino, stat.size)
+}
+
+fn path_filestat_set_times(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+
+    // Times are not tracked.
+    context
+        .vfs
+        .lookup(&dir, &memory.str(args.u32(2), args.u32(3))?)
+        .map(drop)
+}
+
+fn path_open(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+    let path = memory.str(args.u32(2), args.u32(3))?;
+
+    let oflags = args.u32(4);
+    let flags = OpenFlags {
+        create: oflags & OFLAGS_CREAT != 0,
+        directory: oflags & OFLAGS_DIRECTORY != 0,
+        exclusive: oflags & OFLAGS_EXCL != 0,
+        truncate: oflags & OFLAGS_TRUNC != 0,
+    };
+
+    let node = context.vfs.open(&dir, &path, flags)?;
+    let type_ = context.vfs.stat(&node)?.type_;
+
+    let fd = context.free_fd();
+    memory.write(args.u32(8), fd.to_le_bytes())?;
+    context.fds.insert(
+        fd,
+        Fd::Node(OpenNode {
+            node,
+            type_,
+            offset: 0,
+            append: args.u32(7) as u16 & FDFLAGS_APPEND != 0,
+            preopen: None,
+        }),
+    );
+
+    Ok(())
+}
+
+fn path_readlink(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+    context
+        .vfs
+        .lookup(&dir, &memory.str(args.u32(1), args.u32(2))?)?;
+
+    // Symbolic links are resolved on the host, so none is ever seen.
+    Err(errno::INVAL)
+}
+
+fn path_remove_directory(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+
+    context
+        .vfs
+        .remove(&dir, &memory.str(args.u32(1), args.u32(2))?, true)
+}
+
+fn path_rename(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+    let new_dir = context.dir(args.u32(3))?;
+    let path = memory.str(args.u32(1), args.u32(2))?;
+    let new_path = memory.str(args.u32(4), args.u32(5))?;
+
+    context.vfs.rename(&dir, &path, &new_dir, &new_path)
+}
+
+fn path_unlink_file(
+    context: &mut Context,
+    memory: &mut Memory<'_>,
+    args: Args<'_>,
+) -> Result<(), Errno> {
+    let dir = context.dir(args.u32(0))?;
+
+    context
+        .vfs
+        .remove(&dir, &memory.str(args.u32(1), args.u32(2))?, false)
+}
+
+fn random_get(context: &mut Context, memory: &mut Memory<'_>, args: Args<'_>) -> Result<(), Errno> {
+    let mut buf = memory.buffer(args.u32(0), args.u32(1))?;
+    for chunk in buf.chunks_mut(8) {
+        let random = context.next_random().to_le_bytes();
+        chunk.copy_from_slice(&random[..chunk.len()]);
+    }
+
+    memory.write_bytes(args.u32(0), &buf)
+}
+
+fn filetype(type_: FileType) -> u8 {
+    match type_ {
+        FileType::File => FILETYPE_REGULAR_FILE,
+        FileType::Dir => FILETYPE_DIRECTORY,
+    }
+}
+
+fn to_io(errno: Errno) -> io::Error {
+    let kind = match errno {
+        errno::NOENT => io::ErrorKind::NotFound,
+        errno::EXIST => io::ErrorKind::AlreadyExists,
+        errno::NOTDIR => io::ErrorKind::NotADirectory,
+        errno::ISDIR => io::ErrorKind::IsADirectory,
+        errno::ACCES | errno::NOTCAPABLE => io::ErrorKind::PermissionDenied,
+        _ => io::ErrorKind::Other,
+    };
+
+    io::Error::new(kind, format!("WASI error {errno}"))
+}
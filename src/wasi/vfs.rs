@@ -0,0 +1,541 @@
+//! The filesystem seen by WASI programs.
+//!
+//! Files and directories either live in memory, or are paths on the host.
+//! Paths are always resolved relative to a directory, and can't escape it:
+//! absolute paths are rejected, `..` can't go above the directory, and host
+//! paths that leave it through a symbolic link are rejected too.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use super::errno::{self, Errno};
+
+/// The maximum size of an in-memory file, so that writing far past its end
+/// doesn't exhaust the memory of the host.
+const MAX_FILE_SIZE: u64 = 1 << 30;
+
+/// A file or a directory.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Node {
+    /// An inode of the in-memory filesystem.
+    Memory(usize),
+    /// A path on the host.
+    Host(PathBuf),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum FileType {
+    File,
+    Dir,
+}
+
+/// The metadata of a node.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Stat {
+    pub(crate) type_: FileType,
+    /// The inode number, which is zero for host nodes.
+    pub(crate) ino: u64,
+    pub(crate) size: u64,
+}
+
+/// An entry of a directory.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    pub(crate) type_: FileType,
+    pub(crate) ino: u64,
+}
+
+/// How a node is opened, from the `oflags` of `path_open`.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct OpenFlags {
+    /// Creates a file if the path doesn't exist.
+    pub(crate) create: bool,
+    /// Fails if the path doesn't designate a directory.
+    pub(crate) directory: bool,
+    /// Fails if the path exists, along with `create`.
+    pub(crate) exclusive: bool,
+    /// Truncates the file to zero bytes.
+    pub(crate) truncate: bool,
+}
+
+/// The in-memory filesystem.
+///
+/// Inodes are never freed, so that open files keep working once unlinked.
+#[derive(Debug, Default)]
+pub(crate) struct Vfs {
+    inodes: Vec<Inode>,
+}
+
+#[derive(Debug)]
+enum Inode {
+    File(Vec<u8>),
+    Dir(BTreeMap<String, usize>),
+}
+
+impl Vfs {
+    /// Creates an empty in-memory directory.
+    pub(crate) fn new_dir(&mut self) -> Node {
+        Node::Memory(self.alloc(Inode::Dir(BTreeMap::new())))
+    }
+
+    /// Returns the node designated by `path`, relative to `dir`.
+    pub(crate) fn lookup(&self, dir: &Node, path: &str) -> Result<Node, Errno> {
+        let (parent, name) = self.parent(dir, path)?;
+
+        match (parent, name) {
+            (parent, None) => Ok(parent),
+            (Node::Memory(parent), Some(name)) => self.child(parent, name).map(Node::Memory),
+            (Node::Host(parent), Some(name)) => confine(&parent, parent.join(name)).map(Node::Host),
+        }
+    }
+
+    /// Opens the node designated by `path`, relative to `dir`.
+    pub(crate) fn open(&mut self, dir: &Node, path: &str, flags: OpenFlags) -> Result<Node, Errno> {
+        let node = match self.lookup(dir, path) {
+            Ok(_) if flags.create && flags.exclusive => return Err(errno::EXIST),
+            Ok(node) => node,
+            Err(errno::NOENT) if flags.create && !flags.directory => self.create_file(dir, path)?,
+            Err(error) => return Err(error),
+        };
+
+        let type_ = self.stat(&node)?.type_;
+        if flags.directory && type_ != FileType::Dir {
+            return Err(errno::NOTDIR);
+        }
+
+        if flags.truncate {
+            self.set_size(&node, 0)?;
+        }
+
+        Ok(node)
+    }
+
+    pub(crate) fn stat(&self, node: &Node) -> Result<Stat, Errno> {
+        match node {
+            Node::Memory(inode) => Ok(match &self.inodes[*inode] {
+                Inode::File(data) => Stat {
+                    type_: FileType::File,
+                    ino: *inode as u64,
+                    size: data.len() as u64,
+                },
+                Inode::Dir(entries) => Stat {
+                    type_: FileType::Dir,
+                    ino: *inode as u64,
+                    size: entries.len() as u64,
+                },
+            }),
+
+            Node::Host(path) => {
+                let metadata = fs::metadata(path).map_err(from_io)?;
+
+                Ok(Stat {
+                    type_: if metadata.is_dir() {
+                        FileType::Dir
+                    } else {
+                        FileType::File
+                    },
+                    ino: 0,
+                    size: metadata.len(),
+                })
+            }
+        }
+    }
+
+    /// Reads the bytes of the file `node` starting at `offset` into `buf`,
+    /// and returns how many were read.
+    pub(crate) fn read(&self, node: &Node, offset: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+        match node {
+            Node::Memory(inode) => {
+                let data = self.file(*inode)?;
+                let data = data.get(offset as usize..).unwrap_or_default();
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+
+                Ok(len)
+            }
+
+            Node::Host(path) => {
+                let mut file = File::open(path).map_err(from_io)?;
+                file.seek(SeekFrom::Start(offset)).map_err(from_io)?;
+                file.read(buf).map_err(from_io)
+            }
+        }
+    }
+
+    /// Writes `data` to the file `node` at `offset`, extending it if needed.
+    pub(crate) fn write(&mut self, node: &Node, offset: u64, data: &[u8]) -> Result<(), Errno> {
+        match node {
+            Node::Memory(inode) => {
+                let file = self.file_mut(*inode)?;
+                let end = offset.saturating_add(data.len() as u64);
+                if end > MAX_FILE_SIZE {
+                    return Err(errno::FBIG);
+                }
+
+                if (file.len() as u64) < end {
+                    file.resize(end as usize, 0);
+                }
+
+                file[offset as usize..end as usize].copy_from_slice(data);
+
+                Ok(())
+            }
+
+            Node::Host(path) => {
+                let mut file = OpenOptions::new().write(true).open(path).map_err(from_io)?;
+                file.seek(SeekFrom::Start(offset)).map_err(from_io)?;
+                file.write_all(data).map_err(from_io)
+            }
+        }
+    }
+
+    /// Truncates or extends the file `node` to `size` bytes.
+    pub(crate) fn set_size(&mut self, node: &Node, size: u64) -> Result<(), Errno> {
+        match node {
+            Node::Memory(inode) => {
+                let file = self.file_mut(*inode)?;
+                if size > MAX_FILE_SIZE {
+                    return Err(errno::FBIG);
+                }
+
+                file.resize(size as usize, 0);
+
+                Ok(())
+            }
+
+            Node::Host(path) => {
+                let file = OpenOptions::new().write(true).open(path).map_err(from_io)?;
+                file.set_len(size).map_err(from_io)
+            }
+        }
+    }
+
+    /// Returns the entries of the directory `node`, sorted by name, `.` and
+    /// `..` included.
+    pub(crate) fn read_dir(&self, node: &Node) -> Result<Vec<Entry>, Errno> {
+        let dot = |name: &str| Entry {
+            name: name.to_owned(),
+            type_: FileType::Dir,
+            ino: 0,
+        };
+        let mut entries = vec![dot("."), dot("..")];
+
+        match node {
+            Node::Memory(inode) => {
+                let Inode::Dir(children) = &self.inodes[*inode] else {
+                    return Err(errno::NOTDIR);
+                };
+
+                for (name, &inode) in children {
+                    entries.push(Entry {
+                        name: name.clone(),
+                        type_: self.stat(&Node::Memory(inode))?.type_,
+                        ino: inode as u64,
+                    });
+                }
+            }
+
+            Node::Host(path) => {
+                let mut children = Vec::new();
+                for entry in fs::read_dir(path).map_err(from_io)? {
+                    let entry = entry.map_err(from_io)?;
+                    let is_dir = entry.file_type().map_err(from_io)?.is_dir();
+
+                    children.push(Entry {
+                        name: entry.file_name().to_string_lossy().into_owned(),
+                        type_: if is_dir {
+                            FileType::Dir
+                        } else {
+                            FileType::File
+                        },
+                        ino: 0,
+                    });
+                }
+
+                children.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+                entries.extend(children);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Creates the directory `path`, relative to `dir`.
+    pub(crate) fn create_dir(&mut self, dir: &Node, path: &str) -> Result<(), Errno> {
+        match self.parent(dir, path)? {
+            (_, None) => Err(errno::EXIST),
+
+            (Node::Memory(parent), Some(name)) => {
+                if self.child(parent, name).is_ok() {
+                    return Err(errno::EXIST);
+                }
+
+                let inode = self.alloc(Inode::Dir(BTreeMap::new()));
+                self.dir_mut(parent)?.insert(name.to_owned(), inode);
+
+                Ok(())
+            }
+
+            (Node::Host(parent), Some(name)) => fs::create_dir(parent.join(name)).map_err(from_io),
+        }
+    }
+
+    /// Removes the file or, if `is_dir` is set, the empty directory `path`,
+    /// relative to `dir`.
+    pub(crate) fn remove(&mut self, dir: &Node, path: &str, is_dir: bool) -> Result<(), Errno> {
+        let node = self.lookup(dir, path)?;
+        let type_ = self.stat(&node)?.type_;
+
+        match (type_, is_dir) {
+            (FileType::Dir, false) => return Err(errno::ISDIR),
+            (FileType::File, true) => return Err(errno::NOTDIR),
+            _ => {}
+        }
+
+        match self.parent(dir, path)? {
+            // `.` and `..` can't be removed.
+            (_, None) => Err(errno::INVAL),
+
+            (Node::Memory(parent), Some(name)) => {
+                if matches!(&self.inodes[self.child(parent, name)?], Inode::Dir(entries) if !entries.is_empty())
+                {
+                    return Err(errno::NOTEMPTY);
+                }
+
+                self.dir_mut(parent)?.remove(name);
+
+                Ok(())
+            }
+
+            (Node::Host(parent), Some(name)) => {
+                let path = parent.join(name);
+
+                match is_dir {
+                    true => fs::remove_dir(path).map_err(from_io),
+                    false => fs::remove_file(path).map_err(from_io),
+                }
+            }
+        }
+    }
+
+    /// Moves `path`, relative to `dir`, to `new_path`, relative to
+    /// `new_dir`, replacing what was there.
+    pub(crate) fn rename(
+        &mut self,
+        dir: &Node,
+        path: &str,
+        new_dir: &Node,
+        new_path: &str,
+    ) -> Result<(), Errno> {
+        let node = self.lookup(dir, path)?;
+
+        match (self.parent(dir, path)?, self.parent(new_dir, new_path)?) {
+            ((_, None), _) | (_, (_, None)) => Err(errno::INVAL),
+
+            ((Node::Memory(parent), Some(name)), (Node::Memory(new_parent), Some(new_name))) => {
+                let Node::Memory(inode) = node else {
+                    unreachable!("in-memory directory containing a host node")
+                };
+
+                // A directory can't be moved into itself.
+                if self.contains(inode, new_parent) {
+                    return Err(errno::INVAL);
+                }
+
+                if let Ok(existing) = self.child(new_parent, new_name) {
+                    match (&self.inodes[inode], &self.inodes[existing]) {
+                        (Inode::File(_), Inode::Dir(_)) => return Err(errno::ISDIR),
+                        (Inode::Dir(_), Inode::File(_)) => return Err(errno::NOTDIR),
+                        (Inode::Dir(_), Inode::Dir(entries)) if !entries.is_empty() => {
+                            return Err(errno::NOTEMPTY);
+                        }
+                        _ => {}
+                    }
+                }
+
+                self.dir_mut(parent)?.remove(name);
+                self.dir_mut(new_parent)?.insert(new_name.to_owned(), inode);
+
+                Ok(())
+            }
+
+            ((Node::Host(parent), Some(name)), (Node::Host(new_parent), Some(new_name))) => {
+                fs::rename(parent.join(name), new_parent.join(new_name)).map_err(from_io)
+            }
+
+            _ => Err(errno::XDEV),
+        }
+    }
+
+    /// Creates an empty file at `path`, relative to `dir`.
+    fn create_file(&mut self, dir: &Node, path: &str) -> Result<Node, Errno> {
+        match self.parent(dir, path)? {
+            (_, None) => Err(errno::ISDIR),
+
+            (Node::Memory(parent), Some(name)) => {
+                let inode = self.alloc(Inode::File(Vec::new()));
+                self.dir_mut(parent)?.insert(name.to_owned(), inode);
+
+                Ok(Node::Memory(inode))
+            }
+
+            (Node::Host(parent), Some(name)) => {
+                let path = parent.join(name);
+                File::create_new(&path).map_err(from_io)?;
+
+                Ok(Node::Host(path))
+            }
+        }
+    }
+
+    /// Resolves all the components of `path` but the last one, relative to
+    /// `dir`.
+    ///
+    /// Returns the directory containing the last component along with its
+    /// name, or the directory itself if the path ends with `.` or `..`.
+    fn parent<'p>(&self, dir: &Node, path: &'p str) -> Result<(Node, Option<&'p str>), Errno> {
+        if path.is_empty() {
+            return Err(errno::NOENT);
+        }
+
+        if path.starts_with('/') {
+            return Err(errno::NOTCAPABLE);
+        }
+
+        let mut components = path
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect::<Vec<_>>();
+        let name = match components.last() {
+            Some(&"..") | None => None,
+            Some(_) => components.pop(),
+        };
+
+        match dir {
+            Node::Memory(inode) => {
+                // The ancestors of the current directory, up to `dir`.
+                let mut ancestors = vec![*inode];
+
+                for component in components {
+                    if component == ".." {
+                        if ancestors.len() == 1 {
+                            return Err(errno::NOTCAPABLE);
+                        }
+
+                        ancestors.pop();
+                    } else {
+                        let child = self.child(*ancestors.last().unwrap(), component)?;
+                        if !matches!(self.inodes[child], Inode::Dir(_)) {
+                            return Err(errno::NOTDIR);
+                        }
+
+                        ancestors.push(child);
+                    }
+                }
+
+                Ok((Node::Memory(*ancestors.last().unwrap()), name))
+            }
+
+            Node::Host(root) => {
+                let mut parent = root.clone();
+                let mut depth = 0;
+
+                for component in components {
+                    if component == ".." {
+                        if depth == 0 {
+                            return Err(errno::NOTCAPABLE);
+                        }
+
+                        parent.pop();
+                        depth -= 1;
+                    } else {
+                        parent.push(component);
+                        depth += 1;
+                    }
+                }
+
+                let parent = confine(root, parent)?;
+                if !parent.is_dir() {
+                    return Err(errno::NOTDIR);
+                }
+
+                Ok((Node::Host(parent), name))
+            }
+        }
+    }
+
+    fn alloc(&mut self, inode: Inode) -> usize {
+        self.inodes.push(inode);
+        self.inodes.len() - 1
+    }
+
+    fn child(&self, dir: usize, name: &str) -> Result<usize, Errno> {
+        match &self.inodes[dir] {
+            Inode::Dir(entries) => entries.get(name).copied().ok_or(errno::NOENT),
+            Inode::File(_) => Err(errno::NOTDIR),
+        }
+    }
+
+    /// Whether `descendant` is `dir` or is in the subtree of `dir`.
+    fn contains(&self, dir: usize, descendant: usize) -> bool {
+        dir == descendant
+            || match &self.inodes[dir] {
+                Inode::Dir(entries) => entries
+                    .values()
+                    .any(|&child| self.contains(child, descendant)),
+                Inode::File(_) => false,
+            }
+    }
+
+    fn file(&self, inode: usize) -> Result<&Vec<u8>, Errno> {
+        match &self.inodes[inode] {
+            Inode::File(data) => Ok(data),
+            Inode::Dir(_) => Err(errno::ISDIR),
+        }
+    }
+
+    fn file_mut(&mut self, inode: usize) -> Result<&mut Vec<u8>, Errno> {
+        match &mut self.inodes[inode] {
+            Inode::File(data) => Ok(data),
+            Inode::Dir(_) => Err(errno::ISDIR),
+        }
+    }
+
+    fn dir_mut(&mut self, inode: usize) -> Result<&mut BTreeMap<String, usize>, Errno> {
+        match &mut self.inodes[inode] {
+            Inode::Dir(entries) => Ok(entries),
+            Inode::File(_) => Err(errno::NOTDIR),
+        }
+    }
+}
+
+/// Checks that `path`, once symbolic links are resolved, is still in `root`,
+/// and returns its canonical form.
+fn confine(root: &Path, path: PathBuf) -> Result<PathBuf, Errno> {
+    let root = root.canonicalize().map_err(from_io)?;
+    let path = path.canonicalize().map_err(from_io)?;
+
+    if !path.starts_with(root) {
+        return Err(errno::NOTCAPABLE);
+    }
+
+    Ok(path)
+}
+
+fn from_io(error: io::Error) -> Errno {
+    match error.kind() {
+        io::ErrorKind::NotFound => errno::NOENT,
+        io::ErrorKind::PermissionDenied => errno::ACCES,
+        io::ErrorKind::AlreadyExists => errno::EXIST,
+        io::ErrorKind::NotADirectory => errno::NOTDIR,
+        io::ErrorKind::IsADirectory => errno::ISDIR,
+        io::ErrorKind::DirectoryNotEmpty => errno::NOTEMPTY,
+        io::ErrorKind::InvalidInput => errno::INVAL,
+        _ => errno::IO,
+    }
+}
@@ -0,0 +1,155 @@
+//! Runs the `exwasmable` binary on small modules.
+
+use std::{
+    path::PathBuf,
+    process::{Command, ExitStatus},
+};
+
+const PROGRAM: &str = r#"
+    (module
+        (memory 1)
+        (func $add (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1)))
+        (func $div (param i32 i32) (result i32)
+            (i32.div_s (local.get 0) (local.get 1)))
+        (func $ratio (export "ratio") (param i32 i32) (result i32 f64)
+            (call $div (local.get 0) (local.get 1))
+            (f64.div (f64.convert_i32_s (local.get 0)) (f64.convert_i32_s (local.get 1))))
+        (func $spin (export "spin")
+            (loop $again (br $again)))
+        (func $grow (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0))))
+"#;
+
+const WASI_PROGRAM: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_read"
+            (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit"
+            (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 32) "in.txt")
+        (func (export "_start")
+            (drop (call $path_open
+                (i32.const 3) (i32.const 0) (i32.const 32) (i32.const 6)
+                (i32.const 0) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 20)))
+            (i32.store (i32.const 0) (i32.const 64))
+            (i32.store (i32.const 4) (i32.const 64))
+            (drop (call $fd_read (i32.load (i32.const 20)) (i32.const 0) (i32.const 1) (i32.const 16)))
+            (i32.store (i32.const 4) (i32.load (i32.const 16)))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16)))
+            (call $proc_exit (i32.const 7))))
+"#;
+
+fn run(args: &[&str]) -> (ExitStatus, String, String) {
+    run_program(PROGRAM, args)
+}
+
+/// The output of a run: its exit status, its standard output and its
+/// standard error.
+fn run_program(program: &str, args: &[&str]) -> (ExitStatus, String, String) {
+    // Each test writes its own copy of the module, since they run in
+    // parallel.
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "cli-{}-{}.wasm",
+        std::process::id(),
+        args.join("-").replace(['/', ' '], "_")
+    ));
+    std::fs::write(&path, wat::parse_str(program).unwrap()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_exwasmable"))
+        .args(
+            args.iter()
+                .map(|arg| arg.replace("MODULE", path.to_str().unwrap())),
+        )
+        .output()
+        .unwrap();
+
+    std::fs::remove_file(path).unwrap();
+
+    (
+        output.status,
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn prints_results() {
+    let (status, stdout, _) = run(&["MODULE", "add", "41", "1"]);
+    assert!(status.success());
+    assert_eq!(stdout, "42\n");
+
+    let (status, stdout, _) = run(&["--engine", "register", "MODULE", "ratio", "-7", "2"]);
+    assert!(status.success());
+    assert_eq!(stdout, "-3\n-3.5\n");
+}
+
+#[test]
+fn reports_traps_with_a_backtrace() {
+    let (status, _, stderr) = run(&["MODULE", "ratio", "1", "0"]);
+    assert!(!status.success());
+    assert_eq!(
+        stderr,
+        "error: trap: integer divide by zero\nbacktrace:\n  0: func[1]\n  1: ratio\n",
+    );
+}
+
+#[test]
+fn limits_fuel_and_memory() {
+    let (status, _, stderr) = run(&["--fuel", "1000", "MODULE", "spin"]);
+    assert!(!status.success());
+    assert!(stderr.starts_with("error: trap: all fuel consumed\n"));
+
+    let (_, stdout, _) = run(&["--max-memory", "4", "MODULE", "grow", "3"]);
+    assert_eq!(stdout, "1\n");
+
+    let (_, stdout, _) = run(&["--max-memory", "4", "MODULE", "grow", "4"]);
+    assert_eq!(stdout, "-1\n");
+}
+
+#[test]
+fn traces_instructions() {
+    let (status, _, stderr) = run(&["--trace", "MODULE", "add", "1", "2"]);
+    assert!(status.success());
+    assert!(stderr.lines().count() > 1);
+    assert!(stderr.lines().all(|line| line.starts_with("add @ ")));
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    let (status, _, stderr) = run(&["MODULE", "add", "1"]);
+    assert!(!status.success());
+    assert_eq!(stderr, "error: `add` expects 2 arguments, got 1\n");
+
+    let (status, _, stderr) = run(&["MODULE", "add", "1", "x"]);
+    assert!(!status.success());
+    assert_eq!(stderr, "error: invalid i32 argument `x`\n");
+
+    let (status, _, stderr) = run(&["MODULE", "sub"]);
+    assert!(!status.success());
+    assert_eq!(stderr, "error: the module exports no function `sub`\n");
+}
+
+#[test]
+fn runs_wasi_programs() {
+    let dir =
+        PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("cli-wasi-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("in.txt"), "from the host\n").unwrap();
+
+    let dir_arg = format!("{}::/data", dir.display());
+    let (status, stdout, stderr) =
+        run_program(WASI_PROGRAM, &["--wasi", "--dir", &dir_arg, "MODULE"]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+
+    // The program exits with `proc_exit`, which is not a failure.
+    assert_eq!(status.code(), Some(7));
+    assert_eq!(stdout, "from the host\n");
+    assert_eq!(stderr, "");
+}
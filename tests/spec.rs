@@ -0,0 +1,614 @@
+//! Runs the `.wast` spec scripts of `tests/spec`.
+//!
+//! Each directory of `tests/spec` holds the scripts of one proposal, `core`
+//! being the ones of the core specification. The scripts are written in the
+//! format of the upstream testsuite (<https://github.com/WebAssembly/testsuite>)
+//! and cover a small subset of it; they are not copies of its files.
+//!
+//! Scripts are never edited to make them pass. The directives that are
+//! known to fail are listed in [`EXPECTED_FAILURES`] instead, so that
+//! upstream files can be added unmodified.
+//!
+//! The upstream files are not vendored yet. Until they are, the list is
+//! empty because every directive of the local scripts passes; vendoring them
+//! should copy them as they are, and list the directives that fail there.
+//!
+//! Every script is run with each engine. The test prints the number of
+//! passed, expected to fail and failed directives of every script and
+//! proposal, and fails if any directive failed unexpectedly, or passed while
+//! it was expected to fail.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    fs,
+    iter::zip,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use exwasmable::{
+    ExternAddr, Num, Ref, RefType, Val,
+    interpreter::{Engine, EvaluationStatus, Interpreter},
+    module::{Module, ModuleError},
+    store::{InstanceAddr, InstantiationError, Store},
+};
+use wast::{
+    QuoteWat, Wast, WastArg, WastDirective, WastExecute, WastInvoke, WastRet, Wat,
+    core::{AbstractHeapType, HeapType, NanPattern, V128Pattern, WastArgCore, WastRetCore},
+    parser::ParseBuffer,
+    token::{F32, F64, Id},
+};
+
+/// The host module that the scripts import from as `spectest`.
+const SPECTEST: &str = r#"
+    (module
+        (global (export "global_i32") i32 (i32.const 666))
+        (global (export "global_i64") i64 (i64.const 666))
+        (global (export "global_f32") f32 (f32.const 666.6))
+        (global (export "global_f64") f64 (f64.const 666.6))
+        (table (export "table") 10 20 funcref)
+        (memory (export "memory") 1 2)
+        (func (export "print"))
+        (func (export "print_i32") (param i32))
+        (func (export "print_i64") (param i64))
+        (func (export "print_f32") (param f32))
+        (func (export "print_f64") (param f64))
+        (func (export "print_i32_f32") (param i32 f32))
+        (func (export "print_f64_f64") (param f64 f64)))
+"#;
+
+/// The directives that are known to fail with both engines, as
+/// `proposal/script.wast:line`, along with the reason why.
+const EXPECTED_FAILURES: &[(&str, &str)] = &[];
+
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    /// The number of directives that failed as expected.
+    expected: usize,
+    /// The location and reason of each failed directive.
+    failures: Vec<String>,
+}
+
+/// The engines the scripts are run with.
+const ENGINES: [Engine; 2] = [Engine::Stack, Engine::Register];
+
+#[test]
+fn spec_testsuite() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+
+    let mut proposals = BTreeMap::<String, BTreeMap<String, Vec<Report>>>::new();
+    for proposal in fs::read_dir(&root).unwrap() {
+        let proposal = proposal.unwrap().path();
+        let name = proposal.file_name().unwrap().to_string_lossy().into_owned();
+
+        let scripts = proposals.entry(name).or_default();
+        for script in fs::read_dir(&proposal).unwrap() {
+            let script = script.unwrap().path();
+            if script.extension().is_some_and(|ext| ext == "wast") {
+                let name = script.file_name().unwrap().to_string_lossy().into_owned();
+                let reports = ENGINES
+                    .iter()
+                    .map(|engine| run_script(&script, *engine))
+                    .collect();
+                scripts.insert(name, reports);
+            }
+        }
+    }
+
+    print!("{:<32}", "");
+    for engine in ENGINES {
+        print!(" {:>26}", format!("{engine:?}").to_lowercase());
+    }
+    print!("\n{:<32}", "script");
+    for _ in ENGINES {
+        print!(" {:>8} {:>8} {:>8}", "passed", "expected", "failed");
+    }
+    println!();
+
+    let mut failures = Vec::new();
+    for (proposal, scripts) in &proposals {
+        print!("{proposal:<32}");
+        for engine in 0..ENGINES.len() {
+            let reports = scripts.values().map(|reports| &reports[engine]);
+            let passed = reports.clone().map(|report| report.passed).sum::<usize>();
+            let expected = reports.clone().map(|report| report.expected).sum::<usize>();
+            let failed = reports.map(|report| report.failures.len()).sum::<usize>();
+            print!(" {passed:>8} {expected:>8} {failed:>8}");
+        }
+        println!();
+
+        for (script, reports) in scripts {
+            print!("  {script:<30}");
+            for (engine, report) in zip(ENGINES, reports) {
+                print!(
+                    " {:>8} {:>8} {:>8}",
+                    report.passed,
+                    report.expected,
+                    report.failures.len()
+                );
+
+                failures.extend(
+                    report.failures.iter().map(|failure| {
+                        format!("{proposal}/{script}:{failure} ({engine:?} engine)")
+                    }),
+                );
+            }
+            println!();
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "failed directives:\n{}",
+        failures.join("\n")
+    );
+}
+
+fn run_script(path: &Path, engine: Engine) -> Report {
+    let proposal = path
+        .parent()
+        .unwrap()
+        .file_name()
+        .unwrap()
+        .to_string_lossy();
+    let script = path.file_name().unwrap().to_string_lossy();
+    let contents = fs::read_to_string(path).unwrap();
+    let mut report = Report::default();
+
+    let buffer = ParseBuffer::new(&contents).unwrap();
+    let wast = match wast::parser::parse::<Wast>(&buffer) {
+        Ok(wast) => wast,
+        Err(error) => {
+            report.failures.push(format!("0:0: {}", error.message()));
+            return report;
+        }
+    };
+
+    let mut runner = Runner::new(engine);
+
+    // Panics are failures of the directive that caused them, and are
+    // reported as such rather than printed.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    for directive in wast.directives {
+        let (line, col) = directive.span().linecol_in(&contents);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| runner.run(directive)))
+            .unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("unknown panic");
+                Err(format!("panicked: {message}"))
+            });
+
+        let location = format!("{proposal}/{script}:{}", line + 1);
+        let expected = EXPECTED_FAILURES
+            .iter()
+            .any(|(failure, _)| *failure == location);
+
+        match (result, expected) {
+            (Ok(()), false) => report.passed += 1,
+            (Err(_), true) => report.expected += 1,
+            (Ok(()), true) => report.failures.push(format!(
+                "{}:{}: passed, but is listed as an expected failure",
+                line + 1,
+                col + 1
+            )),
+            (Err(reason), false) => {
+                report
+                    .failures
+                    .push(format!("{}:{}: {reason}", line + 1, col + 1))
+            }
+        }
+    }
+
+    panic::set_hook(hook);
+
+    report
+}
+
+/// The state of a script.
+struct Runner {
+    store: Store,
+    engine: Engine,
+    /// The instances that have a name in the script.
+    instances: HashMap<String, InstanceAddr>,
+    /// The modules that are defined but not instantiated, by name.
+    definitions: HashMap<String, Vec<u8>>,
+    /// The last instantiated module.
+    current: Option<InstanceAddr>,
+}
+
+type Outcome = Result<(), String>;
+
+/// Why a module couldn't be instantiated.
+enum Failure {
+    /// The module couldn't be decoded.
+    Malformed(String),
+    Invalid(String),
+    Unlinkable(String),
+    Trap,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Malformed(error) => write!(f, "malformed module: {error}"),
+            Failure::Invalid(error) => error.fmt(f),
+            Failure::Unlinkable(error) => error.fmt(f),
+            Failure::Trap => write!(f, "instantiation trapped"),
+        }
+    }
+}
+
+impl Runner {
+    fn new(engine: Engine) -> Runner {
+        let mut store = Store::default();
+        let spectest = Module::from_bytes(&wat::parse_str(SPECTEST).unwrap()).unwrap();
+        let spectest = store.instantiate(&spectest).unwrap();
+        store.register("spectest", spectest);
+
+        Runner {
+            store,
+            engine,
+            instances: HashMap::new(),
+            definitions: HashMap::new(),
+            current: None,
+        }
+    }
+
+    fn run(&mut self, directive: WastDirective) -> Outcome {
+        match directive {
+            WastDirective::Module(mut module) => {
+                let name = quote_name(&module);
+                let bytes = module.encode().map_err(|error| error.to_string())?;
+                let instance = self
+                    .instantiate(&bytes)
+                    .map_err(|error| error.to_string())?;
+                self.define(name, instance);
+
+                Ok(())
+            }
+
+            WastDirective::ModuleDefinition(mut module) => {
+                let name = quote_name(&module).ok_or("anonymous module definition")?;
+                let bytes = module.encode().map_err(|error| error.to_string())?;
+                self.definitions.insert(name, bytes);
+
+                Ok(())
+            }
+
+            WastDirective::ModuleInstance {
+                instance, module, ..
+            } => {
+                let module = module.ok_or("anonymous module instance")?;
+                let bytes = self.definitions[module.name()].clone();
+                let addr = self
+                    .instantiate(&bytes)
+                    .map_err(|error| error.to_string())?;
+                self.define(instance.map(|id| id.name().to_owned()), addr);
+
+                Ok(())
+            }
+
+            WastDirective::Register { name, module, .. } => {
+                let instance = self.instance(module)?;
+                self.store.register(name, instance);
+
+                Ok(())
+            }
+
+            WastDirective::Invoke(invoke) => self.invoke(invoke).map(drop),
+
+            WastDirective::AssertReturn { exec, results, .. } => {
+                let values = match exec {
+                    WastExecute::Invoke(invoke) => self.invoke(invoke)?,
+                    WastExecute::Get { module, global, .. } => {
+                        let instance = self.instance(module)?;
+                        let value = self
+                            .store
+                            .global(instance, global)
+                            .ok_or_else(|| format!("unknown global `{global}`"))?;
+                        vec![value]
+                    }
+                    WastExecute::Wat(_) => return Err("unsupported assertion".to_owned()),
+                };
+
+                check_results(&values, &results)
+            }
+
+            WastDirective::AssertTrap { exec, .. } => match exec {
+                WastExecute::Invoke(invoke) => self.assert_trap(invoke),
+                WastExecute::Wat(mut module) => {
+                    let bytes = module.encode().map_err(|error| error.to_string())?;
+                    match self.instantiate(&bytes) {
+                        Err(Failure::Trap) => Ok(()),
+                        Err(error) => Err(format!("expected a trap, got `{error}`")),
+                        Ok(_) => Err("expected a trap".to_owned()),
+                    }
+                }
+                WastExecute::Get { .. } => Err("unsupported assertion".to_owned()),
+            },
+
+            WastDirective::AssertExhaustion { call, .. } => self.assert_trap(call),
+
+            WastDirective::AssertException { exec, .. } => match exec {
+                WastExecute::Invoke(invoke) => self.assert_exception(invoke),
+                _ => Err("unsupported assertion".to_owned()),
+            },
+
+            WastDirective::AssertMalformed { mut module, .. } => {
+                // Malformed text modules are rejected by the text parser.
+                let Ok(bytes) = module.encode() else {
+                    return Ok(());
+                };
+
+                match self.instantiate(&bytes) {
+                    Err(Failure::Malformed(_)) => Ok(()),
+                    Err(error) => Err(format!("expected a malformed module, got `{error}`")),
+                    Ok(_) => Err("expected a malformed module".to_owned()),
+                }
+            }
+
+            WastDirective::AssertInvalid { mut module, .. } => {
+                let bytes = module.encode().map_err(|error| error.to_string())?;
+
+                match self.instantiate(&bytes) {
+                    Err(Failure::Invalid(_)) => Ok(()),
+                    Err(error) => Err(format!("expected an invalid module, got `{error}`")),
+                    Ok(_) => Err("expected an invalid module".to_owned()),
+                }
+            }
+
+            WastDirective::AssertUnlinkable { mut module, .. } => {
+                let bytes = module.encode().map_err(|error| error.to_string())?;
+
+                match self.instantiate(&bytes) {
+                    Err(Failure::Unlinkable(_)) => Ok(()),
+                    Err(error) => Err(format!("expected an unlinkable module, got `{error}`")),
+                    Ok(_) => Err("expected an unlinkable module".to_owned()),
+                }
+            }
+
+            _ => Err("unsupported directive".to_owned()),
+        }
+    }
+
+    fn instantiate(&mut self, bytes: &[u8]) -> Result<InstanceAddr, Failure> {
+        let module = Module::from_bytes(bytes).map_err(|error| match error {
+            ModuleError::Malformed(error) => Failure::Malformed(error.to_string()),
+            error => Failure::Invalid(error.to_string()),
+        })?;
+
+        self.store
+            .instantiate(&module)
+            .map_err(|error| match error {
+                InstantiationError::Unlinkable(error) => Failure::Unlinkable(error),
+                InstantiationError::Trap(_)
+                | InstantiationError::Exit(_)
+                | InstantiationError::Exception(_) => Failure::Trap,
+                error @ InstantiationError::ResourceLimit(_) => {
+                    Failure::Unlinkable(error.to_string())
+                }
+            })
+    }
+
+    fn define(&mut self, name: Option<String>, instance: InstanceAddr) {
+        if let Some(name) = name {
+            self.instances.insert(name, instance);
+        }
+
+        self.current = Some(instance);
+    }
+
+    fn instance(&self, name: Option<Id>) -> Result<InstanceAddr, String> {
+        match name {
+            Some(name) => self
+                .instances
+                .get(name.name())
+                .copied()
+                .ok_or_else(|| format!("unknown module `{}`", name.name())),
+            None => self.current.ok_or_else(|| "no module".to_owned()),
+        }
+    }
+
+    fn invoke(&mut self, invoke: WastInvoke) -> Result<Vec<Val>, String> {
+        let instance = self.instance(invoke.module)?;
+        let args = invoke.args.iter().map(arg).collect::<Result<Vec<_>, _>>()?;
+
+        let mut interpreter = Interpreter::with_engine(&mut self.store, self.engine);
+        match interpreter.run_in(instance, invoke.name, args) {
+            Ok(EvaluationStatus::Value(values)) => Ok(values),
+            Ok(EvaluationStatus::Exited(code)) => {
+                Err(format!("`{}` exited with code {code}", invoke.name))
+            }
+            Ok(EvaluationStatus::Exception(_)) => {
+                Err(format!("`{}` threw an exception", invoke.name))
+            }
+            Err(_) => Err(format!("`{}` trapped", invoke.name)),
+        }
+    }
+
+    fn assert_exception(&mut self, invoke: WastInvoke) -> Outcome {
+        let instance = self.instance(invoke.module)?;
+        let args = invoke.args.iter().map(arg).collect::<Result<Vec<_>, _>>()?;
+
+        let mut interpreter = Interpreter::with_engine(&mut self.store, self.engine);
+        match interpreter.run_in(instance, invoke.name, args) {
+            Ok(EvaluationStatus::Exception(_)) => Ok(()),
+            _ => Err(format!("expected `{}` to throw an exception", invoke.name)),
+        }
+    }
+
+    fn assert_trap(&mut self, invoke: WastInvoke) -> Outcome {
+        let instance = self.instance(invoke.module)?;
+        let args = invoke.args.iter().map(arg).collect::<Result<Vec<_>, _>>()?;
+
+        let mut interpreter = Interpreter::with_engine(&mut self.store, self.engine);
+        match interpreter.run_in(instance, invoke.name, args) {
+            Ok(_) => Err(format!("expected `{}` to trap", invoke.name)),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+fn quote_name(module: &QuoteWat) -> Option<String> {
+    match module {
+        QuoteWat::Wat(Wat::Module(module)) => module.id.map(|id| id.name().to_owned()),
+        _ => None,
+    }
+}
+
+fn arg(arg: &WastArg) -> Result<Val, String> {
+    let WastArg::Core(arg) = arg else {
+        return Err("unsupported argument".to_owned());
+    };
+
+    let val = match arg {
+        WastArgCore::I32(val) => Val::from(*val),
+        WastArgCore::I64(val) => Val::from(*val),
+        WastArgCore::F32(val) => Val::from(f32::from_bits(val.bits)),
+        WastArgCore::F64(val) => Val::from(f64::from_bits(val.bits)),
+        WastArgCore::V128(val) => Val::V128(u128::from_le_bytes(val.to_le_bytes())),
+        WastArgCore::RefNull(type_) => Val::Ref(Ref::Null(ref_type(type_)?)),
+        // Host references are external references converted to the `any`
+        // hierarchy, which keeps their cell as it is.
+        WastArgCore::RefExtern(addr) | WastArgCore::RefHost(addr) => {
+            Val::Ref(Ref::Extern(ExternAddr(*addr)))
+        }
+    };
+
+    Ok(val)
+}
+
+// Returns the type of the null references of type `type_`, which are typed
+// by the top type of their hierarchy.
+fn ref_type(type_: &HeapType) -> Result<RefType, String> {
+    let HeapType::Abstract { ty, .. } = type_ else {
+        // Null references of a concrete type are assumed to be null function
+        // references.
+        return Ok(RefType::FUNCREF);
+    };
+
+    match ty {
+        AbstractHeapType::Func | AbstractHeapType::NoFunc => Ok(RefType::FUNCREF),
+        AbstractHeapType::Extern | AbstractHeapType::NoExtern => Ok(RefType::EXTERNREF),
+        AbstractHeapType::Exn | AbstractHeapType::NoExn => Ok(RefType::EXNREF),
+        AbstractHeapType::Any
+        | AbstractHeapType::Eq
+        | AbstractHeapType::I31
+        | AbstractHeapType::Struct
+        | AbstractHeapType::Array
+        | AbstractHeapType::None => Ok(RefType::ANYREF),
+        _ => Err(format!("unsupported reference type `{type_:?}`")),
+    }
+}
+
+fn check_results(values: &[Val], expected: &[WastRet]) -> Outcome {
+    if values.len() != expected.len() {
+        return Err(format!(
+            "expected {} results, got {values:?}",
+            expected.len()
+        ));
+    }
+
+    for (value, expected) in values.iter().zip(expected) {
+        let WastRet::Core(expected) = expected else {
+            return Err("unsupported result".to_owned());
+        };
+
+        if !matches_result(value, expected) {
+            return Err(format!("expected `{expected:?}`, got `{value:?}`"));
+        }
+    }
+
+    Ok(())
+}
+
+fn matches_f32(val: f32, expected: &NanPattern<F32>) -> bool {
+    let bits = val.to_bits();
+    match expected {
+        NanPattern::CanonicalNan => bits & 0x7fff_ffff == 0x7fc0_0000,
+        NanPattern::ArithmeticNan => bits & 0x7fc0_0000 == 0x7fc0_0000,
+        NanPattern::Value(expected) => bits == expected.bits,
+    }
+}
+
+fn matches_f64(val: f64, expected: &NanPattern<F64>) -> bool {
+    let bits = val.to_bits();
+    match expected {
+        NanPattern::CanonicalNan => bits & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000,
+        NanPattern::ArithmeticNan => bits & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000,
+        NanPattern::Value(expected) => bits == expected.bits,
+    }
+}
+
+fn matches_result(value: &Val, expected: &WastRetCore) -> bool {
+    match (value, expected) {
+        (Val::Num(Num::I32(val)), WastRetCore::I32(expected)) => val == expected,
+        (Val::Num(Num::I64(val)), WastRetCore::I64(expected)) => val == expected,
+
+        (Val::Num(Num::F32(val)), WastRetCore::F32(expected)) => matches_f32(*val, expected),
+        (Val::Num(Num::F64(val)), WastRetCore::F64(expected)) => matches_f64(*val, expected),
+
+        (Val::V128(val), WastRetCore::V128(expected)) => {
+            let bytes = val.to_le_bytes();
+            // The lanes of the result, as `N`-byte integers.
+            fn lanes<const N: usize>(bytes: &[u8; 16]) -> impl Iterator<Item = [u8; N]> {
+                bytes.chunks_exact(N).map(|lane| lane.try_into().unwrap())
+            }
+
+            match expected {
+                V128Pattern::I8x16(expected) => {
+                    zip(lanes(&bytes), expected).all(|(lane, e)| i8::from_le_bytes(lane) == *e)
+                }
+                V128Pattern::I16x8(expected) => {
+                    zip(lanes(&bytes), expected).all(|(lane, e)| i16::from_le_bytes(lane) == *e)
+                }
+                V128Pattern::I32x4(expected) => {
+                    zip(lanes(&bytes), expected).all(|(lane, e)| i32::from_le_bytes(lane) == *e)
+                }
+                V128Pattern::I64x2(expected) => {
+                    zip(lanes(&bytes), expected).all(|(lane, e)| i64::from_le_bytes(lane) == *e)
+                }
+                V128Pattern::F32x4(expected) => zip(lanes(&bytes), expected)
+                    .all(|(lane, e)| matches_f32(f32::from_le_bytes(lane), e)),
+                V128Pattern::F64x2(expected) => zip(lanes(&bytes), expected)
+                    .all(|(lane, e)| matches_f64(f64::from_le_bytes(lane), e)),
+            }
+        }
+
+        (Val::Ref(Ref::Null(type_)), WastRetCore::RefNull(expected)) => expected
+            .as_ref()
+            .is_none_or(|expected| ref_type(expected) == Ok(*type_)),
+
+        (Val::Ref(Ref::Extern(addr)), WastRetCore::RefExtern(Some(expected))) => {
+            addr.0 == *expected
+        }
+        (Val::Ref(Ref::Extern(addr)), WastRetCore::RefHost(expected)) => addr.0 == *expected,
+
+        // References of the `any` hierarchy converted to external references
+        // keep their kind.
+        (
+            Val::Ref(Ref::Extern(_) | Ref::I31(_) | Ref::Struct(_) | Ref::Array(_)),
+            WastRetCore::RefExtern(None) | WastRetCore::RefAny,
+        ) => true,
+        (Val::Ref(Ref::I31(_) | Ref::Struct(_) | Ref::Array(_)), WastRetCore::RefEq) => true,
+        (Val::Ref(Ref::I31(_)), WastRetCore::RefI31) => true,
+        (Val::Ref(Ref::Struct(_)), WastRetCore::RefStruct) => true,
+        (Val::Ref(Ref::Array(_)), WastRetCore::RefArray) => true,
+
+        (Val::Ref(Ref::Func(_)), WastRetCore::RefFunc(_)) => true,
+
+        (value, WastRetCore::Either(expected)) => expected
+            .iter()
+            .any(|expected| matches_result(value, expected)),
+
+        _ => false,
+    }
+}
@@ -0,0 +1,55 @@
+;; Decoding of the binary format
+
+(module binary "\00asm" "\01\00\00\00")
+(module binary "\00asm\01\00\00\00")
+
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\07\01\60\02\7f\7f\01\7f"             ;; type section: (i32 i32) -> i32
+  "\03\02\01\00"                            ;; function section
+  "\07\07\01\03add\00\00"                   ;; export section: "add"
+  "\0a\09\01\07\00\20\00\20\01\6a\0b"       ;; code section
+)
+(assert_return (invoke "add" (i32.const 40) (i32.const 2)) (i32.const 42))
+
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\01") "unexpected end")
+(assert_malformed (module binary "\00as") "unexpected end")
+(assert_malformed (module binary "asm\00") "magic header not detected")
+(assert_malformed (module binary "\00asm\00\00\00\01") "unknown binary version")
+(assert_malformed (module binary "\00asm\02\00\00\00") "unknown binary version")
+(assert_malformed (module binary "\00asm" "\01\00\00\00" "\01\07\01\60\02\7f\7f\01") "unexpected end")
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"                    ;; type section
+    "\03\02\01\00"                          ;; function section
+    "\0a\04\01\02\00\ff"                    ;; code section: unknown opcode 0xff
+  )
+  "illegal opcode"
+)
+(assert_malformed
+  (module quote "(func (i32.const 0x1_0000_0000) (drop))")
+  "i32 constant out of range"
+)
+(assert_malformed
+  (module quote "(func $f) (func $f)")
+  "duplicate func"
+)
+
+(assert_invalid
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"                    ;; type section
+    "\03\02\01\00"                          ;; function section
+  )
+  "function and code section have inconsistent lengths"
+)
+(assert_invalid
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\03\02\01\00"                          ;; function section referring to type 0
+    "\0a\04\01\02\00\0b"                    ;; code section
+  )
+  "unknown type"
+)
@@ -0,0 +1,110 @@
+;; Bulk memory and table instructions, and passive segments
+
+(module
+  (memory 1)
+  (data $d0 "\aa\bb\cc\dd")
+  (data $d1 "\01\02\03")
+
+  (func (export "fill") (param i32 i32 i32) (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy") (param i32 i32 i32) (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "init") (param i32 i32 i32) (memory.init $d0 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "drop") (data.drop $d0))
+  (func (export "load8_u") (param i32) (result i32) (i32.load8_u (local.get 0)))
+)
+
+(invoke "fill" (i32.const 1) (i32.const 0xff) (i32.const 3))
+(assert_return (invoke "load8_u" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "load8_u" (i32.const 1)) (i32.const 0xff))
+(assert_return (invoke "load8_u" (i32.const 3)) (i32.const 0xff))
+(assert_return (invoke "load8_u" (i32.const 4)) (i32.const 0))
+(invoke "fill" (i32.const 0) (i32.const 0xbbccdd) (i32.const 2))
+(assert_return (invoke "load8_u" (i32.const 1)) (i32.const 0xdd))
+(assert_return (invoke "fill" (i32.const 0x10000) (i32.const 0) (i32.const 0)))
+(assert_trap (invoke "fill" (i32.const 0x10001) (i32.const 0) (i32.const 0)) "out of bounds memory access")
+(assert_trap (invoke "fill" (i32.const 0xff00) (i32.const 1) (i32.const 0x101)) "out of bounds memory access")
+;; A trapping fill doesn't write anything.
+(assert_return (invoke "load8_u" (i32.const 0xff00)) (i32.const 0))
+
+(invoke "init" (i32.const 10) (i32.const 1) (i32.const 3))
+(assert_return (invoke "load8_u" (i32.const 9)) (i32.const 0))
+(assert_return (invoke "load8_u" (i32.const 10)) (i32.const 0xbb))
+(assert_return (invoke "load8_u" (i32.const 12)) (i32.const 0xdd))
+(assert_return (invoke "load8_u" (i32.const 13)) (i32.const 0))
+(assert_trap (invoke "init" (i32.const 0) (i32.const 2) (i32.const 3)) "out of bounds memory access")
+(assert_trap (invoke "init" (i32.const 0xffff) (i32.const 0) (i32.const 2)) "out of bounds memory access")
+(assert_return (invoke "init" (i32.const 0) (i32.const 4) (i32.const 0)))
+
+(invoke "copy" (i32.const 11) (i32.const 10) (i32.const 3))
+(assert_return (invoke "load8_u" (i32.const 11)) (i32.const 0xbb))
+(assert_return (invoke "load8_u" (i32.const 12)) (i32.const 0xcc))
+(assert_return (invoke "load8_u" (i32.const 13)) (i32.const 0xdd))
+(invoke "copy" (i32.const 10) (i32.const 11) (i32.const 3))
+(assert_return (invoke "load8_u" (i32.const 10)) (i32.const 0xbb))
+(assert_return (invoke "load8_u" (i32.const 11)) (i32.const 0xcc))
+(assert_return (invoke "load8_u" (i32.const 12)) (i32.const 0xdd))
+(assert_trap (invoke "copy" (i32.const 0xfffe) (i32.const 0) (i32.const 3)) "out of bounds memory access")
+(assert_trap (invoke "copy" (i32.const 0) (i32.const 0xfffe) (i32.const 3)) "out of bounds memory access")
+(assert_return (invoke "copy" (i32.const 0x10000) (i32.const 0x10000) (i32.const 0)))
+
+(invoke "drop")
+(assert_trap (invoke "init" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds memory access")
+(assert_return (invoke "init" (i32.const 0) (i32.const 0) (i32.const 0)))
+(invoke "drop")
+
+(module
+  (table $t 4 funcref)
+  (table $u 4 funcref)
+  (elem $e func $zero $one $two)
+  (elem $declared declare func $zero)
+  (func $zero (result i32) (i32.const 0))
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+
+  (func (export "init") (param i32 i32 i32) (table.init $t $e (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "drop") (elem.drop $e))
+  (func (export "copy") (param i32 i32 i32) (table.copy $t $t (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy-to-u") (param i32 i32 i32) (table.copy $u $t (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "call-t") (param i32) (result i32) (call_indirect $t (result i32) (local.get 0)))
+  (func (export "call-u") (param i32) (result i32) (call_indirect $u (result i32) (local.get 0)))
+  (func (export "ref-func") (result funcref) (ref.func $zero))
+)
+
+(assert_trap (invoke "call-t" (i32.const 0)) "uninitialized element")
+(invoke "init" (i32.const 1) (i32.const 0) (i32.const 3))
+(assert_return (invoke "call-t" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "call-t" (i32.const 3)) (i32.const 2))
+(assert_trap (invoke "init" (i32.const 2) (i32.const 0) (i32.const 3)) "out of bounds table access")
+(assert_trap (invoke "init" (i32.const 0) (i32.const 2) (i32.const 2)) "out of bounds table access")
+(invoke "copy" (i32.const 0) (i32.const 1) (i32.const 3))
+(assert_return (invoke "call-t" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "call-t" (i32.const 2)) (i32.const 2))
+(assert_return (invoke "call-t" (i32.const 3)) (i32.const 2))
+(invoke "copy-to-u" (i32.const 2) (i32.const 0) (i32.const 2))
+(assert_return (invoke "call-u" (i32.const 3)) (i32.const 1))
+(assert_trap (invoke "call-u" (i32.const 0)) "uninitialized element")
+(assert_trap (invoke "copy-to-u" (i32.const 3) (i32.const 0) (i32.const 2)) "out of bounds table access")
+(invoke "drop")
+(assert_trap (invoke "init" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds table access")
+(assert_return (invoke "init" (i32.const 0) (i32.const 0) (i32.const 0)))
+(assert_return (invoke "ref-func") (ref.func))
+
+(assert_invalid
+  (module (func (data.drop 0)))
+  "unknown data segment"
+)
+(assert_invalid
+  (module (memory 1) (data "") (func (memory.init 1 (i32.const 0) (i32.const 0) (i32.const 0))))
+  "unknown data segment"
+)
+(assert_invalid
+  (module (table 1 funcref) (func (elem.drop 0)))
+  "unknown elem segment"
+)
+(assert_invalid
+  (module (table 1 funcref) (elem externref) (func (table.init 0 (i32.const 0) (i32.const 0) (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (table 1 funcref) (table 1 externref) (func (table.copy 0 1 (i32.const 0) (i32.const 0) (i32.const 0))))
+  "type mismatch"
+)
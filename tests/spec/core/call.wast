@@ -0,0 +1,119 @@
+;; Direct and indirect calls
+
+(module
+  (type $proc (func))
+  (type $out-i32 (func (result i32)))
+  (type $i64-i64 (func (param i64) (result i64)))
+
+  (table funcref (elem $const-i32 $fac $id-i64 $const-i32-dup $trap))
+
+  (func $const-i32 (type $out-i32) (i32.const 0x132))
+  (func $const-i32-dup (result i32) (i32.const 0x133))
+  (func $id-i64 (type $i64-i64) (local.get 0))
+  (func $trap (type $proc) (unreachable))
+
+  (func $fac (export "fac") (type $i64-i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (i64.const 1))
+      (else (i64.mul (local.get 0) (call $fac (i64.sub (local.get 0) (i64.const 1)))))))
+
+  (func $fac-acc (param i64 i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (local.get 1))
+      (else (call $fac-acc (i64.sub (local.get 0) (i64.const 1)) (i64.mul (local.get 0) (local.get 1))))))
+  (func (export "fac-acc") (param i64) (result i64) (call $fac-acc (local.get 0) (i64.const 1)))
+
+  (func $even (export "even") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 44))
+      (else (call $odd (i32.sub (local.get 0) (i32.const 1))))))
+  (func $odd (export "odd") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 99))
+      (else (call $even (i32.sub (local.get 0) (i32.const 1))))))
+
+  (func $runaway (export "runaway") (call $runaway))
+  (func $mutual-a (export "mutual-runaway") (call $mutual-b))
+  (func $mutual-b (call $mutual-a))
+  (func $deep-frames (export "deep-frames") (param i32) (result i32) (local i64 i64 i64 i64 i64 i64 i64 i64)
+    (call $deep-frames (i32.add (local.get 0) (i32.const 1))))
+
+  (func $many-args (param i32 i64 f32 f64 i32 i64) (result f64)
+    (f64.add
+      (f64.convert_i32_s (local.get 0))
+      (f64.add
+        (f64.convert_i64_s (local.get 1))
+        (f64.add
+          (f64.promote_f32 (local.get 2))
+          (f64.add
+            (local.get 3)
+            (f64.add (f64.convert_i32_s (local.get 4)) (f64.convert_i64_s (local.get 5))))))))
+  (func (export "many-args") (result f64)
+    (call $many-args (i32.const 1) (i64.const 2) (f32.const 3) (f64.const 4) (i32.const 5) (i64.const 6)))
+
+  (func (export "dispatch") (param i32 i64) (result i64)
+    (call_indirect (type $i64-i64) (local.get 1) (local.get 0)))
+  (func (export "dispatch-i32") (param i32) (result i32)
+    (call_indirect (type $out-i32) (local.get 0)))
+  (func (export "dispatch-proc") (param i32)
+    (call_indirect (type $proc) (local.get 0)))
+)
+
+(assert_return (invoke "fac" (i64.const 0)) (i64.const 1))
+(assert_return (invoke "fac" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "fac" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "fac-acc" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "even" (i32.const 0)) (i32.const 44))
+(assert_return (invoke "even" (i32.const 77)) (i32.const 99))
+(assert_return (invoke "even" (i32.const 100)) (i32.const 44))
+(assert_return (invoke "odd" (i32.const 200)) (i32.const 99))
+(assert_return (invoke "many-args") (f64.const 21))
+
+(assert_exhaustion (invoke "runaway") "call stack exhausted")
+(assert_exhaustion (invoke "mutual-runaway") "call stack exhausted")
+(assert_exhaustion (invoke "deep-frames" (i32.const 0)) "call stack exhausted")
+
+(assert_return (invoke "dispatch" (i32.const 1) (i64.const 5)) (i64.const 120))
+(assert_return (invoke "dispatch" (i32.const 2) (i64.const 5)) (i64.const 5))
+(assert_return (invoke "dispatch-i32" (i32.const 0)) (i32.const 0x132))
+(assert_return (invoke "dispatch-i32" (i32.const 3)) (i32.const 0x133))
+(assert_trap (invoke "dispatch" (i32.const 0) (i64.const 2)) "indirect call type mismatch")
+(assert_trap (invoke "dispatch" (i32.const 5) (i64.const 2)) "undefined element")
+(assert_trap (invoke "dispatch" (i32.const -1) (i64.const 2)) "undefined element")
+(assert_trap (invoke "dispatch-proc" (i32.const 4)) "unreachable")
+
+;; After the traps, the interpreter is still usable.
+(assert_return (invoke "fac" (i64.const 3)) (i64.const 6))
+
+(module
+  (type $t (func (result i32)))
+  (table 3 funcref)
+  (elem (i32.const 1) $f)
+  (func $f (type $t) (i32.const 7))
+  (func (export "call") (param i32) (result i32) (call_indirect (type $t) (local.get 0)))
+)
+
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+(assert_return (invoke "call" (i32.const 1)) (i32.const 7))
+(assert_trap (invoke "call" (i32.const 3)) "undefined element")
+
+(assert_invalid
+  (module (func $arity (call 1)) (func (param i32)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $unknown-func (call 1)))
+  "unknown function"
+)
+(assert_invalid
+  (module (type (func)) (func (call_indirect (type 0) (i32.const 0))))
+  "unknown table"
+)
+(assert_invalid
+  (module (type (func)) (table 0 funcref) (func (call_indirect (type 1) (i32.const 0))))
+  "unknown type"
+)
+(assert_invalid
+  (module (type (func)) (table 0 funcref) (func (call_indirect (type 0) (i64.const 0))))
+  "type mismatch"
+)
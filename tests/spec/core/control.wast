@@ -0,0 +1,253 @@
+;; Blocks, loops, conditionals and branches
+
+(module
+  (func (export "block-value") (result i32)
+    (block (result i32) (i32.const 1) (br 0 (i32.const 7)) (drop) (i32.const 2)))
+  (func (export "nested-br") (param i32) (result i32)
+    (block $outer (result i32)
+      (block $inner (result i32)
+        (drop (br_if $outer (i32.const 10) (local.get 0)))
+        (i32.const 20))
+      (i32.const 1)
+      (i32.add)))
+  (func (export "loop-sum") (param $n i32) (result i32) (local $acc i32)
+    (loop $again
+      (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+      (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $again))
+    (local.get $acc))
+  (func (export "if-else") (param i32) (result i32)
+    (if (result i32) (local.get 0) (then (i32.const 1)) (else (i32.const 2))))
+  (func (export "if-no-else") (param i32) (result i32) (local $r i32)
+    (local.set $r (i32.const 5))
+    (if (local.get 0) (then (local.set $r (i32.const 6))))
+    (local.get $r))
+  (func (export "br_table") (param i32) (result i32)
+    (block $3
+      (block $2
+        (block $1
+          (block $0
+            (br_table $0 $1 $2 $3 (local.get 0)))
+          (return (i32.const 100)))
+        (return (i32.const 101)))
+      (return (i32.const 102)))
+    (i32.const 103))
+  (func (export "br_table-value") (param i32) (result i32)
+    (block $b (result i32)
+      (block $a (result i32)
+        (br_table $a $b $a (i32.const 50) (local.get 0)))
+      (i32.add (i32.const 1))))
+  (func (export "return-from-loop") (result i32)
+    (loop (return (i32.const 9)))
+    (i32.const 0))
+  (func (export "unwind-block") (result i32)
+    (block (i32.const 1) (i32.const 2) (br 0))
+    (i32.const 3))
+  (func (export "unwind-loop") (param i32) (result i32)
+    (block $exit (result i32)
+      (loop $l
+        (i32.const 8) (i32.const 9)
+        (drop)
+        (br_if $exit (local.get 0))
+        (drop)
+        (local.set 0 (i32.const 1))
+        (br $l))
+      (i32.const 0)))
+  (func (export "select-i32") (param i32 i32 i32) (result i32)
+    (select (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "select-f64") (param f64 f64 i32) (result f64)
+    (select (result f64) (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "nop") (result i32)
+    (nop) (i32.const 1) (nop) (nop))
+  (func (export "unreachable-after-br") (result i32)
+    (block (result i32) (br 0 (i32.const 3)) (unreachable)))
+  (func (export "dead-code") (result i32)
+    (return (i32.const 4))
+    (i32.add))
+  (func (export "local-tee") (param i32) (result i32) (local i64)
+    (i32.add (local.tee 0 (i32.const 3)) (local.get 0)))
+  (func (export "locals-zeroed") (result i64) (local i32 i64 f32 f64)
+    (local.get 1))
+)
+
+(assert_return (invoke "block-value") (i32.const 7))
+(assert_return (invoke "nested-br" (i32.const 0)) (i32.const 21))
+(assert_return (invoke "nested-br" (i32.const 1)) (i32.const 10))
+(assert_return (invoke "loop-sum" (i32.const 10)) (i32.const 55))
+(assert_return (invoke "loop-sum" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "if-else" (i32.const 0)) (i32.const 2))
+(assert_return (invoke "if-else" (i32.const -7)) (i32.const 1))
+(assert_return (invoke "if-no-else" (i32.const 0)) (i32.const 5))
+(assert_return (invoke "if-no-else" (i32.const 1)) (i32.const 6))
+(assert_return (invoke "br_table" (i32.const 0)) (i32.const 100))
+(assert_return (invoke "br_table" (i32.const 1)) (i32.const 101))
+(assert_return (invoke "br_table" (i32.const 2)) (i32.const 102))
+(assert_return (invoke "br_table" (i32.const 3)) (i32.const 103))
+(assert_return (invoke "br_table" (i32.const 4)) (i32.const 103))
+(assert_return (invoke "br_table" (i32.const -1)) (i32.const 103))
+(assert_return (invoke "br_table-value" (i32.const 0)) (i32.const 51))
+(assert_return (invoke "br_table-value" (i32.const 1)) (i32.const 50))
+(assert_return (invoke "br_table-value" (i32.const 2)) (i32.const 51))
+(assert_return (invoke "br_table-value" (i32.const 100)) (i32.const 51))
+(assert_return (invoke "return-from-loop") (i32.const 9))
+(assert_return (invoke "unwind-block") (i32.const 3))
+(assert_return (invoke "unwind-loop" (i32.const 0)) (i32.const 8))
+(assert_return (invoke "unwind-loop" (i32.const 1)) (i32.const 8))
+(assert_return (invoke "select-i32" (i32.const 1) (i32.const 2) (i32.const 1)) (i32.const 1))
+(assert_return (invoke "select-i32" (i32.const 1) (i32.const 2) (i32.const 0)) (i32.const 2))
+(assert_return (invoke "select-f64" (f64.const 1) (f64.const 2) (i32.const -1)) (f64.const 1))
+(assert_return (invoke "select-f64" (f64.const 1) (f64.const 2) (i32.const 0)) (f64.const 2))
+(assert_return (invoke "nop") (i32.const 1))
+(assert_return (invoke "unreachable-after-br") (i32.const 3))
+(assert_return (invoke "dead-code") (i32.const 4))
+(assert_return (invoke "local-tee" (i32.const 0)) (i32.const 6))
+(assert_return (invoke "locals-zeroed") (i64.const 0))
+
+(assert_invalid
+  (module (func $type-value-empty-vs-num (result i32) (block (result i32))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $type-value-num-vs-void (block (i32.const 1))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $if-without-else (result i32) (if (result i32) (i32.const 1) (then (i32.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $unbound-label (br 1)))
+  "unknown label"
+)
+(assert_invalid
+  (module (func $br_table-arity (block (result i32) (block (br_table 0 1 (i32.const 0) (i32.const 0))) (i32.const 1)) (drop)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $select-mismatch (result i32) (select (i32.const 1) (i64.const 1) (i32.const 1))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $unknown-local (local.get 1)))
+  "unknown local"
+)
+(assert_invalid
+  (module (func $unreachable-polymorphic (result i32) (unreachable) (i64.const 0)))
+  "type mismatch"
+)
+
+(module
+  (func (export "unreachable") (unreachable))
+  (func (export "unreachable-in-block") (result i32)
+    (block (result i32) (i32.const 1) (unreachable)))
+)
+
+(assert_trap (invoke "unreachable") "unreachable")
+(assert_trap (invoke "unreachable-in-block") "unreachable")
+
+;; Blocks with parameters and several results
+
+(module
+  (type $i32-i32x2 (func (param i32) (result i32 i32)))
+  (func (export "block-results") (result i32 i64 f32)
+    (block (result i32 i64 f32) (i32.const 1) (i64.const 2) (f32.const 3)))
+  (func (export "block-params") (param i32 i32) (result i32)
+    (local.get 0) (local.get 1)
+    (block (param i32 i32) (result i32) (i32.sub)))
+  (func (export "block-type-index") (param i32) (result i32 i32)
+    (local.get 0)
+    (block (type $i32-i32x2) (local.tee 0) (local.get 0) (i32.add) (local.get 0)))
+  (func (export "br-results") (param i32) (result i32 i32)
+    (block $b (result i32 i32)
+      (br_if $b (i32.const 1) (i32.const 2) (local.get 0))
+      (drop) (drop)
+      (i32.const 3) (i32.const 4)))
+  (func (export "br_table-results") (param i32) (result i64 i32)
+    (block $b (result i64 i32)
+      (block $a (result i64 i32)
+        (br_table $a $b (i64.const 5) (i32.const 6) (local.get 0)))
+      (i32.add (i32.const 10))))
+  (func (export "if-params") (param i32 i32) (result i32)
+    (local.get 0) (local.get 1)
+    (if (param i32 i32) (result i32) (local.get 0)
+      (then (i32.add))
+      (else (i32.sub))))
+  (func (export "if-params-no-else") (param i32) (result i32)
+    (i32.const 10)
+    (if (param i32) (result i32) (local.get 0)
+      (then (i32.const 1) (i32.add))))
+  (func (export "if-results") (param i32) (result i32 f64)
+    (if (result i32 f64) (local.get 0)
+      (then (i32.const 1) (f64.const 1.5))
+      (else (i32.const 2) (f64.const 2.5))))
+  ;; Computes the n-th Fibonacci number, with the loop parameters holding
+  ;; the last two numbers.
+  (func (export "loop-params") (param $n i32) (result i32)
+    (i32.const 0) (i32.const 1)
+    (loop $l (param i32 i32) (result i32 i32)
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $l
+        (call $step)
+        (i32.gt_s (local.get $n) (i32.const 0))))
+    (drop))
+  (func $step (param i32 i32) (result i32 i32)
+    (local.get 1) (i32.add (local.get 0) (local.get 1)))
+  (func (export "loop-params-unwind") (result i32) (local $i i32) (local $acc i32)
+    (i32.const 7)
+    (loop $l (param i32) (result i32)
+      (local.set $acc (i32.add (i32.const 1)))
+      (i64.const 9)
+      (local.get $acc)
+      (br_if $l
+        (i32.lt_s (local.tee $i (i32.add (local.get $i) (i32.const 1))) (i32.const 3)))
+      (local.set $acc)
+      (drop)
+      (local.get $acc)))
+  (func (export "results-through-calls") (result i32 i32 i32)
+    (call $swap (i32.const 1) (i32.const 2) (i32.const 3)))
+  (func $swap (param i32 i32 i32) (result i32 i32 i32)
+    (local.get 2) (local.get 1) (local.get 0))
+)
+
+(assert_return (invoke "block-results") (i32.const 1) (i64.const 2) (f32.const 3))
+(assert_return (invoke "block-params" (i32.const 10) (i32.const 3)) (i32.const 7))
+(assert_return (invoke "block-type-index" (i32.const 4)) (i32.const 8) (i32.const 4))
+(assert_return (invoke "br-results" (i32.const 1)) (i32.const 1) (i32.const 2))
+(assert_return (invoke "br-results" (i32.const 0)) (i32.const 3) (i32.const 4))
+(assert_return (invoke "br_table-results" (i32.const 0)) (i64.const 5) (i32.const 16))
+(assert_return (invoke "br_table-results" (i32.const 1)) (i64.const 5) (i32.const 6))
+(assert_return (invoke "if-params" (i32.const 7) (i32.const 2)) (i32.const 9))
+(assert_return (invoke "if-params" (i32.const 0) (i32.const 2)) (i32.const -2))
+(assert_return (invoke "if-params-no-else" (i32.const 1)) (i32.const 11))
+(assert_return (invoke "if-params-no-else" (i32.const 0)) (i32.const 10))
+(assert_return (invoke "if-results" (i32.const 1)) (i32.const 1) (f64.const 1.5))
+(assert_return (invoke "if-results" (i32.const 0)) (i32.const 2) (f64.const 2.5))
+(assert_return (invoke "loop-params" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "loop-params" (i32.const 10)) (i32.const 55))
+(assert_return (invoke "loop-params-unwind") (i32.const 10))
+(assert_return (invoke "results-through-calls") (i32.const 3) (i32.const 2) (i32.const 1))
+
+(assert_invalid
+  (module (func $block-param-missing (block (param i32) (drop))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $block-param-type (i64.const 0) (block (param i32) (drop))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $loop-br-params (result i32) (i32.const 0) (loop (param i32) (result i32) (br 0 (i64.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $if-no-else-params (result i32) (i64.const 0) (if (param i64) (result i32) (i32.const 1) (then (drop) (i32.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $else-params (i32.const 0) (if (param i32) (i32.const 1) (then (drop)) (else))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func $unknown-block-type (block (type 3))))
+  "unknown type"
+)
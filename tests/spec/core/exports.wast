@@ -0,0 +1,26 @@
+;; Exports
+
+(module (func) (export "a" (func 0)))
+(module (func) (export "a" (func 0)) (export "b" (func 0)))
+(module (global i32 (i32.const 0)) (export "a" (global 0)))
+(module (table 0 funcref) (export "a" (table 0)))
+(module (memory 0) (export "a" (memory 0)))
+(module (func (export "")))
+
+(module $M
+  (func (export "e") (param $n i32) (result i32) (i32.add (local.get $n) (i32.const 10)))
+  (global (export "g") i32 (i32.const 55))
+  (global (export "mut") (mut f32) (f32.const 1.5))
+)
+
+(assert_return (invoke "e" (i32.const 42)) (i32.const 52))
+(assert_return (invoke $M "e" (i32.const 42)) (i32.const 52))
+(assert_return (get "g") (i32.const 55))
+(assert_return (get $M "mut") (f32.const 1.5))
+
+(assert_invalid (module (func) (export "a" (func 1))) "unknown function")
+(assert_invalid (module (func) (export "a" (func 0)) (export "a" (func 0))) "duplicate export name")
+(assert_invalid (module (func) (global i32 (i32.const 0)) (export "a" (func 0)) (export "a" (global 0))) "duplicate export name")
+(assert_invalid (module (global i32 (i32.const 0)) (export "a" (global 1))) "unknown global")
+(assert_invalid (module (table 0 funcref) (export "a" (table 1))) "unknown table")
+(assert_invalid (module (memory 0) (export "a" (memory 1))) "unknown memory")
@@ -0,0 +1,97 @@
+;; Globals
+
+(module
+  (global (import "spectest" "global_i32") i32)
+  (global $imported-f64 (import "spectest" "global_f64") f64)
+  (global $a i32 (i32.const -2))
+  (global $b i64 (i64.const -5))
+  (global $x (mut i32) (i32.const -12))
+  (global $y (mut i64) (i64.const -15))
+  (global $z f32 (f32.const 1.5))
+  (global $from-import i32 (global.get 0))
+  (global $r externref (ref.null extern))
+  (global $mr (mut funcref) (ref.null func))
+  (global $f funcref (ref.func $get-a))
+
+  (global (export "exported-a") i32 (i32.const 42))
+
+  (func $get-a (export "get-a") (result i32) (global.get $a))
+  (func (export "get-b") (result i64) (global.get $b))
+  (func (export "get-x") (result i32) (global.get $x))
+  (func (export "get-y") (result i64) (global.get $y))
+  (func (export "get-z") (result f32) (global.get $z))
+  (func (export "get-from-import") (result i32) (global.get $from-import))
+  (func (export "get-imported-f64") (result f64) (global.get $imported-f64))
+  (func (export "set-x") (param i32) (global.set $x (local.get 0)))
+  (func (export "set-y") (param i64) (global.set $y (local.get 0)))
+  (func (export "get-r") (result externref) (global.get $r))
+  (func (export "get-mr") (result funcref) (global.get $mr))
+  (func (export "set-mr") (global.set $mr (global.get $f)))
+  (func (export "as-loop-first") (result i32)
+    (loop (result i32) (global.get $x) (call $dummy) (call $dummy)))
+  (func $dummy)
+)
+
+(assert_return (invoke "get-a") (i32.const -2))
+(assert_return (invoke "get-b") (i64.const -5))
+(assert_return (invoke "get-x") (i32.const -12))
+(assert_return (invoke "get-y") (i64.const -15))
+(assert_return (invoke "get-z") (f32.const 1.5))
+(assert_return (invoke "get-from-import") (i32.const 666))
+(assert_return (invoke "get-imported-f64") (f64.const 666.6))
+(assert_return (invoke "get-r") (ref.null extern))
+(assert_return (invoke "get-mr") (ref.null func))
+(assert_return (get "exported-a") (i32.const 42))
+
+(assert_return (invoke "set-x" (i32.const 6)))
+(assert_return (invoke "set-y" (i64.const 7)))
+(assert_return (invoke "get-x") (i32.const 6))
+(assert_return (invoke "get-y") (i64.const 7))
+(assert_return (invoke "as-loop-first") (i32.const 6))
+(assert_return (invoke "set-mr"))
+(assert_return (invoke "get-mr") (ref.func))
+
+(assert_invalid
+  (module (global f32 (f32.const 0)) (func (global.set 0 (f32.const 1))))
+  "immutable global"
+)
+(assert_invalid
+  (module (import "spectest" "global_i32" (global i32)) (func (global.set 0 (i32.const 1))))
+  "immutable global"
+)
+(assert_invalid
+  (module (global i32 (i32.ctz (i32.const 0))))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global i32 (nop)))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global i32 (f32.const 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i32 (i32.const 0) (i32.const 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i32 (global.get 0)))
+  "unknown global"
+)
+(assert_invalid
+  (module (global i32 (i32.const 0)) (global i32 (global.get 1)))
+  "unknown global"
+)
+(assert_invalid
+  (module (global $m (mut i32) (i32.const 0)) (global i32 (global.get $m)))
+  "constant expression required"
+)
+(assert_invalid
+  (module (func (result i32) (global.get 0)))
+  "unknown global"
+)
+(assert_invalid
+  (module (global i32 (i32.const 0)) (func (result i64) (global.get 0)))
+  "type mismatch"
+)
@@ -0,0 +1,90 @@
+;; Imports from the spectest host module
+
+(module
+  (type $func_i32 (func (param i32)))
+  (type $func_f64 (func (param f64)))
+
+  (import "spectest" "print_i32" (func (param i32)))
+  (func (import "spectest" "print_i32") (param i32))
+  (import "spectest" "print_f64" (func $print_f64 (type $func_f64)))
+  (import "spectest" "global_i32" (global $g i32))
+  (import "spectest" "table" (table $t 10 20 funcref))
+  (import "spectest" "memory" (memory 1 2))
+
+  (func (export "p1") (param i32) (call 0 (local.get 0)))
+  (func (export "p2") (param i32) (call 1 (local.get 0)))
+  (func (export "p3") (param f64) (call $print_f64 (local.get 0)))
+  (func (export "get") (result i32) (global.get $g))
+  (func (export "table-size") (result i32) (table.size $t))
+  (func (export "memory-size") (result i32) (memory.size))
+)
+
+(assert_return (invoke "p1" (i32.const 13)))
+(assert_return (invoke "p2" (i32.const 14)))
+(assert_return (invoke "p3" (f64.const 15)))
+(assert_return (invoke "get") (i32.const 666))
+(assert_return (invoke "table-size") (i32.const 10))
+(assert_return (invoke "memory-size") (i32.const 1))
+
+(module (import "spectest" "memory" (memory 0)))
+(module (import "spectest" "memory" (memory 1)))
+(module (import "spectest" "memory" (memory 0 2)))
+(module (import "spectest" "memory" (memory 1 3)))
+(module (import "spectest" "table" (table 0 funcref)))
+(module (import "spectest" "table" (table 10 20 funcref)))
+
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 2)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 1 1)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "table" (table 0 funcref) ) (import "spectest" "table" (table 0 externref)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "print_i32" (func (param i64))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "print_i32" (func (param i32) (result i32))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "global_i32" (global i64)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "print" (global i32)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "unknown" (func)))
+  "unknown import"
+)
+(assert_unlinkable
+  (module (import "test" "unknown" (global i32)))
+  "unknown import"
+)
+
+(assert_invalid
+  (module (import "spectest" "print_i32" (func (type 1))))
+  "unknown type"
+)
+
+;; Imports are resolved in order, so a failure leaves no instance behind.
+(module
+  (func (export "f") (result i32) (i32.const 1))
+)
+(register "first")
+(assert_unlinkable
+  (module
+    (import "first" "f" (func (result i32)))
+    (import "first" "g" (func))
+  )
+  "unknown import"
+)
+(assert_return (invoke "f") (i32.const 1))
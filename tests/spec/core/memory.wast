@@ -0,0 +1,117 @@
+;; Linear memory: loads, stores, data segments and growth
+
+(module
+  (memory 1)
+  (data (i32.const 0) "abcdefghijklmnopqrstuvwxyz")
+  (data (i32.const 0xfff8) "\01\02\03\04\05\06\07\08")
+
+  (func (export "8u_good1") (param $i i32) (result i32) (i32.load8_u offset=0 (local.get $i)))
+  (func (export "8u_good2") (param $i i32) (result i32) (i32.load8_u align=1 (local.get $i)))
+  (func (export "8u_good3") (param $i i32) (result i32) (i32.load8_u offset=1 align=1 (local.get $i)))
+  (func (export "8u_good5") (param $i i32) (result i32) (i32.load8_u offset=25 align=1 (local.get $i)))
+  (func (export "8s_good") (param $i i32) (result i32) (i32.load8_s offset=2 (local.get $i)))
+  (func (export "16u_good") (param $i i32) (result i32) (i32.load16_u offset=1 (local.get $i)))
+  (func (export "32_good") (param $i i32) (result i32) (i32.load offset=2 align=1 (local.get $i)))
+  (func (export "64_good") (param $i i32) (result i64) (i64.load offset=1 (local.get $i)))
+  (func (export "32_bad") (param $i i32) (drop (i32.load offset=4294967295 (local.get $i))))
+
+  (func (export "i64_load32_s") (param i32) (result i64) (i64.load32_s (local.get 0)))
+  (func (export "i64_load16_u") (param i32) (result i64) (i64.load16_u (local.get 0)))
+  (func (export "store8") (param i32 i32) (i32.store8 (local.get 0) (local.get 1)))
+  (func (export "store16") (param i32 i64) (i64.store16 (local.get 0) (local.get 1)))
+  (func (export "store32") (param i32 i32) (i32.store (local.get 0) (local.get 1)))
+  (func (export "load32") (param i32) (result i32) (i32.load (local.get 0)))
+  (func (export "store_f32") (param i32 f32) (f32.store (local.get 0) (local.get 1)))
+  (func (export "load_f32") (param i32) (result f32) (f32.load (local.get 0)))
+  (func (export "store_f64") (param i32 f64) (f64.store offset=8 (local.get 0) (local.get 1)))
+  (func (export "load_f64") (param i32) (result f64) (f64.load offset=8 (local.get 0)))
+  (func (export "size") (result i32) (memory.size))
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke "8u_good1" (i32.const 0)) (i32.const 97))
+(assert_return (invoke "8u_good2" (i32.const 0)) (i32.const 97))
+(assert_return (invoke "8u_good3" (i32.const 0)) (i32.const 98))
+(assert_return (invoke "8u_good5" (i32.const 0)) (i32.const 122))
+(assert_return (invoke "8u_good5" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "8u_good1" (i32.const 65535)) (i32.const 8))
+(assert_trap (invoke "8u_good1" (i32.const 65536)) "out of bounds memory access")
+(assert_trap (invoke "8u_good3" (i32.const 65535)) "out of bounds memory access")
+(assert_trap (invoke "8u_good1" (i32.const -1)) "out of bounds memory access")
+(assert_return (invoke "8s_good" (i32.const 0)) (i32.const 99))
+(assert_return (invoke "16u_good" (i32.const 0)) (i32.const 25442))
+(assert_return (invoke "32_good" (i32.const 0)) (i32.const 1717920867))
+(assert_return (invoke "64_good" (i32.const 0)) (i64.const 0x6968676665646362))
+(assert_return (invoke "32_good" (i32.const 0xfff6)) (i32.const 0x04030201))
+(assert_trap (invoke "32_good" (i32.const 0xfffb)) "out of bounds memory access")
+(assert_trap (invoke "64_good" (i32.const 0xfff8)) "out of bounds memory access")
+(assert_trap (invoke "32_bad" (i32.const 0)) "out of bounds memory access")
+(assert_trap (invoke "32_bad" (i32.const 1)) "out of bounds memory access")
+
+(invoke "store32" (i32.const 100) (i32.const 0x80808080))
+(assert_return (invoke "i64_load32_s" (i32.const 100)) (i64.const 0xffffffff80808080))
+(assert_return (invoke "i64_load16_u" (i32.const 100)) (i64.const 0x8080))
+(invoke "store8" (i32.const 101) (i32.const 0x1ff))
+(assert_return (invoke "load32" (i32.const 100)) (i32.const 0x8080ff80))
+(invoke "store16" (i32.const 102) (i64.const 0x123456789abc))
+(assert_return (invoke "load32" (i32.const 100)) (i32.const 0x9abcff80))
+(invoke "store_f32" (i32.const 200) (f32.const nan:0x200000))
+(assert_return (invoke "load_f32" (i32.const 200)) (f32.const nan:0x200000))
+(assert_return (invoke "load32" (i32.const 200)) (i32.const 0x7fa00000))
+(invoke "store_f64" (i32.const 200) (f64.const -0x1.5p+3))
+(assert_return (invoke "load_f64" (i32.const 200)) (f64.const -0x1.5p+3))
+(assert_trap (invoke "store32" (i32.const 65533) (i32.const 0)) "out of bounds memory access")
+;; A store that is partially out of bounds doesn't write anything.
+(assert_return (invoke "8u_good1" (i32.const 65533)) (i32.const 6))
+
+(assert_return (invoke "size") (i32.const 1))
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "size") (i32.const 3))
+(assert_return (invoke "8u_good1" (i32.const 65536)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 0x10000)) (i32.const -1))
+(assert_return (invoke "size") (i32.const 3))
+
+(module
+  (memory 0 2)
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+)
+
+(assert_trap (invoke "load" (i32.const 0)) "out of bounds memory access")
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const -1))
+
+(module (memory 0) (func (export "size") (result i32) (memory.size)))
+(assert_return (invoke "size") (i32.const 0))
+
+(assert_trap
+  (module (memory 1) (data (i32.const 0xffff) "ab"))
+  "out of bounds memory access"
+)
+
+(assert_invalid (module (memory 1 0)) "size minimum must not be greater than maximum")
+(assert_invalid (module (memory 65537)) "memory size must be at most 65536 pages (4GiB)")
+(assert_invalid (module (memory 1 65537)) "memory size must be at most 65536 pages (4GiB)")
+(assert_invalid (module (func (drop (i32.load (i32.const 0))))) "unknown memory")
+(assert_invalid (module (func (drop (memory.size)))) "unknown memory")
+(assert_invalid (module (data (i32.const 0) "")) "unknown memory")
+(assert_invalid
+  (module (memory 1) (func (drop (i32.load align=8 (i32.const 0)))))
+  "alignment must not be larger than natural"
+)
+(assert_invalid
+  (module (memory 1) (func (drop (i64.load8_u align=2 (i32.const 0)))))
+  "alignment must not be larger than natural"
+)
+(assert_invalid
+  (module (memory 1) (func (i32.store (i32.const 0) (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (memory 1) (data (i64.const 0) ""))
+  "type mismatch"
+)
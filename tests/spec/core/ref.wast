@@ -0,0 +1,52 @@
+;; Reference types
+
+(module
+  (func $f (export "f") (result i32) (i32.const 9))
+  (elem declare func $g)
+  (func $g)
+
+  (func (export "null-func") (result funcref) (ref.null func))
+  (func (export "null-extern") (result externref) (ref.null extern))
+  (func (export "is_null-func") (param funcref) (result i32) (ref.is_null (local.get 0)))
+  (func (export "is_null-extern") (param externref) (result i32) (ref.is_null (local.get 0)))
+  (func (export "id-extern") (param externref) (result externref) (local.get 0))
+  (func (export "ref-func-f") (result funcref) (ref.func $f))
+  (func (export "ref-func-g-is-null") (result i32) (ref.is_null (ref.func $g)))
+  (func (export "select-extern") (param externref externref i32) (result externref)
+    (select (result externref) (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "local-default") (result externref) (local externref) (local.get 0))
+)
+
+(assert_return (invoke "null-func") (ref.null func))
+(assert_return (invoke "null-extern") (ref.null extern))
+(assert_return (invoke "is_null-func" (ref.null func)) (i32.const 1))
+(assert_return (invoke "is_null-extern" (ref.null extern)) (i32.const 1))
+(assert_return (invoke "is_null-extern" (ref.extern 1)) (i32.const 0))
+(assert_return (invoke "is_null-extern" (ref.extern 0)) (i32.const 0))
+(assert_return (invoke "id-extern" (ref.extern 1)) (ref.extern 1))
+(assert_return (invoke "id-extern" (ref.null extern)) (ref.null extern))
+(assert_return (invoke "ref-func-f") (ref.func))
+(assert_return (invoke "ref-func-g-is-null") (i32.const 0))
+(assert_return (invoke "select-extern" (ref.extern 1) (ref.extern 2) (i32.const 0)) (ref.extern 2))
+(assert_return (invoke "local-default") (ref.null extern))
+
+(assert_invalid
+  (module (func $f) (func (drop (ref.func $f))))
+  "undeclared function reference"
+)
+(assert_invalid
+  (module (func (drop (ref.func 7))))
+  "unknown function"
+)
+(assert_invalid
+  (module (func (result externref) (ref.null func)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (result i32) (ref.is_null (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (result funcref) (select (ref.null func) (ref.null func) (i32.const 1))))
+  "type mismatch"
+)
@@ -0,0 +1,72 @@
+;; Tables and the instructions that access them
+
+(module
+  (table $t1 2 funcref)
+  (table $t2 3 10 externref)
+  (elem (table $t1) (i32.const 0) func $f $g)
+  (func $f (result i32) (i32.const 1))
+  (func $g (result i32) (i32.const 2))
+
+  (func (export "get-t1") (param i32) (result funcref) (table.get $t1 (local.get 0)))
+  (func (export "get-t2") (param i32) (result externref) (table.get $t2 (local.get 0)))
+  (func (export "set-t2") (param i32 externref) (table.set $t2 (local.get 0) (local.get 1)))
+  (func (export "null-t1") (param i32) (table.set $t1 (local.get 0) (ref.null func)))
+  (func (export "is-null-t1") (param i32) (result i32) (ref.is_null (table.get $t1 (local.get 0))))
+  (func (export "size-t1") (result i32) (table.size $t1))
+  (func (export "size-t2") (result i32) (table.size $t2))
+  (func (export "grow-t2") (param i32 externref) (result i32) (table.grow $t2 (local.get 1) (local.get 0)))
+  (func (export "fill-t2") (param i32 externref i32) (table.fill $t2 (local.get 0) (local.get 1) (local.get 2)))
+)
+
+(assert_return (invoke "get-t1" (i32.const 0)) (ref.func))
+(assert_return (invoke "is-null-t1" (i32.const 1)) (i32.const 0))
+(assert_trap (invoke "get-t1" (i32.const 2)) "out of bounds table access")
+(assert_return (invoke "null-t1" (i32.const 1)))
+(assert_return (invoke "is-null-t1" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "get-t2" (i32.const 2)) (ref.null extern))
+(assert_return (invoke "set-t2" (i32.const 2) (ref.extern 7)))
+(assert_return (invoke "get-t2" (i32.const 2)) (ref.extern 7))
+(assert_trap (invoke "set-t2" (i32.const 3) (ref.extern 7)) "out of bounds table access")
+
+(assert_return (invoke "size-t1") (i32.const 2))
+(assert_return (invoke "size-t2") (i32.const 3))
+(assert_return (invoke "grow-t2" (i32.const 2) (ref.extern 5)) (i32.const 3))
+(assert_return (invoke "size-t2") (i32.const 5))
+(assert_return (invoke "get-t2" (i32.const 4)) (ref.extern 5))
+(assert_return (invoke "grow-t2" (i32.const 6) (ref.null extern)) (i32.const -1))
+(assert_return (invoke "grow-t2" (i32.const 5) (ref.null extern)) (i32.const 5))
+(assert_return (invoke "size-t2") (i32.const 10))
+
+(assert_return (invoke "fill-t2" (i32.const 1) (ref.extern 3) (i32.const 2)))
+(assert_return (invoke "get-t2" (i32.const 0)) (ref.null extern))
+(assert_return (invoke "get-t2" (i32.const 1)) (ref.extern 3))
+(assert_return (invoke "get-t2" (i32.const 2)) (ref.extern 3))
+(assert_return (invoke "get-t2" (i32.const 3)) (ref.extern 5))
+(assert_return (invoke "fill-t2" (i32.const 10) (ref.extern 3) (i32.const 0)))
+(assert_trap (invoke "fill-t2" (i32.const 9) (ref.extern 4) (i32.const 2)) "out of bounds table access")
+(assert_return (invoke "get-t2" (i32.const 9)) (ref.null extern))
+
+(module
+  (table 0 funcref)
+  (func (export "grow") (param i32) (result i32) (table.grow (ref.null func) (local.get 0)))
+)
+
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 0x10000)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const -1)) (i32.const -1))
+
+(assert_invalid (module (table 1 0 funcref)) "size minimum must not be greater than maximum")
+(assert_invalid (module (func (drop (table.size 0)))) "unknown table")
+(assert_invalid
+  (module (table 1 externref) (func (result funcref) (table.get 0 (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (table 1 funcref) (func (table.set 0 (i32.const 0) (ref.null extern))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (table 1 funcref) (func $f) (elem (i32.const 0) externref (ref.null extern)))
+  "type mismatch"
+)
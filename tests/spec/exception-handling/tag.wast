@@ -0,0 +1,70 @@
+;; Tag definitions, imports and exports
+
+(module (tag))
+(module (tag (param i32)))
+(module (tag (param i32 i64 f32 f64 v128 funcref externref exnref)))
+(module (type $t (func (param i32))) (tag (type $t)))
+
+(assert_invalid (module (tag (result i32))) "non-empty tag result type")
+(assert_invalid (module (func (throw 0))) "unknown tag 0")
+(assert_invalid (module (tag) (export "e" (tag 1))) "unknown export index 1")
+
+;; Importing a tag keeps its identity, so its exceptions are caught with it.
+(module $M
+  (tag $e (export "e") (param i32))
+  (func (export "throw") (param i32) (throw $e (local.get 0)))
+)
+(register "M" $M)
+
+(module
+  (import "M" "e" (tag $e (param i32)))
+  (import "M" "throw" (func $throw (param i32)))
+  (tag $local (param i32))
+
+  (func (export "catch-imported") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h) (call $throw (local.get 0)))
+      (i32.const -1)))
+
+  (func (export "catch-local") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $local $h) (call $throw (local.get 0)))
+      (i32.const -1)))
+
+  (func (export "throw-imported") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e $h) (throw $e (local.get 0)))
+      (i32.const -1)))
+)
+
+(assert_return (invoke "catch-imported" (i32.const 42)) (i32.const 42))
+(assert_exception (invoke "catch-local" (i32.const 42)))
+(assert_return (invoke "throw-imported" (i32.const 7)) (i32.const 7))
+
+;; Each instantiation defines new tags.
+(module $N
+  (tag $e (export "e"))
+  (func (export "throw") (throw $e))
+)
+(register "N" $N)
+
+(module
+  (import "N" "throw" (func $throw))
+  (tag $e)
+  (func (export "catch") (result i32)
+    (block $h
+      (try_table (catch $e $h) (call $throw))
+      (return (i32.const 0)))
+    (i32.const 1))
+)
+
+(assert_exception (invoke "catch"))
+
+(assert_unlinkable
+  (module (import "M" "e" (tag (param i64))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "M" "throw" (tag (param i32))))
+  "incompatible import type"
+)
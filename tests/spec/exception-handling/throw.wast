@@ -0,0 +1,62 @@
+;; Throwing exceptions
+
+(module
+  (tag $e0)
+  (tag $e-i32 (param i32))
+  (tag $e-f32 (param f32))
+  (tag $e-i64 (param i64))
+  (tag $e-i32-i32 (param i32 i32))
+
+  (func $throw-if (export "throw-if") (param i32) (result i32)
+    (local.get 0)
+    (i32.const 0) (if (i32.ne) (then (throw $e0)))
+    (i32.const 0))
+
+  (func (export "throw-param-f32") (param f32) (local.get 0) (throw $e-f32))
+  (func (export "throw-param-i64") (param i64) (local.get 0) (throw $e-i64))
+  (func (export "throw-param-i32-i32") (param i32 i32)
+    (throw $e-i32-i32 (local.get 0) (local.get 1)))
+
+  (func (export "throw-polymorphic") (throw $e0) (throw $e-i32))
+  (func (export "throw-polymorphic-block") (block (result i32) (throw $e0)) (throw $e-i32))
+
+  (func (export "throw-1-2") (i32.const 1) (i32.const 2) (throw $e-i32-i32))
+
+  ;; Throwing in dead code doesn't throw.
+  (func (export "throw-dead") (result i32)
+    (return (i32.const 3))
+    (throw $e0))
+
+  ;; Exceptions unwind the frames of the callers.
+  (func $deep (param i32)
+    (if (local.get 0)
+      (then (call $deep (i32.sub (local.get 0) (i32.const 1))))
+      (else (throw $e-i32 (i32.const 9)))))
+  (func (export "throw-deep") (param i32) (call $deep (local.get 0)))
+)
+
+(assert_return (invoke "throw-if" (i32.const 0)) (i32.const 0))
+(assert_exception (invoke "throw-if" (i32.const 10)))
+(assert_exception (invoke "throw-if" (i32.const -1)))
+
+(assert_exception (invoke "throw-param-f32" (f32.const 5.0)))
+(assert_exception (invoke "throw-param-i64" (i64.const 5)))
+(assert_exception (invoke "throw-param-i32-i32" (i32.const 1) (i32.const 2)))
+(assert_exception (invoke "throw-polymorphic"))
+(assert_exception (invoke "throw-polymorphic-block"))
+(assert_exception (invoke "throw-1-2"))
+(assert_return (invoke "throw-dead") (i32.const 3))
+(assert_exception (invoke "throw-deep" (i32.const 100)))
+
+;; An uncaught exception doesn't break the next invocations.
+(assert_return (invoke "throw-if" (i32.const 0)) (i32.const 0))
+
+(assert_invalid (module (func (throw 0))) "unknown tag 0")
+(assert_invalid
+  (module (tag (param i32)) (func (throw 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (tag (param i32)) (func (i64.const 5) (throw 0)))
+  "type mismatch"
+)
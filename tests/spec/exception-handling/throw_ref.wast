@@ -0,0 +1,105 @@
+;; Rethrowing exceptions with `throw_ref`
+
+(module
+  (tag $e0)
+  (tag $e1)
+  (tag $e-i32 (param i32))
+
+  (func (export "catch-throw_ref-0")
+    (block $h (result exnref)
+      (try_table (catch_ref $e0 $h) (throw $e0))
+      (unreachable))
+    (throw_ref))
+
+  (func (export "catch-throw_ref-1") (param i32) (result i32)
+    (block $h (result exnref)
+      (try_table (result i32) (catch_ref $e0 $h) (throw $e0))
+      (return))
+    (if (param exnref) (i32.eqz (local.get 0))
+      (then (throw_ref))
+      (else (drop)))
+    (i32.const 23))
+
+  (func (export "catchall-throw_ref-0")
+    (block $h (result exnref)
+      (try_table (result exnref) (catch_all_ref $h) (throw $e0)))
+    (throw_ref))
+
+  (func (export "catchall-throw_ref-1") (param i32) (result i32)
+    (block $h (result exnref)
+      (try_table (result i32) (catch_all_ref $h) (throw $e0))
+      (return))
+    (if (param exnref) (i32.eqz (local.get 0))
+      (then (throw_ref))
+      (else (drop)))
+    (i32.const 23))
+
+  ;; The payload is kept when rethrowing.
+  (func (export "rethrow-payload") (param i32) (result i32)
+    (block $outer (result i32)
+      (try_table (result i32) (catch $e-i32 $outer)
+        (block $h (result i32 exnref)
+          (try_table (catch_ref $e-i32 $h) (throw $e-i32 (local.get 0)))
+          (unreachable))
+        (throw_ref)))
+    (i32.const 1)
+    (i32.add))
+
+  ;; References can be stored in locals and rethrown later.
+  (func (export "rethrow-stored") (param i32) (result i32)
+    (local $exn1 exnref)
+    (local $exn2 exnref)
+    (block $h1 (result exnref)
+      (try_table (result exnref) (catch_ref $e1 $h1) (throw $e1)))
+    (local.set $exn1)
+    (block $h2 (result exnref)
+      (try_table (result exnref) (catch_ref $e0 $h2) (throw $e0)))
+    (local.set $exn2)
+    (block $h0
+      (try_table (catch $e0 $h0)
+        (if (i32.eq (local.get 0) (i32.const 0)) (then (throw_ref (local.get $exn1))))
+        (if (i32.eq (local.get 0) (i32.const 1)) (then (throw_ref (local.get $exn2)))))
+      (return (i32.const 23)))
+    (i32.const 0))
+
+  ;; Rethrowing doesn't make the exception catchable by another tag.
+  (func (export "rethrow-tag") (result i32)
+    (block $h1
+      (block $h (result exnref)
+        (try_table (catch_all_ref $h) (throw $e0))
+        (unreachable))
+      (try_table (param exnref) (catch $e1 $h1) (throw_ref))
+      (unreachable))
+    (i32.const 1))
+
+  (func (export "throw_ref-null")
+    (throw_ref (ref.null exn)))
+
+  (func (export "is-null") (param exnref) (result i32)
+    (ref.is_null (local.get 0)))
+
+  (func $catch-ref (result exnref)
+    (block $h (result exnref)
+      (try_table (catch_all_ref $h) (throw $e0))
+      (unreachable)))
+  (func (export "caught-is-null") (result i32)
+    (ref.is_null (call $catch-ref)))
+)
+
+(assert_exception (invoke "catch-throw_ref-0"))
+(assert_exception (invoke "catch-throw_ref-1" (i32.const 0)))
+(assert_return (invoke "catch-throw_ref-1" (i32.const 1)) (i32.const 23))
+(assert_exception (invoke "catchall-throw_ref-0"))
+(assert_exception (invoke "catchall-throw_ref-1" (i32.const 0)))
+(assert_return (invoke "catchall-throw_ref-1" (i32.const 1)) (i32.const 23))
+(assert_return (invoke "rethrow-payload" (i32.const 41)) (i32.const 42))
+(assert_exception (invoke "rethrow-stored" (i32.const 0)))
+(assert_return (invoke "rethrow-stored" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "rethrow-stored" (i32.const 2)) (i32.const 23))
+(assert_exception (invoke "rethrow-tag"))
+(assert_trap (invoke "throw_ref-null") "null exception reference")
+(assert_return (invoke "is-null" (ref.null exn)) (i32.const 1))
+(assert_return (invoke "caught-is-null") (i32.const 0))
+
+(assert_invalid (module (func (throw_ref))) "type mismatch")
+(assert_invalid (module (func (block (result exnref) (throw_ref (i32.const 0))))) "type mismatch")
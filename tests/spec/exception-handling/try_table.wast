@@ -0,0 +1,316 @@
+;; Catching exceptions with `try_table`
+
+(module
+  (tag $e0)
+  (tag $e1)
+  (tag $e2)
+  (tag $e-i32 (param i32))
+  (tag $e-f32 (param f32))
+  (tag $e-i64 (param i64))
+  (tag $e-i32-i64 (param i32 i64))
+
+  (func $throw-if (param i32) (result i32)
+    (local.get 0)
+    (i32.const 0) (if (i32.ne) (then (throw $e0)))
+    (i32.const 0))
+
+  (func (export "simple-throw-catch") (param i32) (result i32)
+    (block $h
+      (try_table (result i32) (catch $e0 $h)
+        (if (i32.eqz (local.get 0)) (then (throw $e0)) (else))
+        (i32.const 42))
+      (return))
+    (i32.const 23))
+
+  (func (export "unreachable-not-caught")
+    (block $h (try_table (catch_all $h) (unreachable)) (return)))
+
+  (func $div (param i32 i32) (result i32)
+    (local.get 0) (local.get 1) (i32.div_u))
+  (func (export "trap-in-callee") (param i32 i32) (result i32)
+    (block $h
+      (try_table (result i32) (catch_all $h)
+        (local.get 0) (local.get 1) (call $div))
+      (return))
+    (i32.const 11))
+
+  (func (export "catch-complex-1") (param i32) (result i32)
+    (block $h1
+      (try_table (result i32) (catch $e1 $h1)
+        (block $h0
+          (try_table (result i32) (catch $e0 $h0)
+            (if (i32.eqz (local.get 0))
+              (then (throw $e0))
+              (else
+                (if (i32.eq (local.get 0) (i32.const 1))
+                  (then (throw $e1))
+                  (else (throw $e2)))))
+            (i32.const 2))
+          (br 1))
+        (i32.const 3))
+      (return))
+    (i32.const 4))
+
+  (func (export "catch-complex-2") (param i32) (result i32)
+    (block $h0
+      (block $h1
+        (try_table (result i32) (catch $e0 $h0) (catch $e1 $h1)
+          (if (i32.eqz (local.get 0))
+            (then (throw $e0))
+            (else
+              (if (i32.eq (local.get 0) (i32.const 1))
+                (then (throw $e1))
+                (else (throw $e2)))))
+          (i32.const 2))
+        (return))
+      (return (i32.const 3)))
+    (i32.const 4))
+
+  (func (export "throw-catch-param-i32") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (result i32) (catch $e-i32 $h)
+        (throw $e-i32 (local.get 0)))
+      (return))
+    (return))
+
+  (func (export "throw-catch-param-f32") (param f32) (result f32)
+    (block $h (result f32)
+      (try_table (result f32) (catch $e-f32 $h)
+        (throw $e-f32 (local.get 0)))
+      (return))
+    (return))
+
+  (func (export "throw-catch-param-i64") (param i64) (result i64)
+    (block $h (result i64)
+      (try_table (result i64) (catch $e-i64 $h)
+        (throw $e-i64 (local.get 0)))
+      (return))
+    (return))
+
+  (func (export "throw-catch-param-i32-i64") (param i32 i64) (result i32 i64)
+    (block $h (result i32 i64)
+      (try_table (result i32 i64) (catch $e-i32-i64 $h)
+        (throw $e-i32-i64 (local.get 0) (local.get 1)))
+      (return))
+    (return))
+
+  (func $throw-param-i32 (param i32) (throw $e-i32 (local.get 0)))
+  (func (export "catch-param-i32") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (result i32) (catch $e-i32 $h)
+        (i32.const 0)
+        (call $throw-param-i32 (local.get 0)))
+      (return))
+    (return))
+
+  ;; The operands pushed in the body are dropped when catching.
+  (func (export "catch-drops-operands") (param i32) (result i32)
+    (i32.const 100)
+    (block $h (result i32)
+      (i32.const 1) (i32.const 2) (i32.const 3)
+      (try_table (result i32) (catch $e-i32 $h)
+        (i64.const 4) (f32.const 5)
+        (call $throw-param-i32 (local.get 0))
+        (drop) (drop) (i32.const 6))
+      (drop) (drop) (drop) (drop)
+      (i32.const 7))
+    (i32.add))
+
+  ;; `try_table` takes parameters like other blocks.
+  (func (export "try-with-param") (result i32)
+    (block $h (result i32)
+      (i32.const 1)
+      (try_table (param i32) (result i32) (catch $e-i32 $h)
+        (throw $e-i32)))
+    (i32.const 10)
+    (i32.add))
+
+  (func (export "catch-all") (param i32) (result i32)
+    (block $h
+      (try_table (result i32) (catch_all $h)
+        (call $throw-if (local.get 0)))
+      (return))
+    (i32.const 1))
+
+  ;; The first matching clause is taken.
+  (func (export "catch-order") (param i32) (result i32)
+    (block $h2
+      (block $h1
+        (block $h0
+          (try_table (catch $e0 $h0) (catch_all $h1) (catch $e1 $h2)
+            (if (i32.eqz (local.get 0)) (then (throw $e0)))
+            (if (i32.eq (local.get 0) (i32.const 1)) (then (throw $e1)))
+            (return (i32.const 0)))
+          (unreachable))
+        (return (i32.const 10)))
+      (return (i32.const 11)))
+    (i32.const 12))
+
+  ;; Exceptions thrown in a catch branch go to the enclosing handlers.
+  (func (export "rethrow-in-handler") (result i32)
+    (block $outer (result i32)
+      (try_table (result i32) (catch $e-i32 $outer)
+        (block $inner
+          (try_table (catch $e0 $inner) (throw $e0))
+          (unreachable))
+        (throw $e-i32 (i32.const 5))))
+    (i32.const 1)
+    (i32.add))
+
+  ;; Catching to a loop label runs the loop again.
+  (func (export "catch-to-loop") (result i32)
+    (local $n i32)
+    (loop $l
+      (local.set $n (i32.add (local.get $n) (i32.const 1)))
+      (if (i32.lt_u (local.get $n) (i32.const 10))
+        (then (try_table (catch $e0 $l) (throw $e0)))))
+    (local.get $n))
+
+  ;; Catching to the function label returns from it.
+  (func (export "catch-to-func") (result i32)
+    (try_table (catch $e-i32 0) (throw $e-i32 (i32.const 77)))
+    (i32.const 0))
+
+  ;; An empty `try_table` doesn't catch anything.
+  (func (export "catchless-try") (param i32) (result i32)
+    (block $h
+      (try_table (result i32) (catch $e0 $h)
+        (try_table (result i32) (call $throw-if (local.get 0))))
+      (return))
+    (i32.const 1))
+
+  ;; Exceptions go through `try_table`s that don't catch them.
+  (func (export "uncaught") (param i32) (result i32)
+    (block $h
+      (try_table (result i32) (catch $e1 $h)
+        (call $throw-if (local.get 0)))
+      (return))
+    (i32.const 1))
+
+  ;; A handler only covers its body, not the code after it.
+  (func (export "after-try") (result i32)
+    (block $h
+      (try_table (catch_all $h))
+      (throw $e0))
+    (i32.const 0))
+
+  ;; Deep recursion is unwound at once.
+  (func $recurse (param i32)
+    (if (i32.eqz (local.get 0)) (then (throw $e-i32 (i32.const 99))))
+    (call $recurse (i32.sub (local.get 0) (i32.const 1))))
+  (func (export "catch-deep") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e-i32 $h) (call $recurse (local.get 0)))
+      (i32.const 0)))
+
+  ;; Tail calls replace the frame of the caller, and its handlers.
+  (func $tail (param i32) (return_call $throw-param-i32 (local.get 0)))
+  (func (export "catch-tail-call") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (catch $e-i32 $h) (call $tail (local.get 0)))
+      (i32.const 0)))
+)
+
+(assert_return (invoke "simple-throw-catch" (i32.const 0)) (i32.const 23))
+(assert_return (invoke "simple-throw-catch" (i32.const 1)) (i32.const 42))
+
+(assert_trap (invoke "unreachable-not-caught") "unreachable")
+(assert_trap (invoke "trap-in-callee" (i32.const 7) (i32.const 0)) "integer divide by zero")
+(assert_return (invoke "trap-in-callee" (i32.const 8) (i32.const 2)) (i32.const 4))
+
+(assert_return (invoke "catch-complex-1" (i32.const 0)) (i32.const 3))
+(assert_return (invoke "catch-complex-1" (i32.const 1)) (i32.const 4))
+(assert_exception (invoke "catch-complex-1" (i32.const 2)))
+
+(assert_return (invoke "catch-complex-2" (i32.const 0)) (i32.const 4))
+(assert_return (invoke "catch-complex-2" (i32.const 1)) (i32.const 3))
+(assert_exception (invoke "catch-complex-2" (i32.const 2)))
+
+(assert_return (invoke "throw-catch-param-i32" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "throw-catch-param-i32" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "throw-catch-param-i32" (i32.const 10)) (i32.const 10))
+(assert_return (invoke "throw-catch-param-f32" (f32.const 5.0)) (f32.const 5.0))
+(assert_return (invoke "throw-catch-param-f32" (f32.const 10.5)) (f32.const 10.5))
+(assert_return (invoke "throw-catch-param-i64" (i64.const 5)) (i64.const 5))
+(assert_return (invoke "throw-catch-param-i64" (i64.const -1)) (i64.const -1))
+(assert_return
+  (invoke "throw-catch-param-i32-i64" (i32.const 1) (i64.const 2))
+  (i32.const 1) (i64.const 2)
+)
+
+(assert_return (invoke "catch-param-i32" (i32.const 5)) (i32.const 5))
+(assert_return (invoke "catch-drops-operands" (i32.const 5)) (i32.const 105))
+(assert_return (invoke "try-with-param") (i32.const 11))
+
+(assert_return (invoke "catch-all" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "catch-all" (i32.const 1)) (i32.const 1))
+
+(assert_return (invoke "catch-order" (i32.const 0)) (i32.const 10))
+(assert_return (invoke "catch-order" (i32.const 1)) (i32.const 11))
+(assert_return (invoke "catch-order" (i32.const 2)) (i32.const 0))
+
+(assert_return (invoke "rethrow-in-handler") (i32.const 6))
+(assert_return (invoke "catch-to-loop") (i32.const 10))
+(assert_return (invoke "catch-to-func") (i32.const 77))
+
+(assert_return (invoke "catchless-try" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "catchless-try" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "uncaught" (i32.const 0)) (i32.const 0))
+(assert_exception (invoke "uncaught" (i32.const 1)))
+(assert_exception (invoke "after-try"))
+
+(assert_return (invoke "catch-deep" (i32.const 1000)) (i32.const 99))
+(assert_return (invoke "catch-tail-call" (i32.const 8)) (i32.const 8))
+
+;; Exceptions thrown by imported functions are caught too.
+(module $thrower
+  (tag $e (export "e") (param i32))
+  (func (export "throw") (param i32) (throw $e (local.get 0)))
+)
+(register "thrower" $thrower)
+
+(module
+  (import "thrower" "e" (tag $e (param i32)))
+  (import "thrower" "throw" (func $throw (param i32)))
+
+  (func (export "catch-import") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (result i32) (catch $e $h)
+        (call $throw (local.get 0))
+        (i32.const 0))))
+
+  (table funcref (elem $throw))
+  (func (export "catch-indirect") (param i32) (result i32)
+    (block $h (result i32)
+      (try_table (result i32) (catch $e $h)
+        (call_indirect (param i32) (local.get 0) (i32.const 0))
+        (i32.const 0))))
+)
+
+(assert_return (invoke "catch-import" (i32.const 3)) (i32.const 3))
+(assert_return (invoke "catch-indirect" (i32.const 4)) (i32.const 4))
+
+(assert_invalid
+  (module (func (try_table (catch_all 1))))
+  "unknown label 1"
+)
+(assert_invalid
+  (module (tag $e) (func (result i32) (try_table (catch $e 0)) (i32.const 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (tag $e (param i32)) (func (block (try_table (catch $e 1)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (block (result i32) (try_table (catch_all 0)) (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (result i32) (try_table (result i32))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (try_table (result i32) (i64.const 0))))
+  "type mismatch"
+)
@@ -0,0 +1,78 @@
+;; Arithmetic in constant expressions
+
+(module $M
+  (global (export "base") i32 (i32.const 0x10))
+  (global (export "big") i64 (i64.const 0x7fffffffffffffff))
+)
+(register "M" $M)
+
+(module
+  (import "M" "base" (global $base i32))
+  (import "M" "big" (global $big i64))
+
+  (global $a i32 (i32.add (global.get $base) (i32.const 4)))
+  (global $b i32 (i32.mul (global.get $a) (i32.sub (i32.const 3) (i32.const 1))))
+  (global $c i32 (i32.sub (i32.const 0) (i32.const 1)))
+  (global $d i32 (i32.mul (i32.const 0x10000) (i32.const 0x10000)))
+  (global $e i64 (i64.add (global.get $big) (i64.const 1)))
+  (global $f i64 (i64.mul (i64.sub (i64.const 2) (i64.const 5)) (i64.const 7)))
+
+  (memory 1)
+  (data (offset (i32.add (global.get $base) (i32.const 2))) "\2a")
+
+  (table 4 funcref)
+  (elem (offset (i32.sub (i32.const 4) (i32.const 1))) func $f)
+  (func $f (result i32) (i32.const 7))
+
+  (func (export "a") (result i32) (global.get $a))
+  (func (export "b") (result i32) (global.get $b))
+  (func (export "c") (result i32) (global.get $c))
+  (func (export "d") (result i32) (global.get $d))
+  (func (export "e") (result i64) (global.get $e))
+  (func (export "f") (result i64) (global.get $f))
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "call") (param i32) (result i32) (call_indirect (result i32) (local.get 0)))
+)
+
+(assert_return (invoke "a") (i32.const 0x14))
+(assert_return (invoke "b") (i32.const 0x28))
+(assert_return (invoke "c") (i32.const -1))
+(assert_return (invoke "d") (i32.const 0))
+(assert_return (invoke "e") (i64.const 0x8000000000000000))
+(assert_return (invoke "f") (i64.const -21))
+(assert_return (invoke "load" (i32.const 0x12)) (i32.const 0x2a))
+(assert_return (invoke "call" (i32.const 3)) (i32.const 7))
+(assert_trap (invoke "call" (i32.const 2)) "uninitialized element")
+
+;; A segment whose computed offset is out of bounds fails to instantiate.
+(assert_trap
+  (module
+    (memory 1)
+    (data (offset (i32.mul (i32.const 0x100) (i32.const 0x100))) "\00"))
+  "out of bounds memory access"
+)
+
+(assert_invalid
+  (module (global i32 (i32.add (i32.const 0) (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i64 (i64.mul (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i32 (i64.add (i64.const 0) (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i32 (i32.div_s (i32.const 1) (i32.const 1))))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global f32 (f32.add (f32.const 0) (f32.const 0))))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global $g (mut i32) (i32.const 0)) (global i32 (i32.add (global.get $g) (i32.const 1))))
+  "constant expression required"
+)
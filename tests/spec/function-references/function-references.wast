@@ -0,0 +1,151 @@
+;; Typed function references, which are called with `call_ref`
+
+(module $M
+  (type $unop (func (param i32) (result i32)))
+  (type $unop2 (func (param i32) (result i32)))
+  (type $apply (func (param (ref $unop) i32) (result i32)))
+
+  (func $inc (export "inc") (type $unop) (i32.add (local.get 0) (i32.const 1)))
+  (func $dec (type $unop2) (i32.sub (local.get 0) (i32.const 1)))
+  (elem declare func $inc $dec)
+
+  (global $inc (ref $unop) (ref.func $inc))
+  (global $null (export "null") (ref null $unop) (ref.null $unop))
+  (table $funcs 2 (ref $unop) (ref.func $inc))
+
+  (func (export "call") (param i32) (result i32)
+    (call_ref $unop (local.get 0) (global.get $inc)))
+  (func (export "call-null") (result i32)
+    (call_ref $unop (i32.const 0) (global.get $null)))
+  ;; Equivalent types are the same type.
+  (func (export "call-equivalent") (param i32) (result i32)
+    (call_ref $unop (local.get 0) (ref.func $dec)))
+  (func (export "call-table") (param i32) (result i32)
+    (call_ref $unop (local.get 0) (table.get $funcs (i32.const 1))))
+  (func $twice (type $apply)
+    (call_ref $unop (call_ref $unop (local.get 1) (local.get 0)) (local.get 0)))
+  (func (export "twice") (param i32) (result i32)
+    (call $twice (ref.func $inc) (local.get 0)))
+  (func (export "tail") (param i32) (result i32)
+    (return_call_ref $unop (local.get 0) (ref.func $dec)))
+
+  (func (export "as-non-null") (param funcref) (result i32)
+    (ref.is_null (ref.as_non_null (local.get 0))))
+  (func $on-null (export "on-null") (param (ref null $unop)) (result i32)
+    (block $null
+      (return (call_ref $unop (i32.const 10) (br_on_null $null (local.get 0)))))
+    (i32.const -1))
+  (func $on-non-null (export "on-non-null") (param (ref null $unop)) (result i32)
+    (i32.const 20)
+    (block $non-null (param i32) (result i32 (ref $unop))
+      (br_on_non_null $non-null (local.get 0))
+      (drop)
+      (return (i32.const -1)))
+    (call_ref $unop))
+  (func (export "on-null-inc") (result i32) (call $on-null (ref.func $inc)))
+  (func (export "on-non-null-inc") (result i32) (call $on-non-null (ref.func $inc)))
+
+  ;; Non-nullable locals are initialized by a `local.set` in the same block.
+  (func (export "local") (param i32) (result i32)
+    (local $f (ref $unop))
+    (local.set $f (ref.func $inc))
+    (call_ref $unop (local.get 0) (local.get $f)))
+)
+(register "M" $M)
+
+(assert_return (invoke "call" (i32.const 1)) (i32.const 2))
+(assert_trap (invoke "call-null") "null function reference")
+(assert_return (invoke "call-equivalent" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "call-table" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "twice" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "tail" (i32.const 1)) (i32.const 0))
+(assert_trap (invoke "as-non-null" (ref.null func)) "null reference")
+(assert_return (invoke "on-null" (ref.null $unop)) (i32.const -1))
+(assert_return (invoke "on-non-null" (ref.null $unop)) (i32.const -1))
+(assert_return (invoke "on-null-inc") (i32.const 11))
+(assert_return (invoke "on-non-null-inc") (i32.const 21))
+(assert_return (invoke "local" (i32.const 5)) (i32.const 6))
+
+;; Imports of functions and globals are matched up to type equivalence.
+(module
+  (type $t (func (param i32) (result i32)))
+  (type $g (func (param (ref $t))))
+  (import "M" "inc" (func (type $t)))
+  (import "M" "null" (global (ref null $t)))
+)
+(assert_unlinkable
+  (module (type $t (func)) (import "M" "null" (global (ref null $t))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (type $t (func (param i32) (result i32))) (import "M" "null" (global (ref $t))))
+  "incompatible import type"
+)
+
+;; Typed references are subtypes of their abstract type, but not the other
+;; way around, and nullable references are not non-nullable ones.
+(module
+  (type $t (func))
+  (func $f (type $t))
+  (elem declare func $f)
+  (func (result funcref) (ref.func $f))
+  (func (result (ref func)) (ref.func $f))
+  (func (result (ref null $t)) (ref.func $f))
+)
+(assert_invalid
+  (module (type $t (func)) (func (param funcref) (result (ref null $t)) (local.get 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (type $t (func)) (func (param (ref null $t)) (result (ref $t)) (local.get 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (type $t (func))
+    (type $u (func (param i32)))
+    (func (param (ref $u)) (call_ref $t (local.get 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (type $t (func)) (func (param externref) (call_ref $t (local.get 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (param i32) (drop (ref.as_non_null (local.get 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (param funcref) (block (br_on_non_null 0 (local.get 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (param (ref null 1))))
+  "unknown type"
+)
+(assert_invalid
+  (module (type (func (param (ref 1)))) (type (func)))
+  "unknown type"
+)
+
+;; Non-nullable locals must be set before they are read, and tables of
+;; non-nullable references need an initializer.
+(assert_invalid
+  (module (func (local (ref func)) (drop (local.get 0))))
+  "uninitialized local"
+)
+(assert_invalid
+  (module
+    (func (param funcref) (local (ref func))
+      (block (local.set 1 (ref.as_non_null (local.get 0))))
+      (drop (local.get 1))))
+  "uninitialized local"
+)
+(assert_invalid
+  (module (type $t (func)) (table 1 (ref $t)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (type $t (func)) (table 1 (ref $t) (ref.null $t)))
+  "type mismatch"
+)
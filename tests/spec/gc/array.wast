@@ -0,0 +1,139 @@
+;; Arrays, which are allocated from operands, data and element segments
+
+(module
+  (type $vec (array f32))
+  (type $mvec (array (mut f32)))
+  (type $bytes (array (mut i8)))
+  (type $funcs (array (mut funcref)))
+  (type $f (func (result i32)))
+
+  (global (ref $vec) (array.new $vec (f32.const 1) (i32.const 3)))
+  (global (ref $vec) (array.new_default $vec (i32.const 3)))
+  (global (ref $vec) (array.new_fixed $vec 2 (f32.const 1) (f32.const 2)))
+
+  (data $d "\01\02\03\04\05\06\07\08")
+  (elem $e func $f $g)
+  (func $f (type $f) (i32.const 1))
+  (func $g (type $f) (i32.const 2))
+
+  (func (export "new") (result anyref)
+    (array.new_default $vec (i32.const 3)))
+
+  (func $get (param $i i32) (param $v (ref $vec)) (result f32)
+    (array.get $vec (local.get $v) (local.get $i)))
+  (func (export "get") (param $i i32) (result f32)
+    (call $get (local.get $i) (global.get 0)))
+  (func (export "get_fixed") (param $i i32) (result f32)
+    (call $get (local.get $i) (global.get 2)))
+  (func (export "get_null") (result f32)
+    (array.get $vec (ref.null $vec) (i32.const 0)))
+
+  (func $set_get (param $v (ref $mvec)) (param $i i32) (param $y f32) (result f32)
+    (array.set $mvec (local.get $v) (local.get $i) (local.get $y))
+    (array.get $mvec (local.get $v) (local.get $i)))
+  (func (export "set_get") (param $i i32) (param $y f32) (result f32)
+    (call $set_get (array.new_default $mvec (i32.const 3)) (local.get $i) (local.get $y)))
+
+  (func (export "len") (result i32)
+    (array.len (global.get 1)))
+  (func (export "len_null") (result i32)
+    (array.len (ref.null $vec)))
+  (func (export "new_too_large") (result anyref)
+    (array.new_default $vec (i32.const -1)))
+
+  (func (export "fill") (param $i i32) (param $n i32) (result i32 i32 i32)
+    (local $a (ref $bytes))
+    (local.set $a (array.new_default $bytes (i32.const 3)))
+    (array.fill $bytes (local.get $a) (local.get $i) (i32.const 0xf0f) (local.get $n))
+    (array.get_s $bytes (local.get $a) (i32.const 0))
+    (array.get_u $bytes (local.get $a) (i32.const 1))
+    (array.get_u $bytes (local.get $a) (i32.const 2)))
+
+  (func (export "copy") (param $d i32) (param $s i32) (param $n i32) (result i32 i32 i32 i32)
+    (local $a (ref $bytes))
+    (local.set $a (array.new_data $bytes $d (i32.const 0) (i32.const 4)))
+    (array.copy $bytes $bytes
+      (local.get $a) (local.get $d) (local.get $a) (local.get $s) (local.get $n))
+    (array.get_u $bytes (local.get $a) (i32.const 0))
+    (array.get_u $bytes (local.get $a) (i32.const 1))
+    (array.get_u $bytes (local.get $a) (i32.const 2))
+    (array.get_u $bytes (local.get $a) (i32.const 3)))
+
+  (func (export "new_data") (param $o i32) (param $n i32) (result i32)
+    (array.len (array.new_data $bytes $d (local.get $o) (local.get $n))))
+  (func (export "init_data") (param $d i32) (param $s i32) (param $n i32) (result i32)
+    (local $a (ref $bytes))
+    (local.set $a (array.new_default $bytes (i32.const 4)))
+    (array.init_data $bytes $d (local.get $a) (local.get $d) (local.get $s) (local.get $n))
+    (array.get_u $bytes (local.get $a) (i32.const 3)))
+  (func (export "drop_data") (data.drop $d))
+
+  (func (export "new_elem") (param $i i32) (result i32)
+    (call_ref $f
+      (ref.cast (ref $f)
+        (array.get $funcs
+          (array.new_elem $funcs $e (i32.const 0) (i32.const 2))
+          (local.get $i)))))
+  (func (export "init_elem") (param $n i32) (result i32)
+    (local $a (ref $funcs))
+    (local.set $a (array.new_default $funcs (i32.const 2)))
+    (array.init_elem $funcs $e (local.get $a) (i32.const 0) (i32.const 0) (local.get $n))
+    (ref.is_null (array.get $funcs (local.get $a) (i32.const 1))))
+)
+
+(assert_return (invoke "new") (ref.array))
+(assert_return (invoke "get" (i32.const 0)) (f32.const 1))
+(assert_return (invoke "get" (i32.const 2)) (f32.const 1))
+(assert_trap (invoke "get" (i32.const 3)) "out of bounds array access")
+(assert_return (invoke "get_fixed" (i32.const 1)) (f32.const 2))
+(assert_trap (invoke "get_null") "null array reference")
+(assert_return (invoke "set_get" (i32.const 1) (f32.const 7)) (f32.const 7))
+(assert_trap (invoke "set_get" (i32.const 10) (f32.const 7)) "out of bounds array access")
+(assert_return (invoke "len") (i32.const 3))
+(assert_trap (invoke "len_null") "null array reference")
+(assert_trap (invoke "new_too_large") "allocation too large")
+
+(assert_return (invoke "fill" (i32.const 1) (i32.const 2))
+  (i32.const 0) (i32.const 0x0f) (i32.const 0x0f))
+(assert_return (invoke "fill" (i32.const 3) (i32.const 0))
+  (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_trap (invoke "fill" (i32.const 2) (i32.const 2)) "out of bounds array access")
+
+(assert_return (invoke "copy" (i32.const 1) (i32.const 0) (i32.const 3))
+  (i32.const 1) (i32.const 1) (i32.const 2) (i32.const 3))
+(assert_return (invoke "copy" (i32.const 0) (i32.const 1) (i32.const 3))
+  (i32.const 2) (i32.const 3) (i32.const 4) (i32.const 4))
+(assert_trap (invoke "copy" (i32.const 0) (i32.const 2) (i32.const 3))
+  "out of bounds array access")
+
+(assert_return (invoke "new_data" (i32.const 2) (i32.const 6)) (i32.const 6))
+(assert_trap (invoke "new_data" (i32.const 2) (i32.const 7)) "out of bounds memory access")
+(assert_return (invoke "init_data" (i32.const 0) (i32.const 4) (i32.const 4)) (i32.const 8))
+(assert_trap (invoke "init_data" (i32.const 1) (i32.const 0) (i32.const 4))
+  "out of bounds array access")
+(assert_return (invoke "drop_data"))
+(assert_trap (invoke "new_data" (i32.const 0) (i32.const 1)) "out of bounds memory access")
+(assert_return (invoke "new_data" (i32.const 0) (i32.const 0)) (i32.const 0))
+
+(assert_return (invoke "new_elem" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "init_elem" (i32.const 2)) (i32.const 0))
+(assert_return (invoke "init_elem" (i32.const 1)) (i32.const 1))
+(assert_trap (invoke "init_elem" (i32.const 3)) "out of bounds array access")
+
+(assert_invalid
+  (module
+    (type $a (array i32))
+    (func (param (ref $a)) (array.set $a (local.get 0) (i32.const 0) (i32.const 1))))
+  "array is immutable")
+(assert_invalid
+  (module
+    (type $a (array (mut anyref)))
+    (data $d "")
+    (func (result anyref) (array.new_data $a $d (i32.const 0) (i32.const 0))))
+  "array type is not numeric or vector")
+(assert_invalid
+  (module
+    (type $a (array (mut i32)))
+    (elem $e func)
+    (func (result anyref) (array.new_elem $a $e (i32.const 0) (i32.const 0))))
+  "type mismatch")
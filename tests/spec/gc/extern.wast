@@ -0,0 +1,24 @@
+;; Conversions between external references and the `any` hierarchy
+
+(module
+  (type $s (struct))
+  (global $s (ref $s) (struct.new $s))
+  (global $e externref (extern.convert_any (global.get $s)))
+
+  (func (export "internalize") (param externref) (result anyref)
+    (any.convert_extern (local.get 0)))
+  (func (export "externalize") (param anyref) (result externref)
+    (extern.convert_any (local.get 0)))
+  (func (export "externalize-i31") (result externref)
+    (extern.convert_any (ref.i31 (i32.const 1))))
+  (func (export "round-trip") (result i32)
+    (ref.eq (global.get $s)
+      (ref.cast (ref eq) (any.convert_extern (global.get $e)))))
+)
+
+(assert_return (invoke "internalize" (ref.extern 1)) (ref.host 1))
+(assert_return (invoke "internalize" (ref.null extern)) (ref.null any))
+(assert_return (invoke "externalize" (ref.host 2)) (ref.extern 2))
+(assert_return (invoke "externalize" (ref.null any)) (ref.null extern))
+(assert_return (invoke "externalize-i31") (ref.extern))
+(assert_return (invoke "round-trip") (i32.const 1))
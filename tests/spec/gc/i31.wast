@@ -0,0 +1,44 @@
+;; Unboxed 31-bit integers
+
+(module
+  (global $i (ref i31) (ref.i31 (i32.const 42)))
+  (global $m (mut (ref null i31)) (ref.i31 (i32.const -1)))
+
+  (func (export "new") (param i32) (result (ref i31))
+    (ref.i31 (local.get 0)))
+  (func (export "get_u") (param i32) (result i32)
+    (i31.get_u (ref.i31 (local.get 0))))
+  (func (export "get_s") (param i32) (result i32)
+    (i31.get_s (ref.i31 (local.get 0))))
+  (func (export "get_u-null") (result i32)
+    (i31.get_u (ref.null i31)))
+  (func (export "get_s-null") (result i32)
+    (i31.get_s (ref.null i31)))
+  (func (export "get_global") (result i32 i32)
+    (i31.get_u (global.get $i))
+    (i31.get_s (global.get $m)))
+  (func (export "eq") (param i32 i32) (result i32)
+    (ref.eq (ref.i31 (local.get 0)) (ref.i31 (local.get 1))))
+)
+
+(assert_return (invoke "new" (i32.const 1)) (ref.i31))
+(assert_return (invoke "get_u" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "get_u" (i32.const 100)) (i32.const 100))
+(assert_return (invoke "get_u" (i32.const -1)) (i32.const 0x7fff_ffff))
+(assert_return (invoke "get_u" (i32.const 0x3fff_ffff)) (i32.const 0x3fff_ffff))
+(assert_return (invoke "get_u" (i32.const 0x4000_0000)) (i32.const 0x4000_0000))
+(assert_return (invoke "get_u" (i32.const 0x8000_0000)) (i32.const 0))
+(assert_return (invoke "get_s" (i32.const -1)) (i32.const -1))
+(assert_return (invoke "get_s" (i32.const 0x3fff_ffff)) (i32.const 0x3fff_ffff))
+(assert_return (invoke "get_s" (i32.const 0x4000_0000)) (i32.const -0x4000_0000))
+(assert_return (invoke "get_s" (i32.const 0x8000_0000)) (i32.const 0))
+(assert_trap (invoke "get_u-null") "null i31 reference")
+(assert_trap (invoke "get_s-null") "null i31 reference")
+(assert_return (invoke "get_global") (i32.const 42) (i32.const -1))
+(assert_return (invoke "eq" (i32.const 5) (i32.const 5)) (i32.const 1))
+(assert_return (invoke "eq" (i32.const 5) (i32.const 6)) (i32.const 0))
+(assert_return (invoke "eq" (i32.const 0) (i32.const 0x8000_0000)) (i32.const 1))
+
+(assert_invalid
+  (module (func (result i32) (i31.get_u (i32.const 0))))
+  "type mismatch")
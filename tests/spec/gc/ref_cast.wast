@@ -0,0 +1,126 @@
+;; Reference tests, casts and cast branches
+
+(module
+  (type $s (sub (struct)))
+  (type $t (sub $s (struct (field i32))))
+  (type $a (array i8))
+  (type $f (func))
+
+  (table $refs 10 anyref)
+  (elem declare func $g)
+  (func $g (type $f))
+
+  (func $init (param externref)
+    (table.set $refs (i32.const 0) (ref.null any))
+    (table.set $refs (i32.const 1) (ref.i31 (i32.const 7)))
+    (table.set $refs (i32.const 2) (struct.new_default $s))
+    (table.set $refs (i32.const 3) (struct.new_default $t))
+    (table.set $refs (i32.const 4) (array.new_default $a (i32.const 2)))
+    (table.set $refs (i32.const 5) (any.convert_extern (local.get 0)))
+    (table.set $refs (i32.const 6) (ref.null struct)))
+  (func (export "init") (param externref) (call $init (local.get 0)))
+
+  (func (export "test") (param $i i32) (result i32 i32 i32 i32 i32 i32 i32 i32 i32)
+    (local $r anyref)
+    (local.set $r (table.get $refs (local.get $i)))
+    (ref.test anyref (local.get $r))
+    (ref.test (ref any) (local.get $r))
+    (ref.test (ref eq) (local.get $r))
+    (ref.test (ref i31) (local.get $r))
+    (ref.test (ref struct) (local.get $r))
+    (ref.test (ref array) (local.get $r))
+    (ref.test (ref $s) (local.get $r))
+    (ref.test (ref $t) (local.get $r))
+    (ref.test nullref (local.get $r)))
+
+  (func (export "cast-s") (param $i i32)
+    (drop (ref.cast (ref null $s) (table.get $refs (local.get $i)))))
+  (func (export "cast-t") (param $i i32)
+    (drop (ref.cast (ref $t) (table.get $refs (local.get $i)))))
+  (func (export "cast-i31") (param $i i32) (result i32)
+    (i31.get_u (ref.cast (ref i31) (table.get $refs (local.get $i)))))
+
+  (func (export "func") (result i32 i32)
+    (ref.test (ref $f) (ref.func $g))
+    (ref.test (ref null nofunc) (ref.func $g)))
+
+  ;; `br_on_cast` returns 1 for structures, `br_on_cast_fail` 1 for
+  ;; anything else, and both 2 for the rest.
+  (func (export "br_on_cast") (param $i i32) (result i32)
+    (block $l (result (ref null struct))
+      (br_on_cast $l anyref (ref null struct) (table.get $refs (local.get $i)))
+      (drop)
+      (return (i32.const 2)))
+    (drop)
+    (i32.const 1))
+  (func (export "br_on_cast_fail") (param $i i32) (result i32)
+    (block $l (result anyref)
+      (br_on_cast_fail $l anyref (ref $t) (table.get $refs (local.get $i)))
+      (drop)
+      (return (i32.const 2)))
+    (drop)
+    (i32.const 1))
+)
+
+(invoke "init" (ref.extern 0))
+
+(assert_return (invoke "test" (i32.const 0))
+  (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)
+  (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 1))
+(assert_return (invoke "test" (i32.const 1))
+  (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 0)
+  (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_return (invoke "test" (i32.const 2))
+  (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 1)
+  (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0))
+(assert_return (invoke "test" (i32.const 3))
+  (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 1)
+  (i32.const 0) (i32.const 1) (i32.const 1) (i32.const 0))
+(assert_return (invoke "test" (i32.const 4))
+  (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0)
+  (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_return (invoke "test" (i32.const 5))
+  (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0)
+  (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_return (invoke "test" (i32.const 6))
+  (i32.const 1) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)
+  (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 1))
+
+(assert_return (invoke "cast-s" (i32.const 0)))
+(assert_trap (invoke "cast-s" (i32.const 1)) "cast failure")
+(assert_return (invoke "cast-s" (i32.const 2)))
+(assert_return (invoke "cast-s" (i32.const 3)))
+(assert_trap (invoke "cast-s" (i32.const 4)) "cast failure")
+(assert_trap (invoke "cast-t" (i32.const 0)) "cast failure")
+(assert_trap (invoke "cast-t" (i32.const 2)) "cast failure")
+(assert_return (invoke "cast-t" (i32.const 3)))
+(assert_return (invoke "cast-i31" (i32.const 1)) (i32.const 7))
+(assert_trap (invoke "cast-i31" (i32.const 5)) "cast failure")
+
+(assert_return (invoke "func") (i32.const 1) (i32.const 0))
+
+(assert_return (invoke "br_on_cast" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "br_on_cast" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "br_on_cast" (i32.const 3)) (i32.const 1))
+(assert_return (invoke "br_on_cast" (i32.const 4)) (i32.const 2))
+(assert_return (invoke "br_on_cast_fail" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "br_on_cast_fail" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "br_on_cast_fail" (i32.const 3)) (i32.const 2))
+
+(assert_invalid
+  (module (func (param funcref) (result i32) (ref.test (ref any) (local.get 0))))
+  "type mismatch")
+(assert_invalid
+  (module
+    (func (param anyref) (result anyref)
+      (block $l (result (ref i31))
+        (br_on_cast $l anyref (ref null i31) (local.get 0))
+        (drop)
+        (unreachable))))
+  "type mismatch")
+(assert_invalid
+  (module
+    (func (param structref) (result anyref)
+      (block $l (result anyref)
+        (br_on_cast $l structref (ref i31) (local.get 0)))))
+  "type mismatch")
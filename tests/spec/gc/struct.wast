@@ -0,0 +1,96 @@
+;; Structures, which are allocated with `struct.new` and `struct.new_default`
+
+(module
+  (type $vec (struct (field f32) (field $y (mut f32)) (field $z f32)))
+  (type $packed (struct (field $a (mut i8)) (field $b (mut i16))))
+
+  (global (ref $vec) (struct.new $vec (f32.const 1) (f32.const 2) (f32.const 3)))
+  (global (ref $vec) (struct.new_default $vec))
+
+  (func (export "new") (result anyref)
+    (struct.new_default $vec))
+
+  (func $get_0_0 (param $v (ref $vec)) (result f32)
+    (struct.get $vec 0 (local.get $v)))
+  (func (export "get_0_0") (result f32)
+    (call $get_0_0 (global.get 0)))
+  (func $get_vec_0 (param $v (ref $vec)) (result f32)
+    (struct.get $vec 0 (local.get $v)))
+  (func (export "get_vec_0") (result f32)
+    (call $get_vec_0 (global.get 1)))
+  (func $get_y (param $v (ref null $vec)) (result f32)
+    (struct.get $vec $y (local.get $v)))
+  (func (export "get_y") (result f32)
+    (call $get_y (global.get 0)))
+  (func (export "get_y_null") (result f32)
+    (call $get_y (ref.null $vec)))
+
+  (func $set_get_y (param $v (ref null $vec)) (param $y f32) (result f32)
+    (struct.set $vec $y (local.get $v) (local.get $y))
+    (struct.get $vec $y (local.get $v)))
+  (func (export "set_get_y") (param $y f32) (result f32)
+    (call $set_get_y (global.get 1) (local.get $y)))
+  (func (export "set_get_y_null") (param $y f32) (result f32)
+    (call $set_get_y (ref.null $vec) (local.get $y)))
+
+  ;; Packed fields are wrapped when written, and extended when read.
+  (func (export "packed") (param i32) (result i32 i32 i32 i32)
+    (local $p (ref $packed))
+    (local.set $p (struct.new $packed (local.get 0) (local.get 0)))
+    (struct.get_s $packed $a (local.get $p))
+    (struct.get_u $packed $a (local.get $p))
+    (struct.get_s $packed $b (local.get $p))
+    (struct.get_u $packed $b (local.get $p)))
+  (func (export "packed-set") (param i32) (result i32)
+    (local $p (ref $packed))
+    (local.set $p (struct.new_default $packed))
+    (struct.set $packed $a (local.get $p) (local.get 0))
+    (struct.get_u $packed $a (local.get $p)))
+)
+
+(assert_return (invoke "new") (ref.struct))
+(assert_return (invoke "get_0_0") (f32.const 1))
+(assert_return (invoke "get_vec_0") (f32.const 0))
+(assert_return (invoke "get_y") (f32.const 2))
+(assert_trap (invoke "get_y_null") "null structure reference")
+(assert_return (invoke "set_get_y" (f32.const 7)) (f32.const 7))
+(assert_trap (invoke "set_get_y_null" (f32.const 7)) "null structure reference")
+(assert_return (invoke "packed" (i32.const 0x1_8180))
+  (i32.const -128) (i32.const 0x80) (i32.const -32384) (i32.const 0x8180))
+(assert_return (invoke "packed-set" (i32.const 0x1ff)) (i32.const 0xff))
+
+(assert_invalid
+  (module
+    (type $s (struct (field i32)))
+    (func (param (ref $s)) (struct.set $s 0 (local.get 0) (i32.const 1))))
+  "field is immutable")
+(assert_invalid
+  (module
+    (type $s (struct (field i8)))
+    (func (param (ref $s)) (result i32) (struct.get $s 0 (local.get 0))))
+  "type mismatch")
+(assert_invalid
+  (module
+    (type $s (struct (field i32)))
+    (func (param (ref $s)) (result i32) (struct.get_s $s 0 (local.get 0))))
+  "type mismatch")
+(assert_invalid
+  (module
+    (type $s (struct (field i32)))
+    (func (param (ref $s)) (result i32) (struct.get $s 1 (local.get 0))))
+  "unknown field")
+(assert_invalid
+  (module
+    (type $s (struct (field (ref any))))
+    (func (result anyref) (struct.new_default $s)))
+  "type mismatch")
+(assert_invalid
+  (module
+    (type $f (func))
+    (func (result anyref) (struct.new $f)))
+  "type mismatch")
+(assert_invalid
+  (module
+    (type $s (struct (field i64)))
+    (func (result anyref) (struct.new $s (i32.const 0))))
+  "type mismatch")
@@ -0,0 +1,79 @@
+;; Recursive types, declared supertypes and their checks
+
+(module
+  (rec
+    (type $list (sub (struct (field i32) (field (ref null $list)))))
+    (type $node (sub $list (struct (field i32) (field (ref null $list)) (field f32)))))
+  (type $f1 (sub (func (param (ref $node)) (result anyref))))
+  (type $f2 (sub $f1 (func (param (ref $list)) (result (ref eq)))))
+
+  (func $f2 (type $f2) (struct.new_default $list))
+  (elem declare func $f2)
+
+  ;; Functions of a subtype can be called through their supertype.
+  (func (export "call") (result i32)
+    (ref.test (ref $list)
+      (call_ref $f1
+        (struct.new_default $node)
+        (ref.func $f2))))
+
+  (func (export "length") (param $n i32) (result i32)
+    (local $l (ref null $list))
+    (local $len i32)
+    (loop $build
+      (if (local.get $n)
+        (then
+          (local.set $l (struct.new $node (local.get $n) (local.get $l) (f32.const 0)))
+          (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+          (br $build))))
+    (block $done
+      (loop $walk
+        (br_if $done (ref.is_null (local.get $l)))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (local.set $l (struct.get $list 1 (local.get $l)))
+        (br $walk)))
+    (local.get $len))
+)
+
+(assert_return (invoke "call") (i32.const 1))
+(assert_return (invoke "length" (i32.const 10)) (i32.const 10))
+
+;; Equivalent recursion groups define the same types, in every module.
+(module $A
+  (rec (type $t (struct (field (ref null $t)))))
+  (func (export "new") (result (ref null $t)) (struct.new_default $t)))
+(register "A" $A)
+(module
+  (rec (type $u (struct (field (ref null $u)))))
+  (func (import "A" "new") (result (ref null $u))))
+(assert_unlinkable
+  (module
+    (rec (type $u (struct (field (mut (ref null $u))))))
+    (func (import "A" "new") (result (ref null $u))))
+  "incompatible import type")
+
+(assert_invalid
+  (module
+    (type $s (struct))
+    (type (sub $s (struct))))
+  "sub type")
+(assert_invalid
+  (module
+    (type $s (sub (struct (field i32))))
+    (type (sub $s (struct (field i64)))))
+  "sub type")
+(assert_invalid
+  (module
+    (type $s (sub (struct (field (mut i32)))))
+    (type (sub $s (struct (field i32)))))
+  "sub type")
+(assert_invalid
+  (module
+    (type $f (sub (func (param anyref))))
+    (type (sub $f (func (param eqref)))))
+  "sub type")
+(assert_invalid
+  (module
+    (type (sub 1 (struct)))
+    (type (sub (struct))))
+  "unknown type")
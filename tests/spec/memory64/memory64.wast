@@ -0,0 +1,149 @@
+;; 64-bit memories and tables, which are indexed with i64 addresses
+
+(module $M
+  (memory (export "heap") i64 1 2)
+  (table (export "funcs") i64 2 funcref)
+  (data (i64.const 0) "\01\02\03\04")
+  (elem (table 0) (i64.const 1) func $seven)
+  (data $passive "\11\22\33\44")
+  (memory $small 1)
+
+  (func $seven (result i32) (i32.const 7))
+
+  (func (export "load") (param i64) (result i32) (i32.load (local.get 0)))
+  (func (export "load-offset") (param i64) (result i32)
+    (i32.load offset=0xfff0 (local.get 0)))
+  (func (export "load-far") (param i64) (result i32)
+    (i32.load offset=0xffffffffffff0000 (local.get 0)))
+  (func (export "store8") (param i64 i32) (i32.store8 (local.get 0) (local.get 1)))
+  (func (export "v128-load") (param i64) (result i32)
+    (i32x4.extract_lane 0 (v128.load (local.get 0))))
+  (func (export "rmw") (param i64 i32) (result i32)
+    (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+
+  (func (export "size") (result i64) (memory.size))
+  (func (export "grow") (param i64) (result i64) (memory.grow (local.get 0)))
+  (func (export "fill") (param i64 i32 i64)
+    (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy") (param i64 i64 i64)
+    (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy-to-small") (param i32 i64 i32)
+    (memory.copy $small 0 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "load-small") (param i32) (result i32) (i32.load $small (local.get 0)))
+  (func (export "init") (param i64 i32 i32)
+    (memory.init $passive (local.get 0) (local.get 1) (local.get 2)))
+
+  (func (export "table-size") (result i64) (table.size))
+  (func (export "table-grow") (param i64) (result i64)
+    (table.grow (ref.null func) (local.get 0)))
+  (func (export "call") (param i64) (result i32)
+    (call_indirect (result i32) (local.get 0)))
+  (func (export "is-null") (param i64) (result i32)
+    (ref.is_null (table.get (local.get 0))))
+)
+(register "M" $M)
+
+(assert_return (invoke "load" (i64.const 0)) (i32.const 0x04030201))
+(assert_return (invoke "load" (i64.const 0xfffc)) (i32.const 0))
+(assert_return (invoke "load-offset" (i64.const 0xc)) (i32.const 0))
+(assert_trap (invoke "load" (i64.const 0xfffd)) "out of bounds memory access")
+(assert_trap (invoke "load-offset" (i64.const 0xd)) "out of bounds memory access")
+(assert_trap (invoke "load" (i64.const 0x100000000)) "out of bounds memory access")
+(assert_trap (invoke "load" (i64.const -1)) "out of bounds memory access")
+(assert_trap (invoke "load-far" (i64.const 0x10000)) "out of bounds memory access")
+(assert_trap (invoke "store8" (i64.const 0x10000) (i32.const 1)) "out of bounds memory access")
+
+(invoke "store8" (i64.const 4) (i32.const 0x05))
+(assert_return (invoke "v128-load" (i64.const 1)) (i32.const 0x05040302))
+(assert_return (invoke "rmw" (i64.const 4) (i32.const 1)) (i32.const 5))
+(assert_return (invoke "load" (i64.const 4)) (i32.const 6))
+
+;; Sizes and growth results are i64s, and a failed growth returns -1.
+(assert_return (invoke "size") (i64.const 1))
+(assert_return (invoke "grow" (i64.const 0x100000001)) (i64.const -1))
+(assert_return (invoke "grow" (i64.const 1)) (i64.const 1))
+(assert_return (invoke "grow" (i64.const 1)) (i64.const -1))
+(assert_return (invoke "size") (i64.const 2))
+(assert_return (invoke "load" (i64.const 0x1fffc)) (i32.const 0))
+
+;; Bulk ops take i64 addresses and lengths, except for the length of a copy to
+;; a 32-bit memory, and the source and length of `memory.init`.
+(invoke "fill" (i64.const 0x100) (i32.const 0xaa) (i64.const 3))
+(assert_return (invoke "load" (i64.const 0x100)) (i32.const 0x00aaaaaa))
+(assert_trap (invoke "fill" (i64.const 0) (i32.const 0) (i64.const 0x100000000)) "out of bounds memory access")
+(invoke "copy" (i64.const 0x101) (i64.const 0) (i64.const 2))
+(assert_return (invoke "load" (i64.const 0x100)) (i32.const 0x000201aa))
+(assert_trap (invoke "copy" (i64.const 0) (i64.const -1) (i64.const 1)) "out of bounds memory access")
+(invoke "copy-to-small" (i32.const 8) (i64.const 0) (i32.const 4))
+(assert_return (invoke "load-small" (i32.const 8)) (i32.const 0x04030201))
+(invoke "init" (i64.const 0x200) (i32.const 1) (i32.const 3))
+(assert_return (invoke "load" (i64.const 0x200)) (i32.const 0x00443322))
+(assert_trap (invoke "init" (i64.const 0x1ffff) (i32.const 0) (i32.const 2)) "out of bounds memory access")
+
+;; Tables work the same way.
+(assert_return (invoke "table-size") (i64.const 2))
+(assert_return (invoke "call" (i64.const 1)) (i32.const 7))
+(assert_trap (invoke "call" (i64.const 0)) "uninitialized element")
+(assert_trap (invoke "call" (i64.const 0x100000001)) "undefined element")
+(assert_return (invoke "table-grow" (i64.const 3)) (i64.const 2))
+(assert_return (invoke "table-grow" (i64.const 0x100000000)) (i64.const -1))
+(assert_return (invoke "is-null" (i64.const 4)) (i32.const 1))
+(assert_trap (invoke "is-null" (i64.const 5)) "out of bounds table access")
+
+;; Imports only match memories and tables with the same address type.
+(module
+  (import "M" "heap" (memory i64 1))
+  (import "M" "funcs" (table i64 2 funcref))
+)
+(assert_unlinkable
+  (module (import "M" "heap" (memory 1)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "M" "funcs" (table 2 funcref)))
+  "incompatible import type"
+)
+
+;; Memories can be as large as an i64 can address.
+(module (memory i64 0 0x1000000000000))
+(assert_invalid
+  (module (memory i64 0 0x1000000000001))
+  "memory size must be at most 281474976710656"
+)
+(assert_invalid
+  (module (memory 0 0x10001))
+  "memory size must be at most 65536"
+)
+
+;; Address operands and segment offsets have the address type.
+(assert_invalid
+  (module (memory i64 1) (func (drop (i32.load (i32.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (memory 1) (func (drop (i32.load (i64.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (memory i64 1) (func (result i32) (memory.size)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (memory i64 1) (data (i32.const 0) ""))
+  "type mismatch"
+)
+(assert_invalid
+  (module (table i64 1 funcref) (func (drop (table.get (i32.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (memory i64 1)
+    (memory 1)
+    (func (memory.copy 1 0 (i32.const 0) (i64.const 0) (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (memory 1) (func (drop (i32.load offset=0x100000000 (i32.const 0)))))
+  "offset out of range"
+)
@@ -0,0 +1,103 @@
+;; Modules with several memories, which instructions access by index
+
+(module $M
+  (memory (export "heap") 1)
+  (data (i32.const 0) "\01\02\03\04")
+)
+(register "M" $M)
+
+(module
+  (import "M" "heap" (memory $heap 1))
+  (memory $scratch 1 2)
+  (memory $shared 1 1 shared)
+  (data (memory $scratch) (i32.const 8) "\aa\bb\cc\dd")
+  (data $passive "\11\22\33\44")
+
+  (func (export "load-heap") (param i32) (result i32) (i32.load $heap (local.get 0)))
+  (func (export "load-scratch") (param i32) (result i32) (i32.load $scratch (local.get 0)))
+  (func (export "load8-scratch") (param i32) (result i32) (i32.load8_u 1 (local.get 0)))
+  (func (export "store-scratch") (param i32 i32) (i32.store $scratch (local.get 0) (local.get 1)))
+  (func (export "v128-scratch") (param i32) (result i32)
+    (i32x4.extract_lane 0 (v128.load $scratch (local.get 0))))
+
+  (func (export "size-heap") (result i32) (memory.size $heap))
+  (func (export "size-scratch") (result i32) (memory.size $scratch))
+  (func (export "grow-scratch") (param i32) (result i32) (memory.grow $scratch (local.get 0)))
+
+  (func (export "fill-scratch") (param i32 i32 i32)
+    (memory.fill $scratch (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy-heap-to-scratch") (param i32 i32 i32)
+    (memory.copy $scratch $heap (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy-scratch") (param i32 i32 i32)
+    (memory.copy $scratch $scratch (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "init-scratch") (param i32 i32 i32)
+    (memory.init $scratch $passive (local.get 0) (local.get 1) (local.get 2)))
+
+  (func (export "rmw-shared") (param i32) (result i32)
+    (i32.atomic.rmw.add $shared (i32.const 0) (local.get 0)))
+  (func (export "load-shared") (result i32) (i32.atomic.load $shared (i32.const 0)))
+)
+
+;; Each memory has its own contents.
+(assert_return (invoke "load-heap" (i32.const 0)) (i32.const 0x04030201))
+(assert_return (invoke "load-scratch" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "load-scratch" (i32.const 8)) (i32.const 0xddccbbaa))
+(assert_return (invoke "load8-scratch" (i32.const 9)) (i32.const 0xbb))
+(invoke "store-scratch" (i32.const 0) (i32.const 0x12345678))
+(assert_return (invoke "load-scratch" (i32.const 0)) (i32.const 0x12345678))
+(assert_return (invoke "v128-scratch" (i32.const 0)) (i32.const 0x12345678))
+(assert_return (invoke "load-heap" (i32.const 0)) (i32.const 0x04030201))
+
+;; And its own size.
+(assert_return (invoke "size-heap") (i32.const 1))
+(assert_return (invoke "grow-scratch" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "grow-scratch" (i32.const 1)) (i32.const -1))
+(assert_return (invoke "size-scratch") (i32.const 2))
+(assert_return (invoke "size-heap") (i32.const 1))
+(assert_return (invoke "load-scratch" (i32.const 0x1fffc)) (i32.const 0))
+(assert_trap (invoke "load-heap" (i32.const 0x10000)) "out of bounds memory access")
+
+;; Bulk ops only touch the memories they name.
+(invoke "fill-scratch" (i32.const 0) (i32.const 0xff) (i32.const 2))
+(assert_return (invoke "load-scratch" (i32.const 0)) (i32.const 0x1234ffff))
+(invoke "copy-heap-to-scratch" (i32.const 1) (i32.const 0) (i32.const 3))
+(assert_return (invoke "load-scratch" (i32.const 0)) (i32.const 0x030201ff))
+(invoke "copy-scratch" (i32.const 9) (i32.const 8) (i32.const 3))
+(assert_return (invoke "load-scratch" (i32.const 8)) (i32.const 0xccbbaaaa))
+(invoke "init-scratch" (i32.const 4) (i32.const 1) (i32.const 3))
+(assert_return (invoke "load-scratch" (i32.const 4)) (i32.const 0x00443322))
+(assert_return (invoke "load-heap" (i32.const 0)) (i32.const 0x04030201))
+(assert_trap (invoke "copy-heap-to-scratch" (i32.const 0x1ffff) (i32.const 0) (i32.const 2)) "out of bounds memory access")
+(assert_trap (invoke "copy-heap-to-scratch" (i32.const 0) (i32.const 0xffff) (i32.const 2)) "out of bounds memory access")
+
+;; Atomic accesses too.
+(assert_return (invoke "rmw-shared" (i32.const 5)) (i32.const 0))
+(assert_return (invoke "load-shared") (i32.const 5))
+(assert_return (invoke "load-scratch" (i32.const 0)) (i32.const 0x030201ff))
+
+;; The exported memories are the ones of the module.
+(module
+  (memory (export "a") 1)
+  (memory (export "b") 2)
+)
+(module
+  (import "M" "heap" (memory 1))
+  (memory 0)
+)
+
+(assert_invalid
+  (module (memory 1) (func (drop (i32.load 1 (i32.const 0)))))
+  "unknown memory 1"
+)
+(assert_invalid
+  (module (memory 1) (memory 1) (func (memory.copy 0 2 (i32.const 0) (i32.const 0) (i32.const 0))))
+  "unknown memory 2"
+)
+(assert_invalid
+  (module (memory 1) (func (drop (i32.atomic.load 1 (i32.const 0)))))
+  "unknown memory 1"
+)
+(assert_invalid
+  (module (memory 1) (data (memory 1) (i32.const 0) ""))
+  "unknown memory 1"
+)
@@ -0,0 +1,88 @@
+;; Relaxed SIMD instructions, whose results may be any of the allowed ones
+
+(module
+  (func (export "i8x16.relaxed_swizzle") (param v128 v128) (result v128) (i8x16.relaxed_swizzle (local.get 0) (local.get 1)))
+  (func (export "i32x4.relaxed_trunc_f32x4_s") (param v128) (result v128) (i32x4.relaxed_trunc_f32x4_s (local.get 0)))
+  (func (export "i32x4.relaxed_trunc_f32x4_u") (param v128) (result v128) (i32x4.relaxed_trunc_f32x4_u (local.get 0)))
+  (func (export "i32x4.relaxed_trunc_f64x2_s_zero") (param v128) (result v128) (i32x4.relaxed_trunc_f64x2_s_zero (local.get 0)))
+  (func (export "f32x4.relaxed_madd") (param v128 v128 v128) (result v128) (f32x4.relaxed_madd (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "f64x2.relaxed_nmadd") (param v128 v128 v128) (result v128) (f64x2.relaxed_nmadd (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i8x16.relaxed_laneselect") (param v128 v128 v128) (result v128) (i8x16.relaxed_laneselect (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i64x2.relaxed_laneselect") (param v128 v128 v128) (result v128) (i64x2.relaxed_laneselect (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "f32x4.relaxed_min") (param v128 v128) (result v128) (f32x4.relaxed_min (local.get 0) (local.get 1)))
+  (func (export "f64x2.relaxed_max") (param v128 v128) (result v128) (f64x2.relaxed_max (local.get 0) (local.get 1)))
+  (func (export "i16x8.relaxed_q15mulr_s") (param v128 v128) (result v128) (i16x8.relaxed_q15mulr_s (local.get 0) (local.get 1)))
+  (func (export "i16x8.relaxed_dot_i8x16_i7x16_s") (param v128 v128) (result v128) (i16x8.relaxed_dot_i8x16_i7x16_s (local.get 0) (local.get 1)))
+  (func (export "i32x4.relaxed_dot_i8x16_i7x16_add_s") (param v128 v128 v128) (result v128) (i32x4.relaxed_dot_i8x16_i7x16_add_s (local.get 0) (local.get 1) (local.get 2)))
+)
+
+(assert_return (invoke "i8x16.relaxed_swizzle"
+    (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+    (v128.const i8x16 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0))
+  (v128.const i8x16 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0))
+(assert_return (invoke "i8x16.relaxed_swizzle"
+    (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+    (v128.const i8x16 -1 -128 16 17 0 0 0 0 0 0 0 0 0 0 0 0))
+  (either (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+          (v128.const i8x16 0 0 0 1 0 0 0 0 0 0 0 0 0 0 0 0)))
+
+(assert_return (invoke "i32x4.relaxed_trunc_f32x4_s" (v128.const f32x4 -1.5 2.5 -2147483520.0 nan))
+  (either (v128.const i32x4 -1 2 -2147483520 0)
+          (v128.const i32x4 -1 2 -2147483520 0x80000000)))
+(assert_return (invoke "i32x4.relaxed_trunc_f32x4_s" (v128.const f32x4 0.0 -0.0 inf -inf))
+  (either (v128.const i32x4 0 0 0x7fffffff 0x80000000)
+          (v128.const i32x4 0 0 0x80000000 0x80000000)))
+(assert_return (invoke "i32x4.relaxed_trunc_f32x4_u" (v128.const f32x4 1.5 4294967040.0 -1.0 nan))
+  (either (v128.const i32x4 1 4294967040 0 0)
+          (v128.const i32x4 1 4294967040 0xffffffff 0xffffffff)))
+(assert_return (invoke "i32x4.relaxed_trunc_f64x2_s_zero" (v128.const f64x2 -3.9 1e10))
+  (either (v128.const i32x4 -3 0x7fffffff 0 0)
+          (v128.const i32x4 -3 0x80000000 0 0)))
+
+(assert_return (invoke "f32x4.relaxed_madd"
+    (v128.const f32x4 1.0 2.0 -3.0 0.5)
+    (v128.const f32x4 2.0 3.0 4.0 0.5)
+    (v128.const f32x4 1.0 -6.0 12.0 0.75))
+  (v128.const f32x4 3.0 0.0 0.0 1.0))
+(assert_return (invoke "f64x2.relaxed_nmadd"
+    (v128.const f64x2 0.1 2.0)
+    (v128.const f64x2 10.0 3.0)
+    (v128.const f64x2 1.0 1.0))
+  (either (v128.const f64x2 -0x1p-54 -5.0)
+          (v128.const f64x2 0.0 -5.0)))
+
+(assert_return (invoke "i8x16.relaxed_laneselect"
+    (v128.const i8x16 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1)
+    (v128.const i8x16 2 2 2 2 2 2 2 2 2 2 2 2 2 2 2 2)
+    (v128.const i8x16 -1 0 -1 0 -1 0 -1 0 -1 0 -1 0 -1 0 -1 0))
+  (v128.const i8x16 1 2 1 2 1 2 1 2 1 2 1 2 1 2 1 2))
+(assert_return (invoke "i64x2.relaxed_laneselect"
+    (v128.const i64x2 -1 -1)
+    (v128.const i64x2 0 0)
+    (v128.const i64x2 0x8000000000000000 0x00000000ffffffff))
+  (either (v128.const i64x2 0x8000000000000000 0x00000000ffffffff)
+          (v128.const i64x2 -1 0)))
+
+(assert_return (invoke "f32x4.relaxed_min" (v128.const f32x4 1.0 -2.0 nan 0.0) (v128.const f32x4 2.0 -3.0 1.0 -0.0))
+  (either (v128.const f32x4 1.0 -3.0 nan:canonical -0.0)
+          (v128.const f32x4 1.0 -3.0 1.0 -0.0)
+          (v128.const f32x4 1.0 -3.0 nan:canonical 0.0)
+          (v128.const f32x4 1.0 -3.0 1.0 0.0)))
+(assert_return (invoke "f64x2.relaxed_max" (v128.const f64x2 1.0 -inf) (v128.const f64x2 -1.0 -2.0))
+  (v128.const f64x2 1.0 -2.0))
+
+(assert_return (invoke "i16x8.relaxed_q15mulr_s"
+    (v128.const i16x8 16384 -16384 32767 -32768 0 1 2 3)
+    (v128.const i16x8 16384 16384 32767 -32768 0 1 2 3))
+  (either (v128.const i16x8 8192 -8192 32766 32767 0 0 0 0)
+          (v128.const i16x8 8192 -8192 32766 -32768 0 0 0 0)))
+
+(assert_return (invoke "i16x8.relaxed_dot_i8x16_i7x16_s"
+    (v128.const i8x16 1 2 3 4 -5 -6 7 8 -128 -128 0 0 0 0 0 0)
+    (v128.const i8x16 1 2 3 4 5 6 -7 -8 127 127 0 0 0 0 0 0))
+  (v128.const i16x8 5 25 -61 -113 -32512 0 0 0))
+(assert_return (invoke "i32x4.relaxed_dot_i8x16_i7x16_add_s"
+    (v128.const i8x16 1 2 3 4 -1 -1 -1 -1 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 5 6 7 8 1 2 3 4 0 0 0 0 0 0 0 0)
+    (v128.const i32x4 1 2 3 4))
+  (v128.const i32x4 71 -8 3 4))
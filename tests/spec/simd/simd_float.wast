@@ -0,0 +1,104 @@
+;; Float lane arithmetic, comparisons and conversions
+
+(module
+  (func (export "f32x4.add") (param v128 v128) (result v128) (f32x4.add (local.get 0) (local.get 1)))
+  (func (export "f32x4.div") (param v128 v128) (result v128) (f32x4.div (local.get 0) (local.get 1)))
+  (func (export "f32x4.min") (param v128 v128) (result v128) (f32x4.min (local.get 0) (local.get 1)))
+  (func (export "f32x4.max") (param v128 v128) (result v128) (f32x4.max (local.get 0) (local.get 1)))
+  (func (export "f32x4.pmin") (param v128 v128) (result v128) (f32x4.pmin (local.get 0) (local.get 1)))
+  (func (export "f32x4.pmax") (param v128 v128) (result v128) (f32x4.pmax (local.get 0) (local.get 1)))
+  (func (export "f32x4.abs") (param v128) (result v128) (f32x4.abs (local.get 0)))
+  (func (export "f32x4.neg") (param v128) (result v128) (f32x4.neg (local.get 0)))
+  (func (export "f32x4.sqrt") (param v128) (result v128) (f32x4.sqrt (local.get 0)))
+  (func (export "f32x4.nearest") (param v128) (result v128) (f32x4.nearest (local.get 0)))
+  (func (export "f32x4.ceil") (param v128) (result v128) (f32x4.ceil (local.get 0)))
+  (func (export "f64x2.mul") (param v128 v128) (result v128) (f64x2.mul (local.get 0) (local.get 1)))
+  (func (export "f64x2.sub") (param v128 v128) (result v128) (f64x2.sub (local.get 0) (local.get 1)))
+  (func (export "f64x2.min") (param v128 v128) (result v128) (f64x2.min (local.get 0) (local.get 1)))
+  (func (export "f64x2.floor") (param v128) (result v128) (f64x2.floor (local.get 0)))
+  (func (export "f64x2.trunc") (param v128) (result v128) (f64x2.trunc (local.get 0)))
+
+  (func (export "f32x4.eq") (param v128 v128) (result v128) (f32x4.eq (local.get 0) (local.get 1)))
+  (func (export "f32x4.ne") (param v128 v128) (result v128) (f32x4.ne (local.get 0) (local.get 1)))
+  (func (export "f64x2.lt") (param v128 v128) (result v128) (f64x2.lt (local.get 0) (local.get 1)))
+  (func (export "f64x2.ge") (param v128 v128) (result v128) (f64x2.ge (local.get 0) (local.get 1)))
+
+  (func (export "i32x4.trunc_sat_f32x4_s") (param v128) (result v128) (i32x4.trunc_sat_f32x4_s (local.get 0)))
+  (func (export "i32x4.trunc_sat_f32x4_u") (param v128) (result v128) (i32x4.trunc_sat_f32x4_u (local.get 0)))
+  (func (export "i32x4.trunc_sat_f64x2_s_zero") (param v128) (result v128) (i32x4.trunc_sat_f64x2_s_zero (local.get 0)))
+  (func (export "i32x4.trunc_sat_f64x2_u_zero") (param v128) (result v128) (i32x4.trunc_sat_f64x2_u_zero (local.get 0)))
+  (func (export "f32x4.convert_i32x4_s") (param v128) (result v128) (f32x4.convert_i32x4_s (local.get 0)))
+  (func (export "f32x4.convert_i32x4_u") (param v128) (result v128) (f32x4.convert_i32x4_u (local.get 0)))
+  (func (export "f64x2.convert_low_i32x4_s") (param v128) (result v128) (f64x2.convert_low_i32x4_s (local.get 0)))
+  (func (export "f64x2.convert_low_i32x4_u") (param v128) (result v128) (f64x2.convert_low_i32x4_u (local.get 0)))
+  (func (export "f32x4.demote_f64x2_zero") (param v128) (result v128) (f32x4.demote_f64x2_zero (local.get 0)))
+  (func (export "f64x2.promote_low_f32x4") (param v128) (result v128) (f64x2.promote_low_f32x4 (local.get 0)))
+)
+
+(assert_return (invoke "f32x4.add" (v128.const f32x4 1 inf 0x1p127 nan) (v128.const f32x4 2 -inf 0x1p127 1))
+  (v128.const f32x4 3 nan:canonical inf nan:canonical))
+(assert_return (invoke "f32x4.div" (v128.const f32x4 1 -1 0 1) (v128.const f32x4 0 0 0 4))
+  (v128.const f32x4 inf -inf nan:canonical 0.25))
+(assert_return (invoke "f32x4.min" (v128.const f32x4 0 -0 nan 1) (v128.const f32x4 -0 0 1 2))
+  (v128.const f32x4 -0 -0 nan:canonical 1))
+(assert_return (invoke "f32x4.max" (v128.const f32x4 0 -0 1 nan) (v128.const f32x4 -0 0 nan 2))
+  (v128.const f32x4 0 0 nan:canonical nan:canonical))
+(assert_return (invoke "f32x4.pmin" (v128.const f32x4 0 -0 nan 1) (v128.const f32x4 -0 0 1 nan))
+  (v128.const f32x4 0 -0 nan 1))
+(assert_return (invoke "f32x4.pmax" (v128.const f32x4 0 -0 nan 1) (v128.const f32x4 -0 0 1 nan))
+  (v128.const f32x4 0 -0 nan 1))
+(assert_return (invoke "f32x4.abs" (v128.const f32x4 -1 -0 -nan inf))
+  (v128.const f32x4 1 0 nan inf))
+(assert_return (invoke "f32x4.neg" (v128.const f32x4 -1 0 nan inf))
+  (v128.const f32x4 1 -0 -nan -inf))
+(assert_return (invoke "f32x4.sqrt" (v128.const f32x4 4 -1 0x1p-2 inf))
+  (v128.const f32x4 2 nan:canonical 0.5 inf))
+(assert_return (invoke "f32x4.nearest" (v128.const f32x4 0.5 1.5 -2.5 -0.4))
+  (v128.const f32x4 0 2 -2 -0))
+(assert_return (invoke "f32x4.ceil" (v128.const f32x4 0.5 -0.5 -1.5 1))
+  (v128.const f32x4 1 -0 -1 1))
+(assert_return (invoke "f64x2.mul" (v128.const f64x2 1e200 -2) (v128.const f64x2 1e200 0.5))
+  (v128.const f64x2 inf -1))
+(assert_return (invoke "f64x2.sub" (v128.const f64x2 inf 3) (v128.const f64x2 inf 0.5))
+  (v128.const f64x2 nan:canonical 2.5))
+(assert_return (invoke "f64x2.min" (v128.const f64x2 -0 nan) (v128.const f64x2 0 -inf))
+  (v128.const f64x2 -0 nan:canonical))
+(assert_return (invoke "f64x2.floor" (v128.const f64x2 -0.5 1.5)) (v128.const f64x2 -1 1))
+(assert_return (invoke "f64x2.trunc" (v128.const f64x2 -0.5 1.5)) (v128.const f64x2 -0 1))
+
+(assert_return (invoke "f32x4.eq" (v128.const f32x4 0 nan 1 2) (v128.const f32x4 -0 nan 1 3))
+  (v128.const i32x4 -1 0 -1 0))
+(assert_return (invoke "f32x4.ne" (v128.const f32x4 0 nan 1 2) (v128.const f32x4 -0 nan 1 3))
+  (v128.const i32x4 0 -1 0 -1))
+(assert_return (invoke "f64x2.lt" (v128.const f64x2 -inf nan) (v128.const f64x2 inf 0))
+  (v128.const i64x2 -1 0))
+(assert_return (invoke "f64x2.ge" (v128.const f64x2 1 nan) (v128.const f64x2 1 nan))
+  (v128.const i64x2 -1 0))
+
+(assert_return (invoke "i32x4.trunc_sat_f32x4_s" (v128.const f32x4 -1.5 nan 3e9 -3e9))
+  (v128.const i32x4 -1 0 2147483647 -2147483648))
+(assert_return (invoke "i32x4.trunc_sat_f32x4_u" (v128.const f32x4 -1.5 nan 3e9 5e9))
+  (v128.const i32x4 0 0 3000000000 -1))
+(assert_return (invoke "i32x4.trunc_sat_f64x2_s_zero" (v128.const f64x2 -1e10 2.9))
+  (v128.const i32x4 -2147483648 2 0 0))
+(assert_return (invoke "i32x4.trunc_sat_f64x2_u_zero" (v128.const f64x2 -1 4294967295.5))
+  (v128.const i32x4 0 -1 0 0))
+(assert_return (invoke "f32x4.convert_i32x4_s" (v128.const i32x4 -1 16777217 0 2147483647))
+  (v128.const f32x4 -1 16777216 0 2147483648))
+(assert_return (invoke "f32x4.convert_i32x4_u" (v128.const i32x4 -1 1 0 2))
+  (v128.const f32x4 4294967296 1 0 2))
+(assert_return (invoke "f64x2.convert_low_i32x4_s" (v128.const i32x4 -1 -2147483648 7 7))
+  (v128.const f64x2 -1 -2147483648))
+(assert_return (invoke "f64x2.convert_low_i32x4_u" (v128.const i32x4 -1 1 7 7))
+  (v128.const f64x2 4294967295 1))
+(assert_return (invoke "f32x4.demote_f64x2_zero" (v128.const f64x2 1e300 0.5))
+  (v128.const f32x4 inf 0.5 0 0))
+(assert_return (invoke "f64x2.promote_low_f32x4" (v128.const f32x4 -0.5 inf 7 7))
+  (v128.const f64x2 -0.5 inf))
+
+(assert_invalid
+  (module (func (result v128) (f32x4.add (v128.const f32x4 0 0 0 0) (f32.const 0))))
+  "type mismatch")
+(assert_invalid
+  (module (func (result v128) (f64x2.sqrt (f64.const 0))))
+  "type mismatch")
@@ -0,0 +1,188 @@
+;; Integer lane arithmetic, saturating ops, comparisons and shifts
+
+(module
+  (func (export "i8x16.add") (param v128 v128) (result v128) (i8x16.add (local.get 0) (local.get 1)))
+  (func (export "i8x16.sub") (param v128 v128) (result v128) (i8x16.sub (local.get 0) (local.get 1)))
+  (func (export "i8x16.add_sat_s") (param v128 v128) (result v128) (i8x16.add_sat_s (local.get 0) (local.get 1)))
+  (func (export "i8x16.add_sat_u") (param v128 v128) (result v128) (i8x16.add_sat_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.sub_sat_s") (param v128 v128) (result v128) (i8x16.sub_sat_s (local.get 0) (local.get 1)))
+  (func (export "i8x16.sub_sat_u") (param v128 v128) (result v128) (i8x16.sub_sat_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.min_s") (param v128 v128) (result v128) (i8x16.min_s (local.get 0) (local.get 1)))
+  (func (export "i8x16.max_u") (param v128 v128) (result v128) (i8x16.max_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.avgr_u") (param v128 v128) (result v128) (i8x16.avgr_u (local.get 0) (local.get 1)))
+  (func (export "i8x16.abs") (param v128) (result v128) (i8x16.abs (local.get 0)))
+  (func (export "i8x16.neg") (param v128) (result v128) (i8x16.neg (local.get 0)))
+  (func (export "i8x16.popcnt") (param v128) (result v128) (i8x16.popcnt (local.get 0)))
+  (func (export "i8x16.narrow_i16x8_s") (param v128 v128) (result v128) (i8x16.narrow_i16x8_s (local.get 0) (local.get 1)))
+  (func (export "i8x16.narrow_i16x8_u") (param v128 v128) (result v128) (i8x16.narrow_i16x8_u (local.get 0) (local.get 1)))
+
+  (func (export "i16x8.mul") (param v128 v128) (result v128) (i16x8.mul (local.get 0) (local.get 1)))
+  (func (export "i16x8.q15mulr_sat_s") (param v128 v128) (result v128) (i16x8.q15mulr_sat_s (local.get 0) (local.get 1)))
+  (func (export "i16x8.extmul_high_i8x16_s") (param v128 v128) (result v128) (i16x8.extmul_high_i8x16_s (local.get 0) (local.get 1)))
+  (func (export "i16x8.extmul_low_i8x16_u") (param v128 v128) (result v128) (i16x8.extmul_low_i8x16_u (local.get 0) (local.get 1)))
+  (func (export "i16x8.extadd_pairwise_i8x16_s") (param v128) (result v128) (i16x8.extadd_pairwise_i8x16_s (local.get 0)))
+  (func (export "i16x8.extend_high_i8x16_u") (param v128) (result v128) (i16x8.extend_high_i8x16_u (local.get 0)))
+
+  (func (export "i32x4.add") (param v128 v128) (result v128) (i32x4.add (local.get 0) (local.get 1)))
+  (func (export "i32x4.mul") (param v128 v128) (result v128) (i32x4.mul (local.get 0) (local.get 1)))
+  (func (export "i32x4.min_u") (param v128 v128) (result v128) (i32x4.min_u (local.get 0) (local.get 1)))
+  (func (export "i32x4.dot_i16x8_s") (param v128 v128) (result v128) (i32x4.dot_i16x8_s (local.get 0) (local.get 1)))
+  (func (export "i32x4.extend_low_i16x8_s") (param v128) (result v128) (i32x4.extend_low_i16x8_s (local.get 0)))
+
+  (func (export "i64x2.sub") (param v128 v128) (result v128) (i64x2.sub (local.get 0) (local.get 1)))
+  (func (export "i64x2.mul") (param v128 v128) (result v128) (i64x2.mul (local.get 0) (local.get 1)))
+  (func (export "i64x2.abs") (param v128) (result v128) (i64x2.abs (local.get 0)))
+  (func (export "i64x2.extmul_low_i32x4_u") (param v128 v128) (result v128) (i64x2.extmul_low_i32x4_u (local.get 0) (local.get 1)))
+
+  (func (export "i8x16.lt_s") (param v128 v128) (result v128) (i8x16.lt_s (local.get 0) (local.get 1)))
+  (func (export "i8x16.lt_u") (param v128 v128) (result v128) (i8x16.lt_u (local.get 0) (local.get 1)))
+  (func (export "i16x8.ge_u") (param v128 v128) (result v128) (i16x8.ge_u (local.get 0) (local.get 1)))
+  (func (export "i32x4.eq") (param v128 v128) (result v128) (i32x4.eq (local.get 0) (local.get 1)))
+  (func (export "i64x2.gt_s") (param v128 v128) (result v128) (i64x2.gt_s (local.get 0) (local.get 1)))
+
+  (func (export "i8x16.shl") (param v128 i32) (result v128) (i8x16.shl (local.get 0) (local.get 1)))
+  (func (export "i8x16.shr_s") (param v128 i32) (result v128) (i8x16.shr_s (local.get 0) (local.get 1)))
+  (func (export "i16x8.shr_u") (param v128 i32) (result v128) (i16x8.shr_u (local.get 0) (local.get 1)))
+  (func (export "i32x4.shl") (param v128 i32) (result v128) (i32x4.shl (local.get 0) (local.get 1)))
+  (func (export "i64x2.shr_s") (param v128 i32) (result v128) (i64x2.shr_s (local.get 0) (local.get 1)))
+
+  (func (export "all_true") (param v128) (result i32) (i32x4.all_true (local.get 0)))
+  (func (export "any_true") (param v128) (result i32) (v128.any_true (local.get 0)))
+  (func (export "bitmask") (param v128) (result i32) (i8x16.bitmask (local.get 0)))
+)
+
+(assert_return (invoke "i8x16.add"
+    (v128.const i8x16 127 -128 1 2 3 4 5 6 7 8 9 10 11 12 13 -1)
+    (v128.const i8x16 1 -1 1 1 1 1 1 1 1 1 1 1 1 1 1 1))
+  (v128.const i8x16 -128 127 2 3 4 5 6 7 8 9 10 11 12 13 14 0))
+(assert_return (invoke "i8x16.sub"
+    (v128.const i8x16 -128 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 127 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.add_sat_s"
+    (v128.const i8x16 127 -128 1 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 127 -128 2 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.add_sat_u"
+    (v128.const i8x16 255 250 1 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 10 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 255 255 2 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.sub_sat_s"
+    (v128.const i8x16 -128 127 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 -128 127 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.sub_sat_u"
+    (v128.const i8x16 1 200 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 2 100 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 0 100 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.min_s"
+    (v128.const i8x16 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 -1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.max_u"
+    (v128.const i8x16 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 -1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.avgr_u"
+    (v128.const i8x16 255 0 1 2 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 255 1 2 2 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 255 1 2 2 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.abs" (v128.const i8x16 -128 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 -128 1 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.neg" (v128.const i8x16 -128 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i8x16 -128 1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.popcnt" (v128.const i8x16 -1 0 1 3 7 15 31 63 127 -128 0x55 0 0 0 0 0))
+  (v128.const i8x16 8 0 1 2 3 4 5 6 7 1 4 0 0 0 0 0))
+(assert_return (invoke "i8x16.narrow_i16x8_s"
+    (v128.const i16x8 128 -129 127 -128 0 1 -1 300)
+    (v128.const i16x8 -300 0 0 0 0 0 0 5))
+  (v128.const i8x16 127 -128 127 -128 0 1 -1 127 -128 0 0 0 0 0 0 5))
+(assert_return (invoke "i8x16.narrow_i16x8_u"
+    (v128.const i16x8 256 -1 255 0 0 0 0 0)
+    (v128.const i16x8 0 0 0 0 0 0 0 128))
+  (v128.const i8x16 255 0 255 0 0 0 0 0 0 0 0 0 0 0 0 128))
+
+(assert_return (invoke "i16x8.mul"
+    (v128.const i16x8 256 -1 3 0 0 0 0 0)
+    (v128.const i16x8 256 -1 -3 0 0 0 0 0))
+  (v128.const i16x8 0 1 -9 0 0 0 0 0))
+(assert_return (invoke "i16x8.q15mulr_sat_s"
+    (v128.const i16x8 -32768 16384 -16384 1 0 0 0 0)
+    (v128.const i16x8 -32768 16384 16384 16384 0 0 0 0))
+  (v128.const i16x8 32767 8192 -8192 1 0 0 0 0))
+(assert_return (invoke "i16x8.extmul_high_i8x16_s"
+    (v128.const i8x16 0 0 0 0 0 0 0 0 -128 -128 127 1 0 0 0 0)
+    (v128.const i8x16 0 0 0 0 0 0 0 0 -128 127 127 -1 0 0 0 0))
+  (v128.const i16x8 16384 -16256 16129 -1 0 0 0 0))
+(assert_return (invoke "i16x8.extmul_low_i8x16_u"
+    (v128.const i8x16 255 255 2 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 255 1 3 0 0 0 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i16x8 65025 255 6 0 0 0 0 0))
+(assert_return (invoke "i16x8.extadd_pairwise_i8x16_s" (v128.const i8x16 -128 -128 127 127 -1 1 0 0 0 0 0 0 0 0 0 0))
+  (v128.const i16x8 -256 254 0 0 0 0 0 0))
+(assert_return (invoke "i16x8.extend_high_i8x16_u" (v128.const i8x16 0 0 0 0 0 0 0 0 -1 1 0 0 0 0 0 -128))
+  (v128.const i16x8 255 1 0 0 0 0 0 128))
+
+(assert_return (invoke "i32x4.add" (v128.const i32x4 0x7fffffff 1 2 3) (v128.const i32x4 1 1 1 1))
+  (v128.const i32x4 0x80000000 2 3 4))
+(assert_return (invoke "i32x4.mul" (v128.const i32x4 0x10000 -2 3 4) (v128.const i32x4 0x10000 3 3 -4))
+  (v128.const i32x4 0 -6 9 -16))
+(assert_return (invoke "i32x4.min_u" (v128.const i32x4 -1 1 0 5) (v128.const i32x4 1 -1 0 4))
+  (v128.const i32x4 1 1 0 4))
+(assert_return (invoke "i32x4.dot_i16x8_s"
+    (v128.const i16x8 -32768 -32768 1 2 3 4 0 0)
+    (v128.const i16x8 -32768 -32768 5 6 -7 8 0 0))
+  (v128.const i32x4 -2147483648 17 11 0))
+(assert_return (invoke "i32x4.extend_low_i16x8_s" (v128.const i16x8 -1 32767 -32768 1 0 0 0 0))
+  (v128.const i32x4 -1 32767 -32768 1))
+
+(assert_return (invoke "i64x2.sub" (v128.const i64x2 0 -9223372036854775808) (v128.const i64x2 1 1))
+  (v128.const i64x2 -1 9223372036854775807))
+(assert_return (invoke "i64x2.mul" (v128.const i64x2 0x100000000 -3) (v128.const i64x2 0x100000000 7))
+  (v128.const i64x2 0 -21))
+(assert_return (invoke "i64x2.abs" (v128.const i64x2 -9223372036854775808 -5))
+  (v128.const i64x2 -9223372036854775808 5))
+(assert_return (invoke "i64x2.extmul_low_i32x4_u" (v128.const i32x4 -1 -1 0 0) (v128.const i32x4 -1 2 0 0))
+  (v128.const i64x2 0xfffffffe00000001 0x1fffffffe))
+
+(assert_return (invoke "i8x16.lt_s"
+    (v128.const i8x16 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 1))
+  (v128.const i8x16 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0 -1))
+(assert_return (invoke "i8x16.lt_u"
+    (v128.const i8x16 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0)
+    (v128.const i8x16 1 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 1))
+  (v128.const i8x16 0 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 -1))
+(assert_return (invoke "i16x8.ge_u" (v128.const i16x8 -1 0 1 1 0 0 0 0) (v128.const i16x8 1 -1 1 2 0 0 0 0))
+  (v128.const i16x8 -1 0 -1 0 -1 -1 -1 -1))
+(assert_return (invoke "i32x4.eq" (v128.const i32x4 1 2 3 4) (v128.const i32x4 1 0 3 0))
+  (v128.const i32x4 -1 0 -1 0))
+(assert_return (invoke "i64x2.gt_s" (v128.const i64x2 1 -1) (v128.const i64x2 -1 1))
+  (v128.const i64x2 -1 0))
+
+(assert_return (invoke "i8x16.shl" (v128.const i8x16 1 -1 0x40 0 0 0 0 0 0 0 0 0 0 0 0 0) (i32.const 9))
+  (v128.const i8x16 2 -2 -128 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i8x16.shr_s" (v128.const i8x16 -128 64 0 0 0 0 0 0 0 0 0 0 0 0 0 0) (i32.const 6))
+  (v128.const i8x16 -2 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0))
+(assert_return (invoke "i16x8.shr_u" (v128.const i16x8 -1 2 0 0 0 0 0 0) (i32.const -15))
+  (v128.const i16x8 0x7fff 1 0 0 0 0 0 0))
+(assert_return (invoke "i32x4.shl" (v128.const i32x4 1 -1 3 0) (i32.const 32))
+  (v128.const i32x4 1 -1 3 0))
+(assert_return (invoke "i64x2.shr_s" (v128.const i64x2 -9223372036854775808 8) (i32.const 63))
+  (v128.const i64x2 -1 0))
+
+(assert_return (invoke "all_true" (v128.const i32x4 1 -1 0x100 2)) (i32.const 1))
+(assert_return (invoke "all_true" (v128.const i32x4 1 -1 0 2)) (i32.const 0))
+(assert_return (invoke "any_true" (v128.const i64x2 0 0x100)) (i32.const 1))
+(assert_return (invoke "any_true" (v128.const i64x2 0 0)) (i32.const 0))
+(assert_return (invoke "bitmask" (v128.const i8x16 -1 0 -128 127 0 0 0 0 0 0 0 0 0 0 0 -2)) (i32.const 0x8005))
+
+(assert_invalid
+  (module (func (result v128) (i8x16.shl (v128.const i32x4 0 0 0 0) (i64.const 1))))
+  "type mismatch")
+(assert_invalid
+  (module (func (result v128) (i32x4.add (v128.const i32x4 0 0 0 0) (i32.const 1))))
+  "type mismatch")
+(assert_invalid
+  (module (func (result i32) (i32x4.all_true (i32.const 1))))
+  "type mismatch")
@@ -0,0 +1,109 @@
+;; Splats, lane accesses, shuffles and swizzles
+
+(module
+  (func (export "i8x16_extract_lane_s") (param v128) (result i32)
+    (i8x16.extract_lane_s 15 (local.get 0)))
+  (func (export "i8x16_extract_lane_u") (param v128) (result i32)
+    (i8x16.extract_lane_u 15 (local.get 0)))
+  (func (export "i16x8_extract_lane_s") (param v128) (result i32)
+    (i16x8.extract_lane_s 1 (local.get 0)))
+  (func (export "i16x8_extract_lane_u") (param v128) (result i32)
+    (i16x8.extract_lane_u 1 (local.get 0)))
+  (func (export "i32x4_extract_lane") (param v128) (result i32)
+    (i32x4.extract_lane 3 (local.get 0)))
+  (func (export "i64x2_extract_lane") (param v128) (result i64)
+    (i64x2.extract_lane 1 (local.get 0)))
+  (func (export "f32x4_extract_lane") (param v128) (result f32)
+    (f32x4.extract_lane 2 (local.get 0)))
+  (func (export "f64x2_extract_lane") (param v128) (result f64)
+    (f64x2.extract_lane 0 (local.get 0)))
+
+  (func (export "i8x16_replace_lane") (param v128 i32) (result v128)
+    (i8x16.replace_lane 0 (local.get 0) (local.get 1)))
+  (func (export "i16x8_replace_lane") (param v128 i32) (result v128)
+    (i16x8.replace_lane 7 (local.get 0) (local.get 1)))
+  (func (export "i32x4_replace_lane") (param v128 i32) (result v128)
+    (i32x4.replace_lane 2 (local.get 0) (local.get 1)))
+  (func (export "i64x2_replace_lane") (param v128 i64) (result v128)
+    (i64x2.replace_lane 1 (local.get 0) (local.get 1)))
+  (func (export "f32x4_replace_lane") (param v128 f32) (result v128)
+    (f32x4.replace_lane 1 (local.get 0) (local.get 1)))
+  (func (export "f64x2_replace_lane") (param v128 f64) (result v128)
+    (f64x2.replace_lane 0 (local.get 0) (local.get 1)))
+
+  (func (export "i8x16_splat") (param i32) (result v128) (i8x16.splat (local.get 0)))
+  (func (export "i16x8_splat") (param i32) (result v128) (i16x8.splat (local.get 0)))
+  (func (export "i32x4_splat") (param i32) (result v128) (i32x4.splat (local.get 0)))
+  (func (export "i64x2_splat") (param i64) (result v128) (i64x2.splat (local.get 0)))
+  (func (export "f32x4_splat") (param f32) (result v128) (f32x4.splat (local.get 0)))
+  (func (export "f64x2_splat") (param f64) (result v128) (f64x2.splat (local.get 0)))
+
+  (func (export "shuffle") (param v128 v128) (result v128)
+    (i8x16.shuffle 31 0 30 1 29 2 28 3 27 4 26 5 25 6 24 7 (local.get 0) (local.get 1)))
+  (func (export "swizzle") (param v128 v128) (result v128)
+    (i8x16.swizzle (local.get 0) (local.get 1)))
+
+  (func (export "const") (result v128)
+    (v128.const i16x8 1 -1 2 -2 3 -3 4 -4))
+)
+
+(assert_return (invoke "i8x16_extract_lane_s" (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 -1)) (i32.const -1))
+(assert_return (invoke "i8x16_extract_lane_u" (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 -1)) (i32.const 255))
+(assert_return (invoke "i16x8_extract_lane_s" (v128.const i16x8 0 -32768 0 0 0 0 0 0)) (i32.const -32768))
+(assert_return (invoke "i16x8_extract_lane_u" (v128.const i16x8 0 -32768 0 0 0 0 0 0)) (i32.const 32768))
+(assert_return (invoke "i32x4_extract_lane" (v128.const i32x4 1 2 3 -4)) (i32.const -4))
+(assert_return (invoke "i64x2_extract_lane" (v128.const i64x2 1 0x8000_0000_0000_0000)) (i64.const 0x8000_0000_0000_0000))
+(assert_return (invoke "f32x4_extract_lane" (v128.const f32x4 1.5 2.5 -inf 4.5)) (f32.const -inf))
+(assert_return (invoke "f64x2_extract_lane" (v128.const f64x2 -0.25 1e100)) (f64.const -0.25))
+
+(assert_return (invoke "i8x16_replace_lane" (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15) (i32.const 0x1ff))
+  (v128.const i8x16 -1 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15))
+(assert_return (invoke "i16x8_replace_lane" (v128.const i16x8 0 0 0 0 0 0 0 0) (i32.const 0x12345))
+  (v128.const i16x8 0 0 0 0 0 0 0 0x2345))
+(assert_return (invoke "i32x4_replace_lane" (v128.const i32x4 1 2 3 4) (i32.const -3))
+  (v128.const i32x4 1 2 -3 4))
+(assert_return (invoke "i64x2_replace_lane" (v128.const i64x2 1 2) (i64.const -2))
+  (v128.const i64x2 1 -2))
+(assert_return (invoke "f32x4_replace_lane" (v128.const f32x4 1 2 3 4) (f32.const nan))
+  (v128.const f32x4 1 nan 3 4))
+(assert_return (invoke "f64x2_replace_lane" (v128.const f64x2 1 2) (f64.const -0x1p-1074))
+  (v128.const f64x2 -0x1p-1074 2))
+
+(assert_return (invoke "i8x16_splat" (i32.const 0x1fe)) (v128.const i8x16 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2 -2))
+(assert_return (invoke "i16x8_splat" (i32.const 65535)) (v128.const i16x8 -1 -1 -1 -1 -1 -1 -1 -1))
+(assert_return (invoke "i32x4_splat" (i32.const 42)) (v128.const i32x4 42 42 42 42))
+(assert_return (invoke "i64x2_splat" (i64.const -7)) (v128.const i64x2 -7 -7))
+(assert_return (invoke "f32x4_splat" (f32.const -0.0)) (v128.const f32x4 -0.0 -0.0 -0.0 -0.0))
+(assert_return (invoke "f64x2_splat" (f64.const inf)) (v128.const f64x2 inf inf))
+
+(assert_return
+  (invoke "shuffle"
+    (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+    (v128.const i8x16 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31))
+  (v128.const i8x16 31 0 30 1 29 2 28 3 27 4 26 5 25 6 24 7))
+(assert_return
+  (invoke "swizzle"
+    (v128.const i8x16 100 101 102 103 104 105 106 107 108 109 110 111 112 113 114 115)
+    (v128.const i8x16 15 14 13 12 0 1 2 3 16 17 -1 -128 7 7 7 7))
+  (v128.const i8x16 115 114 113 112 100 101 102 103 0 0 0 0 107 107 107 107))
+(assert_return (invoke "const") (v128.const i16x8 1 -1 2 -2 3 -3 4 -4))
+
+(assert_invalid
+  (module (func (result i32) (i32x4.extract_lane 0 (i32.const 0))))
+  "type mismatch")
+(assert_invalid
+  (module (func (result v128) (i32x4.replace_lane 0 (v128.const i32x4 0 0 0 0) (i64.const 0))))
+  "type mismatch")
+(assert_invalid
+  (module (func (result v128) (i64x2.splat (i32.const 0))))
+  "type mismatch")
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\05\01\60\00\01\7f"                    ;; type section: [] -> [i32]
+    "\03\02\01\00"                             ;; function section
+    "\0a\19\01\17\00"                          ;; code section
+    "\fd\0c\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00"  ;; v128.const
+    "\fd\15\10"                                ;; i8x16.extract_lane_s 16
+    "\0b")
+  "invalid lane index")
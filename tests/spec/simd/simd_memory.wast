@@ -0,0 +1,114 @@
+;; Vector loads and stores, constants, globals and bitwise ops
+
+(module
+  (memory 1)
+  (data (i32.const 0) "\00\01\02\03\04\05\06\07\08\09\0a\0b\0c\0d\0e\0f\80\81\82\83\84\85\86\87")
+
+  (global $g (mut v128) (v128.const i32x4 1 2 3 4))
+
+  (func (export "v128.load") (param i32) (result v128) (v128.load offset=1 (local.get 0)))
+  (func (export "v128.load8x8_s") (param i32) (result v128) (v128.load8x8_s (local.get 0)))
+  (func (export "v128.load8x8_u") (param i32) (result v128) (v128.load8x8_u (local.get 0)))
+  (func (export "v128.load16x4_s") (param i32) (result v128) (v128.load16x4_s (local.get 0)))
+  (func (export "v128.load32x2_u") (param i32) (result v128) (v128.load32x2_u (local.get 0)))
+  (func (export "v128.load8_splat") (param i32) (result v128) (v128.load8_splat (local.get 0)))
+  (func (export "v128.load64_splat") (param i32) (result v128) (v128.load64_splat (local.get 0)))
+  (func (export "v128.load32_zero") (param i32) (result v128) (v128.load32_zero (local.get 0)))
+  (func (export "v128.load64_zero") (param i32) (result v128) (v128.load64_zero (local.get 0)))
+  (func (export "v128.load16_lane") (param i32 v128) (result v128)
+    (v128.load16_lane 3 (local.get 0) (local.get 1)))
+  (func (export "v128.load64_lane") (param i32 v128) (result v128)
+    (v128.load64_lane 0 (local.get 0) (local.get 1)))
+
+  (func (export "v128.store") (param i32 v128) (result v128)
+    (v128.store (local.get 0) (local.get 1))
+    (v128.load (local.get 0)))
+  (func (export "v128.store8_lane") (param i32 v128) (result i32)
+    (v128.store8_lane 15 (local.get 0) (local.get 1))
+    (i32.load8_u (local.get 0)))
+  (func (export "v128.store32_lane") (param i32 v128) (result i32)
+    (v128.store32_lane 1 (local.get 0) (local.get 1))
+    (i32.load (local.get 0)))
+
+  (func (export "global") (param v128) (result v128)
+    (global.get $g)
+    (global.set $g (local.get 0)))
+  (func (export "locals") (result v128)
+    (local v128)
+    (local.get 0))
+  (func (export "select") (param i32) (result v128)
+    (select (v128.const i32x4 1 1 1 1) (v128.const i32x4 2 2 2 2) (local.get 0)))
+
+  (func (export "not") (param v128) (result v128) (v128.not (local.get 0)))
+  (func (export "andnot") (param v128 v128) (result v128) (v128.andnot (local.get 0) (local.get 1)))
+  (func (export "xor") (param v128 v128) (result v128) (v128.xor (local.get 0) (local.get 1)))
+  (func (export "bitselect") (param v128 v128 v128) (result v128)
+    (v128.bitselect (local.get 0) (local.get 1) (local.get 2)))
+)
+
+(assert_return (invoke "v128.load" (i32.const 0))
+  (v128.const i8x16 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 0x80))
+(assert_return (invoke "v128.load8x8_s" (i32.const 14))
+  (v128.const i16x8 14 15 -128 -127 -126 -125 -124 -123))
+(assert_return (invoke "v128.load8x8_u" (i32.const 14))
+  (v128.const i16x8 14 15 128 129 130 131 132 133))
+(assert_return (invoke "v128.load16x4_s" (i32.const 16))
+  (v128.const i32x4 -32384 -31870 -31356 -30842))
+(assert_return (invoke "v128.load32x2_u" (i32.const 16))
+  (v128.const i64x2 0x83828180 0x87868584))
+(assert_return (invoke "v128.load8_splat" (i32.const 17))
+  (v128.const i8x16 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81 0x81))
+(assert_return (invoke "v128.load64_splat" (i32.const 0))
+  (v128.const i64x2 0x0706050403020100 0x0706050403020100))
+(assert_return (invoke "v128.load32_zero" (i32.const 4))
+  (v128.const i32x4 0x07060504 0 0 0))
+(assert_return (invoke "v128.load64_zero" (i32.const 8))
+  (v128.const i64x2 0x0f0e0d0c0b0a0908 0))
+(assert_return (invoke "v128.load16_lane" (i32.const 2) (v128.const i16x8 -1 -1 -1 -1 -1 -1 -1 -1))
+  (v128.const i16x8 -1 -1 -1 0x0302 -1 -1 -1 -1))
+(assert_return (invoke "v128.load64_lane" (i32.const 16) (v128.const i64x2 0 0))
+  (v128.const i64x2 0x8786858483828180 0))
+(assert_trap (invoke "v128.load" (i32.const 65520)) "out of bounds memory access")
+(assert_trap (invoke "v128.load64_zero" (i32.const 65529)) "out of bounds memory access")
+(assert_return (invoke "v128.load32_zero" (i32.const 65532)) (v128.const i32x4 0 0 0 0))
+
+(assert_return (invoke "v128.store" (i32.const 100) (v128.const i32x4 -1 2 -3 4))
+  (v128.const i32x4 -1 2 -3 4))
+(assert_return (invoke "v128.store8_lane" (i32.const 200) (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0xab))
+  (i32.const 0xab))
+(assert_return (invoke "v128.store32_lane" (i32.const 300) (v128.const i32x4 1 -2 3 4))
+  (i32.const -2))
+(assert_trap (invoke "v128.store" (i32.const 65521) (v128.const i32x4 0 0 0 0)) "out of bounds memory access")
+
+(assert_return (invoke "global" (v128.const i32x4 5 6 7 8)) (v128.const i32x4 1 2 3 4))
+(assert_return (invoke "global" (v128.const i32x4 0 0 0 0)) (v128.const i32x4 5 6 7 8))
+(assert_return (invoke "locals") (v128.const i64x2 0 0))
+(assert_return (invoke "select" (i32.const 0)) (v128.const i32x4 2 2 2 2))
+
+(assert_return (invoke "not" (v128.const i32x4 0 -1 0x0f0f0f0f 1)) (v128.const i32x4 -1 0 0xf0f0f0f0 -2))
+(assert_return (invoke "andnot" (v128.const i32x4 -1 -1 0 0xff) (v128.const i32x4 0 -1 -1 0x0f))
+  (v128.const i32x4 -1 0 0 0xf0))
+(assert_return (invoke "xor" (v128.const i32x4 -1 -1 0 0xff) (v128.const i32x4 0 -1 -1 0x0f))
+  (v128.const i32x4 -1 0 -1 0xf0))
+(assert_return
+  (invoke "bitselect"
+    (v128.const i32x4 0xaaaaaaaa 0xaaaaaaaa 0xaaaaaaaa 0xaaaaaaaa)
+    (v128.const i32x4 0x55555555 0x55555555 0x55555555 0x55555555)
+    (v128.const i32x4 0xffffffff 0 0xffff0000 0x0000ffff))
+  (v128.const i32x4 0xaaaaaaaa 0x55555555 0xaaaa5555 0x5555aaaa))
+
+(assert_invalid
+  (module (memory 1) (func (result v128) (v128.load align=32 (i32.const 0))))
+  "alignment must not be larger than natural")
+(assert_invalid
+  (module (memory 1) (func (result v128) (v128.load16_lane align=4 0 (i32.const 0) (v128.const i32x4 0 0 0 0))))
+  "alignment must not be larger than natural")
+(assert_invalid
+  (module (func (result v128) (v128.load (i32.const 0))))
+  "unknown memory")
+(assert_invalid
+  (module (func (result i32) (ref.is_null (v128.const i32x4 0 0 0 0))))
+  "type mismatch")
+(assert_invalid
+  (module (global v128 (i32.const 0)))
+  "type mismatch")
@@ -0,0 +1,101 @@
+;; Tail calls with `return_call` and `return_call_indirect`
+
+(module
+  (import "spectest" "print_i32" (func $print_i32 (param i32)))
+  (type $i64-i64 (func (param i64) (result i64)))
+  (type $i64-i32 (func (param i64) (result i32)))
+  (table funcref (elem $even-indirect $odd-indirect $fac-acc))
+
+  (func $fac-acc (param i64 i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (local.get 1))
+      (else
+        (return_call $fac-acc
+          (i64.sub (local.get 0) (i64.const 1))
+          (i64.mul (local.get 0) (local.get 1))))))
+  (func (export "fac") (param i64) (result i64)
+    (return_call $fac-acc (local.get 0) (i64.const 1)))
+
+  (func $count (export "count") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (local.get 0))
+      (else (return_call $count (i64.sub (local.get 0) (i64.const 1))))))
+
+  (func $even (export "even") (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 44))
+      (else (return_call $odd (i64.sub (local.get 0) (i64.const 1))))))
+  (func $odd (export "odd") (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 99))
+      (else (return_call $even (i64.sub (local.get 0) (i64.const 1))))))
+
+  (func $even-indirect (export "even-indirect") (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 44))
+      (else
+        (return_call_indirect (type $i64-i32)
+          (i64.sub (local.get 0) (i64.const 1)) (i32.const 1)))))
+  (func $odd-indirect (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 99))
+      (else
+        (return_call_indirect (type $i64-i32)
+          (i64.sub (local.get 0) (i64.const 1)) (i32.const 0)))))
+
+  (func (export "indirect-type-mismatch") (result i64)
+    (return_call_indirect (type $i64-i64) (i64.const 0) (i32.const 0)))
+  (func (export "indirect-out-of-bounds") (result i64)
+    (return_call_indirect (type $i64-i64) (i64.const 0) (i32.const 7)))
+
+  ;; The locals of the caller don't leak into the callee.
+  (func $locals (param i32) (result i32) (local i32)
+    (if (result i32) (local.get 0)
+      (then (return_call $locals (i32.const 0)))
+      (else (local.get 1))))
+  (func (export "locals") (result i32) (local i32 i32)
+    (local.set 0 (i32.const 5))
+    (local.set 1 (i32.const 6))
+    (return_call $locals (i32.const 1)))
+
+  (func (export "host") (param i32)
+    (return_call $print_i32 (local.get 0)))
+)
+
+(assert_return (invoke "fac" (i64.const 0)) (i64.const 1))
+(assert_return (invoke "fac" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "fac" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "count" (i64.const 1_000_000)) (i64.const 0))
+(assert_return (invoke "even" (i64.const 0)) (i32.const 44))
+(assert_return (invoke "even" (i64.const 1)) (i32.const 99))
+(assert_return (invoke "even" (i64.const 1_000_000)) (i32.const 44))
+(assert_return (invoke "odd" (i64.const 999_999)) (i32.const 44))
+(assert_return (invoke "even-indirect" (i64.const 1_000_001)) (i32.const 99))
+(assert_trap (invoke "indirect-type-mismatch") "indirect call type mismatch")
+(assert_trap (invoke "indirect-out-of-bounds") "undefined element")
+(assert_return (invoke "locals") (i32.const 0))
+(assert_return (invoke "host" (i32.const 1)))
+
+(assert_invalid
+  (module
+    (func $f (result i64) (i64.const 0))
+    (func (result i32) (return_call $f)))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (func $f (param i32))
+    (func (return_call $f (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (type $t (func (result i32)))
+    (table 1 funcref)
+    (func (result i64) (return_call_indirect (type $t) (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (return_call 1)))
+  "unknown function"
+)
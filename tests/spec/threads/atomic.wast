@@ -0,0 +1,126 @@
+;; Atomic loads, stores, read-modify-writes and compare-exchanges
+
+(module
+  (memory 1 1 shared)
+
+  (func (export "init") (param i64) (i64.store (i32.const 0) (local.get 0)))
+
+  (func (export "i32.atomic.load") (param i32) (result i32) (i32.atomic.load (local.get 0)))
+  (func (export "i64.atomic.load") (param i32) (result i64) (i64.atomic.load (local.get 0)))
+  (func (export "i32.atomic.load8_u") (param i32) (result i32) (i32.atomic.load8_u (local.get 0)))
+  (func (export "i32.atomic.load16_u") (param i32) (result i32) (i32.atomic.load16_u (local.get 0)))
+  (func (export "i64.atomic.load8_u") (param i32) (result i64) (i64.atomic.load8_u (local.get 0)))
+  (func (export "i64.atomic.load16_u") (param i32) (result i64) (i64.atomic.load16_u (local.get 0)))
+  (func (export "i64.atomic.load32_u") (param i32) (result i64) (i64.atomic.load32_u (local.get 0)))
+
+  (func (export "i32.atomic.store") (param i32 i32) (i32.atomic.store (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.store") (param i32 i64) (i64.atomic.store (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.store8") (param i32 i32) (i32.atomic.store8 (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.store16") (param i32 i64) (i64.atomic.store16 (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.store32") (param i32 i64) (i64.atomic.store32 (local.get 0) (local.get 1)))
+
+  (func (export "i32.atomic.rmw.add") (param i32 i32) (result i32) (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw.sub") (param i32 i64) (result i64) (i64.atomic.rmw.sub (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw8.add_u") (param i32 i32) (result i32) (i32.atomic.rmw8.add_u (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw16.and_u") (param i32 i32) (result i32) (i32.atomic.rmw16.and_u (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw8.or_u") (param i32 i64) (result i64) (i64.atomic.rmw8.or_u (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw32.xor_u") (param i32 i64) (result i64) (i64.atomic.rmw32.xor_u (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw.xchg") (param i32 i64) (result i64) (i64.atomic.rmw.xchg (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw16.xchg_u") (param i32 i32) (result i32) (i32.atomic.rmw16.xchg_u (local.get 0) (local.get 1)))
+
+  (func (export "i32.atomic.rmw.cmpxchg") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i64.atomic.rmw.cmpxchg") (param i32 i64 i64) (result i64)
+    (i64.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i32.atomic.rmw8.cmpxchg_u") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw8.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i64.atomic.rmw32.cmpxchg_u") (param i32 i64 i64) (result i64)
+    (i64.atomic.rmw32.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+)
+
+(invoke "init" (i64.const 0x0706050403020100))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 0x03020100))
+(assert_return (invoke "i32.atomic.load" (i32.const 4)) (i32.const 0x07060504))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0706050403020100))
+(assert_return (invoke "i32.atomic.load8_u" (i32.const 3)) (i32.const 0x03))
+(assert_return (invoke "i32.atomic.load16_u" (i32.const 6)) (i32.const 0x0706))
+(assert_return (invoke "i64.atomic.load8_u" (i32.const 7)) (i64.const 0x07))
+(assert_return (invoke "i64.atomic.load16_u" (i32.const 2)) (i64.const 0x0302))
+(assert_return (invoke "i64.atomic.load32_u" (i32.const 4)) (i64.const 0x07060504))
+
+(invoke "i32.atomic.store" (i32.const 0) (i32.const 0xffeeddcc))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x07060504ffeeddcc))
+(invoke "i64.atomic.store" (i32.const 0) (i64.const 0x0123456789abcdef))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0123456789abcdef))
+(invoke "i32.atomic.store8" (i32.const 1) (i32.const 0x1122))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0123456789ab22ef))
+(invoke "i64.atomic.store16" (i32.const 6) (i64.const -1))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0xffff456789ab22ef))
+(invoke "i64.atomic.store32" (i32.const 0) (i64.const 0))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0xffff456700000000))
+
+;; Read-modify-writes return the previous value, and wrap.
+(invoke "init" (i64.const 0x00000000ffffffff))
+(assert_return (invoke "i32.atomic.rmw.add" (i32.const 0) (i32.const 2)) (i32.const -1))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0000000000000001))
+(assert_return (invoke "i64.atomic.rmw.sub" (i32.const 0) (i64.const 2)) (i64.const 1))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const -1))
+(invoke "init" (i64.const 0x00000000000000ff))
+(assert_return (invoke "i32.atomic.rmw8.add_u" (i32.const 0) (i32.const 0x102)) (i32.const 0xff))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x01))
+(invoke "init" (i64.const 0x0000000012345678))
+(assert_return (invoke "i32.atomic.rmw16.and_u" (i32.const 2) (i32.const 0x00f0)) (i32.const 0x1234))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0000000000305678))
+(assert_return (invoke "i64.atomic.rmw8.or_u" (i32.const 0) (i64.const 0x0f)) (i64.const 0x78))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x000000000030567f))
+(assert_return (invoke "i64.atomic.rmw32.xor_u" (i32.const 0) (i64.const 0xffffffff00ffffff)) (i64.const 0x0030567f))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0000000000cfa980))
+(assert_return (invoke "i64.atomic.rmw.xchg" (i32.const 0) (i64.const 42)) (i64.const 0x00cfa980))
+(assert_return (invoke "i32.atomic.rmw16.xchg_u" (i32.const 0) (i32.const 0x10007)) (i32.const 42))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 7))
+
+;; Compare-exchanges only store if the wrapped expected value matches.
+(invoke "init" (i64.const 0x0000000100000002))
+(assert_return (invoke "i32.atomic.rmw.cmpxchg" (i32.const 0) (i32.const 1) (i32.const 5)) (i32.const 2))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0000000100000002))
+(assert_return (invoke "i32.atomic.rmw.cmpxchg" (i32.const 0) (i32.const 2) (i32.const 5)) (i32.const 2))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0000000100000005))
+(assert_return (invoke "i64.atomic.rmw.cmpxchg" (i32.const 0) (i64.const 0x0000000100000005) (i64.const -1)) (i64.const 0x0000000100000005))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const -1))
+(assert_return (invoke "i32.atomic.rmw8.cmpxchg_u" (i32.const 1) (i32.const 0x1ff) (i32.const 0x100)) (i32.const 0xff))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0xffffffffffff00ff))
+(assert_return (invoke "i64.atomic.rmw32.cmpxchg_u" (i32.const 4) (i64.const 0x1ffffffff) (i64.const 0x100000003)) (i64.const 0xffffffff))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x00000003ffff00ff))
+
+;; Atomic accesses must be aligned, and in bounds.
+(assert_trap (invoke "i32.atomic.load" (i32.const 2)) "unaligned atomic")
+(assert_trap (invoke "i64.atomic.load" (i32.const 4)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.load16_u" (i32.const 1)) "unaligned atomic")
+(assert_trap (invoke "i64.atomic.store" (i32.const 12) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.rmw.add" (i32.const 1) (i32.const 0)) "unaligned atomic")
+(assert_trap (invoke "i64.atomic.rmw.cmpxchg" (i32.const 4) (i64.const 0) (i64.const 0)) "unaligned atomic")
+(assert_return (invoke "i32.atomic.load8_u" (i32.const 65535)) (i32.const 0))
+(assert_trap (invoke "i32.atomic.load" (i32.const 65536)) "out of bounds memory access")
+(assert_trap (invoke "i64.atomic.rmw.xchg" (i32.const 65536) (i64.const 0)) "out of bounds memory access")
+(assert_return (invoke "i64.atomic.load" (i32.const 65528)) (i64.const 0))
+
+;; Atomic instructions also work on memories that are not shared.
+(module
+  (memory 1)
+  (func (export "rmw") (param i32) (result i32)
+    (drop (i32.atomic.rmw.add (i32.const 8) (local.get 0)))
+    (i32.atomic.rmw.add (i32.const 8) (local.get 0)))
+)
+
+(assert_return (invoke "rmw" (i32.const 3)) (i32.const 3))
+(assert_return (invoke "rmw" (i32.const 3)) (i32.const 9))
+
+(assert_invalid
+  (module (memory 1) (func (i32.atomic.store (i32.const 0) (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 0)))))
+  "unknown memory 0"
+)
+(assert_invalid (module (memory 1 shared)) "shared memory must have maximum")
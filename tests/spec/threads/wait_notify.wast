@@ -0,0 +1,59 @@
+;; Waiting and notifying on a single thread
+
+(module
+  (memory 1 1 shared)
+
+  (func (export "init") (param i64) (i64.store (i32.const 0) (local.get 0)))
+
+  (func (export "memory.atomic.notify") (param i32 i32) (result i32)
+    (memory.atomic.notify (local.get 0) (local.get 1)))
+  (func (export "memory.atomic.wait32") (param i32 i32 i64) (result i32)
+    (memory.atomic.wait32 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "memory.atomic.wait64") (param i32 i64 i64) (result i32)
+    (memory.atomic.wait64 (local.get 0) (local.get 1) (local.get 2)))
+)
+
+(invoke "init" (i64.const 0xffffffff00000000))
+
+;; Nothing is waiting.
+(assert_return (invoke "memory.atomic.notify" (i32.const 0) (i32.const 0)) (i32.const 0))
+(assert_return (invoke "memory.atomic.notify" (i32.const 0) (i32.const 10)) (i32.const 0))
+
+;; The value is not the expected one.
+(assert_return (invoke "memory.atomic.wait32" (i32.const 0) (i32.const 1) (i64.const -1)) (i32.const 1))
+(assert_return (invoke "memory.atomic.wait64" (i32.const 0) (i64.const 0) (i64.const -1)) (i32.const 1))
+
+;; The timeout expires.
+(assert_return (invoke "memory.atomic.wait32" (i32.const 0) (i32.const 0) (i64.const 0)) (i32.const 2))
+(assert_return (invoke "memory.atomic.wait32" (i32.const 4) (i32.const -1) (i64.const 1000)) (i32.const 2))
+(assert_return (invoke "memory.atomic.wait64" (i32.const 0) (i64.const 0xffffffff00000000) (i64.const 0)) (i32.const 2))
+
+(assert_trap (invoke "memory.atomic.notify" (i32.const 2) (i32.const 1)) "unaligned atomic")
+(assert_trap (invoke "memory.atomic.wait32" (i32.const 2) (i32.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "memory.atomic.wait64" (i32.const 4) (i64.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "memory.atomic.notify" (i32.const 65536) (i32.const 1)) "out of bounds memory access")
+(assert_trap (invoke "memory.atomic.wait32" (i32.const 65536) (i32.const 0) (i64.const 0)) "out of bounds memory access")
+
+;; Notifying an unshared memory wakes nobody, and waiting on it traps.
+(module
+  (memory 1)
+
+  (func (export "memory.atomic.notify") (param i32 i32) (result i32)
+    (memory.atomic.notify (local.get 0) (local.get 1)))
+  (func (export "memory.atomic.wait32") (param i32 i32 i64) (result i32)
+    (memory.atomic.wait32 (local.get 0) (local.get 1) (local.get 2)))
+)
+
+(assert_return (invoke "memory.atomic.notify" (i32.const 0) (i32.const 1)) (i32.const 0))
+(assert_trap (invoke "memory.atomic.notify" (i32.const 1) (i32.const 1)) "unaligned atomic")
+(assert_trap (invoke "memory.atomic.wait32" (i32.const 0) (i32.const 0) (i64.const 0)) "expected shared memory")
+
+;; Shared memories only match shared imports.
+(module (memory (export "memory") 1 2 shared))
+(register "shared")
+(module (memory (export "memory") 1 2))
+(register "unshared")
+
+(module (import "shared" "memory" (memory 1 2 shared)))
+(assert_unlinkable (module (import "shared" "memory" (memory 1 2))) "incompatible import type")
+(assert_unlinkable (module (import "unshared" "memory" (memory 1 2 shared))) "incompatible import type")